@@ -1,6 +1,7 @@
 use amzn_codewhisperer_client::operation::create_subscription_token::CreateSubscriptionTokenError;
 use amzn_codewhisperer_client::operation::generate_completions::GenerateCompletionsError;
 use amzn_codewhisperer_client::operation::list_available_customizations::ListAvailableCustomizationsError;
+use amzn_codewhisperer_client::operation::list_available_models::ListAvailableModelsError;
 use amzn_codewhisperer_client::operation::list_available_profiles::ListAvailableProfilesError;
 use amzn_codewhisperer_client::operation::send_telemetry_event::SendTelemetryEventError;
 pub use amzn_codewhisperer_streaming_client::operation::generate_assistant_response::GenerateAssistantResponseError;
@@ -79,6 +80,9 @@ pub enum ApiClientError {
     #[error(transparent)]
     ListAvailableProfilesError(#[from] SdkError<ListAvailableProfilesError, HttpResponse>),
 
+    #[error(transparent)]
+    ListAvailableModelsError(#[from] SdkError<ListAvailableModelsError, HttpResponse>),
+
     #[error(transparent)]
     AuthError(#[from] AuthError),
 
@@ -93,6 +97,10 @@ pub enum ApiClientError {
     // Credential errors
     #[error("failed to load credentials: {}", .0)]
     Credentials(CredentialsError),
+
+    /// The request was cancelled by the user before a response was received.
+    #[error("the request was cancelled")]
+    Cancelled,
 }
 
 impl ApiClientError {
@@ -107,6 +115,7 @@ impl ApiClientError {
             Self::CodewhispererChatResponseStream(_) => None,
             Self::QDeveloperChatResponseStream(_) => None,
             Self::ListAvailableProfilesError(e) => sdk_status_code(e),
+            Self::ListAvailableModelsError(e) => sdk_status_code(e),
             Self::SendTelemetryEvent(e) => sdk_status_code(e),
             Self::CreateSubscriptionToken(e) => sdk_status_code(e),
             Self::QuotaBreach { status_code, .. } => *status_code,
@@ -116,6 +125,20 @@ impl ApiClientError {
             Self::ModelOverloadedError { status_code, .. } => *status_code,
             Self::MonthlyLimitReached { status_code } => *status_code,
             Self::Credentials(_e) => None,
+            Self::Cancelled => None,
+        }
+    }
+
+    /// Whether the request failed because the service could not be reached, e.g. when offline,
+    /// rather than because of the service.
+    pub fn is_connectivity_error(&self) -> bool {
+        match self {
+            Self::CodewhispererGenerateAssistantResponse(e) => sdk_is_connectivity_error(e),
+            Self::QDeveloperSendMessage(e) => sdk_is_connectivity_error(e),
+            Self::CodewhispererChatResponseStream(e) => sdk_is_connectivity_error(e),
+            Self::QDeveloperChatResponseStream(e) => sdk_is_connectivity_error(e),
+            Self::ListAvailableModelsError(e) => sdk_is_connectivity_error(e),
+            _ => false,
         }
     }
 }
@@ -132,6 +155,7 @@ impl ReasonCode for ApiClientError {
             Self::CodewhispererChatResponseStream(e) => sdk_error_code(e),
             Self::QDeveloperChatResponseStream(e) => sdk_error_code(e),
             Self::ListAvailableProfilesError(e) => sdk_error_code(e),
+            Self::ListAvailableModelsError(e) => sdk_error_code(e),
             Self::SendTelemetryEvent(e) => sdk_error_code(e),
             Self::CreateSubscriptionToken(e) => sdk_error_code(e),
             Self::QuotaBreach { .. } => "QuotaBreachError".to_string(),
@@ -141,6 +165,7 @@ impl ReasonCode for ApiClientError {
             Self::ModelOverloadedError { .. } => "ModelOverloadedError".to_string(),
             Self::MonthlyLimitReached { .. } => "MonthlyLimitReached".to_string(),
             Self::Credentials(_) => "CredentialsError".to_string(),
+            Self::Cancelled => "Cancelled".to_string(),
         }
     }
 }
@@ -151,6 +176,14 @@ fn sdk_error_code<T: ProvideErrorMetadata, R>(e: &SdkError<T, R>) -> String {
         .unwrap_or_else(|| e.to_string())
 }
 
+fn sdk_is_connectivity_error<E, R>(e: &SdkError<E, R>) -> bool {
+    match e {
+        SdkError::DispatchFailure(failure) => failure.is_io() || failure.is_timeout(),
+        SdkError::TimeoutError(_) => true,
+        _ => false,
+    }
+}
+
 fn sdk_status_code<E>(e: &SdkError<E, Response>) -> Option<u16> {
     e.raw_response().map(|res| res.status().as_u16())
 }
@@ -159,6 +192,7 @@ fn sdk_status_code<E>(e: &SdkError<E, Response>) -> Option<u16> {
 mod tests {
     use std::error::Error as _;
 
+    use aws_smithy_runtime_api::client::result::ConnectorError;
     use aws_smithy_runtime_api::http::Response;
     use aws_smithy_types::body::SdkBody;
     use aws_smithy_types::event_stream::Message;
@@ -223,4 +257,17 @@ mod tests {
             println!("{error} {error:?}");
         }
     }
+
+    #[test]
+    fn test_is_connectivity_error() {
+        let connector_error = ConnectorError::io("connection refused".into());
+        assert!(
+            ApiClientError::QDeveloperSendMessage(SdkError::dispatch_failure(connector_error)).is_connectivity_error()
+        );
+        assert!(
+            ApiClientError::CodewhispererGenerateAssistantResponse(SdkError::timeout_error("timed out"))
+                .is_connectivity_error()
+        );
+        assert!(!all_errors().iter().any(ApiClientError::is_connectivity_error));
+    }
 }
@@ -54,6 +54,7 @@ use crate::database::settings::Setting;
 use crate::database::{
     AuthProfile,
     Database,
+    ModelInfo,
 };
 use crate::os::{
     Env,
@@ -232,6 +233,35 @@ impl ApiClient {
         Ok(profiles)
     }
 
+    pub async fn list_available_models(&self) -> Result<Vec<ModelInfo>, ApiClientError> {
+        if cfg!(test) {
+            return Ok(vec![
+                ModelInfo {
+                    model_id: "CLAUDE_SONNET_4_20250514_V1_0".to_owned(),
+                    description: None,
+                },
+                ModelInfo {
+                    model_id: "CLAUDE_3_7_SONNET_20250219_V1_0".to_owned(),
+                    description: None,
+                },
+            ]);
+        }
+
+        let mut models = vec![];
+        let mut stream = self
+            .client
+            .list_available_models()
+            .origin(amzn_codewhisperer_client::types::Origin::Cli)
+            .set_profile_arn(self.profile.as_ref().map(|p| p.arn.clone()))
+            .into_paginator()
+            .send();
+        while let Some(models_output) = stream.next().await {
+            models.extend(models_output?.models().iter().cloned().map(ModelInfo::from));
+        }
+
+        Ok(models)
+    }
+
     pub async fn create_subscription_token(&self) -> Result<CreateSubscriptionTokenOutput, ApiClientError> {
         if cfg!(test) {
             return Ok(CreateSubscriptionTokenOutput::builder()
@@ -99,6 +99,47 @@ pub struct ConversationState {
     pub history: Option<Vec<ChatMessage>>,
 }
 
+impl ConversationState {
+    /// Estimates the size in bytes of the request payload, i.e. the content of the messages, the
+    /// tool specifications and results, and the base64 encoded images.
+    pub fn payload_size(&self) -> usize {
+        self.user_input_message.payload_size()
+            + self
+                .history
+                .iter()
+                .flatten()
+                .map(|message| match message {
+                    ChatMessage::AssistantResponseMessage(message) => {
+                        message.content.len() + message.tool_uses.as_ref().map_or(0, json_size)
+                    },
+                    ChatMessage::UserInputMessage(message) => message.payload_size(),
+                })
+                .sum::<usize>()
+    }
+}
+
+/// Size of the JSON serialization of `value`, without allocating it.
+fn json_size(value: &impl Serialize) -> usize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    match serde_json::to_writer(&mut counter, value) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ChatMessage {
     AssistantResponseMessage(AssistantResponseMessage),
@@ -890,6 +931,37 @@ impl From<UserInputMessage> for amzn_qdeveloper_streaming_client::types::UserInp
     }
 }
 
+impl UserInputMessage {
+    fn payload_size(&self) -> usize {
+        let images = self
+            .images
+            .iter()
+            .flatten()
+            .map(|image| match &image.source {
+                ImageSource::Bytes(bytes) => bytes.len().div_ceil(3) * 4,
+                ImageSource::Unknown => 0,
+            })
+            .sum::<usize>();
+        let context = self.user_input_message_context.as_ref().map_or(0, |context| {
+            let tool_results = context
+                .tool_results
+                .iter()
+                .flatten()
+                .flat_map(|result| &result.content)
+                .map(|block| match block {
+                    ToolResultContentBlock::Json(document) => json_size(&FigDocumentRef(document)),
+                    ToolResultContentBlock::Text(text) => text.len(),
+                })
+                .sum::<usize>();
+            context.env_state.as_ref().map_or(0, json_size)
+                + context.git_state.as_ref().map_or(0, |state| state.status.len())
+                + context.tools.as_ref().map_or(0, json_size)
+                + tool_results
+        });
+        self.content.len() + images + context
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct UserInputMessageContext {
     pub env_state: Option<EnvState>,
@@ -1002,6 +1074,44 @@ mod tests {
         assert_eq!(format!("{codewhisper_minimal:?}"), format!("{qdeveloper_minimal:?}"));
     }
 
+    #[test]
+    fn test_payload_size() {
+        let message = |content: &str| UserInputMessage {
+            content: content.to_string(),
+            user_input_message_context: None,
+            user_intent: None,
+            images: None,
+            model_id: None,
+        };
+        let state = ConversationState {
+            conversation_id: None,
+            user_input_message: UserInputMessage {
+                images: Some(vec![ImageBlock {
+                    format: ImageFormat::Png,
+                    source: ImageSource::Bytes(vec![0; 6]),
+                }]),
+                user_input_message_context: Some(UserInputMessageContext {
+                    tool_results: Some(vec![ToolResult {
+                        tool_use_id: "id".to_string(),
+                        content: vec![ToolResultContentBlock::Text("abc".to_string())],
+                        status: ToolResultStatus::Success,
+                    }]),
+                    ..Default::default()
+                }),
+                ..message("hello")
+            },
+            history: Some(vec![
+                ChatMessage::UserInputMessage(message("hey")),
+                ChatMessage::AssistantResponseMessage(AssistantResponseMessage {
+                    message_id: None,
+                    content: "hi".to_string(),
+                    tool_uses: None,
+                }),
+            ]),
+        };
+        assert_eq!(state.payload_size(), 5 + 8 + 3 + 3 + 2);
+    }
+
     #[test]
     fn build_assistant_response_message() {
         let message = AssistantResponseMessage {
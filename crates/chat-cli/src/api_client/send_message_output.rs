@@ -21,6 +21,11 @@ impl SendMessageOutput {
         }
     }
 
+    /// Closes the response stream, so that the rest of the response is not received.
+    pub fn close(&mut self) {
+        *self = SendMessageOutput::Mock(Vec::new());
+    }
+
     pub async fn recv(&mut self) -> Result<Option<ChatResponseStream>, ApiClientError> {
         match self {
             SendMessageOutput::Codewhisperer(output) => Ok(output
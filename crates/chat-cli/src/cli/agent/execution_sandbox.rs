@@ -0,0 +1,38 @@
+use std::fmt::Display;
+
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// The restricted environment that commands run by execute_bash are executed in. Sandboxing is
+/// opt-in and fails closed: if the sandbox cannot be set up on the current platform, the command
+/// is not run.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ExecutionSandbox {
+    /// Commands run without any restrictions
+    #[default]
+    None,
+    /// Commands run without network access
+    NoNetwork,
+    /// Commands run without network access, in a temporary working directory
+    Restricted,
+}
+
+impl ExecutionSandbox {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::NoNetwork => "noNetwork",
+            Self::Restricted => "restricted",
+        }
+    }
+}
+
+impl Display for ExecutionSandbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,64 @@
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Generation parameters requested for the responses of an agent. Parameters that are not set
+/// are left to the service.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GenerationParameters {
+    /// Sampling temperature, from 0 to 1. Lower values make responses more deterministic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 0.0, max = 1.0))]
+    pub temperature: Option<f64>,
+    /// Nucleus sampling probability mass, from 0 to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 0.0, max = 1.0))]
+    pub top_p: Option<f64>,
+    /// Maximum number of tokens of a response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub max_response_tokens: Option<u32>,
+}
+
+impl GenerationParameters {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Describes the parameters that are set, e.g. `temperature 0.2, max response tokens 4096`.
+    pub fn describe(&self) -> Option<String> {
+        let parameters = [
+            self.temperature.map(|t| format!("temperature {t}")),
+            self.top_p.map(|p| format!("top-p {p}")),
+            self.max_response_tokens.map(|n| format!("max response tokens {n}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        (!parameters.is_empty()).then(|| parameters.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let parameters = serde_json::from_str::<GenerationParameters>("{}").unwrap();
+        assert!(parameters.is_empty());
+        assert_eq!(parameters.describe(), None);
+
+        let parameters =
+            serde_json::from_str::<GenerationParameters>(r#"{ "temperature": 0.2, "maxResponseTokens": 4096 }"#)
+                .unwrap();
+        assert_eq!(
+            parameters.describe().as_deref(),
+            Some("temperature 0.2, max response tokens 4096")
+        );
+        assert!(serde_json::from_str::<GenerationParameters>(r#"{ "topK": 5 }"#).is_err());
+    }
+}
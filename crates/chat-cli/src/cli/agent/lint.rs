@@ -0,0 +1,379 @@
+//! Checks of agent configs, reporting every problem found along with where it is in the file,
+//! rather than only the first one found when deserializing.
+
+use std::fmt::Display;
+
+use globset::Glob;
+use jsonschema::error::ValidationErrorKind;
+use schemars::schema_for;
+use serde_json::Value;
+
+use super::Agent;
+use super::hook::HookTrigger;
+use crate::cli::chat::cli::doctor::find_executable;
+
+/// Settings of tools in `toolsSettings` that hold globs.
+const GLOB_SETTINGS: &[&str] = &["allowedPaths", "deniedPaths"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The agent cannot be loaded.
+    Error,
+    /// The agent can be loaded, but will likely not work as intended.
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub severity: Severity,
+    /// JSON pointer to the offending value, e.g. `/toolsSettings/fs_read/allowedPaths/0`.
+    pub pointer: String,
+    /// 1-based line of the offending value, or of the offending key for unknown fields.
+    pub line: usize,
+    /// 1-based column, in characters.
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}:{}: {severity}: ", self.line, self.column)?;
+        if !self.pointer.is_empty() {
+            write!(f, "{}: ", self.pointer)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Lints the agent config `content`. MCP server commands are searched for in `path`, the value of
+/// the PATH environment variable.
+pub fn lint(content: &[u8], path: &str) -> Vec<LintIssue> {
+    let text = String::from_utf8_lossy(content);
+    let value = match serde_json::from_str::<Value>(&text) {
+        Ok(value) => value,
+        Err(err) => {
+            // The error already includes the location.
+            let message = err.to_string();
+            let message = message
+                .rsplit_once(" at line ")
+                .map_or(message.as_str(), |(message, _)| message);
+            return vec![LintIssue {
+                severity: Severity::Error,
+                pointer: String::new(),
+                line: err.line().max(1),
+                column: err.column().max(1),
+                message: message.to_string(),
+            }];
+        },
+    };
+
+    let mut issues = Vec::new();
+    let mut push = |severity: Severity, pointer: String, at_key: bool, message: String| {
+        let (line, column) = locate(&text, &pointer, at_key);
+        issues.push(LintIssue {
+            severity,
+            pointer,
+            line,
+            column,
+            message,
+        });
+    };
+
+    let hooks = value.get("hooks").and_then(Value::as_object);
+    let is_trigger = |key: &str| serde_json::from_value::<HookTrigger>(Value::String(key.to_string())).is_ok();
+    if let Ok(validator) = serde_json::to_value(schema_for!(Agent)).map(|schema| jsonschema::validator_for(&schema)) {
+        match validator {
+            Ok(validator) => {
+                for err in validator.iter_errors(&value) {
+                    let pointer = err.instance_path.to_string();
+                    match &err.kind {
+                        ValidationErrorKind::AdditionalProperties { unexpected } => {
+                            for key in unexpected {
+                                push(
+                                    Severity::Error,
+                                    format!("{pointer}/{}", escape(key)),
+                                    true,
+                                    format!("unknown field `{key}`"),
+                                );
+                            }
+                        },
+                        _ => push(Severity::Error, pointer, false, err.to_string()),
+                    }
+                }
+            },
+            Err(err) => tracing::error!("Failed to compile the agent schema: {err}"),
+        }
+    }
+
+    for trigger in hooks.into_iter().flat_map(|hooks| hooks.keys()) {
+        if !is_trigger(trigger) {
+            push(
+                Severity::Error,
+                format!("/hooks/{}", escape(trigger)),
+                true,
+                format!(
+                    "unknown hook trigger `{trigger}`, expected one of `{}`, `{}`",
+                    HookTrigger::AgentSpawn,
+                    HookTrigger::UserPromptSubmit
+                ),
+            );
+        }
+    }
+
+    for (tool, settings) in value
+        .get("toolsSettings")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+    {
+        for setting in GLOB_SETTINGS {
+            let Some(globs) = settings.get(*setting).and_then(Value::as_array) else {
+                continue;
+            };
+            for (i, glob) in globs.iter().enumerate() {
+                if let Some(Err(err)) = glob.as_str().map(Glob::new) {
+                    push(
+                        Severity::Error,
+                        format!("/toolsSettings/{}/{setting}/{i}", escape(tool)),
+                        false,
+                        format!("invalid glob: {}", err.kind()),
+                    );
+                }
+            }
+        }
+    }
+
+    for (name, server) in value.get("mcpServers").and_then(Value::as_object).into_iter().flatten() {
+        let pointer = format!("/mcpServers/{}", escape(name));
+        match server.get("command").and_then(Value::as_str) {
+            Some(command) if command.trim().is_empty() => push(
+                Severity::Error,
+                format!("{pointer}/command"),
+                false,
+                format!("MCP server `{name}` has an empty command"),
+            ),
+            Some(command) => {
+                let disabled = server.get("disabled").and_then(Value::as_bool).unwrap_or(false);
+                if !disabled && find_executable(command, path).is_none() {
+                    push(
+                        Severity::Warning,
+                        format!("{pointer}/command"),
+                        false,
+                        format!("command `{command}` of MCP server `{name}` is not found"),
+                    );
+                }
+            },
+            // Reported by the schema.
+            None => {},
+        }
+    }
+
+    issues.sort_by_key(|issue| (issue.line, issue.column));
+    issues
+}
+
+/// Escapes a JSON pointer token.
+fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// Returns the 1-based line and column of the value at `pointer` in `text`, or of its key when
+/// `at_key` is set. Falls back to the closest enclosing value that exists.
+fn locate(text: &str, pointer: &str, at_key: bool) -> (usize, usize) {
+    let tokens = pointer.split('/').skip(1).map(unescape).collect::<Vec<_>>();
+    let mut locator = Locator {
+        bytes: text.as_bytes(),
+        pos: 0,
+    };
+    locator.skip_whitespace();
+    let offset = locator.find(&tokens, at_key);
+
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+/// Walks JSON that is known to be valid.
+struct Locator<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Locator<'_> {
+    /// Returns the offset of the value at `tokens`, starting from the value at the cursor.
+    fn find(&mut self, tokens: &[String], at_key: bool) -> usize {
+        let start = self.pos;
+        let Some((token, rest)) = tokens.split_first() else {
+            return start;
+        };
+        match self.bytes.get(self.pos) {
+            Some(b'{') => {
+                self.pos += 1;
+                loop {
+                    self.skip_whitespace();
+                    if self.bytes.get(self.pos) != Some(&b'"') {
+                        return start;
+                    }
+                    let key_start = self.pos;
+                    let key = self.string();
+                    self.skip_whitespace();
+                    self.pos += 1; // :
+                    self.skip_whitespace();
+                    if key == *token {
+                        return match (rest.is_empty(), at_key) {
+                            (true, true) => key_start,
+                            _ => self.find(rest, at_key),
+                        };
+                    }
+                    self.skip_value();
+                    self.skip_whitespace();
+                    if self.bytes.get(self.pos) != Some(&b',') {
+                        return start;
+                    }
+                    self.pos += 1;
+                }
+            },
+            Some(b'[') => {
+                let Ok(index) = token.parse::<usize>() else {
+                    return start;
+                };
+                self.pos += 1;
+                for _ in 0..index {
+                    self.skip_whitespace();
+                    self.skip_value();
+                    self.skip_whitespace();
+                    if self.bytes.get(self.pos) != Some(&b',') {
+                        return start;
+                    }
+                    self.pos += 1;
+                }
+                self.skip_whitespace();
+                match self.bytes.get(self.pos) {
+                    Some(b']') | None => start,
+                    Some(_) => self.find(rest, at_key),
+                }
+            },
+            _ => start,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads the string at the cursor.
+    fn string(&mut self) -> String {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(byte) = self.bytes.get(self.pos) {
+            self.pos += 1;
+            match byte {
+                b'\\' => self.pos += 1,
+                b'"' => break,
+                _ => {},
+            }
+        }
+        let raw = &self.bytes[start..self.pos.min(self.bytes.len())];
+        serde_json::from_slice(raw).unwrap_or_default()
+    }
+
+    fn skip_value(&mut self) {
+        let mut depth = 0usize;
+        while let Some(byte) = self.bytes.get(self.pos) {
+            match byte {
+                b'"' => {
+                    self.string();
+                    if depth == 0 {
+                        return;
+                    }
+                    continue;
+                },
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' if depth == 0 => return,
+                b'}' | b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.pos += 1;
+                        return;
+                    }
+                },
+                b',' if depth == 0 => return,
+                byte if depth == 0 && byte.is_ascii_whitespace() => return,
+                _ => {},
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(content: &str, path: &str) -> Vec<(Severity, String, usize, usize)> {
+        lint(content.as_bytes(), path)
+            .into_iter()
+            .map(|issue| (issue.severity, issue.pointer, issue.line, issue.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        assert_eq!(issues("{}", ""), vec![]);
+        assert_eq!(issues("{\n  \"tools\": [\"*\"],\n}", ""), vec![(
+            Severity::Error,
+            String::new(),
+            3,
+            1
+        )]);
+
+        let content = r#"{
+  "description": "test",
+  "tool": ["*"],
+  "hooks": {
+    "agentSpawn": [{ "command": "git status" }],
+    "onExit": [{ "command": "echo bye" }]
+  },
+  "toolsSettings": {
+    "fs_read": { "allowedPaths": ["src/**", "src/[a"] }
+  },
+  "mcpServers": {
+    "empty": { "command": "" },
+    "missing": { "command": "definitely-not-installed" },
+    "disabled": { "command": "definitely-not-installed", "disabled": true }
+  }
+}"#;
+        assert_eq!(issues(content, ""), vec![
+            (Severity::Error, "/tool".to_string(), 3, 3),
+            (Severity::Error, "/hooks/onExit".to_string(), 6, 5),
+            (
+                Severity::Error,
+                "/toolsSettings/fs_read/allowedPaths/1".to_string(),
+                9,
+                45
+            ),
+            (Severity::Error, "/mcpServers/empty/command".to_string(), 12, 27),
+            (Severity::Warning, "/mcpServers/missing/command".to_string(), 13, 29),
+        ]);
+    }
+
+    #[test]
+    fn test_locate() {
+        let text = "{\"a\": [1, {\"b~/\": \"x\"}], \"c\": {}}";
+        assert_eq!(locate(text, "", false), (1, 1));
+        assert_eq!(locate(text, "/a/1/b~0~1", false), (1, 19));
+        assert_eq!(locate(text, "/a/1/b~0~1", true), (1, 12));
+        assert_eq!(locate(text, "/c/d", false), (1, 31));
+    }
+}
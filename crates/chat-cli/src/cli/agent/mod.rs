@@ -1,7 +1,11 @@
+mod execution_sandbox;
+mod generation;
 pub mod hook;
 mod legacy;
+mod lint;
 mod mcp_config;
 mod root_command_args;
+mod trust_messages;
 mod wrapper_types;
 
 use std::borrow::Borrow;
@@ -24,17 +28,19 @@ use crossterm::style::{
     Stylize as _,
 };
 use crossterm::{
-    execute,
     queue,
     style,
 };
+pub use execution_sandbox::ExecutionSandbox;
 use eyre::bail;
+pub use generation::GenerationParameters;
+use lint::{
+    LintIssue,
+    Severity,
+};
 pub use mcp_config::McpServerConfig;
 pub use root_command_args::*;
-use schemars::{
-    JsonSchema,
-    schema_for,
-};
+use schemars::JsonSchema;
 use serde::{
     Deserialize,
     Serialize,
@@ -45,6 +51,7 @@ use tracing::{
     error,
     warn,
 };
+pub use trust_messages::TrustMessages;
 use wrapper_types::ResourcePath;
 pub use wrapper_types::{
     OriginalToolName,
@@ -89,6 +96,16 @@ pub enum AgentConfigError {
     MissingFilename,
     #[error("Failed to parse legacy mcp config: {0}")]
     BadLegacyMcpConfig(#[from] eyre::Report),
+    #[error("Agent config at {} is invalid:\n{}", path.display(), format_issues(issues))]
+    Lint { path: PathBuf, issues: Vec<LintIssue> },
+}
+
+fn format_issues(issues: &[LintIssue]) -> String {
+    issues
+        .iter()
+        .map(|issue| format!("  {issue}"))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 /// An [Agent] is a declarative way of configuring a given instance of q chat. Currently, it is
@@ -115,7 +132,7 @@ pub enum AgentConfigError {
 ///
 /// Where agents are instantiated from their config, we would need to convert them from "cold" to
 /// "warm".
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 #[schemars(description = "An Agent is a declarative way of configuring a given instance of q chat.")]
 pub struct Agent {
@@ -130,6 +147,13 @@ pub struct Agent {
     /// agent. This should be seen as the same category of context as a system prompt.
     #[serde(default)]
     pub prompt: Option<String>,
+    /// Name or id of the model used by default with this agent. Choosing a model with --model or
+    /// /model takes precedence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Generation parameters requested for the responses of this agent
+    #[serde(default, skip_serializing_if = "GenerationParameters::is_empty")]
+    pub generation: GenerationParameters,
     /// Configuration for Model Context Protocol (MCP) servers
     #[serde(default)]
     pub mcp_servers: McpServerConfig,
@@ -160,6 +184,26 @@ pub struct Agent {
     /// you configure in the mcpServers field in this config
     #[serde(default)]
     pub use_legacy_mcp_json: bool,
+    /// Customizes the trust related messages shown during the session, such as the warning
+    /// displayed when all tools are trusted
+    #[serde(default)]
+    pub trust_messages: TrustMessages,
+    /// Runs the commands of execute_bash and the code of scratchpad in a restricted environment
+    #[serde(default)]
+    pub execution_sandbox: ExecutionSandbox,
+    /// The directory that fs_read, fs_write, and execute_bash are confined to when
+    /// strictWorkspace is enabled. Relative paths are resolved from the current working directory,
+    /// which is also the default
+    #[serde(default)]
+    pub workspace_root: Option<String>,
+    /// Whether tools must ask for approval before touching a path outside of the workspace root,
+    /// even when they are trusted
+    #[serde(default)]
+    pub strict_workspace: bool,
+    /// Path of a JSONL file that every tool use is appended to, along with its approval
+    /// decision and outcome. Relative paths are resolved from the current working directory
+    #[serde(default)]
+    pub audit_log: Option<String>,
     #[serde(skip)]
     pub path: Option<PathBuf>,
 }
@@ -170,6 +214,8 @@ impl Default for Agent {
             name: "default".to_string(),
             description: Some("Default agent".to_string()),
             prompt: Default::default(),
+            model: None,
+            generation: Default::default(),
             mcp_servers: Default::default(),
             tools: vec!["*".to_string()],
             tool_aliases: Default::default(),
@@ -186,6 +232,11 @@ impl Default for Agent {
             hooks: Default::default(),
             tools_settings: Default::default(),
             use_legacy_mcp_json: true,
+            trust_messages: Default::default(),
+            execution_sandbox: Default::default(),
+            workspace_root: None,
+            strict_workspace: false,
+            audit_log: None,
             path: None,
         }
     }
@@ -217,6 +268,7 @@ impl Agent {
             .to_string();
 
         self.name = name.clone();
+        self.path = Some(path.to_path_buf());
 
         if let (true, Some(global_mcp_config)) = (self.use_legacy_mcp_json, global_mcp_config) {
             let mut stderr = std::io::stderr();
@@ -296,6 +348,18 @@ impl Agent {
         global_mcp_config: &mut Option<McpServerConfig>,
     ) -> Result<Agent, AgentConfigError> {
         let content = os.fs.read(&agent_path).await?;
+        let (errors, warnings) = lint::lint(&content, &os.env.get("PATH").unwrap_or_default())
+            .into_iter()
+            .partition::<Vec<_>, _>(|issue| issue.severity == Severity::Error);
+        if !errors.is_empty() {
+            return Err(AgentConfigError::Lint {
+                path: agent_path.as_ref().to_path_buf(),
+                issues: errors,
+            });
+        }
+        for issue in warnings {
+            warn!(path = %agent_path.as_ref().display(), "{issue}");
+        }
         let mut agent = serde_json::from_slice::<Agent>(&content).map_err(|e| AgentConfigError::InvalidJson {
             error: e,
             path: agent_path.as_ref().to_path_buf(),
@@ -621,25 +685,20 @@ impl Agents {
 
         let _ = output.flush();
 
-        // Post parsing validation here
-        let schema = schema_for!(Agent);
         let agents = all_agents
             .into_iter()
             .map(|a| (a.name.clone(), a))
             .collect::<HashMap<_, _>>();
-        let active_agent = agents.get(&active_idx);
-
-        'validate: {
-            match (serde_json::to_value(schema), active_agent) {
-                (Ok(schema), Some(agent)) => {
-                    let Ok(instance) = serde_json::to_value(agent) else {
-                        let name = &agent.name;
-                        error!("Error converting active agent {name} to value for validation. Skipping");
-                        break 'validate;
-                    };
-                    if let Err(e) = jsonschema::validate(&schema, &instance).map_err(|e| e.to_owned()) {
-                        let name = &agent.name;
-                        let _ = execute!(
+
+        // Errors stop agents from loading, but warnings are only surfaced for the active agent
+        if let Some((name, path)) = agents
+            .get(&active_idx)
+            .and_then(|agent| Some((&agent.name, agent.path.as_ref()?)))
+        {
+            match os.fs.read(path).await {
+                Ok(content) => {
+                    for issue in lint::lint(&content, &os.env.get("PATH").unwrap_or_default()) {
+                        let _ = queue!(
                             output,
                             style::SetForegroundColor(Color::Yellow),
                             style::Print("WARNING "),
@@ -648,20 +707,12 @@ impl Agents {
                             style::SetForegroundColor(Color::Green),
                             style::Print(name),
                             style::ResetColor,
-                            style::Print(" is malformed at "),
-                            style::SetForegroundColor(Color::Yellow),
-                            style::Print(&e.instance_path),
-                            style::ResetColor,
-                            style::Print(format!(": {e}\n")),
+                            style::Print(format!(" at {issue}\n")),
                         );
                     }
+                    let _ = output.flush();
                 },
-                (Err(e), _) => {
-                    error!("Failed to convert agent definition to schema: {e}. Skipping validation");
-                },
-                (_, None) => {
-                    warn!("Skipping config validation because there is no active agent");
-                },
+                Err(e) => error!("Error reading active agent {name} for validation: {e}. Skipping"),
             }
         }
 
@@ -700,15 +751,20 @@ impl Agents {
     // This "static" way avoids needing to construct a tool instance.
     fn default_permission_label(&self, tool_name: &str) -> String {
         let label = match tool_name {
-            "fs_read" => "trusted".dark_green().bold(),
+            "fs_read" | "list_files" => "trusted".dark_green().bold(),
             "fs_write" => "not trusted".dark_grey(),
             #[cfg(not(windows))]
             "execute_bash" => "trust read-only commands".dark_grey(),
             #[cfg(windows)]
             "execute_cmd" => "trust read-only commands".dark_grey(),
             "use_aws" => "trust read-only commands".dark_grey(),
+            "http_request" => "trust read-only requests".dark_grey(),
             "report_issue" => "trusted".dark_green().bold(),
             "thinking" => "trusted (prerelease)".dark_green().bold(),
+            "update_plan" | "semantic_search" | "kb_search" | "aws_read" | "logs_tail" | "browser" => {
+                "trusted".dark_green().bold()
+            },
+            "process_list" | "process_output" | "process_kill" => "trusted".dark_green().bold(),
             _ if self.trust_all_tools => "trusted".dark_grey().bold(),
             _ => "not trusted".dark_grey(),
         };
@@ -738,8 +794,7 @@ async fn load_agents_from_entries(
     res
 }
 
-#[cfg(test)]
-fn validate_agent_name(name: &str) -> eyre::Result<()> {
+pub fn validate_agent_name(name: &str) -> eyre::Result<()> {
     // Check if name is empty
     if name.is_empty() {
         eyre::bail!("Agent name cannot be empty");
@@ -17,11 +17,16 @@ use eyre::{
 };
 use schemars::schema_for;
 
+use super::lint::{
+    Severity,
+    lint,
+};
 use super::{
     Agent,
     Agents,
     McpServerConfig,
 };
+use crate::cli::chat::agent_socket;
 use crate::database::settings::Setting;
 use crate::os::Os;
 use crate::util::directories::{
@@ -62,6 +67,18 @@ pub enum AgentSubcommands {
         #[arg(long, short)]
         path: String,
     },
+    /// Check an agent config for problems, such as unknown fields, invalid hook triggers, bad
+    /// globs, and missing MCP server commands, printing where each one is. Exits with an error if
+    /// any are found, e.g. for CI
+    Lint {
+        /// Path of the agent config
+        file: PathBuf,
+        /// Also exit with an error if there are warnings
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+    /// Remove the sockets left behind by chat sessions that are no longer running
+    Gc,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Args)]
@@ -135,6 +152,40 @@ impl AgentArgs {
                 rename_agent(os, &mut agents, agent.clone(), new_name.clone()).await?;
                 writeln!(stderr, "\n✓ Renamed agent '{}' to '{}'\n", agent, new_name)?;
             },
+            Some(AgentSubcommands::Gc) => {
+                let mut removed = agent_socket::gc(&agent_socket::socket_dir());
+                removed.extend(agent_socket::gc(&agent_socket::legacy_socket_dir()));
+                for path in &removed {
+                    writeln!(stderr, "Removed {}", path.display())?;
+                }
+                let running = agent_socket::sessions(&agent_socket::socket_dir()).len();
+                writeln!(
+                    stderr,
+                    "\n✓ Removed {} files of stale sessions, {running} sessions are running\n",
+                    removed.len()
+                )?;
+            },
+            Some(AgentSubcommands::Lint { file, deny_warnings }) => {
+                let content = match os.fs.read(&file).await {
+                    Ok(content) => content,
+                    Err(e) => bail!("Failed to read {}: {e}", file.display()),
+                };
+                let issues = lint(&content, &os.env.get("PATH").unwrap_or_default());
+                for issue in &issues {
+                    writeln!(stderr, "{}:{issue}", file.display())?;
+                }
+
+                let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+                let warnings = issues.len() - errors;
+                if issues.is_empty() {
+                    writeln!(stderr, "✓ {} is valid", file.display())?;
+                } else {
+                    writeln!(stderr, "\n{errors} errors, {warnings} warnings")?;
+                }
+                if errors > 0 || (deny_warnings && warnings > 0) {
+                    return Ok(ExitCode::FAILURE);
+                }
+            },
             Some(AgentSubcommands::Validate { path }) => {
                 let mut global_mcp_config = None::<McpServerConfig>;
                 let agent = Agent::load(os, path.as_str(), &mut global_mcp_config).await;
@@ -337,4 +388,16 @@ mod tests {
             })
         );
     }
+    #[test]
+    fn test_agent_subcommand_lint() {
+        assert_parse!(
+            ["agent", "lint", "agent.json", "--deny-warnings"],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Lint {
+                    file: PathBuf::from("agent.json"),
+                    deny_warnings: true,
+                })
+            })
+        );
+    }
 }
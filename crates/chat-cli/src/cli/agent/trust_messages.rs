@@ -0,0 +1,59 @@
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Controls the trust related messaging shown to the user over the course of a chat session.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TrustMessages {
+    /// Whether to show the warning banner on startup when all tools are trusted
+    #[serde(default = "TrustMessages::default_true")]
+    pub show_trust_all_banner: bool,
+    /// Whether to show hints on how to trust a tool when asking for tool approval
+    #[serde(default = "TrustMessages::default_true")]
+    pub show_tool_trust_hints: bool,
+    /// Custom notice to display on startup in place of the default trust-all warning. Useful for
+    /// organizations that mandate specific security wording
+    #[serde(default)]
+    pub security_notice: Option<String>,
+}
+
+impl Default for TrustMessages {
+    fn default() -> Self {
+        Self {
+            show_trust_all_banner: true,
+            show_tool_trust_hints: true,
+            security_notice: None,
+        }
+    }
+}
+
+impl TrustMessages {
+    fn default_true() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deser_defaults() {
+        let messages = serde_json::from_str::<TrustMessages>("{}").unwrap();
+        assert_eq!(messages, TrustMessages::default());
+
+        let messages = serde_json::from_str::<TrustMessages>(
+            r#"{ "showTrustAllBanner": false, "securityNotice": "Tools run under corp policy 12." }"#,
+        )
+        .unwrap();
+        assert!(!messages.show_trust_all_banner);
+        assert!(messages.show_tool_trust_hints);
+        assert_eq!(
+            messages.security_notice.as_deref(),
+            Some("Tools run under corp policy 12.")
+        );
+    }
+}
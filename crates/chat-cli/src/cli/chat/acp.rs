@@ -0,0 +1,555 @@
+//! Agent-client protocol frontend of `q chat --acp`, which lets editors drive a session over
+//! stdio.
+//!
+//! Messages are JSON-RPC 2.0, one per line. The client sends the requests:
+//!
+//! - `initialize`: returns the protocol version and the name and version of the agent.
+//! - `session/prompt` with `{ "text": ... }`: sends a message, or a slash command, to the session.
+//! - `session/status`: returns the state of the session.
+//!
+//! The session sends the notifications:
+//!
+//! - `session/output` with `{ "stream": "stdout" | "stderr", "text": ... }`: text written by the
+//!   session, without formatting. Responses are streamed to `stdout` as they are received.
+//! - `session/status` with `{ "status": ... }`: the state of the session changed.
+//! - `session/awaitingInput` with `{ "prompt": ... }`: the session is waiting for a message.
+//!
+//! Before running tools that need approval, the session sends the request
+//! `session/requestPermission` with `{ "tools": [...] }`, which the client answers with
+//! `{ "decision": "approve" | "reject" | "trust" }`.
+
+use std::collections::VecDeque;
+use std::io::{
+    BufRead,
+    Write,
+};
+use std::sync::atomic::{
+    AtomicU64,
+    Ordering,
+};
+use std::sync::mpsc::{
+    Receiver,
+    Sender,
+    channel,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    Value,
+    json,
+};
+use spinners::Spinner;
+use tracing::{
+    debug,
+    error,
+};
+
+use super::renderer::{
+    Renderer,
+    SessionStatus,
+};
+
+const PROTOCOL_VERSION: u32 = 1;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// A JSON-RPC message received from the client.
+#[derive(Debug, Deserialize)]
+struct Message {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    result: Option<Value>,
+}
+
+/// Messages of the client that the session waits for.
+#[derive(Debug, PartialEq)]
+enum Incoming {
+    Prompt(String),
+    Response { id: u64, result: Option<Value> },
+}
+
+#[derive(Debug, Deserialize)]
+struct PromptParams {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PermissionResult {
+    decision: Decision,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Decision {
+    Approve,
+    Reject,
+    Trust,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// State shared between the session and the thread reading the client's messages.
+struct Shared {
+    status: Mutex<SessionStatus>,
+    out: Mutex<Box<dyn Write + Send>>,
+    next_id: AtomicU64,
+}
+
+impl std::fmt::Debug for Shared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared").finish_non_exhaustive()
+    }
+}
+
+impl Shared {
+    fn new(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            status: Mutex::new(SessionStatus::default()),
+            out: Mutex::new(out),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    fn write(&self, message: &Value) {
+        let Ok(mut out) = self.out.lock() else {
+            return;
+        };
+        let written = serde_json::to_writer(&mut *out, message)
+            .map_err(std::io::Error::from)
+            .and_then(|_| out.write_all(b"\n"))
+            .and_then(|_| out.flush());
+        if let Err(err) = written {
+            error!(?err, "failed to write to the client");
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.write(&json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Sends a request to the client, returning its id.
+    fn request(&self, method: &str, params: Value) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.write(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        id
+    }
+
+    fn respond(&self, id: Value, result: Result<Value, (i64, String)>) {
+        self.write(&match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => {
+                json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+            },
+        });
+    }
+
+    /// Handles a line received from the client, returning what the session waits for.
+    fn handle_line(&self, line: &str) -> Option<Incoming> {
+        let message = match serde_json::from_str::<Message>(line) {
+            Ok(message) => message,
+            Err(err) => {
+                self.respond(Value::Null, Err((PARSE_ERROR, err.to_string())));
+                return None;
+            },
+        };
+
+        let Some(method) = message.method else {
+            // A response to a request of the session.
+            return match message.id.as_ref().and_then(Value::as_u64) {
+                Some(id) => Some(Incoming::Response {
+                    id,
+                    result: message.result,
+                }),
+                None => None,
+            };
+        };
+        let id = message.id.unwrap_or(Value::Null);
+
+        match method.as_str() {
+            "initialize" => {
+                self.respond(
+                    id,
+                    Ok(json!({
+                        "protocolVersion": PROTOCOL_VERSION,
+                        "agentInfo": { "name": "q chat", "version": env!("CARGO_PKG_VERSION") },
+                    })),
+                );
+                None
+            },
+            "session/prompt" => match serde_json::from_value::<PromptParams>(message.params) {
+                Ok(params) => {
+                    self.respond(id, Ok(json!({})));
+                    Some(Incoming::Prompt(params.text))
+                },
+                Err(err) => {
+                    self.respond(id, Err((INVALID_PARAMS, err.to_string())));
+                    None
+                },
+            },
+            "session/status" => {
+                let status = self.status.lock().map(|status| status.clone()).unwrap_or_default();
+                self.respond(id, Ok(json!({ "status": status })));
+                None
+            },
+            _ => {
+                self.respond(id, Err((METHOD_NOT_FOUND, format!("Unknown method {method}"))));
+                None
+            },
+        }
+    }
+}
+
+/// The protocol frontend.
+#[derive(Debug)]
+pub struct Acp {
+    shared: Arc<Shared>,
+}
+
+impl Acp {
+    /// Starts reading messages from stdin, writing messages to stdout. Returns the source of the
+    /// prompts sent by the client.
+    pub fn start() -> (Self, AcpInput) {
+        let shared = Arc::new(Shared::new(Box::new(std::io::stdout())));
+        let (sender, receiver) = channel();
+        {
+            let shared = shared.clone();
+            std::thread::spawn(move || read_messages(std::io::stdin().lock(), &shared, &sender));
+        }
+        (Self { shared: shared.clone() }, AcpInput::new(shared, receiver))
+    }
+
+    /// Returns a stream whose writes are sent to the client as output notifications.
+    pub fn output(&self, stream: OutputStream) -> AcpOutput {
+        AcpOutput::new(NotificationWriter {
+            stream,
+            shared: self.shared.clone(),
+        })
+    }
+}
+
+impl Renderer for Acp {
+    fn update(&self, status: &SessionStatus) {
+        if let Ok(mut current) = self.shared.status.lock() {
+            if *current == *status {
+                return;
+            }
+            *current = status.clone();
+        }
+        self.shared.notify("session/status", json!({ "status": status }));
+    }
+
+    fn busy(&self, message: &str) -> Option<Spinner> {
+        if let Ok(status) = self.shared.status.lock() {
+            let status = SessionStatus {
+                activity: message.to_string(),
+                ..status.clone()
+            };
+            self.shared.notify("session/status", json!({ "status": status }));
+        }
+        None
+    }
+
+    fn reads_terminal(&self) -> bool {
+        false
+    }
+}
+
+fn read_messages(input: impl BufRead, shared: &Shared, sender: &Sender<Incoming>) {
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                error!(?err, "failed to read from the client");
+                break;
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(incoming) = shared.handle_line(&line) {
+            if sender.send(incoming).is_err() {
+                break;
+            }
+        }
+    }
+    debug!("the client closed the connection");
+}
+
+#[derive(Clone)]
+struct NotificationWriter {
+    stream: OutputStream,
+    shared: Arc<Shared>,
+}
+
+impl Write for NotificationWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = String::from_utf8_lossy(buf).replace('\r', "");
+        if !text.is_empty() {
+            self.shared
+                .notify("session/output", json!({ "stream": self.stream, "text": text }));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Output stream of a session sent to the client, with ANSI escapes removed.
+pub struct AcpOutput {
+    writer: Box<strip_ansi_escapes::Writer<NotificationWriter>>,
+    /// Where [Self::writer] writes to, kept to create other outputs to the same destination.
+    destination: NotificationWriter,
+}
+
+impl AcpOutput {
+    fn new(destination: NotificationWriter) -> Self {
+        Self {
+            writer: Box::new(strip_ansi_escapes::Writer::new(destination.clone())),
+            destination,
+        }
+    }
+
+    /// Returns another output writing to the client, see [super::output::SessionOutput::duplicate].
+    pub fn duplicate(&self) -> Self {
+        Self::new(self.destination.clone())
+    }
+}
+
+impl std::fmt::Debug for AcpOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AcpOutput").finish_non_exhaustive()
+    }
+}
+
+impl Write for AcpOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Prompts and approvals sent by the client.
+#[derive(Debug)]
+pub struct AcpInput {
+    shared: Arc<Shared>,
+    incoming: Receiver<Incoming>,
+    /// Prompts received while waiting for an approval, read next.
+    queued: VecDeque<String>,
+}
+
+impl AcpInput {
+    fn new(shared: Arc<Shared>, incoming: Receiver<Incoming>) -> Self {
+        Self {
+            shared,
+            incoming,
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Waits for the next prompt of the client, or asks it for permission if a tool use is
+    /// awaiting approval. Returns `None` once the client closes stdin.
+    pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+        let tools = self
+            .shared
+            .status
+            .lock()
+            .map(|status| {
+                status
+                    .pending_tools
+                    .iter()
+                    .filter(|tool| tool.awaiting_approval)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        if !tools.is_empty() {
+            return self.request_permission(json!({ "tools": tools }));
+        }
+
+        if let Some(line) = self.queued.pop_front() {
+            return Some(line);
+        }
+        let prompt = String::from_utf8_lossy(&strip_ansi_escapes::strip(prompt)).into_owned();
+        self.shared.notify("session/awaitingInput", json!({ "prompt": prompt }));
+        loop {
+            match self.incoming.recv().ok()? {
+                Incoming::Prompt(line) => return Some(line),
+                Incoming::Response { id, .. } => debug!(id, "ignoring an unexpected response"),
+            }
+        }
+    }
+
+    fn request_permission(&mut self, params: Value) -> Option<String> {
+        let request_id = self.shared.request("session/requestPermission", params);
+        loop {
+            match self.incoming.recv().ok()? {
+                Incoming::Prompt(line) => self.queued.push_back(line),
+                Incoming::Response { id, result } if id == request_id => {
+                    let decision = result
+                        .and_then(|result| serde_json::from_value::<PermissionResult>(result).ok())
+                        .map_or(Decision::Reject, |result| result.decision);
+                    return Some(
+                        match decision {
+                            Decision::Approve => "y",
+                            Decision::Reject => "n",
+                            Decision::Trust => "t",
+                        }
+                        .to_string(),
+                    );
+                },
+                Incoming::Response { id, .. } => debug!(id, "ignoring an unexpected response"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::renderer::PendingTool;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn messages(&self) -> Vec<Value> {
+            let bytes = std::mem::take(&mut *self.0.lock().unwrap());
+            String::from_utf8(bytes)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_handle_line() {
+        let buffer = Buffer::default();
+        let shared = Shared::new(Box::new(buffer.clone()));
+
+        assert_eq!(
+            shared.handle_line(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#),
+            None
+        );
+        assert_eq!(buffer.messages()[0]["result"]["protocolVersion"], PROTOCOL_VERSION);
+
+        assert_eq!(
+            shared.handle_line(r#"{"jsonrpc":"2.0","id":2,"method":"session/prompt","params":{"text":"hi"}}"#),
+            Some(Incoming::Prompt("hi".to_string()))
+        );
+        assert_eq!(buffer.messages(), vec![
+            json!({ "jsonrpc": "2.0", "id": 2, "result": {} })
+        ]);
+
+        assert_eq!(shared.handle_line(r#"{"jsonrpc":"2.0","id":3,"method":"nope"}"#), None);
+        assert_eq!(buffer.messages()[0]["error"]["code"], METHOD_NOT_FOUND);
+
+        assert_eq!(shared.handle_line("not json"), None);
+        assert_eq!(buffer.messages()[0]["error"]["code"], PARSE_ERROR);
+
+        assert_eq!(
+            shared.handle_line(r#"{"jsonrpc":"2.0","id":7,"result":{"decision":"trust"}}"#),
+            Some(Incoming::Response {
+                id: 7,
+                result: Some(json!({ "decision": "trust" }))
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_line() {
+        let buffer = Buffer::default();
+        let shared = Arc::new(Shared::new(Box::new(buffer.clone())));
+        let (sender, receiver) = channel();
+        let mut input = AcpInput::new(shared.clone(), receiver);
+
+        sender.send(Incoming::Prompt("hello".to_string())).unwrap();
+        assert_eq!(input.read_line("> "), Some("hello".to_string()));
+        assert_eq!(buffer.messages(), vec![json!({
+            "jsonrpc": "2.0",
+            "method": "session/awaitingInput",
+            "params": { "prompt": "> " },
+        })]);
+
+        shared.status.lock().unwrap().pending_tools = vec![PendingTool {
+            name: "fs_write".to_string(),
+            awaiting_approval: true,
+        }];
+        // Prompts sent while a tool use awaits approval are kept for later.
+        sender.send(Incoming::Prompt("later".to_string())).unwrap();
+        sender
+            .send(Incoming::Response {
+                id: 1,
+                result: Some(json!({ "decision": "approve" })),
+            })
+            .unwrap();
+        assert_eq!(input.read_line("> "), Some("y".to_string()));
+        let request = &buffer.messages()[0];
+        assert_eq!(request["method"], "session/requestPermission");
+        assert_eq!(request["params"]["tools"][0]["name"], "fs_write");
+
+        shared.status.lock().unwrap().pending_tools.clear();
+        assert_eq!(input.read_line("> "), Some("later".to_string()));
+
+        drop(sender);
+        assert_eq!(input.read_line("> "), None);
+    }
+
+    #[test]
+    fn test_output() {
+        let buffer = Buffer::default();
+        let shared = Arc::new(Shared::new(Box::new(buffer.clone())));
+        let mut output = AcpOutput::new(NotificationWriter {
+            stream: OutputStream::Stdout,
+            shared,
+        });
+        crossterm::execute!(
+            output,
+            crossterm::style::SetForegroundColor(crossterm::style::Color::Green),
+            crossterm::style::Print("Hello"),
+            crossterm::style::ResetColor,
+        )
+        .unwrap();
+        assert_eq!(buffer.messages(), vec![json!({
+            "jsonrpc": "2.0",
+            "method": "session/output",
+            "params": { "stream": "stdout", "text": "Hello" },
+        })]);
+    }
+}
@@ -0,0 +1,888 @@
+//! Socket of an interactive session, through which `q chat send` routes prompts from other
+//! terminals into the session running in the same directory, `q chat control` controls it, and
+//! `q chat watch` follows it.
+//!
+//! Each session listens on `/tmp/qchat-<uid>/<pid>`, in a directory only the user can access, and
+//! registers itself in `<pid>.json` next to it, see [SessionEntry]. Connections from other users
+//! are refused, unless they are allowed by [Setting::ChatSocketAllowedUsers], in which case the
+//! directory and socket are opened up to them.
+//!
+//! Requests and replies are single lines:
+//!
+//! - `CWD` is answered with the working directory of the session.
+//! - `PROMPT <text>` queues a prompt, given as a JSON string. Once the session has responded to it,
+//!   the reply is `RESPONSE <text>`, or `ERROR <message>`, also as JSON strings.
+//! - `PAUSE`, `RESUME`, `CANCEL`, and `COMPACT` control the session, see [Control], and are
+//!   answered with `OK` once received.
+//! - `WATCH` is answered with `OK`, followed by an `EVENT <json>` line for each [SessionEvent] of
+//!   the session until it ends. Nothing else can be sent on the connection.
+//!
+//! [Setting::ChatSocketCommands] restricts the commands that are accepted.
+//!
+//! Sessions that are no longer running are removed from the directory when a session starts, and
+//! by `q agent gc`.
+//!
+//! Queued prompts run before the session next reads input. Since reading input cannot be
+//! interrupted, a session that is waiting for input shows that a prompt was received, which runs
+//! once the current line is submitted.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use clap::ValueEnum;
+use eyre::{
+    Result,
+    bail,
+};
+use rustyline::ExternalPrinter;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use time::OffsetDateTime;
+use tokio::sync::{
+    broadcast,
+    mpsc,
+    oneshot,
+};
+use tokio::task::JoinHandle;
+use tracing::{
+    debug,
+    warn,
+};
+
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+const NOTICE: &str = "Received a prompt from q chat send, press enter to run it.";
+const COMPACT_NOTICE: &str = "Received /compact from q chat control, press enter to run it.";
+
+/// Directory of the sockets of all sessions of the user.
+pub fn socket_dir() -> PathBuf {
+    #[cfg(unix)]
+    return std::env::temp_dir().join(format!("qchat-{}", nix::unistd::getuid()));
+    #[cfg(not(unix))]
+    return std::env::temp_dir().join("qchat");
+}
+
+/// Directory shared by the sessions of all users in earlier versions.
+pub fn legacy_socket_dir() -> PathBuf {
+    std::env::temp_dir().join("qchat")
+}
+
+/// Socket of the session running in this process.
+pub fn socket_path() -> PathBuf {
+    socket_dir().join(std::process::id().to_string())
+}
+
+/// Registration of a session, stored next to its socket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEntry {
+    pub pid: u32,
+    pub cwd: PathBuf,
+    /// Unix timestamp in seconds of when the session started.
+    pub started_at: i64,
+}
+
+/// Who can connect to the socket of a session, and which commands they can send.
+#[derive(Debug, Clone, Default)]
+pub struct SocketPolicy {
+    /// Ids of the users who can connect besides the user, see [Setting::ChatSocketAllowedUsers].
+    pub allowed_users: Vec<u32>,
+    /// Commands that are accepted besides `CWD`, or all of them if `None`, see
+    /// [Setting::ChatSocketCommands].
+    pub commands: Option<Vec<String>>,
+}
+
+impl SocketPolicy {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let allowed_users = settings
+            .get(Setting::ChatSocketAllowedUsers)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|uid| uid.as_u64()?.try_into().ok())
+            .collect();
+        let commands = settings
+            .get(Setting::ChatSocketCommands)
+            .and_then(Value::as_array)
+            .map(|commands| {
+                commands
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_uppercase)
+                    .collect()
+            });
+        Self {
+            allowed_users,
+            commands,
+        }
+    }
+
+    fn allows(&self, command: &str) -> bool {
+        command == "CWD"
+            || self
+                .commands
+                .as_ref()
+                .is_none_or(|commands| commands.iter().any(|allowed| allowed == command))
+    }
+}
+
+/// Commands controlling a session, sent by `q chat control`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Control {
+    /// Pause the session before its next request
+    Pause,
+    /// Resume the paused session
+    Resume,
+    /// Cancel the response or tool execution in progress, like ctrl + c
+    Cancel,
+    /// Compact the history once the session is done responding
+    Compact,
+}
+
+impl Control {
+    fn command(self) -> &'static str {
+        match self {
+            Self::Pause => "PAUSE",
+            Self::Resume => "RESUME",
+            Self::Cancel => "CANCEL",
+            Self::Compact => "COMPACT",
+        }
+    }
+}
+
+/// Events of a session, streamed to `q chat watch`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SessionEvent {
+    /// A prompt was submitted.
+    Prompt { text: String },
+    /// Text of the response, as it streams.
+    ResponseText { text: String },
+    /// The response ended.
+    ResponseEnd,
+    /// A tool is used.
+    ToolUse { name: String },
+    /// A tool use waits for the approval of the user.
+    ToolApproval { name: String },
+    /// A tool use finished.
+    ToolResult { name: String, success: bool },
+    /// An error was shown to the user.
+    Error { message: String },
+}
+
+/// Errors of reaching a session, as opposed to the errors the session replies with.
+#[derive(Debug, thiserror::Error)]
+pub enum SocketError {
+    #[error("No interactive session is running in {}", .0.display())]
+    NoSessionIn(PathBuf),
+    #[error("No interactive session is running with the pid {0}")]
+    NoSessionWithPid(u32),
+}
+
+type Reply = std::result::Result<String, String>;
+
+#[derive(Debug)]
+struct QueuedPrompt {
+    text: String,
+    reply: oneshot::Sender<Reply>,
+}
+
+/// State shared by a session and the task serving its socket.
+struct Shared {
+    cwd: PathBuf,
+    policy: SocketPolicy,
+    prompts: mpsc::UnboundedSender<QueuedPrompt>,
+    controls: mpsc::UnboundedSender<Control>,
+    /// Whether the session is waiting for input.
+    idle: AtomicBool,
+    printer: Mutex<Option<Box<dyn ExternalPrinter + Send>>>,
+    /// Sender of the ctrl + c signals of the session, used for [Control::Cancel].
+    interrupt: Mutex<Option<broadcast::Sender<()>>>,
+    events: Mutex<Option<broadcast::Sender<SessionEvent>>>,
+}
+
+impl Shared {
+    /// Shows `notice` if the session is waiting for input.
+    fn notify(&self, notice: &str) {
+        if self.idle.load(Ordering::Relaxed) {
+            if let Some(printer) = self.printer.lock().ok().as_mut().and_then(|printer| printer.as_mut()) {
+                let _ = printer.print(notice.to_string());
+            }
+        }
+    }
+}
+
+/// The listening socket of a session.
+pub struct AgentSocket {
+    path: PathBuf,
+    prompts: mpsc::UnboundedReceiver<QueuedPrompt>,
+    controls: mpsc::UnboundedReceiver<Control>,
+    /// Reply to the prompt being run.
+    pending_reply: Option<oneshot::Sender<Reply>>,
+    shared: Arc<Shared>,
+    handle: JoinHandle<()>,
+}
+
+impl std::fmt::Debug for AgentSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AgentSocket")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AgentSocket {
+    /// Starts listening on the socket of this process, for the session running in `cwd`.
+    pub fn bind(cwd: PathBuf, policy: SocketPolicy) -> Result<Self> {
+        Self::bind_at(socket_path(), cwd, policy)
+    }
+
+    #[cfg(unix)]
+    fn bind_at(path: PathBuf, cwd: PathBuf, policy: SocketPolicy) -> Result<Self> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let shared = !policy.allowed_users.is_empty();
+        if let Some(parent) = path.parent() {
+            create_socket_dir(parent, shared)?;
+            let removed = gc(parent);
+            if !removed.is_empty() {
+                debug!(?removed, "removed the sockets of sessions that are no longer running");
+            }
+        }
+        // A socket left behind by a process that had the same pid.
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        let mode = if shared { 0o666 } else { 0o600 };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+        let entry = SessionEntry {
+            pid: std::process::id(),
+            cwd: cwd.clone(),
+            started_at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+        std::fs::write(path.with_extension("json"), serde_json::to_vec(&entry)?)?;
+
+        let (prompt_sender, prompts) = mpsc::unbounded_channel();
+        let (control_sender, controls) = mpsc::unbounded_channel();
+        let shared = Arc::new(Shared {
+            cwd,
+            policy,
+            prompts: prompt_sender,
+            controls: control_sender,
+            idle: AtomicBool::new(false),
+            printer: Mutex::new(None),
+            interrupt: Mutex::new(None),
+            events: Mutex::new(None),
+        });
+        let handle = tokio::spawn(listen(listener, shared.clone()));
+
+        Ok(Self {
+            path,
+            prompts,
+            controls,
+            pending_reply: None,
+            shared,
+            handle,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn bind_at(_path: PathBuf, _cwd: PathBuf, _policy: SocketPolicy) -> Result<Self> {
+        bail!("Sessions can only receive prompts from q chat send on unix platforms")
+    }
+
+    /// Sets the printer used to show that a prompt was received while the session is waiting
+    /// for input.
+    pub fn set_printer(&self, printer: Box<dyn ExternalPrinter + Send>) {
+        if let Ok(mut current) = self.shared.printer.lock() {
+            *current = Some(printer);
+        }
+    }
+
+    /// Sets the sender of the ctrl + c signals of the session, through which [Control::Cancel]
+    /// interrupts it.
+    pub fn set_interrupt(&self, interrupt: broadcast::Sender<()>) {
+        if let Ok(mut current) = self.shared.interrupt.lock() {
+            *current = Some(interrupt);
+        }
+    }
+
+    /// Sets the sender of the events of the session, which are streamed to `q chat watch`.
+    pub fn set_events(&self, events: broadcast::Sender<SessionEvent>) {
+        if let Ok(mut current) = self.shared.events.lock() {
+            *current = Some(events);
+        }
+    }
+
+    pub fn set_idle(&self, idle: bool) {
+        self.shared.idle.store(idle, Ordering::Relaxed);
+    }
+
+    /// Returns the next received control, other than [Control::Cancel], which is sent as ctrl + c.
+    pub fn next_control(&mut self) -> Option<Control> {
+        self.controls.try_recv().ok()
+    }
+
+    /// Waits for the next received control, see [Self::next_control].
+    pub async fn wait_control(&mut self) -> Option<Control> {
+        self.controls.recv().await
+    }
+
+    /// Returns the next queued prompt, whose reply is sent with [Self::reply].
+    pub fn next_prompt(&mut self) -> Option<String> {
+        let prompt = self.prompts.try_recv().ok()?;
+        self.pending_reply = Some(prompt.reply);
+        Some(prompt.text)
+    }
+
+    /// Replies to the prompt being run, if any.
+    pub fn reply(&mut self, reply: Reply) {
+        if let Some(sender) = self.pending_reply.take() {
+            // The sender may have given up waiting.
+            let _ = sender.send(reply);
+        }
+    }
+}
+
+impl Drop for AgentSocket {
+    fn drop(&mut self) {
+        self.handle.abort();
+        let _ = std::fs::remove_file(&self.path);
+        let _ = std::fs::remove_file(self.path.with_extension("json"));
+    }
+}
+
+/// Creates the socket directory `dir`, only accessible by the user unless `shared`, failing if it
+/// belongs to another user.
+#[cfg(unix)]
+fn create_socket_dir(dir: &Path, shared: bool) -> Result<()> {
+    use std::os::unix::fs::{
+        MetadataExt,
+        PermissionsExt,
+    };
+
+    std::fs::create_dir_all(dir)?;
+    let metadata = std::fs::metadata(dir)?;
+    if metadata.uid() != nix::unistd::getuid().as_raw() {
+        bail!("{} belongs to another user", dir.display());
+    }
+    // Other users allowed to connect need to find the sockets, but never to create files.
+    let mode = if shared { 0o755 } else { 0o700 };
+    if metadata.permissions().mode() & 0o777 != mode {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+/// Returns the sessions registered in `dir`, and whether each is still running.
+pub fn sessions(dir: &Path) -> Vec<(SessionEntry, bool)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut sessions = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| serde_json::from_slice::<SessionEntry>(&std::fs::read(entry.path()).ok()?).ok())
+        .map(|session| {
+            let running = is_running(dir, session.pid);
+            (session, running)
+        })
+        .collect::<Vec<_>>();
+    sessions.sort_by_key(|(session, _)| session.started_at);
+    sessions
+}
+
+/// Removes the sockets and registrations in `dir` of the sessions that are no longer running,
+/// returning the removed files.
+pub fn gc(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut removed = Vec::new();
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let Some(pid) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid != std::process::id() && !is_running(dir, pid) && std::fs::remove_file(&path).is_ok() {
+            removed.push(path);
+        }
+    }
+    removed.sort();
+    removed
+}
+
+/// Whether the session of `pid` in `dir` is running: the process exists, and its socket accepts
+/// connections, since the pid may have been reused.
+#[cfg(unix)]
+fn is_running(dir: &Path, pid: u32) -> bool {
+    let Ok(raw_pid) = i32::try_from(pid) else {
+        return false;
+    };
+    let exists = match nix::sys::signal::kill(nix::unistd::Pid::from_raw(raw_pid), None) {
+        Ok(()) | Err(nix::errno::Errno::EPERM) => true,
+        Err(_) => false,
+    };
+    exists && std::os::unix::net::UnixStream::connect(dir.join(pid.to_string())).is_ok()
+}
+
+#[cfg(not(unix))]
+fn is_running(_dir: &Path, _pid: u32) -> bool {
+    false
+}
+
+#[cfg(unix)]
+async fn listen(listener: tokio::net::UnixListener, shared: Arc<Shared>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                warn!(?err, "failed to accept a connection on the agent socket");
+                continue;
+            },
+        };
+        let uid = stream.peer_cred().map(|cred| cred.uid());
+        if !uid
+            .as_ref()
+            .is_ok_and(|uid| *uid == nix::unistd::getuid().as_raw() || shared.policy.allowed_users.contains(uid))
+        {
+            warn!(?uid, "refused a connection on the agent socket from another user");
+            tokio::spawn(refuse(stream));
+            continue;
+        }
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve(stream, &shared).await {
+                debug!(?err, "error serving a connection on the agent socket");
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn refuse(mut stream: tokio::net::UnixStream) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let message = "Permission denied: the session belongs to another user";
+    stream.write_all(error_reply(message)?.as_bytes()).await?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn error_reply(message: &str) -> Result<String> {
+    Ok(format!("ERROR {}\n", serde_json::to_string(message)?))
+}
+
+#[cfg(unix)]
+async fn serve(stream: tokio::net::UnixStream, shared: &Shared) -> Result<()> {
+    use tokio::io::{
+        AsyncBufReadExt,
+        AsyncWriteExt,
+        BufReader,
+    };
+
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+    let (command, argument) = line.trim_end().split_once(' ').unwrap_or((line.trim_end(), ""));
+    let control = Control::value_variants()
+        .iter()
+        .copied()
+        .find(|control| control.command() == command);
+
+    let reply = match (command, control) {
+        ("CWD", _) => format!("{}\n", shared.cwd.display()),
+        ("PROMPT" | "WATCH", _) | (_, Some(_)) if !shared.policy.allows(command) => error_reply(&format!(
+            "{command} is disabled by {}",
+            Setting::ChatSocketCommands.as_ref()
+        ))?,
+        ("PROMPT", _) => {
+            let text = serde_json::from_str::<String>(argument)?;
+            let (reply, receiver) = oneshot::channel();
+            shared.prompts.send(QueuedPrompt { text, reply })?;
+            shared.notify(NOTICE);
+            match receiver.await {
+                Ok(Ok(response)) => format!("RESPONSE {}\n", serde_json::to_string(&response)?),
+                Ok(Err(message)) => error_reply(&message)?,
+                Err(_) => error_reply("The session ended")?,
+            }
+        },
+        ("WATCH", _) => {
+            let events = shared.events.lock().ok().and_then(|events| events.clone());
+            let Some(events) = events else {
+                return Ok(writer
+                    .write_all(error_reply("The session cannot be watched")?.as_bytes())
+                    .await?);
+            };
+            let mut events = events.subscribe();
+            writer.write_all(b"OK\n").await?;
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        writer
+                            .write_all(format!("EVENT {}\n", serde_json::to_string(&event)?).as_bytes())
+                            .await?;
+                    },
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!(skipped, "a watcher of the session fell behind");
+                    },
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                }
+            }
+        },
+        (_, Some(Control::Cancel)) => {
+            let interrupt = shared.interrupt.lock().ok().and_then(|interrupt| interrupt.clone());
+            match interrupt {
+                _ if shared.idle.load(Ordering::Relaxed) => error_reply("The session is waiting for input")?,
+                Some(interrupt) if interrupt.send(()).is_ok() => "OK\n".to_string(),
+                _ => error_reply("The session cannot be cancelled")?,
+            }
+        },
+        (_, Some(control)) => {
+            shared.controls.send(control)?;
+            if control == Control::Compact {
+                shared.notify(COMPACT_NOTICE);
+            }
+            "OK\n".to_string()
+        },
+        (_, None) => error_reply(&format!("Unknown command {command}"))?,
+    };
+    writer.write_all(reply.as_bytes()).await?;
+    Ok(())
+}
+
+/// Directories of the sessions that can be reached: the user's first, then those of the other
+/// users, which only accept connections from the users they are shared with.
+fn session_dirs() -> Vec<PathBuf> {
+    let own = socket_dir();
+    let shared = std::fs::read_dir(std::env::temp_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("qchat-"))
+        .map(|entry| entry.path())
+        .filter(|dir| *dir != own)
+        .collect::<Vec<_>>();
+    std::iter::once(own).chain(shared).collect()
+}
+
+/// Sends `prompt` to the session running in `cwd`, returning its response.
+pub async fn send(cwd: &Path, prompt: &str) -> Result<String> {
+    send_in(&session_dirs(), cwd, prompt).await
+}
+
+/// Sends `control` to the session running in `cwd`.
+pub async fn control(cwd: &Path, control: Control) -> Result<()> {
+    control_in(&session_dirs(), cwd, control).await
+}
+
+async fn send_in(dirs: &[PathBuf], cwd: &Path, prompt: &str) -> Result<String> {
+    let reply = request_in(dirs, cwd, &format!("PROMPT {}", serde_json::to_string(prompt)?)).await?;
+    match reply.strip_prefix("RESPONSE ") {
+        Some(response) => Ok(serde_json::from_str(response)?),
+        None => bail!("Unexpected reply from the session: {reply}"),
+    }
+}
+
+async fn control_in(dirs: &[PathBuf], cwd: &Path, control: Control) -> Result<()> {
+    let reply = request_in(dirs, cwd, control.command()).await?;
+    match reply.as_str() {
+        "OK" => Ok(()),
+        _ => bail!("Unexpected reply from the session: {reply}"),
+    }
+}
+
+/// Streams the events of the session with the pid `target`, or listening on the socket at the
+/// path `target`, to `on_event` until it ends.
+pub async fn watch(target: &str, on_event: impl FnMut(SessionEvent) -> Result<()>) -> Result<()> {
+    watch_in(&session_dirs(), target, on_event).await
+}
+
+#[cfg(unix)]
+async fn watch_in(dirs: &[PathBuf], target: &str, mut on_event: impl FnMut(SessionEvent) -> Result<()>) -> Result<()> {
+    use tokio::io::{
+        AsyncBufReadExt,
+        AsyncWriteExt,
+        BufReader,
+    };
+
+    let path = match target.parse::<u32>() {
+        Ok(pid) => match dirs
+            .iter()
+            .map(|dir| dir.join(pid.to_string()))
+            .find(|path| path.exists())
+        {
+            Some(path) => path,
+            None => return Err(SocketError::NoSessionWithPid(pid).into()),
+        },
+        Err(_) => PathBuf::from(target),
+    };
+    let mut stream = match tokio::net::UnixStream::connect(&path).await {
+        Ok(stream) => stream,
+        Err(err) => bail!("Failed to connect to the session at {}: {err}", path.display()),
+    };
+    stream.write_all(b"WATCH\n").await?;
+
+    let mut lines = BufReader::new(stream).lines();
+    match lines.next_line().await?.as_deref() {
+        Some("OK") => (),
+        Some(reply) => match reply.strip_prefix("ERROR ") {
+            Some(message) => bail!("{}", serde_json::from_str::<String>(message)?),
+            None => bail!("Unexpected reply from the session: {reply}"),
+        },
+        None => bail!("The session ended"),
+    }
+    while let Some(line) = lines.next_line().await? {
+        match line.strip_prefix("EVENT ").map(serde_json::from_str::<SessionEvent>) {
+            Some(Ok(event)) => on_event(event)?,
+            // Events of later versions.
+            _ => debug!(?line, "ignoring an unknown event"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn watch_in(_dirs: &[PathBuf], _target: &str, _on_event: impl FnMut(SessionEvent) -> Result<()>) -> Result<()> {
+    bail!("q chat watch is only supported on unix platforms")
+}
+
+/// Sends a request line to the most recent session running in `cwd` in any of `dirs`, returning
+/// the reply unless it is an error.
+#[cfg(unix)]
+async fn request_in(dirs: &[PathBuf], cwd: &Path, line: &str) -> Result<String> {
+    for dir in dirs {
+        let Some(mut stream) = find_session(dir, cwd).await else {
+            continue;
+        };
+        let reply = request(&mut stream, line).await?;
+        if let Some(message) = reply.strip_prefix("ERROR ") {
+            bail!("{}", serde_json::from_str::<String>(message)?);
+        }
+        return Ok(reply);
+    }
+    Err(SocketError::NoSessionIn(cwd.to_path_buf()).into())
+}
+
+#[cfg(not(unix))]
+async fn request_in(_dirs: &[PathBuf], _cwd: &Path, _line: &str) -> Result<String> {
+    bail!("Sessions can only be reached by q chat send and q chat control on unix platforms")
+}
+
+/// Connects to the socket of the most recent session running in `cwd`.
+#[cfg(unix)]
+async fn find_session(dir: &Path, cwd: &Path) -> Option<tokio::net::UnixStream> {
+    let mut sockets = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect::<Vec<_>>();
+    sockets.sort_by(|a, b| b.0.cmp(&a.0));
+
+    for (_, path) in sockets {
+        // Sockets of sessions that are no longer running refuse connections.
+        let Ok(mut stream) = tokio::net::UnixStream::connect(&path).await else {
+            continue;
+        };
+        let session_cwd = request(&mut stream, "CWD").await.ok();
+        if session_cwd.is_some_and(|session_cwd| Path::new(&session_cwd) == cwd) {
+            return tokio::net::UnixStream::connect(&path).await.ok();
+        }
+    }
+    None
+}
+
+/// Sends a request line, returning the reply without its line ending.
+#[cfg(unix)]
+async fn request(stream: &mut tokio::net::UnixStream, line: &str) -> Result<String> {
+    use tokio::io::{
+        AsyncBufReadExt,
+        AsyncWriteExt,
+        BufReader,
+    };
+
+    stream.write_all(format!("{line}\n").as_bytes()).await?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).await?;
+    Ok(reply.trim_end_matches('\n').to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = PathBuf::from("/projects/app");
+        let mut socket = AgentSocket::bind_at(dir.path().join("1"), cwd.clone(), SocketPolicy::default()).unwrap();
+
+        let client = {
+            let dirs = [dir.path().to_path_buf()];
+            let cwd = cwd.clone();
+            tokio::spawn(async move { send_in(&dirs, &cwd, "what changed?").await })
+        };
+        let prompt = loop {
+            if let Some(prompt) = socket.next_prompt() {
+                break prompt;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        assert_eq!(prompt, "what changed?");
+        socket.reply(Ok("Nothing\nat all".to_string()));
+        assert_eq!(client.await.unwrap().unwrap(), "Nothing\nat all");
+
+        let err = send_in(&[dir.path().to_path_buf()], Path::new("/elsewhere"), "hi")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SocketError>(),
+            Some(SocketError::NoSessionIn(cwd)) if cwd == Path::new("/elsewhere")
+        ));
+
+        drop(socket);
+        assert!(!dir.path().join("1").exists());
+        assert!(!dir.path().join("1.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_gc() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid = std::process::id().to_string();
+        let cwd = PathBuf::from("/projects/app");
+        // A pid above the maximum on linux, so that it is never running.
+        std::fs::write(dir.path().join("4194305"), "").unwrap();
+        std::fs::write(dir.path().join("4194305.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("notes"), "").unwrap();
+
+        let socket = AgentSocket::bind_at(dir.path().join(&pid), cwd.clone(), SocketPolicy::default()).unwrap();
+        assert!(!dir.path().join("4194305").exists());
+        assert!(!dir.path().join("4194305.json").exists());
+        assert!(dir.path().join("notes").exists());
+
+        let sessions = sessions(dir.path());
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].0.cwd, cwd);
+        assert!(sessions[0].1);
+        assert!(gc(dir.path()).is_empty());
+
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(dir.path().metadata().unwrap().permissions().mode() & 0o777, 0o700);
+        drop(socket);
+    }
+
+    #[tokio::test]
+    async fn test_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = |path: &Path| path.metadata().unwrap().permissions().mode() & 0o777;
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = PathBuf::from("/projects/app");
+
+        let socket = AgentSocket::bind_at(dir.path().join("1"), cwd.clone(), SocketPolicy::default()).unwrap();
+        assert_eq!(mode(dir.path()), 0o700);
+        assert_eq!(mode(&dir.path().join("1")), 0o600);
+        drop(socket);
+
+        let socket = AgentSocket::bind_at(dir.path().join("1"), cwd.clone(), SocketPolicy {
+            allowed_users: vec![4242],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(mode(dir.path()), 0o755);
+        assert_eq!(mode(&dir.path().join("1")), 0o666);
+        drop(socket);
+    }
+
+    #[tokio::test]
+    async fn test_control() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = [dir.path().to_path_buf()];
+        let cwd = PathBuf::from("/projects/app");
+        let mut socket = AgentSocket::bind_at(dir.path().join("1"), cwd.clone(), SocketPolicy {
+            commands: Some(vec!["PAUSE".to_string(), "CANCEL".to_string()]),
+            ..Default::default()
+        })
+        .unwrap();
+
+        control_in(&dirs, &cwd, Control::Pause).await.unwrap();
+        assert_eq!(socket.next_control(), Some(Control::Pause));
+        assert_eq!(socket.next_control(), None);
+
+        let err = control_in(&dirs, &cwd, Control::Compact).await.unwrap_err();
+        assert!(err.to_string().contains("COMPACT is disabled"));
+        let err = send_in(&dirs, &cwd, "hi").await.unwrap_err();
+        assert!(err.to_string().contains("PROMPT is disabled"));
+
+        let (interrupt, mut interrupted) = broadcast::channel(1);
+        socket.set_interrupt(interrupt);
+        control_in(&dirs, &cwd, Control::Cancel).await.unwrap();
+        assert!(interrupted.try_recv().is_ok());
+        assert_eq!(socket.next_control(), None);
+
+        socket.set_idle(true);
+        let err = control_in(&dirs, &cwd, Control::Cancel).await.unwrap_err();
+        assert!(err.to_string().contains("waiting for input"));
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = [dir.path().to_path_buf()];
+        let socket =
+            AgentSocket::bind_at(dir.path().join("1"), PathBuf::from("/projects/app"), Default::default()).unwrap();
+
+        let err = watch_in(&dirs, "1", |_| Ok(())).await.unwrap_err();
+        assert!(err.to_string().contains("cannot be watched"));
+        let err = watch_in(&dirs, "2", |_| Ok(())).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SocketError>(),
+            Some(SocketError::NoSessionWithPid(2))
+        ));
+
+        let (events, _) = broadcast::channel(16);
+        socket.set_events(events.clone());
+        let (sender, mut watched) = mpsc::unbounded_channel();
+        let watcher = tokio::spawn(async move { watch_in(&dirs, "1", |event| Ok(sender.send(event)?)).await });
+        while events.receiver_count() == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let sent = [
+            SessionEvent::Prompt { text: "hi".to_string() },
+            SessionEvent::ResponseText {
+                text: "Hello".to_string(),
+            },
+            SessionEvent::ResponseEnd,
+            SessionEvent::ToolUse {
+                name: "fs_read".to_string(),
+            },
+        ];
+        for event in &sent {
+            events.send(event.clone()).unwrap();
+        }
+        for event in sent {
+            assert_eq!(watched.recv().await, Some(event));
+        }
+        watcher.abort();
+    }
+}
@@ -0,0 +1,210 @@
+use serde::Serialize;
+use sha2::{
+    Digest,
+    Sha256,
+};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tracing::warn;
+
+use super::tools::{
+    PermissionPath,
+    QueuedTool,
+};
+use super::{
+    ConversationState,
+    tool_stats,
+};
+use crate::os::Os;
+
+/// How the use of a tool was approved or refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ApprovalDecision {
+    /// Allowed without prompting, by the agent's permissions or because all tools are trusted.
+    Trusted,
+    /// Approved by the user when prompted.
+    Approved,
+    /// Refused by the user when prompted.
+    Rejected,
+    /// Refused by the agent's tool settings or workspace boundary, without prompting.
+    Denied,
+}
+
+/// The outcome of a tool use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExitStatus {
+    Success,
+    Error,
+    NotExecuted,
+}
+
+/// A line of the audit log configured with the `auditLog` field of an agent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// RFC 3339 timestamp of when the record was written.
+    pub timestamp: String,
+    pub conversation_id: String,
+    pub agent: String,
+    pub tool_name: String,
+    pub tool_use_id: String,
+    /// Hex encoded SHA-256 hash of the arguments of the tool use, as sent by the model.
+    pub args_sha256: String,
+    pub decision: ApprovalDecision,
+    pub status: ExitStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Absolute paths the tool read or wrote, or passed as arguments to a command.
+    pub files: Vec<String>,
+}
+
+impl AuditRecord {
+    pub fn new(
+        os: &Os,
+        conversation_id: &str,
+        agent: &str,
+        tool: &QueuedTool,
+        decision: ApprovalDecision,
+        status: ExitStatus,
+    ) -> Self {
+        Self {
+            timestamp: OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default(),
+            conversation_id: conversation_id.to_string(),
+            agent: agent.to_string(),
+            tool_name: tool.name.clone(),
+            tool_use_id: tool.id.clone(),
+            args_sha256: tool.args_hash.clone(),
+            decision,
+            status,
+            duration_ms: None,
+            files: tool
+                .tool
+                .paths()
+                .into_iter()
+                .map(|path| {
+                    PermissionPath::new(os, path, &[])
+                        .resolved()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the hex encoded SHA-256 hash of the JSON encoding of `args`.
+pub fn args_hash(args: &serde_json::Value) -> String {
+    hex::encode(Sha256::digest(args.to_string().as_bytes()))
+}
+
+/// Records the use of `tool` in the statistics of the project, and in the audit log of the active
+/// agent if it has one.
+pub async fn record_tool_use(
+    os: &Os,
+    conversation: &ConversationState,
+    tool: &QueuedTool,
+    decision: ApprovalDecision,
+    status: ExitStatus,
+    duration_ms: Option<u64>,
+) {
+    tool_stats::record_tool_use(os, &tool.name, decision, status, duration_ms);
+    let Some(agent) = conversation.agents.get_active() else {
+        return;
+    };
+    let Some(path) = &agent.audit_log else {
+        return;
+    };
+    let mut record = AuditRecord::new(os, conversation.conversation_id(), &agent.name, tool, decision, status);
+    record.duration_ms = duration_ms;
+    append_record(os, path, &record).await;
+}
+
+/// Appends `record` as a line to the audit log at `path`. Relative paths are resolved from the
+/// current working directory.
+///
+/// Failing to write the log is not fatal to the session, so errors are only logged.
+pub async fn append_record(os: &Os, path: &str, record: &AuditRecord) {
+    let path = PermissionPath::new(os, path, &[]).resolved().to_path_buf();
+    let mut line = match serde_json::to_string(record) {
+        Ok(line) => line,
+        Err(err) => {
+            warn!(?err, "failed to serialize audit record");
+            return;
+        },
+    };
+    line.push('\n');
+
+    if let Some(parent) = path.parent() {
+        if let Err(err) = os.fs.create_dir_all(parent).await {
+            warn!(?err, ?path, "failed to create the directory of the audit log");
+            return;
+        }
+    }
+    if let Err(err) = os.fs.append(&path, line).await {
+        warn!(?err, ?path, "failed to write to the audit log");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::tools::Tool;
+    use crate::cli::chat::tools::fs_write::FsWrite;
+
+    #[tokio::test]
+    async fn test_append_record() {
+        let os = Os::new().await.unwrap();
+        let args = serde_json::json!({ "command": "create", "path": "/src/main.rs", "file_text": "" });
+        let tool = QueuedTool {
+            id: "tool_id".to_string(),
+            name: "fs_write".to_string(),
+            accepted: true,
+            tool: Tool::FsWrite(serde_json::from_value::<FsWrite>(args.clone()).unwrap()),
+            args_hash: args_hash(&args),
+            approval: Some(ApprovalDecision::Approved),
+        };
+
+        let mut record = AuditRecord::new(
+            &os,
+            "conv_id",
+            "default",
+            &tool,
+            ApprovalDecision::Approved,
+            ExitStatus::Success,
+        );
+        record.duration_ms = Some(12);
+        assert_eq!(record.files, vec!["/src/main.rs".to_string()]);
+        assert_eq!(record.args_sha256.len(), 64);
+
+        append_record(&os, "/logs/audit.jsonl", &record).await;
+        append_record(
+            &os,
+            "/logs/audit.jsonl",
+            &AuditRecord::new(
+                &os,
+                "conv_id",
+                "default",
+                &tool,
+                ApprovalDecision::Denied,
+                ExitStatus::NotExecuted,
+            ),
+        )
+        .await;
+
+        let log = os.fs.read_to_string("/logs/audit.jsonl").await.unwrap();
+        let lines = log
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["toolName"], "fs_write");
+        assert_eq!(lines[0]["decision"], "approved");
+        assert_eq!(lines[0]["status"], "success");
+        assert_eq!(lines[0]["durationMs"], 12);
+        assert_eq!(lines[1]["decision"], "denied");
+        assert_eq!(lines[1]["status"], "notExecuted");
+        assert!(lines[1].get("durationMs").is_none());
+    }
+}
@@ -0,0 +1,299 @@
+//! Lexical index of the workspace, from which auto-context selects the files most relevant to each
+//! prompt.
+//!
+//! The index is stored under `.amazonq/index` of the workspace and updated incrementally: files
+//! whose modification time and size are unchanged are not read again, and files whose content
+//! hash is unchanged are not tokenized again. Files are ranked with BM25 over the terms of their
+//! content and path.
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::time::UNIX_EPOCH;
+
+use eyre::Result;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+use tracing::warn;
+use walkdir::{
+    DirEntry,
+    WalkDir,
+};
+
+use crate::os::Os;
+
+/// Number of files selected for each prompt.
+pub const AUTO_CONTEXT_TOP_K: usize = 5;
+/// Files larger than this are not indexed.
+pub const MAX_FILE_SIZE: u64 = 256 * 1024;
+/// Files beyond this number are not indexed.
+const MAX_FILES: usize = 20_000;
+/// Directories of dependencies and build output, which are not indexed.
+pub const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "build", "dist", "vendor", "__pycache__"];
+/// How many times more a term in the path of a file counts than a term in its content.
+const PATH_TERM_WEIGHT: u32 = 3;
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+/// Path of the index of the workspace at `root`.
+pub fn index_path(root: &Path) -> PathBuf {
+    root.join(".amazonq").join("index").join("files.json")
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WorkspaceIndex {
+    /// Indexed files by their path relative to the workspace.
+    files: HashMap<String, IndexedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedFile {
+    modified: u64,
+    size: u64,
+    hash: String,
+    terms: HashMap<String, u32>,
+    len: u32,
+}
+
+impl WorkspaceIndex {
+    /// Loads the index of the workspace at `root`, which is empty if it does not exist yet.
+    pub async fn load(os: &Os, root: &Path) -> Self {
+        let path = index_path(root);
+        if !os.fs.exists(&path) {
+            return Self::default();
+        }
+        match os.fs.read(&path).await.map(|content| serde_json::from_slice(&content)) {
+            Ok(Ok(index)) => index,
+            Ok(Err(err)) => {
+                warn!(?err, "discarding the invalid index at {}", path.display());
+                Self::default()
+            },
+            Err(err) => {
+                warn!(?err, "failed to read the index at {}", path.display());
+                Self::default()
+            },
+        }
+    }
+
+    pub async fn save(&self, os: &Os, root: &Path) -> Result<()> {
+        let path = index_path(root);
+        if let Some(parent) = path.parent() {
+            os.fs.create_dir_all(parent).await?;
+        }
+        os.fs.write(&path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+
+    /// Brings the index up to date with the files of the workspace at `root`, returning the number
+    /// of files that were indexed again.
+    pub fn update(&mut self, os: &Os, root: &Path) -> usize {
+        let real_root = os.fs.chroot_path(root);
+        let mut seen = HashSet::new();
+        let mut updated = 0;
+
+        for entry in workspace_files(&real_root) {
+            let Ok(relative) = entry.path().strip_prefix(&real_root) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().to_string();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.len() > MAX_FILE_SIZE {
+                continue;
+            }
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs());
+
+            if let Some(file) = self.files.get(&relative) {
+                if file.modified == modified && file.size == metadata.len() {
+                    seen.insert(relative);
+                    continue;
+                }
+            }
+
+            // Binary files are not indexed.
+            let Ok(content) = std::fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            if content.contains('\0') {
+                continue;
+            }
+            let hash = hex::encode(Sha256::digest(content.as_bytes()));
+            match self.files.get_mut(&relative) {
+                Some(file) if file.hash == hash => {
+                    file.modified = modified;
+                    file.size = metadata.len();
+                },
+                _ => {
+                    let mut terms = HashMap::new();
+                    for term in tokenize(&content) {
+                        *terms.entry(term).or_insert(0) += 1;
+                    }
+                    for term in tokenize(&relative) {
+                        *terms.entry(term).or_insert(0) += PATH_TERM_WEIGHT;
+                    }
+                    let len = terms.values().sum();
+                    self.files.insert(relative.clone(), IndexedFile {
+                        modified,
+                        size: metadata.len(),
+                        hash,
+                        terms,
+                        len,
+                    });
+                    updated += 1;
+                },
+            }
+            seen.insert(relative);
+        }
+
+        self.files.retain(|path, _| seen.contains(path));
+        updated
+    }
+
+    /// Returns the paths, relative to the workspace, of at most `k` files relevant to `query`,
+    /// most relevant first.
+    pub fn search(&self, query: &str, k: usize) -> Vec<String> {
+        let terms = tokenize(query).collect::<HashSet<_>>();
+        if terms.is_empty() || self.files.is_empty() {
+            return Vec::new();
+        }
+
+        let count = self.files.len() as f64;
+        let average_len = self.files.values().map(|file| file.len as f64).sum::<f64>() / count;
+        let idf = terms
+            .iter()
+            .map(|term| {
+                let df = self.files.values().filter(|file| file.terms.contains_key(term)).count() as f64;
+                (term, ((count - df + 0.5) / (df + 0.5)).ln_1p())
+            })
+            .collect::<Vec<_>>();
+
+        let mut scores = self
+            .files
+            .iter()
+            .filter_map(|(path, file)| {
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * file.len as f64 / average_len.max(1.0));
+                let score = idf
+                    .iter()
+                    .filter_map(|(term, idf)| {
+                        let tf = *file.terms.get(*term)? as f64;
+                        Some(idf * tf * (BM25_K1 + 1.0) / (tf + norm))
+                    })
+                    .sum::<f64>();
+                (score > 0.0).then_some((path, score))
+            })
+            .collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        scores.into_iter().take(k).map(|(path, _)| path.clone()).collect()
+    }
+}
+
+/// Returns the files of the workspace at `root` that are worth indexing, leaving out hidden files
+/// and directories of dependencies and build output.
+pub fn workspace_files(root: &Path) -> impl Iterator<Item = DirEntry> {
+    WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0
+                || !(name.starts_with('.') || entry.file_type().is_dir() && SKIPPED_DIRS.contains(&name.as_ref()))
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .take(MAX_FILES)
+}
+
+/// Splits text into lowercase terms, including the parts of camelCase and snake_case identifiers.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .flat_map(split_camel_case)
+        .filter(|word| word.len() >= 3 && !word.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+}
+
+fn split_camel_case(word: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut previous_lowercase = false;
+    for (i, c) in word.char_indices() {
+        if c.is_uppercase() && previous_lowercase {
+            parts.push(&word[start..i]);
+            start = i;
+        }
+        previous_lowercase = c.is_lowercase() || c.is_ascii_digit();
+    }
+    parts.push(&word[start..]);
+    parts
+}
+
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "with", "that", "this", "from", "are", "was", "how", "what", "where", "why", "does", "can",
+    "you", "not", "but", "all", "any", "use", "into",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("How does parseHTTPRequest handle user_id in the ChatSession?").collect::<Vec<_>>(),
+            vec!["parse", "httprequest", "handle", "user", "chat", "session"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_index() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/ws/src/auth").await.unwrap();
+        os.fs.create_dir_all("/ws/node_modules/dep").await.unwrap();
+        os.fs
+            .write(
+                "/ws/src/auth/token.rs",
+                "fn refresh_token() { /* refreshes the bearer token */ }",
+            )
+            .await
+            .unwrap();
+        os.fs
+            .write("/ws/src/main.rs", "fn main() { run_server(); }")
+            .await
+            .unwrap();
+        os.fs
+            .write("/ws/node_modules/dep/token.js", "token token token")
+            .await
+            .unwrap();
+
+        let root = Path::new("/ws");
+        let mut index = WorkspaceIndex::load(&os, root).await;
+        assert_eq!(index.update(&os, root), 2);
+        assert_eq!(index.search("where is the token refreshed?", 5), vec![
+            "src/auth/token.rs"
+        ]);
+        assert_eq!(index.search("server", 5), vec!["src/main.rs"]);
+        index.save(&os, root).await.unwrap();
+
+        // Unchanged files are not indexed again, and deleted files are removed.
+        let mut index = WorkspaceIndex::load(&os, root).await;
+        os.fs.remove_file("/ws/src/main.rs").await.unwrap();
+        assert_eq!(index.update(&os, root), 0);
+        assert!(index.search("server", 5).is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+
+use clap::{
+    Args,
+    CommandFactory,
+    Subcommand,
+};
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+use serde_json::Value;
+
+use super::SlashCommand;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Separates the turns of an alias, each sent once the response to the previous one is done.
+const TURN_SEPARATOR: &str = ";;";
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    args_conflicts_with_subcommands = true,
+    before_long_help = "Aliases are prompts saved under a name, sent with /NAME. Define them with
+  /alias review = \"Review the staged diff for bugs and style issues, then summarize risks\"
+
+$1 to $9 in the prompt are replaced by the arguments of the alias, and $@ by all of them, e.g.
+/review src/ with the prompt \"Review the changes in $1\". Arguments are appended to prompts
+without any of them.
+
+Separate prompts with ;; to send them one after the other, each once the response to the
+previous one is done. Aliases are stored in the chat.aliases setting."
+)]
+pub struct AliasArgs {
+    #[command(subcommand)]
+    subcommand: Option<AliasSubcommand>,
+    /// Definition of the alias, NAME = PROMPT. Shows the alias when only NAME is given
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    definition: Vec<String>,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum AliasSubcommand {
+    /// List the aliases
+    List,
+    /// Remove aliases
+    Rm {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+}
+
+impl AliasArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let mut aliases = aliases(os);
+        let message = match (self.subcommand, self.definition.as_slice()) {
+            (Some(AliasSubcommand::List), _) | (None, []) => match aliases.is_empty() {
+                true => "No aliases are defined. Define one with /alias NAME = PROMPT".to_string(),
+                false => aliases
+                    .iter()
+                    .map(|(name, turns)| format_alias(name, turns))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+            (Some(AliasSubcommand::Rm { names }), _) => {
+                let (removed, unknown) = names
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|name| aliases.contains_key(name));
+                if !unknown.is_empty() {
+                    return print_error(session, &format!("No alias named {}", unknown.join(", ")));
+                }
+                for name in &removed {
+                    aliases.remove(name);
+                }
+                save(os, &aliases).await?;
+                format!("Removed {}", removed.join(", "))
+            },
+            (None, [name]) if !name.contains('=') => match aliases.get(name) {
+                Some(turns) => format_alias(name, turns),
+                None => return print_error(session, &format!("No alias named {name}")),
+            },
+            (None, definition) => {
+                let (name, turns) = match parse_definition(definition) {
+                    Ok(alias) => alias,
+                    Err(err) => return print_error(session, &err),
+                };
+                if let Err(err) = validate_name(&name) {
+                    return print_error(session, &err);
+                }
+                let message = format!("Saved {}", format_alias(&name, &turns));
+                aliases.insert(name, turns);
+                save(os, &aliases).await?;
+                message
+            },
+        };
+
+        execute!(session.stderr, style::Print(format!("\n{message}\n\n")))?;
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        match &self.subcommand {
+            Some(AliasSubcommand::List) => Some("list"),
+            Some(AliasSubcommand::Rm { .. }) => Some("rm"),
+            None if !self.definition.is_empty() => Some("define"),
+            None => None,
+        }
+    }
+}
+
+/// Returns the aliases stored in [Setting::ChatAliases], with the turns of each. Aliases that are
+/// not a prompt or a list of prompts are ignored.
+pub fn aliases(os: &Os) -> BTreeMap<String, Vec<String>> {
+    let Some(Value::Object(aliases)) = os.database.settings.get(Setting::ChatAliases) else {
+        return BTreeMap::new();
+    };
+    aliases
+        .iter()
+        .filter_map(|(name, value)| {
+            let turns = match value {
+                Value::String(prompt) => vec![prompt.clone()],
+                Value::Array(prompts) => prompts
+                    .iter()
+                    .map(|p| p.as_str().map(str::to_string))
+                    .collect::<Option<_>>()?,
+                _ => return None,
+            };
+            Some((name.clone(), turns))
+        })
+        .collect()
+}
+
+/// Returns the turns of the alias invoked by `input`, e.g. `/review src/`, with its arguments
+/// substituted. Built-in commands take precedence over aliases.
+pub fn expand(os: &Os, input: &str) -> Option<Vec<String>> {
+    let args = shlex::split(input.trim().strip_prefix('/')?)?;
+    let (name, args) = args.split_first()?;
+    if is_command(name) {
+        return None;
+    }
+    let turns = aliases(os).remove(name)?;
+    Some(substitute(&turns, args))
+}
+
+/// Replaces `$1` to `$9` and `$@` in `turns` with `args`. The arguments are appended to the first
+/// turn when no turn refers to them.
+fn substitute(turns: &[String], args: &[String]) -> Vec<String> {
+    let refers_to_args = turns
+        .iter()
+        .any(|turn| turn.contains("$@") || (1..=9).any(|i| turn.contains(&format!("${i}"))));
+    let mut turns = turns
+        .iter()
+        .map(|turn| {
+            let mut turn = turn.replace("$@", &args.join(" "));
+            // In reverse so that $1 does not match the start of a larger number.
+            for i in (1..=9).rev() {
+                turn = turn.replace(&format!("${i}"), args.get(i - 1).map_or("", String::as_str));
+            }
+            turn.trim().to_string()
+        })
+        .collect::<Vec<_>>();
+    if let (false, false, Some(first)) = (refers_to_args, args.is_empty(), turns.first_mut()) {
+        first.push(' ');
+        first.push_str(&args.join(" "));
+    }
+    turns
+}
+
+/// Parses `NAME = PROMPT`, also accepted as `NAME=PROMPT` or `NAME PROMPT`, into the name and the
+/// turns of the alias.
+fn parse_definition(definition: &[String]) -> Result<(String, Vec<String>), String> {
+    let (name, prompt) = match definition {
+        [first, rest @ ..] if first.contains('=') => {
+            let (name, prompt) = first.split_once('=').unwrap_or_default();
+            (name.to_string(), [vec![prompt.to_string()], rest.to_vec()].concat())
+        },
+        [name, eq, rest @ ..] if eq == "=" => (name.clone(), rest.to_vec()),
+        [name, rest @ ..] => (name.clone(), rest.to_vec()),
+        [] => (String::new(), Vec::new()),
+    };
+    let turns = prompt
+        .join(" ")
+        .split(TURN_SEPARATOR)
+        .map(str::trim)
+        .filter(|turn| !turn.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if turns.is_empty() {
+        return Err("Usage: /alias NAME = PROMPT".to_string());
+    }
+    Ok((name.trim().to_string(), turns))
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!(
+            "Invalid alias name {name}. Names start with a letter or digit and contain only letters, digits, hyphens, and underscores"
+        ));
+    }
+    if is_command(name) {
+        return Err(format!("/{name} is a built-in command and cannot be an alias"));
+    }
+    Ok(())
+}
+
+fn is_command(name: &str) -> bool {
+    SlashCommand::command().find_subcommand(name).is_some()
+}
+
+fn format_alias(name: &str, turns: &[String]) -> String {
+    format!("/{name} = {}", turns.join(&format!(" {TURN_SEPARATOR} ")))
+}
+
+async fn save(os: &mut Os, aliases: &BTreeMap<String, Vec<String>>) -> Result<(), ChatError> {
+    let value = aliases
+        .iter()
+        .map(|(name, turns)| {
+            let value = match turns.as_slice() {
+                [prompt] => Value::String(prompt.clone()),
+                turns => turns.iter().cloned().map(Value::String).collect(),
+            };
+            (name.clone(), value)
+        })
+        .collect::<serde_json::Map<_, _>>();
+    os.database
+        .settings
+        .set(Setting::ChatAliases, value)
+        .await
+        .map_err(|err| ChatError::Custom(format!("Failed to save settings: {err}").into()))
+}
+
+fn print_error(session: &mut ChatSession, err: &str) -> Result<ChatState, ChatError> {
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Red),
+        style::Print(format!("\n{err}\n\n")),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().copied().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_definition() {
+        let turns = strings(&["Review the staged diff"]);
+        for definition in [
+            &["review", "=", "Review the staged diff"][..],
+            &["review=Review the staged diff"],
+            &["review", "Review", "the", "staged", "diff"],
+        ] {
+            assert_eq!(
+                parse_definition(&strings(definition)),
+                Ok(("review".to_string(), turns.clone()))
+            );
+        }
+        assert_eq!(
+            parse_definition(&strings(&["ship", "=", "Run the tests ;; Write a commit message"])),
+            Ok((
+                "ship".to_string(),
+                strings(&["Run the tests", "Write a commit message"])
+            ))
+        );
+        assert!(parse_definition(&strings(&["review", "="])).is_err());
+    }
+
+    #[test]
+    fn test_substitute() {
+        let turns = strings(&["Review the changes in $1 for $2", "Summarize $@"]);
+        assert_eq!(substitute(&turns, &strings(&["src/", "bugs"])), vec![
+            "Review the changes in src/ for bugs",
+            "Summarize src/ bugs"
+        ]);
+        assert_eq!(substitute(&turns, &[]), vec!["Review the changes in  for", "Summarize"]);
+        assert_eq!(
+            substitute(&strings(&["Review", "Summarize"]), &strings(&["src/"])),
+            vec!["Review src/", "Summarize"]
+        );
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("review").is_ok());
+        assert!(validate_name("-review").is_err());
+        assert!(validate_name("clear").is_err());
+        assert!(validate_name("profile").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expand() {
+        let mut os = Os::new().await.unwrap();
+        os.database
+            .settings
+            .set(
+                Setting::ChatAliases,
+                json!({ "review": "Review $1", "ship": ["Test", "Commit"], "clear": "Not a command", "bad": 1 }),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(aliases(&os).len(), 3);
+        assert_eq!(
+            expand(&os, "/review 'src/main.rs'"),
+            Some(strings(&["Review src/main.rs"]))
+        );
+        assert_eq!(expand(&os, "/ship"), Some(strings(&["Test", "Commit"])));
+        assert_eq!(expand(&os, "/clear"), None);
+        assert_eq!(expand(&os, "/unknown"), None);
+        assert_eq!(expand(&os, "review"), None);
+    }
+}
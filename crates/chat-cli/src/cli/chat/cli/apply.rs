@@ -0,0 +1,151 @@
+use clap::Args;
+use crossterm::style::{
+    self,
+    Color,
+    Stylize,
+};
+use crossterm::{
+    cursor,
+    execute,
+};
+
+use super::copy::last_code_block;
+use crate::cli::agent::PermissionEvalResult;
+use crate::cli::chat::tools::Tool;
+use crate::cli::chat::tools::fs_write::{
+    CreatePolicy,
+    FsWrite,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct ApplyArgs {
+    /// Number of the code block in the last response, starting at 1
+    index: usize,
+    /// File to write the code block to
+    path: String,
+}
+
+impl ApplyArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let block = match last_code_block(session, self.index) {
+            Ok(block) => block,
+            Err(err) => {
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("\n{err}\n\n")),
+                    style::SetForegroundColor(Color::Reset)
+                )?;
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                });
+            },
+        };
+
+        // Go through fs_write so that the same diff preview and permissions apply as when the
+        // model writes the file.
+        let mut tool = Tool::FsWrite(FsWrite::Create {
+            path: self.path.clone(),
+            file_text: Some(format!("{}\n", block.code)),
+            new_str: None,
+            summary: Some(format!("Apply code block {} of the last response", self.index)),
+            accepted_hunks: None,
+            create_policy: session
+                .conversation
+                .agents
+                .get_active()
+                .map(CreatePolicy::configured)
+                .unwrap_or_default(),
+            roots: session.conversation.workspace_roots().to_vec(),
+        });
+        if let Err(err) = tool.validate(os).await {
+            return Err(ChatError::Custom(
+                format!("Cannot write to {}: {err}", self.path).into(),
+            ));
+        }
+
+        let (permission, outside_workspace) = match session.conversation.agents.get_active() {
+            Some(agent) => (
+                tool.requires_acceptance(os, agent),
+                tool.outside_workspace(os, agent, session.conversation.workspace_roots()),
+            ),
+            None => (PermissionEvalResult::Ask, None),
+        };
+        if permission == PermissionEvalResult::Deny {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!(
+                    "\nWriting to {} is denied by the agent's fs_write settings.\n\n",
+                    self.path
+                )),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        execute!(session.stderr, style::Print("\n"))?;
+        tool.queue_description(os, &mut session.stderr)
+            .await
+            .map_err(|e| ChatError::Custom(format!("failed to print tool, `fs_write`: {e}").into()))?;
+
+        let trusted = (permission == PermissionEvalResult::Allow || session.conversation.agents.trust_all_tools)
+            && outside_workspace.is_none()
+            && !tool.asks_before_replacing(os);
+        if !trusted {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("\nWrite code block {} to {}? ", self.index, self.path)),
+                style::Print("["),
+                style::SetForegroundColor(Color::Green),
+                style::Print("y"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("/"),
+                style::SetForegroundColor(Color::Green),
+                style::Print("n"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("]:\n\n"),
+                style::SetForegroundColor(Color::Reset),
+                cursor::Show,
+            )?;
+
+            let user_input = session
+                .read_user_input("> ".yellow().to_string().as_str(), true)
+                .unwrap_or_default();
+            if !["y", "Y"].contains(&user_input.as_str()) {
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                });
+            }
+        }
+
+        match tool.invoke(os, &mut session.stderr).await {
+            Ok(_) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Green),
+                style::Print(format!("\nWrote code block {} to {}.\n\n", self.index, self.path)),
+                style::SetForegroundColor(Color::Reset)
+            )?,
+            Err(err) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!("\nFailed to write {}: {err}\n\n", self.path)),
+                style::SetForegroundColor(Color::Reset)
+            )?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
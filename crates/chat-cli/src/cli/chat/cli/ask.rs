@@ -0,0 +1,76 @@
+use clap::Args;
+
+use crate::cli::chat::consts::MODEL_OVERRIDE_PREFIX;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct AskArgs {
+    /// Model to send this prompt to, without changing the model of the session
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Prompt to submit
+    #[arg(required = true)]
+    pub prompt: Vec<String>,
+}
+
+impl AskArgs {
+    pub async fn execute(self, _session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        Ok(ChatState::HandleInput {
+            input: self.into_input(),
+        })
+    }
+
+    /// The input submitted for the prompt, with the `@model:` prefix if a model is given.
+    fn into_input(self) -> String {
+        let prompt = self.prompt.join(" ");
+        match self.model {
+            Some(model) => format!("{MODEL_OVERRIDE_PREFIX}{model} {prompt}"),
+            None => prompt,
+        }
+    }
+}
+
+/// Splits an input starting with the `@model:` prefix into the queried model and the prompt.
+pub fn parse_model_override(input: &str) -> Option<(&str, &str)> {
+    let rest = input.trim().strip_prefix(MODEL_OVERRIDE_PREFIX)?;
+    let (query, prompt) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    Some((query, prompt.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model_override() {
+        assert_eq!(
+            parse_model_override("  @model:sonnet explain  this error "),
+            Some(("sonnet", "explain  this error"))
+        );
+        assert_eq!(parse_model_override("@model:sonnet"), Some(("sonnet", "")));
+        assert_eq!(parse_model_override("explain @model:sonnet"), None);
+        assert_eq!(parse_model_override("@models are great"), None);
+    }
+
+    #[test]
+    fn test_ask_input() {
+        let args = AskArgs {
+            model: Some("sonnet".to_string()),
+            prompt: vec!["explain".to_string(), "this".to_string()],
+        };
+        let input = args.into_input();
+        assert_eq!(input, "@model:sonnet explain this");
+        assert_eq!(parse_model_override(&input), Some(("sonnet", "explain this")));
+
+        let args = AskArgs {
+            model: None,
+            prompt: vec!["explain".to_string()],
+        };
+        assert_eq!(args.into_input(), "explain");
+    }
+}
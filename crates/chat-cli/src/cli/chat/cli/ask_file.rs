@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+
+use clap::Args;
+
+use crate::cli::chat::consts::MAX_USER_MESSAGE_SIZE;
+use crate::cli::chat::tools::sanitize_path_tool_arg;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+
+/// Size in bytes of the chunks a file is split into.
+const CHUNK_SIZE: usize = 16_000;
+
+/// Maximum size in bytes of the file content included in the prompt, leaving room for the
+/// question and the rest of the conversation state.
+const MAX_FILE_CONTENT_SIZE: usize = MAX_USER_MESSAGE_SIZE / 2;
+
+#[deny(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct AskFileArgs {
+    /// Path of the file to ask about
+    pub path: String,
+    /// Question about the file
+    #[arg(required = true)]
+    pub question: Vec<String>,
+}
+
+impl AskFileArgs {
+    pub async fn execute(self, os: &Os, _session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        Ok(ChatState::HandleInput {
+            input: self.prompt(os).await?,
+        })
+    }
+
+    /// Returns the prompt asking the question about the content of the file. Files too large to
+    /// be sent whole are split into chunks, of which those most relevant to the question are
+    /// sent.
+    pub async fn prompt(&self, os: &Os) -> Result<String, ChatError> {
+        let path = sanitize_path_tool_arg(os, &self.path);
+        let content = os
+            .fs
+            .read_to_string(&path)
+            .await
+            .map_err(|err| ChatError::Custom(format!("Failed to read {}: {err}", path.display()).into()))?;
+        let question = self.question.join(" ");
+
+        let chunks = chunk(&content, CHUNK_SIZE);
+        let selected = select_chunks(&chunks, &question, MAX_FILE_CONTENT_SIZE);
+        let path = &self.path;
+
+        let mut prompt = format!(
+            "Answer the question below about the file {path}. Reply with only the answer, without using any tools.\n\n"
+        );
+        if selected.len() < chunks.len() {
+            prompt.push_str(&format!(
+                "The file is too large to include whole, so only the {} of its {} lines most relevant to the question are included.\n\n",
+                selected.iter().map(|c| c.end_line - c.start_line + 1).sum::<usize>(),
+                content.lines().count()
+            ));
+        }
+        for chunk in selected {
+            prompt.push_str(&format!(
+                "<file path=\"{path}\" lines=\"{}-{}\">\n{}\n</file>\n",
+                chunk.start_line,
+                chunk.end_line,
+                chunk.text.trim_end_matches('\n')
+            ));
+        }
+        prompt.push_str(&format!("\nQuestion: {question}"));
+
+        Ok(prompt)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Chunk<'a> {
+    /// 1-based line number of the first line of the chunk.
+    start_line: usize,
+    /// 1-based line number of the last line of the chunk.
+    end_line: usize,
+    text: &'a str,
+}
+
+/// Splits `content` into chunks of whole lines of about `size` bytes.
+fn chunk(content: &str, size: usize) -> Vec<Chunk<'_>> {
+    let mut chunks = Vec::new();
+    let (mut start, mut start_line, mut line) = (0, 1, 0);
+    for (offset, _) in content.match_indices('\n') {
+        line += 1;
+        if offset + 1 - start >= size {
+            chunks.push(Chunk {
+                start_line,
+                end_line: line,
+                text: &content[start..=offset],
+            });
+            start = offset + 1;
+            start_line = line + 1;
+        }
+    }
+    if start < content.len() || chunks.is_empty() {
+        chunks.push(Chunk {
+            start_line,
+            end_line: content[start..].lines().count().max(1) + start_line - 1,
+            text: &content[start..],
+        });
+    }
+    chunks
+}
+
+/// Returns the chunks that fit in `budget` bytes in the order of the file, preferring those
+/// sharing the most words with `question`.
+fn select_chunks<'a, 'b>(chunks: &'b [Chunk<'a>], question: &str, budget: usize) -> Vec<&'b Chunk<'a>> {
+    if chunks.iter().map(|c| c.text.len()).sum::<usize>() <= budget {
+        return chunks.iter().collect();
+    }
+
+    let question_words = words(question);
+    let mut ranked = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| (question_words.intersection(&words(chunk.text)).count(), index))
+        .collect::<Vec<_>>();
+    // Ties are broken by position, as the start of a file usually gives the most context.
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut selected = Vec::new();
+    let mut size = 0;
+    for (_, index) in ranked {
+        if size + chunks[index].text.len() <= budget {
+            size += chunks[index].text.len();
+            selected.push(index);
+        }
+    }
+    selected.sort_unstable();
+    selected.into_iter().map(|index| &chunks[index]).collect()
+}
+
+/// Returns the lowercase words of `text` that are long enough to be meaningful.
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.len() > 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk() {
+        let content = "a\nbb\nccc\ndddd\n";
+        let chunks = chunk(content, 5);
+        assert_eq!(chunks, vec![
+            Chunk {
+                start_line: 1,
+                end_line: 2,
+                text: "a\nbb\n",
+            },
+            Chunk {
+                start_line: 3,
+                end_line: 4,
+                text: "ccc\ndddd\n",
+            },
+        ]);
+        assert_eq!(chunks.iter().map(|c| c.text).collect::<String>(), content);
+
+        let chunks = chunk("no trailing newline", 5);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 1));
+    }
+
+    #[test]
+    fn test_select_chunks() {
+        let content = "fn parse_config() {}\nfn render() {}\nfn main() {}\n";
+        let chunks = chunk(content, 1);
+        assert_eq!(select_chunks(&chunks, "anything", 1000).len(), 3);
+
+        let selected = select_chunks(&chunks, "How does render work?", 20);
+        assert_eq!(selected.iter().map(|c| c.start_line).collect::<Vec<_>>(), vec![2]);
+
+        let selected = select_chunks(&chunks, "what does main call after render", 40);
+        assert_eq!(selected.iter().map(|c| c.start_line).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_prompt() {
+        let os = Os::new().await.unwrap();
+        os.fs.write("/notes.txt", "the answer is 42\n").await.unwrap();
+        let args = AskFileArgs {
+            path: "/notes.txt".to_string(),
+            question: vec!["what".to_string(), "is the answer?".to_string()],
+        };
+        let prompt = args.prompt(&os).await.unwrap();
+        assert!(prompt.contains("<file path=\"/notes.txt\" lines=\"1-1\">\nthe answer is 42\n</file>"));
+        assert!(prompt.ends_with("Question: what is the answer?"));
+
+        let args = AskFileArgs {
+            path: "/missing.txt".to_string(),
+            question: vec!["why".to_string()],
+        };
+        assert!(args.prompt(&os).await.is_err());
+    }
+}
@@ -1,4 +1,12 @@
-use clap::Args;
+use clap::{
+    Args,
+    ValueEnum,
+};
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
 
 use crate::cli::chat::consts::MAX_USER_MESSAGE_SIZE;
 use crate::cli::chat::message::UserMessageContent;
@@ -28,6 +36,13 @@ How it works
 • Clears the conversation history to free up space
 • The assistant will reference the summary context in future responses
 
+Strategies (--strategy)
+• summarize: Replace the whole history with a summary (default)
+• sliding-window: Keep the last turns verbatim and summarize the rest.
+  The number of turns kept is set with --messages-to-exclude (default: 3)
+• tools-only: Keep the conversation, but replace tool outputs with a summary of them
+• drop-context: Stop sending context files and hook output, without summarizing
+
 Compaction will be automatically performed whenever the context window overflows.
 To disable this behavior, run: `q settings chat.disableAutoCompaction true`"
 )]
@@ -36,6 +51,9 @@ pub struct CompactArgs {
     prompt: Vec<String>,
     #[arg(long)]
     show_summary: bool,
+    /// How to compact the conversation
+    #[arg(long, value_enum, default_value_t)]
+    strategy: CompactMode,
     /// The number of user and assistant message pairs to exclude from the summarization.
     #[arg(long)]
     messages_to_exclude: Option<usize>,
@@ -51,6 +69,13 @@ pub struct CompactArgs {
 impl CompactArgs {
     pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         let default = CompactStrategy::default();
+        if self.strategy == CompactMode::DropContext {
+            return drop_context(session);
+        }
+        let messages_to_exclude = match self.strategy {
+            CompactMode::SlidingWindow => self.messages_to_exclude.unwrap_or(DEFAULT_SLIDING_WINDOW_TURNS),
+            _ => self.messages_to_exclude.unwrap_or(default.messages_to_exclude),
+        };
         let prompt = if self.prompt.is_empty() {
             None
         } else {
@@ -63,7 +88,8 @@ impl CompactArgs {
 
         session
             .compact_history(os, prompt, self.show_summary, CompactStrategy {
-                messages_to_exclude: self.messages_to_exclude.unwrap_or(default.messages_to_exclude),
+                mode: self.strategy,
+                messages_to_exclude,
                 truncate_large_messages: self.truncate_large_messages.unwrap_or(default.truncate_large_messages),
                 max_message_length: self.max_message_length.map_or(default.max_message_length, |v| {
                     v.clamp(UserMessageContent::TRUNCATED_SUFFIX.len(), MAX_USER_MESSAGE_SIZE)
@@ -73,9 +99,54 @@ impl CompactArgs {
     }
 }
 
+/// Stops sending context files and hook output for the rest of the session.
+fn drop_context(session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    let message = match session.conversation.context_manager.as_mut() {
+        Some(context_manager) if !context_manager.paths.is_empty() || !context_manager.hooks.is_empty() => {
+            let paths = context_manager.paths.len();
+            let hooks = context_manager.hooks.values().map(Vec::len).sum::<usize>();
+            context_manager.clear();
+            context_manager.hooks.clear();
+            context_manager.hook_executor.cache.clear();
+            format!(
+                "\nDropped {paths} context rule(s) and {hooks} hook(s) for this session. Edit the agent config to make this permanent.\n\n"
+            )
+        },
+        _ => "\nThere is no context to drop.\n\n".to_string(),
+    };
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Green),
+        style::Print(message),
+        style::SetForegroundColor(Color::Reset)
+    )?;
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
+
+/// Number of user/assistant pairs kept verbatim by [CompactMode::SlidingWindow] by default.
+const DEFAULT_SLIDING_WINDOW_TURNS: usize = 3;
+
+/// How the conversation history is compacted.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum CompactMode {
+    /// Replace the history with a summary.
+    #[default]
+    Summarize,
+    /// Keep the most recent turns verbatim and replace the rest of the history with a summary.
+    SlidingWindow,
+    /// Keep the history, but replace the outputs of tools with a summary of them.
+    ToolsOnly,
+    /// Drop context files and hook output without summarizing.
+    DropContext,
+}
+
 /// Parameters for performing the history compaction request.
 #[derive(Debug, Copy, Clone)]
 pub struct CompactStrategy {
+    /// How the history is compacted.
+    pub mode: CompactMode,
     /// Number of user/assistant pairs to exclude from the history as part of compaction.
     pub messages_to_exclude: usize,
     /// Whether or not to truncate large messages in the history.
@@ -87,6 +158,7 @@ pub struct CompactStrategy {
 impl Default for CompactStrategy {
     fn default() -> Self {
         Self {
+            mode: Default::default(),
             messages_to_exclude: Default::default(),
             truncate_large_messages: Default::default(),
             max_message_length: MAX_USER_MESSAGE_SIZE,
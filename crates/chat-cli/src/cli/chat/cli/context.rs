@@ -1,6 +1,9 @@
 use std::collections::HashSet;
 
-use clap::Subcommand;
+use clap::{
+    Subcommand,
+    ValueEnum,
+};
 use crossterm::style::{
     Attribute,
     Color,
@@ -11,6 +14,11 @@ use crossterm::{
 };
 
 use crate::cli::chat::consts::CONTEXT_FILES_MAX_SIZE;
+use crate::cli::chat::context::{
+    ContextCommand,
+    directory_context_path,
+    memory_files,
+};
 use crate::cli::chat::token_counter::TokenCounter;
 use crate::cli::chat::util::drop_matched_context_files;
 use crate::cli::chat::{
@@ -31,7 +39,10 @@ more accurate and helpful responses.
 
 Notes:
 • You can add specific files or use glob patterns (e.g., \"*.py\", \"src/**/*.js\")
+• Rules in .amazonq/context.json of the current directory or its parents apply as well, unless a nearer one sets \"inherit\": false
+• Commands added with add-cmd run again once their output is older than --ttl seconds
 • Agent rules apply only to the current agent 
+• Relative rules also match files under roots registered with add-root
 • Context changes are NOT preserved between chat sessions. To make these changes permanent, edit the agent config file."
 )]
 pub enum ContextSubcommand {
@@ -56,12 +67,49 @@ pub enum ContextSubcommand {
         #[arg(required = true)]
         paths: Vec<String>,
     },
-    /// Remove all rules from current profile
+    /// Remove all rules and commands from current profile
     Clear,
+    /// Add a command whose output is run again and included as context with every request
+    AddCmd {
+        /// The shell command, e.g. "kubectl get pods"
+        command: String,
+        /// Seconds for which the output is reused before the command runs again
+        #[arg(long, default_value_t = 60)]
+        ttl: u64,
+        /// Bytes of output to keep, beyond which the output is truncated
+        #[arg(long, default_value_t = 10_000)]
+        max_size: usize,
+    },
+    /// Select the files of the workspace most relevant to each prompt as context
+    Auto {
+        #[arg(value_enum)]
+        state: AutoContextState,
+    },
+    /// Remove a command added with add-cmd
+    RemoveCmd {
+        /// The shell command, as given to add-cmd
+        command: String,
+    },
+    /// Register an additional project root that context rules and tool paths resolve against
+    AddRoot {
+        /// Directory of the project root
+        path: String,
+    },
+    /// Unregister a project root added with add-root
+    RemoveRoot {
+        /// Directory of the project root
+        path: String,
+    },
     #[command(hide = true)]
     Hooks,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum AutoContextState {
+    On,
+    Off,
+}
+
 impl ContextSubcommand {
     pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         let Some(context_manager) = &mut session.conversation.context_manager else {
@@ -80,6 +128,19 @@ impl ContextSubcommand {
         match self {
             Self::Show { expand } => {
                 let profile_context_files = HashSet::<(String, String)>::new();
+                let memory_files = memory_files(os).unwrap_or_default();
+                if !memory_files.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetAttribute(Attribute::Bold),
+                        style::SetForegroundColor(Color::Magenta),
+                        style::Print("\n📝 Memory:\n"),
+                        style::SetAttribute(Attribute::Reset),
+                    )?;
+                    for path in &memory_files {
+                        execute!(session.stderr, style::Print(format!("    {}\n", path.display())))?;
+                    }
+                }
                 execute!(
                     session.stderr,
                     style::SetAttribute(Attribute::Bold),
@@ -115,6 +176,85 @@ impl ContextSubcommand {
                     execute!(session.stderr, style::Print("\n"))?;
                 }
 
+                let directory_contexts = context_manager.directory_contexts(os).await.unwrap_or_default();
+                for (dir, context) in &directory_contexts {
+                    execute!(
+                        session.stderr,
+                        style::SetAttribute(Attribute::Bold),
+                        style::SetForegroundColor(Color::Magenta),
+                        style::Print(format!("📂 Directory ({}):\n", directory_context_path(dir).display())),
+                        style::SetAttribute(Attribute::Reset),
+                    )?;
+                    for path in &context.paths {
+                        execute!(session.stderr, style::Print(format!("    {}\n", path)))?;
+                    }
+                    if context.paths.is_empty() {
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print("    <none>\n"),
+                            style::SetForegroundColor(Color::Reset)
+                        )?;
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+
+                if context_manager.auto {
+                    execute!(
+                        session.stderr,
+                        style::SetAttribute(Attribute::Bold),
+                        style::SetForegroundColor(Color::Magenta),
+                        style::Print("🔎 Auto-context:\n"),
+                        style::SetAttribute(Attribute::Reset),
+                    )?;
+                    for path in &context_manager.auto_files {
+                        execute!(session.stderr, style::Print(format!("    {}\n", path.display())))?;
+                    }
+                    if context_manager.auto_files.is_empty() {
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print("    <no files selected yet>\n"),
+                            style::SetForegroundColor(Color::Reset)
+                        )?;
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+
+                if !context_manager.commands.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetAttribute(Attribute::Bold),
+                        style::SetForegroundColor(Color::Magenta),
+                        style::Print("⚙ Commands:\n"),
+                        style::SetAttribute(Attribute::Reset),
+                    )?;
+                    for command in &context_manager.commands {
+                        execute!(
+                            session.stderr,
+                            style::Print(format!("    {} ", command.command)),
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!("(ttl {}s, max {} bytes)\n", command.ttl_secs, command.max_size)),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+
+                if !context_manager.roots.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetAttribute(Attribute::Bold),
+                        style::SetForegroundColor(Color::Magenta),
+                        style::Print("📁 Roots:\n"),
+                        style::SetAttribute(Attribute::Reset),
+                    )?;
+                    for root in &context_manager.roots {
+                        execute!(session.stderr, style::Print(format!("    {}\n", root.display())))?;
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+
                 if profile_context_files.is_empty() {
                     execute!(
                         session.stderr,
@@ -277,6 +417,100 @@ impl ContextSubcommand {
                     style::SetForegroundColor(Color::Reset)
                 )?;
             },
+            Self::AddCmd { command, ttl, max_size } => {
+                let result = context_manager.add_command(ContextCommand {
+                    command: command.clone(),
+                    ttl_secs: ttl,
+                    max_size,
+                });
+                match result {
+                    Ok(_) => {
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Green),
+                            style::Print(format!("\nAdded command '{}' to context.\n\n", command)),
+                            style::SetForegroundColor(Color::Reset)
+                        )?;
+                    },
+                    Err(e) => {
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Red),
+                            style::Print(format!("\nError: {}\n\n", e)),
+                            style::SetForegroundColor(Color::Reset)
+                        )?;
+                    },
+                }
+            },
+            Self::Auto { state } => {
+                context_manager.set_auto(state == AutoContextState::On);
+                let message = match state {
+                    AutoContextState::On => {
+                        "\nAuto-context enabled. The files most relevant to each prompt are included as context.\n\n"
+                    },
+                    AutoContextState::Off => "\nAuto-context disabled.\n\n",
+                };
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(message),
+                    style::SetForegroundColor(Color::Reset)
+                )?;
+            },
+            Self::RemoveCmd { command } => match context_manager.remove_command(&command) {
+                Ok(_) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!("\nRemoved command '{}' from context.\n\n", command)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                },
+                Err(e) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\nError: {}\n\n", e)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                },
+            },
+            Self::AddRoot { path } => match context_manager.add_root(os, &path).await {
+                Ok(root) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!("\nAdded root {}\n\n", root.display())),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                },
+                Err(e) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\nError: {}\n\n", e)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                },
+            },
+            Self::RemoveRoot { path } => match context_manager.remove_root(os, &path) {
+                Ok(root) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!("\nRemoved root {}\n\n", root.display())),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                },
+                Err(e) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\nError: {}\n\n", e)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                },
+            },
             Self::Hooks => {
                 execute!(
                     session.stderr,
@@ -302,6 +536,11 @@ impl ContextSubcommand {
             ContextSubcommand::Add { .. } => "add",
             ContextSubcommand::Remove { .. } => "remove",
             ContextSubcommand::Clear => "clear",
+            ContextSubcommand::AddCmd { .. } => "add-cmd",
+            ContextSubcommand::RemoveCmd { .. } => "remove-cmd",
+            ContextSubcommand::Auto { .. } => "auto",
+            ContextSubcommand::AddRoot { .. } => "add-root",
+            ContextSubcommand::RemoveRoot { .. } => "remove-root",
             ContextSubcommand::Hooks => "hooks",
         }
     }
@@ -0,0 +1,76 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::parse::{
+    CodeBlock,
+    code_blocks,
+};
+use crate::cli::chat::util::clipboard;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct CopyArgs {
+    /// Number of the code block in the last response, starting at 1
+    #[arg(default_value_t = 1)]
+    index: usize,
+}
+
+impl CopyArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match last_code_block(session, self.index) {
+            Ok(block) => match clipboard::copy_text(&block.code) {
+                Ok(_) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format!(
+                        "\nCopied code block {} ({} lines) to the clipboard.\n\n",
+                        self.index,
+                        block.code.lines().count()
+                    )),
+                    style::SetForegroundColor(Color::Reset)
+                )?,
+                Err(err) => print_error(session, &format!("Failed to copy to the clipboard: {err}"))?,
+            },
+            Err(err) => print_error(session, &err)?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// Returns the `index`th (1-based) fenced code block of the last assistant response.
+pub fn last_code_block(session: &ChatSession, index: usize) -> Result<CodeBlock, String> {
+    let Some(message) = session.conversation.last_assistant_message() else {
+        return Err("There is no response to take a code block from.".to_string());
+    };
+    let blocks = code_blocks(message.content());
+    match blocks.len() {
+        0 => Err("The last response does not contain any code blocks.".to_string()),
+        n if index == 0 || index > n => Err(format!(
+            "Code block {index} does not exist. The last response contains {n} code block{}.",
+            if n == 1 { "" } else { "s" }
+        )),
+        _ => Ok(blocks[index - 1].clone()),
+    }
+}
+
+fn print_error(session: &mut ChatSession, message: &str) -> Result<(), ChatError> {
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Red),
+        style::Print(format!("\n{message}\n\n")),
+        style::SetForegroundColor(Color::Reset)
+    )?;
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use clap::Args;
 use crossterm::execute;
 use crossterm::style::{
@@ -89,6 +91,25 @@ fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
     let file_name = format!("q_prompt_{}.md", Uuid::new_v4());
     let temp_file_path = temp_dir.join(file_name);
 
+    // Write initial content to the file if provided
+    let initial_content = initial_text.unwrap_or_default();
+    std::fs::write(&temp_file_path, &initial_content)
+        .map_err(|e| ChatError::Custom(format!("Failed to create temporary file: {}", e).into()))?;
+
+    launch_editor(&temp_file_path)?;
+
+    // Read the content back
+    let content = std::fs::read_to_string(&temp_file_path)
+        .map_err(|e| ChatError::Custom(format!("Failed to read temporary file: {}", e).into()))?;
+
+    // Clean up the temporary file
+    let _ = std::fs::remove_file(&temp_file_path);
+
+    Ok(content.trim().to_string())
+}
+
+/// Opens `path` in the user's preferred editor, returning once the editor exits
+pub fn launch_editor(path: &Path) -> Result<(), ChatError> {
     // Get the editor from environment variable or use a default
     let editor_cmd = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
 
@@ -102,11 +123,6 @@ fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
 
     let editor_bin = parts.remove(0);
 
-    // Write initial content to the file if provided
-    let initial_content = initial_text.unwrap_or_default();
-    std::fs::write(&temp_file_path, &initial_content)
-        .map_err(|e| ChatError::Custom(format!("Failed to create temporary file: {}", e).into()))?;
-
     // Open the editor with the parsed command and arguments
     let mut cmd = std::process::Command::new(editor_bin);
     // Add any arguments that were part of the EDITOR variable
@@ -115,7 +131,7 @@ fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
     }
     // Add the file path as the last argument
     let status = cmd
-        .arg(&temp_file_path)
+        .arg(path)
         .status()
         .map_err(|e| ChatError::Custom(format!("Failed to open editor: {}", e).into()))?;
 
@@ -123,12 +139,5 @@ fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
         return Err(ChatError::Custom("Editor exited with non-zero status".into()));
     }
 
-    // Read the content back
-    let content = std::fs::read_to_string(&temp_file_path)
-        .map_err(|e| ChatError::Custom(format!("Failed to read temporary file: {}", e).into()))?;
-
-    // Clean up the temporary file
-    let _ = std::fs::remove_file(&temp_file_path);
-
-    Ok(content.trim().to_string())
+    Ok(())
 }
@@ -0,0 +1,80 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+use tracing::warn;
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::telemetry::core::FeedbackVote;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "Rates the last response, to flag good or bad responses without filing an issue.
+
+The rating is kept with the conversation and sent with the telemetry of the session. Rating a
+response again replaces the previous rating. Use /issue to report a bug."
+)]
+pub struct FeedbackArgs {
+    /// Whether the last response was helpful
+    vote: FeedbackVote,
+    /// What was good or wrong about the response
+    comment: Vec<String>,
+}
+
+impl FeedbackArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let comment = Some(self.comment.join(" "))
+            .filter(|comment| !comment.trim().is_empty())
+            .map(|comment| session.conversation.redactor.redact(&comment));
+        let feedback = match session.conversation.record_feedback(self.vote, comment) {
+            Ok(feedback) => feedback.clone(),
+            Err(err) => {
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("\n{err}\n\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                });
+            },
+        };
+
+        os.telemetry
+            .send_message_feedback(
+                &os.database,
+                session.conversation.conversation_id().to_string(),
+                feedback.message_id,
+                feedback.vote,
+                feedback.comment,
+            )
+            .await
+            .ok();
+        if let Err(err) = session.conversation.save(os) {
+            warn!(?err, "failed to save the conversation");
+        }
+
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print(match self.vote {
+                FeedbackVote::Up => "\nThanks, glad it helped!\n\n",
+                FeedbackVote::Down => "\nThanks, your feedback helps to improve the responses.\n\n",
+            }),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
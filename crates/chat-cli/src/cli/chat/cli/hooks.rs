@@ -30,6 +30,7 @@ use futures::stream::{
 use spinners::{
     Spinner,
     Spinners,
+    Stream,
 };
 
 use crate::cli::agent::hook::{
@@ -99,7 +100,11 @@ impl HookExecutor {
         };
 
         if total != 0 {
-            spinner = Some(Spinner::new(Spinners::Dots12, spinner_text(complete, total)));
+            spinner = Some(Spinner::with_stream(
+                Spinners::Dots12,
+                spinner_text(complete, total),
+                Stream::Stderr,
+            ));
         }
 
         // Process results as they complete
@@ -159,7 +164,11 @@ impl HookExecutor {
                     style::ResetColor,
                 )?;
             } else {
-                spinner = Some(Spinner::new(Spinners::Dots, spinner_text(complete, total)));
+                spinner = Some(Spinner::with_stream(
+                    Spinners::Dots,
+                    spinner_text(complete, total),
+                    Stream::Stderr,
+                ));
             }
         }
         drop(futures);
@@ -0,0 +1,452 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use clap::Args;
+use crossterm::style::{
+    self,
+    Color,
+    Stylize,
+};
+use crossterm::{
+    cursor,
+    execute,
+    queue,
+};
+use serde_json::json;
+
+use super::profile::highlight_json;
+use crate::cli::agent::{
+    Agent,
+    validate_agent_name,
+};
+use crate::cli::chat::tools::DEFAULT_APPROVE;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::util::directories::agent_config_dir;
+
+/// Characters of the README summary included in the prompt of the agent.
+const MAX_SUMMARY_LEN: usize = 300;
+
+const READMES: &[&str] = &["README.md", "README.rst", "README.txt", "README"];
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "Inspects the project in the current directory, i.e. its language, build system, test command, and README,
+and generates an agent for it in .amazonq/agents, which is shown for confirmation before being written.
+
+The agent includes the README and manifest as context, trusts the build and test commands, and has a
+prompt describing the project. Switch to it with /agent switch."
+)]
+pub struct InitArgs {
+    /// Name of the agent, the name of the current directory by default
+    #[arg(long, short)]
+    name: Option<String>,
+    /// Overwrite the agent if it already exists
+    #[arg(long, short)]
+    force: bool,
+}
+
+impl InitArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let cwd = os.env.current_dir()?;
+        let project = Project::inspect(&cwd);
+        let name = self.name.unwrap_or_else(|| project.agent_name());
+        if let Err(err) = validate_agent_name(&name) {
+            return print_error(session, &err.to_string());
+        }
+        let path = cwd.join(agent_config_dir()).join(format!("{name}.json"));
+        if os.fs.exists(&path) && !self.force {
+            return print_error(
+                session,
+                &format!(
+                    "An agent already exists at {}. Use /init --force to overwrite it.",
+                    path.display()
+                ),
+            );
+        }
+
+        let agent = project.agent();
+        let content = agent
+            .to_str_pretty()
+            .map_err(|e| ChatError::Custom(format!("Failed to serialize the agent: {e}").into()))?;
+
+        queue!(session.stderr, style::Print("\n"))?;
+        for (label, value) in [
+            ("Language", project.language),
+            ("Build system", project.build_system),
+            ("Build", project.build_command.as_deref()),
+            ("Tests", project.test_command.as_deref()),
+        ] {
+            queue!(
+                session.stderr,
+                style::Print(format!("{label:<14}")),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("{}\n", value.unwrap_or("not detected"))),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        queue!(session.stderr, style::Print(format!("\n{}\n", path.display())))?;
+        highlight_json(&mut session.stderr, &content)
+            .map_err(|e| ChatError::Custom(format!("Error printing the agent: {e}").into()))?;
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("\n\nWrite this agent? "),
+            style::Print("["),
+            style::SetForegroundColor(Color::Green),
+            style::Print("y"),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("/"),
+            style::SetForegroundColor(Color::Green),
+            style::Print("n"),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("]:\n\n"),
+            style::SetForegroundColor(Color::Reset),
+            cursor::Show,
+        )?;
+
+        let user_input = session
+            .read_user_input("> ".yellow().to_string().as_str(), true)
+            .unwrap_or_default();
+        if !["y", "Y"].contains(&user_input.as_str()) {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("\nNo agent was written.\n\n"),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        if let Some(parent) = path.parent() {
+            os.fs.create_dir_all(parent).await?;
+        }
+        os.fs.write(&path, &content).await?;
+        // Makes the agent available to /agent switch right away.
+        match Agent::load(os, &path, &mut None).await {
+            Ok(agent) => {
+                session.conversation.agents.agents.insert(name.clone(), agent);
+            },
+            Err(err) => return print_error(session, &err.to_string()),
+        }
+
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!("\n✔ Created agent {name} at {}\n", path.display())),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!(
+                "Switch to it with /agent switch {name}, or start chatting with it with q chat --agent {name}\n\n"
+            )),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// What is detected about a project from the files at its root.
+#[derive(Debug, Default, PartialEq)]
+struct Project {
+    /// Name of the directory of the project.
+    name: String,
+    language: Option<&'static str>,
+    build_system: Option<&'static str>,
+    build_command: Option<String>,
+    test_command: Option<String>,
+    /// Files describing the project, relative to its root.
+    context_files: Vec<String>,
+    /// First paragraph of the README.
+    summary: Option<String>,
+}
+
+impl Project {
+    fn inspect(dir: &Path) -> Self {
+        let read = |file: &str| std::fs::read_to_string(dir.join(file)).ok();
+        let exists = |file: &str| dir.join(file).is_file();
+        let mut project = Self {
+            name: dir
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let mut manifest = None;
+        if exists("Cargo.toml") {
+            manifest = Some("Cargo.toml");
+            project.language = Some("Rust");
+            project.build_system = Some("Cargo");
+            project.build_command = Some("cargo build".to_string());
+            project.test_command = Some("cargo test".to_string());
+        } else if let Some(package) = read("package.json") {
+            manifest = Some("package.json");
+            let scripts = serde_json::from_str::<serde_json::Value>(&package)
+                .ok()
+                .and_then(|package| package.get("scripts").cloned())
+                .unwrap_or_default();
+            let manager = if exists("pnpm-lock.yaml") {
+                "pnpm"
+            } else if exists("yarn.lock") {
+                "yarn"
+            } else if exists("bun.lockb") || exists("bun.lock") {
+                "bun"
+            } else {
+                "npm"
+            };
+            project.language = Some(if exists("tsconfig.json") {
+                "TypeScript"
+            } else {
+                "JavaScript"
+            });
+            project.build_system = Some(manager);
+            project.build_command = scripts.get("build").map(|_| format!("{manager} run build"));
+            project.test_command = scripts.get("test").map(|_| format!("{manager} run test"));
+        } else if exists("go.mod") {
+            manifest = Some("go.mod");
+            project.language = Some("Go");
+            project.build_system = Some("Go modules");
+            project.build_command = Some("go build ./...".to_string());
+            project.test_command = Some("go test ./...".to_string());
+        } else if let Some(file) = ["pyproject.toml", "setup.py", "requirements.txt"]
+            .into_iter()
+            .find(|file| exists(file))
+        {
+            manifest = Some(file);
+            let pyproject = read("pyproject.toml").unwrap_or_default();
+            project.language = Some("Python");
+            project.build_system = Some(if pyproject.contains("[tool.poetry]") {
+                "Poetry"
+            } else if exists("uv.lock") {
+                "uv"
+            } else {
+                "pip"
+            });
+            let uses_pytest = exists("pytest.ini")
+                || exists("conftest.py")
+                || pyproject.contains("pytest")
+                || read("requirements.txt").is_some_and(|requirements| requirements.contains("pytest"));
+            project.test_command = Some(match uses_pytest {
+                true => "python -m pytest".to_string(),
+                false => "python -m unittest".to_string(),
+            });
+        } else if exists("pom.xml") {
+            manifest = Some("pom.xml");
+            project.language = Some("Java");
+            project.build_system = Some("Maven");
+            project.build_command = Some("mvn package".to_string());
+            project.test_command = Some("mvn test".to_string());
+        } else if let Some(file) = ["build.gradle.kts", "build.gradle"]
+            .into_iter()
+            .find(|file| exists(file))
+        {
+            manifest = Some(file);
+            let gradle = if exists("gradlew") { "./gradlew" } else { "gradle" };
+            project.language = Some(if file.ends_with(".kts") { "Kotlin" } else { "Java" });
+            project.build_system = Some("Gradle");
+            project.build_command = Some(format!("{gradle} build"));
+            project.test_command = Some(format!("{gradle} test"));
+        } else if let Some(makefile) = read("Makefile") {
+            manifest = Some("Makefile");
+            project.build_system = Some("Make");
+            project.build_command = Some("make".to_string());
+            project.test_command = makefile
+                .lines()
+                .any(|line| line.starts_with("test:"))
+                .then(|| "make test".to_string());
+        }
+
+        let readme = READMES.iter().find(|file| exists(file));
+        project.summary = readme.and_then(|file| read(file)).and_then(|readme| summarize(&readme));
+        project.context_files = readme
+            .into_iter()
+            .chain(["CONTRIBUTING.md", "AmazonQ.md"].iter().filter(|file| exists(file)))
+            .copied()
+            .chain(manifest)
+            .map(str::to_string)
+            .chain([".amazonq/rules/**/*.md".to_string()])
+            .collect();
+        project
+    }
+
+    /// The name of the directory, made into a valid agent name.
+    fn agent_name(&self) -> String {
+        let name = self
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect::<String>();
+        let name = name.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+        match name.is_empty() {
+            true => "project".to_string(),
+            false => name.to_string(),
+        }
+    }
+
+    fn agent(&self) -> Agent {
+        let shell_tool = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
+        let commands = self
+            .build_command
+            .iter()
+            .chain(&self.test_command)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut prompt = format!("You are working on the {} project", self.name);
+        if let Some(summary) = &self.summary {
+            prompt.push_str(&format!(", described by its README as: \"{summary}\""));
+        }
+        prompt.push('.');
+        match (self.language, self.build_system) {
+            (Some(language), Some(build_system)) => {
+                prompt.push_str(&format!(" It is written in {language} and built with {build_system}."));
+            },
+            (Some(language), None) => prompt.push_str(&format!(" It is written in {language}.")),
+            (None, Some(build_system)) => prompt.push_str(&format!(" It is built with {build_system}.")),
+            (None, None) => {},
+        }
+        if let Some(build) = &self.build_command {
+            prompt.push_str(&format!(" Build it with `{build}`."));
+        }
+        if let Some(test) = &self.test_command {
+            prompt.push_str(&format!(" Run the tests with `{test}` after making changes."));
+        }
+        prompt.push_str(" Follow the conventions of the surrounding code.");
+
+        let mut allowed_tools = DEFAULT_APPROVE
+            .iter()
+            .copied()
+            .map(str::to_string)
+            .collect::<HashSet<_>>();
+        let mut tools_settings = Default::default();
+        if !commands.is_empty() {
+            allowed_tools.insert(shell_tool.to_string());
+            tools_settings = serde_json::from_value(json!({
+                shell_tool: { "allowedCommands": commands }
+            }))
+            .unwrap_or_default();
+        }
+
+        Agent {
+            description: Some(format!("Agent for the {} project, generated by /init", self.name)),
+            prompt: Some(prompt),
+            allowed_tools,
+            resources: self
+                .context_files
+                .iter()
+                .map(|file| format!("file://{file}").into())
+                .collect(),
+            tools_settings,
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns the first paragraph of prose of a README, skipping headings, badges, and HTML.
+fn summarize(readme: &str) -> Option<String> {
+    let is_prose = |line: &str| {
+        !["#", "[!", "![", "<", "===", "---", "[![", ".."]
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+    };
+    let paragraph = readme
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty() || !is_prose(line))
+        .take_while(|line| !line.is_empty() && is_prose(line))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if paragraph.is_empty() {
+        return None;
+    }
+    match paragraph.char_indices().nth(MAX_SUMMARY_LEN) {
+        Some((i, _)) => Some(format!("{}…", &paragraph[..i])),
+        None => Some(paragraph),
+    }
+}
+
+fn print_error(session: &mut ChatSession, err: &str) -> Result<ChatState, ChatError> {
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Red),
+        style::Print(format!("\n{err}\n\n")),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("My Project");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(
+            root.join("package.json"),
+            r#"{ "scripts": { "test": "jest", "lint": "eslint ." } }"#,
+        )
+        .unwrap();
+        std::fs::write(root.join("yarn.lock"), "").unwrap();
+        std::fs::write(root.join("tsconfig.json"), "{}").unwrap();
+        std::fs::write(
+            root.join("README.md"),
+            "# My Project\n\n[![build](badge.svg)](ci)\n\nA tool that\ndoes things.\n\n## Usage\n",
+        )
+        .unwrap();
+
+        let project = Project::inspect(&root);
+        assert_eq!(project, Project {
+            name: "My Project".to_string(),
+            language: Some("TypeScript"),
+            build_system: Some("yarn"),
+            build_command: None,
+            test_command: Some("yarn run test".to_string()),
+            context_files: vec![
+                "README.md".to_string(),
+                "package.json".to_string(),
+                ".amazonq/rules/**/*.md".to_string()
+            ],
+            summary: Some("A tool that does things.".to_string()),
+        });
+        assert_eq!(project.agent_name(), "My-Project");
+
+        let agent = project.agent();
+        assert!(agent.prompt.unwrap().contains("Run the tests with `yarn run test`"));
+        let shell_tool = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
+        assert!(agent.allowed_tools.contains(shell_tool));
+        assert_eq!(
+            agent.tools_settings.get(shell_tool),
+            Some(&json!({ "allowedCommands": ["yarn run test"] }))
+        );
+    }
+
+    #[test]
+    fn test_inspect_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = Project::inspect(dir.path());
+        assert_eq!(project.language, None);
+        assert_eq!(project.context_files, vec![".amazonq/rules/**/*.md"]);
+        let agent = project.agent();
+        assert!(agent.tools_settings.is_empty());
+        assert_eq!(agent.allowed_tools, HashSet::from(["fs_read".to_string()]));
+    }
+}
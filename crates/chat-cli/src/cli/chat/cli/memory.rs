@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use super::editor::launch_editor;
+use crate::cli::chat::context::{
+    MEMORY_FILE_NAMES,
+    memory_files,
+};
+use crate::cli::chat::token_counter::TokenCounter;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "Memory files, AGENTS.md and AmazonQ.md, hold instructions for agents that are kept with a project.
+Those of the current directory and its parents, up to the root of the git repository, are always
+included as context, ahead of other context files and regardless of the agent."
+)]
+pub enum MemorySubcommand {
+    /// Show the memory files included as context
+    Show,
+    /// Open the nearest memory file in $EDITOR, creating AGENTS.md at the root of the repository
+    /// if there is none
+    Edit,
+}
+
+impl MemorySubcommand {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let files = memory_files(os).map_err(|e| ChatError::Custom(e.to_string().into()))?;
+        match self {
+            Self::Show => {
+                if files.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!(
+                            "\nNo memory file found. Create one with /memory edit, or add {} to the project.\n\n",
+                            MEMORY_FILE_NAMES.join(" or ")
+                        )),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                } else {
+                    execute!(session.stderr, style::Print("\n"))?;
+                    for path in &files {
+                        let tokens = os
+                            .fs
+                            .read_to_string(path)
+                            .await
+                            .map(|content| TokenCounter::count_tokens(&content))
+                            .unwrap_or_default();
+                        execute!(
+                            session.stderr,
+                            style::Print(format!("📝 {} ", path.display())),
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!("(~{tokens} tkns)\n")),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+            },
+            Self::Edit => {
+                let path = match files.into_iter().next() {
+                    Some(path) => path,
+                    None => repository_root(os)?.join(MEMORY_FILE_NAMES[0]),
+                };
+                if let Err(err) = launch_editor(&os.fs.chroot_path(&path)) {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\nError opening editor: {}\n\n", err)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                } else if os.fs.exists(&path) {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!("\n✔ Saved {}\n", path.display())),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("The changes are included as context from the next prompt.\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                } else {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Yellow),
+                        style::Print("\nThe memory file was not saved.\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Show => "show",
+            Self::Edit => "edit",
+        }
+    }
+}
+
+/// Returns the root of the git repository containing the current working directory, or the
+/// current working directory outside of a repository.
+fn repository_root(os: &Os) -> Result<PathBuf, ChatError> {
+    let cwd = os.env.current_dir()?;
+    Ok(cwd
+        .ancestors()
+        .find(|dir| os.fs.exists(dir.join(".git")))
+        .unwrap_or(&cwd)
+        .to_path_buf())
+}
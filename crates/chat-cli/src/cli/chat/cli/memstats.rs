@@ -0,0 +1,202 @@
+use clap::Args;
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+use sysinfo::{
+    ProcessRefreshKind,
+    ProcessesToUpdate,
+    System,
+};
+
+use crate::cli::chat::consts::{
+    DEFAULT_MAX_FAILED_REQUEST_IDS,
+    DEFAULT_MAX_PENDING_TOOL_TELEMETRY_EVENTS,
+    MAX_CONVERSATION_STATE_HISTORY_LEN,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Caps on the in-memory bookkeeping of a chat session, configured through the `chat.memory.*`
+/// settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryCaps {
+    pub transcript_entries: usize,
+    pub failed_request_ids: usize,
+    pub pending_tool_telemetry_events: usize,
+}
+
+impl MemoryCaps {
+    pub fn new(os: &Os) -> Self {
+        let get = |setting: Setting, default: usize| {
+            os.database
+                .settings
+                .get_int(setting)
+                .and_then(|v| usize::try_from(v).ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            transcript_entries: get(Setting::ChatMaxTranscriptEntries, MAX_CONVERSATION_STATE_HISTORY_LEN)
+                .min(MAX_CONVERSATION_STATE_HISTORY_LEN),
+            failed_request_ids: get(Setting::ChatMaxFailedRequestIds, DEFAULT_MAX_FAILED_REQUEST_IDS),
+            pending_tool_telemetry_events: get(
+                Setting::ChatMaxPendingToolTelemetryEvents,
+                DEFAULT_MAX_PENDING_TOOL_TELEMETRY_EVENTS,
+            ),
+        }
+    }
+}
+
+/// Arguments for the memstats command that shows the memory usage of the session
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct MemstatsArgs;
+
+impl MemstatsArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let caps = MemoryCaps::new(os);
+        let history_bytes = serde_json::to_vec(session.conversation.history()).map_or(0, |v| v.len());
+        let transcript_bytes = session.conversation.transcript.iter().map(|t| t.len()).sum::<usize>();
+
+        queue!(
+            session.stderr,
+            style::Print("\n"),
+            style::SetAttribute(Attribute::Bold),
+            style::Print(format!("{:<30}{}\n", "Process memory", format_bytes(process_memory()))),
+            style::SetAttribute(Attribute::Reset),
+            style::Print("\n"),
+        )?;
+
+        for (label, count, bytes, cap) in [
+            (
+                "History",
+                session.conversation.history().len(),
+                Some(history_bytes),
+                Some(MAX_CONVERSATION_STATE_HISTORY_LEN),
+            ),
+            (
+                "Transcript",
+                session.conversation.transcript.len(),
+                Some(transcript_bytes),
+                Some(caps.transcript_entries),
+            ),
+            (
+                "Pending tool telemetry events",
+                session.tool_use_telemetry_events.len(),
+                None,
+                Some(caps.pending_tool_telemetry_events),
+            ),
+            (
+                "Failed request ids",
+                session.failed_request_ids.len(),
+                None,
+                Some(caps.failed_request_ids),
+            ),
+            ("Queued tool uses", session.tool_uses.len(), None, None),
+            ("Pending prompts", session.pending_prompts.len(), None, None),
+        ] {
+            queue!(
+                session.stderr,
+                style::Print(format!("{label:<30}{count}")),
+                style::SetForegroundColor(Color::DarkGrey),
+            )?;
+            if let Some(cap) = cap {
+                queue!(session.stderr, style::Print(format!(" / {cap}")))?;
+            }
+            if let Some(bytes) = bytes {
+                queue!(
+                    session.stderr,
+                    style::Print(format!(" (~{})", format_bytes(Some(bytes as u64))))
+                )?;
+            }
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(Color::Reset),
+                style::Print("\n")
+            )?;
+        }
+
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("\nCaps can be configured with the chat.memory.* settings, e.g. "),
+            style::SetForegroundColor(Color::Green),
+            style::Print("q settings chat.memory.maxTranscriptEntries 100"),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n\n"),
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// Resident memory of the current process, in bytes.
+fn process_memory() -> Option<u64> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_memory(),
+    );
+    system.process(pid).map(|p| p.memory())
+}
+
+pub fn format_bytes(bytes: Option<u64>) -> String {
+    match bytes {
+        Some(b) if b >= 1024 * 1024 => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+        Some(b) if b >= 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        Some(b) => format!("{b} B"),
+        None => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_caps() {
+        let mut os = Os::new().await.unwrap();
+        assert_eq!(MemoryCaps::new(&os), MemoryCaps {
+            transcript_entries: MAX_CONVERSATION_STATE_HISTORY_LEN,
+            failed_request_ids: DEFAULT_MAX_FAILED_REQUEST_IDS,
+            pending_tool_telemetry_events: DEFAULT_MAX_PENDING_TOOL_TELEMETRY_EVENTS,
+        });
+
+        os.database
+            .settings
+            .set(Setting::ChatMaxTranscriptEntries, 10_000)
+            .await
+            .unwrap();
+        os.database
+            .settings
+            .set(Setting::ChatMaxFailedRequestIds, 5)
+            .await
+            .unwrap();
+        let caps = MemoryCaps::new(&os);
+        assert_eq!(caps.transcript_entries, MAX_CONVERSATION_STATE_HISTORY_LEN);
+        assert_eq!(caps.failed_request_ids, 5);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(None), "unknown");
+        assert_eq!(format_bytes(Some(512)), "512 B");
+        assert_eq!(format_bytes(Some(2048)), "2.0 KB");
+        assert_eq!(format_bytes(Some(3 * 1024 * 1024)), "3.0 MB");
+    }
+}
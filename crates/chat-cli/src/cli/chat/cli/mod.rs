@@ -1,31 +1,65 @@
+pub mod alias;
+pub mod apply;
+pub mod ask;
+pub mod ask_file;
 pub mod clear;
 pub mod compact;
 pub mod context;
+pub mod copy;
+pub mod doctor;
 pub mod editor;
+pub mod feedback;
 pub mod hooks;
+pub mod init;
 pub mod knowledge;
 pub mod mcp;
+pub mod memory;
+pub mod memstats;
 pub mod model;
+pub mod paste;
 pub mod persist;
+pub mod pin;
+pub mod plan;
 pub mod profile;
 pub mod prompts;
+pub mod redact;
+pub mod settings;
+pub mod status;
 pub mod subscribe;
 pub mod tools;
+pub mod trash;
 pub mod usage;
 
+use alias::AliasArgs;
+use apply::ApplyArgs;
+use ask::AskArgs;
+use ask_file::AskFileArgs;
 use clap::Parser;
 use clear::ClearArgs;
 use compact::CompactArgs;
 use context::ContextSubcommand;
+use copy::CopyArgs;
+use doctor::DoctorArgs;
 use editor::EditorArgs;
+use feedback::FeedbackArgs;
 use hooks::HooksArgs;
+use init::InitArgs;
 use knowledge::KnowledgeSubcommand;
 use mcp::McpArgs;
+use memory::MemorySubcommand;
+use memstats::MemstatsArgs;
 use model::ModelArgs;
+use paste::PasteArgs;
 use persist::PersistSubcommand;
+use pin::PinArgs;
+use plan::PlanArgs;
 use profile::AgentSubcommand;
 use prompts::PromptsArgs;
+use redact::RedactArgs;
+use settings::SettingsArgs;
+use status::StatusArgs;
 use tools::ToolsArgs;
+use trash::TrashArgs;
 
 use crate::cli::chat::cli::subscribe::SubscribeArgs;
 use crate::cli::chat::cli::usage::UsageArgs;
@@ -33,14 +67,13 @@ use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
-    EXTRA_HELP,
 };
 use crate::cli::issue;
 use crate::os::Os;
 
 /// q (Amazon Q Chat)
 #[derive(Debug, PartialEq, Parser)]
-#[command(color = clap::ColorChoice::Always, term_width = 0, after_long_help = EXTRA_HELP)]
+#[command(color = clap::ColorChoice::Always, term_width = 0)]
 pub enum SlashCommand {
     /// Quit the application
     #[command(aliases = ["q", "exit"])]
@@ -50,9 +83,14 @@ pub enum SlashCommand {
     /// Manage agents
     #[command(subcommand, aliases = ["profile"])]
     Agent(AgentSubcommand),
+    /// Generate an agent for the project in the current directory
+    Init(InitArgs),
     /// Manage context files for the chat session
     #[command(subcommand)]
     Context(ContextSubcommand),
+    /// Show and edit the memory files of the project, AGENTS.md and AmazonQ.md
+    #[command(subcommand)]
+    Memory(MemorySubcommand),
     /// (Beta) Manage knowledge base for persistent context storage. Requires "q settings
     /// chat.enableKnowledge true"
     #[command(subcommand, hide = true)]
@@ -62,10 +100,26 @@ pub enum SlashCommand {
     PromptEditor(EditorArgs),
     /// Summarize the conversation to free up context space
     Compact(CompactArgs),
+    /// Copy a code block of the last response to the clipboard
+    Copy(CopyArgs),
+    /// Write a code block of the last response to a file
+    Apply(ApplyArgs),
+    /// Submit a prompt, optionally to another model for this request only
+    Ask(AskArgs),
+    /// Ask a question about a file and print only the answer
+    AskFile(AskFileArgs),
+    /// Paste the clipboard as a prompt, or attach a copied image to the next prompt
+    Paste(PasteArgs),
+    /// Pin an exchange so that it is kept when the history is truncated or compacted
+    Pin(PinArgs),
+    /// Remove text, e.g. a pasted secret, from the conversation before the next request
+    Redact(RedactArgs),
     /// View and manage tools and permissions
     Tools(ToolsArgs),
     /// Create a new Github issue or make a feature request
     Issue(issue::IssueArgs),
+    /// Rate the last response as helpful (up) or not (down), with an optional comment
+    Feedback(FeedbackArgs),
     /// View and retrieve prompts
     Prompts(PromptsArgs),
     /// View and manage context hooks
@@ -78,6 +132,20 @@ pub enum SlashCommand {
     Model(ModelArgs),
     /// Upgrade to a Q Developer Pro subscription for increased query limits
     Subscribe(SubscribeArgs),
+    /// Define prompts to send with /NAME, with arguments
+    Alias(AliasArgs),
+    /// View the plan Q is following for multi-step tasks
+    Plan(PlanArgs),
+    /// Show the status of the current session
+    Status(StatusArgs),
+    /// Show or change settings without leaving the session
+    Settings(SettingsArgs),
+    /// Show the memory usage of the current session
+    Memstats(MemstatsArgs),
+    /// List and restore the files overwritten or deleted while all tools are trusted
+    Trash(TrashArgs),
+    /// Check authentication, connectivity, MCP servers, and the terminal for problems
+    Doctor(DoctorArgs),
     #[command(flatten)]
     Persist(PersistSubcommand),
     // #[command(flatten)]
@@ -90,11 +158,20 @@ impl SlashCommand {
             Self::Quit => Ok(ChatState::Exit),
             Self::Clear(args) => args.execute(session).await,
             Self::Agent(subcommand) => subcommand.execute(os, session).await,
+            Self::Init(args) => args.execute(os, session).await,
             Self::Context(args) => args.execute(os, session).await,
+            Self::Memory(subcommand) => subcommand.execute(os, session).await,
             Self::Knowledge(subcommand) => subcommand.execute(os, session).await,
             Self::PromptEditor(args) => args.execute(session).await,
             Self::Compact(args) => args.execute(os, session).await,
-            Self::Tools(args) => args.execute(session).await,
+            Self::Copy(args) => args.execute(session).await,
+            Self::Apply(args) => args.execute(os, session).await,
+            Self::Ask(args) => args.execute(session).await,
+            Self::AskFile(args) => args.execute(os, session).await,
+            Self::Paste(args) => args.execute(session).await,
+            Self::Pin(args) => args.execute(session).await,
+            Self::Redact(args) => args.execute(os, session).await,
+            Self::Tools(args) => args.execute(os, session).await,
             Self::Issue(args) => {
                 if let Err(err) = args.execute(os).await {
                     return Err(ChatError::Custom(err.to_string().into()));
@@ -104,12 +181,20 @@ impl SlashCommand {
                     skip_printing_tools: true,
                 })
             },
+            Self::Feedback(args) => args.execute(os, session).await,
             Self::Prompts(args) => args.execute(session).await,
             Self::Hooks(args) => args.execute(session).await,
             Self::Usage(args) => args.execute(os, session).await,
             Self::Mcp(args) => args.execute(session).await,
             Self::Model(args) => args.execute(session).await,
             Self::Subscribe(args) => args.execute(os, session).await,
+            Self::Alias(args) => args.execute(os, session).await,
+            Self::Plan(args) => args.execute(session).await,
+            Self::Status(args) => args.execute(os, session).await,
+            Self::Settings(args) => args.execute(os, session).await,
+            Self::Memstats(args) => args.execute(os, session).await,
+            Self::Trash(args) => args.execute(os, session).await,
+            Self::Doctor(args) => args.execute(os, session).await,
             Self::Persist(subcommand) => subcommand.execute(os, session).await,
             // Self::Root(subcommand) => {
             //     if let Err(err) = subcommand.execute(os, database, telemetry).await {
@@ -128,18 +213,35 @@ impl SlashCommand {
             Self::Quit => "quit",
             Self::Clear(_) => "clear",
             Self::Agent(_) => "agent",
+            Self::Init(_) => "init",
             Self::Context(_) => "context",
+            Self::Memory(_) => "memory",
             Self::Knowledge(_) => "knowledge",
             Self::PromptEditor(_) => "editor",
             Self::Compact(_) => "compact",
+            Self::Copy(_) => "copy",
+            Self::Apply(_) => "apply",
+            Self::Ask(_) => "ask",
+            Self::AskFile(_) => "ask-file",
+            Self::Paste(_) => "paste",
+            Self::Pin(_) => "pin",
+            Self::Redact(_) => "redact",
             Self::Tools(_) => "tools",
             Self::Issue(_) => "issue",
+            Self::Feedback(_) => "feedback",
             Self::Prompts(_) => "prompts",
             Self::Hooks(_) => "hooks",
             Self::Usage(_) => "usage",
             Self::Mcp(_) => "mcp",
             Self::Model(_) => "model",
             Self::Subscribe(_) => "subscribe",
+            Self::Alias(_) => "alias",
+            Self::Plan(_) => "plan",
+            Self::Status(_) => "status",
+            Self::Settings(_) => "settings",
+            Self::Memstats(_) => "memstats",
+            Self::Trash(_) => "trash",
+            Self::Doctor(_) => "doctor",
             Self::Persist(sub) => match sub {
                 PersistSubcommand::Save { .. } => "save",
                 PersistSubcommand::Load { .. } => "load",
@@ -151,9 +253,14 @@ impl SlashCommand {
         match self {
             SlashCommand::Agent(sub) => Some(sub.name()),
             SlashCommand::Context(sub) => Some(sub.name()),
+            SlashCommand::Memory(sub) => Some(sub.name()),
             SlashCommand::Knowledge(sub) => Some(sub.name()),
             SlashCommand::Tools(arg) => arg.subcommand_name(),
             SlashCommand::Prompts(arg) => arg.subcommand_name(),
+            SlashCommand::Plan(arg) => arg.subcommand_name(),
+            SlashCommand::Alias(arg) => arg.subcommand_name(),
+            SlashCommand::Pin(arg) => arg.subcommand_name(),
+            SlashCommand::Trash(arg) => arg.subcommand_name(),
             _ => None,
         }
     }
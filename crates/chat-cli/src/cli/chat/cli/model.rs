@@ -8,7 +8,11 @@ use crossterm::{
     queue,
 };
 use dialoguer::Select;
+use serde_json::Value;
+use time::OffsetDateTime;
+use tracing::warn;
 
+use crate::api_client::Endpoint;
 use crate::auth::builder_id::{
     BuilderIdToken,
     TokenType,
@@ -18,6 +22,11 @@ use crate::cli::chat::{
     ChatSession,
     ChatState,
 };
+use crate::database::settings::Setting;
+use crate::database::{
+    CachedModels,
+    ModelInfo,
+};
 use crate::os::Os;
 
 pub struct ModelOption {
@@ -25,6 +34,7 @@ pub struct ModelOption {
     pub model_id: &'static str,
 }
 
+/// Models known to this version of the CLI, used when the available models cannot be listed.
 pub const MODEL_OPTIONS: [ModelOption; 2] = [
     ModelOption {
         name: "claude-4-sonnet",
@@ -36,6 +46,110 @@ pub const MODEL_OPTIONS: [ModelOption; 2] = [
     },
 ];
 
+/// How long the models listed by the service are used before being listed again.
+const MODEL_CACHE_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// A model that can be used for a chat session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Model {
+    /// Name of the model shown to the user and accepted by `--model`. For models unknown to this
+    /// version of the CLI, this is the model id.
+    pub name: String,
+    pub model_id: String,
+    pub description: Option<String>,
+}
+
+impl From<ModelInfo> for Model {
+    fn from(info: ModelInfo) -> Self {
+        Self {
+            name: MODEL_OPTIONS
+                .iter()
+                .find(|opt| opt.model_id == info.model_id)
+                .map_or_else(|| info.model_id.clone(), |opt| opt.name.to_string()),
+            model_id: info.model_id,
+            description: info.description,
+        }
+    }
+}
+
+impl From<&ModelOption> for Model {
+    fn from(opt: &ModelOption) -> Self {
+        Self {
+            name: opt.name.to_string(),
+            model_id: opt.model_id.to_string(),
+            description: None,
+        }
+    }
+}
+
+/// Returns the models available to the current profile, and allowed by the managed policy.
+///
+/// Models are listed from the service at most once per day. If they cannot be listed, the last
+/// listed models are used, then [MODEL_OPTIONS].
+pub async fn available_models(os: &Os) -> Vec<Model> {
+    let policy = os.database.settings.policy();
+    list_models(os)
+        .await
+        .into_iter()
+        .filter(|model| policy.allows_model(&model.model_id, Some(&model.name)))
+        .collect()
+}
+
+async fn list_models(os: &Os) -> Vec<Model> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    // The models differ between profiles and regions, so only those listed for the current ones
+    // are used.
+    let profile_arn = os.database.get_auth_profile().ok().flatten().map(|profile| profile.arn);
+    let region = Endpoint::configured_value(&os.database).region.to_string();
+    let cached = os
+        .database
+        .get_cached_models()
+        .ok()
+        .flatten()
+        .filter(|cached| cached.profile_arn == profile_arn && cached.region == region);
+    if let Some(cached) = &cached {
+        if now - cached.fetched_at < MODEL_CACHE_TTL_SECS && !cached.models.is_empty() {
+            return cached.models.iter().cloned().map(Model::from).collect();
+        }
+    }
+
+    match os.client.list_available_models().await {
+        Ok(models) if !models.is_empty() => {
+            let cache = CachedModels {
+                fetched_at: now,
+                profile_arn,
+                region,
+                models,
+            };
+            if let Err(err) = os.database.set_cached_models(&cache) {
+                warn!(?err, "failed to cache the available models");
+            }
+            return cache.models.into_iter().map(Model::from).collect();
+        },
+        Ok(_) => warn!("no models are available, using the default models"),
+        Err(err) => warn!(?err, "failed to list the available models"),
+    }
+
+    match cached {
+        Some(cached) if !cached.models.is_empty() => cached.models.into_iter().map(Model::from).collect(),
+        _ => MODEL_OPTIONS.iter().map(Model::from).collect(),
+    }
+}
+
+/// Returns the model in `models` with the given name or id, ignoring case.
+pub fn find_model<'a>(models: &'a [Model], name_or_id: &str) -> Option<&'a Model> {
+    models
+        .iter()
+        .find(|model| model.name.eq_ignore_ascii_case(name_or_id) || model.model_id.eq_ignore_ascii_case(name_or_id))
+}
+
+/// Returns the model matching `query`: the model with that name or id, otherwise the first model
+/// whose name contains it, so that e.g. `sonnet` matches `claude-4-sonnet`.
+pub fn resolve_model<'a>(models: &'a [Model], query: &str) -> Option<&'a Model> {
+    let query_lower = query.to_lowercase();
+    find_model(models, query).or_else(|| models.iter().find(|model| model.name.contains(&query_lower)))
+}
+
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
 pub struct ModelArgs;
@@ -51,13 +165,14 @@ impl ModelArgs {
 pub fn select_model(session: &mut ChatSession) -> Result<Option<ChatState>, ChatError> {
     queue!(session.stderr, style::Print("\n"))?;
     let active_model_id = session.conversation.model.as_deref();
-    let labels: Vec<String> = MODEL_OPTIONS
+    let labels: Vec<String> = session
+        .models
         .iter()
-        .map(|opt| {
-            if (opt.model_id.is_empty() && active_model_id.is_none()) || Some(opt.model_id) == active_model_id {
-                format!("{} (active)", opt.name)
+        .map(|model| {
+            if Some(model.model_id.as_str()) == active_model_id {
+                format!("{} (active)", model.name)
             } else {
-                opt.name.to_owned()
+                model.name.clone()
             }
         })
         .collect();
@@ -83,9 +198,9 @@ pub fn select_model(session: &mut ChatSession) -> Result<Option<ChatState>, Chat
     queue!(session.stderr, style::ResetColor)?;
 
     if let Some(index) = selection {
-        let selected = &MODEL_OPTIONS[index];
-        let model_id_str = selected.model_id.to_string();
-        session.conversation.model = Some(model_id_str);
+        let selected = session.models[index].clone();
+        session.conversation.model = Some(selected.model_id);
+        session.conversation.model_chosen = true;
 
         queue!(
             session.stderr,
@@ -104,9 +219,51 @@ pub fn select_model(session: &mut ChatSession) -> Result<Option<ChatState>, Chat
     }))
 }
 
+/// Returns the ids of the models configured with the `chat.modelFallbacks` setting, in order.
+/// Entries may either be model names, such as `claude-3.7-sonnet`, or model ids.
+pub fn fallback_model_ids(os: &Os, models: &[Model]) -> Vec<String> {
+    let entries = match os.database.settings.get(Setting::ChatModelFallbacks) {
+        Some(Value::Array(entries)) => entries.iter().filter_map(Value::as_str).collect::<Vec<_>>(),
+        Some(Value::String(entries)) => entries.split(',').map(str::trim).collect(),
+        _ => vec![],
+    };
+
+    entries
+        .into_iter()
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            find_model(models, entry)
+                .map_or(entry, |model| model.model_id.as_str())
+                .to_string()
+        })
+        .filter(|id| os.database.settings.policy().allows_model(id, None))
+        .collect()
+}
+
+/// Returns the name of `model_id`, or the id itself for models not in `models`.
+pub fn model_name<'a>(models: &'a [Model], model_id: &'a str) -> &'a str {
+    models
+        .iter()
+        .find(|model| model.model_id == model_id)
+        .map_or(model_id, |model| model.name.as_str())
+}
+
 /// Returns Claude 3.7 for: Amazon IDC users, FRA region users
 /// Returns Claude 4.0 for: Builder ID users, other regions
-pub async fn default_model_id(os: &Os) -> &'static str {
+///
+/// If that model is not in `models`, the first of `models` is returned instead.
+pub async fn default_model_id(os: &Os, models: &[Model]) -> String {
+    let preferred = preferred_model_id(os).await;
+    match models.iter().any(|model| model.model_id == preferred) {
+        true => preferred.to_string(),
+        false => models
+            .first()
+            .map_or(preferred, |model| model.model_id.as_str())
+            .to_string(),
+    }
+}
+
+async fn preferred_model_id(os: &Os) -> &'static str {
     // Check FRA region first
     if let Ok(Some(profile)) = os.database.get_auth_profile() {
         if profile.arn.split(':').nth(3) == Some("eu-central-1") {
@@ -124,3 +281,110 @@ pub async fn default_model_id(os: &Os) -> &'static str {
     // Default to 4.0
     "CLAUDE_SONNET_4_20250514_V1_0"
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_available_models() {
+        let os = Os::new().await.unwrap();
+        let models = available_models(&os).await;
+        assert_eq!(models[1].name, "claude-3.7-sonnet");
+        assert_eq!(os.database.get_cached_models().unwrap().unwrap().models.len(), 2);
+
+        // Cached models are used until they expire.
+        let region = Endpoint::DEFAULT_ENDPOINT.region.to_string();
+        os.database
+            .set_cached_models(&CachedModels {
+                fetched_at: OffsetDateTime::now_utc().unix_timestamp(),
+                profile_arn: None,
+                region: region.clone(),
+                models: vec![ModelInfo {
+                    model_id: "NEW_MODEL_V1_0".to_string(),
+                    description: None,
+                }],
+            })
+            .unwrap();
+        let models = available_models(&os).await;
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].name, "NEW_MODEL_V1_0");
+        assert_eq!(find_model(&models, "new_model_v1_0"), Some(&models[0]));
+        assert_eq!(resolve_model(&models, "new_model"), None);
+        assert_eq!(default_model_id(&os, &models).await, "NEW_MODEL_V1_0");
+
+        os.database
+            .set_cached_models(&CachedModels {
+                fetched_at: 0,
+                profile_arn: None,
+                region: region.clone(),
+                models: vec![],
+            })
+            .unwrap();
+        assert_eq!(available_models(&os).await.len(), 2);
+
+        // Models listed for another profile or region are not used.
+        for (profile_arn, region) in [
+            (
+                Some("arn:aws:codewhisperer:us-east-1:123456789012:profile/OTHER"),
+                region.as_str(),
+            ),
+            (None, "eu-central-1"),
+        ] {
+            os.database
+                .set_cached_models(&CachedModels {
+                    fetched_at: OffsetDateTime::now_utc().unix_timestamp(),
+                    profile_arn: profile_arn.map(str::to_string),
+                    region: region.to_string(),
+                    models: vec![ModelInfo {
+                        model_id: "OTHER_MODEL_V1_0".to_string(),
+                        description: None,
+                    }],
+                })
+                .unwrap();
+            assert_eq!(available_models(&os).await.len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_model_ids() {
+        let mut os = Os::new().await.unwrap();
+        let models = MODEL_OPTIONS.iter().map(Model::from).collect::<Vec<_>>();
+        assert!(fallback_model_ids(&os, &models).is_empty());
+
+        os.database
+            .settings
+            .set(
+                Setting::ChatModelFallbacks,
+                serde_json::json!(["claude-3.7-sonnet", "CUSTOM_MODEL_ID", ""]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(fallback_model_ids(&os, &models), vec![
+            "CLAUDE_3_7_SONNET_20250219_V1_0".to_string(),
+            "CUSTOM_MODEL_ID".to_string()
+        ]);
+
+        os.database
+            .settings
+            .set(Setting::ChatModelFallbacks, "claude-4-sonnet, claude-3.7-sonnet")
+            .await
+            .unwrap();
+        assert_eq!(fallback_model_ids(&os, &models), vec![
+            "CLAUDE_SONNET_4_20250514_V1_0".to_string(),
+            "CLAUDE_3_7_SONNET_20250219_V1_0".to_string()
+        ]);
+        assert_eq!(
+            model_name(&models, "CLAUDE_3_7_SONNET_20250219_V1_0"),
+            "claude-3.7-sonnet"
+        );
+        assert_eq!(model_name(&models, "CUSTOM_MODEL_ID"), "CUSTOM_MODEL_ID");
+        assert_eq!(resolve_model(&models, "sonnet"), Some(&models[0]));
+        assert_eq!(resolve_model(&models, "3.7"), Some(&models[1]));
+        assert_eq!(
+            resolve_model(&models, "CLAUDE_3_7_SONNET_20250219_V1_0"),
+            Some(&models[1])
+        );
+        assert_eq!(resolve_model(&models, "opus"), None);
+    }
+}
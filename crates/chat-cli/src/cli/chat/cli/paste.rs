@@ -0,0 +1,113 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+
+use crate::cli::chat::consts::{
+    MAX_IMAGE_SIZE,
+    MAX_NUMBER_OF_IMAGES_PER_REQUEST,
+};
+use crate::cli::chat::util::clipboard;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct PasteArgs {
+    /// Prompt to submit along with the clipboard content
+    pub prompt: Vec<String>,
+}
+
+impl PasteArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let prompt = self.prompt.join(" ");
+
+        match clipboard::read_image() {
+            Ok(Some(png)) if png.len() > MAX_IMAGE_SIZE => {
+                return print_error(
+                    session,
+                    &format!(
+                        "The clipboard image exceeds the size limit ({}MB).",
+                        MAX_IMAGE_SIZE / (1024 * 1024)
+                    ),
+                );
+            },
+            Ok(Some(_)) if session.pending_images.len() >= MAX_NUMBER_OF_IMAGES_PER_REQUEST => {
+                return print_error(
+                    session,
+                    &format!("At most {MAX_NUMBER_OF_IMAGES_PER_REQUEST} images can be attached to a prompt."),
+                );
+            },
+            Ok(Some(png)) => {
+                let image = clipboard::image_block_from_png(png, session.pending_images.len() + 1);
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format!(
+                        "\nAttached {} ({:.2} KB) from the clipboard.",
+                        image.1.filename,
+                        image.1.size as f64 / 1024.0
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                session.pending_images.push(image);
+
+                if prompt.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::Print(" It will be sent with your next prompt.\n\n")
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                }
+                execute!(session.stderr, style::Print("\n\n"))?;
+                return Ok(ChatState::HandleInput { input: prompt });
+            },
+            // Fall back to text if no image could be read.
+            Ok(None) | Err(_) => (),
+        }
+
+        let text = match clipboard::read_text() {
+            Ok(text) if !text.trim().is_empty() => text,
+            Ok(_) => return print_error(session, "The clipboard is empty."),
+            Err(err) => return print_error(session, &format!("Failed to read the clipboard: {err}")),
+        };
+        let input = match prompt.is_empty() {
+            true => text,
+            false => format!("{prompt}\n\n{text}"),
+        };
+
+        // Display the content as if the user typed it
+        execute!(
+            session.stderr,
+            style::Print("\n"),
+            style::SetAttribute(Attribute::Reset),
+            style::SetForegroundColor(Color::Magenta),
+            style::Print("> "),
+            style::SetAttribute(Attribute::Reset),
+            style::Print(&input),
+            style::Print("\n")
+        )?;
+
+        Ok(ChatState::HandleInput { input })
+    }
+}
+
+fn print_error(session: &mut ChatSession, message: &str) -> Result<ChatState, ChatError> {
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Red),
+        style::Print(format!("\n{message}\n\n")),
+        style::SetForegroundColor(Color::Reset)
+    )?;
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
@@ -51,6 +51,8 @@ impl PersistSubcommand {
 
         match self {
             Self::Save { path, force } => {
+                // The entries left out when resuming the conversation are exported as well.
+                tri!(session.conversation.hydrate_history(os), "export to", &path);
                 let contents = tri!(serde_json::to_string_pretty(&session.conversation), "export to", &path);
                 if os.fs.exists(&path) && !force {
                     execute!(
@@ -0,0 +1,141 @@
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use crate::cli::chat::util::truncate_safe;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+/// Maximum number of bytes of a pinned prompt or response shown by `/pin list`.
+const PREVIEW_LENGTH: usize = 120;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    args_conflicts_with_subcommands = true,
+    before_long_help = "Pinned exchanges are sent as context with every request, so they are kept
+verbatim when the conversation history is truncated or compacted.
+
+Exchanges are numbered from 1, starting with the oldest prompt in the history."
+)]
+pub struct PinArgs {
+    /// Number of the exchange to pin. Defaults to the last exchange
+    index: Option<usize>,
+    #[command(subcommand)]
+    subcommand: Option<PinSubcommand>,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum PinSubcommand {
+    /// List the pinned exchanges
+    List,
+    /// Unpin an exchange
+    #[command(alias = "rm")]
+    Remove {
+        /// Number of the pinned exchange, as shown by /pin list
+        index: usize,
+    },
+}
+
+impl PinArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self.subcommand {
+            None => match session.conversation.pin_exchange(self.index) {
+                Ok(exchange) => {
+                    let prompt = preview(&exchange.prompt);
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!("\nPinned: {prompt}\n\n")),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                },
+                Err(err) => print_error(session, &err)?,
+            },
+            Some(PinSubcommand::List) => {
+                if session.conversation.pinned.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("\nNo exchanges are pinned. Use /pin to pin the last exchange.\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                } else {
+                    queue!(session.stderr, style::Print("\n"))?;
+                    for (i, exchange) in session.conversation.pinned.iter().enumerate() {
+                        queue!(
+                            session.stderr,
+                            style::SetAttribute(Attribute::Bold),
+                            style::Print(format!("{}. ", i + 1)),
+                            style::SetAttribute(Attribute::Reset),
+                            style::Print(format!("> {}\n", preview(&exchange.prompt))),
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!("   {}\n", preview(&exchange.response))),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+            },
+            Some(PinSubcommand::Remove { index }) => {
+                let pinned = &mut session.conversation.pinned;
+                if index == 0 || index > pinned.len() {
+                    print_error(session, &format!("Pinned exchange {index} does not exist."))?;
+                } else {
+                    pinned.remove(index - 1);
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!("\nUnpinned exchange {index}.\n\n")),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        match self.subcommand {
+            Some(PinSubcommand::List) => Some("list"),
+            Some(PinSubcommand::Remove { .. }) => Some("remove"),
+            None => None,
+        }
+    }
+}
+
+/// The first line of `text`, shortened to [PREVIEW_LENGTH].
+fn preview(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    match truncate_safe(line, PREVIEW_LENGTH) {
+        truncated if truncated.len() < text.len() => format!("{truncated}…"),
+        truncated => truncated.to_string(),
+    }
+}
+
+fn print_error(session: &mut ChatSession, message: &str) -> Result<(), ChatError> {
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Red),
+        style::Print(format!("\n{message}\n\n")),
+        style::SetForegroundColor(Color::Reset)
+    )?;
+    Ok(())
+}
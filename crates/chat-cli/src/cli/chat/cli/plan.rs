@@ -0,0 +1,101 @@
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct PlanArgs {
+    #[command(subcommand)]
+    subcommand: Option<PlanSubcommand>,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum PlanSubcommand {
+    /// Discard the current plan
+    Clear,
+}
+
+impl PlanArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self.subcommand {
+            Some(PlanSubcommand::Clear) => {
+                session.conversation.plan = None;
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("\nPlan cleared.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            None => match &session.conversation.plan {
+                Some(plan) => {
+                    let (completed, total) = plan.progress();
+                    queue!(
+                        session.stderr,
+                        style::Print("\n"),
+                        style::SetAttribute(Attribute::Bold),
+                        style::Print("Plan"),
+                        style::SetAttribute(Attribute::Reset),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!(" ({completed}/{total} completed)\n")),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    if let Some(explanation) = &plan.explanation {
+                        queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!("{explanation}\n")),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                    plan.queue_checklist(&mut session.stderr)
+                        .map_err(|e| ChatError::Custom(e.to_string().into()))?;
+                    execute!(session.stderr, style::Print("\n"))?;
+                },
+                None => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(
+                            "\nNo plan has been recorded for this conversation. Q creates one for multi-step tasks.\n\n"
+                        ),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                },
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|s| s.name())
+    }
+}
+
+impl PlanSubcommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Clear => "clear",
+        }
+    }
+}
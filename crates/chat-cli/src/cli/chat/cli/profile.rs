@@ -28,6 +28,9 @@ use crate::cli::agent::{
     create_agent,
     rename_agent,
 };
+use crate::cli::chat::cli::model::find_model;
+use crate::cli::chat::context::ContextManager;
+use crate::cli::chat::tool_manager::ToolManagerBuilder;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
@@ -46,6 +49,7 @@ Notes
 • Construct an agent under ~/.aws/amazonq/agents/ (accessible globally) or cwd/.aws/amazonq/agents (accessible in workspace)
 • See example config under global directory
 • Set default agent to assume with settings by running \"q settings chat.defaultAgent agent_name\"
+• Switch agents without leaving the conversation with /agent switch
 • Each agent maintains its own set of context and customizations"
 )]
 pub enum AgentSubcommand {
@@ -67,9 +71,13 @@ pub enum AgentSubcommand {
     /// Delete the specified agent
     #[command(hide = true)]
     Delete { name: String },
-    /// Switch to the specified agent
-    #[command(hide = true)]
-    Set { name: String },
+    /// Switch to the specified agent, keeping the conversation. Its MCP servers, tool permissions,
+    /// and context files replace the ones of the current agent
+    #[command(alias = "set")]
+    Switch {
+        /// Name of the agent to switch to
+        name: String,
+    },
     /// Rename an agent. Should this be the current active agent, its changes will take effect upon
     /// next launch
     Rename {
@@ -199,11 +207,31 @@ impl AgentSubcommand {
                     style::SetForegroundColor(Color::Reset)
                 )?;
             },
-            Self::Set { .. } | Self::Delete { .. } => {
+            Self::Switch { name } => {
+                if agents.active_idx == name {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Yellow),
+                        style::Print(format!("\nAlready using agent {name}\n\n")),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                } else if let Err(err) = switch_agent(os, session, &name).await {
+                    _print_err!(err);
+                } else {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print("\n✔ Switched to agent "),
+                        style::SetForegroundColor(Color::Cyan),
+                        style::Print(&name),
+                        style::SetForegroundColor(Color::Reset),
+                        style::Print("\n\n"),
+                    )?;
+                }
+            },
+            Self::Delete { .. } => {
                 // As part of the agent implementation, we are disabling the ability to
-                // switch / create profile after a session has started.
-                // TODO: perhaps revive this after we have a decision on profile create /
-                // switch
+                // delete agents after a session has started.
                 let global_path = if let Ok(path) = chat_global_agent_path(os) {
                     path.to_str().unwrap_or("default global agent path").to_string()
                 } else {
@@ -213,7 +241,7 @@ impl AgentSubcommand {
                     session.stderr,
                     style::SetForegroundColor(Color::Yellow),
                     style::Print(format!(
-                        "To make changes or create agents, please do so via create the corresponding config in {}, where you would also find an example config for your reference.\nTo switch agent, launch another instance of q chat with --agent.\n\n",
+                        "To make changes or create agents, please do so via create the corresponding config in {}, where you would also find an example config for your reference.\n\n",
                         global_path
                     )),
                     style::SetAttribute(Attribute::Reset)
@@ -231,14 +259,86 @@ impl AgentSubcommand {
             Self::List => "list",
             Self::Create { .. } => "create",
             Self::Delete { .. } => "delete",
-            Self::Set { .. } => "set",
+            Self::Switch { .. } => "switch",
             Self::Rename { .. } => "rename",
             Self::Schema => "schema",
         }
     }
 }
 
-fn highlight_json(output: &mut impl Write, json_str: &str) -> eyre::Result<()> {
+/// Makes `name` the active agent of the session, reloading its config from disk. The MCP servers
+/// are restarted with the ones of the agent, and the context files of the previous agent are
+/// replaced, while the history is kept.
+async fn switch_agent(os: &mut Os, session: &mut ChatSession, name: &str) -> Result<(), ChatError> {
+    let agents = &session.conversation.agents;
+    let Some(agent) = agents.agents.get(name) else {
+        return Err(ChatError::Custom(
+            format!("No agent named {name}. Run /agent list to see the available agents").into(),
+        ));
+    };
+    let previous = agents.get_active().cloned().unwrap_or_default();
+    let agent = match &agent.path {
+        Some(path) => Agent::load(os, path, &mut None)
+            .await
+            .map_err(|e| ChatError::Custom(e.to_string().into()))?,
+        None => agent.clone(),
+    };
+
+    let mut tool_manager = ToolManagerBuilder::default()
+        .conversation_id(session.conversation.conversation_id())
+        .agent(agent.clone())
+        .build(os, Box::new(session.stderr.duplicate()), session.interactive)
+        .await
+        .map_err(|e| ChatError::Custom(format!("Failed to start the MCP servers: {e}").into()))?;
+    tool_manager
+        .load_tools(os, &mut session.stderr)
+        .await
+        .map_err(|e| ChatError::Custom(format!("Failed to load the tools: {e}").into()))?;
+
+    // The history is reconciled with the tools of the agent before switching to it, so that
+    // aborting keeps the previous agent and its MCP servers.
+    let previous_tool_manager = std::mem::replace(&mut session.conversation.tool_manager, tool_manager);
+    session.conversation.update_state(true).await;
+    let reconciled = match session.interactive {
+        true => session.reconcile_unavailable_tools(os).await,
+        false => Ok(true),
+    };
+    if !matches!(reconciled, Ok(true)) {
+        session.conversation.tool_manager = previous_tool_manager;
+        session.conversation.update_state(true).await;
+        reconciled?;
+        return Err(ChatError::Custom(
+            format!("Kept agent {}, switching to {name} was aborted", previous.name).into(),
+        ));
+    }
+
+    let conversation = &mut session.conversation;
+    conversation
+        .tool_manager
+        .take_over_prompt_completion(&previous_tool_manager);
+    conversation.agents.agents.insert(name.to_string(), agent.clone());
+    conversation
+        .agents
+        .switch(name)
+        .map_err(|e| ChatError::Custom(e.to_string().into()))?;
+    match conversation.context_manager.as_mut() {
+        Some(cm) => cm.switch_agent(&previous, &agent),
+        None => conversation.context_manager = ContextManager::from_agent(&agent, None).ok(),
+    }
+    // A model chosen by the user is kept over the default of the agent.
+    if let Some(model) = agent
+        .model
+        .as_deref()
+        .filter(|_| !conversation.model_chosen)
+        .and_then(|model| find_model(&session.models, model))
+    {
+        conversation.model = Some(model.model_id.clone());
+    }
+    conversation.append_transcript(format!("--- Switched from agent {} to {name} ---", previous.name));
+    Ok(())
+}
+
+pub fn highlight_json(output: &mut impl Write, json_str: &str) -> eyre::Result<()> {
     let ps = SyntaxSet::load_defaults_newlines();
     let ts = ThemeSet::load_defaults();
 
@@ -0,0 +1,112 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+use regex::Regex;
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "Removes text from the conversation, e.g. a secret pasted by accident, before the next request
+is sent. Every occurrence is replaced with [REDACTED] in the history, including tool uses and
+their results, and in the transcript, summary, pins, and the stored conversation.
+
+Use /redact last to remove the last prompt. Responses already received and conversations
+exported with /save are not changed."
+)]
+pub struct RedactArgs {
+    /// Text to remove, or "last" for the last prompt
+    #[arg(required = true)]
+    text: Vec<String>,
+    /// Interpret the text as a regular expression
+    #[arg(long)]
+    regex: bool,
+}
+
+impl RedactArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let pattern = match self.pattern(session) {
+            Ok(pattern) => pattern,
+            Err(err) => return print_error(session, &err),
+        };
+
+        // Older stored entries are redacted too, since the stored history is rewritten.
+        if let Err(err) = session.conversation.hydrate_history(os) {
+            return print_error(session, &format!("Failed to load the stored history: {err}"));
+        }
+        let count = session.conversation.redact_matches(&pattern);
+        if count == 0 {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("\nNothing in the conversation matches.\n\n"),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        // A request sent ahead of time still holds the text.
+        if let Some(prefetch) = session.prefetch.take() {
+            prefetch.cancel_token.cancel();
+        }
+        if let Err(err) = session.conversation.save(os) {
+            return print_error(session, &format!("Failed to save the redacted conversation: {err}"));
+        }
+
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!(
+                "\nRedacted {count} {} from the conversation.\n\n",
+                if count == 1 { "occurrence" } else { "occurrences" }
+            )),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    fn pattern(&self, session: &ChatSession) -> Result<Regex, String> {
+        let text = self.text.join(" ");
+        let pattern = if self.regex {
+            Regex::new(&text).map_err(|err| format!("Invalid regular expression: {err}"))?
+        } else if text == "last" {
+            let prompt = session
+                .conversation
+                .last_prompt()
+                .ok_or("There is no prompt to redact yet.")?;
+            Regex::new(&regex::escape(prompt)).map_err(|err| err.to_string())?
+        } else {
+            Regex::new(&regex::escape(&text)).map_err(|err| err.to_string())?
+        };
+
+        if pattern.is_match("") {
+            return Err("The text to redact cannot be empty.".to_string());
+        }
+        Ok(pattern)
+    }
+}
+
+fn print_error(session: &mut ChatSession, err: &str) -> Result<ChatState, ChatError> {
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Red),
+        style::Print(format!("\n{err}\n\n")),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
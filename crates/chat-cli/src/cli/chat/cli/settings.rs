@@ -0,0 +1,134 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+use serde_json::{
+    Value,
+    json,
+};
+
+use crate::cli::chat::prompt::edit_mode;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "Shows and changes the same settings as q settings. Changes take effect immediately, including in this session.
+
+Values are parsed as JSON when possible, so true, 10 and [\"a\"] are a boolean, a number and a list."
+)]
+pub struct SettingsArgs {
+    /// Setting to show or change. All settings that are set are listed if omitted
+    key: Option<String>,
+    /// Value to set the setting to
+    value: Option<String>,
+    /// Remove the setting, restoring its default
+    #[arg(long, short, conflicts_with = "value", requires = "key")]
+    delete: bool,
+}
+
+impl SettingsArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let Some(key) = self.key else {
+            let settings = os.database.settings.map();
+            let text = match settings.is_empty() {
+                true => "No settings are set.".to_string(),
+                false => settings
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{key} = {value}{}",
+                            locked_marker(os, Setting::try_from(key.as_str()).ok())
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            execute!(session.stderr, style::Print(format!("\n{text}\n\n")))?;
+            return Ok(prompt_user());
+        };
+
+        let setting = match Setting::try_from(key.as_str()) {
+            Ok(setting) => setting,
+            Err(_) => {
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!(
+                        "\nUnknown setting {key}. Press tab after /settings to list them.\n\n"
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                return Ok(prompt_user());
+            },
+        };
+
+        let message = match (self.value, self.delete) {
+            (None, false) => match os.database.settings.get(setting) {
+                Some(value) => format!("{setting} = {value}{}", locked_marker(os, Some(setting))),
+                None => format!("{setting} is not set{}", locked_marker(os, Some(setting))),
+            },
+            (Some(value), _) => {
+                let value = parse_value(&value);
+                let message = format!("Set {setting} to {value}");
+                os.database
+                    .settings
+                    .set(setting, value)
+                    .await
+                    .map_err(|err| ChatError::Custom(format!("Failed to save settings: {err}").into()))?;
+                apply(os, session, setting);
+                message
+            },
+            (None, true) => match os
+                .database
+                .settings
+                .remove(setting)
+                .await
+                .map_err(|err| ChatError::Custom(format!("Failed to save settings: {err}").into()))?
+            {
+                Some(_) => {
+                    apply(os, session, setting);
+                    format!("Removed {setting}")
+                },
+                None => format!("{setting} is not set"),
+            },
+        };
+        execute!(session.stderr, style::Print(format!("\n{message}\n\n")))?;
+
+        Ok(prompt_user())
+    }
+}
+
+/// Returns the note shown after settings that cannot be changed because of the managed policy.
+fn locked_marker(os: &Os, setting: Option<Setting>) -> &'static str {
+    match setting.is_some_and(|setting| os.database.settings.is_locked(setting)) {
+        true => " (locked by policy)",
+        false => "",
+    }
+}
+
+/// Applies a changed setting to the session, for settings read when the session starts.
+fn apply(os: &Os, session: &mut ChatSession, setting: Setting) {
+    if let Setting::ChatEditMode = setting {
+        session.input_source.set_edit_mode(edit_mode(os));
+    }
+}
+
+fn prompt_user() -> ChatState {
+    ChatState::PromptUser {
+        skip_printing_tools: true,
+    }
+}
+
+/// Parses a value as JSON, falling back to a string as `q settings` does.
+fn parse_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| json!(value))
+}
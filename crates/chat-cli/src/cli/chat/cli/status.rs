@@ -0,0 +1,118 @@
+use clap::Args;
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use crate::cli::chat::cli::model::model_name;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::telemetry::TelemetryMode;
+use crate::util::directories;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct StatusArgs;
+
+impl StatusArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let agent = session
+            .conversation
+            .agents
+            .get_active()
+            .map_or("none".to_string(), |a| a.name.clone());
+        let model = session
+            .conversation
+            .model
+            .as_deref()
+            .map_or("default", |id| model_name(&session.models, id));
+        let trust = if session.all_tools_trusted() {
+            "all tools trusted".to_string()
+        } else {
+            let count = session
+                .conversation
+                .agents
+                .get_active()
+                .map_or(0, |a| a.allowed_tools.len());
+            format!("{count} trusted tool(s)")
+        };
+
+        let telemetry = match (os.telemetry.mode(), directories::local_telemetry_path()) {
+            (TelemetryMode::Local, Ok(path)) => format!("local ({})", path.display()),
+            (mode, _) => mode.to_string(),
+        };
+
+        let generation = session
+            .conversation
+            .agents
+            .get_active()
+            .and_then(|a| a.generation.describe())
+            .map_or("service defaults".to_string(), |parameters| {
+                format!("{parameters} (not supported by the service, not applied)")
+            });
+
+        let title = session.conversation.title.clone().map(|title| ("Title", title));
+
+        queue!(session.stderr, style::Print("\n"))?;
+        for (label, value) in [("Conversation", session.conversation.conversation_id().to_string())]
+            .into_iter()
+            .chain(title)
+            .chain([
+                ("Agent", agent),
+                ("Model", model.to_string()),
+                ("Generation", generation),
+                ("Tools", trust),
+                ("Messages", session.conversation.history().len().to_string()),
+                ("Telemetry", telemetry),
+            ])
+        {
+            queue!(
+                session.stderr,
+                style::SetAttribute(Attribute::Bold),
+                style::Print(format!("{label:<14}")),
+                style::SetAttribute(Attribute::Reset),
+                style::Print(format!("{value}\n")),
+            )?;
+        }
+
+        queue!(
+            session.stderr,
+            style::SetAttribute(Attribute::Bold),
+            style::Print(format!("{:<14}", "Plan")),
+            style::SetAttribute(Attribute::Reset),
+        )?;
+        match &session.conversation.plan {
+            Some(plan) => {
+                let (completed, total) = plan.progress();
+                queue!(
+                    session.stderr,
+                    style::Print(format!("{completed}/{total} steps completed\n")),
+                )?;
+                plan.queue_checklist(&mut session.stderr)
+                    .map_err(|e| ChatError::Custom(e.to_string().into()))?;
+            },
+            None => {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("none\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+        }
+        execute!(session.stderr, style::Print("\n"))?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
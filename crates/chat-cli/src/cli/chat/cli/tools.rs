@@ -26,7 +26,9 @@ use crate::cli::chat::{
     ChatSession,
     ChatState,
     TRUST_ALL_TEXT,
+    tool_stats,
 };
+use crate::os::Os;
 use crate::util::consts::MCP_SERVER_TOOL_DELIMITER;
 
 #[deny(missing_docs)]
@@ -37,9 +39,9 @@ pub struct ToolsArgs {
 }
 
 impl ToolsArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         if let Some(subcommand) = self.subcommand {
-            return subcommand.execute(session).await;
+            return subcommand.execute(os, session).await;
         }
 
         // No subcommand - print the current tools and their permissions.
@@ -195,10 +197,12 @@ pub enum ToolsSubcommand {
     TrustAll,
     /// Reset all tools to default permission levels
     Reset,
+    /// Show how often each tool was used, approved, and failed in this project
+    Stats,
 }
 
 impl ToolsSubcommand {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         // Here we need to obtain the list of host tool names
         let existing_custom_tools = session
             .conversation
@@ -340,9 +344,17 @@ impl ToolsSubcommand {
                     )?;
                 }
             },
-            Self::TrustAll => {
-                session.conversation.agents.trust_all_tools = true;
-                queue!(session.stderr, style::Print(TRUST_ALL_TEXT))?;
+            Self::TrustAll => match os.database.settings.policy().disable_trust_all {
+                true => queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print("\nTrusting all tools is disabled by the managed policy.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
+                false => {
+                    session.conversation.agents.trust_all_tools = true;
+                    queue!(session.stderr, style::Print(TRUST_ALL_TEXT))?;
+                },
             },
             Self::Reset => {
                 session.conversation.agents.trust_all_tools = false;
@@ -381,6 +393,32 @@ impl ToolsSubcommand {
                     style::SetForegroundColor(Color::Reset),
                 )?;
             },
+            Self::Stats => {
+                let dir = os.env.current_dir()?;
+                let stats = tool_stats::load(os, &dir);
+                if stats.tools.is_empty() {
+                    queue!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("\nNo tool uses recorded in {} yet.\n", dir.display())),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                } else {
+                    queue!(session.stderr, style::Print(format!("\n{}", stats.to_table())))?;
+                    let suggested = stats.suggested_allowed_tools();
+                    if !suggested.is_empty() {
+                        queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!(
+                                "\nAlways approved when asked, consider adding them to allowedTools: {}\n",
+                                suggested.join(", ")
+                            )),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                }
+            },
         };
 
         session.stderr.flush()?;
@@ -397,6 +435,7 @@ impl ToolsSubcommand {
             ToolsSubcommand::Untrust { .. } => "untrust",
             ToolsSubcommand::TrustAll => "trust-all",
             ToolsSubcommand::Reset => "reset",
+            ToolsSubcommand::Stats => "stats",
         }
     }
 }
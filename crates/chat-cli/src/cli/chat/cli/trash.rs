@@ -0,0 +1,126 @@
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    self,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+use time::OffsetDateTime;
+
+use crate::cli::chat::trash::{
+    self,
+    TrashEntry,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "While all tools are trusted, copies of the files that fs_write and rename_symbol change and
+that execute_bash removes with rm are kept under .amazonq/trash. They are removed after
+chat.trashRetentionDays days, 7 by default, and not kept at all when it is 0."
+)]
+pub struct TrashArgs {
+    #[command(subcommand)]
+    subcommand: Option<TrashSubcommand>,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum TrashSubcommand {
+    /// List the files kept in the trash
+    List,
+    /// Put a file of the trash back where it was
+    Restore {
+        /// Number of the entry, as listed by /trash list
+        id: u64,
+    },
+}
+
+impl TrashArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let root = os.env.current_dir()?;
+        match self.subcommand {
+            Some(TrashSubcommand::List) | None => {
+                let entries = trash::list(os, &root).await;
+                if entries.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("\nThe trash is empty.\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                } else {
+                    queue!(session.stderr, style::Print("\n"))?;
+                    let now = OffsetDateTime::now_utc().unix_timestamp();
+                    for entry in entries.iter().rev() {
+                        queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Green),
+                            style::Print(format!("{:>4}  ", entry.id)),
+                            style::SetForegroundColor(Color::Reset),
+                            style::Print(display_path(&root, &entry.path)),
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!(
+                                "  {} ago, {}\n",
+                                format_age(now - entry.trashed_at),
+                                entry.reason
+                            )),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+            },
+            Some(TrashSubcommand::Restore { id }) => match trash::restore(os, &root, id).await {
+                Ok(TrashEntry { path, .. }) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format!("\nRestored {}.\n\n", display_path(&root, &path))),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
+                Err(err) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("\nFailed to restore entry {id}: {err}\n\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        match &self.subcommand {
+            Some(TrashSubcommand::List) => Some("list"),
+            Some(TrashSubcommand::Restore { .. }) => Some("restore"),
+            None => None,
+        }
+    }
+}
+
+fn display_path(root: &std::path::Path, path: &std::path::Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).display().to_string()
+}
+
+fn format_age(seconds: i64) -> String {
+    match seconds.max(0) {
+        s if s < 60 => format!("{s}s"),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (24 * 60 * 60)),
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use clap::Args;
 use crossterm::style::{
     Attribute,
@@ -9,7 +11,12 @@ use crossterm::{
     style,
 };
 
+use crate::cli::chat::cli::memstats::format_bytes;
 use crate::cli::chat::consts::CONTEXT_WINDOW_SIZE;
+use crate::cli::chat::network_stats::{
+    Histogram,
+    NetworkStats,
+};
 use crate::cli::chat::token_counter::{
     CharCount,
     TokenCount,
@@ -22,10 +29,21 @@ use crate::cli::chat::{
 use crate::os::Os;
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
-pub struct UsageArgs;
+pub struct UsageArgs {
+    /// Show the sizes and latencies of the requests sent during the session
+    #[arg(long)]
+    pub network: bool,
+}
 
 impl UsageArgs {
     pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if self.network {
+            print_network_stats(&mut session.stderr, &session.network_stats)?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
         let state = session
             .conversation
             .backend_conversation_state(os, true, &mut session.stderr)
@@ -183,6 +201,39 @@ impl UsageArgs {
             )),
         )?;
 
+        if let Some(stats) = session
+            .conversation
+            .context_manager
+            .as_ref()
+            .map(|manager| manager.cache_stats())
+            .filter(|stats| stats.file_hits + stats.file_misses > 0)
+        {
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!(
+                    "Context cache: {} of {} file reads skipped, context reused on {} of {} turns\n",
+                    stats.file_hits,
+                    stats.file_hits + stats.file_misses,
+                    stats.block_hits,
+                    stats.block_hits + stats.block_misses,
+                )),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+
+        if let Some(average) = session.network_stats.turns.average() {
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!(
+                    "Turn timings: {average} on average over {} turns\n",
+                    session.network_stats.turns.turns
+                )),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+
         queue!(
             session.stderr,
             style::SetAttribute(Attribute::Bold),
@@ -212,3 +263,95 @@ impl UsageArgs {
         })
     }
 }
+
+fn print_network_stats(output: &mut impl std::io::Write, stats: &NetworkStats) -> Result<(), ChatError> {
+    if stats.requests == 0 {
+        execute!(output, style::Print("\nNo request was sent yet.\n\n"))?;
+        return Ok(());
+    }
+
+    let bytes = |bytes: usize| format_bytes(Some(bytes as u64));
+    let seconds = |duration: Option<Duration>| duration.map_or("-".to_string(), |d| format!("{:.1}s", d.as_secs_f64()));
+    let percentiles = |value: &dyn Fn(&_) -> Option<Duration>| {
+        format!(
+            "p50 {}  p90 {}  max {}",
+            seconds(stats.percentile(50, value)),
+            seconds(stats.percentile(90, value)),
+            seconds(stats.percentile(100, value))
+        )
+    };
+
+    queue!(
+        output,
+        style::SetAttribute(Attribute::Bold),
+        style::Print(format!("\nNetwork ({} requests)\n", stats.requests)),
+        style::SetAttribute(Attribute::Reset),
+        style::Print(format!(
+            "  {:<22}{} total, {} average, {} largest\n",
+            "Sent",
+            format_bytes(Some(stats.bytes_sent)),
+            bytes(stats.bytes_sent as usize / stats.requests),
+            bytes(stats.largest_request().unwrap_or_default())
+        )),
+        style::Print(format!(
+            "  {:<22}{} total, {} average\n",
+            "Received",
+            format_bytes(Some(stats.bytes_received)),
+            bytes(stats.bytes_received as usize / stats.requests)
+        )),
+        style::Print(format!(
+            "  {:<22}{}\n",
+            "Time to first chunk",
+            percentiles(&|s| s.time_to_first_chunk)
+        )),
+        style::Print(format!(
+            "  {:<22}{}\n",
+            "Total duration",
+            percentiles(&|s| Some(s.duration))
+        )),
+    )?;
+
+    print_histogram(output, "Request sizes", &stats.request_sizes, |b| format_bytes(Some(b)))?;
+    print_histogram(output, "Time to first chunk", &stats.first_chunk_latencies, |ms| {
+        format!("{}s", ms / 1000)
+    })?;
+    execute!(output, style::Print("\n"))?;
+    Ok(())
+}
+
+fn print_histogram(
+    output: &mut impl std::io::Write,
+    title: &str,
+    histogram: &Histogram,
+    label: impl Fn(u64) -> String,
+) -> Result<(), ChatError> {
+    const BAR_WIDTH: usize = 30;
+    let max = histogram
+        .buckets()
+        .map(|(_, _, count)| count)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+
+    queue!(
+        output,
+        style::SetAttribute(Attribute::Bold),
+        style::Print(format!("\n{title}\n")),
+        style::SetAttribute(Attribute::Reset),
+    )?;
+    for (lower, upper, count) in histogram.buckets() {
+        let range = match upper {
+            Some(upper) => format!("< {}", label(upper)),
+            None => format!(">= {}", label(lower)),
+        };
+        queue!(
+            output,
+            style::Print(format!("  {range:<12}")),
+            style::SetForegroundColor(Color::DarkCyan),
+            style::Print("█".repeat((count * BAR_WIDTH).div_ceil(max))),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(" {count}\n")),
+        )?;
+    }
+    Ok(())
+}
@@ -1,38 +1,29 @@
-use std::io::{
-    BufReader,
-    Cursor,
-    Write,
-    stdout,
-};
+//! Command selector bound to ctrl + s (see the `fuzzySearch` action of the
+//! [keymap](super::keymap)), which picks a command with the [fuzzy_picker](super::fuzzy_picker),
+//! then its arguments when it takes files, context paths or tools.
 
-use crossterm::execute;
-use crossterm::terminal::{
-    EnterAlternateScreen,
-    LeaveAlternateScreen,
-};
-use eyre::{
-    Result,
-    eyre,
-};
+use std::sync::Arc;
+
+use eyre::Result;
 use rustyline::{
     Cmd,
     ConditionalEventHandler,
     EventContext,
     RepeatCount,
 };
-use skim::prelude::*;
-use tempfile::NamedTempFile;
+use walkdir::WalkDir;
 
 use super::context::ContextManager;
+use super::fuzzy_picker::pick;
 use crate::os::Os;
 
-pub struct SkimCommandSelector {
+pub struct CommandSelector {
     os: Os,
     context_manager: Arc<ContextManager>,
     tool_names: Vec<String>,
 }
 
-impl SkimCommandSelector {
+impl CommandSelector {
     /// This allows the ConditionalEventHandler handle function to be bound to a KeyEvent.
     pub fn new(os: Os, context_manager: Arc<ContextManager>, tool_names: Vec<String>) -> Self {
         Self {
@@ -43,9 +34,9 @@ impl SkimCommandSelector {
     }
 }
 
-impl ConditionalEventHandler for SkimCommandSelector {
+impl ConditionalEventHandler for CommandSelector {
     fn handle(&self, _evt: &rustyline::Event, _n: RepeatCount, _positive: bool, _os: &EventContext<'_>) -> Option<Cmd> {
-        // Launch skim command selector with the context manager if available
+        // Launch the command selector with the context manager if available
         match select_command(&self.os, self.context_manager.as_ref(), &self.tool_names) {
             Ok(Some(command)) => Some(Cmd::Insert(1, command)),
             _ => {
@@ -69,98 +60,55 @@ pub fn get_available_commands() -> Vec<String> {
     commands
 }
 
-/// Format commands for skim display
-/// Create a standard set of skim options with consistent styling
-fn create_skim_options(prompt: &str, multi: bool) -> Result<SkimOptions> {
-    SkimOptionsBuilder::default()
-        .height("100%".to_string())
-        .prompt(prompt.to_string())
-        .reverse(true)
-        .multi(multi)
-        .build()
-        .map_err(|e| eyre!("Failed to build skim options: {}", e))
-}
-
-/// Run skim with the given options and items in an alternate screen
-/// This helper function handles entering/exiting the alternate screen and running skim
-fn run_skim_with_options(options: &SkimOptions, items: SkimItemReceiver) -> Result<Option<Vec<Arc<dyn SkimItem>>>> {
-    // Enter alternate screen to prevent skim output from persisting in terminal history
-    execute!(stdout(), EnterAlternateScreen).map_err(|e| eyre!("Failed to enter alternate screen: {}", e))?;
-
-    let selected_items =
-        Skim::run_with(options, Some(items)).and_then(|out| if out.is_abort { None } else { Some(out.selected_items) });
-
-    execute!(stdout(), LeaveAlternateScreen).map_err(|e| eyre!("Failed to leave alternate screen: {}", e))?;
-
-    Ok(selected_items)
-}
-
-/// Extract string selections from skim items
-fn extract_selections(items: Vec<Arc<dyn SkimItem>>) -> Vec<String> {
-    items.iter().map(|item| item.output().to_string()).collect()
-}
-
-/// Launch skim with the given items and return the selected item
-pub fn launch_skim_selector(items: &[String], prompt: &str, multi: bool) -> Result<Option<Vec<String>>> {
-    let mut temp_file_for_skim_input = NamedTempFile::new()?;
-    temp_file_for_skim_input.write_all(items.join("\n").as_bytes())?;
-
-    let options = create_skim_options(prompt, multi)?;
-    let item_reader = SkimItemReader::default();
-    let items = item_reader.of_bufread(BufReader::new(std::fs::File::open(temp_file_for_skim_input.path())?));
-
-    // Run skim and get selected items
-    match run_skim_with_options(&options, items)? {
-        Some(items) if !items.is_empty() => {
-            let selections = extract_selections(items);
-            Ok(Some(selections))
-        },
-        _ => Ok(None), // User cancelled or no selection
+/// Lists the files under the current directory: those tracked or not ignored by git in a git
+/// repository, and otherwise all files outside of hidden directories.
+fn list_files() -> Vec<String> {
+    let git_files = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+    };
+    if let Some(mut files) = git_files(&["ls-files"]) {
+        files.extend(git_files(&["ls-files", "--others", "--exclude-standard"]).unwrap_or_default());
+        files.sort_unstable();
+        files.dedup();
+        return files;
     }
-}
-
-/// Select files using skim
-pub fn select_files_with_skim() -> Result<Option<Vec<String>>> {
-    // Create skim options with appropriate settings
-    let options = create_skim_options("Select files: ", true)?;
 
-    // Create a command that will be executed by skim
-    // This command checks if git is installed and if we're in a git repo
-    // Otherwise falls back to find command
-    let find_cmd = r#"
-    # Check if git is available and we're in a git repo
-    if command -v git >/dev/null 2>&1 && git rev-parse --is-inside-work-tree &>/dev/null; then
-        # Git repository - respect .gitignore
-        { git ls-files; git ls-files --others --exclude-standard; } | sort | uniq
-    else
-        # Not a git repository or git not installed - use find command
-        find . -type f -not -path '*/\.*'
-    fi
-    "#;
-
-    // Create a command collector that will execute the find command
-    let item_reader = SkimItemReader::default();
-    let items = item_reader.of_bufread(BufReader::new(
-        std::process::Command::new("sh")
-            .args(["-c", find_cmd])
-            .stdout(std::process::Stdio::piped())
-            .spawn()?
-            .stdout
-            .ok_or_else(|| eyre!("Failed to get stdout from command"))?,
-    ));
+    WalkDir::new(".")
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            entry
+                .path()
+                .strip_prefix(".")
+                .ok()
+                .map(|path| path.to_string_lossy().replace('\\', "/"))
+        })
+        .collect()
+}
 
-    // Run skim with the command output as a stream
-    match run_skim_with_options(&options, items)? {
-        Some(items) if !items.is_empty() => {
-            let selections = extract_selections(items);
-            Ok(Some(selections))
-        },
-        _ => Ok(None), // User cancelled or no selection
+/// Select files under the current directory
+pub fn select_files() -> Result<Option<Vec<String>>> {
+    let files = list_files();
+    if files.is_empty() {
+        return Ok(None);
     }
+    pick(&files, "Select files: ", true)
 }
 
-/// Select context paths using skim
-pub fn select_context_paths_with_skim(context_manager: &ContextManager) -> Result<Option<(Vec<String>, bool)>> {
+/// Select context paths to remove
+pub fn select_context_paths(context_manager: &ContextManager) -> Result<Option<(Vec<String>, bool)>> {
     let mut all_paths = Vec::new();
 
     // Get profile-specific paths
@@ -172,18 +120,8 @@ pub fn select_context_paths_with_skim(context_manager: &ContextManager) -> Resul
         return Ok(None); // No paths to select
     }
 
-    // Create skim options
-    let options = create_skim_options("Select paths to remove: ", true)?;
-
-    // Create item reader
-    let item_reader = SkimItemReader::default();
-    let items = item_reader.of_bufread(Cursor::new(all_paths.join("\n")));
-
-    // Run skim and get selected paths
-    match run_skim_with_options(&options, items)? {
-        Some(items) if !items.is_empty() => {
-            let selected_paths = extract_selections(items);
-
+    match pick(&all_paths, "Select paths to remove: ", true)? {
+        Some(selected_paths) if !selected_paths.is_empty() => {
             // Check if any global paths were selected
             let has_global = selected_paths.iter().any(|p| p.starts_with("(global)"));
 
@@ -211,14 +149,14 @@ pub fn select_context_paths_with_skim(context_manager: &ContextManager) -> Resul
 pub fn select_command(_os: &Os, context_manager: &ContextManager, tools: &[String]) -> Result<Option<String>> {
     let commands = get_available_commands();
 
-    match launch_skim_selector(&commands, "Select command: ", false)? {
+    match pick(&commands, "Select command: ", false)? {
         Some(selections) if !selections.is_empty() => {
             let selected_command = &selections[0];
 
             match CommandType::from_str(selected_command) {
                 Some(CommandType::ContextAdd(cmd)) => {
                     // For context add commands, we need to select files
-                    match select_files_with_skim()? {
+                    match select_files()? {
                         Some(files) if !files.is_empty() => {
                             // Construct the full command with selected files
                             let mut cmd = cmd.clone();
@@ -233,7 +171,7 @@ pub fn select_command(_os: &Os, context_manager: &ContextManager, tools: &[Strin
                 },
                 Some(CommandType::ContextRemove(cmd)) => {
                     // For context rm commands, we need to select from existing context paths
-                    match select_context_paths_with_skim(context_manager)? {
+                    match select_context_paths(context_manager)? {
                         Some((paths, has_global)) if !paths.is_empty() => {
                             // Construct the full command with selected paths
                             let mut full_cmd = cmd.clone();
@@ -250,12 +188,9 @@ pub fn select_command(_os: &Os, context_manager: &ContextManager, tools: &[Strin
                     }
                 },
                 Some(CommandType::Tools(_)) => {
-                    let options = create_skim_options("Select tool: ", false)?;
-                    let item_reader = SkimItemReader::default();
-                    let items = item_reader.of_bufread(Cursor::new(tools.join("\n")));
-                    let selected_tool = match run_skim_with_options(&options, items)? {
-                        Some(items) if !items.is_empty() => Some(items[0].output().to_string()),
-                        _ => None,
+                    let selected_tool = match pick(tools, "Select tool: ", false)? {
+                        Some(tools) => tools.into_iter().next(),
+                        None => None,
                     };
 
                     match selected_tool {
@@ -295,7 +230,7 @@ enum CommandType {
 
 impl CommandType {
     fn needs_agent_selection(&self) -> bool {
-        matches!(self, CommandType::Agent("set" | "delete" | "rename"))
+        matches!(self, CommandType::Agent("switch" | "delete" | "rename"))
     }
 
     fn from_str(cmd: &str) -> Option<CommandType> {
@@ -307,7 +242,7 @@ impl CommandType {
             match cmd {
                 "/tools trust" => Some(CommandType::Tools("trust")),
                 "/tools untrust" => Some(CommandType::Tools("untrust")),
-                "/agent set" => Some(CommandType::Agent("set")),
+                "/agent switch" => Some(CommandType::Agent("switch")),
                 "/agent delete" => Some(CommandType::Agent("delete")),
                 "/agent rename" => Some(CommandType::Agent("rename")),
                 "/agent create" => Some(CommandType::Agent("create")),
@@ -336,7 +271,7 @@ mod tests {
             "/context rm",
             "/tools trust",
             "/tools untrust",
-            "/agent set",
+            "/agent switch",
             "/agent delete",
             "/agent rename",
             "/agent create",
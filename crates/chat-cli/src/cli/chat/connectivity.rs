@@ -0,0 +1,53 @@
+//! Probing of the service endpoint, so that prompts that failed while offline are sent once the
+//! connection returns rather than failing.
+
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use crate::api_client::Endpoint;
+use crate::os::Os;
+use crate::request::client_builder;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const FIRST_PROBE_DELAY: Duration = Duration::from_secs(2);
+const MAX_PROBE_DELAY: Duration = Duration::from_secs(30);
+
+/// Sends a request to the configured endpoint, returning how long it took to get a response.
+///
+/// Any response counts, since only reaching the endpoint matters.
+pub async fn probe(os: &Os) -> Result<Duration, reqwest::Error> {
+    let endpoint = Endpoint::configured_value(&os.database);
+    let client = client_builder().build()?;
+    let start = Instant::now();
+    client.get(endpoint.url()).timeout(PROBE_TIMEOUT).send().await?;
+    Ok(start.elapsed())
+}
+
+/// Probes the endpoint with an increasing delay until it can be reached.
+pub async fn wait_until_online(os: &Os) {
+    let mut delays = probe_delays();
+    loop {
+        tokio::time::sleep(delays.next().unwrap_or(MAX_PROBE_DELAY)).await;
+        if probe(os).await.is_ok() {
+            return;
+        }
+    }
+}
+
+/// Delays between probes, doubling from [FIRST_PROBE_DELAY] up to [MAX_PROBE_DELAY].
+fn probe_delays() -> impl Iterator<Item = Duration> {
+    std::iter::successors(Some(FIRST_PROBE_DELAY), |delay| Some((*delay * 2).min(MAX_PROBE_DELAY)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_delays() {
+        let delays = probe_delays().take(6).map(|delay| delay.as_secs()).collect::<Vec<_>>();
+        assert_eq!(delays, vec![2, 4, 8, 16, 30, 30]);
+    }
+}
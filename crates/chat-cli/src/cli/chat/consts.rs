@@ -7,6 +7,19 @@ pub const MAX_CURRENT_WORKING_DIRECTORY_LEN: usize = 256;
 /// Limit to send the number of messages as part of chat.
 pub const MAX_CONVERSATION_STATE_HISTORY_LEN: usize = 250;
 
+/// History entries loaded when resuming a conversation, i.e. as many as can be sent without
+/// trimming the history. The older ones are only loaded when needed.
+pub const RESUMED_HISTORY_LEN: usize = (MAX_CONVERSATION_STATE_HISTORY_LEN - 6) / 2;
+
+/// Default caps for the in-memory bookkeeping of a chat session, overridable through the
+/// `chat.memory.*` settings. The transcript can only be capped below
+/// [MAX_CONVERSATION_STATE_HISTORY_LEN].
+pub const DEFAULT_MAX_FAILED_REQUEST_IDS: usize = 50;
+pub const DEFAULT_MAX_PENDING_TOOL_TELEMETRY_EVENTS: usize = 100;
+
+/// Request size above which the user is warned, overridable through `chat.largeRequestWarningKb`.
+pub const DEFAULT_LARGE_REQUEST_WARNING_KB: usize = 256;
+
 /// Actual service limit is 800_000
 pub const MAX_TOOL_RESPONSE_SIZE: usize = 400_000;
 
@@ -22,6 +35,10 @@ pub const MAX_CHARS: usize = TokenCounter::token_to_chars(CONTEXT_WINDOW_SIZE);
 
 pub const DUMMY_TOOL_NAME: &str = "dummy";
 
+/// Prefix of a prompt sent to another model for the current user turn only, e.g.
+/// `@model:sonnet explain this error`.
+pub const MODEL_OVERRIDE_PREFIX: &str = "@model:";
+
 pub const MAX_NUMBER_OF_IMAGES_PER_REQUEST: usize = 10;
 
 /// In bytes - 10 MB
@@ -1,6 +1,24 @@
 use std::collections::HashMap;
+use std::hash::{
+    DefaultHasher,
+    Hash,
+    Hasher,
+};
 use std::io::Write;
-use std::path::Path;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Stdio;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::{
+    Duration,
+    Instant,
+    SystemTime,
+};
 
 use eyre::{
     Result,
@@ -11,9 +29,18 @@ use serde::{
     Deserialize,
     Serialize,
 };
+use tracing::warn;
 
+use super::auto_context::{
+    AUTO_CONTEXT_TOP_K,
+    WorkspaceIndex,
+};
 use super::consts::CONTEXT_FILES_MAX_SIZE;
-use super::util::drop_matched_context_files;
+use super::token_counter::TokenCounter;
+use super::util::{
+    drop_matched_context_files,
+    truncate_safe_in_place,
+};
 use crate::cli::agent::Agent;
 use crate::cli::agent::hook::{
     Hook,
@@ -23,6 +50,88 @@ use crate::cli::chat::ChatError;
 use crate::cli::chat::cli::hooks::HookExecutor;
 use crate::os::Os;
 
+/// Names of the memory files, instructions for agents kept with a project, in order of
+/// precedence. They are looked for in the current working directory and its parents.
+pub const MEMORY_FILE_NAMES: &[&str] = &["AGENTS.md", "AmazonQ.md"];
+
+/// Time after which a context command is killed.
+const CONTEXT_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A command registered with `/context add-cmd`, whose output is included in the context of every
+/// request like a context file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextCommand {
+    pub command: String,
+    /// Seconds for which the output is reused before the command runs again.
+    pub ttl_secs: u64,
+    /// Bytes of output to keep, beyond which the output is truncated.
+    pub max_size: usize,
+}
+
+impl ContextCommand {
+    /// Name of the output among the context files.
+    pub fn name(&self) -> String {
+        format!("$ {}", self.command)
+    }
+}
+
+/// Context rules of a directory, read from its `.amazonq/context.json`. They apply whenever the
+/// current working directory is the directory or beneath it, e.g. for a subproject of a monorepo.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryContext {
+    /// File paths or glob patterns, relative to the directory.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Whether the rules of parent directories apply as well.
+    #[serde(default = "default_inherit")]
+    pub inherit: bool,
+}
+
+fn default_inherit() -> bool {
+    true
+}
+
+/// Path of the context rules of `dir`, see [DirectoryContext].
+pub fn directory_context_path(dir: &Path) -> PathBuf {
+    dir.join(".amazonq").join("context.json")
+}
+
+/// Hit and miss counts of the context caches, shown by `/usage`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextCacheStats {
+    /// Context files whose content was reused because they were not modified since the last read.
+    pub file_hits: usize,
+    pub file_misses: usize,
+    /// Turns that reused the formatted context files because none of them changed.
+    pub block_hits: usize,
+    pub block_misses: usize,
+}
+
+/// Content of a context file as of its last read.
+#[derive(Debug)]
+struct CachedFile {
+    modified: SystemTime,
+    len: u64,
+    content: String,
+}
+
+#[derive(Debug, Default)]
+struct ContextCache {
+    files: HashMap<PathBuf, CachedFile>,
+    /// Hash of the context files of the last turn and the context formatted from them.
+    block: Option<(u64, String)>,
+    stats: ContextCacheStats,
+}
+
+/// Returns a hash of the names and contents of `files`.
+pub fn context_files_hash(files: &[(String, String)]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    files.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// Manager for context files and profiles.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextManager {
@@ -31,10 +140,28 @@ pub struct ContextManager {
     pub current_profile: String,
     /// List of file paths or glob patterns to include in the context.
     pub paths: Vec<String>,
+    /// Additional project roots that relative context rules, path formatting, and tool
+    /// permissions are resolved against, besides the current working directory.
+    #[serde(default)]
+    pub roots: Vec<PathBuf>,
+    /// Commands whose output is included as context.
+    #[serde(default)]
+    pub commands: Vec<ContextCommand>,
+    /// Output of each command, and when it ran.
+    #[serde(skip)]
+    command_outputs: HashMap<String, (Instant, String)>,
+    /// Whether the files most relevant to each prompt are selected from the workspace as context.
+    #[serde(default)]
+    pub auto: bool,
+    /// Files selected for the last prompt when [Self::auto] is enabled.
+    #[serde(skip)]
+    pub auto_files: Vec<PathBuf>,
     /// Map of Hook Name to [`Hook`]. The hook name serves as the hook's ID.
     pub hooks: HashMap<HookTrigger, Vec<Hook>>,
     #[serde(skip)]
     pub hook_executor: HookExecutor,
+    #[serde(skip)]
+    cache: Arc<Mutex<ContextCache>>,
 }
 
 impl ContextManager {
@@ -50,11 +177,34 @@ impl ContextManager {
             max_context_files_size: max_context_files_size.unwrap_or(CONTEXT_FILES_MAX_SIZE),
             current_profile: agent.name.clone(),
             paths,
+            roots: Vec::new(),
+            commands: Vec::new(),
+            command_outputs: HashMap::new(),
+            auto: false,
+            auto_files: Vec::new(),
             hooks: agent.hooks.clone(),
             hook_executor: HookExecutor::new(),
+            cache: Default::default(),
         })
     }
 
+    /// Swaps the context files and hooks of `previous` for the ones of `agent`, keeping what was
+    /// added during the session: other paths, project roots, commands, and automatic selection.
+    pub fn switch_agent(&mut self, previous: &Agent, agent: &Agent) {
+        let previous_paths = Self::from_agent(previous, None).map(|cm| cm.paths).unwrap_or_default();
+        let mut paths = Self::from_agent(agent, None).map(|cm| cm.paths).unwrap_or_default();
+        for path in self.paths.drain(..) {
+            if !previous_paths.contains(&path) && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        self.paths = paths;
+        self.current_profile = agent.name.clone();
+        self.hooks = agent.hooks.clone();
+        // Spawn hooks of the new agent run with the next prompt.
+        self.hook_executor = HookExecutor::new();
+    }
+
     /// Add paths to the context configuration.
     ///
     /// # Arguments
@@ -68,13 +218,23 @@ impl ContextManager {
         if !force {
             let mut context_files = Vec::new();
 
-            // Check each path to make sure it exists or matches at least one file
+            // Check each path to make sure it exists or matches at least one file under any root
+            let base_dirs = self.base_dirs(os)?;
             for path in &paths {
                 // We're using a temporary context_files vector just for validation
                 // Pass is_validation=true to ensure we error if glob patterns don't match any files
-                match process_path(os, path, &mut context_files, true).await {
-                    Ok(_) => {}, // Path is valid
-                    Err(e) => return Err(eyre!("Invalid path '{}': {}. Use --force to add anyway.", path, e)),
+                let mut error = None;
+                for base_dir in &base_dirs {
+                    match process_path(os, path, base_dir, &mut context_files, true, &self.cache).await {
+                        Ok(_) => {
+                            error = None;
+                            break;
+                        },
+                        Err(e) => error = error.or(Some(e)),
+                    }
+                }
+                if let Some(e) = error {
+                    return Err(eyre!("Invalid path '{}': {}. Use --force to add anyway.", path, e));
                 }
             }
         }
@@ -90,6 +250,36 @@ impl ContextManager {
         Ok(())
     }
 
+    /// Registers an additional project root.
+    pub async fn add_root(&mut self, os: &Os, path: &str) -> Result<PathBuf> {
+        let root = resolve_root(os, path)?;
+        if !os.fs.exists(&root) {
+            return Err(eyre!("Path '{}' does not exist", root.display()));
+        }
+        if self.roots.contains(&root) {
+            return Err(eyre!("Root '{}' already exists.", root.display()));
+        }
+        self.roots.push(root.clone());
+        Ok(root)
+    }
+
+    /// Unregisters a project root.
+    pub fn remove_root(&mut self, os: &Os, path: &str) -> Result<PathBuf> {
+        let root = resolve_root(os, path)?;
+        let Some(index) = self.roots.iter().position(|r| *r == root) else {
+            return Err(eyre!("Root '{}' is not registered", root.display()));
+        };
+        self.roots.remove(index);
+        Ok(root)
+    }
+
+    /// The directories that relative context rules are resolved against.
+    fn base_dirs(&self, os: &Os) -> Result<Vec<PathBuf>> {
+        let mut dirs = vec![os.env.current_dir()?];
+        dirs.extend(self.roots.iter().cloned());
+        Ok(dirs)
+    }
+
     /// Remove paths from the context configuration.
     ///
     /// # Arguments
@@ -109,9 +299,56 @@ impl ContextManager {
         Ok(())
     }
 
-    /// Clear all paths from the context configuration.
+    /// Clear all paths and commands from the context configuration.
     pub fn clear(&mut self) {
         self.paths.clear();
+        self.commands.clear();
+        self.command_outputs.clear();
+    }
+
+    /// Registers a command whose output is included as context.
+    pub fn add_command(&mut self, command: ContextCommand) -> Result<()> {
+        if command.command.trim().is_empty() {
+            return Err(eyre!("The command is empty"));
+        }
+        if self.commands.iter().any(|c| c.command == command.command) {
+            return Err(eyre!("Command '{}' already exists.", command.command));
+        }
+        self.commands.push(command);
+        Ok(())
+    }
+
+    /// Unregisters a command added with [Self::add_command].
+    pub fn remove_command(&mut self, command: &str) -> Result<()> {
+        let Some(index) = self.commands.iter().position(|c| c.command == command) else {
+            return Err(eyre!("Command '{}' was not found in the context", command));
+        };
+        self.commands.remove(index);
+        self.command_outputs.remove(command);
+        Ok(())
+    }
+
+    /// Returns the output of every registered command as (name, output) pairs, running the
+    /// commands whose output is older than their TTL.
+    pub async fn get_command_outputs(&mut self) -> Vec<(String, String)> {
+        let mut outputs = Vec::new();
+        for command in &self.commands {
+            let cached = self
+                .command_outputs
+                .get(&command.command)
+                .filter(|(ran_at, _)| ran_at.elapsed() < Duration::from_secs(command.ttl_secs));
+            let output = match cached {
+                Some((_, output)) => output.clone(),
+                None => {
+                    let output = run_context_command(command).await;
+                    self.command_outputs
+                        .insert(command.command.clone(), (Instant::now(), output.clone()));
+                    output
+                },
+            };
+            outputs.push((command.name(), output));
+        }
+        outputs
     }
 
     /// Get all context files (global + profile-specific).
@@ -126,36 +363,108 @@ impl ContextManager {
     /// # Returns
     /// A Result containing a vector of (filename, content) pairs or an error
     pub async fn get_context_files(&self, os: &Os) -> Result<Vec<(String, String)>> {
+        let mut context_files = self.get_memory_files(os).await?;
+        context_files.extend(self.get_other_context_files(os).await?);
+        Ok(context_files)
+    }
+
+    async fn get_memory_files(&self, os: &Os) -> Result<Vec<(String, String)>> {
+        let mut files = Vec::new();
+        for path in memory_files(os)? {
+            add_file_to_context(os, &path, &mut files, &self.cache).await?;
+        }
+        Ok(files)
+    }
+
+    /// Returns the context files other than the memory files, which are left out when also
+    /// matched by a path.
+    async fn get_other_context_files(&self, os: &Os) -> Result<Vec<(String, String)>> {
+        let memory_files = memory_files(os)?
+            .into_iter()
+            .flat_map(|path| [path.to_string_lossy().to_string(), os.fs.chroot_path_str(&path)])
+            .collect::<Vec<_>>();
         let mut context_files = Vec::new();
 
         self.collect_context_files(os, &self.paths, &mut context_files).await?;
+        for (dir, context) in self.directory_contexts(os).await? {
+            for path in &context.paths {
+                process_path(os, path, &dir, &mut context_files, false, &self.cache).await?;
+            }
+        }
+        for path in &self.auto_files {
+            if os.fs.exists(path) {
+                add_file_to_context(os, path, &mut context_files, &self.cache).await?;
+            }
+        }
 
         context_files.sort_by(|a, b| a.0.cmp(&b.0));
         context_files.dedup_by(|a, b| a.0 == b.0);
+        context_files.retain(|(filename, _)| !memory_files.contains(filename));
 
         Ok(context_files)
     }
 
+    /// Enables or disables the selection of relevant files for each prompt.
+    pub fn set_auto(&mut self, auto: bool) {
+        self.auto = auto;
+        self.auto_files.clear();
+    }
+
+    /// Selects the files of the workspace most relevant to `prompt` as context, updating the
+    /// index of the workspace first, and returns them.
+    pub async fn select_auto_context(&mut self, os: &Os, prompt: &str) -> Result<&[PathBuf]> {
+        let root = os.env.current_dir()?;
+        let mut index = WorkspaceIndex::load(os, &root).await;
+        if index.update(os, &root) > 0 {
+            index.save(os, &root).await?;
+        }
+        self.auto_files = index
+            .search(prompt, AUTO_CONTEXT_TOP_K)
+            .into_iter()
+            .map(|path| root.join(path))
+            .collect();
+        Ok(&self.auto_files)
+    }
+
+    /// Returns the context rules of the current working directory and its parents, see
+    /// [DirectoryContext].
+    pub async fn directory_contexts(&self, os: &Os) -> Result<Vec<(PathBuf, DirectoryContext)>> {
+        Ok(directory_contexts_from(os, &os.env.current_dir()?).await)
+    }
+
     pub async fn get_context_files_by_path(&self, os: &Os, path: &str) -> Result<Vec<(String, String)>> {
         let mut context_files = Vec::new();
-        process_path(os, path, &mut context_files, true).await?;
+        for base_dir in self.base_dirs(os)? {
+            process_path(os, path, &base_dir, &mut context_files, false, &self.cache).await?;
+        }
+        context_files.sort_by(|a, b| a.0.cmp(&b.0));
+        context_files.dedup_by(|a, b| a.0 == b.0);
         Ok(context_files)
     }
 
-    /// Collects context files and optionally drops files if the total size exceeds the limit.
-    /// Returns (files_to_use, dropped_files)
+    /// Collects context files and command outputs, and optionally drops them if the total size
+    /// exceeds the limit. Memory files come first and are never dropped. Returns (files_to_use,
+    /// dropped_files)
     pub async fn collect_context_files_with_limit(
-        &self,
+        &mut self,
         os: &Os,
     ) -> Result<(Vec<(String, String)>, Vec<(String, String)>)> {
-        let mut files = self.get_context_files(os).await?;
+        let mut memory_files = self.get_memory_files(os).await?;
+        let mut files = self.get_other_context_files(os).await?;
+        files.extend(self.get_command_outputs().await);
 
-        let dropped_files = drop_matched_context_files(&mut files, self.max_context_files_size).unwrap_or_default();
+        let memory_size = memory_files
+            .iter()
+            .map(|(_, content)| TokenCounter::count_tokens(content))
+            .sum::<usize>();
+        let limit = self.max_context_files_size.saturating_sub(memory_size);
+        let dropped_files = drop_matched_context_files(&mut files, limit).unwrap_or_default();
 
         // remove dropped files from files
         files.retain(|file| !dropped_files.iter().any(|dropped| dropped.0 == file.0));
+        memory_files.extend(files);
 
-        Ok((files, dropped_files))
+        Ok((memory_files, dropped_files))
     }
 
     async fn collect_context_files(
@@ -164,13 +473,44 @@ impl ContextManager {
         paths: &[String],
         context_files: &mut Vec<(String, String)>,
     ) -> Result<()> {
+        let base_dirs = self.base_dirs(os)?;
         for path in paths {
-            // Use is_validation=false to handle non-matching globs gracefully
-            process_path(os, path, context_files, false).await?;
+            for base_dir in &base_dirs {
+                // Use is_validation=false to handle non-matching globs gracefully
+                process_path(os, path, base_dir, context_files, false, &self.cache).await?;
+            }
         }
         Ok(())
     }
 
+    /// Returns the context formatted on the last turn if it was formatted from context files with
+    /// the hash `hash`, see [context_files_hash].
+    pub fn cached_context_block(&self, hash: u64) -> Option<String> {
+        let mut cache = self.cache.lock().ok()?;
+        match &cache.block {
+            Some((cached, block)) if *cached == hash => {
+                let block = block.clone();
+                cache.stats.block_hits += 1;
+                Some(block)
+            },
+            _ => {
+                cache.stats.block_misses += 1;
+                None
+            },
+        }
+    }
+
+    /// Caches the context formatted from the context files with the hash `hash`.
+    pub fn cache_context_block(&self, hash: u64, block: String) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.block = Some((hash, block));
+        }
+    }
+
+    pub fn cache_stats(&self) -> ContextCacheStats {
+        self.cache.lock().map(|cache| cache.stats).unwrap_or_default()
+    }
+
     /// Run all the currently enabled hooks from both the global and profile contexts.
     /// # Returns
     /// A vector containing pairs of a [`Hook`] definition and its execution output
@@ -183,6 +523,63 @@ impl ContextManager {
     }
 }
 
+/// Returns the context rules of `dir` and its parents, nearest first. Rules of a directory that
+/// does not inherit take precedence over those of its parents, which are left out.
+async fn directory_contexts_from(os: &Os, dir: &Path) -> Vec<(PathBuf, DirectoryContext)> {
+    let mut contexts = Vec::new();
+    for dir in dir.ancestors() {
+        let path = directory_context_path(dir);
+        if !os.fs.exists(&path) {
+            continue;
+        }
+        let context = match read_directory_context(os, &path).await {
+            Ok(context) => context,
+            Err(err) => {
+                warn!(?err, "failed to read the context rules at {}", path.display());
+                continue;
+            },
+        };
+        let inherit = context.inherit;
+        contexts.push((dir.to_path_buf(), context));
+        if !inherit {
+            break;
+        }
+    }
+    contexts
+}
+
+/// Returns the memory files of the current working directory and its parents, nearest first, see
+/// [MEMORY_FILE_NAMES].
+pub fn memory_files(os: &Os) -> Result<Vec<PathBuf>> {
+    Ok(memory_files_from(os, &os.env.current_dir()?, os.env.home().as_deref()))
+}
+
+/// Returns the memory files of `dir` and its parents, nearest first, up to the root of the git
+/// repository containing `dir`. When `dir` is in `home`, directories above it are not searched.
+fn memory_files_from(os: &Os, dir: &Path, home: Option<&Path>) -> Vec<PathBuf> {
+    let home = home.filter(|home| dir.starts_with(home));
+    let mut files = Vec::new();
+    for dir in dir.ancestors() {
+        if home.is_some_and(|home| !dir.starts_with(home)) {
+            break;
+        }
+        files.extend(
+            MEMORY_FILE_NAMES
+                .iter()
+                .map(|name| dir.join(name))
+                .filter(|path| os.fs.exists(path)),
+        );
+        if os.fs.exists(dir.join(".git")) {
+            break;
+        }
+    }
+    files
+}
+
+async fn read_directory_context(os: &Os, path: &Path) -> Result<DirectoryContext> {
+    Ok(serde_json::from_str(&os.fs.read_to_string(path).await?)?)
+}
+
 /// Process a path, handling glob patterns and file types.
 ///
 /// This method:
@@ -194,16 +591,20 @@ impl ContextManager {
 ///
 /// # Arguments
 /// * `path` - The path to process
+/// * `base_dir` - The directory that relative paths are resolved against
 /// * `context_files` - The collection to add files to
 /// * `is_validation` - If true, error when glob patterns don't match; if false, silently skip
+/// * `cache` - The cache of the files read before
 ///
 /// # Returns
 /// A Result indicating success or an error
 async fn process_path(
     os: &Os,
     path: &str,
+    base_dir: &Path,
     context_files: &mut Vec<(String, String)>,
     is_validation: bool,
+    cache: &Mutex<ContextCache>,
 ) -> Result<()> {
     // Expand ~ to home directory
     let expanded_path = if path.starts_with('~') {
@@ -220,7 +621,7 @@ async fn process_path(
     let full_path = if expanded_path.starts_with('/') {
         expanded_path
     } else {
-        os.env.current_dir()?.join(&expanded_path).to_string_lossy().to_string()
+        base_dir.join(&expanded_path).to_string_lossy().to_string()
     };
 
     // Required in chroot testing scenarios so that we can use `Path::exists`.
@@ -237,7 +638,7 @@ async fn process_path(
                     match entry {
                         Ok(path) => {
                             if path.is_file() {
-                                add_file_to_context(os, &path, context_files).await?;
+                                add_file_to_context(os, &path, context_files, cache).await?;
                                 found_any = true;
                             }
                         },
@@ -259,14 +660,14 @@ async fn process_path(
         let path = Path::new(&full_path);
         if path.exists() {
             if path.is_file() {
-                add_file_to_context(os, path, context_files).await?;
+                add_file_to_context(os, path, context_files, cache).await?;
             } else if path.is_dir() {
                 // For directories, add all files in the directory (non-recursive)
                 let mut read_dir = os.fs.read_dir(path).await?;
                 while let Some(entry) = read_dir.next_entry().await? {
                     let path = entry.path();
                     if path.is_file() {
-                        add_file_to_context(os, &path, context_files).await?;
+                        add_file_to_context(os, &path, context_files, cache).await?;
                     }
                 }
             }
@@ -279,6 +680,67 @@ async fn process_path(
     Ok(())
 }
 
+/// Runs a context command, returning its output truncated to the size of the command, or the
+/// reason it failed.
+async fn run_context_command(command: &ContextCommand) -> String {
+    #[cfg(unix)]
+    let mut cmd = tokio::process::Command::new("bash");
+    #[cfg(unix)]
+    cmd.arg("-c");
+
+    #[cfg(windows)]
+    let mut cmd = tokio::process::Command::new("cmd");
+    #[cfg(windows)]
+    cmd.arg("/C");
+
+    cmd.arg(&command.command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut output = match tokio::time::timeout(CONTEXT_COMMAND_TIMEOUT, cmd.output()).await {
+        Ok(Ok(output)) if output.status.success() => String::from_utf8_lossy(&output.stdout).to_string(),
+        Ok(Ok(output)) => format!(
+            "{}{}\n(command returned non-zero exit code: {})",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+            output.status
+        ),
+        Ok(Err(err)) => format!("(failed to execute command: {err})"),
+        Err(_) => format!(
+            "(command timed out after {} seconds)",
+            CONTEXT_COMMAND_TIMEOUT.as_secs()
+        ),
+    };
+    truncate_safe_in_place(&mut output, command.max_size, " ... truncated");
+    output
+}
+
+/// Resolves a root given to `/context add-root` into an absolute path, expanding `~` and
+/// removing `.` and `..` components.
+fn resolve_root(os: &Os, path: &str) -> Result<PathBuf> {
+    let expanded = match path.strip_prefix('~') {
+        Some(rest) => os
+            .env
+            .home()
+            .ok_or(eyre!("Could not determine home directory"))?
+            .join(rest.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    };
+    let mut root = PathBuf::new();
+    for component in os.env.current_dir()?.join(expanded).components() {
+        match component {
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir => {
+                root.pop();
+            },
+            c => root.push(c),
+        }
+    }
+    Ok(root)
+}
+
 /// Add a file to the context collection.
 ///
 /// This method:
@@ -288,16 +750,53 @@ async fn process_path(
 /// # Arguments
 /// * `path` - The path to the file
 /// * `context_files` - The collection to add the file to
+/// * `cache` - The cache of the files read before
 ///
 /// # Returns
 /// A Result indicating success or an error
-async fn add_file_to_context(os: &Os, path: &Path, context_files: &mut Vec<(String, String)>) -> Result<()> {
+async fn add_file_to_context(
+    os: &Os,
+    path: &Path,
+    context_files: &mut Vec<(String, String)>,
+    cache: &Mutex<ContextCache>,
+) -> Result<()> {
     let filename = path.to_string_lossy().to_string();
-    let content = os.fs.read_to_string(path).await?;
+    let content = read_context_file(os, path, cache).await?;
     context_files.push((filename, content));
     Ok(())
 }
 
+/// Reads `path`, reusing the content of the last read if the size and modification time of the
+/// file are unchanged.
+async fn read_context_file(os: &Os, path: &Path, cache: &Mutex<ContextCache>) -> Result<String> {
+    let metadata = os.fs.metadata(path).await.ok();
+    let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+    if let (Some(metadata), Some(modified), Ok(mut cache)) = (&metadata, modified, cache.lock()) {
+        if let Some(content) = cache
+            .files
+            .get(path)
+            .filter(|file| file.modified == modified && file.len == metadata.len())
+            .map(|file| file.content.clone())
+        {
+            cache.stats.file_hits += 1;
+            return Ok(content);
+        }
+    }
+
+    let content = os.fs.read_to_string(path).await?;
+    if let Ok(mut cache) = cache.lock() {
+        cache.stats.file_misses += 1;
+        if let (Some(metadata), Some(modified)) = (metadata, modified) {
+            cache.files.insert(path.to_path_buf(), CachedFile {
+                modified,
+                len: metadata.len(),
+                content: content.clone(),
+            });
+        }
+    }
+    Ok(content)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -353,4 +852,203 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_switch_agent() {
+        let previous = Agent {
+            resources: vec!["file://README.md".into(), "file://docs/*.md".into()],
+            ..Default::default()
+        };
+        let agent = Agent {
+            name: "rust".to_string(),
+            resources: vec!["file://Cargo.toml".into(), "file://docs/*.md".into()],
+            ..Default::default()
+        };
+        let mut manager = ContextManager::from_agent(&previous, None).unwrap();
+        manager.paths.push("notes.md".to_string());
+        manager.auto = true;
+
+        manager.switch_agent(&previous, &agent);
+        assert_eq!(manager.current_profile, "rust");
+        assert_eq!(manager.paths, vec!["Cargo.toml", "docs/*.md", "notes.md"]);
+        assert!(manager.auto);
+    }
+
+    #[tokio::test]
+    async fn test_roots() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        let mut manager = create_test_context_manager(None).expect("Failed to create test context manager");
+
+        os.fs.create_dir_all("/other-project/docs").await?;
+        os.fs.write("/other-project/docs/guide.md", "guide").await?;
+        os.fs.create_dir_all("/docs").await?;
+        os.fs.write("/docs/readme.md", "readme").await?;
+
+        assert!(
+            manager
+                .add_paths(&os, vec!["docs/guide.md".to_string()], false)
+                .await
+                .is_err()
+        );
+        assert!(manager.add_root(&os, "/missing").await.is_err());
+
+        let root = manager.add_root(&os, "/other-project/docs/..").await?;
+        assert_eq!(root, PathBuf::from("/other-project"));
+        assert!(manager.add_root(&os, "/other-project").await.is_err());
+        assert_eq!(manager.roots, vec![root.clone()]);
+
+        manager.add_paths(&os, vec!["docs/*.md".to_string()], false).await?;
+        let files = manager.get_context_files(&os).await?;
+        assert_eq!(files.len(), 2);
+        assert!(
+            files
+                .iter()
+                .any(|(path, content)| path.ends_with("guide.md") && content == "guide")
+        );
+        assert!(
+            files
+                .iter()
+                .any(|(path, content)| path.ends_with("readme.md") && content == "readme")
+        );
+
+        manager.remove_root(&os, "/other-project")?;
+        assert!(manager.roots.is_empty());
+        assert_eq!(manager.get_context_files(&os).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_commands() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        let mut manager = create_test_context_manager(Some(5)).expect("Failed to create test context manager");
+
+        let command = |command: &str, ttl_secs| ContextCommand {
+            command: command.to_string(),
+            ttl_secs,
+            max_size: 20,
+        };
+        manager.add_command(command("echo $RANDOM$RANDOM", 3600))?;
+        manager.add_command(command("seq 1 100", 0))?;
+        assert!(manager.add_command(command("seq 1 100", 0)).is_err());
+
+        let outputs = manager.get_command_outputs().await;
+        assert_eq!(outputs[0].0, "$ echo $RANDOM$RANDOM");
+        assert_eq!(outputs[1].1.len(), 20);
+        assert!(outputs[1].1.ends_with(" ... truncated"));
+        // The output is reused until the TTL expires.
+        assert_eq!(manager.get_command_outputs().await[0], outputs[0]);
+
+        // Outputs count against the size limit like files.
+        let (used, dropped) = manager.collect_context_files_with_limit(&os).await?;
+        assert_eq!(used.len() + dropped.len(), 2);
+        assert_eq!(dropped[0].0, "$ seq 1 100");
+
+        manager.remove_command("seq 1 100")?;
+        assert!(manager.remove_command("seq 1 100").is_err());
+        assert_eq!(manager.get_command_outputs().await.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_directory_contexts() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        let manager = create_test_context_manager(None).expect("Failed to create test context manager");
+
+        os.fs.create_dir_all("/repo/.amazonq").await?;
+        os.fs.create_dir_all("/repo/services/api/.amazonq").await?;
+        os.fs.create_dir_all("/repo/services/web/.amazonq").await?;
+        os.fs
+            .write("/repo/.amazonq/context.json", r#"{"paths": ["README.md"]}"#)
+            .await?;
+        os.fs
+            .write("/repo/services/api/.amazonq/context.json", r#"{"paths": ["*.md"]}"#)
+            .await?;
+        os.fs
+            .write(
+                "/repo/services/web/.amazonq/context.json",
+                r#"{"paths": ["NOTES.md"], "inherit": false}"#,
+            )
+            .await?;
+
+        let contexts = directory_contexts_from(&os, Path::new("/repo/services/api/src")).await;
+        let dirs = contexts.iter().map(|(dir, _)| dir.clone()).collect::<Vec<_>>();
+        assert_eq!(dirs, vec![PathBuf::from("/repo/services/api"), PathBuf::from("/repo")]);
+        assert_eq!(contexts[0].1.paths, vec!["*.md".to_string()]);
+
+        let contexts = directory_contexts_from(&os, Path::new("/repo/services/web")).await;
+        assert_eq!(contexts.len(), 1);
+        assert!(!contexts[0].1.inherit);
+
+        // The working directory of tests is the root, which has no rules of its own.
+        assert!(manager.directory_contexts(&os).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_files() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/repo/.git").await?;
+        os.fs.create_dir_all("/repo/pkg/src").await?;
+        os.fs.write("/AmazonQ.md", "outside the repository").await?;
+        os.fs.write("/repo/AGENTS.md", "repository").await?;
+        os.fs.write("/repo/pkg/AmazonQ.md", "package").await?;
+
+        assert_eq!(memory_files_from(&os, Path::new("/repo/pkg/src"), None), vec![
+            PathBuf::from("/repo/pkg/AmazonQ.md"),
+            PathBuf::from("/repo/AGENTS.md")
+        ]);
+        assert_eq!(
+            memory_files_from(&os, Path::new("/repo/pkg/src"), Some(Path::new("/repo/pkg"))),
+            vec![PathBuf::from("/repo/pkg/AmazonQ.md")]
+        );
+        assert_eq!(memory_files_from(&os, Path::new("/"), None), vec![PathBuf::from(
+            "/AmazonQ.md"
+        )]);
+
+        // Memory files come first, and are not repeated when also matched by a path.
+        let mut manager = create_test_context_manager(Some(1))?;
+        manager.paths = vec!["AmazonQ.md".to_string()];
+        let (files, dropped) = manager.collect_context_files_with_limit(&os).await?;
+        assert_eq!(files, vec![(
+            "/AmazonQ.md".to_string(),
+            "outside the repository".to_string()
+        )]);
+        assert!(dropped.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_context_cache() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        let mut manager = create_test_context_manager(None).expect("Failed to create test context manager");
+        os.fs.write("cached.md", "v1").await?;
+        manager.add_paths(&os, vec!["cached.md".to_string()], false).await?;
+        let before = manager.cache_stats();
+
+        let files = manager.get_context_files(&os).await?;
+        assert_eq!(files[0].1, "v1");
+        assert_eq!(manager.cache_stats().file_hits, before.file_hits + 1);
+        assert_eq!(manager.cache_stats().file_misses, before.file_misses);
+
+        os.fs.write("cached.md", "version 2").await?;
+        let changed = manager.get_context_files(&os).await?;
+        assert_eq!(changed[0].1, "version 2");
+        assert_eq!(manager.cache_stats().file_misses, before.file_misses + 1);
+
+        let hash = context_files_hash(&changed);
+        assert_ne!(hash, context_files_hash(&files));
+        assert_eq!(manager.cached_context_block(hash), None);
+        manager.cache_context_block(hash, "block".to_string());
+        assert_eq!(manager.cached_context_block(hash), Some("block".to_string()));
+        assert_eq!(manager.cached_context_block(context_files_hash(&files)), None);
+        let stats = manager.cache_stats();
+        assert_eq!((stats.block_hits, stats.block_misses), (1, 2));
+
+        Ok(())
+    }
 }
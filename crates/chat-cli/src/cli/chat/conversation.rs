@@ -4,6 +4,11 @@ use std::collections::{
     VecDeque,
 };
 use std::io::Write;
+use std::ops::Range;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::sync::atomic::Ordering;
 
 use crossterm::style::Color;
@@ -11,6 +16,8 @@ use crossterm::{
     execute,
     style,
 };
+use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::{
     Deserialize,
     Serialize,
@@ -20,37 +27,59 @@ use tracing::{
     warn,
 };
 
-use super::cli::compact::CompactStrategy;
+use super::cli::compact::{
+    CompactMode,
+    CompactStrategy,
+};
 use super::consts::{
     DUMMY_TOOL_NAME,
     MAX_CHARS,
     MAX_CONVERSATION_STATE_HISTORY_LEN,
 };
-use super::context::ContextManager;
+use super::context::{
+    ContextManager,
+    context_files_hash,
+};
+use super::injection::{
+    InjectionFinding,
+    InjectionGuard,
+};
+use super::language;
 use super::message::{
     AssistantMessage,
     ToolUseResult,
+    ToolUseResultBlock,
     UserMessage,
 };
 use super::parser::RequestMetadata;
+use super::redact::{
+    Redactor,
+    replace_json_matches,
+    replace_matches,
+};
 use super::token_counter::{
     CharCount,
     CharCounter,
 };
 use super::tool_manager::ToolManager;
+use super::tools::update_plan::Plan;
 use super::tools::{
     InputSchema,
     QueuedTool,
     ToolOrigin,
     ToolSpec,
 };
-use super::util::serde_value_to_document;
+use super::util::{
+    serde_value_to_document,
+    truncate_safe,
+};
 use crate::api_client::model::{
     ChatMessage,
     ConversationState as FigConversationState,
     ImageBlock,
     Tool,
     ToolInputSchema,
+    ToolResultStatus,
     ToolSpecification,
     UserInputMessage,
 };
@@ -60,11 +89,58 @@ use crate::cli::agent::hook::{
     HookTrigger,
 };
 use crate::cli::chat::ChatError;
+use crate::database::DatabaseError;
 use crate::mcp_client::Prompt;
 use crate::os::Os;
+use crate::telemetry::core::FeedbackVote;
 
 const CONTEXT_ENTRY_START_HEADER: &str = "--- CONTEXT ENTRY BEGIN ---\n";
 const CONTEXT_ENTRY_END_HEADER: &str = "--- CONTEXT ENTRY END ---\n\n";
+/// Replaces the outputs of tools compacted with [CompactMode::ToolsOnly].
+/// Maximum length in bytes of a generated conversation title.
+const TITLE_MAX_LENGTH: usize = 60;
+
+/// Maximum length in bytes of each side of the exchange a title is generated from.
+const TITLE_EXCHANGE_MAX_LENGTH: usize = 2_000;
+
+const COMPACTED_TOOL_OUTPUT: &str = "[Tool output removed by compaction. Refer to the summary of tool results.]";
+
+/// Content of the results of tool uses cancelled by [ConversationState::cancel_tool_uses].
+const CANCELLED_TOOL_OUTPUT: &str = "Tool use was cancelled because the tool is no longer available";
+
+/// A user/assistant exchange pinned with `/pin`, that is sent as context with every request so
+/// that neither history truncation nor compaction can drop it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedExchange {
+    pub prompt: String,
+    /// The text content of every assistant response to the prompt, including those in between
+    /// tool uses.
+    pub response: String,
+}
+
+/// A rating of a response given with `/feedback`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageFeedback {
+    pub message_id: String,
+    pub vote: FeedbackVote,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// The history entries stored in the database by [ConversationState::save].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StoredHistory {
+    path: PathBuf,
+    /// Sequence numbers of the stored entries.
+    stored: Range<i64>,
+    /// Sequence number of the first entry of the history. Greater than the start of
+    /// [Self::stored] once older entries are dropped, until the next save deletes them, or when
+    /// older entries are not loaded.
+    first_seq: i64,
+    /// Number of stored entries before [Self::first_seq] that are part of the conversation but
+    /// were not loaded when resuming it, see [ConversationState::hydrate_history].
+    unloaded: i64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HistoryEntry {
@@ -105,6 +181,43 @@ pub struct ConversationState {
     /// Model explicitly selected by the user in this conversation state via `/model`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// The latest plan recorded by the model through the update_plan tool.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<Plan>,
+    /// Exchanges pinned with `/pin`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pinned: Vec<PinnedExchange>,
+    /// Ratings given to responses with `/feedback`, at most one per response.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feedback: Vec<MessageFeedback>,
+    /// Short title generated after the first exchange.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Whether [Self::model] was chosen with `--model` or `/model`, in which case it is kept when
+    /// switching to an agent with a default model.
+    #[serde(default)]
+    pub model_chosen: bool,
+    /// Model used instead of [Self::model] for the requests of the current user turn, set with
+    /// the `@model:` prefix.
+    #[serde(skip)]
+    pub model_override: Option<String>,
+    /// Masks secrets in tool results and the transcript.
+    #[serde(skip)]
+    pub redactor: Redactor,
+    /// Flags tool results and context that look like prompt injection.
+    #[serde(skip)]
+    pub injection_guard: InjectionGuard,
+    /// Context flagged by the injection guard that the user was not warned about yet.
+    #[serde(skip)]
+    flagged_context: Vec<(String, Vec<InjectionFinding>)>,
+    /// The history entries already stored, so that saving only appends the new ones. [None] when
+    /// the history must be rewritten.
+    #[serde(skip)]
+    stored_history: Option<StoredHistory>,
+    /// Character counts of the messages in [Self::valid_history_range], cleared whenever the
+    /// history changes.
+    #[serde(skip)]
+    history_size: Option<ConversationSize>,
 }
 
 impl ConversationState {
@@ -146,6 +259,17 @@ impl ConversationState {
             latest_summary: None,
             agents,
             model: current_model_id,
+            plan: None,
+            pinned: Vec::new(),
+            feedback: Vec::new(),
+            title: None,
+            model_chosen: false,
+            model_override: None,
+            redactor: Redactor::default(),
+            injection_guard: InjectionGuard::default(),
+            flagged_context: Vec::new(),
+            stored_history: None,
+            history_size: None,
         }
     }
 
@@ -157,15 +281,185 @@ impl ConversationState {
         &self.history
     }
 
-    /// Clears the conversation history and optionally the summary.
+    /// Returns the project roots registered with `/context add-root`.
+    pub fn workspace_roots(&self) -> &[PathBuf] {
+        self.context_manager
+            .as_ref()
+            .map(|cm| cm.roots.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the most recent assistant message with text content.
+    pub fn last_assistant_message(&self) -> Option<&AssistantMessage> {
+        self.history
+            .iter()
+            .rev()
+            .map(|entry| &entry.assistant)
+            .find(|assistant| !assistant.content().trim().is_empty())
+    }
+
+    /// Clears the conversation history and plan, and optionally the summary, pins, and title.
     pub fn clear(&mut self, preserve_summary: bool) {
         self.next_message = None;
         self.history.clear();
+        self.stored_history = None;
+        self.history_size = None;
+        self.plan = None;
         if !preserve_summary {
             self.latest_summary = None;
+            self.pinned.clear();
+            self.title = None;
         }
     }
 
+    /// Creates a request asking the model for a short title of the conversation, based on its
+    /// first exchange. Returns [None] if the conversation already has a title or there is no
+    /// complete exchange yet.
+    pub fn create_title_request(&self) -> Option<FigConversationState> {
+        if self.title.is_some() {
+            return None;
+        }
+        let exchange = self.exchanges().into_iter().next()?;
+        if exchange.response.is_empty() {
+            return None;
+        }
+
+        let content = format!(
+            "[SYSTEM NOTE: This is an automated request, not from the user]\n\n\
+            Write a title of at most 6 words for the conversation below. \
+            Respond with the title only, without quotes or punctuation at the end.\n\n\
+            User: {}\n\nAssistant: {}",
+            truncate_safe(&exchange.prompt, TITLE_EXCHANGE_MAX_LENGTH),
+            truncate_safe(&exchange.response, TITLE_EXCHANGE_MAX_LENGTH),
+        );
+
+        Some(FigConversationState {
+            conversation_id: None,
+            user_input_message: UserMessage::new_prompt(content)
+                .into_user_input_message(self.model.clone(), &HashMap::new()),
+            history: None,
+        })
+    }
+
+    /// Returns the last prompt of the history.
+    pub fn last_prompt(&self) -> Option<&str> {
+        self.history.iter().rev().find_map(|entry| entry.user.prompt())
+    }
+
+    /// Replaces the matches of `pattern` with [REDACTED](super::redact::REDACTED) in the history,
+    /// including tool uses and their results, and in the transcript, summary, pins, and title.
+    /// Returns the number of replaced matches.
+    ///
+    /// The stored history is rewritten on the next [Self::save], so [Self::hydrate_history] must
+    /// be called first for older stored entries to be kept.
+    pub fn redact_matches(&mut self, pattern: &Regex) -> usize {
+        fn redact_serialized<T: Serialize + DeserializeOwned>(value: &mut T, pattern: &Regex) -> usize {
+            let Ok(mut json) = serde_json::to_value(&*value) else {
+                return 0;
+            };
+            let count = replace_json_matches(&mut json, pattern);
+            if count > 0 {
+                match serde_json::from_value(json) {
+                    Ok(redacted) => *value = redacted,
+                    Err(err) => {
+                        warn!(?err, "failed to redact a message");
+                        return 0;
+                    },
+                }
+            }
+            count
+        }
+
+        let mut count = 0;
+        for entry in &mut self.history {
+            count += redact_serialized(entry, pattern);
+        }
+        if let Some(message) = &mut self.next_message {
+            count += redact_serialized(message, pattern);
+        }
+        for line in &mut self.transcript {
+            count += replace_matches(line, pattern);
+        }
+        if let Some((summary, _)) = &mut self.latest_summary {
+            count += replace_matches(summary, pattern);
+        }
+        for exchange in &mut self.pinned {
+            count += replace_matches(&mut exchange.prompt, pattern);
+            count += replace_matches(&mut exchange.response, pattern);
+        }
+        if let Some(title) = &mut self.title {
+            count += replace_matches(title, pattern);
+        }
+
+        if count > 0 {
+            self.stored_history = None;
+            self.history_size = None;
+        }
+        count
+    }
+
+    /// Returns the exchanges of the history, each starting with a prompt from the user and
+    /// including the tool uses that followed it.
+    pub fn exchanges(&self) -> Vec<PinnedExchange> {
+        let mut exchanges = Vec::<PinnedExchange>::new();
+        for HistoryEntry { user, assistant, .. } in &self.history {
+            if let Some(prompt) = user.prompt() {
+                exchanges.push(PinnedExchange {
+                    prompt: prompt.to_string(),
+                    response: String::new(),
+                });
+            }
+            // Tool results of an exchange truncated from the history are skipped.
+            let Some(exchange) = exchanges.last_mut() else {
+                continue;
+            };
+            let content = assistant.content().trim();
+            if !content.is_empty() {
+                if !exchange.response.is_empty() {
+                    exchange.response.push('\n');
+                }
+                exchange.response.push_str(content);
+            }
+        }
+        exchanges
+    }
+
+    /// Pins the exchange with the 1-based `index` in [Self::exchanges], or the last one.
+    pub fn pin_exchange(&mut self, index: Option<usize>) -> Result<&PinnedExchange, String> {
+        let mut exchanges = self.exchanges();
+        let count = exchanges.len();
+        let index = match index {
+            None if count == 0 => return Err("There is no exchange to pin yet.".to_string()),
+            None => count,
+            Some(index) if index == 0 || index > count => {
+                return Err(format!(
+                    "Exchange {index} does not exist. The conversation has {count} exchanges."
+                ));
+            },
+            Some(index) => index,
+        };
+        let exchange = exchanges.swap_remove(index - 1);
+        if self.pinned.contains(&exchange) {
+            return Err(format!("Exchange {index} is already pinned."));
+        }
+        self.pinned.push(exchange);
+        Ok(self.pinned.last().expect("just pushed"))
+    }
+
+    /// Records a rating of the last response, replacing any previous rating of it.
+    pub fn record_feedback(&mut self, vote: FeedbackVote, comment: Option<String>) -> Result<&MessageFeedback, String> {
+        let Some(message_id) = self.message_id().map(str::to_string) else {
+            return Err("There is no response to give feedback on yet.".to_string());
+        };
+        self.feedback.retain(|feedback| feedback.message_id != message_id);
+        self.feedback.push(MessageFeedback {
+            message_id,
+            vote,
+            comment,
+        });
+        Ok(self.feedback.last().expect("just pushed"))
+    }
+
     /// Appends a collection prompts into history and returns the last message in the collection.
     /// It asserts that the collection ends with a prompt that assumes the role of user.
     pub fn append_prompts(&mut self, mut prompts: VecDeque<Prompt>) -> Option<String> {
@@ -189,6 +483,7 @@ impl ConversationState {
                 let assistant = candidate_asst.take().unwrap();
                 let user = candidate_user.take().unwrap();
                 self.append_assistant_transcript(&assistant);
+                self.history_size = None;
                 self.history.push_back(HistoryEntry {
                     user,
                     assistant,
@@ -224,6 +519,13 @@ impl ConversationState {
         self.next_message = Some(msg);
     }
 
+    /// Attaches images to the [Self::next_message].
+    pub fn attach_images(&mut self, images: Vec<ImageBlock>) {
+        if let Some(next_message) = self.next_message.as_mut() {
+            next_message.images.get_or_insert_default().extend(images);
+        }
+    }
+
     /// Sets the response message according to the currently set [Self::next_message].
     pub fn push_assistant_message(
         &mut self,
@@ -235,17 +537,157 @@ impl ConversationState {
         let next_user_message = self.next_message.take().expect("next user message should exist");
 
         self.append_assistant_transcript(&message);
+        self.history_size = None;
         self.history.push_back(HistoryEntry {
             user: next_user_message,
             assistant: message,
             request_metadata,
         });
 
-        if let Ok(cwd) = std::env::current_dir() {
-            os.database.set_conversation_by_path(cwd, self).ok();
+        if let Err(err) = self.save(os) {
+            warn!(?err, "failed to save the conversation");
         }
     }
 
+    /// Stores the conversation for the current directory.
+    ///
+    /// Only the history entries added since the previous save are written, unless the history was
+    /// rewritten since, e.g. by `/compact` or `/clear`.
+    pub fn save(&mut self, os: &mut Os) -> Result<(), DatabaseError> {
+        let Ok(path) = std::env::current_dir() else {
+            return Ok(());
+        };
+        // The head of the conversation is stored without its history.
+        let history = std::mem::take(&mut self.history);
+        let result = self.save_history(os, &path, &history);
+        self.history = history;
+        result
+    }
+
+    fn save_history(
+        &mut self,
+        os: &mut Os,
+        path: &Path,
+        history: &VecDeque<HistoryEntry>,
+    ) -> Result<(), DatabaseError> {
+        if let Some(StoredHistory {
+            stored,
+            first_seq,
+            unloaded,
+            ..
+        }) = self.stored_history.take().filter(|s| s.path == path)
+        {
+            let new_entries = usize::try_from(stored.end - first_seq).unwrap_or(0).min(history.len());
+            let keep_from = first_seq - unloaded;
+            if os
+                .database
+                .append_conversation_by_path(path, self, stored, keep_from, history.range(new_entries..))?
+            {
+                self.stored_history = Some(StoredHistory {
+                    path: path.to_path_buf(),
+                    stored: keep_from..first_seq + history.len() as i64,
+                    first_seq,
+                    unloaded,
+                });
+                return Ok(());
+            }
+            debug!("the stored conversation changed, rewriting it");
+        }
+
+        os.database.set_conversation_by_path(path, self, history)?;
+        self.stored_history = Some(StoredHistory {
+            path: path.to_path_buf(),
+            stored: 0..history.len() as i64,
+            first_seq: 0,
+            unloaded: 0,
+        });
+        Ok(())
+    }
+
+    /// Restores the history entries stored by [Self::save] for `path` into a conversation loaded
+    /// from the database.
+    ///
+    /// `stored` are the sequence numbers of the stored entries, and `tail` the most recent of
+    /// them as `(sequence number, JSON)` pairs. The older entries are loaded by
+    /// [Self::hydrate_history] when needed.
+    pub fn restore_history(
+        &mut self,
+        path: impl AsRef<Path>,
+        stored: Range<i64>,
+        tail: Vec<(i64, String)>,
+    ) -> Result<(), DatabaseError> {
+        // Conversations stored by older versions hold their history in the head.
+        if tail.is_empty() && !self.history.is_empty() {
+            return Ok(());
+        }
+
+        let tail_start = tail.first().map_or(stored.end, |(seq, _)| *seq);
+        let contiguous = tail
+            .iter()
+            .zip(tail_start..)
+            .all(|((seq, _), expected)| *seq == expected)
+            && tail_start + tail.len() as i64 == stored.end;
+        let mut history = tail
+            .iter()
+            .map(|(_, entry)| serde_json::from_str::<HistoryEntry>(entry))
+            .collect::<Result<VecDeque<_>, _>>()?;
+        // Like the invariants do when trimming the history, start the tail with a prompt rather
+        // than tool results when older entries are left out.
+        let mut first_seq = tail_start;
+        if tail_start > stored.start {
+            while history.len() > 1 && history.front().is_some_and(|entry| entry.user.has_tool_use_results()) {
+                history.pop_front();
+                first_seq += 1;
+            }
+        }
+
+        self.history = history;
+        self.history_size = None;
+        self.stored_history = contiguous.then(|| StoredHistory {
+            path: path.as_ref().to_path_buf(),
+            first_seq,
+            unloaded: first_seq - stored.start,
+            stored,
+        });
+        Ok(())
+    }
+
+    /// Loads the entries of a resumed conversation that were left out by
+    /// [Self::restore_history], so that the whole history is available, e.g. to export it.
+    pub fn hydrate_history(&mut self, os: &Os) -> Result<(), DatabaseError> {
+        let Some(stored) = self.stored_history.as_mut().filter(|stored| stored.unloaded > 0) else {
+            return Ok(());
+        };
+
+        let seqs = stored.first_seq - stored.unloaded..stored.first_seq;
+        let entries = os
+            .database
+            .get_conversation_entries(&stored.path, &self.conversation_id, seqs.clone())?;
+        if entries.len() as i64 != stored.unloaded {
+            warn!(?seqs, found = entries.len(), "missing stored history entries");
+        }
+        let entries = entries
+            .iter()
+            .map(|(_, entry)| serde_json::from_str::<HistoryEntry>(entry))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let count = entries.len();
+        for entry in entries.into_iter().rev() {
+            self.history.push_front(entry);
+        }
+        self.valid_history_range = (self.valid_history_range.0 + count, self.valid_history_range.1 + count);
+        self.history_size = None;
+        match count as i64 == stored.unloaded {
+            true => {
+                stored.first_seq = seqs.start;
+                stored.unloaded = 0;
+            },
+            // The history must be rewritten without the missing entries.
+            false => self.stored_history = None,
+        }
+        Ok(())
+    }
+
     /// Returns the conversation id.
     pub fn conversation_id(&self) -> &str {
         self.conversation_id.as_ref()
@@ -282,8 +724,14 @@ impl ConversationState {
     /// 3. If the last message from the assistant contains tool results, and a next user message is
     ///    set without tool results, then the user message will have "cancelled" tool results.
     pub fn enforce_conversation_invariants(&mut self) {
-        self.valid_history_range =
+        // The first message is rewritten when it contains tool results.
+        let first_changes = self.history.front().is_some_and(|e| e.user.has_tool_use_results());
+        let valid_history_range =
             enforce_conversation_invariants(&mut self.history, &mut self.next_message, &self.tools);
+        if first_changes || valid_history_range != self.valid_history_range {
+            self.history_size = None;
+        }
+        self.valid_history_range = valid_history_range;
     }
 
     /// Here we also need to make sure that the tool result corresponds to one of the tools
@@ -299,16 +747,103 @@ impl ConversationState {
     /// 3. The model had decided to call a tool that does not exist. The intervention here is to
     ///    substitute the non-existent tool name with a dummy.
     pub fn enforce_tool_use_history_invariants(&mut self) {
+        self.history_size = None;
         enforce_tool_use_history_invariants(&mut self.history, &self.tools);
     }
 
-    pub fn add_tool_results(&mut self, tool_results: Vec<ToolUseResult>) {
+    /// Returns the sorted names of the tools that are currently available to the model.
+    pub fn available_tool_names(&self) -> Vec<String> {
+        let mut names = available_tool_names(&self.tools)
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Returns the names of the tools used in the history that cannot be resolved to any of the
+    /// currently available tools, e.g. after switching to an agent with a different set of tools.
+    ///
+    /// Left alone, uses of these tools are substituted with the dummy tool by
+    /// [Self::enforce_tool_use_history_invariants].
+    pub fn unavailable_tool_uses(&self) -> Vec<String> {
+        let tool_names = available_tool_names(&self.tools);
+        let mut unavailable = Vec::new();
+        for HistoryEntry { assistant, .. } in &self.history {
+            let AssistantMessage::ToolUse { tool_uses, .. } = assistant else {
+                continue;
+            };
+            for tool_use in tool_uses {
+                if tool_names.contains(tool_use.name.as_str()) || tool_names.contains(tool_use.orig_name.as_str()) {
+                    continue;
+                }
+                if tool_names
+                    .iter()
+                    .filter(|name| name.ends_with(&tool_use.orig_name))
+                    .count()
+                    == 1
+                {
+                    continue;
+                }
+                if !unavailable.contains(&tool_use.orig_name) {
+                    unavailable.push(tool_use.orig_name.clone());
+                }
+            }
+        }
+        unavailable
+    }
+
+    /// Rewrites every use of the tool `from` in the history to use the tool `to` instead.
+    pub fn remap_tool_uses(&mut self, from: &str, to: &str) {
+        self.stored_history = None;
+        self.history_size = None;
+        for HistoryEntry { assistant, .. } in &mut self.history {
+            if let AssistantMessage::ToolUse { tool_uses, .. } = assistant {
+                for tool_use in tool_uses.iter_mut().filter(|t| t.orig_name == from) {
+                    tool_use.name = to.to_string();
+                    tool_use.orig_name = to.to_string();
+                }
+            }
+        }
+    }
+
+    /// Replaces the results of every use of the tools `names` in the history with a cancelled
+    /// result, and substitutes the uses themselves with the dummy tool.
+    ///
+    /// Uses in the last assistant message are left without results, those are cancelled along with
+    /// the rest of its uses once the next user message is set.
+    pub fn cancel_tool_uses(&mut self, names: &[String]) {
+        self.stored_history = None;
+        let mut cancelled = HashSet::new();
+        for HistoryEntry { user, assistant, .. } in &mut self.history {
+            if let Some(results) = user.tool_use_results_mut() {
+                for result in results.iter_mut().filter(|r| cancelled.contains(&r.tool_use_id)) {
+                    result.content = vec![ToolUseResultBlock::Text(CANCELLED_TOOL_OUTPUT.to_string())];
+                    result.status = ToolResultStatus::Error;
+                }
+            }
+            cancelled.clear();
+            if let AssistantMessage::ToolUse { tool_uses, .. } = assistant {
+                cancelled.extend(
+                    tool_uses
+                        .iter()
+                        .filter(|t| names.contains(&t.orig_name))
+                        .map(|t| t.id.clone()),
+                );
+            }
+        }
+        self.enforce_tool_use_history_invariants();
+    }
+
+    pub fn add_tool_results(&mut self, mut tool_results: Vec<ToolUseResult>) {
         debug_assert!(self.next_message.is_none());
+        self.redactor.redact_tool_results(&mut tool_results);
         self.next_message = Some(UserMessage::new_tool_use_results(tool_results));
     }
 
-    pub fn add_tool_results_with_images(&mut self, tool_results: Vec<ToolUseResult>, images: Vec<ImageBlock>) {
+    pub fn add_tool_results_with_images(&mut self, mut tool_results: Vec<ToolUseResult>, images: Vec<ImageBlock>) {
         debug_assert!(self.next_message.is_none());
+        self.redactor.redact_tool_results(&mut tool_results);
         self.next_message = Some(UserMessage::new_tool_use_results_with_images(tool_results, images));
     }
 
@@ -332,8 +867,38 @@ impl ConversationState {
     ) -> Result<FigConversationState, ChatError> {
         debug_assert!(self.next_message.is_some());
         self.enforce_conversation_invariants();
-        self.history.drain(self.valid_history_range.1..);
-        self.history.drain(..self.valid_history_range.0);
+        let (start, end) = self.valid_history_range;
+        if end < self.history.len() {
+            self.stored_history = None;
+        }
+        self.history.drain(end..);
+        self.history_size = None;
+        self.drain_history_front(start);
+
+        // Files selected for a prompt stay in the context while its tool uses run.
+        if let (Some(context_manager), Some(prompt)) = (
+            self.context_manager.as_mut().filter(|cm| cm.auto),
+            self.next_message.as_ref().and_then(|m| m.prompt()),
+        ) {
+            match context_manager.select_auto_context(os, prompt).await {
+                Ok(files) if !files.is_empty() => {
+                    let cwd = os.env.current_dir().unwrap_or_default();
+                    let files = files
+                        .iter()
+                        .map(|file| file.strip_prefix(&cwd).unwrap_or(file).display().to_string())
+                        .collect::<Vec<_>>();
+                    execute!(
+                        stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("Auto-context: {}\n", files.join(", "))),
+                        style::SetForegroundColor(style::Color::Reset)
+                    )
+                    .ok();
+                },
+                Ok(_) => (),
+                Err(err) => warn!(?err, "failed to select files for auto-context"),
+            }
+        }
 
         let context = self.backend_conversation_state(os, run_hooks, stderr).await?;
         if !context.dropped_context_files.is_empty() {
@@ -401,6 +966,13 @@ impl ConversationState {
             // Get the user prompt from next_message if available
             let user_prompt = self.next_message.as_ref().and_then(|m| m.prompt());
             let hook_results = cm.run_hooks(output, user_prompt).await?;
+            for ((_, hook), hook_output) in &hook_results {
+                let source = format!("hook `{}`", hook.command);
+                let findings = self.injection_guard.scan_context(&source, hook_output);
+                if !findings.is_empty() {
+                    self.flagged_context.push((source, findings));
+                }
+            }
 
             conversation_start_context = Some(format_hook_context(&hook_results, HookTrigger::AgentSpawn));
 
@@ -411,17 +983,29 @@ impl ConversationState {
         }
 
         let (context_messages, dropped_context_files) = self.context_messages(os, conversation_start_context).await;
+        for (source, findings) in std::mem::take(&mut self.flagged_context) {
+            execute!(
+                output,
+                style::SetForegroundColor(Color::DarkYellow),
+                style::Print(format!("\nThe context from {source} may contain prompt injection:\n")),
+                style::Print(findings.iter().map(|f| format!("  - {f}\n")).collect::<String>()),
+                style::SetForegroundColor(style::Color::Reset)
+            )
+            .ok();
+        }
 
+        let history_size = self.history_size();
         Ok(BackendConversationState {
             conversation_id: self.conversation_id.as_str(),
             next_user_message: self.next_message.as_ref(),
+            history_size,
             history: self
                 .history
                 .range(self.valid_history_range.0..self.valid_history_range.1),
             context_messages,
             dropped_context_files,
             tools: &self.tools,
-            model_id: self.model.as_deref(),
+            model_id: self.model_override.as_deref().or(self.model.as_deref()),
         })
     }
 
@@ -437,6 +1021,25 @@ impl ConversationState {
         strategy: CompactStrategy,
     ) -> Result<FigConversationState, ChatError> {
         let mut summary_content = match custom_prompt {
+            _ if strategy.mode == CompactMode::ToolsOnly => {
+                let custom_instruction = custom_prompt
+                    .map(|p| format!("IMPORTANT CUSTOM INSTRUCTION: {}\n\n", p.as_ref()))
+                    .unwrap_or_default();
+                format!(
+                    "[SYSTEM NOTE: This is an automated summarization request, not from the user]\n\n\
+                            FORMAT REQUIREMENTS: Create a structured, concise summary in bullet-point format. DO NOT respond conversationally. DO NOT address the user directly.\n\n\
+                            {custom_instruction}\
+                            The conversation itself will be kept, but the outputs of the tools executed so far will be removed from it. \
+                            Your task is to create a structured summary document of ONLY the tool results, containing:\n\
+                            1) Bullet points for every tool executed, with its arguments and the essential parts of its result\n\
+                            2) Exact file paths, identifiers, error messages, and code from the results that later work depends on\n\n\
+                            FORMAT THE SUMMARY IN THIRD PERSON, NOT AS A DIRECT RESPONSE. Example format:\n\n\
+                            ## TOOLS EXECUTED\n\
+                            * Tool X (arguments): Result Y\n\n\
+                            Remember this is a DOCUMENT not a chat response.\n\
+                            FILTER OUT CHAT CONVENTIONS (greetings, offers to help, etc)."
+                )
+            },
             Some(custom_prompt) => {
                 // Make the custom instructions much more prominent and directive
                 format!(
@@ -532,11 +1135,48 @@ impl ConversationState {
         strategy: CompactStrategy,
         request_metadata: RequestMetadata,
     ) {
-        self.history
-            .drain(..(self.history.len().saturating_sub(strategy.messages_to_exclude)));
+        let end = self.history.len().saturating_sub(strategy.messages_to_exclude);
+        match strategy.mode {
+            CompactMode::ToolsOnly => {
+                self.stored_history = None;
+                self.history_size = None;
+                for HistoryEntry { user, .. } in self.history.range_mut(..end) {
+                    if let Some(results) = user.tool_use_results_mut() {
+                        for result in results {
+                            result.content = vec![ToolUseResultBlock::Text(COMPACTED_TOOL_OUTPUT.to_string())];
+                        }
+                        user.images = None;
+                    }
+                }
+            },
+            _ => self.drain_history_front(end),
+        }
         self.latest_summary = Some((summary, request_metadata));
     }
 
+    /// Drops the `count` oldest entries of the history.
+    fn drain_history_front(&mut self, count: usize) {
+        let count = count.min(self.history.len());
+        self.history.drain(..count);
+        self.history_size = None;
+        if let Some(stored) = &mut self.stored_history {
+            // The entries that were not loaded are older, and dropped as well.
+            stored.first_seq += count as i64;
+            stored.unloaded = 0;
+        }
+    }
+
+    /// Returns the number of tool results that compacting with `strategy` would replace.
+    pub fn compactable_tool_results(&self, strategy: CompactStrategy) -> usize {
+        let end = self.history.len().saturating_sub(strategy.messages_to_exclude);
+        self.history
+            .range(..end)
+            .filter_map(|HistoryEntry { user, .. }| user.tool_use_results())
+            .flatten()
+            .filter(|result| !matches!(result.content.as_slice(), [ToolUseResultBlock::Text(text)] if text == COMPACTED_TOOL_OUTPUT))
+            .count()
+    }
+
     pub fn current_profile(&self) -> Option<&str> {
         if let Some(cm) = self.context_manager.as_ref() {
             Some(cm.current_profile.as_str())
@@ -552,7 +1192,6 @@ impl ConversationState {
     /// - Either add support for multiple context messages if the context is too large to fit inside
     ///   a single user message, or handle this case more gracefully. For now, always return 2
     ///   messages.
-    /// - Cache this return for some period of time.
     async fn context_messages(
         &mut self,
         os: &Os,
@@ -569,6 +1208,16 @@ impl ConversationState {
             context_content.push_str(CONTEXT_ENTRY_END_HEADER);
         }
 
+        if !self.pinned.is_empty() {
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            context_content.push_str("The user pinned these exchanges from the conversation as important. They may no longer be part of the conversation history, so refer to them here.\n\n");
+            context_content.push_str("PINNED EXCHANGES:\n");
+            for (i, PinnedExchange { prompt, response }) in self.pinned.iter().enumerate() {
+                context_content.push_str(&format!("[{}] User: {prompt}\nAssistant: {response}\n", i + 1));
+            }
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+        }
+
         // Add context files if available
         if let Some(context_manager) = self.context_manager.as_mut() {
             match context_manager.collect_context_files_with_limit(os).await {
@@ -577,12 +1226,26 @@ impl ConversationState {
                         dropped_context_files.extend(files_dropped);
                     }
 
-                    if !files_to_use.is_empty() {
-                        context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+                    // Unchanged context files were already scanned and formatted on a previous turn.
+                    let hash = context_files_hash(&files_to_use);
+                    let cached = match files_to_use.is_empty() {
+                        true => None,
+                        false => context_manager.cached_context_block(hash),
+                    };
+                    if let Some(block) = cached {
+                        context_content.push_str(&block);
+                    } else if !files_to_use.is_empty() {
+                        let mut block = CONTEXT_ENTRY_START_HEADER.to_string();
                         for (filename, content) in files_to_use {
-                            context_content.push_str(&format!("[{}]\n{}\n", filename, content));
+                            let findings = self.injection_guard.scan_context(&filename, &content);
+                            if !findings.is_empty() {
+                                self.flagged_context.push((filename.clone(), findings));
+                            }
+                            block.push_str(&format!("[{}]\n{}\n", filename, content));
                         }
-                        context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+                        block.push_str(CONTEXT_ENTRY_END_HEADER);
+                        context_content.push_str(&block);
+                        context_manager.cache_context_block(hash, block);
                     }
                 },
                 Err(e) => {
@@ -595,6 +1258,27 @@ impl ConversationState {
             context_content.push_str(&context);
         }
 
+        if let Some(plan) = &self.plan {
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            context_content.push_str("This is the plan you are currently following for the user's task. Continue working through it and keep it up to date with the update_plan tool as you make progress.\n\n");
+            context_content.push_str("PLAN:\n");
+            context_content.push_str(&plan.to_string());
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+        }
+
+        // The language is detected from the latest prompt, as tool results have none.
+        let latest_prompt = self
+            .next_message
+            .iter()
+            .chain(self.history.iter().rev().map(|entry| &entry.user))
+            .find_map(UserMessage::prompt);
+        if let Some(language) = language::response_language(os, latest_prompt) {
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            context_content.push_str(&language::instruction(&language));
+            context_content.push('\n');
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+        }
+
         if let Some(agent_prompt) = self.agents.get_active().and_then(|a| a.prompt.as_ref()) {
             context_content.push_str(&format!("Follow this instruction: {}", agent_prompt));
         }
@@ -621,6 +1305,44 @@ impl ConversationState {
         self.context_message_length
     }
 
+    /// Estimates the total character count of the context message and the history, without
+    /// building the backend conversation state.
+    pub fn estimated_char_count(&self) -> CharCount {
+        let history = match self.history_size {
+            Some(size) => *size.user_messages + *size.assistant_messages,
+            None => self
+                .history
+                .iter()
+                .map(|entry| *entry.user.char_count() + *entry.assistant.char_count())
+                .sum::<usize>(),
+        };
+        (self.context_message_length.unwrap_or_default() + history).into()
+    }
+
+    /// Returns the character counts of the messages in the valid history range, counting them
+    /// again only after the history changed.
+    fn history_size(&mut self) -> ConversationSize {
+        *self.history_size.get_or_insert_with(|| {
+            let (user_chars, assistant_chars) = self
+                .history
+                .range(self.valid_history_range.0..self.valid_history_range.1)
+                .fold(
+                    (0, 0),
+                    |(user_chars, assistant_chars), HistoryEntry { user, assistant, .. }| {
+                        (
+                            user_chars + *user.char_count(),
+                            assistant_chars + *assistant.char_count(),
+                        )
+                    },
+                );
+            ConversationSize {
+                context_messages: 0.into(),
+                user_messages: user_chars.into(),
+                assistant_messages: assistant_chars.into(),
+            }
+        })
+    }
+
     /// Calculate the total character count in the conversation
     pub async fn calculate_char_count(&mut self, os: &Os) -> Result<CharCount, ChatError> {
         Ok(self
@@ -651,7 +1373,17 @@ impl ConversationState {
         self.append_transcript(format!("{}\n[Tool uses: {tool_uses}]", message.content()));
     }
 
+    /// Drops the oldest transcript entries until at most `max_entries` remain.
+    pub fn trim_transcript(&mut self, max_entries: usize) {
+        let excess = self.transcript.len().saturating_sub(max_entries);
+        self.transcript.drain(..excess);
+    }
+
     pub fn append_transcript(&mut self, message: String) {
+        let message = match self.redactor.is_empty() {
+            true => message,
+            false => self.redactor.redact(&message),
+        };
         if self.transcript.len() >= MAX_CONVERSATION_STATE_HISTORY_LEN {
             self.transcript.pop_front();
         }
@@ -673,6 +1405,8 @@ pub type BackendConversationState<'a> =
 pub struct BackendConversationStateImpl<'a, T, U> {
     pub conversation_id: &'a str,
     pub next_user_message: Option<&'a UserMessage>,
+    /// Character counts of [Self::history], see [Self::calculate_conversation_size].
+    pub history_size: ConversationSize,
     pub history: T,
     pub context_messages: U,
     pub dropped_context_files: Vec<(String, String)>,
@@ -697,20 +1431,9 @@ impl BackendConversationStateImpl<'_, std::collections::vec_deque::Iter<'_, Hist
     }
 
     pub fn calculate_conversation_size(&self) -> ConversationSize {
-        let mut user_chars = 0;
-        let mut assistant_chars = 0;
-        let mut context_chars = 0;
-
-        // Count the chars used by the messages in the history.
-        // this clone is cheap
-        let history = self.history.clone();
-        for HistoryEntry { user, assistant, .. } in history {
-            user_chars += *user.char_count();
-            assistant_chars += *assistant.char_count();
-        }
-
-        // Add any chars from context messages, if available.
-        context_chars += self
+        // The history is counted once per change by the conversation state, only the context
+        // messages are counted here.
+        let context_chars = self
             .context_messages
             .as_ref()
             .map(|v| {
@@ -722,8 +1445,7 @@ impl BackendConversationStateImpl<'_, std::collections::vec_deque::Iter<'_, Hist
 
         ConversationSize {
             context_messages: context_chars.into(),
-            user_messages: user_chars.into(),
-            assistant_messages: assistant_chars.into(),
+            ..self.history_size
         }
     }
 }
@@ -736,6 +1458,25 @@ pub struct ConversationSize {
     pub assistant_messages: CharCount,
 }
 
+/// Extracts a conversation title from the response to [ConversationState::create_title_request].
+pub fn title_from_response(response: &str) -> Option<String> {
+    let line = response.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line.trim_start_matches('#').trim_start();
+    let line = line
+        .strip_prefix("Title:")
+        .or_else(|| line.strip_prefix("title:"))
+        .unwrap_or(line)
+        .trim()
+        .trim_matches(|c| matches!(c, '"' | '\'' | '`' | '*'))
+        .trim_end_matches('.')
+        .trim();
+    match truncate_safe(line, TITLE_MAX_LENGTH) {
+        "" => None,
+        title if title.len() < line.len() => Some(format!("{}…", title.trim_end())),
+        title => Some(title.to_string()),
+    }
+}
+
 /// Converts a list of user/assistant message pairs into a flattened list of ChatMessage.
 fn flatten_history<'a, T>(history: T) -> Vec<ChatMessage>
 where
@@ -878,8 +1619,8 @@ fn enforce_conversation_invariants(
     valid_history_range
 }
 
-fn enforce_tool_use_history_invariants(history: &mut VecDeque<HistoryEntry>, tools: &HashMap<ToolOrigin, Vec<Tool>>) {
-    let tool_names: HashSet<_> = tools
+fn available_tool_names(tools: &HashMap<ToolOrigin, Vec<Tool>>) -> HashSet<&str> {
+    tools
         .values()
         .flat_map(|tools| {
             tools.iter().map(|tool| match tool {
@@ -887,7 +1628,11 @@ fn enforce_tool_use_history_invariants(history: &mut VecDeque<HistoryEntry>, too
             })
         })
         .filter(|name| *name != DUMMY_TOOL_NAME)
-        .collect();
+        .collect()
+}
+
+fn enforce_tool_use_history_invariants(history: &mut VecDeque<HistoryEntry>, tools: &HashMap<ToolOrigin, Vec<Tool>>) {
+    let tool_names = available_tool_names(tools);
 
     for HistoryEntry { assistant, .. } in history {
         if let AssistantMessage::ToolUse { tool_uses, .. } = assistant {
@@ -933,12 +1678,15 @@ mod tests {
     use super::*;
     use crate::api_client::model::{
         AssistantResponseMessage,
+        ImageFormat,
+        ImageSource,
         ToolResultStatus,
     };
     use crate::cli::agent::{
         Agent,
         Agents,
     };
+    use crate::cli::chat::consts::RESUMED_HISTORY_LEN;
     use crate::cli::chat::tool_manager::ToolManager;
 
     const AMAZONQ_FILENAME: &str = "AmazonQ.md";
@@ -1031,6 +1779,34 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_conversation_state_attach_images() {
+        let mut os = Os::new().await.unwrap();
+        let mut output = vec![];
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut output).await.unwrap(),
+            tool_manager,
+            None,
+        )
+        .await;
+
+        conversation
+            .set_next_user_message("what is in this screenshot?".to_string())
+            .await;
+        conversation.attach_images(vec![ImageBlock {
+            format: ImageFormat::Png,
+            source: ImageSource::Bytes(vec![0x89, b'P', b'N', b'G']),
+        }]);
+        let s = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], true)
+            .await
+            .unwrap();
+        assert_eq!(s.user_input_message.images.map(|images| images.len()), Some(1));
+    }
+
     #[tokio::test]
     async fn test_conversation_state_history_handling_truncation() {
         let mut os = Os::new().await.unwrap();
@@ -1061,6 +1837,560 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_title_from_response() {
+        assert_eq!(
+            title_from_response("Fixing the build"),
+            Some("Fixing the build".to_string())
+        );
+        assert_eq!(
+            title_from_response("\n## Title: \"Deploying to Lambda.\"\nMore text"),
+            Some("Deploying to Lambda".to_string())
+        );
+        assert_eq!(title_from_response("  \n\"\"\n"), None);
+        assert_eq!(
+            title_from_response(&"a".repeat(100)),
+            Some(format!("{}…", "a".repeat(60)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_title_request() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        assert!(conversation.create_title_request().is_none());
+
+        conversation
+            .set_next_user_message("how do I list s3 buckets".to_string())
+            .await;
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_response(None, "Run aws s3 ls".into()),
+            None,
+        );
+        let request = conversation.create_title_request().unwrap();
+        assert!(request.history.is_none());
+        assert!(
+            request
+                .user_input_message
+                .content
+                .contains("User: how do I list s3 buckets")
+        );
+        assert!(request.user_input_message.content.contains("Assistant: Run aws s3 ls"));
+
+        conversation.title = Some("Listing S3 buckets".to_string());
+        assert!(conversation.create_title_request().is_none());
+        conversation.clear(false);
+        assert!(conversation.title.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pin_exchange() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        assert!(conversation.pin_exchange(None).is_err());
+
+        conversation.set_next_user_message("use port 8080".to_string()).await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "Noted.".into()), None);
+        conversation.set_next_user_message("read the config".to_string()).await;
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_tool_use(None, "Reading it.".into(), vec![AssistantToolUse {
+                id: "tool_id".to_string(),
+                name: "fs_read".to_string(),
+                ..Default::default()
+            }]),
+            None,
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "tool_id".to_string(),
+            content: vec![ToolUseResultBlock::Text("port = 80".to_string())],
+            status: ToolResultStatus::Success,
+        }]);
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_response(None, "It uses 80.".into()),
+            None,
+        );
+
+        assert_eq!(conversation.exchanges(), vec![
+            PinnedExchange {
+                prompt: "use port 8080".to_string(),
+                response: "Noted.".to_string(),
+            },
+            PinnedExchange {
+                prompt: "read the config".to_string(),
+                response: "Reading it.\nIt uses 80.".to_string(),
+            },
+        ]);
+        assert_eq!(conversation.pin_exchange(Some(1)).unwrap().prompt, "use port 8080");
+        assert!(conversation.pin_exchange(Some(1)).is_err());
+        assert!(conversation.pin_exchange(Some(3)).is_err());
+        assert_eq!(conversation.pin_exchange(None).unwrap().prompt, "read the config");
+
+        // Pinned exchanges survive the history being replaced.
+        conversation.replace_history_with_summary(
+            "summary".to_string(),
+            CompactStrategy::default(),
+            RequestMetadata::default(),
+        );
+        conversation.set_next_user_message("which port?".to_string()).await;
+        let s = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], true)
+            .await
+            .unwrap();
+        let context = match s.history.as_ref().and_then(|h| h.first()) {
+            Some(ChatMessage::UserInputMessage(message)) => message.content.clone(),
+            other => panic!("expected a context message, got {other:?}"),
+        };
+        assert!(
+            context.contains("[1] User: use port 8080\nAssistant: Noted."),
+            "{context}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_response_language() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        let context = |s: FigConversationState| match s.history.as_ref().and_then(|h| h.first()) {
+            Some(ChatMessage::UserInputMessage(message)) => Some(message.content.clone()),
+            _ => None,
+        };
+
+        conversation
+            .set_next_user_message("what does main do?".to_string())
+            .await;
+        let s = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], true)
+            .await
+            .unwrap();
+        assert!(context(s).is_none_or(|context| !context.contains("Respond in")));
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "It starts.".into()), None);
+
+        // Tool results keep the language of the prompt before them.
+        conversation
+            .set_next_user_message("请解释这个函数的作用".to_string())
+            .await;
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_tool_use(None, "".into(), vec![AssistantToolUse {
+                id: "tool_id".to_string(),
+                name: "fs_read".to_string(),
+                ..Default::default()
+            }]),
+            None,
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "tool_id".to_string(),
+            content: vec![ToolUseResultBlock::Text("fn main() {}".to_string())],
+            status: ToolResultStatus::Success,
+        }]);
+        let s = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], true)
+            .await
+            .unwrap();
+        let context = context(s).unwrap();
+        assert!(context.contains("Respond in Chinese"), "{context}");
+    }
+
+    #[tokio::test]
+    async fn test_model_override() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_config,
+            tool_manager,
+            Some("SESSION_MODEL".to_string()),
+        )
+        .await;
+        conversation.set_next_user_message("hello".to_string()).await;
+        let model_id = async |conversation: &mut ConversationState| {
+            conversation
+                .as_sendable_conversation_state(&os, &mut vec![], true)
+                .await
+                .unwrap()
+                .user_input_message
+                .model_id
+        };
+
+        assert_eq!(model_id(&mut conversation).await.as_deref(), Some("SESSION_MODEL"));
+        conversation.model_override = Some("OVERRIDE_MODEL".to_string());
+        assert_eq!(model_id(&mut conversation).await.as_deref(), Some("OVERRIDE_MODEL"));
+        assert_eq!(conversation.model.as_deref(), Some("SESSION_MODEL"));
+    }
+
+    #[tokio::test]
+    async fn test_redact_matches() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+
+        conversation
+            .set_next_user_message("my token is s3cr3t-t0ken".to_string())
+            .await;
+        conversation.append_user_transcript("my token is s3cr3t-t0ken");
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_tool_use(None, "Checking s3cr3t-t0ken.".into(), vec![AssistantToolUse {
+                id: "tool_id".to_string(),
+                name: "execute_bash".to_string(),
+                args: serde_json::json!({ "command": "login --token s3cr3t-t0ken" }),
+                ..Default::default()
+            }]),
+            None,
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "tool_id".to_string(),
+            content: vec![ToolUseResultBlock::Text("logged in with s3cr3t-t0ken".to_string())],
+            status: ToolResultStatus::Success,
+        }]);
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "Done.".into()), None);
+        conversation.pin_exchange(None).unwrap();
+        assert_eq!(conversation.last_prompt(), Some("my token is s3cr3t-t0ken"));
+
+        let pattern = Regex::new(&regex::escape("s3cr3t-t0ken")).unwrap();
+        assert_eq!(conversation.redact_matches(&pattern), 8);
+        let json = serde_json::to_string(&conversation.history).unwrap();
+        assert!(!json.contains("s3cr3t-t0ken"), "{json}");
+        assert!(json.contains("login --token [REDACTED]"), "{json}");
+        assert_eq!(conversation.last_prompt(), Some("my token is [REDACTED]"));
+        assert_eq!(conversation.pinned[0].response, "Checking [REDACTED].\nDone.");
+        assert_eq!(conversation.redact_matches(&pattern), 0);
+    }
+
+    #[tokio::test]
+    async fn test_record_feedback() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        assert!(conversation.record_feedback(FeedbackVote::Up, None).is_err());
+
+        conversation.set_next_user_message("hello".to_string()).await;
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_response(Some("message_1".to_string()), "Hi.".into()),
+            None,
+        );
+        conversation.record_feedback(FeedbackVote::Up, None).unwrap();
+        conversation
+            .record_feedback(FeedbackVote::Down, Some("too short".to_string()))
+            .unwrap();
+        assert_eq!(conversation.feedback, vec![MessageFeedback {
+            message_id: "message_1".to_string(),
+            vote: FeedbackVote::Down,
+            comment: Some("too short".to_string()),
+        }]);
+
+        // Ratings are kept with the conversation.
+        let json = serde_json::to_value(&conversation).unwrap();
+        assert_eq!(json["feedback"][0]["vote"], "down");
+    }
+
+    #[tokio::test]
+    async fn test_replace_history_with_summary_strategies() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+
+        // Three turns, each with a tool use and its result.
+        for i in 0..3 {
+            conversation.set_next_user_message(format!("prompt {i}")).await;
+            conversation.push_assistant_message(
+                &mut os,
+                AssistantMessage::new_tool_use(None, i.to_string(), vec![AssistantToolUse {
+                    id: format!("tool_{i}"),
+                    name: "fs_read".to_string(),
+                    ..Default::default()
+                }]),
+                None,
+            );
+            conversation.add_tool_results(vec![ToolUseResult {
+                tool_use_id: format!("tool_{i}"),
+                content: vec![ToolUseResultBlock::Text(format!("output {i}"))],
+                status: ToolResultStatus::Success,
+            }]);
+            conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, i.to_string()), None);
+        }
+        assert_eq!(conversation.history().len(), 6);
+
+        let tools_only = CompactStrategy {
+            mode: CompactMode::ToolsOnly,
+            messages_to_exclude: 2,
+            ..Default::default()
+        };
+        assert_eq!(conversation.compactable_tool_results(tools_only), 2);
+        conversation.replace_history_with_summary("tools".to_string(), tools_only, RequestMetadata::default());
+        assert_eq!(conversation.history().len(), 6);
+        assert_eq!(conversation.compactable_tool_results(tools_only), 0);
+        let outputs = conversation
+            .history()
+            .iter()
+            .filter_map(|entry| entry.user.tool_use_results())
+            .map(|results| match results[0].content.as_slice() {
+                [ToolUseResultBlock::Text(text)] => text.clone(),
+                _ => panic!("unexpected tool result"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, vec![
+            COMPACTED_TOOL_OUTPUT.to_string(),
+            COMPACTED_TOOL_OUTPUT.to_string(),
+            "output 2".to_string()
+        ]);
+
+        let sliding_window = CompactStrategy {
+            mode: CompactMode::SlidingWindow,
+            messages_to_exclude: 2,
+            ..Default::default()
+        };
+        conversation.replace_history_with_summary("all".to_string(), sliding_window, RequestMetadata::default());
+        assert_eq!(conversation.history().len(), 2);
+        assert_eq!(conversation.history()[0].user.prompt(), Some("prompt 2"));
+        assert_eq!(conversation.latest_summary(), Some("all"));
+    }
+
+    #[tokio::test]
+    async fn test_history_size_cache() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        let counted = |conversation: &ConversationState| {
+            conversation
+                .history()
+                .iter()
+                .map(|entry| (*entry.user.char_count(), *entry.assistant.char_count()))
+                .fold((0, 0), |acc, (user, assistant)| (acc.0 + user, acc.1 + assistant))
+        };
+
+        for i in 0..3 {
+            conversation.set_next_user_message(format!("prompt {i}")).await;
+            let size = conversation
+                .backend_conversation_state(&os, false, &mut vec![])
+                .await
+                .unwrap()
+                .calculate_conversation_size();
+            assert_eq!((*size.user_messages, *size.assistant_messages), counted(&conversation));
+            assert!(conversation.history_size.is_some());
+            conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "a".repeat(i)), None);
+            assert!(conversation.history_size.is_none());
+        }
+
+        conversation.clear(false);
+        assert!(conversation.history_size.is_none());
+        conversation.set_next_user_message("prompt".to_string()).await;
+        let state = conversation
+            .backend_conversation_state(&os, false, &mut vec![])
+            .await
+            .unwrap();
+        assert_eq!(*state.calculate_conversation_size().user_messages, 0);
+    }
+
+    #[tokio::test]
+    async fn test_save_appends_history() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        let cwd = std::env::current_dir().unwrap();
+        let load = |os: &mut Os| {
+            os.database
+                .get_conversation_by_path(&cwd, RESUMED_HISTORY_LEN)
+                .unwrap()
+                .unwrap()
+        };
+        let prompts = |conversation: &ConversationState| {
+            conversation
+                .history()
+                .iter()
+                .map(|entry| entry.user.prompt().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        for i in 0..4 {
+            conversation.set_next_user_message(format!("prompt {i}")).await;
+            conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, i.to_string()), None);
+        }
+        assert_eq!(
+            conversation.stored_history.as_ref().map(|s| s.stored.clone()),
+            Some(0..4)
+        );
+        let loaded = load(&mut os);
+        assert_eq!(prompts(&loaded), prompts(&conversation));
+        assert_eq!(loaded.stored_history, conversation.stored_history);
+
+        // Dropping the oldest entries deletes them on the next save.
+        let sliding_window = CompactStrategy {
+            mode: CompactMode::SlidingWindow,
+            messages_to_exclude: 2,
+            ..Default::default()
+        };
+        conversation.replace_history_with_summary("summary".to_string(), sliding_window, RequestMetadata::default());
+        conversation.set_next_user_message("prompt 4".to_string()).await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "4".to_string()), None);
+        assert_eq!(
+            conversation.stored_history.as_ref().map(|s| s.stored.clone()),
+            Some(2..5)
+        );
+        let loaded = load(&mut os);
+        assert_eq!(prompts(&loaded), vec!["prompt 2", "prompt 3", "prompt 4"]);
+        assert_eq!(loaded.latest_summary(), Some("summary"));
+
+        // Another conversation stored for the same path is replaced rather than appended to.
+        let mut other = load(&mut os);
+        other.conversation_id = "other_conv_id".to_string();
+        other.save(&mut os).unwrap();
+        conversation.set_next_user_message("prompt 5".to_string()).await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "5".to_string()), None);
+        assert_eq!(
+            conversation.stored_history.as_ref().map(|s| s.stored.clone()),
+            Some(0..4)
+        );
+        let loaded = load(&mut os);
+        assert_eq!(loaded.conversation_id(), "fake_conv_id");
+        assert_eq!(prompts(&loaded), prompts(&conversation));
+
+        // Resuming loads the most recent entries, and the older ones when needed.
+        let mut resumed = os.database.get_conversation_by_path(&cwd, 1).unwrap().unwrap();
+        assert_eq!(prompts(&resumed), vec!["prompt 5"]);
+        resumed.set_next_user_message("prompt 6".to_string()).await;
+        resumed.push_assistant_message(&mut os, AssistantMessage::new_response(None, "6".to_string()), None);
+        assert_eq!(prompts(&load(&mut os)), vec![
+            "prompt 2", "prompt 3", "prompt 4", "prompt 5", "prompt 6"
+        ]);
+        resumed.hydrate_history(&os).unwrap();
+        assert_eq!(prompts(&resumed), prompts(&load(&mut os)));
+        assert_eq!(resumed.stored_history, load(&mut os).stored_history);
+
+        // Clearing rewrites the history.
+        conversation.clear(false);
+        conversation.save(&mut os).unwrap();
+        assert!(load(&mut os).history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resume_history_tail() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        let cwd = std::env::current_dir().unwrap();
+        let prompts = |conversation: &ConversationState| {
+            conversation
+                .history()
+                .iter()
+                .filter_map(|entry| entry.user.prompt().map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+
+        conversation.set_next_user_message("prompt 0".to_string()).await;
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_tool_use(None, "Reading it.".into(), vec![AssistantToolUse {
+                id: "tool_id".to_string(),
+                name: "fs_read".to_string(),
+                ..Default::default()
+            }]),
+            None,
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "tool_id".to_string(),
+            content: vec![ToolUseResultBlock::Text("port = 80".to_string())],
+            status: ToolResultStatus::Success,
+        }]);
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "0".into()), None);
+        conversation.set_next_user_message("prompt 1".to_string()).await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "1".into()), None);
+        assert_eq!(
+            conversation.stored_history.as_ref().map(|s| s.stored.clone()),
+            Some(0..3)
+        );
+
+        // The tail does not start with tool results, whose tool uses would be left out.
+        let mut resumed = os.database.get_conversation_by_path(&cwd, 2).unwrap().unwrap();
+        assert_eq!(resumed.history().len(), 1);
+        assert_eq!(prompts(&resumed), vec!["prompt 1"]);
+        let stored = resumed.stored_history.clone().unwrap();
+        assert_eq!((stored.stored, stored.first_seq, stored.unloaded), (0..3, 2, 2));
+
+        resumed.hydrate_history(&os).unwrap();
+        assert_eq!(resumed.history().len(), 3);
+        assert_eq!(prompts(&resumed), vec!["prompt 0", "prompt 1"]);
+        assert_eq!(resumed.stored_history, conversation.stored_history);
+        // Hydrating again is a no-op.
+        resumed.hydrate_history(&os).unwrap();
+        assert_eq!(resumed.history().len(), 3);
+
+        // The entries that were not loaded are deleted along with the dropped ones.
+        let mut resumed = os.database.get_conversation_by_path(&cwd, 1).unwrap().unwrap();
+        resumed.drain_history_front(1);
+        resumed.set_next_user_message("prompt 2".to_string()).await;
+        resumed.push_assistant_message(&mut os, AssistantMessage::new_response(None, "2".into()), None);
+        let loaded = os
+            .database
+            .get_conversation_by_path(&cwd, RESUMED_HISTORY_LEN)
+            .unwrap()
+            .unwrap();
+        assert_eq!(prompts(&loaded), vec!["prompt 2"]);
+        assert_eq!(loaded.stored_history.unwrap().stored, 3..4);
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_history_missing_entries() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        let cwd = std::env::current_dir().unwrap();
+        for i in 0..3 {
+            conversation.set_next_user_message(format!("prompt {i}")).await;
+            conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, i.to_string()), None);
+        }
+        let mut resumed = os.database.get_conversation_by_path(&cwd, 1).unwrap().unwrap();
+
+        // Another conversation replaces the stored one, so the older entries are gone.
+        let mut other = os.database.get_conversation_by_path(&cwd, 1).unwrap().unwrap();
+        other.conversation_id = "other_conv_id".to_string();
+        other.save(&mut os).unwrap();
+
+        resumed.hydrate_history(&os).unwrap();
+        assert_eq!(resumed.history().len(), 1);
+        assert_eq!(resumed.stored_history, None);
+
+        // The history is rewritten by the next save.
+        resumed.save(&mut os).unwrap();
+        let loaded = os
+            .database
+            .get_conversation_by_path(&cwd, RESUMED_HISTORY_LEN)
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.conversation_id(), "fake_conv_id");
+        assert_eq!(loaded.history().len(), 1);
+        assert_eq!(loaded.history()[0].user.prompt(), Some("prompt 2"));
+    }
+
     #[tokio::test]
     async fn test_conversation_state_history_handling_with_tool_results() {
         let mut os = Os::new().await.unwrap();
@@ -1135,6 +2465,40 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_unavailable_tool_uses() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+
+        conversation.set_next_user_message("start".to_string()).await;
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_tool_use(None, "1".to_string(), vec![
+                AssistantToolUse {
+                    id: "1".to_string(),
+                    name: "fs_read".to_string(),
+                    orig_name: "fs_read".to_string(),
+                    ..Default::default()
+                },
+                AssistantToolUse {
+                    id: "2".to_string(),
+                    name: "removed_tool".to_string(),
+                    orig_name: "removed_tool".to_string(),
+                    ..Default::default()
+                },
+            ]),
+            None,
+        );
+        assert_eq!(conversation.unavailable_tool_uses(), vec!["removed_tool".to_string()]);
+
+        conversation.remap_tool_uses("removed_tool", "fs_write");
+        assert!(conversation.unavailable_tool_uses().is_empty());
+        assert!(conversation.available_tool_names().contains(&"fs_write".to_string()));
+    }
+
     #[tokio::test]
     async fn test_conversation_state_with_context_files() {
         let mut os = Os::new().await.unwrap();
@@ -0,0 +1,286 @@
+//! Fuzzy picker drawn in the alternate screen, used by the command selector to pick commands,
+//! files and tools on every platform.
+//!
+//! Typing filters the items, up and down move the cursor, tab marks items when several can be
+//! picked, enter accepts and escape cancels.
+
+use std::collections::BTreeSet;
+use std::io::{
+    Write,
+    stdout,
+};
+
+use crossterm::event::{
+    self,
+    Event,
+    KeyCode,
+    KeyEvent,
+    KeyEventKind,
+    KeyModifiers,
+};
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::terminal::{
+    self,
+    EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::{
+    cursor,
+    execute,
+    queue,
+};
+use eyre::Result;
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+
+/// Lets the user pick from `items`, returning `None` if they cancel. When `multi` is true,
+/// several items can be marked with tab; otherwise, or if none are marked, the item under the
+/// cursor is picked.
+pub fn pick(items: &[String], prompt: &str, multi: bool) -> Result<Option<Vec<String>>> {
+    let mut stdout = stdout();
+    // The picker usually runs while the prompt is being read, which already uses raw mode.
+    let was_raw = terminal::is_raw_mode_enabled()?;
+    if !was_raw {
+        terminal::enable_raw_mode()?;
+    }
+    execute!(stdout, EnterAlternateScreen)?;
+
+    let result = run(&mut stdout, Picker::new(items, multi), prompt);
+
+    execute!(stdout, LeaveAlternateScreen)?;
+    if !was_raw {
+        terminal::disable_raw_mode()?;
+    }
+    result
+}
+
+fn run(output: &mut impl Write, mut picker: Picker<'_>, prompt: &str) -> Result<Option<Vec<String>>> {
+    loop {
+        let height = terminal::size().map_or(24, |(_, rows)| rows as usize);
+        picker.render(output, prompt, height.saturating_sub(1))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match picker.handle_key(key) {
+                Some(Outcome::Picked(items)) => return Ok(Some(items)),
+                Some(Outcome::Cancelled) => return Ok(None),
+                None => (),
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Picked(Vec<String>),
+    Cancelled,
+}
+
+struct Picker<'a> {
+    items: &'a [String],
+    multi: bool,
+    query: String,
+    /// Indices of the items matching the query, best match first.
+    matches: Vec<usize>,
+    /// Position of the cursor in `matches`.
+    cursor: usize,
+    /// Indices of the marked items.
+    marked: BTreeSet<usize>,
+    matcher: SkimMatcherV2,
+}
+
+impl<'a> Picker<'a> {
+    fn new(items: &'a [String], multi: bool) -> Self {
+        Self {
+            items,
+            multi,
+            query: String::new(),
+            matches: (0..items.len()).collect(),
+            cursor: 0,
+            marked: BTreeSet::new(),
+            matcher: SkimMatcherV2::default().smart_case(),
+        }
+    }
+
+    fn update_matches(&mut self) {
+        let mut scored = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| self.matcher.fuzzy_match(item, &self.query).map(|score| (score, i)))
+            .collect::<Vec<_>>();
+        // Ties keep the order of the items.
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.cursor = 0;
+    }
+
+    /// Returns the outcome of the picker if `key` ends it.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Outcome> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(Outcome::Cancelled),
+            KeyCode::Char('c' | 'd' | 'g') if ctrl => return Some(Outcome::Cancelled),
+            KeyCode::Enter => {
+                return Some(match (self.marked.is_empty(), self.matches.get(self.cursor)) {
+                    (false, _) => Outcome::Picked(self.marked.iter().map(|i| self.items[*i].clone()).collect()),
+                    (true, Some(i)) => Outcome::Picked(vec![self.items[*i].clone()]),
+                    (true, None) => Outcome::Cancelled,
+                });
+            },
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Char('p' | 'k') if ctrl => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => self.move_down(),
+            KeyCode::Char('n' | 'j') if ctrl => self.move_down(),
+            KeyCode::Tab if self.multi => {
+                if let Some(i) = self.matches.get(self.cursor) {
+                    if !self.marked.remove(i) {
+                        self.marked.insert(*i);
+                    }
+                }
+                self.move_down();
+            },
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update_matches();
+            },
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.update_matches();
+            },
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.update_matches();
+            },
+            _ => (),
+        }
+        None
+    }
+
+    fn move_down(&mut self) {
+        if self.cursor + 1 < self.matches.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Draws the query line followed by up to `rows` matches, scrolled to show the cursor.
+    fn render(&self, output: &mut impl Write, prompt: &str, rows: usize) -> Result<()> {
+        queue!(
+            output,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0),
+            style::SetForegroundColor(Color::Magenta),
+            style::Print(prompt),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(&self.query),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!("  {}/{}", self.matches.len(), self.items.len())),
+        )?;
+        if self.multi && !self.marked.is_empty() {
+            queue!(output, style::Print(format!(" ({} marked)", self.marked.len())))?;
+        }
+        queue!(output, style::SetForegroundColor(Color::Reset))?;
+
+        let rows = rows.max(1);
+        let first = self.cursor.saturating_sub(rows - 1);
+        for (row, (position, i)) in self.matches.iter().enumerate().skip(first).take(rows).enumerate() {
+            let marker = match self.marked.contains(i) {
+                true => "● ",
+                false => "  ",
+            };
+            queue!(output, cursor::MoveTo(0, row as u16 + 1))?;
+            if position == self.cursor {
+                queue!(
+                    output,
+                    style::SetForegroundColor(Color::Cyan),
+                    style::SetAttribute(Attribute::Bold),
+                    style::Print(format!("> {marker}{}", self.items[*i])),
+                    style::SetAttribute(Attribute::Reset),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            } else {
+                queue!(output, style::Print(format!("  {marker}{}", self.items[*i])))?;
+            }
+        }
+
+        let query_end = (prompt.chars().count() + self.query.chars().count()) as u16;
+        queue!(output, cursor::MoveTo(query_end, 0))?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn type_query(picker: &mut Picker<'_>, query: &str) {
+        for c in query.chars() {
+            assert_eq!(picker.handle_key(key(KeyCode::Char(c))), None);
+        }
+    }
+
+    #[test]
+    fn test_filter_and_pick() {
+        let items = ["/compact", "/context add", "/context rm", "/tools trust"].map(String::from);
+        let mut picker = Picker::new(&items, false);
+        type_query(&mut picker, "ctxrm");
+        assert_eq!(picker.matches, vec![2]);
+
+        picker.handle_key(key(KeyCode::Backspace));
+        picker.handle_key(key(KeyCode::Backspace));
+        assert_eq!(picker.matches, vec![1, 2]);
+
+        picker.handle_key(key(KeyCode::Down));
+        assert_eq!(
+            picker.handle_key(key(KeyCode::Enter)),
+            Some(Outcome::Picked(vec!["/context rm".to_string()]))
+        );
+
+        let mut picker = Picker::new(&items, false);
+        type_query(&mut picker, "zzz");
+        assert_eq!(picker.handle_key(key(KeyCode::Enter)), Some(Outcome::Cancelled));
+        assert_eq!(picker.handle_key(key(KeyCode::Esc)), Some(Outcome::Cancelled));
+    }
+
+    #[test]
+    fn test_multi_pick() {
+        let items = ["src/main.rs", "src/lib.rs", "README.md"].map(String::from);
+        let mut picker = Picker::new(&items, true);
+        picker.handle_key(key(KeyCode::Tab));
+        picker.handle_key(key(KeyCode::Down));
+        picker.handle_key(key(KeyCode::Tab));
+        assert_eq!(
+            picker.handle_key(key(KeyCode::Enter)),
+            Some(Outcome::Picked(vec![
+                "src/main.rs".to_string(),
+                "README.md".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_render_scrolls_to_cursor() {
+        let items = (0..10).map(|i| format!("item {i}")).collect::<Vec<_>>();
+        let mut picker = Picker::new(&items, false);
+        for _ in 0..6 {
+            picker.handle_key(key(KeyCode::Down));
+        }
+        let mut output = Vec::new();
+        picker.render(&mut output, "> ", 3).unwrap();
+        let output = String::from_utf8(strip_ansi_escapes::strip(output)).unwrap();
+        assert!(output.contains("item 6"));
+        assert!(output.contains("item 4"));
+        assert!(!output.contains("item 3"));
+        assert!(!output.contains("item 7"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Configuration of the greeting shown at the start of a session.
+//!
+//! The greeting can be customized with the `chat.greeting.*` settings, or by an organization
+//! through the greeting config file (see [chat_greeting_config_path]), which takes precedence.
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::directories::chat_greeting_config_path;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GreetingConfig {
+    /// Message of the day shown in place of the welcome text.
+    #[serde(default)]
+    pub motd: Option<String>,
+    /// Whether to show a tip, defaults to true.
+    #[serde(default)]
+    pub show_tips: Option<bool>,
+    /// Text shown at the start of every session, even when the greeting is disabled.
+    #[serde(default)]
+    pub policy: Option<String>,
+}
+
+impl GreetingConfig {
+    /// Loads the greeting configured through settings, overridden by the values of the greeting
+    /// config file if it exists.
+    pub async fn load(os: &Os) -> Self {
+        let settings = &os.database.settings;
+        let config = Self {
+            motd: settings.get_string(Setting::ChatGreetingMotd),
+            show_tips: settings.get_bool(Setting::ChatGreetingTipsEnabled),
+            policy: settings.get_string(Setting::ChatGreetingPolicy),
+        };
+
+        let Ok(path) = chat_greeting_config_path(os) else {
+            return config;
+        };
+        let managed = match os.fs.read_to_string(&path).await {
+            Ok(content) => match serde_json::from_str::<Self>(&content) {
+                Ok(managed) => managed,
+                Err(err) => {
+                    warn!(?err, ?path, "invalid greeting config");
+                    return config;
+                },
+            },
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!(?err, ?path, "failed to read the greeting config");
+                }
+                return config;
+            },
+        };
+
+        Self {
+            motd: managed.motd.or(config.motd),
+            show_tips: managed.show_tips.or(config.show_tips),
+            policy: managed.policy.or(config.policy),
+        }
+    }
+
+    pub fn show_tips(&self) -> bool {
+        self.show_tips.unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load() {
+        let mut os = Os::new().await.unwrap();
+        assert_eq!(GreetingConfig::load(&os).await, GreetingConfig::default());
+        assert!(GreetingConfig::default().show_tips());
+
+        os.database
+            .settings
+            .set(Setting::ChatGreetingMotd, "Welcome to Example Corp")
+            .await
+            .unwrap();
+        os.database
+            .settings
+            .set(Setting::ChatGreetingTipsEnabled, true)
+            .await
+            .unwrap();
+        let path = chat_greeting_config_path(&os).unwrap();
+        os.fs.create_dir_all(path.parent().unwrap()).await.unwrap();
+        os.fs
+            .write(
+                &path,
+                r#"{ "showTips": false, "policy": "Do not paste customer data." }"#,
+            )
+            .await
+            .unwrap();
+
+        let config = GreetingConfig::load(&os).await;
+        assert_eq!(config, GreetingConfig {
+            motd: Some("Welcome to Example Corp".to_string()),
+            show_tips: Some(false),
+            policy: Some("Do not paste customer data.".to_string()),
+        });
+        assert!(!config.show_tips());
+    }
+}
@@ -1,24 +1,46 @@
 use eyre::Result;
+use rustyline::EditMode;
+use rustyline::config::Configurer;
 use rustyline::error::ReadlineError;
 
-use super::prompt::rl;
-#[cfg(unix)]
-use super::skim_integration::SkimCommandSelector;
+use super::acp::AcpInput;
+use super::command_selector::CommandSelector;
+use super::keymap::{
+    KeyAction,
+    Keymap,
+    SubmitLine,
+};
+use super::prompt::{
+    ChatHelper,
+    rl,
+};
+use super::server::ServerInput;
+use super::tui::TuiInput;
 use crate::os::Os;
 
 #[derive(Debug)]
-pub struct InputSource(inner::Inner);
+pub struct InputSource {
+    inner: inner::Inner,
+    /// Text the next line starts with, for the user to edit.
+    initial_line: Option<String>,
+}
 
 mod inner {
     use rustyline::Editor;
     use rustyline::history::FileHistory;
 
+    use super::super::acp::AcpInput;
     use super::super::prompt::ChatHelper;
+    use super::super::server::ServerInput;
+    use super::super::tui::TuiInput;
 
     #[allow(clippy::large_enum_variant)]
     #[derive(Debug)]
     pub enum Inner {
         Readline(Editor<ChatHelper, FileHistory>),
+        Tui(TuiInput),
+        Server(ServerInput),
+        Acp(AcpInput),
         #[allow(dead_code)]
         Mock {
             index: usize,
@@ -33,49 +55,131 @@ impl InputSource {
         sender: std::sync::mpsc::Sender<Option<String>>,
         receiver: std::sync::mpsc::Receiver<Vec<String>>,
     ) -> Result<Self> {
-        Ok(Self(inner::Inner::Readline(rl(os, sender, receiver)?)))
+        Ok(Self {
+            inner: inner::Inner::Readline(rl(os, sender, receiver)?),
+            initial_line: None,
+        })
+    }
+
+    /// Reads lines typed into the input box of `--tui`.
+    pub fn new_tui(input: TuiInput) -> Self {
+        Self {
+            inner: inner::Inner::Tui(input),
+            initial_line: None,
+        }
+    }
+
+    /// Reads lines sent by the clients of `q chat serve`.
+    pub fn new_server(input: ServerInput) -> Self {
+        Self {
+            inner: inner::Inner::Server(input),
+            initial_line: None,
+        }
     }
 
-    #[cfg(unix)]
-    pub fn put_skim_command_selector(
+    /// Reads prompts sent by the editor driving `q chat --acp`.
+    pub fn new_acp(input: AcpInput) -> Self {
+        Self {
+            inner: inner::Inner::Acp(input),
+            initial_line: None,
+        }
+    }
+
+    pub fn put_command_selector(
         &mut self,
         os: &Os,
         context_manager: std::sync::Arc<super::context::ContextManager>,
         tool_names: Vec<String>,
     ) {
-        use rustyline::{
-            EventHandler,
-            KeyEvent,
-        };
+        use rustyline::EventHandler;
+
+        if let inner::Inner::Readline(rl) = &mut self.inner {
+            for key in Keymap::load(os).0.keys(KeyAction::FuzzySearch) {
+                rl.bind_sequence(
+                    key.event,
+                    EventHandler::Conditional(Box::new(CommandSelector::new(
+                        os.clone(),
+                        context_manager.clone(),
+                        tool_names.clone(),
+                    ))),
+                );
+            }
+        }
+    }
 
-        use crate::database::settings::Setting;
+    /// Binds the keys of [KeyAction::ApproveTool] while a tool waits for approval, and unbinds
+    /// them otherwise so that they keep their usual meaning.
+    pub fn set_tool_approval_pending(&mut self, os: &Os, pending: bool) {
+        use rustyline::EventHandler;
 
-        if let inner::Inner::Readline(rl) = &mut self.0 {
-            let key_char = match os.database.settings.get_string(Setting::SkimCommandKey) {
-                Some(key) if key.len() == 1 => key.chars().next().unwrap_or('s'),
-                _ => 's', // Default to 's' if setting is missing or invalid
+        if let inner::Inner::Readline(rl) = &mut self.inner {
+            let Some(submitted) = rl.helper().map(ChatHelper::submitted) else {
+                return;
             };
-            rl.bind_sequence(
-                KeyEvent::ctrl(key_char),
-                EventHandler::Conditional(Box::new(SkimCommandSelector::new(
-                    os.clone(),
-                    context_manager,
-                    tool_names,
-                ))),
-            );
+            for key in Keymap::load(os).0.keys(KeyAction::ApproveTool) {
+                match pending {
+                    true => rl.bind_sequence(
+                        key.event,
+                        EventHandler::Conditional(Box::new(SubmitLine::new(submitted.clone(), |_| "y".to_string()))),
+                    ),
+                    false => rl.unbind_sequence(key.event),
+                };
+            }
         }
     }
 
     #[allow(dead_code)]
     pub fn new_mock(lines: Vec<String>) -> Self {
-        Self(inner::Inner::Mock { index: 0, lines })
+        Self {
+            inner: inner::Inner::Mock { index: 0, lines },
+            initial_line: None,
+        }
+    }
+
+    /// Returns a printer that writes above the line being read, without disturbing it.
+    pub fn external_printer(&mut self) -> Option<Box<dyn rustyline::ExternalPrinter + Send>> {
+        match &mut self.inner {
+            inner::Inner::Readline(rl) => match rl.create_external_printer() {
+                Ok(printer) => Some(Box::new(printer)),
+                Err(err) => {
+                    tracing::warn!(?err, "failed to create an external printer");
+                    None
+                },
+            },
+            _ => None,
+        }
+    }
+
+    /// Sets the tool names completed as the arguments of `/tools trust` and `/tools untrust`.
+    pub fn set_tool_names(&mut self, tool_names: Vec<String>) {
+        if let inner::Inner::Readline(rl) = &mut self.inner {
+            if let Some(helper) = rl.helper_mut() {
+                helper.set_tool_names(tool_names);
+            }
+        }
+    }
+
+    /// Changes the key bindings used to edit lines.
+    pub fn set_edit_mode(&mut self, edit_mode: EditMode) {
+        if let inner::Inner::Readline(rl) = &mut self.inner {
+            rl.set_edit_mode(edit_mode);
+        }
+    }
+
+    /// Starts the next line with `text`, which the user can edit before submitting it.
+    pub fn set_initial_line(&mut self, text: String) {
+        self.initial_line = Some(text);
     }
 
     pub fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, ReadlineError> {
-        match &mut self.0 {
+        let initial_line = self.initial_line.take();
+        match &mut self.inner {
             inner::Inner::Readline(rl) => {
                 let prompt = prompt.unwrap_or_default();
-                let curr_line = rl.readline(prompt);
+                let curr_line = match initial_line {
+                    Some(initial_line) => rl.readline_with_initial(prompt, (&initial_line, "")),
+                    None => rl.readline(prompt),
+                };
                 match curr_line {
                     Ok(line) => {
                         let _ = rl.add_history_entry(line.as_str());
@@ -86,10 +190,15 @@ impl InputSource {
 
                         Ok(Some(line))
                     },
-                    Err(ReadlineError::Interrupted | ReadlineError::Eof) => Ok(None),
+                    // Keys of the keymap that submit a line interrupt reading, see [SubmitLine].
+                    Err(ReadlineError::Interrupted) => Ok(rl.helper().and_then(ChatHelper::take_submitted)),
+                    Err(ReadlineError::Eof) => Ok(None),
                     Err(err) => Err(err),
                 }
             },
+            inner::Inner::Tui(input) => Ok(input.read_line(prompt.unwrap_or_default(), initial_line)),
+            inner::Inner::Server(input) => Ok(input.read_line(prompt.unwrap_or_default())),
+            inner::Inner::Acp(input) => Ok(input.read_line(prompt.unwrap_or_default())),
             inner::Inner::Mock { index, lines } => {
                 *index += 1;
                 Ok(lines.get(*index - 1).cloned())
@@ -100,7 +209,7 @@ impl InputSource {
     // We're keeping this method for potential future use
     #[allow(dead_code)]
     pub fn set_buffer(&mut self, content: &str) {
-        if let inner::Inner::Readline(rl) = &mut self.0 {
+        if let inner::Inner::Readline(rl) = &mut self.inner {
             // Add to history so user can access it with up arrow
             let _ = rl.add_history_entry(content);
         }
@@ -0,0 +1,362 @@
+//! Key bindings of the prompt, customized with the `chat.keymap` setting, which maps actions to a
+//! key or a list of keys, e.g.
+//! `q settings chat.keymap '{"fuzzySearch": "ctrl-t", "editor": ["alt-e", "ctrl-x"]}'`.
+//!
+//! Actions missing from the setting keep their default keys. Invalid entries are reported when
+//! the session starts and ignored.
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use rustyline::{
+    Cmd,
+    ConditionalEventHandler,
+    EventContext,
+    KeyCode,
+    KeyEvent,
+    Modifiers,
+    RepeatCount,
+};
+use serde_json::Value;
+
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// What a key does at the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    /// Insert a new line, to write a multi-line prompt.
+    Newline,
+    /// Fuzzy search commands and context files, see [super::command_selector].
+    FuzzySearch,
+    /// Discard the line being written.
+    Cancel,
+    /// Allow the tool waiting for approval, as if `y` was entered.
+    ApproveTool,
+    /// Edit the line being written in `$EDITOR`, as with `/editor`.
+    Editor,
+}
+
+impl KeyAction {
+    pub const ALL: &[Self] = &[
+        Self::Newline,
+        Self::FuzzySearch,
+        Self::Cancel,
+        Self::ApproveTool,
+        Self::Editor,
+    ];
+
+    /// Name of the action in the `chat.keymap` setting.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Newline => "newline",
+            Self::FuzzySearch => "fuzzySearch",
+            Self::Cancel => "cancel",
+            Self::ApproveTool => "approveTool",
+            Self::Editor => "editor",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Newline => "Insert new-line to provide multi-line prompt",
+            Self::FuzzySearch => "Fuzzy search commands and context files",
+            Self::Cancel => "Discard the prompt being written",
+            Self::ApproveTool => "Allow the tool waiting for approval",
+            Self::Editor => "Edit the prompt being written in $EDITOR",
+        }
+    }
+
+    /// Keys bound to the action when `chat.keymap` does not set any. Fuzzy search keeps the key
+    /// of the older `chat.skimCommandKey` setting.
+    fn default_keys(&self, os: &Os) -> Vec<String> {
+        match self {
+            Self::Newline => vec!["ctrl-j".to_string(), "alt-enter".to_string()],
+            Self::FuzzySearch => {
+                let key = match os.database.settings.get_string(Setting::SkimCommandKey) {
+                    Some(key) if key.chars().count() == 1 => key,
+                    _ => "s".to_string(),
+                };
+                vec![format!("ctrl-{key}")]
+            },
+            Self::Cancel => vec!["ctrl-c".to_string()],
+            Self::ApproveTool => Vec::new(),
+            Self::Editor => vec!["alt-e".to_string()],
+        }
+    }
+}
+
+/// A key of the keymap, with how it was written in the setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    pub event: KeyEvent,
+    pub spec: String,
+}
+
+impl std::fmt::Display for Key {
+    /// Formats the key as in the help, e.g. `Ctrl(^) + j`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let KeyEvent(code, mods) = self.event;
+        for (modifier, label) in [
+            (Modifiers::CTRL, "Ctrl(^)"),
+            (Modifiers::ALT, "Alt(⌥)"),
+            (Modifiers::SHIFT, "Shift(⇧)"),
+        ] {
+            if mods.contains(modifier) {
+                write!(f, "{label} + ")?;
+            }
+        }
+        match code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c.to_ascii_lowercase()),
+            KeyCode::Enter => write!(f, "Enter(⏎)"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Delete"),
+            KeyCode::Up => write!(f, "Up"),
+            KeyCode::Down => write!(f, "Down"),
+            KeyCode::Left => write!(f, "Left"),
+            KeyCode::Right => write!(f, "Right"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            _ => write!(f, "{}", self.spec),
+        }
+    }
+}
+
+/// Keys bound to each [KeyAction].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(KeyAction, Vec<Key>)>,
+}
+
+impl Keymap {
+    /// Loads the keymap from the `chat.keymap` setting, with the errors of the entries that were
+    /// ignored.
+    pub fn load(os: &Os) -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        let configured = match os.database.settings.get(Setting::ChatKeymap) {
+            Some(Value::Object(map)) => map.clone(),
+            Some(_) => {
+                errors.push("chat.keymap must map actions to keys".to_string());
+                Default::default()
+            },
+            None => Default::default(),
+        };
+        for name in configured.keys() {
+            if !KeyAction::ALL.iter().any(|action| action.name() == name) {
+                errors.push(format!(
+                    "Unknown action {name} in chat.keymap, expected one of {}",
+                    KeyAction::ALL
+                        .iter()
+                        .map(KeyAction::name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+
+        let mut bindings: Vec<(KeyAction, Vec<Key>)> = Vec::new();
+        for action in KeyAction::ALL {
+            let specs = match configured.get(action.name()) {
+                None => action.default_keys(os),
+                Some(Value::String(spec)) => vec![spec.clone()],
+                Some(Value::Array(specs)) if specs.iter().all(Value::is_string) => {
+                    specs.iter().filter_map(Value::as_str).map(str::to_string).collect()
+                },
+                Some(_) => {
+                    errors.push(format!("chat.keymap.{} must be a key or a list of keys", action.name()));
+                    action.default_keys(os)
+                },
+            };
+
+            let mut keys = Vec::new();
+            for spec in specs {
+                let event = match parse_key(&spec) {
+                    Ok(event) => event,
+                    Err(err) => {
+                        errors.push(format!("chat.keymap.{}: {err}", action.name()));
+                        continue;
+                    },
+                };
+                let bound_to = bindings
+                    .iter()
+                    .find(|(_, keys)| keys.iter().any(|key| key.event == event))
+                    .map(|(action, _)| action.name());
+                if let Some(other) = bound_to {
+                    errors.push(format!(
+                        "chat.keymap.{}: {spec} is already bound to {other}",
+                        action.name()
+                    ));
+                    continue;
+                }
+                keys.push(Key { event, spec });
+            }
+            bindings.push((*action, keys));
+        }
+
+        (Self { bindings }, errors)
+    }
+
+    /// Returns the keys bound to `action`.
+    pub fn keys(&self, action: KeyAction) -> &[Key] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// Submits a line made from the line being written, e.g. `/editor` with it as the initial text.
+///
+/// Readline can only run one command per key, so the line is left in `submitted` and reading is
+/// interrupted, for the reader to return the submitted line instead.
+pub struct SubmitLine {
+    submitted: Arc<Mutex<Option<String>>>,
+    line: fn(&str) -> String,
+}
+
+impl SubmitLine {
+    pub fn new(submitted: Arc<Mutex<Option<String>>>, line: fn(&str) -> String) -> Self {
+        Self { submitted, line }
+    }
+}
+
+impl ConditionalEventHandler for SubmitLine {
+    fn handle(&self, _evt: &rustyline::Event, _n: RepeatCount, _positive: bool, ctx: &EventContext<'_>) -> Option<Cmd> {
+        if let Ok(mut submitted) = self.submitted.lock() {
+            *submitted = Some((self.line)(ctx.line()));
+        }
+        Some(Cmd::Interrupt)
+    }
+}
+
+/// Parses a key such as `ctrl-j`, `alt-enter` or `f2`. Modifiers are separated from the key with
+/// `-` or `+`.
+pub fn parse_key(spec: &str) -> Result<KeyEvent, String> {
+    let spec = spec.trim().to_lowercase();
+    let mut parts = spec.split(['-', '+']).collect::<Vec<_>>();
+    // A trailing separator is the key itself, e.g. ctrl--.
+    if parts.len() > 1 && parts.last() == Some(&"") {
+        parts.pop();
+        if let Some(last) = parts.last_mut() {
+            *last = if spec.ends_with('+') { "+" } else { "-" };
+        }
+    }
+    let Some((key, modifiers)) = parts.split_last() else {
+        return Err(format!("Invalid key {spec}"));
+    };
+
+    let mut mods = Modifiers::NONE;
+    for modifier in modifiers {
+        mods |= match *modifier {
+            "ctrl" | "control" => Modifiers::CTRL,
+            "alt" | "meta" | "option" => Modifiers::ALT,
+            "shift" => Modifiers::SHIFT,
+            other => return Err(format!("Invalid modifier {other} in {spec}")),
+        };
+    }
+
+    let code = match *key {
+        "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "esc" | "escape" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        key if key.len() > 1 && key.starts_with('f') => match key[1..].parse::<u8>() {
+            Ok(n @ 1..=12) => KeyCode::F(n),
+            _ => return Err(format!("Invalid key {spec}")),
+        },
+        key => {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(format!("Invalid key {spec}")),
+            }
+        },
+    };
+    if mods == Modifiers::NONE && matches!(code, KeyCode::Char(_)) {
+        return Err(format!(
+            "{spec} would stop the key from being typed, add a modifier such as ctrl-{spec}"
+        ));
+    }
+
+    Ok(KeyEvent::normalize(KeyEvent(code, mods)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(
+            parse_key("ctrl-j"),
+            Ok(KeyEvent::normalize(KeyEvent(KeyCode::Char('j'), Modifiers::CTRL)))
+        );
+        assert_eq!(parse_key("Ctrl+J"), parse_key("ctrl-j"));
+        assert_eq!(parse_key("alt-enter"), Ok(KeyEvent(KeyCode::Enter, Modifiers::ALT)));
+        assert_eq!(parse_key("f2"), Ok(KeyEvent(KeyCode::F(2), Modifiers::NONE)));
+        assert_eq!(parse_key("ctrl--"), Ok(KeyEvent(KeyCode::Char('-'), Modifiers::CTRL)));
+        assert!(parse_key("j").is_err());
+        assert!(parse_key("hyper-j").is_err());
+        assert!(parse_key("ctrl-jj").is_err());
+        assert!(parse_key("f13").is_err());
+    }
+
+    #[test]
+    fn test_display_key() {
+        let key = |spec: &str| Key {
+            event: parse_key(spec).unwrap(),
+            spec: spec.to_string(),
+        };
+        assert_eq!(key("ctrl-j").to_string(), "Ctrl(^) + j");
+        assert_eq!(key("alt-enter").to_string(), "Alt(⌥) + Enter(⏎)");
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let mut os = Os::new().await.unwrap();
+        let (keymap, errors) = Keymap::load(&os);
+        assert!(errors.is_empty());
+        assert_eq!(keymap.keys(KeyAction::Newline).len(), 2);
+        assert_eq!(keymap.keys(KeyAction::FuzzySearch)[0].spec, "ctrl-s");
+        assert!(keymap.keys(KeyAction::ApproveTool).is_empty());
+
+        os.database.settings.set(Setting::SkimCommandKey, "t").await.unwrap();
+        assert_eq!(Keymap::load(&os).0.keys(KeyAction::FuzzySearch)[0].spec, "ctrl-t");
+
+        os.database
+            .settings
+            .set(
+                Setting::ChatKeymap,
+                json!({
+                    "approveTool": "ctrl-y",
+                    "editor": ["ctrl-x", "ctrl-j", "nope"],
+                    "fuzzySearch": 1,
+                    "launch": "ctrl-l",
+                }),
+            )
+            .await
+            .unwrap();
+        let (keymap, errors) = Keymap::load(&os);
+        assert_eq!(errors.len(), 4, "{errors:?}");
+        assert_eq!(keymap.keys(KeyAction::ApproveTool)[0].spec, "ctrl-y");
+        assert_eq!(keymap.keys(KeyAction::FuzzySearch)[0].spec, "ctrl-t");
+        // ctrl-j stays bound to newline, which comes first.
+        let editor = keymap.keys(KeyAction::Editor);
+        assert_eq!(editor.iter().map(|k| k.spec.as_str()).collect::<Vec<_>>(), vec![
+            "ctrl-x"
+        ]);
+    }
+}
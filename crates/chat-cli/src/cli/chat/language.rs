@@ -0,0 +1,214 @@
+//! Language of the responses, set with the `chat.responseLanguage` setting, or otherwise detected
+//! from the prompts of the user so that they do not have to ask for it again every turn.
+//!
+//! Detection is a best-effort guess from the script of the prompt, and from common words for
+//! languages written in the latin script. English prompts, and prompts that are mostly code, are
+//! left alone.
+
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Returns the language the model is told to respond in: the `chat.responseLanguage` setting, or
+/// the language detected from `prompt` when the setting is missing or `auto`. `off` disables both.
+pub fn response_language(os: &Os, prompt: Option<&str>) -> Option<String> {
+    match os.database.settings.get_string(Setting::ChatResponseLanguage) {
+        Some(language) if language.eq_ignore_ascii_case("off") => None,
+        Some(language) if !language.trim().is_empty() && !language.eq_ignore_ascii_case("auto") => {
+            Some(language.trim().to_string())
+        },
+        _ => prompt.and_then(detect).map(str::to_string),
+    }
+}
+
+/// Returns the instruction given to the model to respond in `language`.
+pub fn instruction(language: &str) -> String {
+    format!(
+        "Respond in {language}, unless the user explicitly asks for another language. Keep code, commands, file paths and identifiers as they are."
+    )
+}
+
+/// Common words of the languages written in the latin script, English included so that English
+/// prompts that happen to contain one of the other words are not mistaken for them.
+const LATIN_WORDS: &[(&str, &[&str])] = &[
+    ("English", &[
+        "the", "and", "is", "are", "what", "how", "this", "that", "with", "for", "please", "why", "can", "you",
+    ]),
+    ("Spanish", &[
+        "el", "los", "las", "una", "que", "por", "para", "con", "es", "está", "cómo", "qué", "puedes", "esto",
+    ]),
+    ("French", &[
+        "le", "les", "des", "une", "est", "pour", "avec", "dans", "comment", "pourquoi", "peux", "ce", "cette", "qui",
+    ]),
+    ("German", &[
+        "der", "die", "das", "und", "ist", "nicht", "mit", "ein", "eine", "wie", "warum", "kannst", "bitte", "ich",
+    ]),
+    ("Portuguese", &[
+        "o", "os", "uma", "que", "não", "para", "com", "é", "como", "você", "isso", "está", "por", "favor",
+    ]),
+    ("Italian", &[
+        "il", "gli", "della", "che", "non", "per", "con", "è", "come", "perché", "puoi", "questo", "sono", "una",
+    ]),
+];
+
+/// Returns the language `text` is written in, unless it is English or cannot be told.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let prose = prose(text);
+
+    let (mut latin, mut kana, mut han, mut others) = (0, 0, 0, Vec::<(&'static str, usize)>::new());
+    for c in prose.chars().filter(|c| c.is_alphabetic()) {
+        let script = match c as u32 {
+            0x3040..=0x30ff => {
+                kana += 1;
+                continue;
+            },
+            0x3400..=0x4dbf | 0x4e00..=0x9fff => {
+                han += 1;
+                continue;
+            },
+            0xac00..=0xd7af | 0x1100..=0x11ff | 0x3130..=0x318f => "Korean",
+            0x0400..=0x04ff => match c {
+                'і' | 'ї' | 'є' | 'ґ' | 'І' | 'Ї' | 'Є' | 'Ґ' => "Ukrainian",
+                _ => "Russian",
+            },
+            0x0600..=0x06ff => "Arabic",
+            0x0590..=0x05ff => "Hebrew",
+            0x0370..=0x03ff => "Greek",
+            0x0e00..=0x0e7f => "Thai",
+            0x0900..=0x097f => "Hindi",
+            _ => {
+                latin += 1;
+                continue;
+            },
+        };
+        match others.iter_mut().find(|(name, _)| *name == script) {
+            Some((_, count)) => *count += 1,
+            None => others.push((script, 1)),
+        }
+    }
+    // Ukrainian is told from Russian by its letters that Russian does not have.
+    if let Some(ukrainian) = others.iter().position(|(name, _)| *name == "Ukrainian") {
+        let (_, count) = others.remove(ukrainian);
+        match others.iter_mut().find(|(name, _)| *name == "Russian") {
+            Some(russian) => *russian = ("Ukrainian", russian.1 + count),
+            None => others.push(("Ukrainian", count)),
+        }
+    }
+    match (kana, han) {
+        (0, 0) => (),
+        (0, han) => others.push(("Chinese", han)),
+        (kana, han) => others.push(("Japanese", kana + han)),
+    }
+
+    // Text in other scripts is short for the same meaning, so it takes fewer letters to tell.
+    let total = latin + others.iter().map(|(_, count)| count).sum::<usize>();
+    if let Some((language, count)) = others.into_iter().max_by_key(|(_, count)| *count) {
+        if count >= 2 && count * 4 >= total {
+            return Some(language);
+        }
+    }
+
+    let words = prose
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let (language, hits) = LATIN_WORDS
+        .iter()
+        .map(|(language, common)| {
+            let hits = words.iter().filter(|word| common.contains(&word.as_str())).count();
+            (*language, hits)
+        })
+        .max_by_key(|(_, hits)| *hits)?;
+    let english = LATIN_WORDS[0].1;
+    let english_hits = words.iter().filter(|word| english.contains(&word.as_str())).count();
+    match language {
+        "English" => None,
+        language if hits >= 2 && hits > english_hits => Some(language),
+        _ => None,
+    }
+}
+
+/// Returns `text` without fenced code blocks and inline code, which are not written in the
+/// language of the prompt.
+fn prose(text: &str) -> String {
+    let mut in_fence = false;
+    let mut prose = String::new();
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        for (i, part) in line.split('`').enumerate() {
+            if i % 2 == 0 {
+                prose.push_str(part);
+                prose.push(' ');
+            }
+        }
+        prose.push('\n');
+    }
+    prose
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("How do I read a file in Rust?"), None);
+        assert_eq!(detect("src/main.rs の関数を説明してください"), Some("Japanese"));
+        assert_eq!(detect("请解释这个函数的作用"), Some("Chinese"));
+        assert_eq!(detect("이 함수가 무엇을 하는지 설명해 주세요"), Some("Korean"));
+        assert_eq!(detect("Объясни, что делает эта функция"), Some("Russian"));
+        assert_eq!(
+            detect("Поясни, що робить ця функція, і де її використано"),
+            Some("Ukrainian")
+        );
+        assert_eq!(
+            detect("¿Cómo puedo leer un archivo con Rust? Explica qué hace el código"),
+            Some("Spanish")
+        );
+        assert_eq!(
+            detect("Peux-tu expliquer pourquoi le test échoue dans ce module ?"),
+            Some("French")
+        );
+        assert_eq!(
+            detect("Kannst du bitte erklären, warum der Test nicht funktioniert?"),
+            Some("German")
+        );
+        // Code is not prose.
+        assert_eq!(detect("fix this\n```\nlet 変数 = \"値\";\n```"), None);
+        assert_eq!(detect("What does `el_que` do?"), None);
+        assert_eq!(detect(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_response_language() {
+        let mut os = Os::new().await.unwrap();
+        assert_eq!(response_language(&os, Some("Hello")), None);
+        assert_eq!(
+            response_language(&os, Some("请解释这个函数")).as_deref(),
+            Some("Chinese")
+        );
+
+        os.database
+            .settings
+            .set(Setting::ChatResponseLanguage, "Brazilian Portuguese")
+            .await
+            .unwrap();
+        assert_eq!(
+            response_language(&os, Some("Hello")).as_deref(),
+            Some("Brazilian Portuguese")
+        );
+
+        os.database
+            .settings
+            .set(Setting::ChatResponseLanguage, "off")
+            .await
+            .unwrap();
+        assert_eq!(response_language(&os, Some("请解释这个函数")), None);
+    }
+}
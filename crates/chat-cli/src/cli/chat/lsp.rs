@@ -0,0 +1,699 @@
+//! A minimal client for language servers, used by the diagnostics tool to get the diagnostics of a
+//! workspace without running a full build.
+//!
+//! Servers are started on demand for each language and workspace root, and kept running for the
+//! rest of the session so that later requests only wait for the changed files to be analyzed.
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::fmt;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Stdio;
+use std::sync::{
+    Arc,
+    LazyLock,
+};
+use std::time::Duration;
+
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    Value,
+    json,
+};
+use tokio::io::{
+    AsyncBufRead,
+    AsyncBufReadExt,
+    AsyncReadExt,
+    AsyncWriteExt,
+    BufReader,
+};
+use tokio::process::{
+    Child,
+    ChildStdin,
+    ChildStdout,
+};
+use tokio::sync::{
+    MappedMutexGuard,
+    Mutex,
+    MutexGuard,
+    Notify,
+    oneshot,
+};
+use tokio::time::Instant;
+use tracing::{
+    debug,
+    warn,
+};
+use url::Url;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the server has to be quiet, with no work in progress, before its analysis is
+/// considered done.
+const QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+static SERVERS: LazyLock<Mutex<HashMap<(Language, PathBuf), LanguageServer>>> = LazyLock::new(Default::default);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Rust,
+    TypeScript,
+    Python,
+}
+
+impl Language {
+    /// Returns the language of the file at `path` from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Language::Rust),
+            "ts" | "tsx" | "mts" | "cts" | "js" | "jsx" | "mjs" | "cjs" => Some(Language::TypeScript),
+            "py" | "pyi" => Some(Language::Python),
+            _ => None,
+        }
+    }
+
+    /// Detects the language of the project at `dir` from its manifest and config files.
+    pub fn detect(dir: &Path) -> Option<Self> {
+        [Language::Rust, Language::TypeScript, Language::Python]
+            .into_iter()
+            .find(|language| language.markers().iter().any(|file| dir.join(file).exists()))
+    }
+
+    fn markers(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["Cargo.toml"],
+            Language::TypeScript => &["tsconfig.json", "jsconfig.json", "package.json"],
+            Language::Python => &[
+                "pyproject.toml",
+                "pyrightconfig.json",
+                "setup.py",
+                "setup.cfg",
+                "requirements.txt",
+            ],
+        }
+    }
+
+    /// Returns the root of the project containing `path`: the closest directory with a manifest of
+    /// the language, or for Rust the Cargo workspace containing it.
+    pub fn find_root(self, path: &Path) -> Option<PathBuf> {
+        let mut roots = path
+            .ancestors()
+            .filter(|dir| self.markers().iter().any(|file| dir.join(file).is_file()));
+        let closest = roots.next()?;
+        if self == Language::Rust {
+            let is_workspace = |dir: &Path| {
+                std::fs::read_to_string(dir.join("Cargo.toml")).is_ok_and(|manifest| manifest.contains("[workspace]"))
+            };
+            if !is_workspace(closest) {
+                if let Some(workspace) = roots.find(|dir| is_workspace(dir)) {
+                    return Some(workspace.to_path_buf());
+                }
+            }
+        }
+        Some(closest.to_path_buf())
+    }
+
+    /// The command starting the language server, which talks over stdio.
+    pub fn server_command(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["rust-analyzer"],
+            Language::TypeScript => &["typescript-language-server", "--stdio"],
+            Language::Python => &["pyright-langserver", "--stdio"],
+        }
+    }
+
+    fn language_id(path: &Path) -> &'static str {
+        match path.extension().and_then(|e| e.to_str()).unwrap_or_default() {
+            "rs" => "rust",
+            "ts" | "mts" | "cts" => "typescript",
+            "tsx" => "typescriptreact",
+            "jsx" => "javascriptreact",
+            "py" | "pyi" => "python",
+            _ => "javascript",
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Language::Rust => "Rust",
+            Language::TypeScript => "TypeScript",
+            Language::Python => "Python",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Information => "info",
+            Severity::Hint => "hint",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    /// 1-based line of the start of the diagnostic.
+    pub line: u32,
+    /// 1-based column of the start of the diagnostic, in UTF-16 code units.
+    pub column: u32,
+    pub severity: Severity,
+    pub source: Option<String>,
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn from_lsp(path: &Path, diagnostic: &Value) -> Option<Self> {
+        let start = diagnostic.get("range")?.get("start")?;
+        let severity = match diagnostic.get("severity").and_then(Value::as_u64) {
+            Some(2) => Severity::Warning,
+            Some(3) => Severity::Information,
+            Some(4) => Severity::Hint,
+            _ => Severity::Error,
+        };
+        let code = match diagnostic.get("code") {
+            Some(Value::String(code)) => Some(code.clone()),
+            Some(Value::Number(code)) => Some(code.to_string()),
+            _ => None,
+        };
+        Some(Self {
+            path: path.to_path_buf(),
+            line: start.get("line")?.as_u64()? as u32 + 1,
+            column: start.get("character")?.as_u64()? as u32 + 1,
+            severity,
+            source: diagnostic.get("source").and_then(Value::as_str).map(str::to_string),
+            code,
+            message: diagnostic.get("message")?.as_str()?.to_string(),
+        })
+    }
+}
+
+/// State shared with the task reading the messages of the server.
+#[derive(Debug, Default)]
+struct State {
+    diagnostics: HashMap<PathBuf, Vec<Diagnostic>>,
+    pending: HashMap<i64, oneshot::Sender<Result<Value>>>,
+    /// Tokens of the work the server reported as in progress.
+    progress: HashSet<String>,
+    last_activity: Option<Instant>,
+    exited: bool,
+}
+
+impl State {
+    /// Handles a message from the server, returning the response to send back if it is a request.
+    fn handle(&mut self, message: Value) -> Option<Value> {
+        self.last_activity = Some(Instant::now());
+        let method = message.get("method").and_then(Value::as_str);
+        let id = message.get("id").cloned();
+        match (method, id) {
+            // Requests from the server, which are answered with defaults.
+            (Some(method), Some(id)) => {
+                let result = match method {
+                    "workspace/configuration" => {
+                        let items = message["params"]["items"].as_array().cloned().unwrap_or_default();
+                        Value::Array(items.iter().map(configuration).collect())
+                    },
+                    _ => Value::Null,
+                };
+                Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+            },
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let params = &message["params"];
+                let path = params["uri"]
+                    .as_str()
+                    .and_then(|uri| Url::parse(uri).ok())
+                    .and_then(|uri| uri.to_file_path().ok())?;
+                let diagnostics = params["diagnostics"]
+                    .as_array()
+                    .map(|diagnostics| {
+                        diagnostics
+                            .iter()
+                            .filter_map(|diagnostic| Diagnostic::from_lsp(&path, diagnostic))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                self.diagnostics.insert(path, diagnostics);
+                None
+            },
+            (Some("$/progress"), None) => {
+                let token = message["params"]["token"].to_string();
+                match message["params"]["value"]["kind"].as_str() {
+                    Some("begin") => {
+                        self.progress.insert(token);
+                    },
+                    Some("end") => {
+                        self.progress.remove(&token);
+                    },
+                    _ => (),
+                }
+                None
+            },
+            (Some(_), None) => None,
+            (None, Some(id)) => {
+                let sender = self.pending.remove(&id.as_i64()?)?;
+                let result = match message.get("error") {
+                    Some(error) => Err(eyre!("{}", error["message"].as_str().unwrap_or("unknown error"))),
+                    None => Ok(message.get("result").cloned().unwrap_or_default()),
+                };
+                let _ = sender.send(result);
+                None
+            },
+            (None, None) => None,
+        }
+    }
+}
+
+/// Settings returned to servers asking for their configuration.
+fn configuration(item: &Value) -> Value {
+    match item["section"].as_str() {
+        // Pyright only reports the diagnostics of open files otherwise.
+        Some("python.analysis") => json!({ "diagnosticMode": "workspace" }),
+        _ => Value::Null,
+    }
+}
+
+pub struct LanguageServer {
+    language: Language,
+    child: Child,
+    stdin: Arc<Mutex<ChildStdin>>,
+    state: Arc<std::sync::Mutex<State>>,
+    activity: Arc<Notify>,
+    next_id: i64,
+    /// Versions of the documents opened in the server.
+    versions: HashMap<Url, i32>,
+}
+
+impl LanguageServer {
+    /// Starts the server of `language` for the project at `root`.
+    pub async fn start(language: Language, root: &Path) -> Result<Self> {
+        let command = language.server_command();
+        let mut child = tokio::process::Command::new(command[0])
+            .args(&command[1..])
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err_with(|| format!("Unable to start {}, is it installed?", command[0]))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            bail!("Unable to talk to {}", command[0]);
+        };
+
+        let mut server = Self {
+            language,
+            child,
+            stdin: Arc::new(Mutex::new(stdin)),
+            state: Default::default(),
+            activity: Default::default(),
+            next_id: 0,
+            versions: HashMap::new(),
+        };
+        tokio::spawn(read_messages(
+            stdout,
+            server.stdin.clone(),
+            server.state.clone(),
+            server.activity.clone(),
+        ));
+
+        let root_uri = file_url(root)?;
+        let name = root.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        server
+            .request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "clientInfo": { "name": "q chat" },
+                    "rootUri": root_uri.as_str(),
+                    "workspaceFolders": [{ "uri": root_uri.as_str(), "name": name }],
+                    "capabilities": {
+                        "textDocument": {
+                            "publishDiagnostics": {},
+                            "synchronization": { "didSave": true },
+                        },
+                        "window": { "workDoneProgress": true },
+                        "workspace": { "configuration": true, "workspaceFolders": true },
+                    },
+                }),
+            )
+            .await
+            .wrap_err_with(|| format!("Unable to initialize {}", command[0]))?;
+        server.notify("initialized", json!({})).await?;
+        Ok(server)
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.state().exited
+    }
+
+    /// Opens the file at `path` with the content `text` in the server, or updates it if it was
+    /// already opened, and saves it so that servers checking saved files do so.
+    pub async fn sync_file(&mut self, path: &Path, text: String) -> Result<()> {
+        let uri = file_url(path)?;
+        self.state().last_activity = Some(Instant::now());
+        match self.versions.get_mut(&uri) {
+            Some(version) => {
+                *version += 1;
+                let version = *version;
+                self.notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": { "uri": uri.as_str(), "version": version },
+                        "contentChanges": [{ "text": text }],
+                    }),
+                )
+                .await?;
+            },
+            None => {
+                self.notify(
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri.as_str(),
+                            "languageId": Language::language_id(path),
+                            "version": 1,
+                            "text": text,
+                        },
+                    }),
+                )
+                .await?;
+                self.versions.insert(uri.clone(), 1);
+            },
+        }
+        self.notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": uri.as_str() } }),
+        )
+        .await
+    }
+
+    /// Asks the server to check the whole project, for servers which only check on save.
+    pub async fn check_project(&mut self) -> Result<()> {
+        self.state().last_activity = Some(Instant::now());
+        if self.language == Language::Rust {
+            self.notify("rust-analyzer/runFlycheck", json!({ "textDocument": null }))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Waits until the server is done analyzing, returning false if it is still busy after
+    /// `timeout`.
+    pub async fn wait_until_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let (busy, quiet_at) = {
+                let state = self.state();
+                if state.exited {
+                    return true;
+                }
+                let quiet_at = state.last_activity.map_or_else(Instant::now, |at| at + QUIET_PERIOD);
+                (!state.progress.is_empty(), quiet_at)
+            };
+            let now = Instant::now();
+            if !busy && now >= quiet_at {
+                return true;
+            }
+            if now >= deadline {
+                return false;
+            }
+            let wake_at = if busy { deadline } else { quiet_at.min(deadline) };
+            let _ = tokio::time::timeout_at(wake_at, self.activity.notified()).await;
+        }
+    }
+
+    /// Diagnostics published by the server, for the file at `path` or for all files.
+    pub fn diagnostics(&self, path: Option<&Path>) -> Vec<Diagnostic> {
+        let state = self.state();
+        let mut diagnostics = match path {
+            Some(path) => state.diagnostics.get(path).cloned().unwrap_or_default(),
+            None => state.diagnostics.values().flatten().cloned().collect(),
+        };
+        diagnostics
+            .sort_by(|a, b| (a.severity, &a.path, a.line, a.column).cmp(&(b.severity, &b.path, b.line, b.column)));
+        diagnostics
+    }
+
+    pub async fn shutdown(mut self) {
+        if self.is_running() {
+            if let Err(err) = self.request("shutdown", Value::Null).await {
+                debug!(?err, "language server failed to shut down");
+            }
+            let _ = self.notify("exit", Value::Null).await;
+        }
+        let _ = self.child.kill().await;
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let (sender, receiver) = oneshot::channel();
+        self.state().pending.insert(id, sender);
+        send(
+            &self.stdin,
+            &json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }),
+        )
+        .await?;
+        match tokio::time::timeout(REQUEST_TIMEOUT, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => bail!("The language server exited"),
+            Err(_) => {
+                self.state().pending.remove(&id);
+                bail!("The language server did not respond to {method}")
+            },
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        send(
+            &self.stdin,
+            &json!({ "jsonrpc": "2.0", "method": method, "params": params }),
+        )
+        .await
+    }
+}
+
+/// Returns the server of `language` for the project at `root`, starting it if needed. Other
+/// callers wait for the returned guard to be dropped.
+pub async fn server(language: Language, root: &Path) -> Result<MappedMutexGuard<'static, LanguageServer>> {
+    let mut servers = SERVERS.lock().await;
+    servers.retain(|_, server| server.is_running());
+    let key = (language, root.to_path_buf());
+    if !servers.contains_key(&key) {
+        let server = LanguageServer::start(language, root).await?;
+        servers.insert(key.clone(), server);
+    }
+    MutexGuard::try_map(servers, |servers| servers.get_mut(&key))
+        .ok()
+        .ok_or_else(|| eyre!("The {language} language server is not running"))
+}
+
+/// Shuts down the servers started during the session.
+pub async fn shutdown_all() {
+    let servers = std::mem::take(&mut *SERVERS.lock().await);
+    for (_, server) in servers {
+        server.shutdown().await;
+    }
+}
+
+fn file_url(path: &Path) -> Result<Url> {
+    Url::from_file_path(path)
+        .ok()
+        .ok_or_else(|| eyre!("Invalid path {}", path.display()))
+}
+
+fn encode(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len()).into_bytes()
+}
+
+async fn send(stdin: &Mutex<ChildStdin>, message: &Value) -> Result<()> {
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(&encode(message)).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+/// Reads the next message, returning `None` at the end of the stream.
+async fn read_message(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let mut body = vec![0; length.unwrap_or_default()];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+async fn read_messages(
+    stdout: ChildStdout,
+    stdin: Arc<Mutex<ChildStdin>>,
+    state: Arc<std::sync::Mutex<State>>,
+    activity: Arc<Notify>,
+) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let message = match read_message(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(err) => {
+                warn!(?err, "failed to read a message from the language server");
+                break;
+            },
+        };
+        let response = state.lock().unwrap_or_else(|e| e.into_inner()).handle(message);
+        activity.notify_one();
+        if let Some(response) = response {
+            if let Err(err) = send(&stdin, &response).await {
+                debug!(?err, "failed to respond to the language server");
+            }
+        }
+    }
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    state.exited = true;
+    // Dropping the senders fails the requests waiting for a response.
+    state.pending.clear();
+    activity.notify_one();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_message() {
+        let first = json!({ "jsonrpc": "2.0", "id": 1, "result": { "name": "é" } });
+        let second = json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} });
+        let mut bytes = encode(&first);
+        bytes.extend(b"Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n");
+        bytes.extend(encode(&second));
+        let mut reader = BufReader::new(bytes.as_slice());
+        assert_eq!(read_message(&mut reader).await.unwrap(), Some(first));
+        assert_eq!(read_message(&mut reader).await.unwrap(), Some(second));
+        assert_eq!(read_message(&mut reader).await.unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_handle() {
+        let mut state = State::default();
+
+        let response = state.handle(json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "workspace/configuration",
+            "params": { "items": [{ "section": "rust-analyzer" }, { "section": "python.analysis" }] },
+        }));
+        assert_eq!(
+            response,
+            Some(json!({ "jsonrpc": "2.0", "id": 7, "result": [null, { "diagnosticMode": "workspace" }] }))
+        );
+
+        state.handle(json!({
+            "jsonrpc": "2.0",
+            "method": "$/progress",
+            "params": { "token": "check", "value": { "kind": "begin", "title": "cargo check" } },
+        }));
+        assert!(state.progress.contains("\"check\""));
+        state.handle(json!({
+            "jsonrpc": "2.0",
+            "method": "$/progress",
+            "params": { "token": "check", "value": { "kind": "end" } },
+        }));
+        assert!(state.progress.is_empty());
+
+        state.handle(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {
+                "uri": "file:///project/src/main.rs",
+                "diagnostics": [{
+                    "range": { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 9 } },
+                    "severity": 1,
+                    "code": "E0308",
+                    "source": "rustc",
+                    "message": "mismatched types",
+                }],
+            },
+        }));
+        assert_eq!(state.diagnostics[Path::new("/project/src/main.rs")], vec![Diagnostic {
+            path: PathBuf::from("/project/src/main.rs"),
+            line: 3,
+            column: 5,
+            severity: Severity::Error,
+            source: Some("rustc".to_string()),
+            code: Some("E0308".to_string()),
+            message: "mismatched types".to_string(),
+        }]);
+
+        let (sender, mut receiver) = oneshot::channel();
+        state.pending.insert(1, sender);
+        state.handle(json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32601, "message": "no such method" } }));
+        assert_eq!(receiver.try_recv().unwrap().unwrap_err().to_string(), "no such method");
+    }
+
+    #[test]
+    fn test_find_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let member = dir.path().join("crates/member");
+        std::fs::create_dir_all(member.join("src")).unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\n").unwrap();
+        std::fs::write(member.join("Cargo.toml"), "[package]\nname = \"member\"\n").unwrap();
+
+        assert_eq!(
+            Language::Rust.find_root(&member.join("src")),
+            Some(dir.path().to_path_buf())
+        );
+        assert_eq!(Language::Python.find_root(&member.join("src")), None);
+        assert_eq!(Language::detect(&member), Some(Language::Rust));
+        assert_eq!(Language::from_path(Path::new("app.tsx")), Some(Language::TypeScript));
+        assert_eq!(Language::from_path(Path::new("README.md")), None);
+    }
+}
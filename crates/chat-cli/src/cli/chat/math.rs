@@ -0,0 +1,295 @@
+//! Best-effort rendering of inline LaTeX math as unicode text, e.g. `\frac{1}{2} \alpha^2`
+//! becomes `½ α²`. Anything that has no unicode equivalent is left as written.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Functions that are written as their name, e.g. `\sin`.
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "log", "ln", "exp", "lim", "max", "min", "det", "gcd",
+];
+
+/// Returns the unicode rendering of the LaTeX math `source`.
+pub fn to_unicode(source: &str) -> String {
+    let mut chars = source.chars().peekable();
+    render(&mut chars, None)
+}
+
+/// Renders until the end of input or the `close` character of the current group.
+fn render(chars: &mut Peekable<Chars<'_>>, close: Option<char>) -> String {
+    let mut out = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            c if Some(c) == close => break,
+            '{' => out.push_str(&render(chars, Some('}'))),
+            '\\' => out.push_str(&command(chars)),
+            '^' => out.push_str(&script(&argument(chars), superscript, '^')),
+            '_' => out.push_str(&script(&argument(chars), subscript, '_')),
+            '~' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Renders the argument following a command or script marker: a group or a single token.
+fn argument(chars: &mut Peekable<Chars<'_>>) -> String {
+    while chars.next_if(|c| *c == ' ').is_some() {}
+    match chars.next() {
+        Some('{') => render(chars, Some('}')),
+        Some('\\') => command(chars),
+        Some(c) => c.to_string(),
+        None => String::new(),
+    }
+}
+
+fn command(chars: &mut Peekable<Chars<'_>>) -> String {
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+        name.push(c);
+    }
+    if name.is_empty() {
+        // Escaped symbols and spacing, e.g. `\{` or `\,`.
+        return match chars.next() {
+            Some(',' | ';' | ':' | ' ' | '!') => " ".to_string(),
+            Some('\\') => "\n".to_string(),
+            Some(c) => c.to_string(),
+            None => String::new(),
+        };
+    }
+
+    match name.as_str() {
+        "frac" | "dfrac" | "tfrac" => {
+            let numerator = argument(chars);
+            let denominator = argument(chars);
+            fraction(&numerator, &denominator)
+        },
+        "sqrt" => {
+            let radicand = argument(chars);
+            match radicand.chars().count() {
+                1 => format!("√{radicand}"),
+                _ => format!("√({radicand})"),
+            }
+        },
+        "text" | "mathrm" | "mathbf" | "mathit" | "mathsf" | "mathtt" | "operatorname" | "textbf" | "textit"
+        | "mbox" => argument(chars),
+        "left" | "right" | "big" | "Big" | "bigg" | "Bigg" | "displaystyle" | "limits" => String::new(),
+        "quad" | "qquad" => " ".to_string(),
+        "mathbb" => {
+            let text = argument(chars);
+            text.chars().map(|c| double_struck(c).unwrap_or(c)).collect()
+        },
+        name if FUNCTIONS.contains(&name) => name.to_string(),
+        _ => match symbol(&name) {
+            Some(symbol) => symbol.to_string(),
+            None => format!("\\{name}"),
+        },
+    }
+}
+
+fn fraction(numerator: &str, denominator: &str) -> String {
+    let vulgar = match (numerator, denominator) {
+        ("1", "2") => Some('½'),
+        ("1", "3") => Some('⅓'),
+        ("2", "3") => Some('⅔'),
+        ("1", "4") => Some('¼'),
+        ("3", "4") => Some('¾'),
+        ("1", "5") => Some('⅕'),
+        ("1", "8") => Some('⅛'),
+        _ => None,
+    };
+    if let Some(vulgar) = vulgar {
+        return vulgar.to_string();
+    }
+
+    let wrap = |s: &str| match s.chars().all(|c| c.is_alphanumeric() || c == '.') {
+        true => s.to_string(),
+        false => format!("({s})"),
+    };
+    format!("{}/{}", wrap(numerator), wrap(denominator))
+}
+
+/// Renders a superscript or subscript with unicode characters if all of them have one, falling
+/// back to `marker` and the text otherwise.
+fn script(text: &str, map: fn(char) -> Option<char>, marker: char) -> String {
+    match text.chars().map(map).collect::<Option<String>>() {
+        Some(mapped) if !mapped.is_empty() => mapped,
+        _ if text.chars().count() == 1 => format!("{marker}{text}"),
+        _ => format!("{marker}({text})"),
+    }
+}
+
+fn superscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '+' => '⁺',
+        '-' | '−' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'n' => 'ⁿ',
+        'i' => 'ⁱ',
+        'T' => 'ᵀ',
+        '′' => '′',
+        _ => return None,
+    })
+}
+
+fn subscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '₀',
+        '1' => '₁',
+        '2' => '₂',
+        '3' => '₃',
+        '4' => '₄',
+        '5' => '₅',
+        '6' => '₆',
+        '7' => '₇',
+        '8' => '₈',
+        '9' => '₉',
+        '+' => '₊',
+        '-' | '−' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'x' => 'ₓ',
+        _ => return None,
+    })
+}
+
+fn double_struck(c: char) -> Option<char> {
+    Some(match c {
+        'N' => 'ℕ',
+        'Z' => 'ℤ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'C' => 'ℂ',
+        _ => return None,
+    })
+}
+
+fn symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        // Greek letters
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" | "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" | "vartheta" => "θ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "rho" => "ρ",
+        "sigma" => "σ",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" | "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        // Operators and relations
+        "times" => "×",
+        "cdot" => "·",
+        "div" => "÷",
+        "pm" => "±",
+        "mp" => "∓",
+        "le" | "leq" => "≤",
+        "ge" | "geq" => "≥",
+        "ne" | "neq" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "propto" => "∝",
+        "ll" => "≪",
+        "gg" => "≫",
+        "sum" => "∑",
+        "prod" => "∏",
+        "int" => "∫",
+        "oint" => "∮",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "infty" => "∞",
+        "circ" => "∘",
+        "degree" => "°",
+        "prime" => "′",
+        // Logic and sets
+        "in" => "∈",
+        "notin" => "∉",
+        "subset" => "⊂",
+        "subseteq" => "⊆",
+        "supset" => "⊃",
+        "supseteq" => "⊇",
+        "cup" => "∪",
+        "cap" => "∩",
+        "emptyset" | "varnothing" => "∅",
+        "forall" => "∀",
+        "exists" => "∃",
+        "neg" | "lnot" => "¬",
+        "land" | "wedge" => "∧",
+        "lor" | "vee" => "∨",
+        // Arrows
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        // Dots
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_unicode() {
+        assert_eq!(to_unicode(r"x^2 + y^{10}"), "x² + y¹⁰");
+        assert_eq!(to_unicode(r"a_i + a_{n+1}"), "aᵢ + aₙ₊₁");
+        assert_eq!(to_unicode(r"\frac{1}{2} \alpha"), "½ α");
+        assert_eq!(to_unicode(r"\frac{a+b}{c}"), "(a+b)/c");
+        assert_eq!(to_unicode(r"\sqrt{x^2 + 1} \leq \infty"), "√(x² + 1) ≤ ∞");
+        assert_eq!(to_unicode(r"\sum_{i=1}^{n} i \cdot \Delta"), "∑ᵢ₌₁ⁿ i · Δ");
+        assert_eq!(to_unicode(r"\text{speed} = \frac{d}{t}"), "speed = d/t");
+        assert_eq!(to_unicode(r"e^{i\pi}"), "e^(iπ)");
+        assert_eq!(to_unicode(r"x \in \mathbb{R}, \sin x"), "x ∈ ℝ, sin x");
+        assert_eq!(to_unicode(r"\unknown{x}"), r"\unknownx");
+    }
+}
@@ -226,6 +226,14 @@ impl UserMessage {
         }
     }
 
+    pub fn tool_use_results_mut(&mut self) -> Option<&mut Vec<ToolUseResult>> {
+        match &mut self.content {
+            UserMessageContent::Prompt { .. } => None,
+            UserMessageContent::CancelledToolUses { tool_use_results, .. } => Some(tool_use_results),
+            UserMessageContent::ToolUseResults { tool_use_results } => Some(tool_use_results),
+        }
+    }
+
     pub fn additional_context(&self) -> &str {
         &self.additional_context
     }
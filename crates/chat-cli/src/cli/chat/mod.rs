@@ -1,22 +1,53 @@
+mod acp;
+pub mod agent_socket;
+mod audit;
+mod auto_context;
 pub mod cli;
+mod command_selector;
+mod connectivity;
 mod consts;
 pub mod context;
 mod conversation;
 mod error_formatter;
+mod fuzzy_picker;
+mod greeting;
+mod injection;
 mod input_source;
+mod keymap;
+mod language;
+mod lsp;
+mod math;
 mod message;
+mod network_stats;
+mod output;
+mod pacing;
 mod parse;
-use std::path::MAIN_SEPARATOR;
+use std::path::{
+    MAIN_SEPARATOR,
+    PathBuf,
+};
 mod parser;
 mod prompt;
 mod prompt_parser;
+mod redact;
+mod renderer;
+mod secrets;
+pub mod semantic_index;
+mod server;
 mod server_messenger;
-#[cfg(unix)]
-mod skim_integration;
+mod setup;
+mod shutdown;
+#[cfg(any(test, feature = "test-harness"))]
+pub mod test_harness;
 mod token_counter;
 pub mod tool_manager;
+pub mod tool_stats;
 pub mod tools;
+mod trash;
+mod tui;
+mod type_ahead;
 pub mod util;
+mod watch;
 
 use std::borrow::Cow;
 use std::collections::{
@@ -32,13 +63,34 @@ use std::process::ExitCode;
 use std::sync::Arc;
 use std::time::Duration;
 
+use acp::Acp;
+use agent_socket::{
+    AgentSocket,
+    Control,
+    SessionEvent,
+    SocketPolicy,
+};
 use amzn_codewhisperer_client::types::SubscriptionStatus;
 use clap::{
     Args,
     CommandFactory,
     Parser,
+    Subcommand,
+};
+use cli::alias;
+use cli::ask_file::AskFileArgs;
+use cli::compact::{
+    CompactMode,
+    CompactStrategy,
+};
+use cli::doctor::{
+    self,
+    DoctorArgs,
+};
+use cli::memstats::{
+    MemoryCaps,
+    format_bytes,
 };
-use cli::compact::CompactStrategy;
 use cli::model::select_model;
 pub use conversation::ConversationState;
 use conversation::TokenWarningLevel;
@@ -60,13 +112,26 @@ use eyre::{
     bail,
     eyre,
 };
+use greeting::GreetingConfig;
+use injection::InjectionGuard;
 use input_source::InputSource;
+use keymap::{
+    KeyAction,
+    Keymap,
+};
 use message::{
     AssistantMessage,
     AssistantToolUse,
     ToolUseResult,
     ToolUseResultBlock,
 };
+use network_stats::{
+    NetworkStats,
+    SLOW_REQUEST_THRESHOLD,
+    TurnTimings,
+};
+use output::SessionOutput;
+use pacing::RenderPacer;
 use parse::{
     ParseState,
     interpret_markdown,
@@ -76,7 +141,20 @@ use parser::{
     RequestMetadata,
     SendMessageStream,
 };
+use redact::Redactor;
 use regex::Regex;
+use renderer::{
+    LineRenderer,
+    PendingTool,
+    Renderer,
+    SessionStatus,
+};
+use server::{
+    OutputStream,
+    ServeArgs,
+    Server,
+};
+use shutdown::TerminationSignal;
 use spinners::{
     Spinner,
     Spinners,
@@ -89,16 +167,35 @@ use tokio::sync::{
     Mutex,
     broadcast,
 };
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use tool_manager::{
     ToolManager,
     ToolManagerBuilder,
 };
+use tools::aws_read::AwsReadSettings;
+use tools::browser::BrowserSettings;
+use tools::db_query::DbQuerySettings;
+use tools::execute::{
+    BackgroundProcesses,
+    ExecuteCommand,
+};
+use tools::fs_write::{
+    CreatePolicy,
+    Hunk,
+};
 use tools::gh_issue::GhIssueContext;
+use tools::http_request::HttpRequestSettings;
+use tools::kb_search::KnowledgeBase;
+use tools::post_write::PostWriteSettings;
 use tools::{
     OutputKind,
     QueuedTool,
     Tool,
     ToolSpec,
+    post_write,
+    sanitize_path_tool_arg,
+    scratchpad,
 };
 use tracing::{
     debug,
@@ -107,6 +204,8 @@ use tracing::{
     trace,
     warn,
 };
+use tui::Tui;
+use type_ahead::TypeAhead;
 use util::images::RichImageBlock;
 use util::ui::draw_box;
 use util::{
@@ -117,23 +216,44 @@ use winnow::Partial;
 use winnow::stream::Offset;
 
 use super::agent::PermissionEvalResult;
-use crate::api_client::model::ToolResultStatus;
+use crate::api_client::model::{
+    ChatResponseStream,
+    ToolResultStatus,
+};
 use crate::api_client::{
     self,
     ApiClientError,
 };
 use crate::auth::AuthError;
 use crate::auth::builder_id::is_idc_user;
-use crate::cli::agent::Agents;
+use crate::cli::agent::{
+    Agents,
+    TrustMessages,
+};
+use crate::cli::chat::audit::{
+    ApprovalDecision,
+    ExitStatus,
+};
 use crate::cli::chat::cli::SlashCommand;
+use crate::cli::chat::cli::ask::parse_model_override;
 use crate::cli::chat::cli::model::{
-    MODEL_OPTIONS,
+    Model,
+    available_models,
     default_model_id,
+    fallback_model_ids,
+    find_model,
+    model_name,
+    resolve_model,
 };
 use crate::cli::chat::cli::prompts::{
     GetPromptError,
     PromptsSubcommand,
 };
+use crate::cli::chat::consts::{
+    DEFAULT_LARGE_REQUEST_WARNING_KB,
+    MODEL_OVERRIDE_PREFIX,
+    RESUMED_HISTORY_LEN,
+};
 use crate::database::settings::Setting;
 use crate::mcp_client::Prompt;
 use crate::os::Os;
@@ -155,22 +275,51 @@ const LIMIT_REACHED_TEXT: &str = color_print::cstr! { "You've used all your free
 1. Upgrade to a paid subscription for increased limits. See our Pricing page for what's included> <blue!>https://aws.amazon.com/q/developer/pricing/</blue!>
 2. Wait until next month when your limit automatically resets." };
 
-pub const EXTRA_HELP: &str = color_print::cstr! {"
+/// Returns the text shown after the commands in `/help`, with the keys of `keymap`.
+pub fn extra_help(keymap: &Keymap) -> String {
+    let mut help = color_print::cstr! {"
 <cyan,em>MCP:</cyan,em>
 <black!>You can now configure the Amazon Q CLI to use MCP servers. \nLearn how: https://docs.aws.amazon.com/en_us/amazonq/latest/qdeveloper-ug/command-line-mcp.html</black!>
 
 <cyan,em>Tips:</cyan,em>
 <em>!{command}</em>          <black!>Quickly execute a command in your current session</black!>
-<em>Ctrl(^) + j</em>         <black!>Insert new-line to provide multi-line prompt</black!>
-                    <black!>Alternatively, [Alt(⌥) + Enter(⏎)]</black!>
-<em>Ctrl(^) + s</em>         <black!>Fuzzy search commands and context files</black!>
-                    <black!>Use Tab to select multiple items</black!>
-                    <black!>Change the keybind using: q settings chat.skimCommandKey x</black!>
+<em>@model:{name}</em>       <black!>Send a prompt to another model for this request only</black!>
+"}
+    .to_string();
+    for action in KeyAction::ALL {
+        let keys = keymap.keys(*action);
+        let Some((first, alternatives)) = keys.split_first() else {
+            continue;
+        };
+        help.push_str(&color_print::cformat!(
+            "<em>{:<20}</em><black!>{}</black!>\n",
+            first.to_string(),
+            action.description()
+        ));
+        for key in alternatives {
+            help.push_str(&color_print::cformat!(
+                "{:20}<black!>Alternatively, [{}]</black!>\n",
+                "",
+                key
+            ));
+        }
+        if *action == KeyAction::FuzzySearch {
+            help.push_str(&color_print::cformat!(
+                "{:20}<black!>Use Tab to select multiple items</black!>\n",
+                ""
+            ));
+        }
+    }
+    help.push_str(color_print::cstr! {"<em>chat.keymap</em>         <black!>The keys of the prompt, e.g. {\"editor\": \"ctrl-x\"}</black!>
+                    <black!>Actions: newline, fuzzySearch, cancel, approveTool, editor</black!>
 <em>chat.editMode</em>       <black!>The prompt editing mode (vim or emacs)</black!>
-                    <black!>Change using: q settings chat.skimCommandKey x</black!>
-"};
+                    <black!>Change using: q settings chat.editMode vi</black!>
+"});
+    help
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Args)]
+#[command(disable_help_subcommand = true)]
 pub struct ChatArgs {
     /// Resumes the previous conversation from this directory.
     #[arg(short, long)]
@@ -193,12 +342,116 @@ pub struct ChatArgs {
     pub no_interactive: bool,
     /// The first question to ask
     pub input: Option<String>,
+    /// Write only the final response to stdout, as plain text, with everything else on stderr
+    #[arg(long)]
+    pub quiet: bool,
+    /// Also write the text of responses to this file as they stream, without formatting
+    #[arg(long, value_name = "PATH")]
+    pub tee: Option<PathBuf>,
+    /// Walk through the most common settings before starting the session. Also runs the first
+    /// time chat is started
+    #[arg(long, conflicts_with = "no_interactive")]
+    pub setup: bool,
+    /// Show the session full screen, with panes for the conversation, pending tools and status
+    #[arg(long, conflicts_with = "no_interactive")]
+    pub tui: bool,
+    /// Let an editor drive the session with JSON-RPC messages over stdin and stdout, following the
+    /// agent-client protocol
+    #[arg(long, conflicts_with_all = ["no_interactive", "tui"])]
+    pub acp: bool,
+    /// Exit with a failure if the final response matches this regular expression
+    #[arg(long, value_name = "REGEX")]
+    pub fail_on: Option<String>,
+    #[command(subcommand)]
+    pub subcommand: Option<ChatSubcommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum ChatSubcommand {
+    /// Ask a question about a file and print only the answer, without starting a session
+    Ask(AskFileArgs),
+    /// Serve the session on localhost as an HTTP API, for editors and web UIs
+    Serve(ServeArgs),
+    /// Send a prompt to the interactive session running in this directory, and print its response
+    Send(SendArgs),
+    /// Pause, resume, cancel, or compact the interactive session running in this directory
+    Control(ControlArgs),
+    /// Follow the prompts, responses, and tool uses of another interactive session, read-only
+    Watch(WatchArgs),
+    /// Check authentication, connectivity, MCP servers, and the terminal for problems
+    Doctor(DoctorArgs),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct SendArgs {
+    /// The prompt, which can also be a slash command
+    pub prompt: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct ControlArgs {
+    pub control: Control,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct WatchArgs {
+    /// The pid of the session, or the path of its socket
+    pub session: String,
 }
 
 impl ChatArgs {
     pub async fn execute(mut self, os: &mut Os) -> Result<ExitCode> {
         let mut input = self.input;
 
+        let mut serve = None;
+        match self.subcommand.take() {
+            Some(ChatSubcommand::Ask(args)) => {
+                input = Some(args.prompt(os).await?);
+                self.no_interactive = true;
+            },
+            Some(ChatSubcommand::Send(args)) => {
+                let response = agent_socket::send(&os.env.current_dir()?, &args.prompt).await?;
+                println!("{}", response.trim_end());
+                return Ok(ExitCode::SUCCESS);
+            },
+            Some(ChatSubcommand::Control(args)) => {
+                agent_socket::control(&os.env.current_dir()?, args.control).await?;
+                return Ok(ExitCode::SUCCESS);
+            },
+            Some(ChatSubcommand::Watch(args)) => {
+                watch::run(&args.session).await?;
+                return Ok(ExitCode::SUCCESS);
+            },
+            Some(ChatSubcommand::Doctor(_)) => {
+                let mut stderr = std::io::stderr();
+                let agents = Agents::load(os, self.agent.as_deref(), true, &mut stderr).await;
+                let checks = doctor::run_checks(os, &agents, None).await;
+                let mut stdout = std::io::stdout();
+                doctor::print_checks(&mut stdout, &checks)?;
+                stdout.flush()?;
+                return Ok(match checks.iter().any(|check| check.status == doctor::Status::Error) {
+                    true => ExitCode::FAILURE,
+                    false => ExitCode::SUCCESS,
+                });
+            },
+            Some(ChatSubcommand::Serve(args)) => {
+                self.no_interactive = false;
+                serve = Some(args);
+            },
+            None => (),
+        }
+
+        let fail_on = match self.fail_on.as_deref().map(Regex::new).transpose() {
+            Ok(fail_on) => fail_on,
+            Err(err) => bail!("Invalid --fail-on pattern: {err}"),
+        };
+
+        if let Some(path) = &self.tee {
+            if let Err(err) = os.fs.write(path, "").await {
+                bail!("Failed to create {}: {err}", path.display());
+            }
+        }
+
         if self.no_interactive && input.is_none() {
             if !std::io::stdin().is_terminal() {
                 let mut buffer = String::new();
@@ -243,8 +496,26 @@ impl ChatArgs {
         let agents = {
             let skip_migration = self.no_interactive;
             let mut agents = Agents::load(os, self.agent.as_deref(), skip_migration, &mut stderr).await;
+            if self.trust_all_tools && os.database.settings.policy().disable_trust_all {
+                bail!("--trust-all-tools is disabled by the managed policy");
+            }
             agents.trust_all_tools = self.trust_all_tools;
 
+            let first_run = !self.no_interactive
+                && serve.is_none()
+                && !self.acp
+                && std::io::stdin().is_terminal()
+                && std::io::stdout().is_terminal()
+                && setup::is_first_run(os);
+            if self.setup || first_run {
+                let default_agent = setup::run(os, &agents, &mut stderr).await?;
+                if let Some(name) = default_agent.filter(|_| self.agent.is_none()) {
+                    if let Err(err) = agents.switch(&name) {
+                        warn!(?err, "failed to switch to the default agent chosen during setup");
+                    }
+                }
+            }
+
             if agents
                 .get_active()
                 .is_some_and(|a| !a.mcp_servers.mcp_servers.is_empty())
@@ -271,11 +542,11 @@ impl ChatArgs {
 
         // If modelId is specified, verify it exists before starting the chat
         let model_id: Option<String> = if let Some(model_name) = self.model {
-            let model_name_lower = model_name.to_lowercase();
-            match MODEL_OPTIONS.iter().find(|opt| opt.name == model_name_lower) {
-                Some(opt) => Some((opt.model_id).to_string()),
+            let models = available_models(os).await;
+            match find_model(&models, &model_name) {
+                Some(model) => Some(model.model_id.clone()),
                 None => {
-                    let available_names: Vec<&str> = MODEL_OPTIONS.iter().map(|opt| opt.name).collect();
+                    let available_names: Vec<&str> = models.iter().map(|model| model.name.as_str()).collect();
                     bail!(
                         "Model '{}' does not exist. Available models: {}",
                         model_name,
@@ -300,25 +571,154 @@ impl ChatArgs {
             .await?;
         let tool_config = tool_manager.load_tools(os, &mut stderr).await?;
 
-        ChatSession::new(
+        let mut renderer: Option<Box<dyn Renderer>> = None;
+        let (stdout, stderr, input_source, terminal_width_provider): (
+            SessionOutput,
+            SessionOutput,
+            InputSource,
+            fn() -> Option<usize>,
+        ) = if let Some(args) = serve {
+            let (server, server_input) = match Server::bind(args.port).await {
+                Ok(server) => server,
+                Err(err) => bail!("Failed to listen on port {}: {err}", args.port),
+            };
+            execute!(
+                stderr,
+                style::Print(format!(
+                    "Serving chat on http://{}\nSend the header Authorization: Bearer {} with every request\n",
+                    server.address(),
+                    server.token()
+                ))
+            )?;
+            let outputs = (
+                server.output(OutputStream::Stdout).into(),
+                server.output(OutputStream::Stderr).into(),
+            );
+            renderer = Some(Box::new(server));
+            (outputs.0, outputs.1, InputSource::new_server(server_input), || None)
+        } else if self.acp {
+            let (acp, acp_input) = Acp::start();
+            let outputs = (
+                acp.output(acp::OutputStream::Stdout).into(),
+                acp.output(acp::OutputStream::Stderr).into(),
+            );
+            renderer = Some(Box::new(acp));
+            (outputs.0, outputs.1, InputSource::new_acp(acp_input), || None)
+        } else if self.tui {
+            let (tui, tui_input) = Tui::start();
+            let outputs = (tui.output().into(), tui.output().into());
+            renderer = Some(Box::new(tui));
+            (outputs.0, outputs.1, InputSource::new_tui(tui_input), tui::pane_width)
+        } else {
+            (
+                stdout.into(),
+                stderr.into(),
+                InputSource::new(os, prompt_request_sender, prompt_response_receiver)?,
+                || terminal::window_size().map(|s| s.columns.into()).ok(),
+            )
+        };
+
+        let mut session = ChatSession::new(
             os,
             stdout,
             stderr,
             &conversation_id,
             agents,
             input,
-            InputSource::new(os, prompt_request_sender, prompt_response_receiver)?,
+            input_source,
             self.resume,
-            || terminal::window_size().map(|s| s.columns.into()).ok(),
+            terminal_width_provider,
             tool_manager,
             model_id,
             tool_config,
             !self.no_interactive,
         )
-        .await?
-        .spawn(os)
-        .await
-        .map(|_| ExitCode::SUCCESS)
+        .await?;
+        session.quiet = self.quiet;
+        session.tee = self.tee;
+        match renderer {
+            Some(renderer) => session.renderer = renderer,
+            None if !self.no_interactive => match AgentSocket::bind(
+                os.env.current_dir()?,
+                SocketPolicy::from_settings(&os.database.settings),
+            ) {
+                Ok(socket) => {
+                    if let Some(printer) = session.input_source.external_printer() {
+                        socket.set_printer(printer);
+                    }
+                    socket.set_interrupt(session.ctrlc_tx.clone());
+                    socket.set_events(session.events.clone());
+                    session.agent_socket = Some(socket);
+                },
+                Err(err) => warn!(?err, "failed to listen for prompts from q chat send"),
+            },
+            None => (),
+        }
+        let result = session.spawn(os).await;
+        // Restores the terminal, or stops the server, before anything else is printed.
+        session.renderer = Box::new(LineRenderer);
+        session.agent_socket = None;
+
+        // Don't leave any processes started in the background by execute_bash running
+        BackgroundProcesses::kill_all();
+        lsp::shutdown_all().await;
+        scratchpad::shutdown_all().await;
+
+        result?;
+        if let Some(signal) = session.terminated {
+            // Dropping the tool manager stops the MCP servers.
+            drop(session);
+            return Ok(ExitCode::from(signal.exit_code()));
+        }
+        if let Some(failure) = session.failure {
+            return Ok(failure.into());
+        }
+        let response_matches = |pattern: &Regex| {
+            session
+                .conversation
+                .last_assistant_message()
+                .is_some_and(|message| pattern.is_match(message.content()))
+        };
+        if fail_on.as_ref().is_some_and(response_matches) {
+            return Ok(NonInteractiveFailure::AssertionFailed.into());
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Exit codes of sessions without user input, so that scripts can branch on why a run failed.
+/// Arguments that fail to parse exit with 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonInteractiveFailure {
+    /// Any other error.
+    Other           = 1,
+    /// The model failed to respond.
+    ModelError      = 3,
+    /// A tool use required approval, or was denied by the agent.
+    ToolDenied      = 4,
+    /// The conversation does not fit in the context window of the model.
+    ContextOverflow = 5,
+    /// The final response matched the `--fail-on` pattern.
+    AssertionFailed = 6,
+}
+
+impl NonInteractiveFailure {
+    fn from_error(err: &ChatError) -> Self {
+        match err {
+            ChatError::Client(err) if matches!(**err, ApiClientError::ContextWindowOverflow { .. }) => {
+                Self::ContextOverflow
+            },
+            ChatError::CompactHistoryFailure => Self::ContextOverflow,
+            ChatError::Client(_) | ChatError::SendMessage(_) | ChatError::ResponseStream(_) => Self::ModelError,
+            ChatError::NonInteractiveToolApproval => Self::ToolDenied,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<NonInteractiveFailure> for ExitCode {
+    fn from(failure: NonInteractiveFailure) -> Self {
+        ExitCode::from(failure as u8)
     }
 }
 
@@ -378,6 +778,14 @@ const SMALL_SCREEN_POPULAR_SHORTCUTS: &str = color_print::cstr! {"<black!><green
 </black!>"};
 
 const RESPONSE_TIMEOUT_CONTENT: &str = "Response timed out - message took too long to generate";
+/// Appended to a response that was stopped by the user, so that the model knows it is incomplete.
+const TRUNCATED_RESPONSE_MARKER: &str = "[Response truncated: stopped by the user]";
+/// How long a second Ctrl+C is waited for after stopping a response, to discard it instead.
+const DISCARD_RESPONSE_WINDOW: Duration = Duration::from_secs(1);
+/// How long a cancelled request is waited for to record its [RequestMetadata].
+const CANCELLED_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+/// Number of events kept for watchers of the session that fall behind, see [SessionEvent].
+const SESSION_EVENTS_CAPACITY: usize = 1024;
 const TRUST_ALL_TEXT: &str = color_print::cstr! {"<green!>All tools are now trusted (<red!>!</red!>). Amazon Q will execute tools <bold>without</bold> asking for confirmation.\
 \nAgents can sometimes do unexpected things so understand the risks.</green!>
 \nLearn more at https://docs.aws.amazon.com/amazonq/latest/qdeveloper-ug/command-line-chat-security.html#command-line-chat-trustall-safety"};
@@ -443,6 +851,15 @@ impl ChatError {
             ChatError::CompactHistoryFailure => None,
         }
     }
+
+    /// Whether the request failed because the service could not be reached.
+    fn is_offline(&self) -> bool {
+        match self {
+            ChatError::Client(e) => e.is_connectivity_error(),
+            ChatError::SendMessage(e) => e.source.is_connectivity_error(),
+            _ => false,
+        }
+    }
 }
 
 impl ReasonCode for ChatError {
@@ -481,23 +898,37 @@ impl From<parser::RecvError> for ChatError {
     }
 }
 
+/// A request for the next response that was sent ahead of time.
+#[derive(Debug)]
+struct PrefetchedResponse {
+    handle: JoinHandle<Result<SendMessageStream, parser::SendMessageError>>,
+    request_metadata: Arc<Mutex<Option<RequestMetadata>>>,
+    cancel_token: CancellationToken,
+}
+
 pub struct ChatSession {
     /// For output read by humans and machine
-    pub stdout: std::io::Stdout,
+    pub stdout: SessionOutput,
     /// For display output, only read by humans
-    pub stderr: std::io::Stderr,
+    pub stderr: SessionOutput,
     initial_input: Option<String>,
     /// Whether we're starting a new conversation or continuing an old one.
     existing_conversation: bool,
     input_source: InputSource,
     /// Width of the terminal, required for [ParseState].
     terminal_width_provider: fn() -> Option<usize>,
+    /// Frontend showing the state of the session, see [Renderer].
+    renderer: Box<dyn Renderer>,
     spinner: Option<Spinner>,
     /// [ConversationState].
     conversation: ConversationState,
     tool_uses: Vec<QueuedTool>,
     /// [RequestMetadata] about the ongoing operation.
     user_turn_request_metadata: Vec<RequestMetadata>,
+    /// Time spent running tools in the ongoing user turn, see [TurnTimings].
+    user_turn_tool_time: Duration,
+    /// Sizes and latencies of the requests of the session, see [Self::push_request_metadata].
+    network_stats: NetworkStats,
     pending_tool_index: Option<usize>,
     /// Telemetry events to be sent as part of the conversation. The HashMap key is tool_use_id.
     tool_use_telemetry_events: HashMap<String, ToolUseEventBuilder>,
@@ -507,17 +938,65 @@ pub struct ChatSession {
     failed_request_ids: Vec<String>,
     /// Pending prompts to be sent
     pending_prompts: VecDeque<Prompt>,
+    /// Turns of the alias being sent that are left, each sent once the response to the previous
+    /// one is done, see [alias::AliasArgs].
+    alias_turns: VecDeque<String>,
+    /// Whether the input being handled is a turn of an alias, which is not expanded again.
+    alias_turn: bool,
+    /// Images pasted with `/paste`, sent along with the next prompt
+    pending_images: Vec<RichImageBlock>,
+    /// Request generating the title of the conversation, see [Setting::ChatEnableAutoTitle]
+    pending_title: Option<JoinHandle<Option<String>>>,
+    /// Models that were overloaded since the last successful response, so that falling back
+    /// through [Setting::ChatModelFallbacks] does not retry them.
+    overloaded_models: Vec<String>,
+    /// Whether the current user turn recovered from an error, e.g. by compacting the history or
+    /// retrying with another model.
+    recovered_from_error: bool,
+    /// Whether the hint about `/feedback` was shown, which is done once per session after a turn
+    /// that recovered from an error.
+    feedback_hint_shown: bool,
+    /// Models available to the current profile, see [available_models].
+    models: Vec<Model>,
+    /// Request for the next response sent ahead of time, see [Self::prefetch_response].
+    prefetch: Option<PrefetchedResponse>,
+    /// Text of the response being streamed, kept if the user stops the response.
+    partial_response: String,
+    /// Capture of the keys typed while the model is responding, see [Self::update_type_ahead].
+    type_ahead: Option<TypeAhead>,
+    /// Text typed while the model was responding, offered as the next prompt.
+    queued_input: String,
+    /// Socket receiving prompts from `q chat send`, see [AgentSocket].
+    agent_socket: Option<AgentSocket>,
+    /// Events of the session, streamed to `q chat watch`.
+    events: broadcast::Sender<SessionEvent>,
+    /// Whether the session was paused by `q chat control`, see [Self::apply_socket_controls].
+    paused: bool,
+    /// Whether `q chat control` requested to compact the history once the session is done
+    /// responding.
+    compact_requested: bool,
+    /// Whether only the final response is written to stdout, as plain text, see
+    /// [Self::handle_response].
+    quiet: bool,
+    /// File the text of responses is written to as it streams, see [Self::tee].
+    tee: Option<PathBuf>,
     interactive: bool,
+    /// First failure of a session without user input, which determines its exit code.
+    failure: Option<NonInteractiveFailure>,
     inner: Option<ChatState>,
+    ctrlc_tx: broadcast::Sender<()>,
     ctrlc_rx: broadcast::Receiver<()>,
+    terminate_rx: broadcast::Receiver<TerminationSignal>,
+    /// The signal that terminated the session, see [shutdown].
+    terminated: Option<TerminationSignal>,
 }
 
 impl ChatSession {
     #[allow(clippy::too_many_arguments)]
     pub async fn new(
         os: &mut Os,
-        stdout: std::io::Stdout,
-        mut stderr: std::io::Stderr,
+        stdout: impl Into<SessionOutput>,
+        stderr: impl Into<SessionOutput>,
         conversation_id: &str,
         mut agents: Agents,
         mut input: Option<String>,
@@ -529,23 +1008,40 @@ impl ChatSession {
         tool_config: HashMap<String, ToolSpec>,
         interactive: bool,
     ) -> Result<Self> {
+        let stdout = stdout.into();
+        let mut stderr = stderr.into();
+        let models = available_models(os).await;
+        let model_chosen = model_id.is_some();
         let valid_model_id = match model_id {
             Some(id) => id,
             None => {
+                let from_agent = match agents.get_active().and_then(|a| Some((&a.name, a.model.as_ref()?))) {
+                    Some((agent, model_name)) => match find_model(&models, model_name) {
+                        Some(model) => Some(model.model_id.clone()),
+                        None => {
+                            execute!(
+                                stderr,
+                                style::SetForegroundColor(Color::Yellow),
+                                style::Print("WARNING: "),
+                                style::ResetColor,
+                                style::Print(format!(
+                                    "model {model_name} of agent {agent} is not available. Using the default model.\n"
+                                ))
+                            )?;
+                            None
+                        },
+                    },
+                    None => None,
+                };
                 let from_settings = os
                     .database
                     .settings
                     .get_string(Setting::ChatDefaultModel)
-                    .and_then(|model_name| {
-                        MODEL_OPTIONS
-                            .iter()
-                            .find(|opt| opt.name == model_name)
-                            .map(|opt| opt.model_id.to_owned())
-                    });
+                    .and_then(|model_name| find_model(&models, &model_name).map(|model| model.model_id.clone()));
 
-                match from_settings {
+                match from_agent.or(from_settings) {
                     Some(id) => id,
-                    None => default_model_id(os).await.to_owned(),
+                    None => default_model_id(os, &models).await,
                 }
             },
         };
@@ -554,12 +1050,12 @@ impl ChatSession {
         let mut existing_conversation = false;
         let previous_conversation = std::env::current_dir()
             .ok()
-            .and_then(|cwd| os.database.get_conversation_by_path(cwd).ok())
+            .and_then(|cwd| os.database.get_conversation_by_path(cwd, RESUMED_HISTORY_LEN).ok())
             .flatten();
 
         // Only restore conversations where there were actual messages.
         // Prevents edge case where user clears conversation then exits without chatting.
-        let conversation = match resume_conversation
+        let mut conversation = match resume_conversation
             && previous_conversation
                 .as_ref()
                 .is_some_and(|cs| !cs.history().is_empty())
@@ -589,17 +1085,37 @@ impl ChatSession {
                 cs
             },
             false => {
-                ConversationState::new(conversation_id, agents, tool_config, tool_manager, Some(valid_model_id)).await
+                let mut conversation = ConversationState::new(
+                    conversation_id,
+                    agents,
+                    tool_config,
+                    tool_manager,
+                    Some(valid_model_id.clone()),
+                )
+                .await;
+                conversation.model_chosen = model_chosen;
+                conversation
             },
         };
+        // The model of a resumed conversation may no longer be allowed.
+        if conversation
+            .model
+            .as_ref()
+            .is_some_and(|id| find_model(&models, id).is_none())
+        {
+            conversation.model = Some(valid_model_id);
+        }
+        conversation.redactor = Redactor::new(os);
+        conversation.injection_guard = InjectionGuard::new(os);
 
         // Spawn a task for listening and broadcasting sigints.
         let (ctrlc_tx, ctrlc_rx) = tokio::sync::broadcast::channel(4);
+        let ctrlc_tx_clone = ctrlc_tx.clone();
         tokio::spawn(async move {
             loop {
                 match ctrl_c().await {
                     Ok(_) => {
-                        let _ = ctrlc_tx
+                        let _ = ctrlc_tx_clone
                             .send(())
                             .map_err(|err| error!(?err, "failed to send ctrlc to broadcast channel"));
                     },
@@ -610,6 +1126,8 @@ impl ChatSession {
             }
         });
 
+        let terminate_rx = shutdown::listen(os.database.clone(), os.env.current_dir().unwrap_or_default());
+
         Ok(Self {
             stdout,
             stderr,
@@ -617,18 +1135,43 @@ impl ChatSession {
             existing_conversation,
             input_source,
             terminal_width_provider,
+            renderer: Box::new(LineRenderer),
+            agent_socket: None,
+            events: broadcast::channel(SESSION_EVENTS_CAPACITY).0,
+            paused: false,
+            compact_requested: false,
             spinner: None,
             conversation,
             tool_uses: vec![],
             user_turn_request_metadata: vec![],
+            user_turn_tool_time: Duration::ZERO,
+            network_stats: NetworkStats::default(),
             pending_tool_index: None,
+            pending_images: Vec::new(),
+            pending_title: None,
+            overloaded_models: Vec::new(),
+            recovered_from_error: false,
+            feedback_hint_shown: false,
+            models,
+            prefetch: None,
+            partial_response: String::new(),
+            type_ahead: None,
+            queued_input: String::new(),
+            quiet: false,
+            tee: None,
             tool_use_telemetry_events: HashMap::new(),
             tool_use_status: ToolUseStatus::Idle,
             failed_request_ids: Vec::new(),
             pending_prompts: VecDeque::new(),
+            alias_turns: VecDeque::new(),
+            alias_turn: false,
             interactive,
+            failure: None,
             inner: Some(ChatState::default()),
+            ctrlc_tx,
             ctrlc_rx,
+            terminate_rx,
+            terminated: None,
         })
     }
 
@@ -636,8 +1179,24 @@ impl ChatSession {
         // Update conversation state with new tool information
         self.conversation.update_state(false).await;
 
+        // Trim in-memory bookkeeping in between turns so long-running sessions stay bounded
+        if matches!(self.inner, Some(ChatState::PromptUser { .. })) {
+            self.trim_memory(os).await;
+        }
+
+        if self.pending_title.as_ref().is_some_and(|task| task.is_finished()) {
+            self.apply_title(os).await?;
+        }
+
+        self.update_type_ahead(os);
+        self.renderer.update(&self.status());
+
         let mut ctrl_c_stream = self.ctrlc_rx.resubscribe();
+        let interrupted = self.apply_socket_controls(&mut ctrl_c_stream).await?;
         let result = match self.inner.take().expect("state must always be Some") {
+            _ if interrupted => Err(ChatError::Interrupted {
+                tool_uses: Some(self.tool_uses.clone()),
+            }),
             ChatState::PromptUser { skip_printing_tools } => {
                 match (self.interactive, self.tool_uses.is_empty()) {
                     (false, true) => {
@@ -645,7 +1204,16 @@ impl ChatSession {
                         return Ok(());
                     },
                     (false, false) => {
-                        return Err(ChatError::NonInteractiveToolApproval);
+                        let err = ChatError::NonInteractiveToolApproval;
+                        execute!(
+                            self.stderr,
+                            style::SetForegroundColor(Color::Red),
+                            style::Print(format!("{err}\n")),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                        self.failure.get_or_insert(NonInteractiveFailure::from_error(&err));
+                        self.inner = Some(ChatState::Exit);
+                        return Ok(());
                     },
                     _ => (),
                 };
@@ -680,25 +1248,33 @@ impl ChatSession {
                 }
             },
             ChatState::HandleResponseStream(conversation_state) => {
-                let request_metadata: Arc<Mutex<Option<RequestMetadata>>> = Arc::new(Mutex::new(None));
+                let (request_metadata, cancel_token) = match &self.prefetch {
+                    Some(prefetch) => (Arc::clone(&prefetch.request_metadata), prefetch.cancel_token.clone()),
+                    None => (Arc::new(Mutex::new(None)), CancellationToken::new()),
+                };
                 let request_metadata_clone = Arc::clone(&request_metadata);
 
                 tokio::select! {
-                    res = self.handle_response(os, conversation_state, request_metadata_clone) => res,
+                    res = self.handle_response(os, conversation_state, request_metadata_clone, cancel_token.clone()) => res,
                     Ok(_) = ctrl_c_stream.recv() => {
                         debug!(?request_metadata, "ctrlc received");
-                        // Wait for handle_response to finish handling the ctrlc.
-                        tokio::time::sleep(Duration::from_millis(5)).await;
-                        if let Some(request_metadata) = request_metadata.lock().await.take() {
-                            self.user_turn_request_metadata.push(request_metadata);
+                        cancel_token.cancel();
+                        if let Some(request_metadata) = cancelled_request_metadata(&request_metadata).await {
+                            self.push_request_metadata(request_metadata);
                         }
                         self.send_chat_telemetry(os, TelemetryResult::Cancelled, None, None, None, true).await;
-                        Err(ChatError::Interrupted { tool_uses: None })
+                        self.stop_response(os, &mut ctrl_c_stream).await
                     }
                 }
             },
-            ChatState::RetryModelOverload => tokio::select! {
-                res = self.retry_model_overload(os) => res,
+            ChatState::RetryModelOverload { select_model } => tokio::select! {
+                res = self.retry_model_overload(os, select_model) => res,
+                Ok(_) = ctrl_c_stream.recv() => {
+                    Err(ChatError::Interrupted { tool_uses: None })
+                }
+            },
+            ChatState::WaitForConnectivity => tokio::select! {
+                res = self.wait_for_connectivity(os) => res,
                 Ok(_) = ctrl_c_stream.recv() => {
                     Err(ChatError::Interrupted { tool_uses: None })
                 }
@@ -716,9 +1292,15 @@ impl ChatSession {
 
         // We encountered an error. Handle it.
         error!(?err, "An error occurred processing the current state");
+        self.alias_turns.clear();
+        if let Some(prefetch) = self.prefetch.take() {
+            debug!("cancelling the prefetched request");
+            prefetch.cancel_token.cancel();
+        }
         let (reason, reason_desc) = get_error_reason(&err);
         self.send_error_telemetry(os, reason, Some(reason_desc), err.status_code())
             .await;
+        let failure = NonInteractiveFailure::from_error(&err);
 
         if self.spinner.is_some() {
             drop(self.spinner.take());
@@ -730,6 +1312,34 @@ impl ChatSession {
         }
 
         let (context, report, display_err_message) = match err {
+            _ if err.is_offline() => {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print("Unable to reach Amazon Q, you appear to be offline.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                if self.interactive
+                    && os
+                        .database
+                        .settings
+                        .get_bool(Setting::ChatQueueOfflinePrompts)
+                        .unwrap_or(true)
+                {
+                    execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(
+                            "The prompt will be sent once the connection returns, press ctrl+c to cancel.\n\n"
+                        ),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    self.inner = Some(ChatState::WaitForConnectivity);
+                    return Ok(());
+                }
+                execute!(self.stderr, style::Print("\n"))?;
+                ("Unable to reach Amazon Q", Report::from(err), false)
+            },
             ChatError::Interrupted { tool_uses: ref inter } => {
                 execute!(self.stderr, style::Print("\n\n"))?;
 
@@ -798,6 +1408,7 @@ impl ChatSession {
                         )?;
                         ("The conversation history has overflowed", eyre!(err), false)
                     } else {
+                        self.recovered_from_error = true;
                         self.inner = Some(ChatState::CompactHistory {
                             prompt: None,
                             show_summary: false,
@@ -813,7 +1424,7 @@ impl ChatSession {
                         });
 
                         execute!(
-                            self.stdout,
+                            self.stderr,
                             style::SetForegroundColor(Color::Yellow),
                             style::Print("The context window has overflowed, summarizing the history..."),
                             style::SetAttribute(Attribute::Reset),
@@ -841,6 +1452,41 @@ impl ChatSession {
                     ("Amazon Q is having trouble responding right now", eyre!(err), false)
                 },
                 ApiClientError::ModelOverloadedError { request_id, .. } => {
+                    if let Some(model) = self
+                        .conversation
+                        .model_override
+                        .take()
+                        .or(self.conversation.model.clone())
+                    {
+                        self.overloaded_models.push(model);
+                    }
+                    if let Some(fallback) = fallback_model_ids(os, &self.models)
+                        .into_iter()
+                        .find(|id| !self.overloaded_models.contains(id))
+                    {
+                        execute!(
+                            self.stderr,
+                            style::SetForegroundColor(Color::Yellow),
+                            style::Print(format!(
+                                "\nThe model you've selected is temporarily unavailable. Retrying with {}...\n",
+                                model_name(&self.models, &fallback)
+                            )),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                        if let Some(id) = request_id {
+                            self.conversation.append_transcript(format!(
+                                "Model unavailable (Request ID: {}), retrying with {}",
+                                id, fallback
+                            ));
+                        }
+
+                        self.conversation.model = Some(fallback);
+                        self.recovered_from_error = true;
+                        self.inner = Some(ChatState::RetryModelOverload { select_model: false });
+
+                        return Ok(());
+                    }
+
                     if self.interactive {
                         execute!(
                             self.stderr,
@@ -858,7 +1504,8 @@ impl ChatSession {
                                 .append_transcript(format!("Model unavailable (Request ID: {})", id));
                         }
 
-                        self.inner = Some(ChatState::RetryModelOverload);
+                        self.recovered_from_error = true;
+                        self.inner = Some(ChatState::RetryModelOverload { select_model: true });
 
                         return Ok(());
                     }
@@ -933,6 +1580,9 @@ impl ChatSession {
                             style::SetForegroundColor(Color::Reset),
                         )?;
                     }
+                    if !self.interactive {
+                        self.failure.get_or_insert(failure);
+                    }
 
                     self.inner = Some(ChatState::PromptUser {
                         skip_printing_tools: false,
@@ -966,6 +1616,9 @@ impl ChatSession {
             let text = re.replace_all(&format!("{}: {:?}\n", context, report), "").into_owned();
 
             queue!(self.stderr, style::Print(&text),)?;
+            self.emit(SessionEvent::Error {
+                message: text.trim_end().to_string(),
+            });
             self.conversation.append_transcript(text);
 
             execute!(
@@ -975,6 +1628,10 @@ impl ChatSession {
             )?;
         }
 
+        if !self.interactive {
+            self.failure.get_or_insert(failure);
+        }
+
         self.conversation.enforce_conversation_invariants();
         self.conversation.reset_next_user_message();
         self.pending_tool_index = None;
@@ -1005,6 +1662,20 @@ impl Drop for ChatSession {
     }
 }
 
+/// Waits for a cancelled request to record its [RequestMetadata] in `request_metadata`, which
+/// includes the size of the partial response.
+async fn cancelled_request_metadata(request_metadata: &Mutex<Option<RequestMetadata>>) -> Option<RequestMetadata> {
+    let recorded = async {
+        loop {
+            if let Some(request_metadata) = request_metadata.lock().await.take() {
+                return request_metadata;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+    tokio::time::timeout(CANCELLED_REQUEST_TIMEOUT, recorded).await.ok()
+}
+
 /// The chat execution state.
 ///
 /// Intended to provide more robust handling around state transitions while dealing with, e.g.,
@@ -1035,8 +1706,15 @@ pub enum ChatState {
         /// Parameters for how to perform the compaction request.
         strategy: CompactStrategy,
     },
+    /// Send the current request once the service can be reached again, after failing to reach it.
+    WaitForConnectivity,
     /// Retry the current request if we encounter a model overloaded error.
-    RetryModelOverload,
+    RetryModelOverload {
+        /// Whether the user should select another model first, otherwise the request is retried
+        /// with the current model, e.g. after falling back to the next model in
+        /// [Setting::ChatModelFallbacks].
+        select_model: bool,
+    },
     /// Exit the chat.
     Exit,
 }
@@ -1050,17 +1728,108 @@ impl Default for ChatState {
 }
 
 impl ChatSession {
+    /// Adds the metadata of a request to the current user turn and to the [NetworkStats].
+    fn push_request_metadata(&mut self, request_metadata: RequestMetadata) {
+        self.network_stats.record(&request_metadata);
+        self.user_turn_request_metadata.push(request_metadata);
+    }
+
+    /// Warns when a request is larger than [Setting::ChatLargeRequestWarningKb], naming the
+    /// context as the cause when it makes up most of the request.
+    fn warn_large_request(&mut self, os: &Os, request_size: usize) -> Result<(), ChatError> {
+        let threshold = os
+            .database
+            .settings
+            .get_int(Setting::ChatLargeRequestWarningKb)
+            .map_or(DEFAULT_LARGE_REQUEST_WARNING_KB, |kb| kb.max(0) as usize)
+            * 1024;
+        if threshold == 0 || request_size < threshold {
+            self.network_stats.large_request_warned = false;
+            return Ok(());
+        }
+        if std::mem::replace(&mut self.network_stats.large_request_warned, true) {
+            return Ok(());
+        }
+
+        let size = |bytes: usize| format_bytes(Some(bytes as u64));
+        let context_size = self.conversation.context_message_length().unwrap_or_default();
+        let message = if context_size * 2 >= request_size {
+            format!(
+                "Your context added {} to this request of {}. Run /context show to see the files it includes.",
+                size(context_size),
+                size(request_size)
+            )
+        } else {
+            format!(
+                "This request is {}, mostly from the conversation history. Run /compact to make the next ones smaller.",
+                size(request_size)
+            )
+        };
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::DarkYellow),
+            style::Print(format!("\n{message}\n")),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        Ok(())
+    }
+
+    /// Warns once per session about a response that was slow to start.
+    fn warn_slow_request(&mut self, request_metadata: &RequestMetadata) -> Result<(), ChatError> {
+        let Some(latency) = request_metadata.time_to_first_chunk else {
+            return Ok(());
+        };
+        if latency < SLOW_REQUEST_THRESHOLD || self.network_stats.slow_request_warned {
+            return Ok(());
+        }
+        self.network_stats.slow_request_warned = true;
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!(
+                "The response took {}s to start. Run /usage --network to see the sizes and latencies of the requests.\n\n",
+                latency.as_secs()
+            )),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        Ok(())
+    }
+
     /// Sends a request to the SendMessage API. Emits error telemetry on failure.
+    ///
+    /// The request is sent from its own task, so that it still records its [RequestMetadata]
+    /// when cancelled with `cancel_token` after this future is dropped on sigint.
     async fn send_message(
         &mut self,
         os: &mut Os,
         conversation_state: api_client::model::ConversationState,
         request_metadata_lock: Arc<Mutex<Option<RequestMetadata>>>,
         message_meta_tags: Option<Vec<MessageMetaTag>>,
+        cancel_token: CancellationToken,
     ) -> Result<SendMessageStream, ChatError> {
-        match SendMessageStream::send_message(&os.client, conversation_state, request_metadata_lock, message_meta_tags)
+        let client = os.client.clone();
+        let result = tokio::spawn(async move {
+            SendMessageStream::send_message(
+                &client,
+                conversation_state,
+                request_metadata_lock,
+                message_meta_tags,
+                cancel_token,
+            )
             .await
-        {
+        })
+        .await
+        .map_err(|err| ChatError::Custom(format!("request failed: {err}").into()))?;
+        self.handle_send_message_result(os, result).await
+    }
+
+    /// Emits error telemetry if sending a request failed.
+    async fn handle_send_message_result(
+        &mut self,
+        os: &Os,
+        result: Result<SendMessageStream, parser::SendMessageError>,
+    ) -> Result<SendMessageStream, ChatError> {
+        match result {
             Ok(res) => Ok(res),
             Err(err) => {
                 let (reason, reason_desc) = get_error_reason(&err);
@@ -1079,16 +1848,18 @@ impl ChatSession {
     }
 
     async fn spawn(&mut self, os: &mut Os) -> Result<()> {
-        let is_small_screen = self.terminal_width() < GREETING_BREAK_POINT;
+        let is_small_screen = self.response_width(os) < GREETING_BREAK_POINT;
+        let greeting = GreetingConfig::load(os).await;
         if os
             .database
             .settings
             .get_bool(Setting::ChatGreetingEnabled)
             .unwrap_or(true)
         {
-            let welcome_text = match self.existing_conversation {
-                true => RESUME_TEXT,
-                false => match is_small_screen {
+            let welcome_text = match (self.existing_conversation, &greeting.motd) {
+                (true, _) => RESUME_TEXT,
+                (false, Some(motd)) => motd.as_str(),
+                (false, None) => match is_small_screen {
                     true => SMALL_SCREEN_WELCOME_TEXT,
                     false => WELCOME_TEXT,
                 },
@@ -1096,23 +1867,25 @@ impl ChatSession {
 
             execute!(self.stderr, style::Print(welcome_text), style::Print("\n\n"),)?;
 
-            let tip = ROTATING_TIPS[usize::try_from(rand::random::<u32>()).unwrap_or(0) % ROTATING_TIPS.len()];
-            if is_small_screen {
-                // If the screen is small, print the tip in a single line
-                execute!(
-                    self.stderr,
-                    style::Print("💡 ".to_string()),
-                    style::Print(tip),
-                    style::Print("\n")
-                )?;
-            } else {
-                draw_box(
-                    &mut self.stderr,
-                    "Did you know?",
-                    tip,
-                    GREETING_BREAK_POINT,
-                    Color::DarkGrey,
-                )?;
+            if greeting.show_tips() {
+                let tip = ROTATING_TIPS[usize::try_from(rand::random::<u32>()).unwrap_or(0) % ROTATING_TIPS.len()];
+                if is_small_screen {
+                    // If the screen is small, print the tip in a single line
+                    execute!(
+                        self.stderr,
+                        style::Print("💡 ".to_string()),
+                        style::Print(tip),
+                        style::Print("\n")
+                    )?;
+                } else {
+                    draw_box(
+                        &mut self.stderr,
+                        "Did you know?",
+                        tip,
+                        GREETING_BREAK_POINT,
+                        Color::DarkGrey,
+                    )?;
+                }
             }
 
             execute!(
@@ -1132,42 +1905,220 @@ impl ChatSession {
             execute!(self.stderr, style::Print("\n"), style::SetForegroundColor(Color::Reset))?;
         }
 
-        if self.all_tools_trusted() {
+        let (_, keymap_errors) = Keymap::load(os);
+        for error in keymap_errors {
+            execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::Yellow),
+                style::Print(format!("{error}, ignoring it\n")),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+        }
+
+        // Policy text is mandated by the organization, so it is shown even without the greeting.
+        if let Some(policy) = &greeting.policy {
+            match is_small_screen {
+                true => execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print(format!("{policy}\n\n")),
+                    style::SetForegroundColor(Color::Reset)
+                )?,
+                false => {
+                    draw_box(&mut self.stderr, "Policy", policy, GREETING_BREAK_POINT, Color::Yellow)?;
+                    execute!(self.stderr, style::Print("\n"))?;
+                },
+            }
+        }
+
+        let trust_messages = self.trust_messages();
+        info!(
+            trust_all_tools = self.all_tools_trusted(),
+            allowed_tools = ?self.conversation.agents.get_active().map(|a| &a.allowed_tools),
+            ?trust_messages,
+            "Effective tool trust settings"
+        );
+        if self.all_tools_trusted() && trust_messages.show_trust_all_banner {
+            let notice = match trust_messages.security_notice {
+                Some(ref notice) => notice.as_str(),
+                None => TRUST_ALL_TEXT,
+            };
             queue!(
                 self.stderr,
-                style::Print(format!(
-                    "{}{TRUST_ALL_TEXT}\n\n",
-                    if !is_small_screen { "\n" } else { "" }
-                ))
+                style::Print(format!("{}{notice}\n\n", if !is_small_screen { "\n" } else { "" }))
             )?;
         }
         self.stderr.flush()?;
 
         if let Some(ref id) = self.conversation.model {
-            if let Some(model_option) = MODEL_OPTIONS.iter().find(|option| option.model_id == *id) {
+            if let Some(model) = self.models.iter().find(|model| model.model_id == *id) {
                 execute!(
                     self.stderr,
                     style::SetForegroundColor(Color::Cyan),
-                    style::Print(format!("🤖 You are chatting with {}\n", model_option.name)),
+                    style::Print(format!("🤖 You are chatting with {}\n", model.name)),
                     style::SetForegroundColor(Color::Reset),
                     style::Print("\n")
                 )?;
             }
         }
 
-        if let Some(user_input) = self.initial_input.take() {
-            self.inner = Some(ChatState::HandleInput { input: user_input });
+        if let (true, Some(title)) = (self.interactive, &self.conversation.title) {
+            execute!(self.stderr, terminal::SetTitle(title))?;
         }
 
-        while !matches!(self.inner, Some(ChatState::Exit)) {
-            self.next(os).await?;
+        let interrupted = os
+            .database
+            .take_interrupted_session(os.env.current_dir()?)
+            .ok()
+            .flatten();
+        if let (true, false, Some(interrupted)) = (self.interactive, self.existing_conversation, interrupted) {
+            execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!(
+                    "The last session in this directory was closed by {}. Run ",
+                    interrupted.signal
+                )),
+                style::SetForegroundColor(Color::Green),
+                style::Print("q chat --resume"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(" to continue it.\n\n"),
+                style::SetForegroundColor(Color::Reset),
+            )?;
         }
 
-        Ok(())
-    }
-
-    /// Compacts the conversation history using the strategy specified by [CompactStrategy],
-    /// replacing the history with a summary generated by the model.
+        if self.existing_conversation && self.interactive && !self.reconcile_unavailable_tools(os).await? {
+            execute!(
+                self.stderr,
+                style::Print(
+                    "\nTo resume this conversation, launch q chat with an agent that provides these tools.\n\n"
+                )
+            )?;
+            return Ok(());
+        }
+
+        if let Some(user_input) = self.initial_input.take() {
+            self.inner = Some(ChatState::HandleInput { input: user_input });
+        }
+
+        let mut terminate_rx = self.terminate_rx.resubscribe();
+        while !matches!(self.inner, Some(ChatState::Exit)) {
+            tokio::select! {
+                res = self.next(os) => res?,
+                Ok(signal) = terminate_rx.recv() => self.shutdown(os, signal),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ends the session terminated by `signal`, saving the conversation, see [shutdown].
+    fn shutdown(&mut self, os: &mut Os, signal: TerminationSignal) {
+        info!(signal = signal.name(), "shutting down the session");
+        if let Some(prefetch) = self.prefetch.take() {
+            prefetch.cancel_token.cancel();
+        }
+        if let Err(err) = self.conversation.save(os) {
+            warn!(?err, "failed to save the conversation");
+        }
+        self.terminated = Some(signal);
+        self.inner = Some(ChatState::Exit);
+    }
+
+    /// Prompts the user to reconcile tool uses in the history that refer to tools which are no
+    /// longer available, e.g. when resuming a conversation with an agent that has a different set
+    /// of tools. Returns `false` if the user chose to abort.
+    async fn reconcile_unavailable_tools(&mut self, os: &Os) -> Result<bool, ChatError> {
+        // Tools of MCP servers that are still loading would otherwise be reported as missing.
+        let init_timeout = os
+            .database
+            .settings
+            .get_int(Setting::McpInitTimeout)
+            .map_or(5000_u64, |s| s as u64);
+        let deadline = std::time::Instant::now() + Duration::from_millis(init_timeout);
+        while !self.conversation.tool_manager.pending_clients().await.is_empty() && std::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        self.conversation.update_state(false).await;
+
+        let unavailable = self.conversation.unavailable_tool_uses();
+        if unavailable.is_empty() {
+            return Ok(true);
+        }
+
+        queue!(
+            self.stderr,
+            style::SetForegroundColor(Color::Yellow),
+            style::Print("The following tools used in this conversation are not available with the current agent:\n"),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        for name in &unavailable {
+            queue!(self.stderr, style::Print(format!("  - {name}\n")))?;
+        }
+        execute!(
+            self.stderr,
+            style::Print("\n"),
+            style::Print("[m] Map them to available tools\n"),
+            style::Print("[c] Cancel uses of these tools and continue\n"),
+            style::Print("[a] Abort\n\n"),
+        )?;
+
+        loop {
+            let Some(choice) = self.read_user_input("Choose an option [m/c/a]: ", true) else {
+                return Ok(false);
+            };
+            match choice.trim().to_lowercase().as_str() {
+                "m" => break,
+                "c" => {
+                    self.conversation.cancel_tool_uses(&unavailable);
+                    execute!(self.stderr, style::Print("\n"))?;
+                    return Ok(true);
+                },
+                "a" => return Ok(false),
+                _ => (),
+            }
+        }
+
+        let available = self.conversation.available_tool_names();
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!("\nAvailable tools: {}\n\n", available.join(", "))),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        let mut cancelled = Vec::new();
+        for name in unavailable {
+            loop {
+                let prompt = format!("Map {name} to (leave empty to cancel its uses): ");
+                let Ok(Some(line)) = self.input_source.read_line(Some(&prompt)) else {
+                    return Ok(false);
+                };
+                let target = line.trim();
+                if target.is_empty() {
+                    cancelled.push(name);
+                    break;
+                }
+                if available.iter().any(|t| t == target) {
+                    self.conversation.remap_tool_uses(&name, target);
+                    break;
+                }
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("{target} is not an available tool\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+        }
+        self.conversation.cancel_tool_uses(&cancelled);
+        execute!(self.stderr, style::Print("\n"))?;
+
+        Ok(true)
+    }
+
+    /// Compacts the conversation history using the strategy specified by [CompactStrategy],
+    /// replacing the history with a summary generated by the model.
     ///
     /// If the compact request itself fails, it will be retried depending on [CompactStrategy]
     ///
@@ -1184,16 +2135,16 @@ impl ChatSession {
         // Same pattern as is done for handle_response for getting request metadata on sigint.
         let request_metadata: Arc<Mutex<Option<RequestMetadata>>> = Arc::new(Mutex::new(None));
         let request_metadata_clone = Arc::clone(&request_metadata);
+        let cancel_token = CancellationToken::new();
         let mut ctrl_c_stream = self.ctrlc_rx.resubscribe();
 
         tokio::select! {
-            res = self.compact_history_impl(os, custom_prompt, show_summary, strategy, request_metadata_clone) => res,
+            res = self.compact_history_impl(os, custom_prompt, show_summary, strategy, request_metadata_clone, cancel_token.clone()) => res,
             Ok(_) = ctrl_c_stream.recv() => {
                 debug!(?request_metadata, "ctrlc received in compact history");
-                // Wait for handle_response to finish handling the ctrlc.
-                tokio::time::sleep(Duration::from_millis(5)).await;
-                if let Some(request_metadata) = request_metadata.lock().await.take() {
-                    self.user_turn_request_metadata.push(request_metadata);
+                cancel_token.cancel();
+                if let Some(request_metadata) = cancelled_request_metadata(&request_metadata).await {
+                    self.push_request_metadata(request_metadata);
                 }
                 self.send_chat_telemetry(
                     os,
@@ -1216,15 +2167,22 @@ impl ChatSession {
         show_summary: bool,
         strategy: CompactStrategy,
         request_metadata_lock: Arc<Mutex<Option<RequestMetadata>>>,
+        cancel_token: CancellationToken,
     ) -> Result<ChatState, ChatError> {
         let hist = self.conversation.history();
         debug!(?strategy, ?hist, "compacting history");
 
-        if self.conversation.history().is_empty() {
+        if self.conversation.history().len() <= strategy.messages_to_exclude
+            || (strategy.mode == CompactMode::ToolsOnly && self.conversation.compactable_tool_results(strategy) == 0)
+        {
+            let message = match strategy.mode {
+                CompactMode::ToolsOnly => "\nNo tool outputs to compact.\n\n",
+                _ => "\nConversation too short to compact.\n\n",
+            };
             execute!(
                 self.stderr,
                 style::SetForegroundColor(Color::Yellow),
-                style::Print("\nConversation too short to compact.\n\n"),
+                style::Print(message),
                 style::SetForegroundColor(Color::Reset)
             )?;
 
@@ -1253,7 +2211,7 @@ impl ChatSession {
 
         if self.interactive {
             execute!(self.stderr, cursor::Hide, style::Print("\n"))?;
-            self.spinner = Some(Spinner::new(Spinners::Dots, "Creating summary...".to_string()));
+            self.spinner = self.renderer.busy("Creating summary...");
         }
 
         let mut response = match self
@@ -1262,6 +2220,7 @@ impl ChatSession {
                 summary_state,
                 request_metadata_lock,
                 Some(vec![MessageMetaTag::Compact]),
+                cancel_token,
             )
             .await
         {
@@ -1295,6 +2254,7 @@ impl ChatSession {
                                     truncate_large_messages: true,
                                     max_message_length: 25_000,
                                     messages_to_exclude: 0,
+                                    ..strategy
                                 },
                             });
                         }
@@ -1336,7 +2296,7 @@ impl ChatSession {
                         message,
                         request_metadata,
                     })) => {
-                        self.user_turn_request_metadata.push(request_metadata.clone());
+                        self.push_request_metadata(request_metadata.clone());
                         break (message.content().to_string(), request_metadata);
                     },
                     Some(Ok(_)) => (),
@@ -1345,7 +2305,7 @@ impl ChatSession {
                             self.failed_request_ids.push(request_id.clone());
                         };
 
-                        self.user_turn_request_metadata.push(err.request_metadata.clone());
+                        self.push_request_metadata(err.request_metadata.clone());
 
                         let (reason, reason_desc) = get_error_reason(&err);
                         self.send_chat_telemetry(
@@ -1462,6 +2422,31 @@ impl ChatSession {
     async fn prompt_user(&mut self, os: &Os, skip_printing_tools: bool) -> Result<ChatState, ChatError> {
         execute!(self.stderr, cursor::Show)?;
 
+        if self.pending_tool_index.is_none() {
+            if let Some(state) = self.next_socket_prompt()? {
+                return Ok(state);
+            }
+            if std::mem::take(&mut self.compact_requested) {
+                return Ok(ChatState::CompactHistory {
+                    prompt: None,
+                    show_summary: false,
+                    strategy: CompactStrategy::default(),
+                });
+            }
+            if let Some(turn) = self.alias_turns.pop_front() {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Magenta),
+                    style::Print("> "),
+                    style::SetForegroundColor(Color::Reset),
+                    style::Print(&turn),
+                    style::Print("\n"),
+                )?;
+                self.alias_turn = true;
+                return Ok(ChatState::HandleInput { input: turn });
+            }
+        }
+
         // Check token usage and display warnings if needed
         if self.pending_tool_index.is_none() {
             // Only display warnings when not waiting for tool approval
@@ -1471,7 +2456,44 @@ impl ChatSession {
         }
 
         let show_tool_use_confirmation_dialog = !skip_printing_tools && self.pending_tool_index.is_some();
-        if show_tool_use_confirmation_dialog {
+        if let Some(index) = self.pending_tool_index.filter(|_| show_tool_use_confirmation_dialog) {
+            self.emit(SessionEvent::ToolApproval {
+                name: self.tool_uses[index].tool.display_name(),
+            });
+            let hunks = self.reviewable_hunks(os).len();
+            if hunks > 0 {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("\nThis change has {hunks} hunks. Use '")),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("h"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("' to approve them one by one.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+        }
+        if show_tool_use_confirmation_dialog && !self.trust_messages().show_tool_trust_hints {
+            execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("\nAllow this action? ["),
+                style::SetForegroundColor(Color::Green),
+                style::Print("y"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("/"),
+                style::SetForegroundColor(Color::Green),
+                style::Print("n"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("/"),
+                style::SetForegroundColor(Color::Green),
+                style::Print("t"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("]:\n\n"),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        } else if show_tool_use_confirmation_dialog {
             execute!(
                 self.stderr,
                 style::SetForegroundColor(Color::DarkGrey),
@@ -1496,10 +2518,13 @@ impl ChatSession {
             )?;
         }
 
-        // Do this here so that the skim integration sees an updated view of the context *during the current
-        // q session*. (e.g., if I add files to context, that won't show up for skim for the current
+        // Tools can be added while the session runs, e.g. by MCP servers that finish loading.
+        let tool_names = self.trustable_tool_names();
+        self.input_source.set_tool_names(tool_names);
+
+        // Do this here so that the command selector sees an updated view of the context *during the current
+        // q session*. (e.g., if I add files to context, that won't show up in the selector for the current
         // q session unless we do this in prompt_user... unless you can find a better way)
-        #[cfg(unix)]
         if let Some(ref context_manager) = self.conversation.context_manager {
             use std::sync::Arc;
 
@@ -1514,7 +2539,7 @@ impl ChatSession {
                 .cloned()
                 .collect::<Vec<_>>();
             self.input_source
-                .put_skim_command_selector(os, Arc::new(context_manager.clone()), tool_names);
+                .put_command_selector(os, Arc::new(context_manager.clone()), tool_names);
         }
 
         execute!(
@@ -1522,19 +2547,177 @@ impl ChatSession {
             style::SetForegroundColor(Color::Reset),
             style::SetAttribute(Attribute::Reset)
         )?;
+        self.input_source
+            .set_tool_approval_pending(os, self.pending_tool_index.is_some());
         let prompt = self.generate_tool_trust_prompt();
-        let user_input = match self.read_user_input(&prompt, false) {
-            Some(input) => input,
-            None => return Ok(ChatState::Exit),
+        // Offer what was typed while the model was responding, unless a tool is to be approved.
+        if self.pending_tool_index.is_none() && !self.queued_input.trim().is_empty() {
+            self.input_source
+                .set_initial_line(std::mem::take(&mut self.queued_input));
+        }
+        if let Some(socket) = &self.agent_socket {
+            socket.set_idle(true);
+        }
+        let user_input = self.read_user_input(&prompt, false);
+        if let Some(socket) = &self.agent_socket {
+            socket.set_idle(false);
+        }
+        let Some(user_input) = user_input else {
+            return Ok(ChatState::Exit);
         };
+        // A prompt received while reading this line runs first.
+        if user_input.trim().is_empty() && self.pending_tool_index.is_none() {
+            if let Some(state) = self.next_socket_prompt()? {
+                return Ok(state);
+            }
+        }
 
         self.conversation.append_user_transcript(&user_input);
+        self.emit(SessionEvent::Prompt {
+            text: user_input.clone(),
+        });
         Ok(ChatState::HandleInput { input: user_input })
     }
 
+    /// Applies the controls received from `q chat control`. While the session is paused, waits
+    /// before the states that send a request until it is resumed, returning whether ctrl + c was
+    /// pressed instead, which cancels the state.
+    async fn apply_socket_controls(&mut self, ctrl_c_stream: &mut broadcast::Receiver<()>) -> Result<bool, ChatError> {
+        let Some(socket) = &mut self.agent_socket else {
+            return Ok(false);
+        };
+        while let Some(control) = socket.next_control() {
+            match control {
+                Control::Pause => self.paused = true,
+                Control::Resume => self.paused = false,
+                Control::Compact => self.compact_requested = true,
+                // Sent to the session as ctrl + c.
+                Control::Cancel => (),
+            }
+        }
+        let sends_request = matches!(
+            self.inner,
+            Some(
+                ChatState::HandleInput { .. }
+                    | ChatState::ExecuteTools
+                    | ChatState::CompactHistory { .. }
+                    | ChatState::RetryModelOverload { .. }
+            )
+        );
+        if !self.paused || !sends_request {
+            return Ok(false);
+        }
+
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("\nPaused by q chat control. Run "),
+            style::SetForegroundColor(Color::Green),
+            style::Print("q chat control resume"),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(" to continue, or press ctrl + c to cancel.\n"),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        let interrupted = loop {
+            tokio::select! {
+                control = socket.wait_control() => match control {
+                    Some(Control::Resume) | None => break false,
+                    Some(Control::Compact) => self.compact_requested = true,
+                    Some(Control::Pause | Control::Cancel) => (),
+                },
+                Ok(_) = ctrl_c_stream.recv() => break true,
+            }
+        };
+        self.paused = false;
+        Ok(interrupted)
+    }
+
+    /// Replies to the prompt received from `q chat send` that ran last, with the response to it,
+    /// and runs the next one if any.
+    fn next_socket_prompt(&mut self) -> Result<Option<ChatState>, ChatError> {
+        let Some(socket) = &mut self.agent_socket else {
+            return Ok(None);
+        };
+        let response = self
+            .conversation
+            .last_assistant_message()
+            .map(|message| message.content().to_string())
+            .unwrap_or_default();
+        socket.reply(Ok(response));
+
+        let Some(prompt) = socket.next_prompt() else {
+            return Ok(None);
+        };
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::Magenta),
+            style::Print("> "),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(&prompt),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(" (from q chat send)\n"),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        self.conversation.append_user_transcript(&prompt);
+        self.emit(SessionEvent::Prompt { text: prompt.clone() });
+        Ok(Some(ChatState::HandleInput { input: prompt }))
+    }
+
     async fn handle_input(&mut self, os: &mut Os, mut user_input: String) -> Result<ChatState, ChatError> {
         queue!(self.stderr, style::Print('\n'))?;
 
+        let mut model_override = None;
+        if let Some((query, prompt)) = parse_model_override(&user_input) {
+            let prompt = prompt.to_string();
+            let error = match resolve_model(&self.models, query) {
+                Some(model) if !prompt.is_empty() => {
+                    execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("Sending this request to {}\n\n", model.name)),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    model_override = Some(model.model_id.clone());
+                    None
+                },
+                Some(_) => Some(format!("Usage: {MODEL_OVERRIDE_PREFIX}<model> <prompt>")),
+                None => Some(format!(
+                    "Model '{}' does not exist. Available models: {}",
+                    query,
+                    self.models
+                        .iter()
+                        .map(|m| m.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+            };
+            if let Some(error) = error {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("{error}\n\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: false,
+                });
+            }
+            user_input = prompt;
+        }
+
+        if !std::mem::take(&mut self.alias_turn) {
+            if let Some(mut turns) = alias::expand(os, &user_input).map(VecDeque::from) {
+                user_input = turns.pop_front().unwrap_or_default();
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("{user_input}\n\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                self.alias_turns = turns;
+            }
+        }
+
         let input = user_input.trim();
 
         // handle image path
@@ -1598,6 +2781,21 @@ impl ChatSession {
                     writeln!(self.stderr)?;
                 },
                 Err(err) => {
+                    // The help lists the keys of the keymap, which can change during the session.
+                    let err = match err.kind() {
+                        clap::error::ErrorKind::DisplayHelp => {
+                            let (keymap, _) = Keymap::load(os);
+                            SlashCommand::command()
+                                .after_long_help(extra_help(&keymap))
+                                .try_get_matches_from(
+                                    std::iter::once("slash_command").chain(orig_args.iter().map(String::as_str)),
+                                )
+                                .err()
+                                .unwrap_or(err)
+                        },
+                        _ => err,
+                    };
+
                     // Replace the dummy name with a slash. Also have to check for an ansi sequence
                     // for invalid slash commands (e.g. on a "/doesntexist" input).
                     let ansi_output = err
@@ -1686,6 +2884,9 @@ impl ChatSession {
         } else {
             // Check for a pending tool approval
             if let Some(index) = self.pending_tool_index {
+                if ["h", "H"].contains(&input) && !self.reviewable_hunks(os).is_empty() {
+                    return self.review_hunks(os, index);
+                }
                 let is_trust = ["t", "T"].contains(&input);
                 let tool_use = &mut self.tool_uses[index];
                 if ["y", "Y"].contains(&input) || is_trust {
@@ -1706,6 +2907,7 @@ impl ChatSession {
                         self.conversation.agents.trust_tools(vec![formatted_tool_name]);
                     }
                     tool_use.accepted = true;
+                    tool_use.approval = Some(ApprovalDecision::Approved);
 
                     return Ok(ChatState::ExecuteTools);
                 }
@@ -1717,10 +2919,26 @@ impl ChatSession {
                     .ok_or(ChatError::Custom("Prompt append failed".into()))?;
             }
 
+            let Some(user_input) = self.screen_prompt(os, user_input)? else {
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: false,
+                });
+            };
+
             // Otherwise continue with normal chat on 'n' or other responses
             self.tool_use_status = ToolUseStatus::Idle;
 
-            if self.pending_tool_index.is_some() {
+            if let Some(index) = self.pending_tool_index {
+                audit::record_tool_use(
+                    os,
+                    &self.conversation,
+                    &self.tool_uses[index],
+                    ApprovalDecision::Rejected,
+                    ExitStatus::NotExecuted,
+                    None,
+                )
+                .await;
+
                 // If the user just enters "n", replace the message we send to the model with
                 // something more substantial.
                 // TODO: Update this flow to something that does *not* require two requests just to
@@ -1734,9 +2952,15 @@ impl ChatSession {
                 self.conversation.abandon_tool_use(&self.tool_uses, user_input);
             } else {
                 self.conversation.set_next_user_message(user_input).await;
+                if !self.pending_images.is_empty() {
+                    let images = std::mem::take(&mut self.pending_images);
+                    self.conversation
+                        .attach_images(images.into_iter().map(|(block, _)| block).collect());
+                }
             }
 
             self.reset_user_turn();
+            self.conversation.model_override = model_override;
 
             let conv_state = self
                 .conversation
@@ -1749,7 +2973,7 @@ impl ChatSession {
             queue!(self.stderr, cursor::Hide)?;
 
             if self.interactive {
-                self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_owned()));
+                self.spinner = self.renderer.busy("Thinking...");
             }
 
             Ok(ChatState::HandleResponseStream(conv_state))
@@ -1771,7 +2995,7 @@ impl ChatSession {
                 self.conversation
                     .agents
                     .get_active()
-                    .is_some_and(|a| match tool.tool.requires_acceptance(a) {
+                    .is_some_and(|a| match tool.tool.requires_acceptance(os, a) {
                         PermissionEvalResult::Allow => true,
                         PermissionEvalResult::Ask => false,
                         PermissionEvalResult::Deny => {
@@ -1781,10 +3005,87 @@ impl ChatSession {
                     })
                     || self.conversation.agents.trust_all_tools;
 
+            // Paths outside of a strict workspace always need to be approved, one use at a time.
+            let outside_workspace = self
+                .conversation
+                .agents
+                .get_active()
+                .and_then(|a| tool.tool.outside_workspace(os, a, self.conversation.workspace_roots()));
+            // Applying infrastructure changes always needs to be approved too, unless the agent
+            // allows it explicitly.
+            let applies_infrastructure = self
+                .conversation
+                .agents
+                .get_active()
+                .is_some_and(|a| tool.tool.applies_infrastructure(a));
+            // So does replacing a file under the "ask" create policy.
+            let allowed = allowed
+                && outside_workspace.is_none()
+                && !applies_infrastructure
+                && !tool.tool.asks_before_replacing(os);
+
             if denied {
+                if !self.interactive {
+                    self.failure.get_or_insert(NonInteractiveFailure::ToolDenied);
+                }
+                audit::record_tool_use(
+                    os,
+                    &self.conversation,
+                    tool,
+                    ApprovalDecision::Denied,
+                    ExitStatus::NotExecuted,
+                    None,
+                )
+                .await;
+                let reason = match &tool.tool {
+                    Tool::ExecuteCommand(ExecuteCommand {
+                        matched_rule: Some(rule),
+                        ..
+                    }) => format!(" by the {rule}"),
+                    _ => String::new(),
+                };
+                return Ok(ChatState::HandleInput {
+                    input: format!(
+                        "Tool use with {} was rejected because the arguments supplied were forbidden{reason}",
+                        tool.name
+                    ),
+                });
+            }
+
+            if let (Some(path), false) = (&outside_workspace, self.interactive) {
+                self.failure.get_or_insert(NonInteractiveFailure::ToolDenied);
+                audit::record_tool_use(
+                    os,
+                    &self.conversation,
+                    tool,
+                    ApprovalDecision::Denied,
+                    ExitStatus::NotExecuted,
+                    None,
+                )
+                .await;
+                return Ok(ChatState::HandleInput {
+                    input: format!(
+                        "Tool use with {} was rejected because {} is outside of the workspace",
+                        tool.name,
+                        path.display()
+                    ),
+                });
+            }
+
+            if applies_infrastructure && !self.interactive {
+                self.failure.get_or_insert(NonInteractiveFailure::ToolDenied);
+                audit::record_tool_use(
+                    os,
+                    &self.conversation,
+                    tool,
+                    ApprovalDecision::Denied,
+                    ExitStatus::NotExecuted,
+                    None,
+                )
+                .await;
                 return Ok(ChatState::HandleInput {
                     input: format!(
-                        "Tool use with {} was rejected because the arguments supplied were forbidden",
+                        "Tool use with {} was rejected because applying infrastructure changes needs to be approved",
                         tool.name
                     ),
                 });
@@ -1802,10 +3103,30 @@ impl ChatSession {
             // TODO: Control flow is hacky here because of borrow rules
             let _ = tool;
             self.print_tool_description(os, i, allowed).await?;
+            if let Some(path) = outside_workspace {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print(format!(
+                        "{} is outside of the workspace. Approving allows this use only.\n\n",
+                        path.display()
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            if applies_infrastructure {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print("This applies infrastructure changes. Approving allows this use only.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
             let tool = &mut self.tool_uses[i];
 
             if allowed {
                 tool.accepted = true;
+                tool.approval = Some(ApprovalDecision::Trusted);
                 continue;
             }
 
@@ -1819,25 +3140,110 @@ impl ChatSession {
         // Execute the requested tools.
         let mut tool_results = vec![];
         let mut image_blocks: Vec<RichImageBlock> = Vec::new();
+        // When the user approved none of the tools, the request for the next response is sent as
+        // soon as the result of the last tool is available, and its outcome is rendered after.
+        let prefetch = self.prefetch_enabled(os);
+        let mut last_outcome = None;
 
-        for tool in &self.tool_uses {
+        for (i, tool) in self.tool_uses.iter().enumerate() {
             let mut tool_telemetry = self.tool_use_telemetry_events.entry(tool.id.clone());
             tool_telemetry = tool_telemetry.and_modify(|ev| ev.is_accepted = true);
 
-            let tool_start = std::time::Instant::now();
-            let invoke_result = tool.tool.invoke(os, &mut self.stdout).await;
-
-            if self.spinner.is_some() {
-                queue!(
+            // Stop the spinner so that it does not draw over output streamed by the tool.
+            if let Some(mut spinner) = self.spinner.take() {
+                spinner.stop();
+                execute!(
                     self.stderr,
                     terminal::Clear(terminal::ClearType::CurrentLine),
                     cursor::MoveToColumn(0),
                     cursor::Show
                 )?;
             }
-            execute!(self.stdout, style::Print("\n"))?;
 
+            // With all tools trusted, nobody reviews what gets overwritten or deleted, so copies are
+            // kept in the trash to be restored with /trash restore.
+            if self.conversation.agents.trust_all_tools {
+                let paths = tool.tool.destroyed_paths(os);
+                if !paths.is_empty() {
+                    let reason = match &tool.tool {
+                        Tool::ExecuteCommand(execute_command) => format!("{}: {}", tool.name, execute_command.command),
+                        _ => tool.name.clone(),
+                    };
+                    match trash::keep(os, &os.env.current_dir()?, &paths, &reason).await {
+                        Ok(kept) => {
+                            for entry in kept {
+                                queue!(
+                                    self.stderr,
+                                    style::SetForegroundColor(Color::DarkGrey),
+                                    style::Print(format!(
+                                        "Kept a copy of {} in the trash, restore it with /trash restore {}\n",
+                                        entry.path.display(),
+                                        entry.id
+                                    )),
+                                    style::SetForegroundColor(Color::Reset),
+                                )?;
+                            }
+                        },
+                        Err(err) => warn!(?err, "failed to keep copies of {paths:?} in the trash"),
+                    }
+                }
+            }
+
+            let tool_start = std::time::Instant::now();
+            let tool_start_timestamp_ms = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+            let mut invoke_result = tool.tool.invoke(os, &mut self.stderr).await;
+            // Files written by fs_write are formatted and linted if the agent asks for it, and what
+            // the model needs to know about it is added to the result.
+            if let (Tool::FsWrite(fs_write), Ok(result)) = (&tool.tool, &mut invoke_result) {
+                if let Some(settings) = self
+                    .conversation
+                    .agents
+                    .get_active()
+                    .and_then(PostWriteSettings::from_agent)
+                {
+                    let path = sanitize_path_tool_arg(os, fs_write.path());
+                    match post_write::run(os, &settings, &path, &mut self.stderr).await {
+                        Ok(Some(note)) => {
+                            let text = result.as_str();
+                            result.output = OutputKind::Text(if text.is_empty() {
+                                note
+                            } else {
+                                format!("{text}\n{note}")
+                            });
+                        },
+                        Ok(None) => {},
+                        Err(err) => warn!(?err, "failed to format or lint {}", path.display()),
+                    }
+                }
+            }
+            execute!(self.stderr, style::Print("\n"))?;
+
+            // Emitted through the fields, as the telemetry entry still borrows the session.
+            let event = SessionEvent::ToolResult {
+                name: tool.tool.display_name(),
+                success: invoke_result.is_ok(),
+            };
+            self.stderr.record(&event);
+            let _ = self.events.send(event);
             let tool_time = std::time::Instant::now().duration_since(tool_start);
+            self.user_turn_tool_time += tool_time;
+            tool_telemetry = tool_telemetry.and_modify(|ev| {
+                ev.execution_start_timestamp_ms = u64::try_from(tool_start_timestamp_ms).ok();
+                ev.execution_duration_ms = u64::try_from(tool_time.as_millis()).ok();
+            });
+            audit::record_tool_use(
+                os,
+                &self.conversation,
+                tool,
+                tool.approval.unwrap_or(ApprovalDecision::Trusted),
+                if invoke_result.is_ok() {
+                    ExitStatus::Success
+                } else {
+                    ExitStatus::Error
+                },
+                u64::try_from(tool_time.as_millis()).ok(),
+            )
+            .await;
             if let Tool::Custom(ct) = &tool.tool {
                 tool_telemetry = tool_telemetry.and_modify(|ev| {
                     ev.custom_tool_call_latency = Some(tool_time.as_secs() as usize);
@@ -1846,7 +3252,7 @@ impl ChatSession {
                 });
             }
             let tool_time = format!("{}.{}", tool_time.as_secs(), tool_time.subsec_millis());
-            match invoke_result {
+            let outcome = match invoke_result {
                 Ok(result) => {
                     match result.output {
                         OutputKind::Text(ref text) => {
@@ -1865,18 +3271,10 @@ impl ChatSession {
                     }
 
                     debug!("tool result output: {:#?}", result);
-                    execute!(
-                        self.stdout,
-                        style::Print(CONTINUATION_LINE),
-                        style::Print("\n"),
-                        style::SetForegroundColor(Color::Green),
-                        style::SetAttribute(Attribute::Bold),
-                        style::Print(format!(" ● Completed in {}s", tool_time)),
-                        style::SetForegroundColor(Color::Reset),
-                        style::Print("\n\n"),
-                    )?;
-
                     tool_telemetry = tool_telemetry.and_modify(|ev| ev.is_success = Some(true));
+                    if let Tool::UpdatePlan(update_plan) = &tool.tool {
+                        self.conversation.plan = Some(update_plan.plan.clone());
+                    }
                     if let Tool::Custom(_) = &tool.tool {
                         tool_telemetry
                             .and_modify(|ev| ev.output_token_size = Some(TokenCounter::count_tokens(&result.as_str())));
@@ -1886,26 +3284,14 @@ impl ChatSession {
                         content: vec![result.into()],
                         status: ToolResultStatus::Success,
                     });
+                    Ok(tool_time)
                 },
                 Err(err) => {
                     error!(?err, "An error occurred processing the tool");
-                    execute!(
-                        self.stderr,
-                        style::Print(CONTINUATION_LINE),
-                        style::Print("\n"),
-                        style::SetAttribute(Attribute::Bold),
-                        style::SetForegroundColor(Color::Red),
-                        style::Print(format!(" ● Execution failed after {}s:\n", tool_time)),
-                        style::SetAttribute(Attribute::Reset),
-                        style::SetForegroundColor(Color::Red),
-                        style::Print(&err),
-                        style::SetAttribute(Attribute::Reset),
-                        style::Print("\n\n"),
-                    )?;
-
+                    let reason_desc = self.conversation.redactor.redact(&err.to_string());
                     tool_telemetry.and_modify(|ev| {
                         ev.is_success = Some(false);
-                        ev.reason_desc = Some(err.to_string());
+                        ev.reason_desc = Some(reason_desc);
                     });
                     tool_results.push(ToolUseResult {
                         tool_use_id: tool.id.clone(),
@@ -1922,10 +3308,18 @@ impl ChatSession {
                                 .map_or("No utterance id found".to_string(), |v| v.to_string()),
                         );
                     }
+                    Err((tool_time, err.to_string()))
                 },
+            };
+            if prefetch && i + 1 == self.tool_uses.len() {
+                last_outcome = Some(outcome);
+            } else {
+                print_tool_outcome(&mut self.stderr, outcome)?;
             }
         }
 
+        self.screen_tool_results(&mut tool_results)?;
+
         if !image_blocks.is_empty() {
             let images = image_blocks.into_iter().map(|(block, _)| block).collect();
             self.conversation.add_tool_results_with_images(tool_results, images);
@@ -1939,20 +3333,69 @@ impl ChatSession {
             self.conversation.add_tool_results(tool_results);
         }
 
+        let conv_state = self
+            .conversation
+            .as_sendable_conversation_state(os, &mut self.stderr, false)
+            .await?;
+        if prefetch {
+            self.prefetch_response(os, &conv_state);
+        }
+        if let Some(outcome) = last_outcome {
+            print_tool_outcome(&mut self.stderr, outcome)?;
+        }
+
         execute!(self.stderr, cursor::Hide)?;
         execute!(self.stderr, style::Print("\n"), style::SetAttribute(Attribute::Reset))?;
         if self.interactive {
-            self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_string()));
+            self.spinner = self.renderer.busy("Thinking...");
         }
 
         self.send_chat_telemetry(os, TelemetryResult::Succeeded, None, None, None, false)
             .await;
         self.send_tool_use_telemetry(os).await;
-        return Ok(ChatState::HandleResponseStream(
-            self.conversation
-                .as_sendable_conversation_state(os, &mut self.stderr, false)
-                .await?,
-        ));
+        Ok(ChatState::HandleResponseStream(conv_state))
+    }
+
+    /// Whether the request following the tools to execute is sent ahead of time, see
+    /// [Self::prefetch_response]. This is only the case if all of them are trusted, so that the
+    /// user did not intervene, and it is not disabled with [Setting::ChatEnablePrefetch].
+    fn prefetch_enabled(&self, os: &Os) -> bool {
+        os.database
+            .settings
+            .get_bool(Setting::ChatEnablePrefetch)
+            .unwrap_or(true)
+            && self.tool_uses.iter().all(|tool| {
+                tool.approval
+                    .is_none_or(|approval| approval == ApprovalDecision::Trusted)
+            })
+    }
+
+    /// Sends the request for the next response right away, so that it is in flight while the
+    /// outcome of the last tool is rendered and the session finishes up after the tools.
+    ///
+    /// The response is consumed by the following [ChatState::HandleResponseStream], and the
+    /// request is cancelled if the session errors or is interrupted before then.
+    fn prefetch_response(&mut self, os: &Os, conversation_state: &api_client::model::ConversationState) {
+        let client = os.client.clone();
+        let conversation_state = conversation_state.clone();
+        let request_metadata = Arc::new(Mutex::new(None));
+        let request_metadata_clone = Arc::clone(&request_metadata);
+        let cancel_token = CancellationToken::new();
+        let cancel_token_clone = cancel_token.clone();
+        self.prefetch = Some(PrefetchedResponse {
+            handle: tokio::spawn(async move {
+                SendMessageStream::send_message(
+                    &client,
+                    conversation_state,
+                    request_metadata_clone,
+                    None,
+                    cancel_token_clone,
+                )
+                .await
+            }),
+            request_metadata,
+            cancel_token,
+        });
     }
 
     /// Sends a [crate::api_client::ApiClient::send_message] request to the backend and consumes
@@ -1962,24 +3405,53 @@ impl ChatSession {
     /// response stream was handled, we need an extra parameter:
     /// * `request_metadata_lock` - Updated with the [RequestMetadata] once it has been received
     ///   (either though a successful request, or on an error).
+    /// * `cancel_token` - Cancelled on sigint to abort the request, see [SendMessageStream].
     async fn handle_response(
         &mut self,
         os: &mut Os,
         state: crate::api_client::model::ConversationState,
         request_metadata_lock: Arc<Mutex<Option<RequestMetadata>>>,
+        cancel_token: CancellationToken,
     ) -> Result<ChatState, ChatError> {
-        let mut rx = self.send_message(os, state, request_metadata_lock, None).await?;
+        self.warn_large_request(os, state.payload_size())?;
+        self.partial_response.clear();
+        let mut rx = match self.prefetch.take() {
+            Some(prefetch) => {
+                let result = prefetch
+                    .handle
+                    .await
+                    .map_err(|err| ChatError::Custom(format!("prefetched request failed: {err}").into()))?;
+                self.handle_send_message_result(os, result).await?
+            },
+            None => {
+                self.send_message(os, state, request_metadata_lock, None, cancel_token)
+                    .await?
+            },
+        };
 
         let request_id = rx.request_id().map(String::from);
 
         let mut buf = String::new();
         let mut offset = 0;
         let mut ended = false;
-        let mut state = ParseState::new(Some(self.terminal_width()));
+        let mut state = ParseState::new(Some(self.response_width(os)));
+        state.render_math = os.database.settings.get_bool(Setting::ChatRenderMath).unwrap_or(true);
         let mut response_prefix_printed = false;
 
+        // When quiet, responses are rendered once complete: to stderr if they lead to tool uses,
+        // and otherwise written as plain text to stdout.
+        let mut rendered = Vec::new();
+        let mut response_text = String::new();
+
         let mut tool_uses = Vec::new();
         let mut tool_name_being_recvd: Option<String> = None;
+        // Output that is not read by a human does not need to be paced.
+        let mut pacer = RenderPacer::new(
+            os.database
+                .settings
+                .get_bool(Setting::ChatInstantRender)
+                .unwrap_or(!std::io::stdout().is_terminal()),
+        );
 
         if self.spinner.is_some() {
             drop(self.spinner.take());
@@ -2012,6 +3484,9 @@ impl ChatSession {
                                 response_prefix_printed = true;
                             }
                             buf.push_str(&text);
+                            self.tee(os, &text).await;
+                            self.partial_response.push_str(&text);
+                            self.emit(SessionEvent::ResponseText { text });
                         },
                         parser::ResponseEvent::ToolUse(tool_use) => {
                             if self.spinner.is_some() {
@@ -2036,7 +3511,11 @@ impl ChatSession {
                                 error!(?request_id, ?message, "Encountered an unexpected model response");
                             }
                             self.conversation.push_assistant_message(os, message, Some(rm.clone()));
-                            self.user_turn_request_metadata.push(rm);
+                            response_text = std::mem::take(&mut self.partial_response);
+                            self.tee(os, "\n\n").await;
+                            self.emit(SessionEvent::ResponseEnd);
+                            self.warn_slow_request(&rm)?;
+                            self.push_request_metadata(rm);
                             ended = true;
                         },
                     }
@@ -2046,8 +3525,7 @@ impl ChatSession {
                         self.failed_request_ids.push(request_id.clone());
                     };
 
-                    self.user_turn_request_metadata
-                        .push(recv_error.request_metadata.clone());
+                    self.push_request_metadata(recv_error.request_metadata.clone());
                     let (reason, reason_desc) = get_error_reason(&recv_error);
                     let status_code = recv_error.status_code();
 
@@ -2071,7 +3549,7 @@ impl ChatSession {
                             );
 
                             execute!(self.stderr, cursor::Hide)?;
-                            self.spinner = Some(Spinner::new(Spinners::Dots, "Dividing up the work...".to_string()));
+                            self.spinner = self.renderer.busy("Dividing up the work...");
 
                             // For stream timeouts, we'll tell the model to try and split its response into
                             // smaller chunks.
@@ -2156,6 +3634,7 @@ impl ChatSession {
             // still left in the buffer. I'm not sure how this is intended to be handled.
             if ended {
                 buf.push('\n');
+                state.stream_ended = true;
             }
 
             if tool_name_being_recvd.is_none() && !buf.is_empty() && self.spinner.is_some() {
@@ -2171,7 +3650,11 @@ impl ChatSession {
             // Print the response for normal cases
             loop {
                 let input = Partial::new(&buf[offset..]);
-                match interpret_markdown(input, &mut self.stdout, &mut state) {
+                let parsed = match self.quiet {
+                    true => interpret_markdown(input, &mut rendered, &mut state),
+                    false => interpret_markdown(input, &mut self.stdout, &mut state),
+                };
+                match parsed {
                     Ok(parsed) => {
                         offset += parsed.offset_from(&input);
                         self.stdout.flush()?;
@@ -2184,16 +3667,14 @@ impl ChatSession {
                     },
                 }
 
-                // TODO: We should buffer output based on how much we have to parse, not as a constant
-                // Do not remove unless you are nabochay :)
-                tokio::time::sleep(Duration::from_millis(8)).await;
+                pacer.pace(buf.len() - offset).await;
             }
 
             // Set spinner after showing all of the assistant text content so far.
             if tool_name_being_recvd.is_some() {
                 queue!(self.stderr, cursor::Hide)?;
                 if self.interactive {
-                    self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_string()));
+                    self.spinner = self.renderer.busy("Thinking...");
                 }
             }
 
@@ -2209,11 +3690,15 @@ impl ChatSession {
                 }
 
                 queue!(self.stderr, style::ResetColor, style::SetAttribute(Attribute::Reset))?;
-                execute!(self.stdout, style::Print("\n"))?;
+                let mut output: &mut dyn Write = match self.quiet {
+                    true => &mut rendered,
+                    false => &mut self.stdout,
+                };
+                execute!(&mut output, style::Print("\n"))?;
 
                 for (i, citation) in &state.citations {
                     queue!(
-                        self.stdout,
+                        &mut output,
                         style::Print("\n"),
                         style::SetForegroundColor(Color::Blue),
                         style::Print(format!("[^{i}]: ")),
@@ -2227,6 +3712,15 @@ impl ChatSession {
             }
         }
 
+        if self.quiet {
+            match tool_uses.is_empty() {
+                true => execute!(self.stdout, style::Print(format!("{}\n", response_text.trim_end())))?,
+                false => execute!(self.stderr, style::Print(String::from_utf8_lossy(&rendered)))?,
+            }
+        }
+
+        self.overloaded_models.clear();
+
         if !tool_uses.is_empty() {
             Ok(ChatState::ValidateTools { tool_uses })
         } else {
@@ -2236,12 +3730,234 @@ impl ChatSession {
             self.send_chat_telemetry(os, TelemetryResult::Succeeded, None, None, None, true)
                 .await;
 
+            let timings = TurnTimings::new(&self.user_turn_request_metadata, self.user_turn_tool_time);
+            self.network_stats.turns.record(&timings);
+            if os.database.settings.get_bool(Setting::ChatShowTimings).unwrap_or(false) {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("⏱  {timings}\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            if std::mem::take(&mut self.recovered_from_error) && !self.feedback_hint_shown && self.interactive {
+                self.feedback_hint_shown = true;
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!(
+                        "If this response is not what you expected, you can flag it with {}\n",
+                        "/feedback down [comment]".green()
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+
+            self.request_title(os);
+
             Ok(ChatState::PromptUser {
                 skip_printing_tools: false,
             })
         }
     }
 
+    /// Sends `event` to the watchers of the session, if any, and to the test harness.
+    fn emit(&self, event: SessionEvent) {
+        self.stderr.record(&event);
+        let _ = self.events.send(event);
+    }
+
+    /// Appends `text` to the file given with `--tee`. Writes are not buffered so that the text
+    /// survives the session being killed. The file is no longer written to after a failure.
+    async fn tee(&mut self, os: &Os, text: &str) {
+        let Some(path) = &self.tee else {
+            return;
+        };
+        if let Err(err) = os.fs.append(path, text).await {
+            let _ = execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::Yellow),
+                style::Print(format!("\nFailed to write to {}: {err}\n", path.display())),
+                style::SetForegroundColor(Color::Reset),
+            );
+            self.tee = None;
+        }
+    }
+
+    /// Captures the keys typed while the model is responding or tools are running, and queues
+    /// them as the next prompt once input is read again.
+    fn update_type_ahead(&mut self, os: &Os) {
+        let capture = self.interactive
+            && self.renderer.reads_terminal()
+            && match &self.inner {
+                Some(ChatState::HandleResponseStream(_)) => true,
+                // Commands run by execute_bash may read from the terminal themselves.
+                Some(ChatState::ExecuteTools) => !self
+                    .tool_uses
+                    .iter()
+                    .any(|tool| matches!(tool.tool, Tool::ExecuteCommand(_))),
+                _ => false,
+            }
+            && os.database.settings.get_bool(Setting::ChatTypeAhead).unwrap_or(true);
+
+        match (capture, self.type_ahead.take()) {
+            (true, None) => self.type_ahead = TypeAhead::start(),
+            (true, Some(type_ahead)) => self.type_ahead = Some(type_ahead),
+            (false, Some(type_ahead)) => self.queued_input.push_str(&type_ahead.stop()),
+            (false, None) => (),
+        }
+    }
+
+    /// Snapshot of the session shown by the [Renderer].
+    fn status(&self) -> SessionStatus {
+        let activity = match &self.inner {
+            Some(ChatState::PromptUser { .. }) => "Waiting for input",
+            Some(ChatState::HandleInput { .. }) => "Handling input",
+            Some(ChatState::ValidateTools { .. }) => "Validating tools",
+            Some(ChatState::ExecuteTools) => "Running tools",
+            Some(ChatState::HandleResponseStream(_)) => "Responding",
+            Some(ChatState::CompactHistory { .. }) => "Compacting the conversation",
+            Some(ChatState::RetryModelOverload { .. }) => "Retrying with another model",
+            Some(ChatState::WaitForConnectivity) => "Waiting for the connection to return",
+            Some(ChatState::Exit) | None => "Exiting",
+        };
+        let model = self.conversation.model.as_ref().map(|model_id| {
+            self.models
+                .iter()
+                .find(|model| &model.model_id == model_id)
+                .map_or_else(|| model_id.clone(), |model| model.name.clone())
+        });
+        let context_files = self
+            .conversation
+            .context_manager
+            .as_ref()
+            .map(|context_manager| context_manager.paths.clone())
+            .unwrap_or_default();
+        let pending_tools = self
+            .tool_uses
+            .iter()
+            .enumerate()
+            .map(|(i, tool)| PendingTool {
+                name: tool.name.clone(),
+                awaiting_approval: self.pending_tool_index == Some(i),
+            })
+            .collect();
+
+        SessionStatus {
+            activity: activity.to_string(),
+            model,
+            agent: Some(self.conversation.agents.active_idx.clone()),
+            context_files,
+            pending_tools,
+        }
+    }
+
+    /// Adds the text streamed so far of a response stopped by the user to the conversation,
+    /// marked as truncated, and returns to the prompt.
+    /// Handles a response stopped with ctrl + c. The text streamed so far is kept in the
+    /// conversation, unless ctrl + c is pressed again within [DISCARD_RESPONSE_WINDOW]. When no
+    /// text was streamed yet, or keeping it is disabled, the session is interrupted right away.
+    async fn stop_response(
+        &mut self,
+        os: &mut Os,
+        ctrl_c_stream: &mut broadcast::Receiver<()>,
+    ) -> Result<ChatState, ChatError> {
+        if self.partial_response.trim().is_empty()
+            || !os
+                .database
+                .settings
+                .get_bool(Setting::ChatKeepPartialResponse)
+                .unwrap_or(true)
+        {
+            self.partial_response.clear();
+            return Err(ChatError::Interrupted { tool_uses: None });
+        }
+
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("\n\nResponse stopped. Press ctrl + c again to discard it.\n"),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        match tokio::time::timeout(DISCARD_RESPONSE_WINDOW, ctrl_c_stream.recv()).await {
+            Ok(Ok(_)) => {
+                self.partial_response.clear();
+                Err(ChatError::Interrupted { tool_uses: None })
+            },
+            _ => Ok(self.keep_partial_response(os)),
+        }
+    }
+
+    fn keep_partial_response(&mut self, os: &mut Os) -> ChatState {
+        let content = format!(
+            "{}\n\n{TRUNCATED_RESPONSE_MARKER}",
+            std::mem::take(&mut self.partial_response).trim_end()
+        );
+        self.conversation
+            .push_assistant_message(os, AssistantMessage::new_response(None, content), None);
+        self.tool_uses.clear();
+        self.pending_tool_index = None;
+        self.reset_user_turn();
+
+        ChatState::PromptUser {
+            skip_printing_tools: false,
+        }
+    }
+
+    /// Asks the model for a title of the conversation in the background, once its first
+    /// exchange is complete.
+    fn request_title(&mut self, os: &Os) {
+        if !self.interactive
+            || self.pending_title.is_some()
+            || !os
+                .database
+                .settings
+                .get_bool(Setting::ChatEnableAutoTitle)
+                .unwrap_or(true)
+        {
+            return;
+        }
+        let Some(title_state) = self.conversation.create_title_request() else {
+            return;
+        };
+
+        let client = os.client.clone();
+        self.pending_title = Some(tokio::spawn(async move {
+            let mut output = match client.send_message(title_state).await {
+                Ok(output) => output,
+                Err(err) => {
+                    warn!(?err, "failed to request a conversation title");
+                    return None;
+                },
+            };
+            let mut response = String::new();
+            while let Ok(Some(event)) = output.recv().await {
+                if let ChatResponseStream::AssistantResponseEvent { content } = event {
+                    response.push_str(&content);
+                }
+            }
+            conversation::title_from_response(&response)
+        }));
+    }
+
+    /// Stores the title generated by [Self::request_title] with the conversation, and shows it in
+    /// the title bar of the terminal.
+    async fn apply_title(&mut self, os: &mut Os) -> Result<(), ChatError> {
+        let Some(task) = self.pending_title.take() else {
+            return Ok(());
+        };
+        let Ok(Some(title)) = task.await else {
+            return Ok(());
+        };
+
+        execute!(self.stderr, terminal::SetTitle(&title))?;
+        self.conversation.title = Some(title);
+        if let Err(err) = self.conversation.save(os) {
+            warn!(?err, "failed to save the conversation");
+        }
+        Ok(())
+    }
+
     async fn validate_tools(&mut self, os: &Os, tool_uses: Vec<AssistantToolUse>) -> Result<ChatState, ChatError> {
         let conv_id = self.conversation.conversation_id().to_owned();
         debug!(?tool_uses, "Validating tool uses");
@@ -2251,6 +3967,7 @@ impl ChatSession {
         for tool_use in tool_uses {
             let tool_use_id = tool_use.id.clone();
             let tool_use_name = tool_use.name.clone();
+            let args_hash = audit::args_hash(&tool_use.args);
             let mut tool_telemetry =
                 ToolUseEventBuilder::new(conv_id.clone(), tool_use.id.clone(), self.conversation.model.clone())
                     .set_tool_use_id(tool_use_id.clone())
@@ -2269,6 +3986,8 @@ impl ChatSession {
                                 name: tool_use_name,
                                 tool,
                                 accepted: false,
+                                args_hash,
+                                approval: None,
                             });
                         },
                         Err(err) => {
@@ -2345,23 +4064,50 @@ impl ChatSession {
         Ok(ChatState::ExecuteTools)
     }
 
-    async fn retry_model_overload(&mut self, os: &mut Os) -> Result<ChatState, ChatError> {
-        match select_model(self) {
-            Ok(Some(_)) => (),
-            Ok(None) => {
-                // User did not select a model, so reset the current request state.
-                self.conversation.enforce_conversation_invariants();
-                self.conversation.reset_next_user_message();
-                self.pending_tool_index = None;
-                return Ok(ChatState::PromptUser {
-                    skip_printing_tools: false,
-                });
-            },
-            Err(err) => return Err(err),
+    async fn retry_model_overload(&mut self, os: &mut Os, select: bool) -> Result<ChatState, ChatError> {
+        if select && select_model(self)?.is_none() {
+            // User did not select a model, so reset the current request state.
+            self.conversation.enforce_conversation_invariants();
+            self.conversation.reset_next_user_message();
+            self.pending_tool_index = None;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: false,
+            });
+        }
+
+        if self.interactive {
+            self.spinner = self.renderer.busy("Thinking...");
+        }
+
+        Ok(ChatState::HandleResponseStream(
+            self.conversation
+                .as_sendable_conversation_state(os, &mut self.stderr, true)
+                .await?,
+        ))
+    }
+
+    async fn wait_for_connectivity(&mut self, os: &mut Os) -> Result<ChatState, ChatError> {
+        if self.interactive {
+            self.spinner = self.renderer.busy("Waiting for the connection to return...");
+        }
+        connectivity::wait_until_online(os).await;
+
+        if self.spinner.is_some() {
+            drop(self.spinner.take());
+            queue!(
+                self.stderr,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                cursor::MoveToColumn(0),
+            )?;
         }
-
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print("Back online, sending the prompt.\n\n"),
+            style::SetForegroundColor(Color::Reset),
+        )?;
         if self.interactive {
-            self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_owned()));
+            self.spinner = self.renderer.busy("Thinking...");
         }
 
         Ok(ChatState::HandleResponseStream(
@@ -2383,6 +4129,14 @@ impl ChatSession {
                 .get_active()
                 .map(|a| a.allowed_tools.iter().cloned().collect::<Vec<_>>())
                 .unwrap_or_default();
+            let mut mcp_servers = self
+                .conversation
+                .tool_manager
+                .clients
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            mcp_servers.sort();
             gh_issue.set_context(GhIssueContext {
                 // Ideally we avoid cloning, but this function is not called very often.
                 // Using references with lifetimes requires a large refactor, and Arc<Mutex<T>>
@@ -2391,15 +4145,88 @@ impl ChatSession {
                 transcript: self.conversation.transcript.clone(),
                 failed_request_ids: self.failed_request_ids.clone(),
                 tool_permissions: allowed_tools,
+                mcp_servers,
             });
         }
+        if let Tool::ExecuteCommand(execute_command) = tool {
+            let agent = self.conversation.agents.get_active();
+            execute_command.sandbox = agent.map(|a| a.execution_sandbox).unwrap_or_default();
+            execute_command.matched_rule = agent.and_then(|a| execute_command.matched_rule(a));
+        }
+        if let Tool::FsRead(fs_read) = tool {
+            fs_read.roots = self.conversation.workspace_roots().to_vec();
+        }
+        if let Tool::ListFiles(list_files) = tool {
+            list_files.roots = self.conversation.workspace_roots().to_vec();
+        }
+        if let Tool::RenameSymbol(rename_symbol) = tool {
+            rename_symbol.roots = self.conversation.workspace_roots().to_vec();
+        }
+        if let Tool::FsWrite(fs_write) = tool {
+            fs_write.set_roots(self.conversation.workspace_roots());
+            fs_write.set_create_policy(
+                self.conversation
+                    .agents
+                    .get_active()
+                    .map(CreatePolicy::configured)
+                    .unwrap_or_default(),
+            );
+        }
+        if let Tool::Scratchpad(scratchpad) = tool {
+            scratchpad.sandbox = self
+                .conversation
+                .agents
+                .get_active()
+                .map(|a| a.execution_sandbox)
+                .unwrap_or_default();
+        }
+        if let Tool::AwsRead(aws_read) = tool {
+            aws_read.settings = self
+                .conversation
+                .agents
+                .get_active()
+                .and_then(AwsReadSettings::configured);
+        }
+        if let Tool::DbQuery(db_query) = tool {
+            db_query.settings = self
+                .conversation
+                .agents
+                .get_active()
+                .and_then(DbQuerySettings::configured);
+        }
+        if let Tool::HttpRequest(http_request) = tool {
+            http_request.settings = self
+                .conversation
+                .agents
+                .get_active()
+                .and_then(HttpRequestSettings::configured);
+        }
+        if let Tool::Browser(browser) = tool {
+            browser.settings = self
+                .conversation
+                .agents
+                .get_active()
+                .and_then(BrowserSettings::configured);
+        }
+        if let Tool::KbSearch(kb_search) = tool {
+            kb_search.knowledge_bases = self
+                .conversation
+                .agents
+                .get_active()
+                .map(KnowledgeBase::configured)
+                .unwrap_or_default();
+        }
     }
 
     async fn print_tool_description(&mut self, os: &Os, tool_index: usize, trusted: bool) -> Result<(), ChatError> {
+        self.emit(SessionEvent::ToolUse {
+            name: self.tool_uses[tool_index].tool.display_name(),
+        });
         let tool_use = &self.tool_uses[tool_index];
+        let output = &mut self.stderr;
 
         queue!(
-            self.stdout,
+            output,
             style::SetForegroundColor(Color::Magenta),
             style::Print(format!(
                 "🛠️  Using tool: {}{}",
@@ -2410,7 +4237,7 @@ impl ChatSession {
         )?;
         if let Tool::Custom(ref tool) = tool_use.tool {
             queue!(
-                self.stdout,
+                output,
                 style::SetForegroundColor(Color::Reset),
                 style::Print(" from mcp server "),
                 style::SetForegroundColor(Color::Magenta),
@@ -2420,7 +4247,7 @@ impl ChatSession {
         }
 
         execute!(
-            self.stdout,
+            output,
             style::Print("\n"),
             style::Print(CONTINUATION_LINE),
             style::Print("\n"),
@@ -2429,13 +4256,210 @@ impl ChatSession {
 
         tool_use
             .tool
-            .queue_description(os, &mut self.stdout)
+            .queue_description(os, output)
             .await
             .map_err(|e| ChatError::Custom(format!("failed to print tool, `{}`: {}", tool_use.name, e).into()))?;
 
         Ok(())
     }
 
+    /// Warns the user about tool results that look like prompt injection. When quarantine is
+    /// enabled, the flagged results are withheld from the model unless the user allows them, which
+    /// is never the case in non-interactive sessions.
+    fn screen_tool_results(&mut self, tool_results: &mut [ToolUseResult]) -> Result<(), ChatError> {
+        if !self.conversation.injection_guard.is_enabled() {
+            return Ok(());
+        }
+
+        for result in tool_results.iter_mut() {
+            let findings = injection::scan_tool_result(result);
+            if findings.is_empty() {
+                continue;
+            }
+
+            let tool_name = self
+                .tool_uses
+                .iter()
+                .find(|tool| tool.id == result.tool_use_id)
+                .map_or("tool", |tool| tool.name.as_str())
+                .to_string();
+            queue!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkYellow),
+                style::Print(format!("\nThe output of {tool_name} may contain prompt injection:\n")),
+            )?;
+            for finding in &findings {
+                queue!(self.stderr, style::Print(format!("  - {finding}\n")))?;
+            }
+            execute!(self.stderr, style::SetForegroundColor(Color::Reset))?;
+
+            if !self.conversation.injection_guard.quarantine {
+                continue;
+            }
+            let allowed = self.interactive
+                && self
+                    .read_user_input(
+                        &format!("Add the output of {tool_name} to the conversation? [y/n]: "),
+                        true,
+                    )
+                    .is_some_and(|input| ["y", "Y"].contains(&input.trim()));
+            if !allowed {
+                result.content = vec![ToolUseResultBlock::Text(format!(
+                    "The output of {tool_name} was withheld because it may contain prompt injection ({}).",
+                    findings
+                        .iter()
+                        .map(|f| f.kind.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))];
+                result.status = ToolResultStatus::Error;
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkYellow),
+                    style::Print(format!("Withheld the output of {tool_name} from the model.\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Warns the user about credentials in `prompt` before it is sent, offering to redact them.
+    /// Returns [None] if the user chose not to send the prompt.
+    fn screen_prompt(&mut self, os: &Os, prompt: String) -> Result<Option<String>, ChatError> {
+        if !os
+            .database
+            .settings
+            .get_bool(Setting::ChatSecretDetectionEnabled)
+            .unwrap_or(true)
+        {
+            return Ok(Some(prompt));
+        }
+        let findings = secrets::scan(&prompt);
+        if findings.is_empty() {
+            return Ok(Some(prompt));
+        }
+
+        queue!(
+            self.stderr,
+            style::SetForegroundColor(Color::DarkYellow),
+            style::Print("Your prompt looks like it contains credentials:\n"),
+        )?;
+        for finding in &findings {
+            queue!(
+                self.stderr,
+                style::Print(format!("  - {}: \"{}\"\n", finding.kind, finding.excerpt(&prompt)))
+            )?;
+        }
+        execute!(self.stderr, style::SetForegroundColor(Color::Reset))?;
+        if !self.interactive {
+            return Ok(Some(prompt));
+        }
+
+        loop {
+            let Some(choice) = self.read_user_input("Redact them, send the prompt as is, or cancel? [r/s/c]: ", true)
+            else {
+                return Ok(None);
+            };
+            match choice.trim() {
+                "r" | "R" => {
+                    // The prompt was already added to the transcript.
+                    for finding in &findings {
+                        let secret = Regex::new(&regex::escape(&prompt[finding.range.clone()]))
+                            .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+                        self.conversation.redact_matches(&secret);
+                    }
+                    execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("Redacted the credentials from the prompt.\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    return Ok(Some(secrets::redact(&prompt, &findings)));
+                },
+                "s" | "S" => return Ok(Some(prompt)),
+                "c" | "C" => return Ok(None),
+                _ => (),
+            }
+        }
+    }
+
+    /// Returns the hunks of the fs_write waiting for approval, when it has several that can be
+    /// approved one by one.
+    fn reviewable_hunks(&self, os: &Os) -> Vec<Hunk> {
+        match self
+            .pending_tool_index
+            .and_then(|i| self.tool_uses.get(i))
+            .map(|t| &t.tool)
+        {
+            Some(Tool::FsWrite(fs_write)) => fs_write
+                .hunks(os)
+                .ok()
+                .filter(|hunks| hunks.len() > 1)
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Asks whether to apply each hunk of the fs_write at `index`, as with `git add -p`, then runs
+    /// it with the approved hunks only. Rejecting every hunk rejects the tool use.
+    fn review_hunks(&mut self, os: &Os, index: usize) -> Result<ChatState, ChatError> {
+        let hunks = self.reviewable_hunks(os);
+        let mut accepted = Vec::with_capacity(hunks.len());
+        let mut rest = None;
+        for (i, hunk) in hunks.iter().enumerate() {
+            if let Some(rest) = rest {
+                accepted.push(rest);
+                continue;
+            }
+            queue!(self.stderr, style::Print("\n"))?;
+            hunk.queue(&mut self.stderr)?;
+            execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!(
+                    "\nApply hunk {}/{}? [y: yes, n: no, a: this and the rest, d: none of the rest]:\n\n",
+                    i + 1,
+                    hunks.len()
+                )),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            loop {
+                let Some(input) = self.read_user_input(&"> ".yellow().to_string(), true) else {
+                    // The tool use is left waiting for approval.
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: false,
+                    });
+                };
+                match input.trim() {
+                    "y" | "Y" => accepted.push(true),
+                    "n" | "N" => accepted.push(false),
+                    "a" | "A" => rest = Some(true),
+                    "d" | "D" => rest = Some(false),
+                    _ => continue,
+                }
+                if let Some(rest) = rest {
+                    accepted.push(rest);
+                }
+                break;
+            }
+        }
+
+        if !accepted.contains(&true) {
+            return Ok(ChatState::HandleInput { input: "n".to_string() });
+        }
+        let tool_use = &mut self.tool_uses[index];
+        if let Tool::FsWrite(fs_write) = &mut tool_use.tool {
+            if accepted.contains(&false) {
+                fs_write.accept_hunks(accepted);
+            }
+        }
+        tool_use.accepted = true;
+        tool_use.approval = Some(ApprovalDecision::Approved);
+        Ok(ChatState::ExecuteTools)
+    }
+
     /// Helper function to read user input with a prompt and Ctrl+C handling
     fn read_user_input(&mut self, prompt: &str, exit_on_single_ctrl_c: bool) -> Option<String> {
         let mut ctrl_c = false;
@@ -2474,6 +4498,27 @@ impl ChatSession {
         prompt::generate_prompt(profile.as_deref(), all_trusted)
     }
 
+    /// Enforces the `chat.memory.*` caps on the structures that otherwise grow with the length of
+    /// the session.
+    async fn trim_memory(&mut self, os: &Os) {
+        let caps = MemoryCaps::new(os);
+
+        self.conversation.trim_transcript(caps.transcript_entries);
+
+        let excess = self.failed_request_ids.len().saturating_sub(caps.failed_request_ids);
+        self.failed_request_ids.drain(..excess);
+
+        // Events are normally sent once their tool use completes. Flush any stragglers rather than
+        // holding on to them indefinitely.
+        if self.tool_use_telemetry_events.len() > caps.pending_tool_telemetry_events {
+            debug!(
+                count = self.tool_use_telemetry_events.len(),
+                "Flushing pending tool use telemetry events"
+            );
+            self.send_tool_use_telemetry(os).await;
+        }
+    }
+
     async fn send_tool_use_telemetry(&mut self, os: &Os) {
         for (_, mut event) in self.tool_use_telemetry_events.drain() {
             event.user_input_id = match self.tool_use_status {
@@ -2490,10 +4535,56 @@ impl ChatSession {
         (self.terminal_width_provider)().unwrap_or(80)
     }
 
+    /// Width at which responses are wrapped: the terminal width, capped by the
+    /// `chat.maxResponseWidth` setting so that text stays readable on wide terminals.
+    fn response_width(&self, os: &Os) -> usize {
+        let terminal_width = self.terminal_width();
+        match os
+            .database
+            .settings
+            .get_int(Setting::ChatMaxResponseWidth)
+            .and_then(|max| usize::try_from(max).ok())
+        {
+            Some(max) if max > 0 => terminal_width.min(max),
+            _ => terminal_width,
+        }
+    }
+
     fn all_tools_trusted(&self) -> bool {
         self.conversation.agents.trust_all_tools
     }
 
+    /// Names of the tools accepted by `/tools trust`: the native tools and those of MCP servers.
+    fn trustable_tool_names(&self) -> Vec<String> {
+        use crate::api_client::model::Tool as FigTool;
+        use crate::cli::chat::consts::DUMMY_TOOL_NAME;
+
+        let native = self.conversation.tools.get("native").into_iter().flatten();
+        let mut names = native
+            .map(|FigTool::ToolSpecification(spec)| spec.name.clone())
+            .filter(|name| name != DUMMY_TOOL_NAME)
+            .chain(
+                self.conversation
+                    .tool_manager
+                    .tn_map
+                    .values()
+                    .map(|info| info.host_tool_name.clone()),
+            )
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Trust related messaging configured by the active agent.
+    fn trust_messages(&self) -> TrustMessages {
+        self.conversation
+            .agents
+            .get_active()
+            .map(|a| a.trust_messages.clone())
+            .unwrap_or_default()
+    }
+
     /// Display character limit warnings based on current conversation size
     async fn display_char_warnings(&mut self, os: &Os) -> Result<(), ChatError> {
         let warning_level = self.conversation.get_token_warning_level(os).await?;
@@ -2528,6 +4619,8 @@ impl ChatSession {
     fn reset_user_turn(&mut self) {
         info!(?self.user_turn_request_metadata, "Resetting the current user turn");
         self.user_turn_request_metadata.clear();
+        self.user_turn_tool_time = Duration::ZERO;
+        self.recovered_from_error = false;
     }
 
     /// Sends an "codewhispererterminal_addChatMessage" telemetry event.
@@ -2574,6 +4667,11 @@ impl ChatSession {
             tool_name: self.conversation.latest_tool_use_names(),
             assistant_response_length: md.map(|md| md.response_size as i32),
             message_meta_tags: md.map(|md| md.message_meta_tags.clone()).unwrap_or_default(),
+            request_start_timestamp_ms: md.map(|md| md.request_start_timestamp_ms),
+            stream_end_timestamp_ms: md.map(|md| md.stream_end_timestamp_ms),
+            input_token_count: md
+                .map(|_| TokenCounter::count_tokens_char_count(*self.conversation.estimated_char_count())),
+            output_token_count: md.map(|md| TokenCounter::count_tokens_char_count(md.response_size)),
         };
         os.telemetry
             .send_chat_added_message(&os.database, conversation_id.clone(), result, data)
@@ -2582,6 +4680,7 @@ impl ChatSession {
 
         if is_end_turn {
             let mds = &self.user_turn_request_metadata;
+            let timings = TurnTimings::new(mds, self.user_turn_tool_time);
 
             // Get the user turn duration.
             let start_time = mds.first().map(|md| md.request_start_timestamp_ms);
@@ -2612,6 +4711,8 @@ impl ChatSession {
                         .iter()
                         .filter(|md| matches!(md.chat_conversation_type, Some(ChatConversationType::ToolUse)))
                         .count() as i64,
+                    stream_time_ms: timings.stream_time.as_millis() as i64,
+                    tool_time_ms: timings.tool_time.as_millis() as i64,
                 })
                 .await
                 .ok();
@@ -2661,6 +4762,36 @@ impl ChatSession {
     }
 }
 
+/// Prints how a tool use ended: the time it took to complete, or to fail with the given error.
+fn print_tool_outcome(output: &mut impl Write, outcome: Result<String, (String, String)>) -> Result<(), ChatError> {
+    match outcome {
+        Ok(tool_time) => execute!(
+            output,
+            style::Print(CONTINUATION_LINE),
+            style::Print("\n"),
+            style::SetForegroundColor(Color::Green),
+            style::SetAttribute(Attribute::Bold),
+            style::Print(format!(" ● Completed in {}s", tool_time)),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n\n"),
+        )?,
+        Err((tool_time, err)) => execute!(
+            output,
+            style::Print(CONTINUATION_LINE),
+            style::Print("\n"),
+            style::SetAttribute(Attribute::Bold),
+            style::SetForegroundColor(Color::Red),
+            style::Print(format!(" ● Execution failed after {}s:\n", tool_time)),
+            style::SetAttribute(Attribute::Reset),
+            style::SetForegroundColor(Color::Red),
+            style::Print(err),
+            style::SetAttribute(Attribute::Reset),
+            style::Print("\n\n"),
+        )?,
+    }
+    Ok(())
+}
+
 /// Replaces amzn_codewhisperer_client::types::SubscriptionStatus with a more descriptive type.
 /// See response expectations in [`get_subscription_status`] for reasoning.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -2725,7 +4856,11 @@ where
     Fut: std::future::Future<Output = Result<T, E>>,
 {
     queue!(output, cursor::Hide,).ok();
-    let spinner = Some(Spinner::new(Spinners::Dots, spinner_text.to_owned()));
+    let spinner = Some(Spinner::with_stream(
+        Spinners::Dots,
+        spinner_text.to_owned(),
+        spinners::Stream::Stderr,
+    ));
 
     let result = f().await;
 
@@ -2766,6 +4901,28 @@ mod tests {
 
     use super::*;
     use crate::cli::agent::Agent;
+    use crate::cli::chat::output::{
+        CapturedOutput,
+        Chunk,
+    };
+
+    #[test]
+    fn test_non_interactive_failure() {
+        let overflow = ChatError::Client(Box::new(ApiClientError::ContextWindowOverflow { status_code: None }));
+        assert_eq!(
+            NonInteractiveFailure::from_error(&overflow),
+            NonInteractiveFailure::ContextOverflow
+        );
+        assert_eq!(
+            NonInteractiveFailure::from_error(&ChatError::NonInteractiveToolApproval),
+            NonInteractiveFailure::ToolDenied
+        );
+        assert_eq!(
+            NonInteractiveFailure::from_error(&ChatError::Custom("oops".into())),
+            NonInteractiveFailure::Other
+        );
+        assert_eq!(ExitCode::from(NonInteractiveFailure::ModelError), ExitCode::from(3));
+    }
 
     async fn get_test_agents(os: &Os) -> Agents {
         const AGENT_PATH: &str = "/persona/TestAgent.json";
@@ -2798,6 +4955,12 @@ mod tests {
     #[tokio::test]
     async fn test_flow() {
         let mut os = Os::new().await.unwrap();
+        // The title request would consume the mocked responses.
+        os.database
+            .settings
+            .set(Setting::ChatEnableAutoTitle, false)
+            .await
+            .unwrap();
         os.client.set_mock_output(serde_json::json!([
             [
                 "Sure, I'll create a file for you",
@@ -2851,6 +5014,11 @@ mod tests {
     #[tokio::test]
     async fn test_flow_tool_permissions() {
         let mut os = Os::new().await.unwrap();
+        os.database
+            .settings
+            .set(Setting::ChatEnableAutoTitle, false)
+            .await
+            .unwrap();
         os.client.set_mock_output(serde_json::json!([
             [
                 "Ok",
@@ -2998,6 +5166,11 @@ mod tests {
     async fn test_flow_multiple_tools() {
         // let _ = tracing_subscriber::fmt::try_init();
         let mut os = Os::new().await.unwrap();
+        os.database
+            .settings
+            .set(Setting::ChatEnableAutoTitle, false)
+            .await
+            .unwrap();
         os.client.set_mock_output(serde_json::json!([
             [
                 "Sure, I'll create a file for you",
@@ -3092,6 +5265,11 @@ mod tests {
     async fn test_flow_tools_trust_all() {
         // let _ = tracing_subscriber::fmt::try_init();
         let mut os = Os::new().await.unwrap();
+        os.database
+            .settings
+            .set(Setting::ChatEnableAutoTitle, false)
+            .await
+            .unwrap();
         os.client.set_mock_output(serde_json::json!([
             [
                 "Sure, I'll create a file for you",
@@ -3160,6 +5338,245 @@ mod tests {
         assert!(!os.fs.exists("/file2.txt"));
     }
 
+    /// Returns a non-interactive session with the given mocked responses, and a user message ready
+    /// to be sent.
+    async fn mock_session(os: &mut Os, stdout: impl Into<SessionOutput>, responses: serde_json::Value) -> ChatSession {
+        os.database
+            .settings
+            .set(Setting::ChatEnableAutoTitle, false)
+            .await
+            .unwrap();
+        os.client.set_mock_output(responses);
+        let agents = get_test_agents(os).await;
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let mut session = ChatSession::new(
+            os,
+            stdout,
+            std::io::stderr(),
+            "fake_conv_id",
+            agents,
+            None,
+            InputSource::new_mock(vec![]),
+            false,
+            || Some(80),
+            ToolManager::default(),
+            None,
+            tool_config,
+            false,
+        )
+        .await
+        .unwrap();
+        session.conversation.set_next_user_message("hello".to_string()).await;
+        session
+    }
+
+    /// Returns the text written to `output`, without terminal styling.
+    fn written(output: &CapturedOutput) -> String {
+        output
+            .chunks()
+            .into_iter()
+            .filter_map(|chunk| match chunk {
+                Chunk::Write(_, bytes) => Some(strip_ansi_escapes::strip_str(String::from_utf8_lossy(&bytes))),
+                Chunk::Event(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns a session whose history uses `removed_tool`, answering the prompts with `lines`.
+    async fn session_with_removed_tool(os: &mut Os, lines: &[&str]) -> ChatSession {
+        let mut session = mock_session(os, CapturedOutput::pair().0, serde_json::json!([])).await;
+        session.input_source = InputSource::new_mock(lines.iter().map(|l| (*l).to_string()).collect());
+        session.conversation.push_assistant_message(
+            os,
+            AssistantMessage::new_tool_use(None, String::new(), vec![AssistantToolUse {
+                id: "1".to_string(),
+                name: "removed_tool".to_string(),
+                orig_name: "removed_tool".to_string(),
+                ..Default::default()
+            }]),
+            None,
+        );
+        session.conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![ToolUseResultBlock::Text("removed output".to_string())],
+            status: ToolResultStatus::Success,
+        }]);
+        session
+            .conversation
+            .push_assistant_message(os, AssistantMessage::new_response(None, "done".to_string()), None);
+        assert_eq!(session.conversation.unavailable_tool_uses(), vec!["removed_tool"]);
+        session
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_unavailable_tools() {
+        let mut os = Os::new().await.unwrap();
+
+        let mut session = session_with_removed_tool(&mut os, &["m", "fs_read"]).await;
+        assert!(session.reconcile_unavailable_tools(&os).await.unwrap());
+        assert!(session.conversation.unavailable_tool_uses().is_empty());
+        let history = serde_json::to_string(session.conversation.history()).unwrap();
+        assert!(history.contains("removed output"));
+        assert!(history.contains("fs_read"));
+
+        let mut session = session_with_removed_tool(&mut os, &["c"]).await;
+        assert!(session.reconcile_unavailable_tools(&os).await.unwrap());
+        let history = serde_json::to_string(session.conversation.history()).unwrap();
+        assert!(!history.contains("removed output"));
+        assert!(history.contains("Tool use was cancelled because the tool is no longer available"));
+        assert!(history.contains(crate::cli::chat::consts::DUMMY_TOOL_NAME));
+
+        let mut session = session_with_removed_tool(&mut os, &["a"]).await;
+        assert!(!session.reconcile_unavailable_tools(&os).await.unwrap());
+        let history = serde_json::to_string(session.conversation.history()).unwrap();
+        assert!(history.contains("removed output"));
+    }
+
+    #[tokio::test]
+    async fn test_switch_agent_aborted() {
+        let mut os = Os::new().await.unwrap();
+        let mut session = session_with_removed_tool(&mut os, &["a"]).await;
+        session.interactive = true;
+        session.conversation.agents.agents.insert("Other".to_string(), Agent {
+            name: "Other".to_string(),
+            ..Default::default()
+        });
+        session.inner = Some(ChatState::HandleInput {
+            input: "/agent switch Other".to_string(),
+        });
+        session.next(&mut os).await.unwrap();
+
+        assert_eq!(session.conversation.agents.active_idx, "TestAgent");
+        assert_eq!(session.conversation.unavailable_tool_uses(), vec!["removed_tool"]);
+        let history = serde_json::to_string(session.conversation.history()).unwrap();
+        assert!(history.contains("removed output"));
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_response_used() {
+        let mut os = Os::new().await.unwrap();
+        let (stdout, _) = CapturedOutput::pair();
+        let responses = serde_json::json!([["Prefetched response"], ["Response sent again"]]);
+        let mut session = mock_session(&mut os, stdout.clone(), responses).await;
+        let conv_state = session
+            .conversation
+            .as_sendable_conversation_state(&os, &mut vec![], false)
+            .await
+            .unwrap();
+        assert!(session.prefetch_enabled(&os));
+        session.prefetch_response(&os, &conv_state);
+        assert!(session.prefetch.is_some());
+
+        session.inner = Some(ChatState::HandleResponseStream(conv_state));
+        session.next(&mut os).await.unwrap();
+        assert!(session.prefetch.is_none());
+        let output = written(&stdout);
+        // The response of the prefetched request is shown, rather than one of a new request.
+        assert!(output.contains("Prefetched response"), "{output}");
+        assert!(!output.contains("Response sent again"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_response_cancelled() {
+        let mut os = Os::new().await.unwrap();
+        let responses = serde_json::json!([["Prefetched response"]]);
+        let mut session = mock_session(&mut os, std::io::stdout(), responses).await;
+        let conv_state = session
+            .conversation
+            .as_sendable_conversation_state(&os, &mut vec![], false)
+            .await
+            .unwrap();
+        session.prefetch_response(&os, &conv_state);
+        let cancel_token = session.prefetch.as_ref().unwrap().cancel_token.clone();
+
+        // The user presses ctrl + c before the prefetched response is consumed.
+        let ctrlc_tx = session.ctrlc_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            ctrlc_tx.send(()).unwrap();
+        });
+        session.inner = Some(ChatState::WaitForConnectivity);
+        session.next(&mut os).await.unwrap();
+        assert!(session.prefetch.is_none());
+        assert!(cancel_token.is_cancelled());
+
+        // Nothing is prefetched with prefetching disabled.
+        os.database
+            .settings
+            .set(Setting::ChatEnablePrefetch, false)
+            .await
+            .unwrap();
+        assert!(!session.prefetch_enabled(&os));
+    }
+
+    #[tokio::test]
+    async fn test_response_width() {
+        let mut os = Os::new().await.unwrap();
+        let (stdout, _) = CapturedOutput::pair();
+        let response = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt.";
+        let mut session = mock_session(&mut os, stdout.clone(), serde_json::json!([[response]])).await;
+        session.terminal_width_provider = || Some(200);
+        assert_eq!(session.response_width(&os), 200);
+
+        for (max, width) in [(0, 200), (300, 200), (40, 40)] {
+            os.database
+                .settings
+                .set(Setting::ChatMaxResponseWidth, max)
+                .await
+                .unwrap();
+            assert_eq!(session.response_width(&os), width);
+        }
+
+        // Responses wrap at the maximum width rather than at the width of the terminal.
+        let conv_state = session
+            .conversation
+            .as_sendable_conversation_state(&os, &mut vec![], false)
+            .await
+            .unwrap();
+        session.inner = Some(ChatState::HandleResponseStream(conv_state));
+        session.next(&mut os).await.unwrap();
+        let output = written(&stdout);
+        let lines = output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        assert!(lines.len() > 1, "{output}");
+        assert!(lines.iter().all(|line| line.chars().count() <= 40), "{output}");
+    }
+
+    #[tokio::test]
+    async fn test_stop_response() {
+        let mut os = Os::new().await.unwrap();
+        let mut session = mock_session(&mut os, std::io::stdout(), serde_json::json!([])).await;
+        let mut ctrl_c_stream = session.ctrlc_rx.resubscribe();
+
+        // Without streamed text, the session is interrupted without waiting for a second ctrl + c.
+        session.partial_response = " \n".to_string();
+        let start = std::time::Instant::now();
+        let res = session.stop_response(&mut os, &mut ctrl_c_stream).await;
+        assert!(matches!(res, Err(ChatError::Interrupted { tool_uses: None })));
+        assert!(start.elapsed() < DISCARD_RESPONSE_WINDOW);
+        assert!(session.partial_response.is_empty());
+
+        // A second ctrl + c discards the streamed text.
+        session.partial_response = "The first half".to_string();
+        session.ctrlc_tx.send(()).unwrap();
+        let res = session.stop_response(&mut os, &mut ctrl_c_stream).await;
+        assert!(matches!(res, Err(ChatError::Interrupted { tool_uses: None })));
+        assert!(session.partial_response.is_empty());
+        assert!(session.conversation.history().is_empty());
+
+        // Otherwise it is kept in the history, marked as truncated.
+        session.partial_response = "The first half  \n".to_string();
+        let res = session.stop_response(&mut os, &mut ctrl_c_stream).await;
+        assert!(matches!(res, Ok(ChatState::PromptUser { .. })));
+        assert_eq!(
+            session.conversation.last_assistant_message().unwrap().content(),
+            format!("The first half\n\n{TRUNCATED_RESPONSE_MARKER}")
+        );
+    }
+
     #[test]
     fn test_editor_content_processing() {
         // Since we no longer have template replacement, this test is simplified
@@ -3179,6 +5596,11 @@ mod tests {
     #[cfg(unix)]
     async fn test_subscribe_flow() {
         let mut os = Os::new().await.unwrap();
+        os.database
+            .settings
+            .set(Setting::ChatEnableAutoTitle, false)
+            .await
+            .unwrap();
         os.client.set_mock_output(serde_json::Value::Array(vec![]));
         let agents = get_test_agents(&os).await;
 
@@ -0,0 +1,292 @@
+//! Sizes and latencies of the requests sent during a session, used to warn about large or slow
+//! requests and shown by `/usage --network`, and the timings of its user turns.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::parser::RequestMetadata;
+
+/// Requests kept for the percentiles, starting from the most recent.
+const MAX_SAMPLES: usize = 500;
+/// Upper bounds (in bytes) of the request size buckets.
+pub const REQUEST_SIZE_BOUNDS: [u64; 6] = [10 << 10, 50 << 10, 100 << 10, 250 << 10, 500 << 10, 1 << 20];
+/// Upper bounds (in milliseconds) of the latency buckets.
+pub const LATENCY_BOUNDS: [u64; 6] = [1_000, 2_000, 5_000, 10_000, 20_000, 30_000];
+/// Time to the first chunk of a response above which the request is reported as slow.
+pub const SLOW_REQUEST_THRESHOLD: Duration = Duration::from_secs(20);
+
+/// Counts of values in buckets delimited by increasing upper bounds, the last bucket holding the
+/// values above all the bounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    bounds: &'static [u64],
+    counts: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+        }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value < *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+    }
+
+    /// Returns the buckets as `(lower bound, upper bound, count)`.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, Option<u64>, usize)> + '_ {
+        self.counts.iter().enumerate().map(|(i, count)| {
+            let lower = if i == 0 { 0 } else { self.bounds[i - 1] };
+            (lower, self.bounds.get(i).copied(), *count)
+        })
+    }
+}
+
+/// A request of the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub request_size: usize,
+    pub response_size: usize,
+    pub time_to_first_chunk: Option<Duration>,
+    pub duration: Duration,
+}
+
+/// Latencies of a user turn, from the prompt until the response without tool uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TurnTimings {
+    /// Time until the first chunk of the response to the prompt.
+    pub time_to_first_token: Option<Duration>,
+    /// Time spent sending the requests of the turn and streaming their responses.
+    pub stream_time: Duration,
+    /// Time spent running tools.
+    pub tool_time: Duration,
+}
+
+impl TurnTimings {
+    pub fn new(requests: &[RequestMetadata], tool_time: Duration) -> Self {
+        Self {
+            time_to_first_token: requests.first().and_then(|md| md.time_to_first_chunk),
+            stream_time: requests
+                .iter()
+                .map(|md| {
+                    Duration::from_millis(md.stream_end_timestamp_ms.saturating_sub(md.request_start_timestamp_ms))
+                })
+                .sum(),
+            tool_time,
+        }
+    }
+}
+
+impl std::fmt::Display for TurnTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(time_to_first_token) = self.time_to_first_token {
+            write!(f, "{:.1}s to first token • ", time_to_first_token.as_secs_f64())?;
+        }
+        write!(f, "{:.1}s streaming", self.stream_time.as_secs_f64())?;
+        if !self.tool_time.is_zero() {
+            write!(f, " • {:.1}s in tools", self.tool_time.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// Sums of the [TurnTimings] of the completed user turns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TurnTotals {
+    pub turns: u32,
+    /// Number of turns with a time to first token, and its sum.
+    first_token_turns: u32,
+    first_token_time: Duration,
+    pub stream_time: Duration,
+    pub tool_time: Duration,
+}
+
+impl TurnTotals {
+    pub fn record(&mut self, timings: &TurnTimings) {
+        self.turns += 1;
+        if let Some(time_to_first_token) = timings.time_to_first_token {
+            self.first_token_turns += 1;
+            self.first_token_time += time_to_first_token;
+        }
+        self.stream_time += timings.stream_time;
+        self.tool_time += timings.tool_time;
+    }
+
+    /// Returns the average timings of a turn.
+    pub fn average(&self) -> Option<TurnTimings> {
+        if self.turns == 0 {
+            return None;
+        }
+        Some(TurnTimings {
+            time_to_first_token: self.first_token_time.checked_div(self.first_token_turns),
+            stream_time: self.stream_time / self.turns,
+            tool_time: self.tool_time / self.turns,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NetworkStats {
+    pub requests: usize,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub request_sizes: Histogram,
+    pub first_chunk_latencies: Histogram,
+    samples: VecDeque<Sample>,
+    /// Whether the last request exceeded the size threshold, so that the warning is shown once
+    /// until the requests get smaller.
+    pub large_request_warned: bool,
+    /// Whether a slow request was reported, which is done once per session.
+    pub slow_request_warned: bool,
+    pub turns: TurnTotals,
+}
+
+impl Default for NetworkStats {
+    fn default() -> Self {
+        Self {
+            requests: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            request_sizes: Histogram::new(&REQUEST_SIZE_BOUNDS),
+            first_chunk_latencies: Histogram::new(&LATENCY_BOUNDS),
+            samples: VecDeque::new(),
+            large_request_warned: false,
+            slow_request_warned: false,
+            turns: TurnTotals::default(),
+        }
+    }
+}
+
+impl NetworkStats {
+    pub fn record(&mut self, metadata: &RequestMetadata) {
+        let sample = Sample {
+            request_size: metadata.request_size,
+            response_size: metadata.response_size,
+            time_to_first_chunk: metadata.time_to_first_chunk,
+            duration: Duration::from_millis(
+                metadata
+                    .stream_end_timestamp_ms
+                    .saturating_sub(metadata.request_start_timestamp_ms),
+            ),
+        };
+
+        self.requests += 1;
+        self.bytes_sent += sample.request_size as u64;
+        self.bytes_received += sample.response_size as u64;
+        self.request_sizes.record(sample.request_size as u64);
+        if let Some(latency) = sample.time_to_first_chunk {
+            self.first_chunk_latencies.record(latency.as_millis() as u64);
+        }
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn largest_request(&self) -> Option<usize> {
+        self.samples.iter().map(|s| s.request_size).max()
+    }
+
+    /// Returns the `p`th percentile (between 0 and 100) of the values selected from the recent
+    /// requests.
+    pub fn percentile<T: Ord + Copy>(&self, p: usize, value: impl Fn(&Sample) -> Option<T>) -> Option<T> {
+        let mut values = self.samples.iter().filter_map(value).collect::<Vec<_>>();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable();
+        let rank = (p * values.len()).div_ceil(100).clamp(1, values.len());
+        Some(values[rank - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(request_size: usize, time_to_first_chunk_ms: u64) -> RequestMetadata {
+        RequestMetadata {
+            request_size,
+            response_size: 100,
+            time_to_first_chunk: Some(Duration::from_millis(time_to_first_chunk_ms)),
+            request_start_timestamp_ms: 1_000,
+            stream_end_timestamp_ms: 1_000 + time_to_first_chunk_ms * 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(&[10, 20]);
+        for value in [0, 9, 10, 25, 100] {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.buckets().collect::<Vec<_>>(), vec![
+            (0, Some(10), 2),
+            (10, Some(20), 1),
+            (20, None, 2)
+        ]);
+    }
+
+    #[test]
+    fn test_record() {
+        let mut stats = NetworkStats::default();
+        assert_eq!(stats.percentile(50, |s| s.time_to_first_chunk), None);
+
+        for (size, latency) in [(1_000, 500), (400_000, 3_000), (20_000, 1_500), (5_000, 25_000)] {
+            stats.record(&metadata(size, latency));
+        }
+        assert_eq!(stats.requests, 4);
+        assert_eq!(stats.bytes_sent, 426_000);
+        assert_eq!(stats.bytes_received, 400);
+        assert_eq!(stats.largest_request(), Some(400_000));
+        assert_eq!(
+            stats.percentile(50, |s| s.time_to_first_chunk),
+            Some(Duration::from_millis(1_500))
+        );
+        assert_eq!(
+            stats.percentile(90, |s| Some(s.duration)),
+            Some(Duration::from_secs(50))
+        );
+        assert_eq!(
+            stats
+                .request_sizes
+                .buckets()
+                .map(|(_, _, count)| count)
+                .collect::<Vec<_>>(),
+            vec![2, 1, 0, 0, 1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_turn_timings() {
+        let timings = TurnTimings::new(&[metadata(1_000, 500), metadata(1_000, 1_500)], Duration::from_secs(2));
+        assert_eq!(timings, TurnTimings {
+            time_to_first_token: Some(Duration::from_millis(500)),
+            stream_time: Duration::from_secs(4),
+            tool_time: Duration::from_secs(2),
+        });
+        assert_eq!(
+            timings.to_string(),
+            "0.5s to first token • 4.0s streaming • 2.0s in tools"
+        );
+
+        let mut totals = TurnTotals::default();
+        assert_eq!(totals.average(), None);
+        totals.record(&timings);
+        totals.record(&TurnTimings::new(&[], Duration::ZERO));
+        assert_eq!(totals.turns, 2);
+        let average = totals.average().unwrap();
+        assert_eq!(
+            average.to_string(),
+            "0.5s to first token • 2.0s streaming • 1.0s in tools"
+        );
+    }
+}
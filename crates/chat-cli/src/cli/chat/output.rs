@@ -0,0 +1,184 @@
+use std::io::Write;
+#[cfg(any(test, feature = "test-harness"))]
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use super::acp::AcpOutput;
+use super::agent_socket::SessionEvent;
+use super::server::ServerOutput;
+use super::tui::TuiOutput;
+
+#[cfg(any(test, feature = "test-harness"))]
+/// The streams a [super::ChatSession] writes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// What was captured from a session: bytes written to a single stream, or an event of the session.
+#[cfg(any(test, feature = "test-harness"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chunk {
+    Write(Stream, Vec<u8>),
+    Event(SessionEvent),
+}
+
+/// An output stream of a [super::ChatSession]. Writes go to the terminal, to the conversation pane
+/// of `--tui`, to the clients of `q chat serve` or `q chat --acp`, or are captured in memory when
+/// the session is driven by the test harness.
+#[derive(Debug)]
+pub enum SessionOutput {
+    Stdout(std::io::Stdout),
+    Stderr(std::io::Stderr),
+    Tui(TuiOutput),
+    Server(ServerOutput),
+    Acp(AcpOutput),
+    #[cfg(any(test, feature = "test-harness"))]
+    Captured(CapturedOutput),
+}
+
+impl From<std::io::Stdout> for SessionOutput {
+    fn from(value: std::io::Stdout) -> Self {
+        Self::Stdout(value)
+    }
+}
+
+impl From<std::io::Stderr> for SessionOutput {
+    fn from(value: std::io::Stderr) -> Self {
+        Self::Stderr(value)
+    }
+}
+
+impl From<AcpOutput> for SessionOutput {
+    fn from(value: AcpOutput) -> Self {
+        Self::Acp(value)
+    }
+}
+
+impl From<ServerOutput> for SessionOutput {
+    fn from(value: ServerOutput) -> Self {
+        Self::Server(value)
+    }
+}
+
+impl From<TuiOutput> for SessionOutput {
+    fn from(value: TuiOutput) -> Self {
+        Self::Tui(value)
+    }
+}
+
+#[cfg(any(test, feature = "test-harness"))]
+impl From<CapturedOutput> for SessionOutput {
+    fn from(value: CapturedOutput) -> Self {
+        Self::Captured(value)
+    }
+}
+
+impl SessionOutput {
+    /// Returns another output writing to the same destination, for output produced outside of
+    /// the session, e.g. by the MCP servers of the [super::tool_manager::ToolManager].
+    pub fn duplicate(&self) -> Self {
+        match self {
+            Self::Stdout(_) => Self::Stdout(std::io::stdout()),
+            Self::Stderr(_) => Self::Stderr(std::io::stderr()),
+            Self::Tui(tui) => Self::Tui(tui.duplicate()),
+            Self::Server(server) => Self::Server(server.duplicate()),
+            Self::Acp(acp) => Self::Acp(acp.duplicate()),
+            #[cfg(any(test, feature = "test-harness"))]
+            Self::Captured(captured) => Self::Captured(captured.clone()),
+        }
+    }
+
+    /// Records `event` when the output is captured, so that the test harness gets it in order with
+    /// the writes around it. Other outputs only get what is written for the event.
+    #[cfg_attr(not(any(test, feature = "test-harness")), allow(clippy::unused_self))]
+    pub fn record(&self, event: &SessionEvent) {
+        #[cfg(any(test, feature = "test-harness"))]
+        if let Self::Captured(captured) = self {
+            captured.push(Chunk::Event(event.clone()));
+        }
+        #[cfg(not(any(test, feature = "test-harness")))]
+        let _ = event;
+    }
+}
+
+impl Write for SessionOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Stdout(stdout) => stdout.write(buf),
+            Self::Stderr(stderr) => stderr.write(buf),
+            Self::Tui(tui) => tui.write(buf),
+            Self::Server(server) => server.write(buf),
+            Self::Acp(acp) => acp.write(buf),
+            #[cfg(any(test, feature = "test-harness"))]
+            Self::Captured(captured) => captured.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Stdout(stdout) => stdout.flush(),
+            Self::Stderr(stderr) => stderr.flush(),
+            Self::Tui(tui) => tui.flush(),
+            Self::Server(server) => server.flush(),
+            Self::Acp(acp) => acp.flush(),
+            #[cfg(any(test, feature = "test-harness"))]
+            Self::Captured(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-harness"))]
+/// Records everything written to a stream into a log shared between streams, preserving the
+/// order in which writes to stdout and stderr were interleaved.
+#[derive(Debug, Clone)]
+pub struct CapturedOutput {
+    stream: Stream,
+    log: Arc<Mutex<Vec<Chunk>>>,
+}
+
+#[cfg(any(test, feature = "test-harness"))]
+impl CapturedOutput {
+    /// Returns a pair of stdout and stderr writers that share the same log.
+    pub fn pair() -> (Self, Self) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        (
+            Self {
+                stream: Stream::Stdout,
+                log: log.clone(),
+            },
+            Self {
+                stream: Stream::Stderr,
+                log,
+            },
+        )
+    }
+
+    /// Returns the captured writes and events, merging consecutive writes to the same stream.
+    pub fn chunks(&self) -> Vec<Chunk> {
+        self.log.lock().expect("output log lock poisoned").clone()
+    }
+
+    fn push(&self, chunk: Chunk) {
+        self.log.lock().expect("output log lock poisoned").push(chunk);
+    }
+}
+
+#[cfg(any(test, feature = "test-harness"))]
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut log = self.log.lock().expect("output log lock poisoned");
+        match log.last_mut() {
+            Some(Chunk::Write(stream, bytes)) if *stream == self.stream => bytes.extend_from_slice(buf),
+            _ => log.push(Chunk::Write(self.stream, buf.to_vec())),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::{
+    Duration,
+    Instant,
+};
+
+/// Longest delay between rendering two markdown elements, used when the response is received
+/// slowly so that text appears smoothly instead of in bursts.
+const MAX_FRAME_DELAY: Duration = Duration::from_millis(8);
+
+/// Number of received bytes waiting to be rendered from which rendering is no longer delayed, so
+/// that it catches up with the response stream.
+const CATCH_UP_BACKLOG: usize = 256;
+
+/// Paces the rendering of a streamed response.
+///
+/// The delay between rendered elements shrinks as more of the response is waiting to be rendered,
+/// and time already spent rendering counts towards it, so rendering never falls behind the stream.
+#[derive(Debug)]
+pub struct RenderPacer {
+    /// Render as fast as data arrives, see [crate::database::settings::Setting::ChatInstantRender].
+    instant: bool,
+    last_frame: Option<Instant>,
+}
+
+impl RenderPacer {
+    pub fn new(instant: bool) -> Self {
+        Self {
+            instant,
+            last_frame: None,
+        }
+    }
+
+    /// Returns how long to wait before rendering the next element, given the number of received
+    /// bytes not rendered yet.
+    fn delay(&self, backlog: usize) -> Duration {
+        if self.instant || backlog >= CATCH_UP_BACKLOG {
+            return Duration::ZERO;
+        }
+
+        let target = MAX_FRAME_DELAY.mul_f64(1.0 - backlog as f64 / CATCH_UP_BACKLOG as f64);
+        match self.last_frame {
+            Some(last_frame) => target.saturating_sub(last_frame.elapsed()),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Waits until the next element should be rendered.
+    pub async fn pace(&mut self, backlog: usize) {
+        let delay = self.delay(backlog);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        self.last_frame = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let mut pacer = RenderPacer::new(false);
+        assert_eq!(pacer.delay(0), Duration::ZERO);
+
+        pacer.last_frame = Some(Instant::now());
+        assert!(pacer.delay(0) > Duration::from_millis(4));
+        assert!(pacer.delay(0) <= MAX_FRAME_DELAY);
+        assert!(pacer.delay(200) < pacer.delay(10));
+        assert_eq!(pacer.delay(CATCH_UP_BACKLOG), Duration::ZERO);
+
+        pacer.last_frame = Some(Instant::now() - MAX_FRAME_DELAY);
+        assert_eq!(pacer.delay(0), Duration::ZERO);
+
+        let mut pacer = RenderPacer::new(true);
+        pacer.last_frame = Some(Instant::now());
+        assert_eq!(pacer.delay(0), Duration::ZERO);
+    }
+}
@@ -31,6 +31,7 @@ use winnow::combinator::{
 use winnow::error::{
     ErrMode,
     ErrorKind,
+    Needed,
     ParserError,
 };
 use winnow::prelude::*;
@@ -50,8 +51,14 @@ const HEADING_COLOR: Color = Color::Magenta;
 const BLOCKQUOTE_COLOR: Color = Color::DarkGrey;
 const URL_TEXT_COLOR: Color = Color::Blue;
 const URL_LINK_COLOR: Color = Color::DarkGrey;
+const TABLE_BORDER_COLOR: Color = Color::DarkGrey;
+const MATH_COLOR: Color = Color::Cyan;
 
 const DEFAULT_RULE_WIDTH: usize = 40;
+/// Columns of a table are not shrunk below this width to fit the terminal.
+const MIN_TABLE_COLUMN_WIDTH: usize = 3;
+/// Markers of bulleted items by nesting level.
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error<'a> {
@@ -90,6 +97,16 @@ pub struct ParseState {
     pub set_newline: bool,
     pub newline: bool,
     pub citations: Vec<(String, String)>,
+    /// Whether the whole response was received, so that blocks such as tables do not wait for
+    /// more input.
+    pub stream_ended: bool,
+    /// Nesting level of the block quote of the current line, repeated on wrapped lines.
+    pub quote_level: usize,
+    /// Width of the prefix of the list item of the current line, by which wrapped lines are
+    /// indented.
+    pub hanging_indent: usize,
+    /// Whether LaTeX math such as `$x^2$` is rendered as unicode, rather than left as written.
+    pub render_math: bool,
 }
 
 impl ParseState {
@@ -104,6 +121,10 @@ impl ParseState {
             set_newline: false,
             newline: true,
             citations: vec![],
+            stream_ended: false,
+            quote_level: 0,
+            hanging_indent: 0,
+            render_math: true,
         }
     }
 }
@@ -142,6 +163,7 @@ pub fn interpret_markdown<'a, 'b>(
                 // More importantly, it's needed to support manual wordwrapping
                 text,
                 // multiline patterns
+                table,
                 blockquote,
                 // linted_codeblock,
                 codeblock_begin,
@@ -152,6 +174,7 @@ pub fn interpret_markdown<'a, 'b>(
                 numbered_item,
                 // inline patterns
                 code,
+                math,
                 citation,
                 url,
                 bold,
@@ -226,10 +249,12 @@ fn bulleted_item<'a, 'b>(
             return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
         }
 
-        let ws = (space0, alt(("-", "*")), space1).parse_next(i)?.0;
-        let print = format!("{ws}• ");
+        let ws = (space0, alt(("-", "*", "+")), space1).parse_next(i)?.0;
+        let indent = list_indent(ws);
+        let print = format!("{indent}{} ", BULLETS[(indent.len() / 2) % BULLETS.len()]);
 
         queue_newline_or_advance(&mut o, state, print.width())?;
+        state.hanging_indent = print.width();
         queue(&mut o, style::Print(print))
     }
 }
@@ -243,10 +268,11 @@ fn numbered_item<'a, 'b>(
             return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
         }
 
-        let (ws, digits, _, _) = (space0, digit1, ".", space1).parse_next(i)?;
-        let print = format!("{ws}{digits}. ");
+        let (ws, digits, delimiter, _) = (space0, digit1, alt((".", ")")), space1).parse_next(i)?;
+        let print = format!("{}{digits}{delimiter} ", list_indent(ws));
 
         queue_newline_or_advance(&mut o, state, print.width())?;
+        state.hanging_indent = print.width();
         queue(&mut o, style::Print(print))
     }
 }
@@ -268,6 +294,8 @@ fn horizontal_rule<'a, 'b>(
 
         state.column = 0;
         state.set_newline = true;
+        state.quote_level = 0;
+        state.hanging_indent = 0;
 
         let rule_width = state.terminal_width.unwrap_or(DEFAULT_RULE_WIDTH);
         queue(&mut o, style::Print(format!("{}\n", "━".repeat(rule_width))))
@@ -290,6 +318,67 @@ fn code<'a, 'b>(
     }
 }
 
+/// Renders inline math delimited by `$`, `$$`, `\(` and `\)`, or `\[` and `\]`. As `$` is
+/// also used for amounts of money, it only delimits math when not followed by a space when
+/// opening, and not preceded by a space or followed by a digit when closing.
+fn math<'a, 'b>(
+    mut o: impl Write + 'b,
+    state: &'b mut ParseState,
+) -> impl FnMut(&mut Partial<&'a str>) -> PResult<(), Error<'a>> + 'b {
+    move |i| {
+        if !state.render_math {
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+        }
+
+        let open = alt(("$$", "$", "\\(", "\\[")).parse_next(i)?;
+        let (close, multiline) = match open {
+            "$$" => ("$$", true),
+            "\\(" => ("\\)", false),
+            "\\[" => ("\\]", true),
+            _ => ("$", false),
+        };
+
+        // Math ends on the same line, or before the end of the paragraph for display math.
+        let input: &'a str = **i;
+        let limit = match multiline {
+            true => input.find("\n\n"),
+            false => input.find('\n'),
+        };
+        let Some(end) = input[..limit.unwrap_or(input.len())].find(close) else {
+            if limit.is_none() && !state.stream_ended {
+                return Err(ErrMode::Incomplete(Needed::Unknown));
+            }
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+        };
+        let source = &input[..end];
+        if source.trim().is_empty() {
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+        }
+        if open == "$" {
+            let following = input[end + close.len()..].chars().next();
+            if source.starts_with(char::is_whitespace)
+                || source.ends_with(char::is_whitespace)
+                || following.is_some_and(|c| c.is_ascii_digit())
+            {
+                return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+            }
+        }
+        i.next_slice(end + close.len());
+
+        let source = source
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&amp;", "&");
+        let out = super::math::to_unicode(source.trim());
+
+        queue_newline_or_advance(&mut o, state, out.width())?;
+        queue(&mut o, style::SetForegroundColor(MATH_COLOR))?;
+        queue(&mut o, style::Print(out))?;
+        queue(&mut o, style::ResetColor)
+    }
+}
+
 fn blockquote<'a, 'b>(
     mut o: impl Write + 'b,
     state: &'b mut ParseState,
@@ -306,6 +395,9 @@ fn blockquote<'a, 'b>(
 
         queue(&mut o, style::SetForegroundColor(BLOCKQUOTE_COLOR))?;
         queue_newline_or_advance(&mut o, state, print.width())?;
+        state.quote_level = level;
+        // The content of the quote may start with a heading or list item.
+        state.set_newline = true;
         queue(&mut o, style::Print(print))
     }
 }
@@ -491,6 +583,8 @@ fn line_ending<'a, 'b>(
 
         state.column = 0;
         state.set_newline = true;
+        state.quote_level = 0;
+        state.hanging_indent = 0;
 
         queue(&mut o, style::ResetColor)?;
         queue(&mut o, style::SetAttribute(style::Attribute::Reset))?;
@@ -522,8 +616,11 @@ fn queue_newline_or_advance<'a, 'b>(
 ) -> Result<(), ErrMode<Error<'a>>> {
     if let Some(terminal_width) = state.terminal_width {
         if state.column > 0 && state.column + width > terminal_width {
-            state.column = width;
+            // Continue the quote and list item of the wrapped line.
+            let prefix = format!("{}{}", "│ ".repeat(state.quote_level), " ".repeat(state.hanging_indent));
+            state.column = prefix.width() + width;
             queue(&mut o, style::Print('\n'))?;
+            queue(&mut o, style::Print(prefix))?;
             return Ok(());
         }
     }
@@ -534,6 +631,212 @@ fn queue_newline_or_advance<'a, 'b>(
     Ok(())
 }
 
+/// Returns the indentation of a list item with tabs expanded, so that nested items line up.
+fn list_indent(ws: &str) -> String {
+    ws.replace('\t', "    ")
+}
+
+/// Column alignment of a table, given by its delimiter row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// Renders a GFM table once all of its rows were received. Columns are aligned as given by the
+/// delimiter row, and shrunk with their cells wrapped when the table is wider than the terminal.
+fn table<'a, 'b>(
+    mut o: impl Write + 'b,
+    state: &'b mut ParseState,
+) -> impl FnMut(&mut Partial<&'a str>) -> PResult<(), Error<'a>> + 'b {
+    move |i| {
+        if !state.newline {
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+        }
+
+        let input: &'a str = **i;
+        if input.trim_start_matches(' ').is_empty() {
+            return Err(ErrMode::Incomplete(Needed::Unknown));
+        }
+
+        let mut rows = Vec::new();
+        let mut alignments = Vec::new();
+        let mut consumed = 0;
+        loop {
+            let rest = &input[consumed..];
+            if !rest.trim_start_matches(' ').starts_with('|') {
+                if rest.is_empty() && !state.stream_ended {
+                    return Err(ErrMode::Incomplete(Needed::Unknown));
+                }
+                if rows.len() < 2 {
+                    return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+                }
+                break;
+            }
+
+            let (line, len) = match rest.find('\n') {
+                Some(end) => (&rest[..end], end + 1),
+                None if state.stream_ended => (rest, rest.len()),
+                None => return Err(ErrMode::Incomplete(Needed::Unknown)),
+            };
+            let cells = table_cells(line);
+            if rows.len() == 1 {
+                match table_alignments(&cells) {
+                    Some(parsed) => alignments = parsed,
+                    None => return Err(ErrMode::from_error_kind(i, ErrorKind::Fail)),
+                }
+            }
+            rows.push(cells);
+            consumed += len;
+        }
+
+        rows.remove(1);
+        let columns = alignments.len();
+        for row in &mut rows {
+            row.resize(columns, String::new());
+        }
+        let widths = table_column_widths(&rows, state.terminal_width);
+
+        for (index, row) in rows.iter().enumerate() {
+            let wrapped = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| wrap_cell(cell, *width))
+                .collect::<Vec<_>>();
+            let height = wrapped.iter().map(Vec::len).max().unwrap_or(1);
+            for line in 0..height {
+                for (column, cell) in wrapped.iter().enumerate() {
+                    if column > 0 {
+                        queue(&mut o, style::SetForegroundColor(TABLE_BORDER_COLOR))?;
+                        queue(&mut o, style::Print(" │ "))?;
+                        queue(&mut o, style::ResetColor)?;
+                    }
+                    let text = cell.get(line).map_or("", String::as_str);
+                    let padding = widths[column].saturating_sub(text.width());
+                    let (before, after) = match alignments.get(column).copied().unwrap_or(Alignment::Left) {
+                        Alignment::Left => (0, padding),
+                        Alignment::Center => (padding / 2, padding - padding / 2),
+                        Alignment::Right => (padding, 0),
+                    };
+                    queue(&mut o, style::Print(" ".repeat(before)))?;
+                    if index == 0 {
+                        queue(&mut o, style::SetAttribute(Attribute::Bold))?;
+                        queue(&mut o, style::Print(text))?;
+                        queue(&mut o, style::SetAttribute(Attribute::NormalIntensity))?;
+                    } else {
+                        queue(&mut o, style::Print(text))?;
+                    }
+                    // Trailing spaces of the last column are not needed for alignment.
+                    if column + 1 < columns {
+                        queue(&mut o, style::Print(" ".repeat(after)))?;
+                    }
+                }
+                queue(&mut o, style::Print("\n"))?;
+            }
+
+            if index == 0 {
+                let rule = widths.iter().map(|width| "─".repeat(*width)).collect::<Vec<_>>();
+                queue(&mut o, style::SetForegroundColor(TABLE_BORDER_COLOR))?;
+                queue(&mut o, style::Print(format!("{}\n", rule.join("─┼─"))))?;
+                queue(&mut o, style::ResetColor)?;
+            }
+        }
+
+        i.next_slice(consumed);
+        state.column = 0;
+        state.set_newline = true;
+        Ok(())
+    }
+}
+
+/// Returns the cells of a table row, with escaped pipes and inline markup removed.
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.replace("\\|", "\u{0}")
+        .split('|')
+        .map(|cell| {
+            cell.trim()
+                .replace('\u{0}', "|")
+                .replace("**", "")
+                .replace("__", "")
+                .replace('`', "")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
+
+/// Returns the alignment of each column if `cells` is the delimiter row of a table, e.g.
+/// `|:---|:---:|---:|`.
+fn table_alignments(cells: &[String]) -> Option<Vec<Alignment>> {
+    cells
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Alignment::Center,
+                (false, true) => Alignment::Right,
+                _ => Alignment::Left,
+            })
+        })
+        .collect()
+}
+
+/// Returns the width of each column, shrinking the widest columns until the table fits
+/// `terminal_width`.
+fn table_column_widths(rows: &[Vec<String>], terminal_width: Option<usize>) -> Vec<usize> {
+    let columns = rows.first().map_or(0, Vec::len);
+    let mut widths = (0..columns)
+        .map(|column| rows.iter().map(|row| row[column].width()).max().unwrap_or(0).max(1))
+        .collect::<Vec<_>>();
+
+    if let Some(terminal_width) = terminal_width {
+        let available = terminal_width.saturating_sub(3 * columns.saturating_sub(1));
+        while widths.iter().sum::<usize>() > available {
+            let Some(widest) = widths.iter_mut().filter(|w| **w > MIN_TABLE_COLUMN_WIDTH).max() else {
+                break;
+            };
+            *widest -= 1;
+        }
+    }
+
+    widths
+}
+
+/// Wraps the text of a cell into lines of at most `width` columns, breaking words that do not
+/// fit on a line of their own.
+fn wrap_cell(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in text.split_whitespace() {
+        let line = lines.last_mut().expect("lines is not empty");
+        if !line.is_empty() && line.width() + 1 + word.width() <= width {
+            line.push(' ');
+            line.push_str(word);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(String::new());
+        }
+        for c in word.chars() {
+            let line = lines.last_mut().expect("lines is not empty");
+            if line.width() + c.width().unwrap_or(0) > width && !line.is_empty() {
+                lines.push(c.to_string());
+            } else {
+                line.push(c);
+            }
+        }
+    }
+    lines
+}
+
 fn queue<'a>(mut o: impl Write, command: impl Command) -> Result<(), ErrMode<Error<'a>>> {
     use crossterm::QueueableCommand;
     o.queue(command).map_err(|err| ErrMode::Cut(Error::Stdio(err)))?;
@@ -637,6 +940,53 @@ fn codeblock_fallback<'a, 'b>(
     }
 }
 
+/// A fenced code block of a markdown document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The language given after the opening fence, if any.
+    pub language: Option<String>,
+    pub code: String,
+}
+
+/// Returns the fenced code blocks of `markdown` in order of appearance, following the same rules
+/// as [interpret_markdown]: a block opens with ```` ``` ```` at the start of a line and closes at
+/// the next ```` ``` ````. An unterminated block extends to the end of the document.
+pub fn code_blocks(markdown: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut rest = markdown;
+    let mut at_line_start = true;
+
+    while !rest.is_empty() {
+        if at_line_start && rest.starts_with("```") {
+            let Some((language, body)) = rest[3..].split_once('\n') else {
+                break;
+            };
+            let (code, remaining) = match body.find("```") {
+                Some(end) => (&body[..end], &body[end + 3..]),
+                None => (body, ""),
+            };
+            let language = language.trim();
+            blocks.push(CodeBlock {
+                language: (!language.is_empty()).then(|| language.to_string()),
+                code: code.strip_suffix('\n').unwrap_or(code).to_string(),
+            });
+            rest = remaining;
+            at_line_start = false;
+            continue;
+        }
+
+        match rest.find('\n') {
+            Some(newline) => {
+                rest = &rest[newline + 1..];
+                at_line_start = true;
+            },
+            None => break,
+        }
+    }
+
+    blocks
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
@@ -656,6 +1006,8 @@ mod tests {
                 input.push(' ');
 
                 let mut state = ParseState::new(Some(80));
+                // The whole input is available at once.
+                state.stream_ended = true;
                 let mut presult = vec![];
                 let mut offset = 0;
 
@@ -759,4 +1111,113 @@ mod tests {
     validate!(square_bracket_url_like_2, "[text](without url part", [style::Print(
         "[text](without url part"
     )]);
+
+    /// Renders `markdown` as a complete response, returning the output without styling.
+    fn render(markdown: &str, terminal_width: usize) -> String {
+        let mut input = markdown.to_owned();
+        input.push('\n');
+
+        let mut state = ParseState::new(Some(terminal_width));
+        state.stream_ended = true;
+        let mut output = vec![];
+        let mut offset = 0;
+        loop {
+            let partial = Partial::new(&input[offset..]);
+            match interpret_markdown(partial, &mut output, &mut state) {
+                Ok(parsed) => {
+                    offset += parsed.offset_from(&partial);
+                    state.newline = state.set_newline;
+                    state.set_newline = false;
+                },
+                Err(err) => match err.into_inner() {
+                    Some(err) => panic!("{err}"),
+                    None => break,
+                },
+            }
+        }
+
+        String::from_utf8(strip_ansi_escapes::strip(output)).unwrap()
+    }
+
+    #[test]
+    fn test_table() {
+        let markdown = "| Name | Count | Status |\n|:-----|:-----:|-------:|\n| a | 1 | ok |\n| **bb** | 10 | failed |";
+        assert_eq!(
+            render(markdown, 80),
+            "Name │ Count │ Status\n─────┼───────┼───────\na    │   1   │     ok\nbb   │  10   │ failed\n"
+        );
+
+        // Cells are wrapped when the table is wider than the terminal.
+        let markdown = "| Key | Description |\n|---|---|\n| x | a long description |";
+        assert_eq!(
+            render(markdown, 16),
+            "Key │ Descriptio\n    │ n\n────┼───────────\nx   │ a long\n    │ descriptio\n    │ n\n"
+        );
+    }
+
+    #[test]
+    fn test_table_incomplete() {
+        // A table is not rendered until all of its rows were received.
+        let mut state = ParseState::new(Some(80));
+        let input = "| a | b |\n|---|---|\n| 1 | 2 |\n";
+        let mut output = vec![];
+        let err = interpret_markdown(Partial::new(input), &mut output, &mut state).unwrap_err();
+        assert!(err.is_incomplete());
+
+        // A line starting with a pipe is not a table without a delimiter row.
+        assert_eq!(render("| not a table", 80), "| not a table\n");
+    }
+
+    #[test]
+    fn test_nested_lists() {
+        assert_eq!(
+            render("- one\n  - two\n    - three\n1. first\n   2) second", 80),
+            "• one\n  ◦ two\n    ▪ three\n1. first\n   2) second\n"
+        );
+
+        // Wrapped lines are indented under the text of the item.
+        assert_eq!(render("- aaaa bbbb cccc", 12), "• aaaa bbbb \n  cccc\n");
+    }
+
+    #[test]
+    fn test_quoted_list() {
+        assert_eq!(render("> - one\n> - two", 80), "│ • one\n│ • two\n");
+        assert_eq!(render("> aaaa bbbb cccc", 12), "│ aaaa bbbb \n│ cccc\n");
+    }
+
+    #[test]
+    fn test_math() {
+        assert_eq!(render("area is $\\pi r^2$ here", 80), "area is π r² here\n");
+        assert_eq!(render("so \\(\\frac{1}{2}\\) and \\[x_1\\]", 80), "so ½ and x₁\n");
+        assert_eq!(render("$$\\alpha \\leq \\beta$$", 80), "α ≤ β\n");
+        // Amounts of money are not math.
+        assert_eq!(render("costs $5 and $10 total", 80), "costs $5 and $10 total\n");
+        assert_eq!(render("from $a$5", 80), "from $a$5\n");
+
+        let mut state = ParseState::new(Some(80));
+        state.render_math = false;
+        let mut output = vec![];
+        interpret_markdown(Partial::new("$x^2$ "), &mut output, &mut state).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "$");
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        let markdown = "Here you go:\n```rust\nfn main() {}\n```\nand inline ```not a block``` text\n```\necho hi\necho bye\n```\n```py\nprint(1)";
+        assert_eq!(code_blocks(markdown), vec![
+            CodeBlock {
+                language: Some("rust".to_string()),
+                code: "fn main() {}".to_string(),
+            },
+            CodeBlock {
+                language: None,
+                code: "echo hi\necho bye".to_string(),
+            },
+            CodeBlock {
+                language: Some("py".to_string()),
+                code: "print(1)".to_string(),
+            },
+        ]);
+        assert!(code_blocks("no code here").is_empty());
+    }
 }
@@ -184,6 +184,8 @@ impl SendMessageStream {
     /// * `request_metadata_lock` - a mutex that will be updated with metadata about the consumed
     ///   response stream on stream completion (ie, [ResponseEvent::EndStream] is returned) or on
     ///   drop.
+    /// * `cancel_token` - cancels the request when it is still waiting for the response, or closes
+    ///   the response stream, updating `request_metadata_lock` either way.
     ///
     /// # Details
     ///
@@ -201,46 +203,59 @@ impl SendMessageStream {
         conversation_state: ConversationState,
         request_metadata_lock: Arc<Mutex<Option<RequestMetadata>>>,
         message_meta_tags: Option<Vec<MessageMetaTag>>,
+        cancel_token: CancellationToken,
     ) -> Result<Self, SendMessageError> {
         let message_id = uuid::Uuid::new_v4().to_string();
         info!(?message_id, "Generated new message id");
         let user_prompt_length = conversation_state.user_input_message.content.len();
+        let request_size = conversation_state.payload_size();
         let model_id = conversation_state.user_input_message.model_id.clone();
         let message_meta_tags = message_meta_tags.unwrap_or_default();
 
-        let cancel_token = CancellationToken::new();
-        let cancel_token_clone = cancel_token.clone();
-
         let start_time = Instant::now();
         let start_time_sys = SystemTime::now();
-        debug!(?start_time, "sending send_message request");
-        let response = client
-            .send_message(conversation_state)
-            .await
-            .map_err(|err| SendMessageError {
-                source: err,
-                request_metadata: RequestMetadata {
+        debug!(?start_time, request_size, "sending send_message request");
+        let response = tokio::select! {
+            res = client.send_message(conversation_state) => res,
+            _ = cancel_token.cancelled() => Err(ApiClientError::Cancelled),
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                let request_metadata = RequestMetadata {
                     message_id: message_id.clone(),
                     request_start_timestamp_ms: system_time_to_unix_ms(start_time_sys),
                     stream_end_timestamp_ms: system_time_to_unix_ms(SystemTime::now()),
                     model_id: model_id.clone(),
                     user_prompt_length,
+                    request_size,
                     message_meta_tags: message_meta_tags.clone(),
                     // Other fields are irrelevant if we can't get a successful response
                     ..Default::default()
-                },
-            })?;
+                };
+                if matches!(err, ApiClientError::Cancelled) {
+                    debug!("send_message was cancelled");
+                    *request_metadata_lock.lock().await = Some(request_metadata.clone());
+                }
+                return Err(SendMessageError {
+                    source: err,
+                    request_metadata,
+                });
+            },
+        };
         let elapsed = start_time.elapsed();
         debug!(?elapsed, "send_message succeeded");
 
         let request_id = response.request_id().map(str::to_string);
         let (ev_tx, ev_rx) = mpsc::channel(16);
+        let cancel_token_clone = cancel_token.clone();
         tokio::spawn(async move {
             ResponseParser::new(
                 response,
                 message_id,
                 model_id,
                 user_prompt_length,
+                request_size,
                 message_meta_tags,
                 ev_tx,
                 start_time,
@@ -302,6 +317,8 @@ struct ResponseParser {
     model_id: Option<String>,
     /// Length of the user prompt for the initial request.
     user_prompt_length: usize,
+    /// Estimated size (in bytes) of the request payload.
+    request_size: usize,
     /// Meta tags for the initial request.
     message_meta_tags: Vec<MessageMetaTag>,
     /// Time immediately before sending the request.
@@ -321,6 +338,7 @@ impl ResponseParser {
         message_id: String,
         model_id: Option<String>,
         user_prompt_length: usize,
+        request_size: usize,
         message_meta_tags: Vec<MessageMetaTag>,
         event_tx: mpsc::Sender<Result<ResponseEvent, RecvError>>,
         request_start_time: Instant,
@@ -333,6 +351,7 @@ impl ResponseParser {
             message_id,
             model_id,
             user_prompt_length,
+            request_size,
             message_meta_tags,
             ended: false,
             event_tx,
@@ -359,16 +378,20 @@ impl ResponseParser {
 
             let cancel_token = self.cancel_token.clone();
             tokio::select! {
-                res = self.recv() => {
-                    let _ = self.event_tx.send(res).await.map_err(|err| error!(?err, "failed to send event to channel"));
-                },
+                // Stop as soon as cancelled, even with the rest of the response ready.
+                biased;
                 _ = cancel_token.cancelled() => {
                     debug!("response parser was cancelled");
                     let err = self.error(RecvErrorKind::Cancelled);
+                    // Stop receiving the response rather than leaving it to stream in the background.
+                    self.response.close();
                     *self.request_metadata.lock().await = Some(err.request_metadata.clone());
                     let _ = self.event_tx.send(Err(err)).await.map_err(|err| error!(?err, "failed to send error to channel"));
                     return;
                 },
+                res = self.recv() => {
+                    let _ = self.event_tx.send(res).await.map_err(|err| error!(?err, "failed to send event to channel"));
+                },
             }
         }
     }
@@ -608,6 +631,7 @@ impl ResponseParser {
             // here.
             stream_end_timestamp_ms: system_time_to_unix_ms(SystemTime::now()),
             user_prompt_length: self.user_prompt_length,
+            request_size: self.request_size,
             message_meta_tags: self.message_meta_tags.clone(),
             tool_use_ids_and_names: self
                 .tool_uses
@@ -656,6 +680,10 @@ pub struct RequestMetadata {
     pub time_between_chunks: Vec<Duration>,
     /// Total size (in bytes) of the user prompt associated with the request.
     pub user_prompt_length: usize,
+    /// Estimated size (in bytes) of the request payload, see
+    /// [crate::api_client::model::ConversationState::payload_size].
+    #[serde(default)]
+    pub request_size: usize,
     /// Total size (in bytes) of the response.
     pub response_size: usize,
     /// [ChatConversationType] for the returned assistant message.
@@ -733,6 +761,7 @@ mod tests {
             "".to_string(),
             None,
             1,
+            1,
             vec![],
             mpsc::channel(32).0,
             Instant::now(),
@@ -745,4 +774,39 @@ mod tests {
             println!("{:?}", parser.recv().await.unwrap());
         }
     }
+
+    #[tokio::test]
+    async fn test_response_parser_cancelled() {
+        let events = vec![ChatResponseStream::AssistantResponseEvent {
+            content: "hello".to_string(),
+        }];
+        let (event_tx, mut event_rx) = mpsc::channel(32);
+        let cancel_token = CancellationToken::new();
+        let request_metadata = Arc::new(Mutex::new(None));
+        let mut parser = ResponseParser::new(
+            SendMessageOutput::Mock(events),
+            "".to_string(),
+            None,
+            1,
+            1,
+            vec![],
+            event_tx,
+            Instant::now(),
+            SystemTime::now(),
+            cancel_token.clone(),
+            Arc::clone(&request_metadata),
+        );
+
+        cancel_token.cancel();
+        parser.try_recv().await;
+        assert!(matches!(
+            event_rx.recv().await,
+            Some(Err(RecvError {
+                source: RecvErrorKind::Cancelled,
+                ..
+            }))
+        ));
+        assert!(request_metadata.lock().await.is_some());
+        assert!(matches!(&parser.response, SendMessageOutput::Mock(events) if events.is_empty()));
+    }
 }
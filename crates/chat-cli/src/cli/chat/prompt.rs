@@ -1,4 +1,9 @@
 use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::sync::{
+    Arc,
+    Mutex,
+};
 
 use eyre::Result;
 use rustyline::completion::{
@@ -19,6 +24,7 @@ use rustyline::validate::{
     Validator,
 };
 use rustyline::{
+    Behavior,
     Cmd,
     Completer,
     CompletionType,
@@ -35,6 +41,11 @@ use rustyline::{
 };
 use winnow::stream::AsChar;
 
+use super::keymap::{
+    KeyAction,
+    Keymap,
+    SubmitLine,
+};
 pub use super::prompt_parser::generate_prompt;
 use super::prompt_parser::parse_prompt_components;
 use crate::database::settings::Setting;
@@ -45,12 +56,15 @@ pub const COMMANDS: &[&str] = &[
     "/help",
     "/editor",
     "/issue",
+    "/feedback up",
+    "/feedback down",
     "/quit",
     "/tools",
     "/tools trust",
     "/tools untrust",
     "/tools trust-all",
     "/tools reset",
+    "/tools stats",
     "/mcp",
     "/model",
     "/agent",
@@ -59,8 +73,9 @@ pub const COMMANDS: &[&str] = &[
     "/agent create",
     "/agent delete",
     "/agent rename",
-    "/agent set",
+    "/agent switch",
     "/agent schema",
+    "/init",
     "/prompts",
     "/context",
     "/context help",
@@ -69,6 +84,14 @@ pub const COMMANDS: &[&str] = &[
     "/context add",
     "/context rm",
     "/context clear",
+    "/context add-root",
+    "/context remove-root",
+    "/context add-cmd",
+    "/context remove-cmd",
+    "/context auto on",
+    "/context auto off",
+    "/memory show",
+    "/memory edit",
     "/hooks",
     "/hooks help",
     "/hooks add",
@@ -79,10 +102,31 @@ pub const COMMANDS: &[&str] = &[
     "/hooks disable-all",
     "/compact",
     "/compact help",
+    "/copy",
+    "/apply",
+    "/ask",
+    "/ask-file",
+    "/paste",
+    "/pin",
+    "/pin list",
+    "/pin remove",
+    "/redact",
+    "/redact last",
     "/usage",
     "/save",
     "/load",
     "/subscribe",
+    "/alias",
+    "/alias list",
+    "/alias rm",
+    "/plan",
+    "/plan clear",
+    "/status",
+    "/settings",
+    "/memstats",
+    "/trash",
+    "/trash list",
+    "/trash restore",
 ];
 
 /// Complete commands that start with a slash
@@ -97,6 +141,55 @@ fn complete_command(word: &str, start: usize) -> (usize, Vec<String>) {
     )
 }
 
+/// Complete the subcommands and flags of a slash command, e.g. `/tools tr` to `/tools trust`
+fn complete_subcommand(line: &str, pos: usize, start: usize) -> Option<(usize, Vec<String>)> {
+    let typed = &line[..pos];
+    if !typed.starts_with('/') || !typed.contains(' ') {
+        return None;
+    }
+    let mut completions = COMMANDS
+        .iter()
+        .filter(|command| command.len() > typed.len() && command.starts_with(typed))
+        // Only the word being typed is completed, not the words that follow it
+        .map(|command| command[start..].split(' ').next().unwrap_or_default().to_owned())
+        .collect::<Vec<_>>();
+    completions.dedup();
+    (!completions.is_empty()).then_some((start, completions))
+}
+
+/// Complete tool names, as the arguments of `/tools trust` and `/tools untrust`
+fn complete_tool_name(line: &str, word: &str, start: usize, tool_names: &[String]) -> Option<(usize, Vec<String>)> {
+    if !line.starts_with("/tools trust ") && !line.starts_with("/tools untrust ") {
+        return None;
+    }
+    Some((
+        start,
+        tool_names
+            .iter()
+            .filter(|name| name.starts_with(word))
+            .cloned()
+            .collect(),
+    ))
+}
+
+/// Complete the keys of settings, as the first argument of `/settings`
+fn complete_setting(line: &str, word: &str, start: usize) -> Option<(usize, Vec<String>)> {
+    let args = line.strip_prefix("/settings ")?;
+    if args[..start - "/settings ".len()].trim().is_empty() {
+        Some((
+            start,
+            Setting::ALL
+                .iter()
+                .map(|setting| setting.as_ref())
+                .filter(|key| key.starts_with(word))
+                .map(str::to_owned)
+                .collect(),
+        ))
+    } else {
+        None
+    }
+}
+
 /// A wrapper around FilenameCompleter that provides enhanced path detection
 /// and completion capabilities for the chat interface.
 pub struct PathCompleter {
@@ -163,6 +256,8 @@ impl PromptCompleter {
 pub struct ChatCompleter {
     path_completer: PathCompleter,
     prompt_completer: PromptCompleter,
+    /// Names of the tools that can be trusted, see [ChatHelper::set_tool_names].
+    tool_names: Vec<String>,
 }
 
 impl ChatCompleter {
@@ -170,6 +265,7 @@ impl ChatCompleter {
         Self {
             path_completer: PathCompleter::new(),
             prompt_completer: PromptCompleter::new(sender, receiver),
+            tool_names: Vec::new(),
         }
     }
 }
@@ -185,11 +281,23 @@ impl Completer for ChatCompleter {
     ) -> Result<(usize, Vec<Self::Candidate>), ReadlineError> {
         let (start, word) = extract_word(line, pos, None, |c| c.is_space());
 
-        // Handle command completion
-        if word.starts_with('/') {
+        // Handle command completion. Later words starting with a slash are absolute paths.
+        if word.starts_with('/') && start == 0 {
             return Ok(complete_command(word, start));
         }
 
+        if let Some(completions) = complete_subcommand(line, pos, start) {
+            return Ok(completions);
+        }
+
+        if let Some(completions) = complete_setting(line, word, start) {
+            return Ok(completions);
+        }
+
+        if let Some(completions) = complete_tool_name(line, word, start, &self.tool_names) {
+            return Ok(completions);
+        }
+
         if line.starts_with('@') {
             let search_word = line.strip_prefix('@').unwrap_or("");
             if let Ok(completions) = self.prompt_completer.complete_prompt(search_word) {
@@ -311,6 +419,8 @@ pub struct ChatHelper {
     #[rustyline(Hinter)]
     hinter: ChatHinter,
     validator: MultiLineValidator,
+    /// Line submitted by a key of the [Keymap], see [SubmitLine].
+    submitted: Arc<Mutex<Option<String>>>,
 }
 
 impl ChatHelper {
@@ -318,6 +428,21 @@ impl ChatHelper {
     pub fn update_hinter_history(&mut self, command: &str) {
         self.hinter.update_history(command);
     }
+
+    /// Sets the tool names completed as the arguments of `/tools trust` and `/tools untrust`.
+    pub fn set_tool_names(&mut self, tool_names: Vec<String>) {
+        self.completer.tool_names = tool_names;
+    }
+
+    /// Returns where the keys of the [Keymap] leave the line they submit.
+    pub fn submitted(&self) -> Arc<Mutex<Option<String>>> {
+        Arc::clone(&self.submitted)
+    }
+
+    /// Takes the line submitted by a key of the [Keymap] since the last call, if any.
+    pub fn take_submitted(&self) -> Option<String> {
+        self.submitted.lock().ok()?.take()
+    }
 }
 
 impl Validator for ChatHelper {
@@ -368,19 +493,36 @@ impl Highlighter for ChatHelper {
     }
 }
 
+/// Returns the key bindings of the prompt, set with the `chat.editMode` setting.
+pub fn edit_mode(os: &Os) -> EditMode {
+    match os.database.settings.get_string(Setting::ChatEditMode).as_deref() {
+        Some("vi" | "vim") => EditMode::Vi,
+        _ => EditMode::Emacs,
+    }
+}
+
+/// Returns where the prompt is drawn. When stdout is piped but input comes from the terminal, the
+/// prompt is drawn on the terminal itself so that it does not end up in the piped output.
+fn behavior(stdin_is_terminal: bool, stdout_is_terminal: bool) -> Behavior {
+    match (stdin_is_terminal, stdout_is_terminal) {
+        (true, false) => Behavior::PreferTerm,
+        _ => Behavior::Stdio,
+    }
+}
+
 pub fn rl(
     os: &Os,
     sender: std::sync::mpsc::Sender<Option<String>>,
     receiver: std::sync::mpsc::Receiver<Vec<String>>,
 ) -> Result<Editor<ChatHelper, DefaultHistory>> {
-    let edit_mode = match os.database.settings.get_string(Setting::ChatEditMode).as_deref() {
-        Some("vi" | "vim") => EditMode::Vi,
-        _ => EditMode::Emacs,
-    };
     let config = Config::builder()
         .history_ignore_space(true)
         .completion_type(CompletionType::List)
-        .edit_mode(edit_mode)
+        .edit_mode(edit_mode(os))
+        .behavior(behavior(
+            std::io::stdin().is_terminal(),
+            std::io::stdout().is_terminal(),
+        ))
         .build();
 
     // Default to disabled if setting doesn't exist
@@ -393,29 +535,38 @@ pub fn rl(
         completer: ChatCompleter::new(sender, receiver),
         hinter: ChatHinter::new(history_hints_enabled),
         validator: MultiLineValidator,
+        submitted: Default::default(),
     };
 
+    let submitted = h.submitted();
     let mut rl = Editor::with_config(config)?;
     rl.set_helper(Some(h));
 
-    // Add custom keybinding for Alt+Enter to insert a newline
-    rl.bind_sequence(
-        KeyEvent(KeyCode::Enter, Modifiers::ALT),
-        EventHandler::Simple(Cmd::Insert(1, "\n".to_string())),
-    );
-
-    // Add custom keybinding for Ctrl+J to insert a newline
-    rl.bind_sequence(
-        KeyEvent(KeyCode::Char('j'), Modifiers::CTRL),
-        EventHandler::Simple(Cmd::Insert(1, "\n".to_string())),
-    );
-
     // Add custom keybinding for Ctrl+F to accept hint (like fish shell)
     rl.bind_sequence(
         KeyEvent(KeyCode::Char('f'), Modifiers::CTRL),
         EventHandler::Simple(Cmd::CompleteHint),
     );
 
+    let (keymap, _) = Keymap::load(os);
+    for key in keymap.keys(KeyAction::Newline) {
+        rl.bind_sequence(key.event, EventHandler::Simple(Cmd::Insert(1, "\n".to_string())));
+    }
+    for key in keymap.keys(KeyAction::Cancel) {
+        rl.bind_sequence(key.event, EventHandler::Simple(Cmd::Interrupt));
+    }
+    for key in keymap.keys(KeyAction::Editor) {
+        rl.bind_sequence(
+            key.event,
+            EventHandler::Conditional(Box::new(SubmitLine::new(submitted.clone(), |line| {
+                match line.trim().is_empty() {
+                    true => "/editor".to_string(),
+                    false => format!("/editor {}", shlex::try_quote(line).unwrap_or(line.into())),
+                }
+            }))),
+        );
+    }
+
     Ok(rl)
 }
 
@@ -448,6 +599,63 @@ mod tests {
         assert!(completions.contains(&"/help".to_string()));
     }
 
+    #[test]
+    fn test_chat_completer_setting_completion() {
+        let (prompt_request_sender, _) = std::sync::mpsc::channel::<Option<String>>();
+        let (_, prompt_response_receiver) = std::sync::mpsc::channel::<Vec<String>>();
+        let completer = ChatCompleter::new(prompt_request_sender, prompt_response_receiver);
+        let empty_history = DefaultHistory::new();
+        let os = Context::new(&empty_history);
+
+        let line = "/settings chat.edit";
+        let (start, completions) = completer.complete(line, line.len(), &os).unwrap();
+        assert_eq!(start, "/settings ".len());
+        assert_eq!(completions, vec!["chat.editMode".to_string()]);
+
+        // Values are not completed as keys
+        let line = "/settings chat.editMode chat.";
+        let (_, completions) = completer.complete(line, line.len(), &os).unwrap();
+        assert!(!completions.contains(&"chat.editMode".to_string()));
+    }
+
+    #[test]
+    fn test_chat_completer_argument_completion() {
+        let (prompt_request_sender, _) = std::sync::mpsc::channel::<Option<String>>();
+        let (_, prompt_response_receiver) = std::sync::mpsc::channel::<Vec<String>>();
+        let mut completer = ChatCompleter::new(prompt_request_sender, prompt_response_receiver);
+        completer.tool_names = vec![
+            "execute_bash".to_string(),
+            "fs_read".to_string(),
+            "fs_write".to_string(),
+        ];
+        let empty_history = DefaultHistory::new();
+        let os = Context::new(&empty_history);
+
+        let line = "/tools tr";
+        assert_eq!(
+            completer.complete(line, line.len(), &os).unwrap(),
+            (7, vec!["trust".to_string(), "trust-all".to_string()])
+        );
+
+        let line = "/context show --ex";
+        assert_eq!(
+            completer.complete(line, line.len(), &os).unwrap(),
+            (14, vec!["--expand".to_string()])
+        );
+
+        let line = "/tools trust execute_bash fs_";
+        assert_eq!(
+            completer.complete(line, line.len(), &os).unwrap(),
+            (26, vec!["fs_read".to_string(), "fs_write".to_string()])
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.md"), "").unwrap();
+        let line = format!("/context add {}/no", dir.path().display());
+        let (_, completions) = completer.complete(&line, line.len(), &os).unwrap();
+        assert_eq!(completions, vec![format!("{}/notes.md", dir.path().display())]);
+    }
+
     #[test]
     fn test_chat_completer_no_completion() {
         let (prompt_request_sender, _) = std::sync::mpsc::channel::<Option<String>>();
@@ -475,6 +683,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            submitted: Default::default(),
         };
 
         // Test basic prompt highlighting
@@ -491,6 +700,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            submitted: Default::default(),
         };
 
         // Test warning prompt highlighting
@@ -507,6 +717,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            submitted: Default::default(),
         };
 
         // Test profile prompt highlighting
@@ -523,6 +734,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            submitted: Default::default(),
         };
 
         // Test profile + warning prompt highlighting
@@ -542,6 +754,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            submitted: Default::default(),
         };
 
         // Test invalid prompt format (should return as-is)
@@ -597,4 +810,12 @@ mod tests {
         let hint = hinter.hint(line, pos, &ctx);
         assert_eq!(hint, None);
     }
+
+    #[test]
+    fn test_behavior() {
+        assert_eq!(behavior(true, true), Behavior::Stdio);
+        assert_eq!(behavior(true, false), Behavior::PreferTerm);
+        assert_eq!(behavior(false, false), Behavior::Stdio);
+        assert_eq!(behavior(false, true), Behavior::Stdio);
+    }
 }
@@ -0,0 +1,232 @@
+use regex::Regex;
+use serde_json::Value;
+use tracing::warn;
+
+use super::message::{
+    ToolUseResult,
+    ToolUseResultBlock,
+};
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Environment variables whose values are always treated as secrets.
+const SENSITIVE_ENV_VARS: &[&str] = &[
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "AWS_SECURITY_TOKEN",
+    "GITHUB_TOKEN",
+    "GH_TOKEN",
+    "NPM_TOKEN",
+];
+
+/// Environment variables with a name containing one of these are treated as secrets.
+const SENSITIVE_ENV_VAR_PARTS: &[&str] = &[
+    "SECRET",
+    "TOKEN",
+    "PASSWORD",
+    "PASSWD",
+    "API_KEY",
+    "APIKEY",
+    "PRIVATE_KEY",
+    "CREDENTIAL",
+];
+
+/// Replaces the text masked by the redaction patterns and `/redact`.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Values shorter than this are not redacted, since masking them would mangle unrelated output.
+const MIN_SECRET_LEN: usize = 8;
+
+/// Masks secrets in text before it is sent to the model, stored in the transcript, or recorded in
+/// telemetry.
+///
+/// Secrets are the values of sensitive environment variables, and anything matching the regexes
+/// configured with the `chat.redaction.patterns` setting. Redaction can be turned off with
+/// `chat.redaction.enabled`.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    /// Pairs of environment variable name and value, longest value first.
+    env_secrets: Vec<(String, String)>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    pub fn new(os: &Os) -> Self {
+        if !os
+            .database
+            .settings
+            .get_bool(Setting::ChatRedactionEnabled)
+            .unwrap_or(true)
+        {
+            return Self::default();
+        }
+
+        let mut env_secrets = os
+            .env
+            .vars()
+            .into_iter()
+            .filter(|(name, value)| is_sensitive_env_var(name) && value.len() >= MIN_SECRET_LEN)
+            .collect::<Vec<_>>();
+        // Longer values first so that a secret containing another is masked as a whole.
+        env_secrets.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+
+        let patterns = match os.database.settings.get(Setting::ChatRedactionPatterns) {
+            Some(Value::Array(patterns)) => patterns.iter().filter_map(Value::as_str).collect::<Vec<_>>(),
+            Some(Value::String(pattern)) => vec![pattern.as_str()],
+            _ => vec![],
+        }
+        .into_iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(err) => {
+                warn!(?err, pattern, "ignoring invalid redaction pattern");
+                None
+            },
+        })
+        .collect();
+
+        Self { env_secrets, patterns }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.env_secrets.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Returns `text` with all secrets masked.
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (name, value) in &self.env_secrets {
+            if text.contains(value.as_str()) {
+                text = text.replace(value.as_str(), &format!("[REDACTED:{name}]"));
+            }
+        }
+        for pattern in &self.patterns {
+            if let std::borrow::Cow::Owned(replaced) = pattern.replace_all(&text, REDACTED) {
+                text = replaced;
+            }
+        }
+        text
+    }
+
+    /// Masks secrets in every string contained in `value`.
+    pub fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => *s = self.redact(s),
+            Value::Array(values) => values.iter_mut().for_each(|v| self.redact_json(v)),
+            Value::Object(map) => map.values_mut().for_each(|v| self.redact_json(v)),
+            _ => (),
+        }
+    }
+
+    pub fn redact_tool_results(&self, results: &mut [ToolUseResult]) {
+        if self.is_empty() {
+            return;
+        }
+        for block in results.iter_mut().flat_map(|r| r.content.iter_mut()) {
+            match block {
+                ToolUseResultBlock::Text(text) => *text = self.redact(text),
+                ToolUseResultBlock::Json(json) => self.redact_json(json),
+            }
+        }
+    }
+}
+
+/// Replaces the matches of `pattern` in `text` with [REDACTED], returning the number of matches.
+pub fn replace_matches(text: &mut String, pattern: &Regex) -> usize {
+    let count = pattern.find_iter(text).count();
+    if count > 0 {
+        *text = pattern.replace_all(text, REDACTED).into_owned();
+    }
+    count
+}
+
+/// Replaces the matches of `pattern` in every string contained in `value`, returning the number
+/// of matches.
+pub fn replace_json_matches(value: &mut Value, pattern: &Regex) -> usize {
+    match value {
+        Value::String(s) => replace_matches(s, pattern),
+        Value::Array(values) => values.iter_mut().map(|v| replace_json_matches(v, pattern)).sum(),
+        Value::Object(map) => map.values_mut().map(|v| replace_json_matches(v, pattern)).sum(),
+        _ => 0,
+    }
+}
+
+fn is_sensitive_env_var(name: &str) -> bool {
+    let name = name.to_uppercase();
+    SENSITIVE_ENV_VARS.contains(&name.as_str()) || SENSITIVE_ENV_VAR_PARTS.iter().any(|part| name.contains(part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::model::ToolResultStatus;
+    use crate::os::Env;
+
+    async fn redactor(vars: &[(&str, &str)], patterns: Option<Value>) -> Redactor {
+        let mut os = Os::new().await.unwrap();
+        os.env = Env::from_slice(vars);
+        if let Some(patterns) = patterns {
+            os.database
+                .settings
+                .set(Setting::ChatRedactionPatterns, patterns)
+                .await
+                .unwrap();
+        }
+        Redactor::new(&os)
+    }
+
+    #[tokio::test]
+    async fn test_redact_env_vars() {
+        let redactor = redactor(
+            &[
+                ("AWS_SECRET_ACCESS_KEY", "wJalrXUtnFEMI/K7MDENG"),
+                ("MY_SERVICE_TOKEN", "abcdefgh12345"),
+                ("SHORT_TOKEN", "abc"),
+                ("HOME", "/home/testuser"),
+            ],
+            None,
+        )
+        .await;
+
+        assert_eq!(
+            redactor.redact("key=wJalrXUtnFEMI/K7MDENG token=abcdefgh12345 short=abc home=/home/testuser"),
+            "key=[REDACTED:AWS_SECRET_ACCESS_KEY] token=[REDACTED:MY_SERVICE_TOKEN] short=abc home=/home/testuser"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redact_patterns() {
+        let redactor = redactor(&[], Some(serde_json::json!(["ghp_[A-Za-z0-9]+", "("]))).await;
+        assert_eq!(redactor.patterns.len(), 1);
+        assert_eq!(redactor.redact("token ghp_abc123 here"), "token [REDACTED] here");
+
+        let mut results = vec![ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![
+                ToolUseResultBlock::Text("ghp_xyz".to_string()),
+                ToolUseResultBlock::Json(serde_json::json!({ "stdout": ["ghp_xyz", 1] })),
+            ],
+            status: ToolResultStatus::Success,
+        }];
+        redactor.redact_tool_results(&mut results);
+        assert!(matches!(&results[0].content[0], ToolUseResultBlock::Text(t) if t == "[REDACTED]"));
+        assert!(
+            matches!(&results[0].content[1], ToolUseResultBlock::Json(j) if *j == serde_json::json!({ "stdout": ["[REDACTED]", 1] }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redaction_disabled() {
+        let mut os = Os::new().await.unwrap();
+        os.env = Env::from_slice(&[("GITHUB_TOKEN", "ghp_secretvalue")]);
+        os.database
+            .settings
+            .set(Setting::ChatRedactionEnabled, false)
+            .await
+            .unwrap();
+        let redactor = Redactor::new(&os);
+        assert!(redactor.is_empty());
+        assert_eq!(redactor.redact("ghp_secretvalue"), "ghp_secretvalue");
+    }
+}
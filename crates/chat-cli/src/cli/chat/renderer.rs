@@ -0,0 +1,67 @@
+//! Frontends of a [super::ChatSession].
+//!
+//! The text of the conversation is written to the session's output streams by every frontend.
+//! A [Renderer] shows everything else: the state of the session, the tools waiting to run and
+//! progress while the session is busy.
+
+use std::fmt::Debug;
+
+use serde::Serialize;
+use spinners::{
+    Spinner,
+    Spinners,
+    Stream,
+};
+
+/// Snapshot of a session shown by a [Renderer], taken whenever its state changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionStatus {
+    /// What the session is doing, e.g. `Waiting for input`.
+    pub activity: String,
+    pub model: Option<String>,
+    pub agent: Option<String>,
+    pub context_files: Vec<String>,
+    /// Tools requested by the model that have not run yet.
+    pub pending_tools: Vec<PendingTool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTool {
+    pub name: String,
+    pub awaiting_approval: bool,
+}
+
+pub trait Renderer: Debug + Send + Sync {
+    /// Shows the status of the session.
+    fn update(&self, status: &SessionStatus);
+
+    /// Shows that the session is busy with `message` until the next status update, returning the
+    /// spinner to drop once done if the renderer draws one.
+    fn busy(&self, message: &str) -> Option<Spinner>;
+
+    /// Whether the session may read the terminal itself, e.g. to capture type-ahead input.
+    fn reads_terminal(&self) -> bool;
+}
+
+/// The default frontend, which writes lines to the terminal and only shows a spinner while the
+/// session is busy. The spinner is drawn on stderr so that it never ends up in piped output.
+#[derive(Debug, Default)]
+pub struct LineRenderer;
+
+impl Renderer for LineRenderer {
+    fn update(&self, _status: &SessionStatus) {}
+
+    fn busy(&self, message: &str) -> Option<Spinner> {
+        Some(Spinner::with_stream(
+            Spinners::Dots,
+            message.to_string(),
+            Stream::Stderr,
+        ))
+    }
+
+    fn reads_terminal(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,289 @@
+//! Embedding index of the workspace searched by the `semantic_search` tool, which is built and
+//! updated with `q index`.
+//!
+//! The index is stored under `.amazonq/index/semantic` of the workspace. Files are split into
+//! overlapping chunks of lines, and only the chunks of files whose content hash changed since the
+//! last update are embedded again.
+
+use std::collections::HashMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::{
+    Result,
+    bail,
+};
+use semantic_search_client::client::SemanticContext;
+use semantic_search_client::client::embedder_factory::create_embedder;
+use semantic_search_client::embedding::{
+    EmbeddingType,
+    TextEmbedderTrait,
+};
+use semantic_search_client::types::DataPoint;
+use serde_json::Value;
+use sha2::{
+    Digest,
+    Sha256,
+};
+
+use super::auto_context::{
+    MAX_FILE_SIZE,
+    workspace_files,
+};
+
+/// Lines of each chunk.
+const CHUNK_LINES: usize = 40;
+/// Lines shared by consecutive chunks, so that code spanning a boundary is found as a whole.
+const CHUNK_OVERLAP: usize = 10;
+
+/// Directory of the index of the workspace at `root`.
+pub fn index_dir(root: &Path) -> PathBuf {
+    root.join(".amazonq").join("index").join("semantic")
+}
+
+/// A chunk of a file matching a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeMatch {
+    /// Path relative to the workspace.
+    pub path: String,
+    /// First line of the chunk, starting from 1.
+    pub start_line: usize,
+    pub text: String,
+    /// Distance from the query, lower is more relevant.
+    pub distance: f32,
+}
+
+/// Result of [SemanticIndex::update].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexUpdate {
+    /// Files that were embedded again.
+    pub indexed: usize,
+    /// Files that were removed from the workspace.
+    pub removed: usize,
+    /// Files in the index.
+    pub total: usize,
+}
+
+pub struct SemanticIndex {
+    root: PathBuf,
+    embedder: Box<dyn TextEmbedderTrait>,
+    context: SemanticContext,
+    /// Content hash of each indexed file, by its path relative to the workspace.
+    hashes: HashMap<String, String>,
+}
+
+impl std::fmt::Debug for SemanticIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SemanticIndex")
+            .field("root", &self.root)
+            .field("files", &self.hashes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SemanticIndex {
+    /// Whether `q index` was run for the workspace at `root`.
+    pub fn exists(root: &Path) -> bool {
+        index_dir(root).join("files.json").exists()
+    }
+
+    /// Opens the index of the workspace at `root`, which is empty if it was never built.
+    pub fn open(root: &Path) -> Result<Self> {
+        Self::open_with(root, EmbeddingType::default())
+    }
+
+    fn open_with(root: &Path, embedding_type: EmbeddingType) -> Result<Self> {
+        let dir = index_dir(root);
+        let hashes = match std::fs::read(dir.join("files.json")) {
+            Ok(content) => serde_json::from_slice(&content)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            root: root.to_path_buf(),
+            embedder: create_embedder(embedding_type)?,
+            context: SemanticContext::new(dir.join("data.json"))?,
+            hashes,
+        })
+    }
+
+    /// Brings the index up to date with the files of the workspace, calling `progress` with the
+    /// number of files checked so far.
+    pub fn update(&mut self, mut progress: impl FnMut(usize)) -> Result<IndexUpdate> {
+        let mut hashes = HashMap::new();
+        let mut changed = HashMap::new();
+        for (i, entry) in workspace_files(&self.root).enumerate() {
+            progress(i + 1);
+            if entry.metadata().map_or(true, |metadata| metadata.len() > MAX_FILE_SIZE) {
+                continue;
+            }
+            // Binary files are not indexed.
+            let Ok(content) = std::fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            if content.contains('\0') {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(&self.root) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().to_string();
+            let hash = hex::encode(Sha256::digest(content.as_bytes()));
+            if self.hashes.get(&relative) != Some(&hash) {
+                changed.insert(relative.clone(), content);
+            }
+            hashes.insert(relative, hash);
+        }
+
+        let removed = self.hashes.keys().filter(|path| !hashes.contains_key(*path)).count();
+        let mut points = self
+            .context
+            .get_data_points()
+            .iter()
+            .filter(|point| {
+                point_path(point).is_some_and(|path| hashes.contains_key(path) && !changed.contains_key(path))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for (path, content) in &changed {
+            for (start_line, text) in chunk_lines(content) {
+                let mut payload = HashMap::new();
+                payload.insert("path".to_string(), Value::from(path.as_str()));
+                payload.insert("start_line".to_string(), Value::from(start_line));
+                payload.insert("text".to_string(), Value::from(text.as_str()));
+                points.push(DataPoint {
+                    id: 0,
+                    payload,
+                    vector: self.embedder.embed(&format!("{path}\n{text}"))?,
+                });
+            }
+        }
+        for (id, point) in points.iter_mut().enumerate() {
+            point.id = id;
+        }
+
+        // Points cannot be removed from a context, so the context is created again.
+        let dir = index_dir(&self.root);
+        let data_path = dir.join("data.json");
+        if data_path.exists() {
+            std::fs::remove_file(&data_path)?;
+        }
+        self.context = SemanticContext::new(data_path)?;
+        self.context.add_data_points(points)?;
+        self.context.save()?;
+        std::fs::write(dir.join("files.json"), serde_json::to_vec(&hashes)?)?;
+
+        let update = IndexUpdate {
+            indexed: changed.len(),
+            removed,
+            total: hashes.len(),
+        };
+        self.hashes = hashes;
+        Ok(update)
+    }
+
+    /// Returns at most `limit` chunks relevant to `query`, most relevant first.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<CodeMatch>> {
+        if query.trim().is_empty() {
+            bail!("The query is empty");
+        }
+        let vector = self.embedder.embed(query)?;
+        Ok(self
+            .context
+            .search(&vector, limit)?
+            .into_iter()
+            .filter_map(|result| {
+                Some(CodeMatch {
+                    path: point_path(&result.point)?.to_string(),
+                    start_line: result.point.payload.get("start_line")?.as_u64()? as usize,
+                    text: result.point.payload.get("text")?.as_str()?.to_string(),
+                    distance: result.distance,
+                })
+            })
+            .collect())
+    }
+}
+
+fn point_path(point: &DataPoint) -> Option<&str> {
+    point.payload.get("path")?.as_str()
+}
+
+/// Splits `content` into overlapping chunks of lines, returned with the line they start at.
+fn chunk_lines(content: &str) -> Vec<(usize, String)> {
+    let lines = content.lines().collect::<Vec<_>>();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let text = lines[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push((start + 1, text));
+        }
+        if end == lines.len() {
+            break;
+        }
+        start += CHUNK_LINES - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_lines() {
+        let content = (1..=75).map(|i| format!("line {i}")).collect::<Vec<_>>().join("\n");
+        let chunks = chunk_lines(&content);
+        assert_eq!(chunks.iter().map(|(start, _)| *start).collect::<Vec<_>>(), vec![
+            1, 31, 61
+        ]);
+        assert!(chunks[1].1.starts_with("line 31\n"));
+        assert!(chunks[2].1.ends_with("line 75"));
+    }
+
+    #[test]
+    fn test_update_and_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(
+            root.join("src/retry.rs"),
+            "fn with_retry() {\n    // retry the request with exponential backoff\n}\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("src/render.rs"), "fn draw_table() {\n    // render rows\n}\n").unwrap();
+
+        let mut index = SemanticIndex::open_with(root, EmbeddingType::BM25).unwrap();
+        let update = index.update(|_| {}).unwrap();
+        assert_eq!(update, IndexUpdate {
+            indexed: 2,
+            removed: 0,
+            total: 2
+        });
+        assert!(SemanticIndex::exists(root));
+
+        let matches = index.search("where is retry backoff implemented", 1).unwrap();
+        assert_eq!(matches[0].path, "src/retry.rs");
+        assert_eq!(matches[0].start_line, 1);
+
+        // Only changed files are embedded again, and removed files leave the index.
+        std::fs::remove_file(root.join("src/render.rs")).unwrap();
+        let mut index = SemanticIndex::open_with(root, EmbeddingType::BM25).unwrap();
+        let update = index.update(|_| {}).unwrap();
+        assert_eq!(update, IndexUpdate {
+            indexed: 0,
+            removed: 1,
+            total: 1
+        });
+        assert!(
+            index
+                .search("render rows", 5)
+                .unwrap()
+                .iter()
+                .all(|m| m.path == "src/retry.rs")
+        );
+    }
+}
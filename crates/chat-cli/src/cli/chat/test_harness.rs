@@ -0,0 +1,488 @@
+//! Utilities for driving a [ChatSession] end to end in tests.
+//!
+//! The session reads its input from a mock [InputSource] and writes to captured streams. What it
+//! does is returned as a list of [UiEvent]s: typed events for prompts, responses, tool uses and
+//! errors, in order with the lines it wrote, with terminal styling removed, which makes it suitable
+//! for both assertions and snapshot testing.
+//!
+//! Enabled in this crate's tests, or for downstream use with the `test-harness` feature.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use eyre::Result;
+
+use super::ChatSession;
+use super::agent_socket::SessionEvent;
+use super::input_source::InputSource;
+use super::output::{
+    CapturedOutput,
+    Chunk,
+    Stream,
+};
+use super::tool_manager::ToolManager;
+use super::tools::ToolSpec;
+use crate::cli::agent::Agents;
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Something the session did, or a single line of output it wrote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UiEvent {
+    Stdout(String),
+    Stderr(String),
+    /// A prompt was sent to the model.
+    Prompt(String),
+    /// The whole text of a response of the model.
+    AssistantText(String),
+    /// A tool is used.
+    ToolUse(String),
+    /// A tool use waits for the approval of the user.
+    ToolApproval(String),
+    /// A tool use finished.
+    ToolResult {
+        tool: String,
+        success: bool,
+    },
+    /// An error was shown to the user.
+    Error(String),
+}
+
+impl Display for UiEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UiEvent::Stdout(line) => write!(f, "[stdout] {line}"),
+            UiEvent::Stderr(line) => write!(f, "[stderr] {line}"),
+            UiEvent::Prompt(text) => write!(f, "[prompt] {text}"),
+            UiEvent::AssistantText(text) => write!(f, "[assistant] {text}"),
+            UiEvent::ToolUse(tool) => write!(f, "[tool use] {tool}"),
+            UiEvent::ToolApproval(tool) => write!(f, "[tool approval] {tool}"),
+            UiEvent::ToolResult { tool, success } => {
+                write!(
+                    f,
+                    "[tool result] {tool} {}",
+                    if *success { "succeeded" } else { "failed" }
+                )
+            },
+            UiEvent::Error(message) => write!(f, "[error] {message}"),
+        }
+    }
+}
+
+/// Renders events one per line, in the order they were written.
+pub fn render(events: &[UiEvent]) -> String {
+    events.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+/// Builder for running a [ChatSession] against a list of user inputs.
+#[derive(Debug, Default)]
+pub struct SessionHarness {
+    agents: Agents,
+    mock_responses: Option<serde_json::Value>,
+    quiet: bool,
+    tee: Option<PathBuf>,
+}
+
+impl SessionHarness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Agents available to the session.
+    pub fn agents(mut self, agents: Agents) -> Self {
+        self.agents = agents;
+        self
+    }
+
+    /// Responses returned by the mock model client, in the format accepted by
+    /// [crate::api_client::ApiClient::set_mock_output].
+    pub fn mock_responses(mut self, responses: serde_json::Value) -> Self {
+        self.mock_responses = Some(responses);
+        self
+    }
+
+    /// Whether the session only writes the final response to stdout, as with `--quiet`.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// File the text of responses is written to, as with `--tee`.
+    pub fn tee(mut self, path: impl Into<PathBuf>) -> Self {
+        self.tee = Some(path.into());
+        self
+    }
+
+    /// Runs an interactive session that receives `inputs` as user input, and returns everything
+    /// it wrote. The session is ended with `/quit` once the inputs are exhausted.
+    ///
+    /// The greeting is disabled through the `chat.greeting.enabled` setting of `os` since it
+    /// contains randomized tips.
+    pub async fn run(self, os: &mut Os, inputs: &[&str]) -> Result<Vec<UiEvent>> {
+        os.database.settings.set(Setting::ChatGreetingEnabled, false).await?;
+        // A title request would take one of the mock responses, racing the session's requests.
+        os.database.settings.set(Setting::ChatEnableAutoTitle, false).await?;
+        if let Some(responses) = self.mock_responses {
+            os.client.set_mock_output(responses);
+        }
+
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))?;
+        let mut lines = inputs.iter().map(|s| (*s).to_string()).collect::<Vec<_>>();
+        lines.push("/quit".to_string());
+
+        let (stdout, stderr) = CapturedOutput::pair();
+        let log = stdout.clone();
+        let mut session = ChatSession::new(
+            os,
+            stdout,
+            stderr,
+            "fake_conv_id",
+            self.agents,
+            None,
+            InputSource::new_mock(lines),
+            false,
+            || Some(80),
+            ToolManager::default(),
+            None,
+            tool_config,
+            true,
+        )
+        .await?;
+        session.quiet = self.quiet;
+        session.tee = self.tee;
+        session.spawn(os).await?;
+
+        Ok(into_events(log.chunks()))
+    }
+}
+
+fn into_events(chunks: Vec<Chunk>) -> Vec<UiEvent> {
+    let mut events = Vec::new();
+    // Writes to the same stream are joined until the stream changes, so that an event in the
+    // middle of a line does not split it. The complete lines are added before the event.
+    let mut pending: Option<(Stream, Vec<u8>)> = None;
+    // The text of a response streams in pieces, and is one event once it ends.
+    let mut response: Option<String> = None;
+    for chunk in chunks {
+        match chunk {
+            Chunk::Write(stream, bytes) => match &mut pending {
+                Some((pending_stream, pending_bytes)) if *pending_stream == stream => {
+                    pending_bytes.extend_from_slice(&bytes);
+                },
+                _ => {
+                    if let Some((stream, bytes)) = pending.replace((stream, bytes)) {
+                        push_lines(&mut events, stream, &bytes);
+                    }
+                },
+            },
+            Chunk::Event(event) => {
+                if let Some((stream, bytes)) = &mut pending {
+                    if let Some(end) = bytes.iter().rposition(|byte| *byte == b'\n') {
+                        let lines = bytes.drain(..=end).collect::<Vec<_>>();
+                        push_lines(&mut events, *stream, &lines);
+                    }
+                }
+                if let SessionEvent::ResponseText { text } = event {
+                    response.get_or_insert_default().push_str(&text);
+                    continue;
+                }
+                if let Some(text) = response.take() {
+                    events.push(UiEvent::AssistantText(text));
+                }
+                match event {
+                    SessionEvent::Prompt { text } => events.push(UiEvent::Prompt(text)),
+                    SessionEvent::ToolUse { name } => events.push(UiEvent::ToolUse(name)),
+                    SessionEvent::ToolApproval { name } => events.push(UiEvent::ToolApproval(name)),
+                    SessionEvent::ToolResult { name, success } => {
+                        events.push(UiEvent::ToolResult { tool: name, success });
+                    },
+                    SessionEvent::Error { message } => events.push(UiEvent::Error(message)),
+                    SessionEvent::ResponseText { .. } | SessionEvent::ResponseEnd => (),
+                }
+            },
+        }
+    }
+    if let Some((stream, bytes)) = pending {
+        push_lines(&mut events, stream, &bytes);
+    }
+    if let Some(text) = response {
+        events.push(UiEvent::AssistantText(text));
+    }
+    events
+}
+
+/// Adds the lines written to `stream`, without terminal styling and trailing whitespace.
+fn push_lines(events: &mut Vec<UiEvent>, stream: Stream, bytes: &[u8]) {
+    let text = strip_ansi_escapes::strip_str(String::from_utf8_lossy(bytes));
+    for line in text.lines() {
+        let line = line.trim_end().to_string();
+        events.push(match stream {
+            Stream::Stdout => UiEvent::Stdout(line),
+            Stream::Stderr => UiEvent::Stderr(line),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::agent::Agent;
+
+    async fn run_command(inputs: &[&str]) -> String {
+        let mut os = Os::new().await.unwrap();
+        let events = SessionHarness::new().run(&mut os, inputs).await.unwrap();
+        let home = os.fs.chroot_path(os.env.home().unwrap());
+        render(&events).replace(&home.to_string_lossy().to_string(), "~")
+    }
+
+    macro_rules! snapshot_command {
+        ($name:ident, $($input:expr),+) => {
+            #[tokio::test]
+            async fn $name() {
+                insta::assert_snapshot!(run_command(&[$($input),+]).await);
+            }
+        };
+    }
+
+    #[tokio::test]
+    async fn test_prompt_with_agent() {
+        let mut os = Os::new().await.unwrap();
+        let mut agents = Agents::default();
+        agents.agents.insert("TestAgent".to_string(), Agent {
+            name: "TestAgent".to_string(),
+            ..Default::default()
+        });
+        agents.switch("TestAgent").unwrap();
+
+        let events = SessionHarness::new()
+            .agents(agents)
+            .mock_responses(serde_json::json!([["Hello! How can I help?"]]))
+            .run(&mut os, &["hi", "/status"])
+            .await
+            .unwrap();
+        insta::assert_snapshot!(render(&events));
+    }
+
+    #[tokio::test]
+    async fn test_apply_code_block() {
+        let mut os = Os::new().await.unwrap();
+        let events = SessionHarness::new()
+            .mock_responses(serde_json::json!([["Try this:\n```rust\nfn main() {}\n```\n"]]))
+            .run(&mut os, &["hi", "/apply 2 /main.rs", "/apply 1 /main.rs", "y"])
+            .await
+            .unwrap();
+
+        assert!(events.contains(&UiEvent::Stderr(
+            "Code block 2 does not exist. The last response contains 1 code block.".to_string()
+        )));
+        assert!(events.contains(&UiEvent::Stderr("Wrote code block 1 to /main.rs.".to_string())));
+        assert_eq!(os.fs.read_to_string("/main.rs").await.unwrap(), "fn main() {}\n");
+    }
+
+    #[tokio::test]
+    async fn test_quiet() {
+        let mut os = Os::new().await.unwrap();
+        let events = SessionHarness::new()
+            .quiet(true)
+            .mock_responses(serde_json::json!([["The answer is **42**."]]))
+            .run(&mut os, &["what is the answer?"])
+            .await
+            .unwrap();
+
+        let stdout = events
+            .iter()
+            .filter_map(|event| match event {
+                UiEvent::Stdout(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(stdout, vec!["The answer is **42**."]);
+    }
+
+    #[tokio::test]
+    async fn test_tee() {
+        let mut os = Os::new().await.unwrap();
+        SessionHarness::new()
+            .tee("/response.txt")
+            .mock_responses(serde_json::json!([["The answer is **42**."], ["Anything else?"]]))
+            .run(&mut os, &["what is the answer?", "thanks"])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            os.fs.read_to_string("/response.txt").await.unwrap(),
+            "The answer is **42**.\n\nAnything else?\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_settings_command() {
+        let mut os = Os::new().await.unwrap();
+        let events = SessionHarness::new()
+            .run(&mut os, &[
+                "/settings chat.editMode vi",
+                "/settings chat.maxResponseWidth 100",
+                "/settings chat.editMode",
+                "/settings chat.unknown",
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            os.database.settings.get_string(Setting::ChatEditMode).as_deref(),
+            Some("vi")
+        );
+        assert_eq!(os.database.settings.get_int(Setting::ChatMaxResponseWidth), Some(100));
+        assert!(events.contains(&UiEvent::Stderr("chat.editMode = \"vi\"".to_string())));
+        assert!(
+            events
+                .iter()
+                .any(|event| event.to_string().contains("Unknown setting chat.unknown"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_help_shows_keymap() {
+        let mut os = Os::new().await.unwrap();
+        os.database
+            .settings
+            .set(Setting::ChatKeymap, serde_json::json!({ "approveTool": "ctrl-y" }))
+            .await
+            .unwrap();
+        let events = SessionHarness::new().run(&mut os, &["/help"]).await.unwrap();
+
+        let help = events.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n");
+        assert!(help.contains("Ctrl(^) + y"), "{help}");
+        assert!(help.contains("Allow the tool waiting for approval"));
+        assert!(help.contains("Alt(⌥) + Enter(⏎)"));
+    }
+
+    #[tokio::test]
+    async fn test_alias() {
+        let mut os = Os::new().await.unwrap();
+        let events = SessionHarness::new()
+            .mock_responses(serde_json::json!([["Found a bug."], ["Low risk."]]))
+            .run(&mut os, &[
+                "/alias review = \"Review $1 for bugs ;; Summarize the risks\"",
+                "/review src/",
+            ])
+            .await
+            .unwrap();
+
+        assert!(events.contains(&UiEvent::Stderr(
+            "Saved /review = Review $1 for bugs ;; Summarize the risks".to_string()
+        )));
+        assert!(events.contains(&UiEvent::Stderr("Review src/ for bugs".to_string())));
+        assert!(events.contains(&UiEvent::Stderr("> Summarize the risks".to_string())));
+        assert!(events.contains(&UiEvent::Stdout("> Low risk.".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_tool_approval_on_stderr() {
+        let mut os = Os::new().await.unwrap();
+        let events = SessionHarness::new()
+            .mock_responses(serde_json::json!([
+                [
+                    "Creating the file.",
+                    {
+                        "tool_use_id": "1",
+                        "name": "fs_write",
+                        "args": {
+                            "command": "create",
+                            "file_text": "Hello, world!",
+                            "path": "/file.txt",
+                        }
+                    }
+                ],
+                ["The file is created."],
+            ]))
+            .run(&mut os, &["create a file", "y"])
+            .await
+            .unwrap();
+
+        let stdout = events
+            .iter()
+            .filter(|event| matches!(event, UiEvent::Stdout(_)))
+            .collect::<Vec<_>>();
+        let stderr = events
+            .iter()
+            .filter(|event| matches!(event, UiEvent::Stderr(_)))
+            .collect::<Vec<_>>();
+        let contains = |events: &[&UiEvent], text: &str| events.iter().any(|event| event.to_string().contains(text));
+        // Only the responses are written to stdout, so that they can be piped.
+        assert!(contains(&stdout, "Creating the file."));
+        assert!(contains(&stdout, "The file is created."));
+        for text in ["Using tool", "Hello, world!", "Allow this action?", "Completed in"] {
+            assert!(contains(&stderr, text), "{text} is not written to stderr");
+            assert!(!contains(&stdout, text), "{text} is written to stdout");
+        }
+        assert_eq!(os.fs.read_to_string("/file.txt").await.unwrap(), "Hello, world!\n");
+
+        let typed = events
+            .iter()
+            .filter(|event| !matches!(event, UiEvent::Stdout(_) | UiEvent::Stderr(_)))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(typed, vec![
+            UiEvent::Prompt("create a file".to_string()),
+            UiEvent::AssistantText("Creating the file.".to_string()),
+            UiEvent::ToolUse("fs_write".to_string()),
+            UiEvent::ToolApproval("fs_write".to_string()),
+            UiEvent::Prompt("y".to_string()),
+            UiEvent::ToolResult {
+                tool: "fs_write".to_string(),
+                success: true,
+            },
+            UiEvent::AssistantText("The file is created.".to_string()),
+            UiEvent::Prompt("/quit".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_into_events() {
+        let text = |text: &str| Chunk::Event(SessionEvent::ResponseText { text: text.to_string() });
+        let events = into_events(vec![
+            Chunk::Write(Stream::Stderr, b"\x1b[32mhello\x1b[0m\nworld  \n".to_vec()),
+            text("Hel"),
+            Chunk::Write(Stream::Stdout, b"out\n".to_vec()),
+            text("lo"),
+            Chunk::Event(SessionEvent::ResponseEnd),
+            Chunk::Event(SessionEvent::ToolResult {
+                name: "fs_read".to_string(),
+                success: false,
+            }),
+        ]);
+        assert_eq!(events, vec![
+            UiEvent::Stderr("hello".to_string()),
+            UiEvent::Stderr("world".to_string()),
+            UiEvent::Stdout("out".to_string()),
+            UiEvent::AssistantText("Hello".to_string()),
+            UiEvent::ToolResult {
+                tool: "fs_read".to_string(),
+                success: false,
+            },
+        ]);
+        assert_eq!(
+            render(&events),
+            "[stderr] hello\n[stderr] world\n[stdout] out\n[assistant] Hello\n[tool result] fs_read failed"
+        );
+    }
+
+    snapshot_command!(test_slash_quit, "/quit");
+    snapshot_command!(test_slash_unknown, "/notacommand");
+    snapshot_command!(test_slash_clear, "/clear", "y");
+    snapshot_command!(test_slash_agent_list, "/agent list");
+    snapshot_command!(test_slash_agent_switch, "/agent switch foo");
+    snapshot_command!(test_slash_context_show, "/context show");
+    snapshot_command!(test_slash_tools, "/tools");
+    snapshot_command!(test_slash_tools_trust, "/tools trust fs_write");
+    snapshot_command!(test_slash_hooks, "/hooks");
+    snapshot_command!(test_slash_prompts, "/prompts list");
+    snapshot_command!(test_slash_mcp, "/mcp");
+    snapshot_command!(test_slash_usage, "/usage");
+    snapshot_command!(test_slash_plan, "/plan");
+    snapshot_command!(test_slash_plan_clear, "/plan clear");
+    snapshot_command!(test_slash_status, "/status");
+    snapshot_command!(test_slash_copy, "/copy");
+}
@@ -21,6 +21,7 @@ use std::sync::atomic::{
 use std::sync::{
     Arc,
     RwLock as SyncRwLock,
+    Weak,
 };
 use std::time::{
     Duration,
@@ -68,16 +69,51 @@ use crate::cli::chat::server_messenger::{
     ServerMessengerBuilder,
     UpdateEventMessage,
 };
+use crate::cli::chat::tools::aws_read::{
+    AwsRead,
+    AwsReadSettings,
+};
+use crate::cli::chat::tools::browser::{
+    Browser,
+    BrowserSettings,
+};
 use crate::cli::chat::tools::custom_tool::{
     CustomTool,
     CustomToolClient,
 };
+use crate::cli::chat::tools::db_query::{
+    DbQuery,
+    DbQuerySettings,
+};
+use crate::cli::chat::tools::diagnostics::Diagnostics;
 use crate::cli::chat::tools::execute::ExecuteCommand;
 use crate::cli::chat::tools::fs_read::FsRead;
 use crate::cli::chat::tools::fs_write::FsWrite;
 use crate::cli::chat::tools::gh_issue::GhIssue;
+use crate::cli::chat::tools::http_request::{
+    HttpRequest,
+    HttpRequestSettings,
+};
+use crate::cli::chat::tools::infra_plan::InfraPlan;
+use crate::cli::chat::tools::kb_search::{
+    KbSearch,
+    KnowledgeBase,
+};
 use crate::cli::chat::tools::knowledge::Knowledge;
+use crate::cli::chat::tools::list_files::ListFiles;
+use crate::cli::chat::tools::logs_tail::LogsTail;
+use crate::cli::chat::tools::process::{
+    Process,
+    ProcessKill,
+    ProcessOutput,
+};
+use crate::cli::chat::tools::rename_symbol::RenameSymbol;
+use crate::cli::chat::tools::run_tests::RunTests;
+use crate::cli::chat::tools::schema_validation::validate_tool_args;
+use crate::cli::chat::tools::scratchpad::Scratchpad;
+use crate::cli::chat::tools::semantic_search::SemanticSearch;
 use crate::cli::chat::tools::thinking::Thinking;
+use crate::cli::chat::tools::update_plan::UpdatePlan;
 use crate::cli::chat::tools::use_aws::UseAws;
 use crate::cli::chat::tools::{
     Tool,
@@ -200,6 +236,18 @@ impl ToolManagerBuilder {
         let pre_initialized = enabled_servers
             .into_iter()
             .filter_map(|(server_name, server_config)| {
+                if !os.database.settings.policy().allows_mcp_server(&server_name) {
+                    let _ = queue!(
+                        output,
+                        style::SetForegroundColor(style::Color::DarkGrey),
+                        style::Print("○ "),
+                        style::SetForegroundColor(style::Color::Blue),
+                        style::Print(&server_name),
+                        style::ResetColor,
+                        style::Print(" is not allowed by the managed policy\n"),
+                    );
+                    return None;
+                }
                 if server_name.contains(MCP_SERVER_TOOL_DELIMITER) {
                     let _ = queue!(
                         output,
@@ -622,17 +670,25 @@ impl ToolManagerBuilder {
         let sender = self.prompt_list_sender.take();
         let receiver = self.prompt_list_receiver.take();
         let prompts = Arc::new(SyncRwLock::new(HashMap::default()));
-        // TODO: accommodate hot reload of mcp servers
-        if let (Some(sender), Some(receiver)) = (sender, receiver) {
-            let clients = clients.iter().fold(HashMap::new(), |mut acc, (n, c)| {
+        let prompt_clients = Arc::new(SyncRwLock::new(clients.iter().fold(
+            HashMap::new(),
+            |mut acc, (n, c)| {
                 acc.insert(n.clone(), Arc::downgrade(c));
                 acc
-            });
+            },
+        )));
+        if let (Some(sender), Some(receiver)) = (sender, receiver) {
+            let prompt_clients = prompt_clients.clone();
             let prompts_clone = prompts.clone();
             tokio::task::spawn_blocking(move || {
                 let receiver = Arc::new(std::sync::Mutex::new(receiver));
                 loop {
                     let search_word = receiver.lock().map_err(|e| eyre::eyre!("{:?}", e))?.recv()?;
+                    // Cloned so that the servers can be replaced while prompts are listed
+                    let clients = prompt_clients
+                        .read()
+                        .map_err(|e| eyre::eyre!("Error retrieving read lock on prompt clients {}", e))?
+                        .clone();
                     if clients
                         .values()
                         .any(|client| client.upgrade().is_some_and(|c| c.is_prompts_out_of_date()))
@@ -711,6 +767,7 @@ impl ToolManagerBuilder {
             conversation_id,
             clients,
             prompts,
+            prompt_clients,
             pending_clients: pending,
             notify: Some(notify),
             loading_status_sender,
@@ -818,6 +875,10 @@ pub struct ToolManager {
     /// cases where multiple servers offer prompts with the same name.
     pub prompts: Arc<SyncRwLock<HashMap<String, Vec<PromptBundle>>>>,
 
+    /// The servers whose prompts are tab completed, shared with the task listing them so that it
+    /// follows the servers when they are replaced. See [Self::take_over_prompt_completion].
+    prompt_clients: Arc<SyncRwLock<HashMap<String, Weak<CustomToolClient>>>>,
+
     /// A notifier to understand if the initial loading has completed.
     /// This is only used for initial loading and is discarded after.
     notify: Option<Arc<Notify>>,
@@ -865,6 +926,7 @@ impl Clone for ToolManager {
             has_new_stuff: self.has_new_stuff.clone(),
             new_tool_specs: self.new_tool_specs.clone(),
             prompts: self.prompts.clone(),
+            prompt_clients: self.prompt_clients.clone(),
             tn_map: self.tn_map.clone(),
             schema: self.schema.clone(),
             is_interactive: self.is_interactive,
@@ -876,6 +938,21 @@ impl Clone for ToolManager {
 }
 
 impl ToolManager {
+    /// Tab completes the prompts of this tool manager's servers with the task of `previous`, which
+    /// is the only one listening to the input, e.g. when the servers are replaced by switching
+    /// agents.
+    pub fn take_over_prompt_completion(&mut self, previous: &ToolManager) {
+        match (previous.prompt_clients.write(), self.prompt_clients.read()) {
+            (Ok(mut previous_clients), Ok(clients)) => *previous_clients = clients.clone(),
+            _ => error!("Error retrieving lock on prompt clients"),
+        }
+        if let Ok(mut prompts) = previous.prompts.write() {
+            prompts.clear();
+        }
+        self.prompt_clients = previous.prompt_clients.clone();
+        self.prompts = previous.prompts.clone();
+    }
+
     pub async fn load_tools(
         &mut self,
         os: &mut Os,
@@ -884,7 +961,8 @@ impl ToolManager {
         let tx = self.loading_status_sender.take();
         let notify = self.notify.take();
         self.schema = {
-            let tool_list = &self.agent.lock().await.tools;
+            let agent = self.agent.lock().await;
+            let tool_list = &agent.tools;
             let is_allow_all = tool_list.len() == 1 && tool_list.first().is_some_and(|n| n == "*");
             let is_allow_native = tool_list.iter().any(|t| t.as_str() == "@builtin");
             let mut tool_specs =
@@ -903,6 +981,60 @@ impl ToolManager {
             if !crate::cli::chat::tools::knowledge::Knowledge::is_enabled(os) {
                 tool_specs.remove("knowledge");
             }
+            // The knowledge base tool is only offered to agents that configure knowledge bases, whose
+            // names are listed for the model to choose from.
+            // Likewise, the read-only AWS and database tools are only offered to agents that configure the
+            // profile or connections they use.
+            if let Some(settings) = AwsReadSettings::configured(&agent) {
+                if let Some(spec) = tool_specs.get_mut("aws_read") {
+                    spec.description
+                        .push_str(&format!(" Calls are made with the AWS profile '{}'.", settings.profile));
+                }
+            } else {
+                tool_specs.remove("aws_read");
+            }
+            if let Some(settings) = DbQuerySettings::configured(&agent) {
+                if let Some(spec) = tool_specs.get_mut("db_query") {
+                    let names = settings.connections.keys().map(String::as_str).collect::<Vec<_>>();
+                    spec.description
+                        .push_str(&format!(" The configured connections are: {}.", names.join(", ")));
+                }
+            } else {
+                tool_specs.remove("db_query");
+            }
+            if let Some(settings) = HttpRequestSettings::configured(&agent) {
+                if let Some(spec) = tool_specs.get_mut("http_request") {
+                    spec.description.push_str(&format!(
+                        " The allowed hosts are: {}.",
+                        settings.allowed_hosts.join(", ")
+                    ));
+                    if !settings.open_api.is_empty() {
+                        let names = settings.open_api.keys().map(String::as_str).collect::<Vec<_>>();
+                        spec.description
+                            .push_str(&format!(" The configured OpenAPI specs are: {}.", names.join(", ")));
+                    }
+                }
+            } else {
+                tool_specs.remove("http_request");
+            }
+            if let Some(settings) = BrowserSettings::configured(&agent) {
+                if let Some(spec) = tool_specs.get_mut("browser") {
+                    spec.description.push_str(&format!(
+                        " The allowed hosts are: {}.",
+                        settings.allowed_hosts.join(", ")
+                    ));
+                }
+            } else {
+                tool_specs.remove("browser");
+            }
+            let knowledge_bases = KnowledgeBase::configured(&agent);
+            if knowledge_bases.is_empty() {
+                tool_specs.remove("kb_search");
+            } else if let Some(spec) = tool_specs.get_mut("kb_search") {
+                let names = knowledge_bases.iter().map(|kb| kb.name.as_str()).collect::<Vec<_>>();
+                spec.description
+                    .push_str(&format!(" The configured knowledge bases are: {}.", names.join(", ")));
+            }
 
             #[cfg(windows)]
             {
@@ -925,6 +1057,10 @@ impl ToolManager {
                     "summary": {
                         "type": "string",
                         "description": "A brief explanation of what the command does"
+                    },
+                    "background": {
+                        "type": "boolean",
+                        "description": "Run the command in the background and return immediately with a process id instead of waiting for it to exit. Use this for long-running commands such as dev servers or file watchers. Use process_output to read its logs and process_kill to stop it."
                     }
                     },
                         "required": ["command"]})),
@@ -1044,9 +1180,23 @@ impl ToolManager {
             status: ToolResultStatus::Error,
         };
 
+        // Check the arguments against the schema first, for errors that name the offending field
+        // rather than failing deep inside of deserialization.
+        if let Some(spec) = self.schema.get(&value.name) {
+            if let Err(message) = validate_tool_args(&value.name, &spec.input_schema.0, &value.args) {
+                return Err(ToolResult {
+                    tool_use_id: value.id,
+                    content: vec![ToolResultContentBlock::Text(message)],
+                    status: ToolResultStatus::Error,
+                });
+            }
+        }
+
         Ok(match value.name.as_str() {
             "fs_read" => Tool::FsRead(serde_json::from_value::<FsRead>(value.args).map_err(map_err)?),
             "fs_write" => Tool::FsWrite(serde_json::from_value::<FsWrite>(value.args).map_err(map_err)?),
+            "list_files" => Tool::ListFiles(serde_json::from_value::<ListFiles>(value.args).map_err(map_err)?),
+            "rename_symbol" => Tool::RenameSymbol(serde_json::from_value::<RenameSymbol>(value.args).map_err(map_err)?),
             #[cfg(windows)]
             "execute_cmd" => {
                 Tool::ExecuteCommand(serde_json::from_value::<ExecuteCommand>(value.args).map_err(map_err)?)
@@ -1056,9 +1206,30 @@ impl ToolManager {
                 Tool::ExecuteCommand(serde_json::from_value::<ExecuteCommand>(value.args).map_err(map_err)?)
             },
             "use_aws" => Tool::UseAws(serde_json::from_value::<UseAws>(value.args).map_err(map_err)?),
+            "aws_read" => Tool::AwsRead(serde_json::from_value::<AwsRead>(value.args).map_err(map_err)?),
+            "infra_plan" => Tool::InfraPlan(serde_json::from_value::<InfraPlan>(value.args).map_err(map_err)?),
+            "run_tests" => Tool::RunTests(serde_json::from_value::<RunTests>(value.args).map_err(map_err)?),
+            "diagnostics" => Tool::Diagnostics(serde_json::from_value::<Diagnostics>(value.args).map_err(map_err)?),
+            "scratchpad" => Tool::Scratchpad(serde_json::from_value::<Scratchpad>(value.args).map_err(map_err)?),
+            "db_query" => Tool::DbQuery(serde_json::from_value::<DbQuery>(value.args).map_err(map_err)?),
+            "browser" => Tool::Browser(serde_json::from_value::<Browser>(value.args).map_err(map_err)?),
+            "http_request" => Tool::HttpRequest(serde_json::from_value::<HttpRequest>(value.args).map_err(map_err)?),
+            "logs_tail" => Tool::LogsTail(serde_json::from_value::<LogsTail>(value.args).map_err(map_err)?),
             "report_issue" => Tool::GhIssue(serde_json::from_value::<GhIssue>(value.args).map_err(map_err)?),
             "thinking" => Tool::Thinking(serde_json::from_value::<Thinking>(value.args).map_err(map_err)?),
             "knowledge" => Tool::Knowledge(serde_json::from_value::<Knowledge>(value.args).map_err(map_err)?),
+            "update_plan" => Tool::UpdatePlan(serde_json::from_value::<UpdatePlan>(value.args).map_err(map_err)?),
+            "semantic_search" => {
+                Tool::SemanticSearch(serde_json::from_value::<SemanticSearch>(value.args).map_err(map_err)?)
+            },
+            "kb_search" => Tool::KbSearch(serde_json::from_value::<KbSearch>(value.args).map_err(map_err)?),
+            "process_list" => Tool::Process(Process::List),
+            "process_output" => Tool::Process(Process::Output(
+                serde_json::from_value::<ProcessOutput>(value.args).map_err(map_err)?,
+            )),
+            "process_kill" => Tool::Process(Process::Kill(
+                serde_json::from_value::<ProcessKill>(value.args).map_err(map_err)?,
+            )),
             // Note that this name is namespaced with server_name{DELIMITER}tool_name
             name => {
                 // Note: tn_map also has tools that underwent no transformation. In otherwords, if
@@ -1069,12 +1240,10 @@ impl ToolManager {
                 } = match self.tn_map.get(name) {
                     Some(tool_info) => Ok::<&ToolInfo, ToolResult>(tool_info),
                     None => {
-                        // No match, we throw an error
+                        // No match, we throw an error that points the model to the right tool
                         Err(ToolResult {
                             tool_use_id: value.id.clone(),
-                            content: vec![ToolResultContentBlock::Text(format!(
-                                "No tool with \"{name}\" is found"
-                            ))],
+                            content: vec![ToolResultContentBlock::Text(self.unknown_tool_message(name))],
                             status: ToolResultStatus::Error,
                         })
                     },
@@ -1108,6 +1277,24 @@ impl ToolManager {
         })
     }
 
+    /// Builds the error returned to the model when it calls a tool that does not exist, listing
+    /// the closest tool names and all available tools so that it can correct the call.
+    fn unknown_tool_message(&self, name: &str) -> String {
+        let mut available = self.schema.keys().map(String::as_str).collect::<Vec<_>>();
+        available.sort_unstable();
+        let mut message = format!("No tool with \"{name}\" is found.");
+        let closest = closest_tool_names(name, &available);
+        if !closest.is_empty() {
+            let closest = closest.iter().map(|n| format!("\"{n}\"")).collect::<Vec<_>>();
+            message.push_str(&format!(" Did you mean {}?", closest.join(" or ")));
+        }
+        message.push_str(&format!(
+            " Only call one of the available tools: {}.",
+            available.join(", ")
+        ));
+        message
+    }
+
     /// Updates tool managers various states with new information
     pub async fn update(&mut self) {
         // A hashmap of <tool name, tool spec>
@@ -1607,10 +1794,46 @@ fn queue_incomplete_load_message(
     )?)
 }
 
+/// Maximum number of suggestions given for an unknown tool name.
+const MAX_TOOL_NAME_SUGGESTIONS: usize = 3;
+
+/// Returns the tool names closest to `name` by edit distance, closest first.
+///
+/// MCP tools are also matched by their name without the server prefix, since models often drop it.
+fn closest_tool_names<'a>(name: &str, available: &[&'a str]) -> Vec<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = (name.len() / 2).max(2);
+    let mut matches = available
+        .iter()
+        .filter_map(|candidate| {
+            let lowercase = candidate.to_lowercase();
+            let unprefixed = lowercase.rsplit(NAMESPACE_DELIMITER).next().unwrap_or(&lowercase);
+            let distance = strsim::levenshtein(&name, &lowercase).min(strsim::levenshtein(&name, unprefixed));
+            (distance <= max_distance).then_some((distance, *candidate))
+        })
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches
+        .into_iter()
+        .take(MAX_TOOL_NAME_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_closest_tool_names() {
+        let available = ["execute_bash", "fs_read", "fs_write", "github___get_issue", "use_aws"];
+        assert_eq!(closest_tool_names("fs_reed", &available), vec!["fs_read"]);
+        assert_eq!(closest_tool_names("execute_cmd", &available), vec!["execute_bash"]);
+        assert_eq!(closest_tool_names("Use_AWS", &available), vec!["use_aws"]);
+        assert_eq!(closest_tool_names("get_issue", &available), vec!["github___get_issue"]);
+        assert!(closest_tool_names("browse_web", &available).is_empty());
+    }
+
     #[test]
     fn test_sanitize_server_name() {
         let regex = regex::Regex::new(VALID_TOOL_NAME).unwrap();
@@ -0,0 +1,223 @@
+//! Statistics of the uses of each tool in a project, kept across sessions so that users can see
+//! which tools they always approve, and notice when a tool starts failing.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use time::OffsetDateTime;
+use tracing::warn;
+
+use super::audit::{
+    ApprovalDecision,
+    ExitStatus,
+};
+use crate::os::Os;
+
+/// Uses approved by the user after which a tool that was never rejected is suggested for
+/// `allowedTools`.
+const SUGGEST_AFTER_APPROVALS: u64 = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolStats {
+    pub tools: BTreeMap<String, ToolStat>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToolStat {
+    /// Uses allowed without prompting.
+    pub trusted: u64,
+    /// Uses approved by the user when prompted.
+    pub approved: u64,
+    /// Uses refused by the user when prompted.
+    pub rejected: u64,
+    /// Uses refused by the agent's tool settings or workspace boundary.
+    pub denied: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Sum of the durations of the executed uses, in milliseconds.
+    pub total_duration_ms: u64,
+    /// Unix timestamp in seconds of the last use.
+    pub last_used_at: i64,
+}
+
+impl ToolStat {
+    pub fn uses(&self) -> u64 {
+        self.trusted + self.approved + self.rejected + self.denied
+    }
+
+    /// Share of the uses the user was prompted for that were approved.
+    pub fn approval_rate(&self) -> Option<f64> {
+        let prompted = self.approved + self.rejected;
+        (prompted > 0).then(|| self.approved as f64 / prompted as f64)
+    }
+
+    /// Share of the executed uses that failed.
+    pub fn failure_rate(&self) -> Option<f64> {
+        let executed = self.succeeded + self.failed;
+        (executed > 0).then(|| self.failed as f64 / executed as f64)
+    }
+
+    pub fn average_duration_ms(&self) -> Option<u64> {
+        let executed = self.succeeded + self.failed;
+        (executed > 0).then(|| self.total_duration_ms / executed)
+    }
+}
+
+impl ToolStats {
+    pub fn record(&mut self, tool: &str, decision: ApprovalDecision, status: ExitStatus, duration_ms: Option<u64>) {
+        let stat = self.tools.entry(tool.to_string()).or_default();
+        match decision {
+            ApprovalDecision::Trusted => stat.trusted += 1,
+            ApprovalDecision::Approved => stat.approved += 1,
+            ApprovalDecision::Rejected => stat.rejected += 1,
+            ApprovalDecision::Denied => stat.denied += 1,
+        }
+        match status {
+            ExitStatus::Success => stat.succeeded += 1,
+            ExitStatus::Error => stat.failed += 1,
+            ExitStatus::NotExecuted => {},
+        }
+        stat.total_duration_ms += duration_ms.unwrap_or_default();
+        stat.last_used_at = OffsetDateTime::now_utc().unix_timestamp();
+    }
+
+    /// Tools the user was prompted for and approved every time, which could be added to the
+    /// `allowedTools` of the agent.
+    pub fn suggested_allowed_tools(&self) -> Vec<&str> {
+        self.tools
+            .iter()
+            .filter(|(_, stat)| stat.approved >= SUGGEST_AFTER_APPROVALS && stat.rejected == 0)
+            .map(|(tool, _)| tool.as_str())
+            .collect()
+    }
+
+    /// Formats the statistics as a table, most used tools first.
+    pub fn to_table(&self) -> String {
+        let percent = |rate: Option<f64>| rate.map_or("-".to_string(), |rate| format!("{:.0}%", rate * 100.0));
+        let mut rows = vec![[
+            "Tool".to_string(),
+            "Uses".to_string(),
+            "Trusted".to_string(),
+            "Approved".to_string(),
+            "Denied".to_string(),
+            "Failed".to_string(),
+            "Avg time".to_string(),
+        ]];
+        let mut tools = self.tools.iter().collect::<Vec<_>>();
+        tools.sort_by_key(|(_, stat)| std::cmp::Reverse(stat.uses()));
+        for (tool, stat) in tools {
+            rows.push([
+                tool.clone(),
+                stat.uses().to_string(),
+                stat.trusted.to_string(),
+                percent(stat.approval_rate()),
+                (stat.rejected + stat.denied).to_string(),
+                percent(stat.failure_rate()),
+                stat.average_duration_ms()
+                    .map_or("-".to_string(), |ms| format!("{:.1}s", ms as f64 / 1000.0)),
+            ]);
+        }
+
+        let mut widths = [0; 7];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut table = String::new();
+        for row in rows {
+            let mut line = format!("{:<width$}", row[0], width = widths[0]);
+            for (cell, width) in row.iter().zip(widths).skip(1) {
+                line.push_str(&format!("  {cell:>width$}"));
+            }
+            table.push_str(line.trim_end());
+            table.push('\n');
+        }
+        table
+    }
+}
+
+/// Returns the statistics of the tools used in `dir`.
+pub fn load(os: &Os, dir: &Path) -> ToolStats {
+    match os.database.get_tool_stats(dir) {
+        Ok(stats) => stats.unwrap_or_default(),
+        Err(err) => {
+            warn!(?err, "failed to read the tool statistics of {}", dir.display());
+            ToolStats::default()
+        },
+    }
+}
+
+/// Records a use of `tool` in the statistics of the current working directory.
+///
+/// Failing to write the statistics is not fatal to the session, so errors are only logged.
+pub fn record_tool_use(os: &Os, tool: &str, decision: ApprovalDecision, status: ExitStatus, duration_ms: Option<u64>) {
+    let Ok(dir) = os.env.current_dir() else {
+        return;
+    };
+    let mut stats = load(os, &dir);
+    stats.record(tool, decision, status, duration_ms);
+    if let Err(err) = os.database.set_tool_stats(&dir, &stats) {
+        warn!(?err, "failed to write the tool statistics of {}", dir.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut stats = ToolStats::default();
+        for _ in 0..SUGGEST_AFTER_APPROVALS {
+            stats.record("fs_write", ApprovalDecision::Approved, ExitStatus::Success, Some(100));
+        }
+        stats.record("fs_read", ApprovalDecision::Trusted, ExitStatus::Success, Some(10));
+        stats.record("fs_read", ApprovalDecision::Trusted, ExitStatus::Error, Some(30));
+        stats.record(
+            "execute_bash",
+            ApprovalDecision::Approved,
+            ExitStatus::Success,
+            Some(2000),
+        );
+        stats.record(
+            "execute_bash",
+            ApprovalDecision::Rejected,
+            ExitStatus::NotExecuted,
+            None,
+        );
+
+        let fs_read = &stats.tools["fs_read"];
+        assert_eq!(fs_read.uses(), 2);
+        assert_eq!(fs_read.approval_rate(), None);
+        assert_eq!(fs_read.failure_rate(), Some(0.5));
+        assert_eq!(fs_read.average_duration_ms(), Some(20));
+        assert_eq!(stats.tools["execute_bash"].approval_rate(), Some(0.5));
+        assert_eq!(stats.suggested_allowed_tools(), vec!["fs_write"]);
+
+        assert_eq!(
+            stats.to_table(),
+            "Tool          Uses  Trusted  Approved  Denied  Failed  Avg time
+fs_write         5        0      100%       0      0%      0.1s
+execute_bash     2        0       50%       1      0%      2.0s
+fs_read          2        2         -       0     50%      0.0s
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_tool_use() {
+        let os = Os::new().await.unwrap();
+        record_tool_use(&os, "fs_read", ApprovalDecision::Trusted, ExitStatus::Success, Some(5));
+        record_tool_use(&os, "fs_read", ApprovalDecision::Denied, ExitStatus::NotExecuted, None);
+        let stats = load(&os, &os.env.current_dir().unwrap());
+        assert_eq!(stats.tools["fs_read"].uses(), 2);
+        assert_eq!(stats.tools["fs_read"].denied, 1);
+    }
+}
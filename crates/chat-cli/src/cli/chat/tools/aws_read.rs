@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::process::Stdio;
+
+use bstr::ByteSlice;
+use convert_case::{
+    Case,
+    Casing,
+};
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::error;
+
+use super::use_aws::aws_command;
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+use crate::cli::agent::Agent;
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+
+/// Prefixes of the operations the tool may call, in kebab case.
+const READONLY_PREFIXES: [&str; 3] = ["describe-", "list-", "get-"];
+/// Read-only operations that return secrets or credentials, which the tool never calls.
+const SENSITIVE_OPERATIONS: [&str; 7] = [
+    "get-secret-value",
+    "get-login-password",
+    "get-session-token",
+    "get-federation-token",
+    "get-authorization-token",
+    "get-role-credentials",
+    "get-credentials-for-identity",
+];
+/// Parameters that would escape the profile and output format set by the tool.
+const RESERVED_PARAMETERS: [&str; 7] = [
+    "profile",
+    "region",
+    "endpoint-url",
+    "output",
+    "query",
+    "no-verify-ssl",
+    "with-decryption",
+];
+/// Items of each array kept when the output is summarized.
+const SUMMARY_ITEMS: [usize; 4] = [20, 10, 5, 1];
+/// Characters of each string kept when the output is summarized.
+const SUMMARY_STRING_LEN: usize = 500;
+
+/// The `aws_read` tool settings of an agent, which scope the tool to a named profile, e.g.
+///
+/// ```json
+/// "toolsSettings": {
+///   "aws_read": { "profile": "readonly", "region": "us-east-1", "allowedServices": ["ec2", "s3api"] }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AwsReadSettings {
+    pub profile: String,
+    pub region: Option<String>,
+    /// Services the tool may call, or all services if empty.
+    #[serde(default)]
+    pub allowed_services: Vec<String>,
+}
+
+impl AwsReadSettings {
+    /// Returns the settings configured for `agent`, if any.
+    pub fn configured(agent: &Agent) -> Option<Self> {
+        let settings = agent.tools_settings.get("aws_read")?;
+        match serde_json::from_value::<Self>(settings.clone()) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                error!("Failed to deserialize tool settings for aws_read: {:?}", e);
+                None
+            },
+        }
+    }
+}
+
+/// The aws_read tool calls read-only AWS APIs with the profile configured for the agent.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AwsRead {
+    pub service_name: String,
+    pub operation_name: String,
+    pub parameters: Option<HashMap<String, Value>>,
+    pub region: Option<String>,
+    pub label: Option<String>,
+    /// The profile and region that calls are made with, and the services they are allowed to, from
+    /// the aws_read settings of the agent. Without a profile, no call is made.
+    #[serde(skip)]
+    pub settings: Option<AwsReadSettings>,
+}
+
+impl AwsRead {
+    fn operation(&self) -> String {
+        self.operation_name.to_case(Case::Kebab)
+    }
+
+    fn region(&self) -> Option<&str> {
+        self.region
+            .as_deref()
+            .or_else(|| self.settings.as_ref().and_then(|s| s.region.as_deref()))
+    }
+
+    /// Returns the parameters as CLI arguments in kebab case.
+    fn cli_parameters(&self) -> Vec<(String, String)> {
+        self.parameters
+            .iter()
+            .flatten()
+            .map(|(name, value)| {
+                let name = name.trim_start_matches("--").to_case(Case::Kebab);
+                let value = value.as_str().map(|s| s.to_string()).unwrap_or(value.to_string());
+                (name, value)
+            })
+            .collect()
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        queue!(
+            output,
+            style::Print("Reading from AWS: "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!("{} {}", self.service_name, self.operation())),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        for (name, value) in self.cli_parameters() {
+            queue!(output, style::Print(format!(" --{name} {value}")))?;
+        }
+        if let Some(settings) = &self.settings {
+            queue!(output, style::Print(format!("\nProfile: {}", settings.profile)))?;
+        }
+        if let Some(region) = self.region() {
+            queue!(output, style::Print(format!("\nRegion: {region}")))?;
+        }
+        if let Some(label) = &self.label {
+            queue!(output, style::Print(format!("\nLabel: {label}")))?;
+        }
+        queue!(output, style::Print("\n"))?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, _os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let Some(settings) = &self.settings else {
+            bail!("No profile is configured for aws_read");
+        };
+        let mut command = aws_command();
+        command
+            .arg("--profile")
+            .arg(&settings.profile)
+            .args(["--output", "json", "--no-cli-pager"]);
+        if let Some(region) = self.region() {
+            command.arg("--region").arg(region);
+        }
+        command.arg(&self.service_name).arg(self.operation());
+        for (name, value) in self.cli_parameters() {
+            command.arg(format!("--{name}"));
+            if !value.is_empty() {
+                command.arg(value);
+            }
+        }
+        let output = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .wrap_err("Unable to run the AWS CLI")?
+            .wait_with_output()
+            .await?;
+        if !output.status.success() {
+            bail!("{}", output.stderr.to_str_lossy().trim());
+        }
+
+        let stdout = output.stdout.to_str_lossy();
+        let text = match serde_json::from_str::<Value>(&stdout) {
+            Ok(value) => summarize(&value, MAX_TOOL_RESPONSE_SIZE / 3),
+            Err(_) => stdout.to_string(),
+        };
+        Ok(InvokeOutput {
+            output: OutputKind::Text(text),
+        })
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        let Some(settings) = &self.settings else {
+            bail!("No profile is configured for aws_read in the current agent");
+        };
+        if !settings.allowed_services.is_empty() && !settings.allowed_services.contains(&self.service_name) {
+            bail!(
+                "The service '{}' is not allowed, expected one of: {}",
+                self.service_name,
+                settings.allowed_services.join(", ")
+            );
+        }
+        let operation = self.operation();
+        if !READONLY_PREFIXES.iter().any(|prefix| operation.starts_with(prefix)) {
+            bail!("Only describe, list, and get operations are allowed, use use_aws for '{operation}'");
+        }
+        if SENSITIVE_OPERATIONS.contains(&operation.as_str()) {
+            bail!("The operation '{operation}' returns secrets or credentials and is not allowed");
+        }
+        for (name, _) in self.cli_parameters() {
+            if RESERVED_PARAMETERS.contains(&name.as_str()) {
+                bail!("The parameter '--{name}' is not allowed");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns `value` as JSON of at most `max_len` bytes, keeping only the first items of long arrays
+/// and the start of long strings, preceded by the number of items of the top-level arrays.
+fn summarize(value: &Value, max_len: usize) -> String {
+    let mut text = String::new();
+    if let Value::Object(map) = value {
+        for (key, value) in map {
+            if let Value::Array(items) = value {
+                let _ = writeln!(text, "{key}: {} items", items.len());
+            }
+        }
+    }
+    for max_items in SUMMARY_ITEMS {
+        let json = serde_json::to_string_pretty(&truncate_value(value, max_items)).unwrap_or_default();
+        if text.len() + json.len() <= max_len || max_items == 1 {
+            text.push_str(&json);
+            break;
+        }
+    }
+    truncate_safe_in_place(&mut text, max_len, " ... truncated");
+    text
+}
+
+fn truncate_value(value: &Value, max_items: usize) -> Value {
+    match value {
+        Value::Array(items) => {
+            let mut truncated = items
+                .iter()
+                .take(max_items)
+                .map(|item| truncate_value(item, max_items))
+                .collect::<Vec<_>>();
+            if items.len() > max_items {
+                truncated.push(Value::from(format!("... {} more items", items.len() - max_items)));
+            }
+            Value::Array(truncated)
+        },
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| (key.clone(), truncate_value(value, max_items)))
+                .collect(),
+        ),
+        Value::String(s) if s.len() > SUMMARY_STRING_LEN => {
+            let mut s = s.clone();
+            truncate_safe_in_place(&mut s, SUMMARY_STRING_LEN, "...");
+            Value::String(s)
+        },
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aws_read(value: Value) -> AwsRead {
+        let mut tool = serde_json::from_value::<AwsRead>(value).unwrap();
+        tool.settings = Some(AwsReadSettings {
+            profile: "readonly".to_string(),
+            region: Some("us-west-2".to_string()),
+            allowed_services: vec!["ec2".to_string(), "secretsmanager".to_string()],
+        });
+        tool
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let os = Os::new().await.unwrap();
+        let mut tool = aws_read(serde_json::json!({
+            "service_name": "ec2",
+            "operation_name": "DescribeInstances",
+            "parameters": { "instanceIds": "i-123" }
+        }));
+        assert!(tool.validate(&os).await.is_ok());
+        assert_eq!(tool.cli_parameters(), vec![(
+            "instance-ids".to_string(),
+            "i-123".to_string()
+        )]);
+        assert_eq!(tool.region(), Some("us-west-2"));
+
+        for (service, operation, parameters) in [
+            ("ec2", "terminate-instances", serde_json::json!({})),
+            ("lambda", "list-functions", serde_json::json!({})),
+            ("secretsmanager", "get_secret_value", serde_json::json!({})),
+            ("ec2", "describe-instances", serde_json::json!({ "--profile": "admin" })),
+        ] {
+            let mut tool = aws_read(serde_json::json!({
+                "service_name": service,
+                "operation_name": operation,
+                "parameters": parameters
+            }));
+            assert!(tool.validate(&os).await.is_err(), "{service} {operation}");
+        }
+
+        let mut tool = aws_read(serde_json::json!({ "service_name": "ec2", "operation_name": "describe-vpcs" }));
+        tool.settings = None;
+        assert!(tool.validate(&os).await.is_err());
+    }
+
+    #[test]
+    fn test_summarize() {
+        let value = serde_json::json!({
+            "Reservations": (0..50).map(|i| serde_json::json!({ "Id": i, "Note": "x".repeat(1000) })).collect::<Vec<_>>(),
+            "NextToken": "abc"
+        });
+        let text = summarize(&value, 100_000);
+        assert!(text.starts_with("Reservations: 50 items\n"));
+        assert!(text.contains("... 30 more items"));
+        assert!(!text.contains(&"x".repeat(600)));
+
+        let text = summarize(&value, 2_000);
+        assert!(text.len() <= 2_000);
+        assert!(text.contains("... 49 more items"));
+    }
+}
@@ -0,0 +1,680 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::process::Stdio;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+    eyre,
+};
+use futures::{
+    SinkExt,
+    StreamExt,
+};
+use serde::Deserialize;
+use serde_json::{
+    Value,
+    json,
+};
+use tokio::io::{
+    AsyncBufReadExt,
+    BufReader,
+};
+use tokio::net::TcpStream;
+use tokio::process::Child;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{
+    MaybeTlsStream,
+    WebSocketStream,
+};
+use tracing::{
+    debug,
+    error,
+};
+use url::Url;
+
+use super::http_request::host_matches;
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+use crate::api_client::model::{
+    ImageBlock,
+    ImageFormat,
+    ImageSource,
+};
+use crate::cli::agent::Agent;
+use crate::cli::chat::consts::MAX_IMAGE_SIZE;
+use crate::cli::chat::util::images::{
+    ImageMetadata,
+    RichImageBlock,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+
+/// Browsers tried in order when the agent does not configure one.
+const EXECUTABLES: [&str; 6] = [
+    "google-chrome",
+    "google-chrome-stable",
+    "chromium",
+    "chromium-browser",
+    "microsoft-edge",
+    "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+];
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time given to the page after it loads to run its scripts and report errors.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+const DEFAULT_WIDTH: u32 = 1280;
+const DEFAULT_HEIGHT: u32 = 800;
+const MAX_CONSOLE_MESSAGES: usize = 20;
+
+/// Returns the title, URL, and a markdown rendering of the visible content of the page, or of the
+/// first element matching the selector passed as argument.
+const EXTRACT_SCRIPT: &str = r#"(selector) => {
+  const root = selector ? document.querySelector(selector) : document.body;
+  if (!root) return { title: document.title, url: location.href, text: null };
+  const SKIP = new Set(['SCRIPT', 'STYLE', 'NOSCRIPT', 'TEMPLATE', 'SVG', 'HEAD', 'IFRAME', 'CANVAS']);
+  const blocks = [];
+  let line = '';
+  const flush = () => {
+    const text = line.replace(/\s+/g, ' ').trim();
+    if (text) blocks.push(text);
+    line = '';
+  };
+  const walk = (node) => {
+    if (node.nodeType === Node.TEXT_NODE) { line += node.textContent; return; }
+    if (node.nodeType !== Node.ELEMENT_NODE || SKIP.has(node.tagName.toUpperCase())) return;
+    const style = getComputedStyle(node);
+    if (style.display === 'none' || style.visibility === 'hidden') return;
+    const children = () => node.childNodes.forEach(walk);
+    const tag = node.tagName.toUpperCase();
+    if (/^H[1-6]$/.test(tag)) { flush(); line = '#'.repeat(+tag[1]) + ' '; children(); flush(); return; }
+    switch (tag) {
+      case 'BR': flush(); return;
+      case 'HR': flush(); blocks.push('---'); return;
+      case 'PRE': flush(); blocks.push('```\n' + node.innerText.replace(/\n$/, '') + '\n```'); return;
+      case 'CODE': line += '`' + node.textContent + '`'; return;
+      case 'IMG': line += `![${node.alt || ''}]`; return;
+      case 'A': line += '['; children(); line += `](${node.href})`; return;
+      case 'LI': flush(); line = '- '; children(); flush(); return;
+      case 'TD': case 'TH': line += ' | '; children(); return;
+      case 'BUTTON': line += '[button: '; children(); line += ']'; return;
+      case 'INPUT': case 'TEXTAREA': case 'SELECT':
+        if (node.type === 'hidden') return;
+        line += `[${node.type || tag.toLowerCase()}${node.name ? ' ' + node.name : ''}: ${node.type === 'password' ? '***' : node.value || node.placeholder || ''}]`;
+        return;
+    }
+    const block = !style.display.startsWith('inline');
+    if (block) flush();
+    children();
+    if (block) flush();
+  };
+  walk(root);
+  flush();
+  return { title: document.title, url: location.href, text: blocks.join('\n') };
+}"#;
+
+/// The `browser` tool settings of an agent, e.g.
+///
+/// ```json
+/// "toolsSettings": {
+///   "browser": {
+///     "allowedHosts": ["localhost:3000", "*.staging.example.com"],
+///     "executable": "/usr/bin/chromium"
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserSettings {
+    /// Hosts pages may be loaded from, with the same patterns as `http_request`.
+    pub allowed_hosts: Vec<String>,
+    /// Path or name of a Chrome compatible browser.
+    pub executable: Option<String>,
+    /// Additional arguments of the browser, such as `--no-sandbox` in containers.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl BrowserSettings {
+    /// Returns the settings configured for `agent`, if it allows any host.
+    pub fn configured(agent: &Agent) -> Option<Self> {
+        let settings = agent.tools_settings.get("browser")?;
+        match serde_json::from_value::<Self>(settings.clone()) {
+            Ok(settings) if !settings.allowed_hosts.is_empty() => Some(settings),
+            Ok(_) => None,
+            Err(e) => {
+                error!("Failed to deserialize tool settings for browser: {:?}", e);
+                None
+            },
+        }
+    }
+
+    fn allows(&self, url: &Url) -> bool {
+        matches!(url.scheme(), "http" | "https") && self.allowed_hosts.iter().any(|pattern| host_matches(pattern, url))
+    }
+}
+
+/// The browser tool loads a page of an allowed host in a headless browser, and returns a screenshot
+/// along with the readable content of the page and the errors it logged.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Browser {
+    pub url: String,
+    /// Only extracts the content of the first element matching this CSS selector.
+    pub selector: Option<String>,
+    pub screenshot: Option<bool>,
+    /// Captures the whole page rather than the viewport.
+    pub full_page: Option<bool>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// The hosts that pages may be loaded from, and the browser that loads them, from the browser
+    /// settings of the agent. Without allowed hosts, no page is loaded.
+    #[serde(skip)]
+    pub settings: Option<BrowserSettings>,
+}
+
+/// The content of a loaded page.
+#[derive(Debug, Default, Deserialize)]
+struct Page {
+    title: String,
+    url: String,
+    text: Option<String>,
+}
+
+impl Browser {
+    fn settings(&self) -> Result<&BrowserSettings> {
+        self.settings
+            .as_ref()
+            .ok_or_else(|| eyre!("No hosts are allowed for browser in the current agent"))
+    }
+
+    fn viewport(&self) -> (u32, u32) {
+        (
+            self.width.unwrap_or(DEFAULT_WIDTH).clamp(320, 3840),
+            self.height.unwrap_or(DEFAULT_HEIGHT).clamp(240, 2160),
+        )
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        let (width, height) = self.viewport();
+        queue!(
+            output,
+            style::Print("Loading "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(&self.url),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(" in a headless browser ({width}x{height})")),
+        )?;
+        if let Some(selector) = &self.selector {
+            queue!(output, style::Print(format!(", extracting '{selector}'")))?;
+        }
+        queue!(output, style::Print("\n"))?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, _os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let settings = self.settings()?;
+        let mut browser = BrowserProcess::launch(settings).await?;
+        let result = self.load(&mut browser.cdp, settings).await;
+        browser.close().await;
+        let (page, console, screenshot) = result?;
+
+        let text = format_page(&page, self.selector.as_deref(), &console);
+        Ok(InvokeOutput {
+            output: match screenshot {
+                Some(image) => OutputKind::Mixed {
+                    text,
+                    images: vec![image],
+                },
+                None => OutputKind::Text(text),
+            },
+        })
+    }
+
+    async fn load(
+        &self,
+        cdp: &mut Cdp,
+        settings: &BrowserSettings,
+    ) -> Result<(Page, Vec<String>, Option<RichImageBlock>)> {
+        let target = cdp.call("Target.createTarget", json!({ "url": "about:blank" })).await?;
+        let target_id = target["targetId"].as_str().unwrap_or_default();
+        let session = cdp
+            .call(
+                "Target.attachToTarget",
+                json!({ "targetId": target_id, "flatten": true }),
+            )
+            .await?;
+        cdp.session = session["sessionId"].as_str().map(str::to_string);
+
+        // Documents are paused before being requested so that the page cannot navigate away from
+        // the allowed hosts, while the resources they use are loaded from anywhere.
+        cdp.allowed_hosts = settings.allowed_hosts.clone();
+        cdp.call(
+            "Fetch.enable",
+            json!({ "patterns": [{ "urlPattern": "*", "resourceType": "Document", "requestStage": "Request" }] }),
+        )
+        .await?;
+        for domain in ["Page.enable", "Runtime.enable", "Log.enable"] {
+            cdp.call(domain, json!({})).await?;
+        }
+        let (width, height) = self.viewport();
+        cdp.call(
+            "Emulation.setDeviceMetricsOverride",
+            json!({ "width": width, "height": height, "deviceScaleFactor": 1, "mobile": false }),
+        )
+        .await?;
+
+        let navigation = cdp.call("Page.navigate", json!({ "url": self.url })).await?;
+        if let Some(error) = navigation["errorText"].as_str() {
+            bail!("Unable to load {}: {error}", self.url);
+        }
+        if !cdp.wait_for_event("Page.loadEventFired", LOAD_TIMEOUT).await? {
+            debug!("{} did not finish loading in {:?}", self.url, LOAD_TIMEOUT);
+        }
+        // No event has an empty name, so this only handles the events of the page for a while.
+        cdp.wait_for_event("", SETTLE_TIME).await?;
+
+        let page = cdp
+            .call(
+                "Runtime.evaluate",
+                json!({
+                    "expression": format!("({EXTRACT_SCRIPT})({})", json!(self.selector)),
+                    "returnByValue": true,
+                }),
+            )
+            .await?;
+        if let Some(exception) = page.get("exceptionDetails") {
+            bail!("Unable to extract the content of the page: {}", exception["text"]);
+        }
+        let page = serde_json::from_value::<Page>(page["result"]["value"].clone()).unwrap_or_default();
+
+        let screenshot = match self.screenshot.unwrap_or(true) {
+            true => self.screenshot(cdp).await?,
+            false => None,
+        };
+        Ok((page, std::mem::take(&mut cdp.console), screenshot))
+    }
+
+    /// Captures a PNG screenshot, or a JPEG one if the PNG is too large to be sent to the model.
+    async fn screenshot(&self, cdp: &mut Cdp) -> Result<Option<RichImageBlock>> {
+        let full_page = self.full_page.unwrap_or_default();
+        for (format, image_format) in [("png", ImageFormat::Png), ("jpeg", ImageFormat::Jpeg)] {
+            let mut params = json!({ "format": format, "captureBeyondViewport": full_page });
+            if format == "jpeg" {
+                params["quality"] = json!(80);
+            }
+            let capture = cdp.call("Page.captureScreenshot", params).await?;
+            let bytes = BASE64.decode(capture["data"].as_str().unwrap_or_default())?;
+            if bytes.len() <= MAX_IMAGE_SIZE {
+                let size = bytes.len() as u64;
+                return Ok(Some((
+                    ImageBlock {
+                        format: image_format,
+                        source: ImageSource::Bytes(bytes),
+                    },
+                    ImageMetadata {
+                        filepath: String::new(),
+                        size,
+                        filename: format!("screenshot.{format}"),
+                    },
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        let settings = self.settings()?;
+        let url = Url::parse(&self.url).wrap_err_with(|| format!("Invalid URL '{}'", self.url))?;
+        if !settings.allows(&url) {
+            bail!(
+                "The host of '{url}' is not allowed, expected one of: {}",
+                settings.allowed_hosts.join(", ")
+            );
+        }
+        if let Some(selector) = &self.selector {
+            if selector.trim().is_empty() {
+                bail!("The selector must not be empty");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_page(page: &Page, selector: Option<&str>, console: &[String]) -> String {
+    let mut text = format!("Title: {}\nURL: {}\n\n", page.title, page.url);
+    match (&page.text, selector) {
+        (Some(content), _) if !content.is_empty() => {
+            let mut content = content.clone();
+            truncate_safe_in_place(
+                &mut content,
+                MAX_TOOL_RESPONSE_SIZE / 3,
+                "\n... truncated, use selector to only extract part of the page",
+            );
+            text.push_str(&content);
+        },
+        (None, Some(selector)) => text.push_str(&format!("No element matches '{selector}'")),
+        _ => text.push_str("(no visible content)"),
+    }
+    if !console.is_empty() {
+        text.push_str("\n\nConsole errors and warnings:\n");
+        text.push_str(&console.join("\n"));
+    }
+    text
+}
+
+/// A headless browser started for a single use of the tool, with its own profile.
+struct BrowserProcess {
+    child: Child,
+    cdp: Cdp,
+    _profile: tempfile::TempDir,
+}
+
+impl BrowserProcess {
+    async fn launch(settings: &BrowserSettings) -> Result<Self> {
+        let profile = tempfile::tempdir()?;
+        let executables = match &settings.executable {
+            Some(executable) => vec![executable.as_str()],
+            None => EXECUTABLES.to_vec(),
+        };
+        let mut child = None;
+        for executable in executables {
+            let spawned = tokio::process::Command::new(executable)
+                .args([
+                    "--headless=new",
+                    "--remote-debugging-port=0",
+                    "--no-first-run",
+                    "--no-default-browser-check",
+                    "--disable-gpu",
+                    "--hide-scrollbars",
+                    "--mute-audio",
+                ])
+                .arg(format!("--user-data-dir={}", profile.path().display()))
+                .args(&settings.args)
+                .arg("about:blank")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn();
+            match spawned {
+                Ok(spawned) => {
+                    child = Some(spawned);
+                    break;
+                },
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {},
+                Err(err) => return Err(err).wrap_err_with(|| format!("Unable to start {executable}")),
+            }
+        }
+        let Some(mut child) = child else {
+            bail!(
+                "No Chrome compatible browser was found, install one or set the executable of the browser tool settings"
+            );
+        };
+
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| eyre!("Unable to talk to the browser"))?;
+        let mut lines = BufReader::new(stderr).lines();
+        let mut output = Vec::new();
+        let endpoint = tokio::time::timeout(STARTUP_TIMEOUT, async {
+            while let Some(line) = lines.next_line().await? {
+                if let Some(endpoint) = devtools_endpoint(&line) {
+                    return Ok(Some(endpoint.to_string()));
+                }
+                output.push(line);
+            }
+            Ok::<_, std::io::Error>(None)
+        })
+        .await;
+        let endpoint = match endpoint {
+            Ok(Ok(Some(endpoint))) => endpoint,
+            Ok(result) => {
+                result?;
+                bail!("The browser exited:\n{}", output.join("\n"));
+            },
+            Err(_) => bail!("The browser did not start in {:?}", STARTUP_TIMEOUT),
+        };
+        // Later output is not needed, but the pipe has to be drained for the browser not to block.
+        tokio::spawn(async move { while let Ok(Some(_)) = lines.next_line().await {} });
+
+        let (ws, _) = tokio_tungstenite::connect_async(endpoint.as_str())
+            .await
+            .wrap_err("Unable to connect to the browser")?;
+        Ok(Self {
+            child,
+            cdp: Cdp {
+                ws,
+                next_id: 0,
+                session: None,
+                allowed_hosts: Vec::new(),
+                events: VecDeque::new(),
+                console: Vec::new(),
+            },
+            _profile: profile,
+        })
+    }
+
+    async fn close(mut self) {
+        self.cdp.session = None;
+        let _ = tokio::time::timeout(Duration::from_secs(2), self.cdp.call("Browser.close", json!({}))).await;
+        let _ = self.child.kill().await;
+    }
+}
+
+/// Returns the DevTools endpoint printed by the browser when it starts.
+fn devtools_endpoint(line: &str) -> Option<&str> {
+    line.strip_prefix("DevTools listening on ")
+        .map(str::trim)
+        .filter(|endpoint| endpoint.starts_with("ws://"))
+}
+
+/// A client of the Chrome DevTools Protocol, which handles the events the tool depends on while
+/// waiting for responses.
+struct Cdp {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+    /// The session of the page, to which commands are sent once attached.
+    session: Option<String>,
+    allowed_hosts: Vec<String>,
+    events: VecDeque<String>,
+    console: Vec<String>,
+}
+
+impl Cdp {
+    async fn send(&mut self, method: &str, params: Value) -> Result<u64> {
+        self.next_id += 1;
+        let mut message = json!({ "id": self.next_id, "method": method, "params": params });
+        if let Some(session) = &self.session {
+            message["sessionId"] = json!(session);
+        }
+        self.ws.send(Message::Text(message.to_string().into())).await?;
+        Ok(self.next_id)
+    }
+
+    async fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.send(method, params).await?;
+        loop {
+            let message = self.next_message().await?;
+            if message["id"].as_u64() == Some(id) {
+                if let Some(error) = message.get("error") {
+                    bail!("{method} failed: {}", error["message"]);
+                }
+                return Ok(message["result"].clone());
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for the event named `name`, returning whether it was received.
+    async fn wait_for_event(&mut self, name: &str, timeout: Duration) -> Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(index) = self.events.iter().position(|event| event == name) {
+                self.events.remove(index);
+                return Ok(true);
+            }
+            match tokio::time::timeout_at(deadline, self.next_message()).await {
+                Ok(message) => message?,
+                Err(_) => return Ok(false),
+            };
+        }
+    }
+
+    async fn next_message(&mut self) -> Result<Value> {
+        loop {
+            let message = match self.ws.next().await {
+                Some(Ok(Message::Text(text))) => serde_json::from_str::<Value>(&text)?,
+                Some(Ok(Message::Close(_))) | None => bail!("The browser closed the connection"),
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(err.into()),
+            };
+            if let Some(method) = message["method"].as_str() {
+                self.handle_event(method, &message["params"]).await?;
+            }
+            return Ok(message);
+        }
+    }
+
+    async fn handle_event(&mut self, method: &str, params: &Value) -> Result<()> {
+        match method {
+            "Fetch.requestPaused" => {
+                let request_id = params["requestId"].clone();
+                let allowed = Url::parse(params["request"]["url"].as_str().unwrap_or_default()).is_ok_and(|url| {
+                    matches!(url.scheme(), "http" | "https")
+                        && self.allowed_hosts.iter().any(|pattern| host_matches(pattern, &url))
+                });
+                if allowed {
+                    self.send("Fetch.continueRequest", json!({ "requestId": request_id }))
+                        .await?;
+                } else {
+                    self.push_console(format!(
+                        "[blocked] navigation to {} is not allowed",
+                        params["request"]["url"].as_str().unwrap_or_default()
+                    ));
+                    self.send(
+                        "Fetch.failRequest",
+                        json!({ "requestId": request_id, "errorReason": "BlockedByClient" }),
+                    )
+                    .await?;
+                }
+            },
+            "Runtime.consoleAPICalled" if matches!(params["type"].as_str(), Some("error" | "warning" | "assert")) => {
+                let args = params["args"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|arg| match &arg["value"] {
+                        Value::String(value) => value.clone(),
+                        Value::Null => arg["description"].as_str().unwrap_or_default().to_string(),
+                        value => value.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                self.push_console(format!(
+                    "[{}] {}",
+                    params["type"].as_str().unwrap_or_default(),
+                    args.join(" ")
+                ));
+            },
+            "Runtime.exceptionThrown" => {
+                let details = &params["exceptionDetails"];
+                let message = details["exception"]["description"]
+                    .as_str()
+                    .or(details["text"].as_str())
+                    .unwrap_or_default();
+                self.push_console(format!("[exception] {message}"));
+            },
+            "Log.entryAdded" if matches!(params["entry"]["level"].as_str(), Some("error" | "warning")) => {
+                let entry = &params["entry"];
+                let mut message = format!(
+                    "[{}] {}",
+                    entry["level"].as_str().unwrap_or_default(),
+                    entry["text"].as_str().unwrap_or_default()
+                );
+                if let Some(url) = entry["url"].as_str() {
+                    message.push_str(&format!(" ({url})"));
+                }
+                self.push_console(message);
+            },
+            _ => self.events.push_back(method.to_string()),
+        }
+        Ok(())
+    }
+
+    fn push_console(&mut self, message: String) {
+        if self.console.len() < MAX_CONSOLE_MESSAGES {
+            self.console.push(message);
+        } else if self.console.len() == MAX_CONSOLE_MESSAGES {
+            self.console.push("... more messages omitted".to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_devtools_endpoint() {
+        assert_eq!(
+            devtools_endpoint("DevTools listening on ws://127.0.0.1:41235/devtools/browser/4f1c\n"),
+            Some("ws://127.0.0.1:41235/devtools/browser/4f1c")
+        );
+        assert_eq!(devtools_endpoint("[0101/000000.000:WARNING:gpu_init.cc] failed"), None);
+    }
+
+    #[test]
+    fn test_format_page() {
+        let page = Page {
+            title: "Home".to_string(),
+            url: "http://localhost:3000/".to_string(),
+            text: Some("# Welcome\n- [Docs](http://localhost:3000/docs)".to_string()),
+        };
+        assert_eq!(
+            format_page(&page, None, &["[error] Failed to load resource: 404".to_string()]),
+            "Title: Home\nURL: http://localhost:3000/\n\n# Welcome\n- [Docs](http://localhost:3000/docs)\n\nConsole errors and warnings:\n[error] Failed to load resource: 404"
+        );
+        let page = Page { text: None, ..page };
+        assert_eq!(
+            format_page(&page, Some("#app"), &[]),
+            "Title: Home\nURL: http://localhost:3000/\n\nNo element matches '#app'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let os = Os::new().await.unwrap();
+        let mut tool = Browser {
+            url: "http://localhost:3000/login".to_string(),
+            selector: None,
+            screenshot: None,
+            full_page: None,
+            width: None,
+            height: None,
+            settings: None,
+        };
+        assert!(tool.validate(&os).await.is_err());
+        tool.settings = Some(BrowserSettings {
+            allowed_hosts: vec!["localhost:3000".to_string()],
+            executable: None,
+            args: Vec::new(),
+        });
+        assert!(tool.validate(&os).await.is_ok());
+        tool.url = "http://localhost:8080/".to_string();
+        assert!(tool.validate(&os).await.is_err());
+        tool.url = "file:///etc/passwd".to_string();
+        assert!(tool.validate(&os).await.is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::time::Duration;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::Deserialize;
+
+use super::{
+    InvokeOutput,
+    OutputKind,
+    sanitize_path_tool_arg,
+};
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::lsp::{
+    self,
+    Diagnostic,
+    Language,
+    Severity,
+};
+use crate::os::Os;
+
+/// How long to wait for the language server to finish analyzing, which includes loading the
+/// project the first time.
+const ANALYSIS_TIMEOUT: Duration = Duration::from_secs(90);
+/// Diagnostics returned to the model.
+const MAX_DIAGNOSTICS: usize = 100;
+
+/// The diagnostics tool returns the errors and warnings reported by the language server of a
+/// project for a file or for the whole project.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Diagnostics {
+    /// File to return the diagnostics of, returns the diagnostics of the project if not given.
+    pub path: Option<String>,
+    /// Detected from the file or the project if not given.
+    pub language: Option<Language>,
+}
+
+impl Diagnostics {
+    /// Returns the language, the file if any, and the project root to get the diagnostics of.
+    fn target(&self, os: &Os) -> Result<(Language, Option<PathBuf>, PathBuf)> {
+        let cwd = os.fs.chroot_path(os.env.current_dir()?);
+        let file = self
+            .path
+            .as_ref()
+            .map(|path| os.fs.chroot_path(sanitize_path_tool_arg(os, path)));
+        let language = self
+            .language
+            .or_else(|| file.as_deref().and_then(Language::from_path))
+            .or_else(|| Language::detect(&cwd))
+            .ok_or_else(|| eyre!("Could not detect the language, specify it with 'language'"))?;
+        let root = language
+            .find_root(file.as_deref().and_then(Path::parent).unwrap_or(&cwd))
+            .unwrap_or(cwd);
+        Ok((language, file, root))
+    }
+
+    /// Language servers run build scripts and macros of the project, so using them needs to be
+    /// approved unless the agent trusts the tool.
+    pub fn eval_perm(agent: &Agent) -> PermissionEvalResult {
+        if agent.allowed_tools.contains("diagnostics") {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    pub fn queue_description(&self, os: &Os, output: &mut impl Write) -> Result<()> {
+        let (language, _, root) = self.target(os)?;
+        queue!(
+            output,
+            style::Print("Getting the diagnostics of "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(self.path.as_deref().unwrap_or("the project")),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(
+                " from {} in {}\n",
+                language.server_command()[0],
+                root.display()
+            )),
+        )?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let (language, file, root) = self.target(os)?;
+        let mut server = lsp::server(language, &root).await?;
+        match &file {
+            Some(file) => server.sync_file(file, tokio::fs::read_to_string(file).await?).await?,
+            None => server.check_project().await?,
+        }
+        let complete = server.wait_until_idle(ANALYSIS_TIMEOUT).await;
+        let diagnostics = server.diagnostics(file.as_deref());
+        drop(server);
+
+        let mut text = format_diagnostics(&diagnostics, &root);
+        if !complete {
+            text.push_str(
+                "\nThe language server is still analyzing the project, so the diagnostics may be incomplete. Use the tool again to get the rest.",
+            );
+        }
+        if file.is_none() && language != Language::Rust {
+            let _ = write!(
+                text,
+                "\nThe {language} language server may only report the diagnostics of the files it has opened."
+            );
+        }
+        Ok(InvokeOutput {
+            output: OutputKind::Text(text),
+        })
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        if let Some(path) = &self.path {
+            let path = sanitize_path_tool_arg(os, path);
+            if !os.fs.exists(&path) {
+                bail!("'{}' does not exist", path.display());
+            }
+            if os.fs.symlink_metadata(&path).await?.is_dir() {
+                bail!(
+                    "'{}' is a directory, omit the path to get the diagnostics of the project",
+                    path.display()
+                );
+            }
+        }
+        self.target(os)?;
+        Ok(())
+    }
+}
+
+/// Formats errors and warnings as `path:line:column: severity[code] message`, with paths relative
+/// to `root`.
+fn format_diagnostics(diagnostics: &[Diagnostic], root: &Path) -> String {
+    let diagnostics = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity <= Severity::Warning)
+        .collect::<Vec<_>>();
+    if diagnostics.is_empty() {
+        return "No errors or warnings.".to_string();
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    let mut text = format!(
+        "{errors} error{}, {warnings} warning{}",
+        if errors == 1 { "" } else { "s" },
+        if warnings == 1 { "" } else { "s" }
+    );
+    if diagnostics.len() > MAX_DIAGNOSTICS {
+        let _ = write!(text, ", showing the first {MAX_DIAGNOSTICS}");
+    }
+    text.push('\n');
+    for diagnostic in diagnostics.iter().take(MAX_DIAGNOSTICS) {
+        let path = diagnostic.path.strip_prefix(root).unwrap_or(&diagnostic.path);
+        let _ = write!(
+            text,
+            "{}:{}:{}: {}",
+            path.display(),
+            diagnostic.line,
+            diagnostic.column,
+            diagnostic.severity
+        );
+        if let Some(code) = &diagnostic.code {
+            let _ = write!(text, "[{code}]");
+        }
+        if let Some(source) = &diagnostic.source {
+            let _ = write!(text, " ({source})");
+        }
+        let _ = writeln!(text, " {}", diagnostic.message.replace('\n', "\n    "));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_diagnostics() {
+        let diagnostic = |severity: Severity, line: u32, message: &str| Diagnostic {
+            path: PathBuf::from("/project/src/main.rs"),
+            line,
+            column: 5,
+            severity,
+            source: Some("rustc".to_string()),
+            code: (severity == Severity::Error).then(|| "E0308".to_string()),
+            message: message.to_string(),
+        };
+        let text = format_diagnostics(
+            &[
+                diagnostic(Severity::Error, 3, "mismatched types\nexpected `u32`"),
+                diagnostic(Severity::Warning, 7, "unused variable: `x`"),
+                diagnostic(
+                    Severity::Hint,
+                    7,
+                    "if this is intentional, prefix it with an underscore",
+                ),
+            ],
+            Path::new("/project"),
+        );
+        assert_eq!(
+            text,
+            "1 error, 1 warning\n\
+             src/main.rs:3:5: error[E0308] (rustc) mismatched types\n    expected `u32`\n\
+             src/main.rs:7:5: warning (rustc) unused variable: `x`\n"
+        );
+        assert_eq!(
+            format_diagnostics(&[diagnostic(Severity::Hint, 1, "hint")], Path::new("/")),
+            "No errors or warnings."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/project/src").await.unwrap();
+        os.fs.write("/project/src/main.rs", "fn main() {}\n").await.unwrap();
+        os.fs.write("/project/notes.txt", "").await.unwrap();
+
+        let mut tool = Diagnostics {
+            path: Some("/project/src/main.rs".to_string()),
+            language: None,
+        };
+        assert!(tool.validate(&os).await.is_ok());
+        let (language, file, _) = tool.target(&os).unwrap();
+        assert_eq!(language, Language::Rust);
+        assert_eq!(file, Some(os.fs.chroot_path("/project/src/main.rs")));
+
+        tool.path = Some("/project/src".to_string());
+        assert!(tool.validate(&os).await.is_err());
+        tool.path = Some("/project/missing.rs".to_string());
+        assert!(tool.validate(&os).await.is_err());
+        tool.path = Some("/project/notes.txt".to_string());
+        assert!(tool.validate(&os).await.is_err());
+        tool.language = Some(Language::Python);
+        assert!(tool.validate(&os).await.is_ok());
+    }
+}
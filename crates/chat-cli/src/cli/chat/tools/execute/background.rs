@@ -0,0 +1,307 @@
+use std::collections::{
+    BTreeMap,
+    VecDeque,
+};
+use std::process::Stdio;
+use std::sync::{
+    Arc,
+    LazyLock,
+    Mutex,
+};
+use std::time::Instant;
+
+use eyre::{
+    Context as EyreContext,
+    Result,
+    bail,
+};
+use serde::Serialize;
+use tempfile::TempDir;
+use tokio::io::{
+    AsyncBufReadExt,
+    AsyncRead,
+    BufReader,
+};
+use tracing::{
+    error,
+    warn,
+};
+
+use super::{
+    ShellCommand,
+    shell_command,
+};
+use crate::cli::agent::ExecutionSandbox;
+use crate::util::process::{
+    Pid,
+    terminate_process,
+};
+
+/// Max number of lines retained per background process. Older lines are dropped.
+const LINE_COUNT: usize = 1024;
+
+static PROCESSES: LazyLock<Mutex<BackgroundProcesses>> = LazyLock::new(Default::default);
+
+/// Status of a background process, as reported back to the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ProcessStatus {
+    Running,
+    Exited { exit_status: Option<i32> },
+    Killed,
+}
+
+/// Summary of a background process.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    pub process_id: u32,
+    pub pid: Option<u32>,
+    pub command: String,
+    pub running_secs: u64,
+    #[serde(flatten)]
+    pub status: ProcessStatus,
+}
+
+/// Interleaved stdout and stderr of a background process.
+#[derive(Debug, Default)]
+struct ProcessLog {
+    lines: VecDeque<String>,
+    /// Total number of lines ever written to the log
+    total: usize,
+    /// Number of lines already returned by [BackgroundProcesses::read_output]
+    read: usize,
+}
+
+impl ProcessLog {
+    fn push(&mut self, line: String) {
+        if self.lines.len() >= LINE_COUNT {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+        self.total += 1;
+    }
+
+    /// Returns the lines that have not been read yet, and marks them as read.
+    fn take_unread(&mut self) -> Vec<String> {
+        let first_retained = self.total - self.lines.len();
+        let skip = self.read.saturating_sub(first_retained);
+        self.read = self.total;
+        self.lines.iter().skip(skip).cloned().collect()
+    }
+
+    fn tail(&mut self, n: usize) -> Vec<String> {
+        self.read = self.total;
+        self.lines
+            .iter()
+            .skip(self.lines.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct BackgroundProcess {
+    command: String,
+    started_at: Instant,
+    child: tokio::process::Child,
+    log: Arc<Mutex<ProcessLog>>,
+    killed: bool,
+    /// Temporary working directory of sandboxed processes, removed once the process is dropped
+    _workdir: Option<TempDir>,
+}
+
+impl BackgroundProcess {
+    fn status(&mut self) -> ProcessStatus {
+        if self.killed {
+            return ProcessStatus::Killed;
+        }
+        match self.child.try_wait() {
+            Ok(None) => ProcessStatus::Running,
+            Ok(Some(status)) => ProcessStatus::Exited {
+                exit_status: status.code(),
+            },
+            Err(err) => {
+                error!(%err, "Failed to get status of background process");
+                ProcessStatus::Exited { exit_status: None }
+            },
+        }
+    }
+
+    fn kill(&mut self) {
+        if self.status() != ProcessStatus::Running {
+            return;
+        }
+        // Prefer a graceful termination so that servers get a chance to clean up.
+        let terminated = self
+            .child
+            .id()
+            .is_some_and(|pid| terminate_process(Pid::from_u32(pid)).is_ok());
+        if !terminated {
+            if let Err(err) = self.child.start_kill() {
+                warn!(%err, "Failed to kill background process");
+            }
+        }
+        self.killed = true;
+    }
+}
+
+/// Registry of the processes launched in the background by execute_bash over the course of the
+/// session.
+#[derive(Debug, Default)]
+pub struct BackgroundProcesses {
+    next_id: u32,
+    processes: BTreeMap<u32, BackgroundProcess>,
+}
+
+impl BackgroundProcesses {
+    /// Spawns `command` in the background inside of `sandbox`, returning the id of the new process.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn spawn(command: &str, sandbox: ExecutionSandbox) -> Result<ProcessInfo> {
+        let ShellCommand {
+            command: mut cmd,
+            workdir,
+        } = shell_command(command, sandbox)?;
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err_with(|| format!("Unable to spawn command '{}'", command))?;
+
+        let log = Arc::new(Mutex::new(ProcessLog::default()));
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(collect_lines(stdout, log.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(collect_lines(stderr, log.clone()));
+        }
+
+        let mut processes = lock();
+        processes.next_id += 1;
+        let id = processes.next_id;
+        processes.processes.insert(id, BackgroundProcess {
+            command: command.to_string(),
+            started_at: Instant::now(),
+            child,
+            log,
+            killed: false,
+            _workdir: workdir,
+        });
+        Ok(processes.info(id).expect("process was just inserted"))
+    }
+
+    pub fn list() -> Vec<ProcessInfo> {
+        let mut processes = lock();
+        let ids = processes.processes.keys().copied().collect::<Vec<_>>();
+        ids.into_iter().filter_map(|id| processes.info(id)).collect()
+    }
+
+    /// Returns the output of the given process. If `tail` is provided, the last `tail` lines are
+    /// returned, otherwise only the lines written since the previous read.
+    pub fn read_output(id: u32, tail: Option<usize>) -> Result<(ProcessInfo, String)> {
+        let mut processes = lock();
+        let Some(info) = processes.info(id) else {
+            bail!("No background process with id {id}");
+        };
+        let process = processes.processes.get(&id).expect("process exists");
+        let mut log = process.log.lock().expect("process log lock poisoned");
+        let lines = match tail {
+            Some(n) => log.tail(n),
+            None => log.take_unread(),
+        };
+        Ok((info, lines.join("\n")))
+    }
+
+    pub fn kill(id: u32) -> Result<ProcessInfo> {
+        let mut processes = lock();
+        let Some(process) = processes.processes.get_mut(&id) else {
+            bail!("No background process with id {id}");
+        };
+        process.kill();
+        Ok(processes.info(id).expect("process exists"))
+    }
+
+    /// Kills every background process that is still running. Called when the chat session ends.
+    pub fn kill_all() {
+        for process in lock().processes.values_mut() {
+            process.kill();
+        }
+    }
+
+    fn info(&mut self, id: u32) -> Option<ProcessInfo> {
+        let process = self.processes.get_mut(&id)?;
+        Some(ProcessInfo {
+            process_id: id,
+            pid: process.child.id(),
+            command: process.command.clone(),
+            running_secs: process.started_at.elapsed().as_secs(),
+            status: process.status(),
+        })
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BackgroundProcesses> {
+    PROCESSES.lock().expect("background process lock poisoned")
+}
+
+async fn collect_lines(stream: impl AsyncRead + Unpin, log: Arc<Mutex<ProcessLog>>) {
+    let mut lines = BufReader::new(stream).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => log.lock().expect("process log lock poisoned").push(line),
+            Ok(None) => break,
+            Err(err) => {
+                error!(%err, "Failed to read output of background process");
+                break;
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_log() {
+        let mut log = ProcessLog::default();
+        log.push("a".to_string());
+        log.push("b".to_string());
+        assert_eq!(log.take_unread(), vec!["a", "b"]);
+        assert!(log.take_unread().is_empty());
+
+        for i in 0..LINE_COUNT + 10 {
+            log.push(i.to_string());
+        }
+        let unread = log.take_unread();
+        assert_eq!(unread.len(), LINE_COUNT);
+        assert_eq!(unread.last().unwrap(), &(LINE_COUNT + 9).to_string());
+        assert_eq!(log.tail(2), vec![
+            (LINE_COUNT + 8).to_string(),
+            (LINE_COUNT + 9).to_string()
+        ]);
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_background_process_lifecycle() {
+        let info = BackgroundProcesses::spawn("echo started; sleep 30", ExecutionSandbox::None).unwrap();
+        assert_eq!(info.status, ProcessStatus::Running);
+
+        let mut output = String::new();
+        for _ in 0..50 {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            output = BackgroundProcesses::read_output(info.process_id, None).unwrap().1;
+            if !output.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(output, "started");
+
+        let info = BackgroundProcesses::kill(info.process_id).unwrap();
+        assert_eq!(info.status, ProcessStatus::Killed);
+        assert!(BackgroundProcesses::kill(u32::MAX).is_err());
+    }
+}
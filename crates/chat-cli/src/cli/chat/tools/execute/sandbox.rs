@@ -0,0 +1,108 @@
+use eyre::{
+    Context as EyreContext,
+    Result,
+};
+use tempfile::TempDir;
+
+use crate::cli::agent::ExecutionSandbox;
+
+/// A shell command ready to be spawned, along with the temporary working directory it runs in,
+/// if any. The directory is removed once this is dropped, so it must outlive the process.
+pub struct ShellCommand {
+    pub command: tokio::process::Command,
+    pub workdir: Option<TempDir>,
+}
+
+/// Builds the command used to run `command` through the shell, inside of `sandbox`.
+pub fn shell_command(command: &str, sandbox: ExecutionSandbox) -> Result<ShellCommand> {
+    let mut cmd = match sandbox {
+        ExecutionSandbox::None => platform_shell(None, command),
+        ExecutionSandbox::NoNetwork | ExecutionSandbox::Restricted => no_network_shell(command)?,
+    };
+
+    let workdir = match sandbox {
+        ExecutionSandbox::Restricted => {
+            let dir = tempfile::Builder::new()
+                .prefix("q-sandbox-")
+                .tempdir()
+                .wrap_err("Unable to create sandbox working directory")?;
+            cmd.current_dir(dir.path());
+            Some(dir)
+        },
+        _ => None,
+    };
+
+    Ok(ShellCommand { command: cmd, workdir })
+}
+
+#[cfg(windows)]
+fn platform_shell(wrapper: Option<&[&str]>, command: &str) -> tokio::process::Command {
+    debug_assert!(wrapper.is_none(), "sandboxing is not supported on Windows");
+    let mut cmd = tokio::process::Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn platform_shell(wrapper: Option<&[&str]>, command: &str) -> tokio::process::Command {
+    let shell = std::env::var("AMAZON_Q_CHAT_SHELL").unwrap_or("bash".to_string());
+    let mut cmd = match wrapper {
+        Some([program, args @ ..]) => {
+            let mut cmd = tokio::process::Command::new(program);
+            cmd.args(args).arg(shell);
+            cmd
+        },
+        _ => tokio::process::Command::new(shell),
+    };
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+/// Network isolation is done with a new network namespace on Linux, and with a sandbox-exec
+/// profile on macOS.
+fn no_network_shell(command: &str) -> Result<tokio::process::Command> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(platform_shell(
+            Some(&["unshare", "--user", "--map-root-user", "--net", "--"]),
+            command,
+        ))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Ok(platform_shell(
+            Some(&["sandbox-exec", "-p", "(version 1)(allow default)(deny network*)"]),
+            command,
+        ))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = command;
+        eyre::bail!("Sandboxed command execution is not supported on this platform")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_command() {
+        let cmd = shell_command("echo hello", ExecutionSandbox::None).unwrap();
+        assert!(cmd.workdir.is_none());
+
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        {
+            let cmd = shell_command("echo hello", ExecutionSandbox::Restricted).unwrap();
+            let workdir = cmd.workdir.as_ref().unwrap().path().to_path_buf();
+            assert!(workdir.exists());
+            assert_eq!(cmd.command.as_std().get_current_dir(), Some(workdir.as_path()));
+            let args = cmd.command.as_std().get_args().collect::<Vec<_>>();
+            assert_eq!(args.last().unwrap(), &"echo hello");
+            drop(cmd);
+            assert!(!workdir.exists());
+        }
+    }
+}
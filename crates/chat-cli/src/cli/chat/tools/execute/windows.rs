@@ -1,4 +1,3 @@
-use std::collections::VecDeque;
 use std::io::Write;
 use std::process::Stdio;
 
@@ -12,25 +11,33 @@ use tracing::error;
 
 use super::{
     CommandResult,
-    format_output,
+    OutputBuffer,
+    ShellCommand,
+    shell_command,
 };
+use crate::cli::agent::ExecutionSandbox;
 
 /// Run a command on Windows using cmd.exe.
 /// # Arguments
 /// * `command` - The command to run
-/// * `max_result_size` - max size of output streams, truncating if required
+/// * `sandbox` - The sandbox to run the command in
+/// * `max_result_size` - max size of each output stream, keeping its beginning and end if required
 /// * `updates` - output stream to push informational messages about the progress
 /// # Returns
 /// A [`CommandResult`]
 pub async fn run_command<W: Write>(
     command: &str,
+    sandbox: ExecutionSandbox,
     max_result_size: usize,
     mut updates: Option<W>,
 ) -> Result<CommandResult> {
+    let ShellCommand {
+        command: mut cmd,
+        workdir: _workdir,
+    } = shell_command(command, sandbox)?;
+
     // We need to maintain a handle on stderr and stdout, but pipe it to the terminal as well
-    let mut child = tokio::process::Command::new("cmd")
-        .arg("/C")
-        .arg(command)
+    let mut child = cmd
         .stdin(Stdio::inherit())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -51,9 +58,8 @@ pub async fn run_command<W: Write>(
         let stderr = tokio::io::BufReader::new(stderr);
         let mut stderr = stderr.lines();
 
-        const LINE_COUNT: usize = 1024;
-        let mut stdout_buf = VecDeque::with_capacity(LINE_COUNT);
-        let mut stderr_buf = VecDeque::with_capacity(LINE_COUNT);
+        let mut stdout_buf = OutputBuffer::new(max_result_size);
+        let mut stderr_buf = OutputBuffer::new(max_result_size);
 
         let mut stdout_done = false;
         let mut stderr_done = false;
@@ -63,10 +69,8 @@ pub async fn run_command<W: Write>(
                 line = stdout.next_line(), if !stdout_done => match line {
                     Ok(Some(line)) => {
                         writeln!(u, "{line}")?;
-                        if stdout_buf.len() >= LINE_COUNT {
-                            stdout_buf.pop_front();
-                        }
-                        stdout_buf.push_back(line);
+                        u.flush()?;
+                        stdout_buf.push_line(&line);
                     },
                     Ok(None) => stdout_done = true,
                     Err(err) => error!(%err, "Failed to read stdout of child process"),
@@ -74,10 +78,8 @@ pub async fn run_command<W: Write>(
                 line = stderr.next_line(), if !stderr_done => match line {
                     Ok(Some(line)) => {
                         writeln!(u, "{line}")?;
-                        if stderr_buf.len() >= LINE_COUNT {
-                            stderr_buf.pop_front();
-                        }
-                        stderr_buf.push_back(line);
+                        u.flush()?;
+                        stderr_buf.push_line(&line);
                     },
                     Ok(None) => stderr_done = true,
                     Err(err) => error!(%err, "Failed to read stderr of child process"),
@@ -91,8 +93,8 @@ pub async fn run_command<W: Write>(
 
         u.flush()?;
 
-        stdout_final = stdout_buf.into_iter().collect::<Vec<_>>().join("\n");
-        stderr_final = stderr_buf.into_iter().collect::<Vec<_>>().join("\n");
+        stdout_final = stdout_buf.into_string();
+        stderr_final = stderr_buf.into_string();
     } else {
         // Take output all at once since we are not reporting anything in real time
         let output = child
@@ -101,14 +103,14 @@ pub async fn run_command<W: Write>(
             .wrap_err_with(|| format!("No exit status for '{}'", command))?;
 
         exit_status = output.status;
-        stdout_final = String::from_utf8_lossy(&output.stdout).to_string();
-        stderr_final = String::from_utf8_lossy(&output.stderr).to_string();
+        stdout_final = OutputBuffer::from_output(&String::from_utf8_lossy(&output.stdout), max_result_size);
+        stderr_final = OutputBuffer::from_output(&String::from_utf8_lossy(&output.stderr), max_result_size);
     }
 
     Ok(CommandResult {
         exit_status: exit_status.code(),
-        stdout: format_output(&stdout_final, max_result_size),
-        stderr: format_output(&stderr_final, max_result_size),
+        stdout: stdout_final,
+        stderr: stderr_final,
     })
 }
 
@@ -116,9 +118,11 @@ pub async fn run_command<W: Write>(
 mod tests {
     use crate::cli::chat::tools::OutputKind;
     use crate::cli::chat::tools::execute::ExecuteCommand;
+    use crate::os::Os;
 
     #[tokio::test]
     async fn test_execute_cmd_tool() {
+        let os = Os::new().await.unwrap();
         let mut stdout = std::io::stdout();
 
         // Verifying stdout
@@ -127,7 +131,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
 
@@ -145,7 +149,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
 
@@ -163,7 +167,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
         if let OutputKind::Json(json) = out.output {
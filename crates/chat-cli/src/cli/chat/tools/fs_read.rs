@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 use std::fs::Metadata;
 use std::io::Write;
+use std::path::PathBuf;
 
 use crossterm::queue;
 use crossterm::style::{
@@ -51,6 +52,10 @@ pub struct FsRead {
     // For batch operations
     pub operations: Vec<FsReadOperation>,
     pub summary: Option<String>,
+    /// Roots registered with `/context add-root`, that paths which cannot be read are reported
+    /// relative to.
+    #[serde(skip)]
+    pub roots: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -63,12 +68,25 @@ pub enum FsReadOperation {
 }
 
 impl FsRead {
+    /// The paths read by all operations.
+    pub fn paths(&self) -> Vec<&str> {
+        self.operations
+            .iter()
+            .flat_map(|op| match op {
+                FsReadOperation::Line(FsLine { path, .. })
+                | FsReadOperation::Directory(FsDirectory { path, .. })
+                | FsReadOperation::Search(FsSearch { path, .. }) => vec![path.as_str()],
+                FsReadOperation::Image(FsImage { image_paths }) => image_paths.iter().map(String::as_str).collect(),
+            })
+            .collect()
+    }
+
     pub async fn validate(&mut self, os: &Os) -> Result<()> {
         if self.operations.is_empty() {
             bail!("At least one operation must be provided");
         }
         for op in &mut self.operations {
-            op.validate(os).await?;
+            op.validate(os, &self.roots).await?;
         }
         Ok(())
     }
@@ -306,11 +324,11 @@ impl FsRead {
 }
 
 impl FsReadOperation {
-    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+    pub async fn validate(&mut self, os: &Os, roots: &[PathBuf]) -> Result<()> {
         match self {
             FsReadOperation::Line(fs_line) => fs_line.validate(os).await,
-            FsReadOperation::Directory(fs_directory) => fs_directory.validate(os).await,
-            FsReadOperation::Search(fs_search) => fs_search.validate(os).await,
+            FsReadOperation::Directory(fs_directory) => fs_directory.validate(os, roots).await,
+            FsReadOperation::Search(fs_search) => fs_search.validate(os, roots).await,
             FsReadOperation::Image(fs_image) => fs_image.validate(os).await,
         }
     }
@@ -523,9 +541,9 @@ impl FsSearch {
     const DEFAULT_CONTEXT_LINES: usize = 2;
     const MATCHING_LINE_PREFIX: &str = "→ ";
 
-    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+    pub async fn validate(&mut self, os: &Os, roots: &[PathBuf]) -> Result<()> {
         let path = sanitize_path_tool_arg(os, &self.path);
-        let relative_path = format_path(os.env.current_dir()?, &path);
+        let relative_path = format_path(os.env.current_dir()?, roots, &path);
         if !path.exists() {
             bail!("File not found: {}", relative_path);
         }
@@ -621,9 +639,9 @@ pub struct FsDirectory {
 impl FsDirectory {
     const DEFAULT_DEPTH: usize = 0;
 
-    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+    pub async fn validate(&mut self, os: &Os, roots: &[PathBuf]) -> Result<()> {
         let path = sanitize_path_tool_arg(os, &self.path);
-        let relative_path = format_path(os.env.current_dir()?, &path);
+        let relative_path = format_path(os.env.current_dir()?, roots, &path);
         if !path.exists() {
             bail!("Directory not found: {}", relative_path);
         }
@@ -1,5 +1,8 @@
 use std::io::Write;
-use std::path::Path;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::sync::LazyLock;
 
 use crossterm::queue;
@@ -11,7 +14,6 @@ use eyre::{
     ContextCompat as _,
     Result,
     bail,
-    eyre,
 };
 use globset::{
     Glob,
@@ -33,7 +35,10 @@ use tracing::{
 
 use super::{
     InvokeOutput,
+    OutputKind,
+    PermissionPath,
     format_path,
+    sanitize_path_glob,
     sanitize_path_tool_arg,
     supports_truecolor,
 };
@@ -41,6 +46,7 @@ use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
 };
+use crate::database::settings::Setting;
 use crate::os::Os;
 
 static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
@@ -57,6 +63,17 @@ pub enum FsWrite {
         file_text: Option<String>,
         new_str: Option<String>,
         summary: Option<String>,
+        /// Which of the [Hunk]s of the change the user approved, when they were reviewed one by
+        /// one. Only these are written.
+        #[serde(skip)]
+        accepted_hunks: Option<Vec<bool>>,
+        /// What to do when the file already exists, from the agent's settings.
+        #[serde(skip)]
+        create_policy: CreatePolicy,
+        /// Roots registered with `/context add-root`, that the path is named relative to when it
+        /// is in one of them, and that the path globs of the agent's settings are matched from.
+        #[serde(skip)]
+        roots: Vec<PathBuf>,
     },
     #[serde(rename = "str_replace")]
     StrReplace {
@@ -64,6 +81,26 @@ pub enum FsWrite {
         old_str: String,
         new_str: String,
         summary: Option<String>,
+        /// See [FsWrite::Create::accepted_hunks].
+        #[serde(skip)]
+        accepted_hunks: Option<Vec<bool>>,
+        /// See [FsWrite::Create::roots].
+        #[serde(skip)]
+        roots: Vec<PathBuf>,
+    },
+    /// Several [FsWrite::StrReplace] replacements in the same file, applied in order. Nothing is
+    /// written unless all of them apply.
+    #[serde(rename = "edits")]
+    Edits {
+        path: String,
+        edits: Vec<StrReplaceEdit>,
+        summary: Option<String>,
+        /// See [FsWrite::Create::accepted_hunks].
+        #[serde(skip)]
+        accepted_hunks: Option<Vec<bool>>,
+        /// See [FsWrite::Create::roots].
+        #[serde(skip)]
+        roots: Vec<PathBuf>,
     },
     #[serde(rename = "insert")]
     Insert {
@@ -71,66 +108,178 @@ pub enum FsWrite {
         insert_line: usize,
         new_str: String,
         summary: Option<String>,
+        /// See [FsWrite::Create::roots].
+        #[serde(skip)]
+        roots: Vec<PathBuf>,
     },
     #[serde(rename = "append")]
     Append {
         path: String,
         new_str: String,
         summary: Option<String>,
+        /// See [FsWrite::Create::roots].
+        #[serde(skip)]
+        roots: Vec<PathBuf>,
     },
 }
 
+/// What [FsWrite::Create] does when the file already exists, set with `createPolicy` in the
+/// fs_write tool settings of the agent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CreatePolicy {
+    /// Replace the file, asking for approval as for any other write.
+    #[default]
+    Overwrite,
+    /// Replace the file, but always ask for approval first, even when fs_write is trusted.
+    Ask,
+    /// Refuse to replace the file, so that the model edits it instead.
+    FailIfExists,
+}
+
+impl CreatePolicy {
+    /// Returns the policy configured for `agent`.
+    pub fn configured(agent: &Agent) -> Self {
+        let Some(policy) = agent.tools_settings.get("fs_write").and_then(|s| s.get("createPolicy")) else {
+            return Self::default();
+        };
+        serde_json::from_value(policy.clone()).unwrap_or_else(|e| {
+            error!(
+                "Failed to deserialize the createPolicy tool setting for fs_write: {:?}",
+                e
+            );
+            Self::default()
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CreatePolicy::Overwrite => "overwrite",
+            CreatePolicy::Ask => "ask",
+            CreatePolicy::FailIfExists => "failIfExists",
+        }
+    }
+}
+
+/// One replacement of [FsWrite::Edits].
+#[derive(Debug, Clone, Deserialize)]
+pub struct StrReplaceEdit {
+    pub old_str: String,
+    pub new_str: String,
+}
+
 impl FsWrite {
     pub async fn invoke(&self, os: &Os, output: &mut impl Write) -> Result<InvokeOutput> {
         let cwd = os.env.current_dir()?;
         match self {
-            FsWrite::Create { path, .. } => {
-                let file_text = self.canonical_create_command_text();
+            FsWrite::Create {
+                path,
+                accepted_hunks,
+                create_policy,
+                ..
+            } => {
+                let mut file_text = self.canonical_create_command_text();
                 let path = sanitize_path_tool_arg(os, path);
+                let exists = os.fs.exists(&path);
+                if exists && *create_policy == CreatePolicy::FailIfExists {
+                    bail!("{}", file_exists_error(&format_path(&cwd, self.roots(), &path)));
+                }
                 if let Some(parent) = path.parent() {
                     os.fs.create_dir_all(parent).await?;
                 }
 
-                let invoke_description = if os.fs.exists(&path) {
-                    "Replacing: "
-                } else {
-                    "Creating: "
-                };
+                let invoke_description = if exists { "Replacing: " } else { "Creating: " };
+                let mut note = None;
+                if let Some(accepted) = accepted_hunks {
+                    let prev = match exists {
+                        true => os.fs.read_to_string(&path).await?,
+                        false => String::new(),
+                    };
+                    note = rejected_hunks_note(&prev, &file_text, accepted);
+                    file_text = apply_hunks(&prev, &file_text, accepted);
+                }
+                let relative_path = format_path(cwd, self.roots(), &path);
                 queue!(
                     output,
                     style::Print(invoke_description),
                     style::SetForegroundColor(Color::Green),
-                    style::Print(format_path(cwd, &path)),
+                    style::Print(&relative_path),
                     style::ResetColor,
                     style::Print("\n"),
                 )?;
 
                 write_to_file(os, path, file_text).await?;
-                Ok(Default::default())
+                let result = match exists {
+                    true => format!("Replaced the existing file {relative_path}"),
+                    false => format!("Created the new file {relative_path}"),
+                };
+                Ok(InvokeOutput {
+                    output: OutputKind::Text(match note {
+                        Some(note) => format!("{result}\n\n{note}"),
+                        None => result,
+                    }),
+                })
             },
             FsWrite::StrReplace {
-                path, old_str, new_str, ..
+                path,
+                old_str,
+                new_str,
+                accepted_hunks,
+                ..
             } => {
                 let path = sanitize_path_tool_arg(os, path);
                 let file = os.fs.read_to_string(&path).await?;
-                let matches = file.match_indices(old_str).collect::<Vec<_>>();
                 queue!(
                     output,
                     style::Print("Updating: "),
                     style::SetForegroundColor(Color::Green),
-                    style::Print(format_path(cwd, &path)),
+                    style::Print(format_path(cwd, self.roots(), &path)),
                     style::ResetColor,
                     style::Print("\n"),
                 )?;
-                match matches.len() {
-                    0 => Err(eyre!("no occurrences of \"{old_str}\" were found")),
-                    1 => {
-                        let file = file.replacen(old_str, new_str, 1);
-                        os.fs.write(path, file).await?;
-                        Ok(Default::default())
+                let found = find_old_str(&file, old_str, new_str)?;
+                let replaced = found.replace(&file);
+                let mut notes = vec![found.note(&file)];
+                let written = match accepted_hunks {
+                    Some(accepted) => {
+                        notes.push(rejected_hunks_note(&file, &replaced, accepted));
+                        apply_hunks(&file, &replaced, accepted)
                     },
-                    x => Err(eyre!("{x} occurrences of old_str were found when only 1 is expected")),
-                }
+                    None => replaced,
+                };
+                os.fs.write(path, written).await?;
+                Ok(InvokeOutput {
+                    output: OutputKind::Text(notes.into_iter().flatten().collect::<Vec<_>>().join("\n\n")),
+                })
+            },
+            FsWrite::Edits {
+                path,
+                edits,
+                accepted_hunks,
+                ..
+            } => {
+                let path = sanitize_path_tool_arg(os, path);
+                let file = os.fs.read_to_string(&path).await?;
+                queue!(
+                    output,
+                    style::Print("Updating: "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format_path(cwd, self.roots(), &path)),
+                    style::ResetColor,
+                    style::Print("\n"),
+                )?;
+                let (edited, mut notes) = apply_edits(&file, edits)?;
+                let written = match accepted_hunks {
+                    Some(accepted) => {
+                        notes.extend(rejected_hunks_note(&file, &edited, accepted));
+                        apply_hunks(&file, &edited, accepted)
+                    },
+                    None => edited,
+                };
+                os.fs.write(path, written).await?;
+                Ok(InvokeOutput {
+                    output: OutputKind::Text(notes.join("\n\n")),
+                })
             },
             FsWrite::Insert {
                 path,
@@ -144,7 +293,7 @@ impl FsWrite {
                     output,
                     style::Print("Updating: "),
                     style::SetForegroundColor(Color::Green),
-                    style::Print(format_path(cwd, &path)),
+                    style::Print(format_path(cwd, self.roots(), &path)),
                     style::ResetColor,
                     style::Print("\n"),
                 )?;
@@ -168,7 +317,7 @@ impl FsWrite {
                     output,
                     style::Print("Appending to: "),
                     style::SetForegroundColor(Color::Green),
-                    style::Print(format_path(cwd, &path)),
+                    style::Print(format_path(cwd, self.roots(), &path)),
                     style::ResetColor,
                     style::Print("\n"),
                 )?;
@@ -188,18 +337,31 @@ impl FsWrite {
         let cwd = os.env.current_dir()?;
         self.print_relative_path(os, output)?;
         match self {
-            FsWrite::Create { path, .. } => {
+            FsWrite::Create {
+                path, create_policy, ..
+            } => {
                 let file_text = self.canonical_create_command_text();
                 let path = sanitize_path_tool_arg(os, path);
-                let relative_path = format_path(cwd, &path);
-                let prev = if os.fs.exists(&path) {
+                let relative_path = format_path(cwd, self.roots(), &path);
+                let exists = os.fs.exists(&path);
+                let prev = if exists {
                     let file = os.fs.read_to_string_sync(&path)?;
                     stylize_output_if_able(os, &path, &file)
                 } else {
                     Default::default()
                 };
                 let new = stylize_output_if_able(os, &relative_path, &file_text);
-                print_diff(output, &prev, &new, 1)?;
+                print_styled_diff(os, output, &prev, &new, 1)?;
+                let (color, collision) = match exists {
+                    true => (Color::Yellow, "This replaces the existing file"),
+                    false => (Color::DarkGrey, "This creates a new file"),
+                };
+                queue!(
+                    output,
+                    style::SetForegroundColor(color),
+                    style::Print(format!("{collision} (create policy: {})\n", create_policy.name())),
+                    style::ResetColor,
+                )?;
 
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
@@ -213,7 +375,7 @@ impl FsWrite {
                 ..
             } => {
                 let path = sanitize_path_tool_arg(os, path);
-                let relative_path = format_path(cwd, &path);
+                let relative_path = format_path(cwd, self.roots(), &path);
                 let file = os.fs.read_to_string_sync(&path)?;
 
                 // Diff the old with the new by adding extra context around the line being inserted
@@ -228,7 +390,7 @@ impl FsWrite {
 
                 let old = stylize_output_if_able(os, &relative_path, &old);
                 let new = stylize_output_if_able(os, &relative_path, &new);
-                print_diff(output, &old, &new, start_line)?;
+                print_styled_diff(os, output, &old, &new, start_line)?;
 
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
@@ -239,15 +401,33 @@ impl FsWrite {
                 path, old_str, new_str, ..
             } => {
                 let path = sanitize_path_tool_arg(os, path);
-                let relative_path = format_path(cwd, &path);
+                let relative_path = format_path(cwd, self.roots(), &path);
                 let file = os.fs.read_to_string_sync(&path)?;
-                let (start_line, _) = match line_number_at(&file, old_str) {
-                    Some((start_line, end_line)) => (start_line, end_line),
-                    _ => (0, 0),
-                };
-                let old_str = stylize_output_if_able(os, &relative_path, old_str);
-                let new_str = stylize_output_if_able(os, &relative_path, new_str);
-                print_diff(output, &old_str, &new_str, start_line)?;
+                queue_str_replace_diff(os, output, &relative_path, &file, old_str, new_str)?;
+
+                // Display summary as purpose if available after the diff
+                super::display_purpose(self.get_summary(), output)?;
+
+                Ok(())
+            },
+            FsWrite::Edits { path, edits, .. } => {
+                let path = sanitize_path_tool_arg(os, path);
+                let relative_path = format_path(cwd, self.roots(), &path);
+                let mut file = os.fs.read_to_string_sync(&path)?;
+                for (i, edit) in edits.iter().enumerate() {
+                    queue!(
+                        output,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("Edit {}/{}:\n", i + 1, edits.len())),
+                        style::ResetColor,
+                    )?;
+                    // Later edits apply to the file as changed by the earlier ones.
+                    if let Some(found) =
+                        queue_str_replace_diff(os, output, &relative_path, &file, &edit.old_str, &edit.new_str)?
+                    {
+                        file = found.replace(&file);
+                    }
+                }
 
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
@@ -256,10 +436,10 @@ impl FsWrite {
             },
             FsWrite::Append { path, new_str, .. } => {
                 let path = sanitize_path_tool_arg(os, path);
-                let relative_path = format_path(cwd, &path);
+                let relative_path = format_path(cwd, self.roots(), &path);
                 let start_line = os.fs.read_to_string_sync(&path)?.lines().count() + 1;
                 let file = stylize_output_if_able(os, &relative_path, new_str);
-                print_diff(output, &Default::default(), &file, start_line)?;
+                print_styled_diff(os, output, &Default::default(), &file, start_line)?;
 
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
@@ -269,12 +449,79 @@ impl FsWrite {
         }
     }
 
-    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+    /// Returns the hunks of the change, for the user to approve one by one. Only [FsWrite::Create]
+    /// and [FsWrite::StrReplace] changes can be partially approved.
+    pub fn hunks(&self, os: &Os) -> Result<Vec<Hunk>> {
+        Ok(match self.proposed_change(os)? {
+            Some((old, new)) => diff_hunks(&old, &new),
+            None => Vec::new(),
+        })
+    }
+
+    /// Sets which of the [FsWrite::hunks] are written when the tool is invoked.
+    pub fn accept_hunks(&mut self, accepted: Vec<bool>) {
+        match self {
+            FsWrite::Create { accepted_hunks, .. }
+            | FsWrite::StrReplace { accepted_hunks, .. }
+            | FsWrite::Edits { accepted_hunks, .. } => {
+                *accepted_hunks = Some(accepted);
+            },
+            FsWrite::Insert { .. } | FsWrite::Append { .. } => (),
+        }
+    }
+
+    /// Returns the content of the file before and after the change, for the changes that can be
+    /// partially approved.
+    fn proposed_change(&self, os: &Os) -> Result<Option<(String, String)>> {
         match self {
             FsWrite::Create { path, .. } => {
+                let path = sanitize_path_tool_arg(os, path);
+                let prev = match os.fs.exists(&path) {
+                    true => os.fs.read_to_string_sync(&path)?,
+                    false => String::new(),
+                };
+                Ok(Some((prev, self.canonical_create_command_text())))
+            },
+            FsWrite::StrReplace {
+                path, old_str, new_str, ..
+            } => {
+                let path = sanitize_path_tool_arg(os, path);
+                let file = os.fs.read_to_string_sync(&path)?;
+                match find_old_str(&file, old_str, new_str) {
+                    Ok(found) => {
+                        let replaced = found.replace(&file);
+                        Ok(Some((file, replaced)))
+                    },
+                    Err(_) => Ok(None),
+                }
+            },
+            FsWrite::Edits { path, edits, .. } => {
+                let path = sanitize_path_tool_arg(os, path);
+                let file = os.fs.read_to_string_sync(&path)?;
+                match apply_edits(&file, edits) {
+                    Ok((edited, _)) => Ok(Some((file, edited))),
+                    Err(_) => Ok(None),
+                }
+            },
+            FsWrite::Insert { .. } | FsWrite::Append { .. } => Ok(None),
+        }
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        match self {
+            FsWrite::Create {
+                path, create_policy, ..
+            } => {
                 if path.is_empty() {
                     bail!("Path must not be empty")
                 };
+                let path = sanitize_path_tool_arg(os, path);
+                if *create_policy == CreatePolicy::FailIfExists && os.fs.exists(&path) {
+                    bail!(
+                        "{}",
+                        file_exists_error(&format_path(os.env.current_dir()?, self.roots(), &path))
+                    );
+                }
             },
             FsWrite::StrReplace { path, .. } | FsWrite::Insert { path, .. } => {
                 let path = sanitize_path_tool_arg(os, path);
@@ -282,6 +529,17 @@ impl FsWrite {
                     bail!("The provided path must exist in order to replace or insert contents into it")
                 }
             },
+            FsWrite::Edits { path, edits, .. } => {
+                let path = sanitize_path_tool_arg(os, path);
+                if !path.exists() {
+                    bail!("The provided path must exist in order to edit it")
+                }
+                if edits.is_empty() {
+                    bail!("At least one edit must be provided")
+                }
+                // Fail before asking for approval when the edits cannot all be applied.
+                apply_edits(&os.fs.read_to_string(&path).await?, edits)?;
+            },
             FsWrite::Append { path, new_str, .. } => {
                 if path.is_empty() {
                     bail!("Path must not be empty")
@@ -300,12 +558,13 @@ impl FsWrite {
         let path = match self {
             FsWrite::Create { path, .. } => path,
             FsWrite::StrReplace { path, .. } => path,
+            FsWrite::Edits { path, .. } => path,
             FsWrite::Insert { path, .. } => path,
             FsWrite::Append { path, .. } => path,
         };
         // Sanitize the path to handle tilde expansion
         let path = sanitize_path_tool_arg(os, path);
-        let relative_path = format_path(cwd, &path);
+        let relative_path = format_path(cwd, self.roots(), &path);
         queue!(
             output,
             style::Print("Path: "),
@@ -341,12 +600,75 @@ impl FsWrite {
         match self {
             FsWrite::Create { summary, .. } => summary.as_ref(),
             FsWrite::StrReplace { summary, .. } => summary.as_ref(),
+            FsWrite::Edits { summary, .. } => summary.as_ref(),
             FsWrite::Insert { summary, .. } => summary.as_ref(),
             FsWrite::Append { summary, .. } => summary.as_ref(),
         }
     }
 
-    pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
+    /// Sets what [FsWrite::Create] does when the file already exists.
+    pub fn set_create_policy(&mut self, policy: CreatePolicy) {
+        if let FsWrite::Create { create_policy, .. } = self {
+            *create_policy = policy;
+        }
+    }
+
+    /// Sets the roots registered with `/context add-root`, see [FsWrite::Create::roots].
+    pub fn set_roots(&mut self, workspace_roots: &[PathBuf]) {
+        match self {
+            FsWrite::Create { roots, .. }
+            | FsWrite::StrReplace { roots, .. }
+            | FsWrite::Edits { roots, .. }
+            | FsWrite::Insert { roots, .. }
+            | FsWrite::Append { roots, .. } => workspace_roots.clone_into(roots),
+        }
+    }
+
+    fn roots(&self) -> &[PathBuf] {
+        match self {
+            FsWrite::Create { roots, .. }
+            | FsWrite::StrReplace { roots, .. }
+            | FsWrite::Edits { roots, .. }
+            | FsWrite::Insert { roots, .. }
+            | FsWrite::Append { roots, .. } => roots,
+        }
+    }
+
+    /// Returns the path of the existing file whose content [FsWrite::Create],
+    /// [FsWrite::StrReplace], or [FsWrite::Edits] replace, if any.
+    pub fn replaced_path(&self, os: &Os) -> Option<PathBuf> {
+        match self {
+            FsWrite::Create { path, .. } | FsWrite::StrReplace { path, .. } | FsWrite::Edits { path, .. } => {
+                Some(sanitize_path_tool_arg(os, path)).filter(|path| os.fs.exists(path))
+            },
+            FsWrite::Insert { .. } | FsWrite::Append { .. } => None,
+        }
+    }
+
+    /// Whether the write replaces an existing file under [CreatePolicy::Ask], which always needs to
+    /// be approved.
+    pub fn asks_before_replacing(&self, os: &Os) -> bool {
+        match self {
+            FsWrite::Create {
+                path,
+                create_policy: CreatePolicy::Ask,
+                ..
+            } => os.fs.exists(sanitize_path_tool_arg(os, path)),
+            _ => false,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Create { path, .. }
+            | Self::StrReplace { path, .. }
+            | Self::Edits { path, .. }
+            | Self::Insert { path, .. }
+            | Self::Append { path, .. } => path,
+        }
+    }
+
+    pub fn eval_perm(&self, os: &Os, agent: &Agent) -> PermissionEvalResult {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "camelCase")]
         struct Settings {
@@ -372,7 +694,7 @@ impl FsWrite {
                 let allow_set = {
                     let mut builder = GlobSetBuilder::new();
                     for path in &allowed_paths {
-                        if let Ok(glob) = Glob::new(path) {
+                        if let Ok(glob) = Glob::new(&sanitize_path_glob(os, path)) {
                             builder.add(glob);
                         } else {
                             warn!("Failed to create glob from path given: {path}. Ignoring.");
@@ -384,7 +706,7 @@ impl FsWrite {
                 let deny_set = {
                     let mut builder = GlobSetBuilder::new();
                     for path in &denied_paths {
-                        if let Ok(glob) = Glob::new(path) {
+                        if let Ok(glob) = Glob::new(&sanitize_path_glob(os, path)) {
                             builder.add(glob);
                         } else {
                             warn!("Failed to create glob from path given: {path}. Ignoring.");
@@ -399,11 +721,18 @@ impl FsWrite {
                             Self::Create { path, .. }
                             | Self::Insert { path, .. }
                             | Self::Append { path, .. }
-                            | Self::StrReplace { path, .. } => {
-                                if deny_set.is_match(path) {
+                            | Self::StrReplace { path, .. }
+                            | Self::Edits { path, .. } => {
+                                let path = PermissionPath::new(os, path, self.roots());
+                                if path.any_match(&deny_set) {
                                     return PermissionEvalResult::Deny;
                                 }
-                                if allow_set.is_match(path) {
+                                // The path is outside of the workspace, whatever the globs allow.
+                                if path.is_symlink_escape() {
+                                    warn!(?path, "fs_write path escapes the workspace through a symlink");
+                                    return PermissionEvalResult::Ask;
+                                }
+                                if path.all_match(&allow_set) {
                                     return PermissionEvalResult::Allow;
                                 }
                             },
@@ -499,6 +828,217 @@ fn get_lines_with_context(
     )
 }
 
+/// Terminals narrower than this show diffs unified even with `chat.diffStyle` set to `split`.
+const SPLIT_DIFF_MIN_WIDTH: usize = 120;
+
+/// Prints the comparison between `old_str` and `new_str` side by side with [print_split_diff]
+/// when `chat.diffStyle` is `split` and the terminal is wide enough, and with [print_diff]
+/// otherwise. New files and appended content have nothing to compare, so they are unified.
+fn print_styled_diff(
+    os: &Os,
+    output: &mut impl Write,
+    old_str: &StylizedFile,
+    new_str: &StylizedFile,
+    start_line: usize,
+) -> Result<()> {
+    let split = os
+        .database
+        .settings
+        .get_string(Setting::ChatDiffStyle)
+        .is_some_and(|style| style == "split");
+    let width = os
+        .env
+        .get("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse::<usize>().ok())
+        .or_else(|| crossterm::terminal::size().ok().map(|(columns, _)| columns.into()));
+    match width {
+        Some(width)
+            if split && width >= SPLIT_DIFF_MIN_WIDTH && !old_str.content.is_empty() && !new_str.content.is_empty() =>
+        {
+            print_split_diff(output, old_str, new_str, start_line, width)
+        },
+        _ => print_diff(output, old_str, new_str, start_line),
+    }
+}
+
+/// Returns the colors of the text, gutter and line of a diff line with `tag`.
+fn change_colors(tag: similar::ChangeTag, file: &StylizedFile) -> (Color, Color, Color) {
+    match (tag, file.truecolor) {
+        (similar::ChangeTag::Equal, true) => (style::Color::Reset, file.gutter_bg, file.line_bg),
+        (similar::ChangeTag::Delete, true) => (
+            style::Color::Reset,
+            style::Color::Rgb { r: 79, g: 40, b: 40 },
+            style::Color::Rgb { r: 36, g: 25, b: 28 },
+        ),
+        (similar::ChangeTag::Insert, true) => (
+            style::Color::Reset,
+            style::Color::Rgb { r: 40, g: 67, b: 43 },
+            style::Color::Rgb { r: 24, g: 38, b: 30 },
+        ),
+        (similar::ChangeTag::Equal, false) => (style::Color::Reset, file.gutter_bg, file.line_bg),
+        (similar::ChangeTag::Delete, false) => (style::Color::Red, file.gutter_bg, file.line_bg),
+        (similar::ChangeTag::Insert, false) => (style::Color::Green, file.gutter_bg, file.line_bg),
+    }
+}
+
+/// Prints `old_str` and `new_str` side by side in `width` columns, with the lines that changed
+/// next to each other. Lines too long for their column are cut.
+/// - `start_line` - 1-indexed line number that `old_str` and `new_str` start at.
+fn print_split_diff(
+    output: &mut impl Write,
+    old_str: &StylizedFile,
+    new_str: &StylizedFile,
+    start_line: usize,
+    width: usize,
+) -> Result<()> {
+    let diff = similar::TextDiff::from_lines(&old_str.content, &new_str.content);
+
+    // Pair the lines that were removed with those that replaced them, so that each row shows a
+    // line before and after the change.
+    let mut rows = Vec::new();
+    let (mut deleted, mut inserted) = (Vec::new(), Vec::new());
+    let flush = |rows: &mut Vec<_>, deleted: &mut Vec<_>, inserted: &mut Vec<_>| {
+        let len = deleted.len().max(inserted.len());
+        let mut deleted = std::mem::take(deleted).into_iter();
+        let mut inserted = std::mem::take(inserted).into_iter();
+        for _ in 0..len {
+            rows.push((deleted.next(), inserted.next()));
+        }
+    };
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            similar::ChangeTag::Delete => deleted.push(change),
+            similar::ChangeTag::Insert => inserted.push(change),
+            similar::ChangeTag::Equal => {
+                flush(&mut rows, &mut deleted, &mut inserted);
+                rows.push((Some(change), Some(change)));
+            },
+        }
+    }
+    flush(&mut rows, &mut deleted, &mut inserted);
+
+    let max_line = rows
+        .iter()
+        .flat_map(|(old, new)| [old.and_then(|c| c.old_index()), new.and_then(|c| c.new_index())])
+        .flatten()
+        .max()
+        .unwrap_or(0)
+        + start_line;
+    let line_num_width = terminal_width_required_for_line_count(max_line);
+    // Each column has a sign, its line number and a colon around its text, and the columns are
+    // separated by " │ ".
+    let text_width = width.saturating_sub(3 + 2 * (line_num_width + 4)) / 2;
+
+    for (old, new) in rows {
+        let cell = SplitDiffCell {
+            start_line,
+            line_num_width,
+            text_width,
+        };
+        cell.print(output, old, old_str, true)?;
+        queue!(output, style::Print(" │ "))?;
+        cell.print(output, new, new_str, false)?;
+        queue!(output, style::Print("\n"))?;
+    }
+    queue!(output, style::Print("\n"))?;
+
+    Ok(())
+}
+
+/// Layout of a column of [print_split_diff].
+struct SplitDiffCell {
+    start_line: usize,
+    line_num_width: usize,
+    text_width: usize,
+}
+
+impl SplitDiffCell {
+    /// Prints the line `change` of `file`, or blanks when the other column has a line of its own.
+    /// Lines that did not change are numbered as in the old file on the left, and as in the new
+    /// file on the right.
+    fn print(
+        &self,
+        output: &mut impl Write,
+        change: Option<similar::Change<&str>>,
+        file: &StylizedFile,
+        left: bool,
+    ) -> Result<()> {
+        let Some(change) = change else {
+            queue!(
+                output,
+                style::Print(" ".repeat(self.line_num_width + 4 + self.text_width))
+            )?;
+            return Ok(());
+        };
+        let (text_color, gutter_bg_color, line_bg_color) = change_colors(change.tag(), file);
+        let (sign, index) = match change.tag() {
+            similar::ChangeTag::Equal if left => (" ", change.old_index()),
+            similar::ChangeTag::Equal => (" ", change.new_index()),
+            similar::ChangeTag::Delete => ("-", change.old_index()),
+            similar::ChangeTag::Insert => ("+", change.new_index()),
+        };
+        let line_num = index.map(|i| (i + self.start_line).to_string()).unwrap_or_default();
+        queue!(
+            output,
+            style::SetBackgroundColor(gutter_bg_color),
+            style::SetForegroundColor(text_color),
+            style::Print(format!("{sign} {line_num:>width$}", width = self.line_num_width)),
+            style::SetForegroundColor(style::Color::Reset),
+            style::Print(":"),
+            style::SetForegroundColor(text_color),
+            style::SetBackgroundColor(line_bg_color),
+            style::Print(" "),
+            style::Print(fit_to_width(change.value(), self.text_width)),
+            style::ResetColor,
+        )?;
+        Ok(())
+    }
+}
+
+/// Returns `line`, which may be styled with escape codes, cut or padded with spaces to take
+/// exactly `width` columns.
+fn fit_to_width(line: &str, width: usize) -> String {
+    use unicode_width::UnicodeWidthChar;
+
+    let line = line
+        .trim_end_matches(['\n', '\r'])
+        .trim_end_matches("\x1b[K")
+        .replace('\t', "    ");
+    let mut fitted = String::new();
+    let mut used = 0;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        // Escape codes take no columns, and are kept for the styling of what follows.
+        if c == '\x1b' {
+            fitted.push(c);
+            if chars.peek() == Some(&'[') {
+                for c in chars.by_ref() {
+                    fitted.push(c);
+                    if c != '[' && ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        let c_width = c.width().unwrap_or(0);
+        if used + c_width > width {
+            // Mark the cut, in place of the last character that fit.
+            if let Some(last) = fitted.pop() {
+                used -= last.width().unwrap_or(0);
+            }
+            fitted.push('…');
+            used += 1;
+            break;
+        }
+        fitted.push(c);
+        used += c_width;
+    }
+    fitted.push_str(&" ".repeat(width.saturating_sub(used)));
+    fitted
+}
+
 /// Prints a git-diff style comparison between `old_str` and `new_str`.
 /// - `start_line` - 1-indexed line number that `old_str` and `new_str` start at.
 fn print_diff(
@@ -531,22 +1071,7 @@ fn print_diff(
     }
     for change in diff.iter_all_changes() {
         // Define the colors per line.
-        let (text_color, gutter_bg_color, line_bg_color) = match (change.tag(), new_str.truecolor) {
-            (similar::ChangeTag::Equal, true) => (style::Color::Reset, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Delete, true) => (
-                style::Color::Reset,
-                style::Color::Rgb { r: 79, g: 40, b: 40 },
-                style::Color::Rgb { r: 36, g: 25, b: 28 },
-            ),
-            (similar::ChangeTag::Insert, true) => (
-                style::Color::Reset,
-                style::Color::Rgb { r: 40, g: 67, b: 43 },
-                style::Color::Rgb { r: 24, g: 38, b: 30 },
-            ),
-            (similar::ChangeTag::Equal, false) => (style::Color::Reset, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Delete, false) => (style::Color::Red, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Insert, false) => (style::Color::Green, new_str.gutter_bg, new_str.line_bg),
-        };
+        let (text_color, gutter_bg_color, line_bg_color) = change_colors(change.tag(), new_str);
         // Define the change tag character to print, if any.
         let sign = match change.tag() {
             similar::ChangeTag::Equal => " ",
@@ -607,6 +1132,123 @@ fn print_diff(
     Ok(())
 }
 
+/// Lines of unchanged context around each [Hunk].
+const HUNK_CONTEXT: usize = 3;
+
+/// A group of nearby changed lines, as in `git add -p`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// 1-indexed line the hunk starts at before the change.
+    pub old_start: usize,
+    /// 1-indexed line the hunk starts at after the change.
+    pub new_start: usize,
+    /// The lines of the hunk, without their line endings.
+    pub lines: Vec<(similar::ChangeTag, String)>,
+}
+
+impl std::fmt::Display for Hunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count_without = |tag| self.lines.iter().filter(|(t, _)| *t != tag).count();
+        writeln!(
+            f,
+            "@@ -{},{} +{},{} @@",
+            self.old_start,
+            count_without(similar::ChangeTag::Insert),
+            self.new_start,
+            count_without(similar::ChangeTag::Delete)
+        )?;
+        for (tag, line) in &self.lines {
+            writeln!(f, "{}{line}", tag)?;
+        }
+        Ok(())
+    }
+}
+
+impl Hunk {
+    /// Queues the hunk with its header and changed lines colored as in `git diff`.
+    pub fn queue(&self, output: &mut impl Write) -> std::io::Result<()> {
+        for (i, line) in self.to_string().lines().enumerate() {
+            let color = match line.chars().next() {
+                _ if i == 0 => Color::Cyan,
+                Some('-') => Color::Red,
+                Some('+') => Color::Green,
+                _ => Color::Reset,
+            };
+            queue!(
+                output,
+                style::SetForegroundColor(color),
+                style::Print(line),
+                style::Print("\n"),
+            )?;
+        }
+        queue!(output, style::ResetColor)?;
+        Ok(())
+    }
+}
+
+/// Returns the [Hunk]s of the change from `old` to `new`, with [HUNK_CONTEXT] lines around each.
+pub fn diff_hunks(old: &str, new: &str) -> Vec<Hunk> {
+    let diff = similar::TextDiff::from_lines(old, new);
+    diff.grouped_ops(HUNK_CONTEXT)
+        .iter()
+        .filter_map(|group| {
+            let first = group.first()?;
+            Some(Hunk {
+                old_start: first.old_range().start + 1,
+                new_start: first.new_range().start + 1,
+                lines: group
+                    .iter()
+                    .flat_map(|op| diff.iter_changes(op))
+                    .map(|change| (change.tag(), change.value().trim_end_matches(['\n', '\r']).to_string()))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Returns `old` with only the [Hunk]s of the change to `new` that are `accepted` applied.
+fn apply_hunks(old: &str, new: &str, accepted: &[bool]) -> String {
+    let diff = similar::TextDiff::from_lines(old, new);
+    let groups = diff.grouped_ops(HUNK_CONTEXT);
+    let mut applied = String::new();
+    for op in diff.ops() {
+        let hunk_accepted = groups
+            .iter()
+            .position(|group| group.contains(op))
+            .is_none_or(|i| accepted.get(i).copied().unwrap_or(true));
+        for change in diff.iter_changes(op) {
+            match change.tag() {
+                similar::ChangeTag::Equal => applied.push_str(change.value()),
+                similar::ChangeTag::Delete if !hunk_accepted => applied.push_str(change.value()),
+                similar::ChangeTag::Insert if hunk_accepted => applied.push_str(change.value()),
+                _ => (),
+            }
+        }
+    }
+    applied
+}
+
+/// Returns the note added to the tool result about the hunks of the change from `old` to `new` that
+/// the user rejected, so that the model knows they were not applied.
+fn rejected_hunks_note(old: &str, new: &str, accepted: &[bool]) -> Option<String> {
+    let hunks = diff_hunks(old, new);
+    let rejected = hunks
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !accepted.get(*i).copied().unwrap_or(true))
+        .map(|(_, hunk)| hunk.to_string())
+        .collect::<Vec<_>>();
+    if rejected.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "The user reviewed the change hunk by hunk and rejected {} of {} hunks, which were NOT applied. The rest of the change was written. Rejected hunks:\n{}",
+        rejected.len(),
+        hunks.len(),
+        rejected.join("")
+    ))
+}
+
 /// Returns a 1-indexed line number range of the start and end of `needle` inside `file`.
 fn line_number_at(file: impl AsRef<str>, needle: impl AsRef<str>) -> Option<(usize, usize)> {
     let file = file.as_ref();
@@ -620,6 +1262,278 @@ fn line_number_at(file: impl AsRef<str>, needle: impl AsRef<str>) -> Option<(usi
     }
 }
 
+/// Returns the error of [FsWrite::Create] for an existing file under [CreatePolicy::FailIfExists].
+fn file_exists_error(relative_path: &str) -> String {
+    format!(
+        "{relative_path} already exists and the fs_write create policy does not allow replacing files. Use the `str_replace` or `edits` command to change it instead"
+    )
+}
+
+/// Queues the diff of replacing `old_str` with `new_str` in `file`, returning where `old_str` was
+/// found if it was.
+fn queue_str_replace_diff(
+    os: &Os,
+    output: &mut impl Write,
+    relative_path: &str,
+    file: &str,
+    old_str: &str,
+    new_str: &str,
+) -> Result<Option<OldStrMatch>> {
+    let found = find_old_str(file, old_str, new_str).ok();
+    let (start_line, old_str, new_str) = match &found {
+        Some(found) => (
+            found.start_line(file),
+            &file[found.range.clone()],
+            found.new_str.as_str(),
+        ),
+        None => match line_number_at(file, old_str) {
+            Some((start_line, _)) => (start_line, old_str, new_str),
+            _ => (0, old_str, new_str),
+        },
+    };
+    let old_str = stylize_output_if_able(os, relative_path, old_str);
+    let new_str = stylize_output_if_able(os, relative_path, new_str);
+    print_styled_diff(os, output, &old_str, &new_str, start_line)?;
+    if let Some((fuzzy, confidence)) = found.as_ref().and_then(|found| found.fuzzy) {
+        queue!(
+            output,
+            style::SetForegroundColor(Color::Yellow),
+            style::Print(format!(
+                "old_str does not match the file exactly, it was matched {} ({:.0}% confidence)\n",
+                fuzzy.description(),
+                confidence * 100.0
+            )),
+            style::ResetColor,
+        )?;
+    }
+    Ok(found)
+}
+
+/// Applies the `edits` of [FsWrite::Edits] to `file` in order, returning the edited file and the
+/// notes about the edits that were not matched exactly. Fails if any of the edits does not apply.
+fn apply_edits(file: &str, edits: &[StrReplaceEdit]) -> Result<(String, Vec<String>)> {
+    let mut edited = file.to_string();
+    let mut notes = Vec::new();
+    for (i, edit) in edits.iter().enumerate() {
+        let found = find_old_str(&edited, &edit.old_str, &edit.new_str)
+            .map_err(|err| eyre::eyre!("edit {} of {}: {err}. No edits were applied", i + 1, edits.len()))?;
+        if let Some(note) = found.note(&edited) {
+            notes.push(format!("Edit {}: {note}", i + 1));
+        }
+        edited = found.replace(&edited);
+    }
+    Ok((edited, notes))
+}
+
+/// Minimum similarity between `old_str` and the lines of a near-match, once both are normalized
+/// by a [FuzzyMatch], for the near-match to be used.
+const FUZZY_MATCH_MIN_CONFIDENCE: f32 = 0.9;
+
+/// How `old_str` of a [FsWrite::StrReplace] was matched when it is not found as is in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FuzzyMatch {
+    /// Lines match except for trailing whitespace, line endings, and the length of whitespace runs.
+    Whitespace,
+    /// Lines match once trimmed, so indentation may differ too.
+    LineTrimmed,
+}
+
+impl FuzzyMatch {
+    fn normalize(self, line: &str) -> String {
+        let line = line.trim_end();
+        let content = line.trim_start();
+        let collapsed = content.split_whitespace().collect::<Vec<_>>().join(" ");
+        match self {
+            FuzzyMatch::Whitespace => format!("{}{collapsed}", &line[..line.len() - content.len()]),
+            FuzzyMatch::LineTrimmed => collapsed,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            FuzzyMatch::Whitespace => "ignoring differences in whitespace",
+            FuzzyMatch::LineTrimmed => "ignoring differences in whitespace and indentation",
+        }
+    }
+}
+
+/// Where `old_str` of a [FsWrite::StrReplace] was found in a file.
+#[derive(Debug, Clone, PartialEq)]
+struct OldStrMatch {
+    /// Byte range of the matched text in the file.
+    range: std::ops::Range<usize>,
+    /// The text to replace the match with, reindented to the file for [FuzzyMatch::LineTrimmed].
+    new_str: String,
+    /// How the match was made when it is not exact, and its confidence.
+    fuzzy: Option<(FuzzyMatch, f32)>,
+}
+
+impl OldStrMatch {
+    /// Returns `file` with the match replaced.
+    fn replace(&self, file: &str) -> String {
+        format!(
+            "{}{}{}",
+            &file[..self.range.start],
+            self.new_str,
+            &file[self.range.end..]
+        )
+    }
+
+    /// Returns the 1-indexed line the match starts at.
+    fn start_line(&self, file: &str) -> usize {
+        file[..self.range.start].matches('\n').count() + 1
+    }
+
+    /// Returns the note added to the tool result when the match is not exact, so that the model
+    /// knows what was actually replaced.
+    fn note(&self, file: &str) -> Option<String> {
+        let (fuzzy, confidence) = self.fuzzy?;
+        Some(format!(
+            "old_str did not match the file exactly, so it was matched {} ({:.0}% confidence). The replaced text starting at line {} was:\n{}",
+            fuzzy.description(),
+            confidence * 100.0,
+            self.start_line(file),
+            &file[self.range.clone()]
+        ))
+    }
+}
+
+/// Finds the single occurrence of `old_str` in `file`. When there is none, falls back to matching
+/// whole lines with [FuzzyMatch::Whitespace] then [FuzzyMatch::LineTrimmed], as long as there is a
+/// single match. Lines that are equal once normalized are preferred, then near-matches that differ
+/// on a single line and are similar enough to `old_str`.
+fn find_old_str(file: &str, old_str: &str, new_str: &str) -> Result<OldStrMatch> {
+    match file.match_indices(old_str).collect::<Vec<_>>().as_slice() {
+        [] => (),
+        [(i, _)] => {
+            return Ok(OldStrMatch {
+                range: *i..i + old_str.len(),
+                new_str: new_str.to_string(),
+                fuzzy: None,
+            });
+        },
+        matches => bail!(
+            "{} occurrences of old_str were found when only 1 is expected",
+            matches.len()
+        ),
+    }
+
+    let needle = old_str.lines().collect::<Vec<_>>();
+    if needle.iter().all(|line| line.trim().is_empty()) {
+        bail!("no occurrences of \"{old_str}\" were found");
+    }
+    // Byte ranges of the lines of the file, without their line endings.
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in file.split_inclusive('\n') {
+        lines.push(start..start + line.trim_end_matches(['\n', '\r']).len());
+        start += line.len();
+    }
+
+    // The candidates of each way of matching, as the index of their first line, whether they are
+    // equal to old_str once normalized, and their similarity to it.
+    let candidates = [FuzzyMatch::Whitespace, FuzzyMatch::LineTrimmed].map(|fuzzy| {
+        let normalized_needle = needle.iter().map(|line| fuzzy.normalize(line)).collect::<Vec<_>>();
+        let normalized_file = lines
+            .iter()
+            .map(|range| fuzzy.normalize(&file[range.clone()]))
+            .collect::<Vec<_>>();
+        let candidates = normalized_file
+            .windows(needle.len())
+            .enumerate()
+            .filter_map(|(i, window)| {
+                let differing = window
+                    .iter()
+                    .zip(&normalized_needle)
+                    .filter(|(line, needle_line)| line != needle_line)
+                    .count();
+                match differing {
+                    0 => Some((i, true, 1.0)),
+                    1 => Some((i, false, similarity(&normalized_needle.join("\n"), &window.join("\n")))),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        (fuzzy, candidates)
+    });
+
+    for equal in [true, false] {
+        for (fuzzy, candidates) in &candidates {
+            let matches = candidates
+                .iter()
+                .filter(|(_, is_equal, confidence)| *is_equal == equal && *confidence >= FUZZY_MATCH_MIN_CONFIDENCE)
+                .collect::<Vec<_>>();
+            match matches.as_slice() {
+                [] => (),
+                [(i, _, confidence)] => {
+                    let mut range = lines[*i].start..lines[i + needle.len() - 1].end;
+                    // Keep the line ending when old_str had one.
+                    if old_str.ends_with('\n') {
+                        range.end = file[range.end..].find('\n').map_or(file.len(), |i| range.end + i + 1);
+                    }
+                    let new_str = match fuzzy {
+                        FuzzyMatch::Whitespace => new_str.to_string(),
+                        FuzzyMatch::LineTrimmed => reindent(new_str, &needle, &file[range.clone()]),
+                    };
+                    return Ok(OldStrMatch {
+                        range,
+                        new_str,
+                        fuzzy: Some((*fuzzy, *confidence)),
+                    });
+                },
+                matches => bail!(
+                    "no occurrences of \"{old_str}\" were found, and {} occurrences were found {} when only 1 is expected",
+                    matches.len(),
+                    fuzzy.description()
+                ),
+            }
+        }
+    }
+
+    let closest = candidates
+        .iter()
+        .flat_map(|(_, candidates)| candidates)
+        .max_by(|a, b| a.2.total_cmp(&b.2));
+    if let Some((i, _, confidence)) = closest {
+        bail!(
+            "no occurrences of \"{old_str}\" were found, and the closest text, at line {}, is only {:.0}% similar",
+            i + 1,
+            confidence * 100.0
+        );
+    }
+    bail!("no occurrences of \"{old_str}\" were found")
+}
+
+/// Returns the similarity of `a` and `b` between 0 and 1, comparing their words so that a changed
+/// identifier weighs more than a changed character.
+fn similarity(a: &str, b: &str) -> f32 {
+    similar::TextDiff::from_words(a, b).ratio()
+}
+
+/// Returns `new_str` with the indentation that the lines of `matched` have on top of `old_lines`
+/// added to each of its lines, when all of them have the same extra indentation.
+fn reindent(new_str: &str, old_lines: &[&str], matched: &str) -> String {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut extras = old_lines
+        .iter()
+        .zip(matched.lines())
+        .filter(|(old, _)| !old.trim().is_empty())
+        .map(|(old, matched)| matched[..indent(matched)].strip_suffix(&old[..indent(old)]));
+    let Some(Some(extra)) = extras.next() else {
+        return new_str.to_string();
+    };
+    if extra.is_empty() || !extras.all(|other| other == Some(extra)) {
+        return new_str.to_string();
+    }
+    new_str
+        .split_inclusive('\n')
+        .map(|line| match line.trim().is_empty() {
+            true => line.to_string(),
+            false => format!("{extra}{line}"),
+        })
+        .collect()
+}
+
 /// Returns the number of terminal cells required for displaying line numbers. This is used to
 /// determine how many characters the gutter should allocate when displaying line numbers for a
 /// text file.
@@ -775,6 +1689,15 @@ mod tests {
         });
         let fw = serde_json::from_value::<FsWrite>(v).unwrap();
         assert!(matches!(fw, FsWrite::Append { .. }));
+
+        // edits
+        let v = serde_json::json!({
+            "path": path,
+            "command": "edits",
+            "edits": [{ "old_str": "a", "new_str": "b" }, { "old_str": "c", "new_str": "d" }],
+        });
+        let fw = serde_json::from_value::<FsWrite>(v).unwrap();
+        assert!(matches!(fw, FsWrite::Edits { ref edits, .. } if edits.len() == 2));
     }
 
     #[test]
@@ -1099,6 +2022,343 @@ mod tests {
         assert_eq!(terminal_width_required_for_line_count(999), 3);
     }
 
+    #[test]
+    fn test_fit_to_width() {
+        assert_eq!(fit_to_width("abc\n", 5), "abc  ");
+        assert_eq!(fit_to_width("abcdef\n", 4), "abc…");
+        assert_eq!(fit_to_width("\x1b[31mab\x1b[0m\x1b[K\n", 3), "\x1b[31mab\x1b[0m ");
+        assert_eq!(fit_to_width("漢字漢\n", 4), "漢… ");
+    }
+
+    #[test]
+    fn test_print_split_diff() {
+        let file = |content: &str| StylizedFile {
+            content: content.to_string(),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        print_split_diff(
+            &mut output,
+            &file("one\ntwo\nthree\n"),
+            &file("one\n2\nthree\nfour\n"),
+            10,
+            40,
+        )
+        .unwrap();
+        let output = strip_ansi_escapes::strip_str(String::from_utf8(output).unwrap());
+        assert_eq!(output.lines().map(str::trim_end).collect::<Vec<_>>(), vec![
+            "  10: one          │   10: one",
+            "- 11: two          │ + 11: 2",
+            "  12: three        │   12: three",
+            "                   │ + 13: four",
+            "",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_split_diff_style() {
+        let mut os = Os::new().await.unwrap();
+        let file = |content: &str| StylizedFile {
+            content: content.to_string(),
+            ..Default::default()
+        };
+        let (old, new) = (file("a\n"), file("b\n"));
+        let print = |os: &Os| {
+            let mut output = Vec::new();
+            print_styled_diff(os, &mut output, &old, &new, 1).unwrap();
+            strip_ansi_escapes::strip_str(String::from_utf8(output).unwrap())
+        };
+
+        unsafe { os.env.set_var("COLUMNS", "200") };
+        assert!(!print(&os).contains('│'));
+        os.database.settings.set(Setting::ChatDiffStyle, "split").await.unwrap();
+        assert!(print(&os).contains('│'));
+        // Too narrow to fit both sides.
+        unsafe { os.env.set_var("COLUMNS", "80") };
+        assert!(!print(&os).contains('│'));
+    }
+
+    #[test]
+    fn test_hunks() {
+        let old = (1..=20).map(|i| format!("{i}\n")).collect::<String>();
+        let new = old.replace("\n2\n", "\ntwo\n").replace("18\n", "eighteen\n");
+        let hunks = diff_hunks(&old, &new);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].to_string().lines().next(), Some("@@ -1,5 +1,5 @@"));
+        assert!(hunks[1].to_string().contains("-18\n+eighteen\n"));
+
+        assert_eq!(apply_hunks(&old, &new, &[true, true]), new);
+        assert_eq!(apply_hunks(&old, &new, &[false, false]), old);
+        assert_eq!(
+            apply_hunks(&old, &new, &[false, true]),
+            old.replace("18\n", "eighteen\n")
+        );
+
+        assert_eq!(rejected_hunks_note(&old, &new, &[true, true]), None);
+        let note = rejected_hunks_note(&old, &new, &[true, false]).unwrap();
+        assert!(note.contains("rejected 1 of 2 hunks"));
+        assert!(note.contains("+eighteen"));
+        assert!(!note.contains("+two"));
+    }
+
+    #[tokio::test]
+    async fn test_fs_write_accepted_hunks() {
+        let os = setup_test_directory().await;
+        let mut stdout = std::io::stdout();
+        let old = (1..=20).map(|i| format!("{i}\n")).collect::<String>();
+        os.fs.write("/numbers.txt", &old).await.unwrap();
+
+        let new = old.replace("\n2\n", "\ntwo\n").replace("18\n", "eighteen\n");
+        let mut fs_write = serde_json::from_value::<FsWrite>(serde_json::json!({
+            "path": "/numbers.txt",
+            "command": "create",
+            "file_text": new,
+        }))
+        .unwrap();
+        assert_eq!(fs_write.hunks(&os).unwrap().len(), 2);
+        fs_write.accept_hunks(vec![true, false]);
+        let result = fs_write.invoke(&os, &mut stdout).await.unwrap();
+        assert_eq!(
+            os.fs.read_to_string("/numbers.txt").await.unwrap(),
+            old.replace("\n2\n", "\ntwo\n")
+        );
+        assert!(result.as_str().contains("rejected 1 of 2 hunks"));
+    }
+
+    #[tokio::test]
+    async fn test_fs_write_create_policy() {
+        let os = setup_test_directory().await;
+        let mut stdout = std::io::stdout();
+        let agent = |policy: &str| Agent {
+            tools_settings: serde_json::from_value(serde_json::json!({
+                "fs_write": { "createPolicy": policy }
+            }))
+            .unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(CreatePolicy::configured(&Agent::default()), CreatePolicy::Overwrite);
+        assert_eq!(CreatePolicy::configured(&agent("ask")), CreatePolicy::Ask);
+        assert_eq!(
+            CreatePolicy::configured(&agent("failIfExists")),
+            CreatePolicy::FailIfExists
+        );
+        assert_eq!(CreatePolicy::configured(&agent("sometimes")), CreatePolicy::Overwrite);
+
+        let create = |path: &str, policy: CreatePolicy| {
+            let mut fs_write = serde_json::from_value::<FsWrite>(serde_json::json!({
+                "path": path,
+                "command": "create",
+                "file_text": "new",
+            }))
+            .unwrap();
+            fs_write.set_create_policy(policy);
+            fs_write
+        };
+
+        // Existing files are only replaced when the policy allows it.
+        let mut fs_write = create(TEST_FILE_PATH, CreatePolicy::FailIfExists);
+        assert!(fs_write.validate(&os).await.is_err());
+        assert!(fs_write.invoke(&os, &mut stdout).await.is_err());
+        assert_eq!(os.fs.read_to_string(TEST_FILE_PATH).await.unwrap(), TEST_FILE_CONTENTS);
+        assert!(
+            create("/new-file", CreatePolicy::FailIfExists)
+                .validate(&os)
+                .await
+                .is_ok()
+        );
+
+        assert!(create(TEST_FILE_PATH, CreatePolicy::Ask).asks_before_replacing(&os));
+        assert!(!create("/new-file", CreatePolicy::Ask).asks_before_replacing(&os));
+        assert!(!create(TEST_FILE_PATH, CreatePolicy::Overwrite).asks_before_replacing(&os));
+
+        // The result tells whether the file was replaced or created.
+        let result = create(TEST_FILE_PATH, CreatePolicy::Ask)
+            .invoke(&os, &mut stdout)
+            .await
+            .unwrap();
+        assert!(result.as_str().starts_with("Replaced the existing file"));
+        let result = create("/new-file", CreatePolicy::Ask)
+            .invoke(&os, &mut stdout)
+            .await
+            .unwrap();
+        assert!(result.as_str().starts_with("Created the new file"));
+    }
+
+    #[tokio::test]
+    async fn test_fs_write_tool_edits() {
+        let os = setup_test_directory().await;
+        let mut stdout = std::io::stdout();
+        let edits = |edits: serde_json::Value| {
+            serde_json::from_value::<FsWrite>(serde_json::json!({
+                "path": TEST_FILE_PATH,
+                "command": "edits",
+                "edits": edits,
+            }))
+            .unwrap()
+        };
+        let original = os.fs.read_to_string(TEST_FILE_PATH).await.unwrap();
+
+        // Nothing is written when one of the edits does not apply.
+        let mut fs_write = edits(serde_json::json!([
+            { "old_str": "1: Hello world!", "new_str": "1: Goodbye world!" },
+            { "old_str": "asjidfopjaieopr", "new_str": "1623749" },
+        ]));
+        let err = fs_write.validate(&os).await.unwrap_err();
+        assert!(err.to_string().contains("edit 2 of 2"));
+        assert!(fs_write.invoke(&os, &mut stdout).await.is_err());
+        assert_eq!(os.fs.read_to_string(TEST_FILE_PATH).await.unwrap(), original);
+
+        // Edits apply in order, so later ones can refer to the result of earlier ones.
+        let mut fs_write = edits(serde_json::json!([
+            { "old_str": "1: Hello world!", "new_str": "1: Goodbye world!" },
+            { "old_str": "1: Goodbye", "new_str": "1: Farewell" },
+            { "old_str": "2: This is line 2", "new_str": "2: Goodbye world!" },
+        ]));
+        fs_write.validate(&os).await.unwrap();
+        fs_write.invoke(&os, &mut stdout).await.unwrap();
+        let file = os.fs.read_to_string(TEST_FILE_PATH).await.unwrap();
+        assert_eq!(file.lines().take(2).collect::<Vec<_>>(), vec![
+            "1: Farewell world!",
+            "2: Goodbye world!"
+        ]);
+    }
+
+    #[test]
+    fn test_find_old_str() {
+        let file = "fn main() {\n    let x = 1;  \n    if x == 1 {\n        println!(\"one\");\n    }\n}\n";
+        let replace = |old_str: &str, new_str: &str| {
+            let found = find_old_str(file, old_str, new_str)?;
+            eyre::Ok((found.replace(file), found.fuzzy.map(|(fuzzy, _)| fuzzy)))
+        };
+
+        // Exact matches are used as is.
+        assert_eq!(
+            replace("let x = 1;", "let x = 2;").unwrap(),
+            (file.replace("let x = 1;", "let x = 2;"), None)
+        );
+
+        // Trailing whitespace and runs of whitespace.
+        assert_eq!(
+            replace(
+                "    let x  = 1;\n    if x == 1 {\n",
+                "    let x = 2;\n    if x == 2 {\n"
+            )
+            .unwrap(),
+            (
+                file.replace(
+                    "    let x = 1;  \n    if x == 1 {\n",
+                    "    let x = 2;\n    if x == 2 {\n"
+                ),
+                Some(FuzzyMatch::Whitespace)
+            )
+        );
+
+        // Indentation, with the replacement reindented to the file.
+        assert_eq!(
+            replace(
+                "if x == 1 {\n    println!(\"one\");\n}",
+                "if x == 1 {\n    println!(\"uno\");\n}"
+            )
+            .unwrap(),
+            (file.replace("\"one\"", "\"uno\""), Some(FuzzyMatch::LineTrimmed))
+        );
+
+        // Near-matches that differ on a single line.
+        let found = find_old_str(
+            file,
+            "    let x = 1;\n    if x == 1 {\n        println!(\"one\")\n    }",
+            "    let x = 2;",
+        )
+        .unwrap();
+        assert_eq!(
+            found.replace(file),
+            file.replace(
+                "    let x = 1;  \n    if x == 1 {\n        println!(\"one\");\n    }",
+                "    let x = 2;"
+            )
+        );
+        let (fuzzy, confidence) = found.fuzzy.unwrap();
+        assert_eq!(fuzzy, FuzzyMatch::Whitespace);
+        assert!((FUZZY_MATCH_MIN_CONFIDENCE..1.0).contains(&confidence), "{confidence}");
+
+        // Near-matches that are not similar enough, such as another variable.
+        let err = replace("let y = 1;", "let y = 2;").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("the closest text, at line 2, is only 86% similar"),
+            "{err}"
+        );
+
+        // Ambiguous and missing matches still fail.
+        assert!(replace("}", "]").is_err());
+        assert!(replace("let z = 3;\nlet w = 4;", "").is_err());
+        assert!(replace("\t\n", "").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replaced_path() {
+        let os = setup_test_directory().await;
+        os.fs.write("/replaced.txt", "old").await.unwrap();
+        let replaced_path =
+            |value: serde_json::Value| serde_json::from_value::<FsWrite>(value).unwrap().replaced_path(&os);
+
+        let replaced = Some(os.fs.chroot_path("/replaced.txt"));
+        assert_eq!(
+            replaced_path(serde_json::json!({ "path": "/replaced.txt", "command": "create", "file_text": "new" })),
+            replaced
+        );
+        assert_eq!(
+            replaced_path(serde_json::json!({
+                "path": "/replaced.txt",
+                "command": "str_replace",
+                "old_str": "old",
+                "new_str": "new",
+            })),
+            replaced
+        );
+        assert_eq!(
+            replaced_path(serde_json::json!({
+                "path": "/replaced.txt",
+                "command": "edits",
+                "edits": [{ "old_str": "old", "new_str": "new" }],
+            })),
+            replaced
+        );
+        assert_eq!(
+            replaced_path(serde_json::json!({ "path": "/replaced.txt", "command": "append", "new_str": "more" })),
+            None
+        );
+        assert_eq!(
+            replaced_path(serde_json::json!({ "path": "/missing.txt", "command": "create", "file_text": "new" })),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fs_write_str_replace_fuzzy_note() {
+        let os = setup_test_directory().await;
+        let mut stdout = std::io::stdout();
+        os.fs.write("/fuzzy.txt", "a\n    b   \n    c\n").await.unwrap();
+
+        let result = serde_json::from_value::<FsWrite>(serde_json::json!({
+            "path": "/fuzzy.txt",
+            "command": "str_replace",
+            "old_str": "b\nc",
+            "new_str": "d\nc",
+        }))
+        .unwrap()
+        .invoke(&os, &mut stdout)
+        .await
+        .unwrap();
+        assert_eq!(os.fs.read_to_string("/fuzzy.txt").await.unwrap(), "a\n    d\n    c\n");
+        assert!(
+            result
+                .as_str()
+                .contains("ignoring differences in whitespace and indentation")
+        );
+        assert!(result.as_str().contains("starting at line 2"));
+    }
+
     #[tokio::test]
     async fn test_fs_write_with_tilde_paths() {
         // Create a test context
@@ -1169,4 +2429,76 @@ mod tests {
         let nested_content = os.fs.read_to_string(&nested_file_path).await.unwrap();
         assert_eq!(nested_content, "content in nested path\n");
     }
+
+    #[tokio::test]
+    async fn test_eval_perm_resolves_paths() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/workspace/src").await.unwrap();
+        os.fs.create_dir_all("/secret").await.unwrap();
+        os.fs.symlink("/secret", "/workspace/src/link").await.unwrap();
+
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "allowedTools": ["fs_write"],
+            "toolsSettings": {
+                "fs_write": {
+                    "allowedPaths": ["./src/**", "/workspace/**", "~/notes.md"],
+                    "deniedPaths": ["/secret/**"],
+                },
+            },
+        }))
+        .unwrap();
+
+        let eval = |path: &str| {
+            serde_json::from_value::<FsWrite>(serde_json::json!({
+                "path": path,
+                "command": "create",
+                "file_text": "",
+            }))
+            .unwrap()
+            .eval_perm(&os, &agent)
+        };
+
+        assert_eq!(eval("/workspace/src/main.rs"), PermissionEvalResult::Allow);
+        assert_eq!(eval("./src/main.rs"), PermissionEvalResult::Allow);
+        assert_eq!(eval("~/notes.md"), PermissionEvalResult::Allow);
+        assert_eq!(eval("/workspace/src/../../secret/file"), PermissionEvalResult::Deny);
+        assert_eq!(eval("src/../../secret/file"), PermissionEvalResult::Deny);
+        assert_eq!(eval("/workspace/../etc/passwd"), PermissionEvalResult::Ask);
+        // Resolves into a denied directory through a symlink
+        assert_eq!(eval("/workspace/src/link/file"), PermissionEvalResult::Deny);
+    }
+
+    #[tokio::test]
+    async fn test_eval_perm_symlink_escape() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/workspace/src").await.unwrap();
+        os.fs.create_dir_all("/outside").await.unwrap();
+        os.fs.symlink("/outside", "/workspace/src/link").await.unwrap();
+        os.fs.symlink("/workspace", "/alias").await.unwrap();
+        os.env.set_current_dir("/workspace");
+
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "allowedTools": ["fs_write"],
+            "toolsSettings": {
+                "fs_write": { "allowedPaths": ["**"] },
+            },
+        }))
+        .unwrap();
+        let eval = |path: &str| {
+            serde_json::from_value::<FsWrite>(serde_json::json!({
+                "path": path,
+                "command": "create",
+                "file_text": "",
+            }))
+            .unwrap()
+            .eval_perm(&os, &agent)
+        };
+
+        assert_eq!(eval("/workspace/src/main.rs"), PermissionEvalResult::Allow);
+        // Leaves the workspace through a symlink, even though the globs allow every path
+        assert_eq!(eval("/workspace/src/link/file"), PermissionEvalResult::Ask);
+        assert_eq!(eval("/workspace/src/link/new/file"), PermissionEvalResult::Ask);
+        // Resolves into the workspace, like /tmp to /private/tmp on macOS
+        assert_eq!(eval("/alias/src/main.rs"), PermissionEvalResult::Allow);
+    }
 }
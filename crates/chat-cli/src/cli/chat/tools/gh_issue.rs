@@ -1,5 +1,6 @@
 use std::collections::VecDeque;
 use std::io::Write;
+use std::path::PathBuf;
 
 use crossterm::style::Color;
 use crossterm::{
@@ -11,13 +12,28 @@ use eyre::{
     WrapErr,
     eyre,
 };
-use serde::Deserialize;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    Map,
+    Value,
+};
+use time::OffsetDateTime;
 
 use super::super::context::ContextManager;
 use super::super::util::issue::IssueCreator;
-use super::InvokeOutput;
+use super::{
+    InvokeOutput,
+    OutputKind,
+    PermissionEvalResult,
+};
+use crate::cli::agent::Agent;
+use crate::cli::chat::redact::Redactor;
 use crate::cli::chat::token_counter::TokenCounter;
 use crate::os::Os;
+use crate::os::diagnostics::Diagnostics;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct GhIssue {
@@ -25,9 +41,16 @@ pub struct GhIssue {
     pub expected_behavior: Option<String>,
     pub actual_behavior: Option<String>,
     pub steps_to_reproduce: Option<String>,
+    /// Whether to write a [DiagnosticsBundle] to attach to the issue, instead of pasting the
+    /// session details inline.
+    #[serde(default)]
+    pub include_diagnostics: bool,
 
     #[serde(skip_deserializing)]
     pub context: Option<GhIssueContext>,
+    /// Built by [Self::validate], so that the user reviews it before accepting the tool use.
+    #[serde(skip_deserializing)]
+    pub bundle: Option<DiagnosticsBundle>,
 }
 
 #[derive(Debug, Clone)]
@@ -36,10 +59,27 @@ pub struct GhIssueContext {
     pub transcript: VecDeque<String>,
     pub failed_request_ids: Vec<String>,
     pub tool_permissions: Vec<String>,
+    pub mcp_servers: Vec<String>,
+}
+
+/// Details of the session attached to an issue, with secrets and the home directory masked.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsBundle {
+    pub diagnostics: Diagnostics,
+    pub transcript: Vec<String>,
+    pub transcript_truncated: bool,
+    pub failed_request_ids: Vec<String>,
+    pub settings: Map<String, Value>,
+    pub mcp_servers: Vec<String>,
+    pub trusted_tools: Vec<String>,
 }
 
 /// Max amount of characters to include in the transcript.
 const MAX_TRANSCRIPT_CHAR_LEN: usize = 3_000;
+/// Max amount of characters of the transcript to include in a [DiagnosticsBundle].
+const MAX_BUNDLE_TRANSCRIPT_CHAR_LEN: usize = 30_000;
+/// Number of transcript entries shown when reviewing a [DiagnosticsBundle].
+const REVIEW_TRANSCRIPT_ENTRIES: usize = 4;
 
 impl GhIssue {
     pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
@@ -49,6 +89,38 @@ impl GhIssue {
             ));
         };
 
+        if let Some(bundle) = &self.bundle {
+            let path = bundle_path();
+            os.fs
+                .write(&path, serde_json::to_vec_pretty(bundle)?)
+                .await
+                .wrap_err("failed to write the diagnostics bundle")?;
+            let attach = format!(
+                "See the attached diagnostics bundle `{}`.",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            );
+            let _ = IssueCreator {
+                title: Some(self.title.clone()),
+                expected_behavior: self.expected_behavior.clone(),
+                actual_behavior: Some(match &self.actual_behavior {
+                    Some(behavior) => format!("{behavior}\n\n{attach}"),
+                    None => attach.clone(),
+                }),
+                steps_to_reproduce: self.steps_to_reproduce.clone(),
+                additional_environment: Some(Self::get_request_ids(context)),
+            }
+            .create_url(os)
+            .await
+            .wrap_err("failed to invoke gh issue tool");
+
+            return Ok(InvokeOutput {
+                output: OutputKind::Text(format!(
+                    "The diagnostics bundle was written to {}, the user needs to attach it to the issue.",
+                    path.display()
+                )),
+            });
+        }
+
         // Prepare additional details from the chat session
         let additional_environment = [
             Self::get_chat_settings(context),
@@ -81,33 +153,55 @@ impl GhIssue {
         self.context = Some(context);
     }
 
-    fn get_transcript(context: &GhIssueContext) -> String {
-        let mut transcript_str = String::from("```\n[chat-transcript]\n");
+    /// Only the issue itself can be opened without approval, the diagnostics bundle is reviewed
+    /// first unless the agent trusts the tool.
+    pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
+        if !self.include_diagnostics || agent.allowed_tools.contains("gh_issue") {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    /// Returns the last entries of `transcript` up to `max_len` characters, and whether older
+    /// entries were left out.
+    fn transcript_tail(transcript: &VecDeque<String>, max_len: usize) -> (Vec<String>, bool) {
         let mut is_truncated = false;
-        let transcript: Vec<String> = context.transcript
+        let tail: Vec<String> = transcript
             .iter()
             .rev() // To take last N items
             .scan(0, |user_msg_char_count, line| {
-                if *user_msg_char_count >= MAX_TRANSCRIPT_CHAR_LEN {
-                        is_truncated = true;
+                if *user_msg_char_count >= max_len {
+                    is_truncated = true;
                     return None;
                 }
-                let remaining_chars = MAX_TRANSCRIPT_CHAR_LEN - *user_msg_char_count;
+                let mut remaining_chars = max_len - *user_msg_char_count;
                 let trimmed_line = if line.len() > remaining_chars {
+                    while !line.is_char_boundary(remaining_chars) {
+                        remaining_chars -= 1;
+                    }
                     &line[..remaining_chars]
                 } else {
                     line
                 };
-                *user_msg_char_count += trimmed_line.len();
-
-                // backticks will mess up the markdown
-                let text = trimmed_line.replace("```", r"\```");
-                Some(text)
+                *user_msg_char_count += trimmed_line.len().max(1);
+                Some(trimmed_line.to_string())
             })
             .collect::<Vec<_>>()
             .into_iter()
             .rev() // Now return items to the proper order
             .collect();
+        (tail, is_truncated)
+    }
+
+    fn get_transcript(context: &GhIssueContext) -> String {
+        let mut transcript_str = String::from("```\n[chat-transcript]\n");
+        let (transcript, is_truncated) = Self::transcript_tail(&context.transcript, MAX_TRANSCRIPT_CHAR_LEN);
+        // backticks will mess up the markdown
+        let transcript = transcript
+            .iter()
+            .map(|line| line.replace("```", r"\```"))
+            .collect::<Vec<_>>();
 
         if !transcript.is_empty() {
             transcript_str.push_str(&transcript.join("\n\n"));
@@ -178,17 +272,171 @@ impl GhIssue {
         result_str
     }
 
+    /// Builds the diagnostics bundle of the session in `context`.
+    async fn build_bundle(os: &Os, context: &GhIssueContext) -> DiagnosticsBundle {
+        let redactor = Redactor::new(os);
+        let home = os.env.home().map(|home| home.to_string_lossy().into_owned());
+        let sanitize = |text: &str| {
+            let text = redactor.redact(text);
+            match &home {
+                Some(home) if !home.is_empty() => text.replace(home.as_str(), "~"),
+                _ => text,
+            }
+        };
+
+        let (transcript, transcript_truncated) =
+            Self::transcript_tail(&context.transcript, MAX_BUNDLE_TRANSCRIPT_CHAR_LEN);
+        let mut settings = Value::Object(os.database.settings.map());
+        redactor.redact_json(&mut settings);
+        let Value::Object(settings) = settings else {
+            unreachable!("redaction keeps the settings an object");
+        };
+
+        DiagnosticsBundle {
+            diagnostics: Diagnostics::new(&os.env).await,
+            transcript: transcript.iter().map(|line| sanitize(line)).collect(),
+            transcript_truncated,
+            failed_request_ids: context.failed_request_ids.clone(),
+            settings: settings
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Value::String(value) => (key, Value::String(sanitize(&value))),
+                    value => (key, value),
+                })
+                .collect(),
+            mcp_servers: context.mcp_servers.clone(),
+            trusted_tools: context.tool_permissions.clone(),
+        }
+    }
+
     pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
-        Ok(queue!(
+        queue!(
             output,
             style::Print("I will prepare a github issue with our conversation history.\n\n"),
             style::SetForegroundColor(Color::Green),
             style::Print(format!("Title: {}\n", &self.title)),
             style::ResetColor
-        )?)
+        )?;
+        let Some(bundle) = &self.bundle else {
+            return Ok(());
+        };
+
+        queue!(
+            output,
+            style::Print(
+                "\nA diagnostics bundle will be written to attach to the issue, review it for anything you do not want to share:\n"
+            ),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!(
+                "  Transcript: {} entries{}\n",
+                bundle.transcript.len(),
+                if bundle.transcript_truncated {
+                    " (truncated)"
+                } else {
+                    ""
+                }
+            )),
+            style::Print(format!("  Failed request ids: {}\n", bundle.failed_request_ids.len())),
+            style::Print(format!(
+                "  Settings: {}\n",
+                bundle.settings.keys().cloned().collect::<Vec<_>>().join(", ")
+            )),
+            style::Print(format!("  MCP servers: {}\n", bundle.mcp_servers.join(", "))),
+            style::Print(format!("  Trusted tools: {}\n", bundle.trusted_tools.join(", "))),
+            style::ResetColor,
+        )?;
+        let skipped = bundle.transcript.len().saturating_sub(REVIEW_TRANSCRIPT_ENTRIES);
+        if skipped > 0 {
+            queue!(
+                output,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("  ... {skipped} earlier entries\n")),
+                style::ResetColor,
+            )?;
+        }
+        for line in bundle.transcript.iter().skip(skipped) {
+            queue!(output, style::Print(format!("  │ {}\n", line.replace('\n', "\n  │ "))))?;
+        }
+        Ok(())
     }
 
-    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        if let (true, Some(context)) = (self.include_diagnostics, &self.context) {
+            self.bundle = Some(Self::build_bundle(os, context).await);
+        }
         Ok(())
     }
 }
+
+/// Path of a new diagnostics bundle, in the temporary directory.
+fn bundle_path() -> PathBuf {
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+    std::env::temp_dir().join(format!("q-chat-diagnostics-{timestamp}.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::settings::Setting;
+    use crate::os::Env;
+
+    fn context(transcript: &[&str]) -> GhIssueContext {
+        GhIssueContext {
+            context_manager: None,
+            transcript: transcript.iter().map(|s| (*s).to_string()).collect(),
+            failed_request_ids: vec!["request-1".to_string()],
+            tool_permissions: vec!["fs_read".to_string()],
+            mcp_servers: vec!["git".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_transcript_tail() {
+        let transcript = context(&["first", "second", "third"]).transcript;
+        assert_eq!(
+            GhIssue::transcript_tail(&transcript, 100),
+            (
+                vec!["first".to_string(), "second".to_string(), "third".to_string()],
+                false
+            )
+        );
+        assert_eq!(
+            GhIssue::transcript_tail(&transcript, 8),
+            (vec!["sec".to_string(), "third".to_string()], true)
+        );
+        // Truncated lines keep whole characters.
+        let transcript = context(&["ééé"]).transcript;
+        assert_eq!(GhIssue::transcript_tail(&transcript, 3).0, vec!["é".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_build_bundle() {
+        let mut os = Os::new().await.unwrap();
+        os.env = Env::from_slice(&[
+            ("HOME", "/home/testuser"),
+            ("AWS_SECRET_ACCESS_KEY", "wJalrXUtnFEMI/K7MDENG"),
+        ]);
+        os.database
+            .settings
+            .set(Setting::ChatDefaultModel, "wJalrXUtnFEMI/K7MDENG")
+            .await
+            .unwrap();
+        let context = context(&[
+            "> read /home/testuser/project/notes.md",
+            "my key is wJalrXUtnFEMI/K7MDENG",
+        ]);
+
+        let bundle = GhIssue::build_bundle(&os, &context).await;
+        assert_eq!(bundle.transcript, vec![
+            "> read ~/project/notes.md".to_string(),
+            "my key is [REDACTED:AWS_SECRET_ACCESS_KEY]".to_string()
+        ]);
+        assert_eq!(
+            bundle.settings.get("chat.defaultModel"),
+            Some(&Value::String("[REDACTED:AWS_SECRET_ACCESS_KEY]".to_string()))
+        );
+        assert_eq!(bundle.failed_request_ids, vec!["request-1".to_string()]);
+        assert_eq!(bundle.mcp_servers, vec!["git".to_string()]);
+        assert_eq!(bundle.trusted_tools, vec!["fs_read".to_string()]);
+    }
+}
@@ -0,0 +1,543 @@
+pub mod openapi;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+    eyre,
+};
+use reqwest::header::{
+    CONTENT_TYPE,
+    HeaderMap,
+    HeaderName,
+};
+use reqwest::redirect::Policy;
+use reqwest::{
+    Method,
+    StatusCode,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::error;
+use url::Url;
+
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+use crate::request::client_builder;
+
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 20_000;
+const TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 5;
+const METHODS: [&str; 7] = ["GET", "HEAD", "OPTIONS", "POST", "PUT", "PATCH", "DELETE"];
+/// Methods that do not change anything on the server, which are trusted by default.
+const SAFE_METHODS: [&str; 3] = ["GET", "HEAD", "OPTIONS"];
+/// Response headers whose values are not returned to the model.
+const SENSITIVE_HEADERS: [&str; 2] = ["set-cookie", "www-authenticate"];
+/// Characters of the request body shown when asking for approval.
+const MAX_BODY_PREVIEW_LEN: usize = 500;
+
+/// The `http_request` tool settings of an agent, e.g.
+///
+/// ```json
+/// "toolsSettings": {
+///   "http_request": {
+///     "allowedHosts": ["api.example.com", "*.staging.example.com", "localhost:8080"],
+///     "headers": { "api.example.com": { "Authorization": "Bearer ${EXAMPLE_TOKEN}" } },
+///     "openApi": { "example": "specs/example.yaml" }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpRequestSettings {
+    /// Hosts requests may be sent to, optionally with a port. `*.` matches any subdomain.
+    pub allowed_hosts: Vec<String>,
+    /// Headers added to the requests sent to the hosts matching each key, whose values may refer
+    /// to environment variables as `${NAME}` so that credentials stay out of the agent.
+    #[serde(default)]
+    pub headers: BTreeMap<String, BTreeMap<String, String>>,
+    /// OpenAPI specs by name, as paths or URLs.
+    #[serde(default)]
+    pub open_api: BTreeMap<String, String>,
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+}
+
+fn default_max_response_bytes() -> usize {
+    DEFAULT_MAX_RESPONSE_BYTES
+}
+
+impl HttpRequestSettings {
+    /// Returns the settings configured for `agent`, if it allows any host.
+    pub fn configured(agent: &Agent) -> Option<Self> {
+        let settings = agent.tools_settings.get("http_request")?;
+        match serde_json::from_value::<Self>(settings.clone()) {
+            Ok(settings) if !settings.allowed_hosts.is_empty() => Some(settings),
+            Ok(_) => None,
+            Err(e) => {
+                error!("Failed to deserialize tool settings for http_request: {:?}", e);
+                None
+            },
+        }
+    }
+
+    fn allows(&self, url: &Url) -> bool {
+        matches!(url.scheme(), "http" | "https") && self.allowed_hosts.iter().any(|pattern| host_matches(pattern, url))
+    }
+
+    /// Returns the configured headers for `url`, with the environment variables they refer to
+    /// expanded.
+    fn headers_for(&self, os: &Os, url: &Url) -> Result<Vec<(String, String)>> {
+        let mut headers = Vec::new();
+        for (pattern, values) in &self.headers {
+            if !host_matches(pattern, url) {
+                continue;
+            }
+            for (name, value) in values {
+                headers.push((name.clone(), expand_env(os, value)?));
+            }
+        }
+        Ok(headers)
+    }
+
+    fn header_names_for(&self, url: &Url) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(pattern, _)| host_matches(pattern, url))
+            .flat_map(|(_, values)| values.keys().map(String::as_str))
+            .collect()
+    }
+}
+
+/// Whether the host of `url` matches `pattern`, a host optionally followed by a port, where `*.`
+/// matches any subdomain.
+pub(super) fn host_matches(pattern: &str, url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let pattern = pattern.trim().to_lowercase();
+    let (pattern_host, port) = match pattern.rsplit_once(':') {
+        Some((pattern_host, port)) if port.parse::<u16>().is_ok() => (pattern_host, port.parse::<u16>().ok()),
+        _ => (pattern.as_str(), None),
+    };
+    if port.is_some() && url.port_or_known_default() != port {
+        return false;
+    }
+    let host = host.to_lowercase();
+    match pattern_host.strip_prefix("*.") {
+        Some(domain) => host.ends_with(&format!(".{domain}")),
+        None => host == pattern_host,
+    }
+}
+
+/// Replaces `${NAME}` in `value` with the environment variable `NAME`.
+fn expand_env(os: &Os, value: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        let variable = os
+            .env
+            .get(name)
+            .wrap_err_with(|| format!("The environment variable {name} is not set"))?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&variable);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// The http_request tool sends HTTP requests to the hosts allowed by the agent, or summarizes the
+/// OpenAPI specs configured for it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpRequest {
+    pub method: Option<String>,
+    pub url: Option<String>,
+    pub headers: Option<BTreeMap<String, String>>,
+    /// Sent as is if a string, or as JSON otherwise.
+    pub body: Option<Value>,
+    /// Name of an OpenAPI spec to summarize instead of sending a request.
+    pub describe_api: Option<String>,
+    /// Only summarizes the operations whose path, operation id, summary, or tags contain this.
+    pub operation: Option<String>,
+    /// The hosts that requests may be sent to, with the headers added for them, from the
+    /// http_request settings of the agent. Without allowed hosts, no request is sent.
+    #[serde(skip)]
+    pub settings: Option<HttpRequestSettings>,
+}
+
+impl HttpRequest {
+    fn method(&self) -> String {
+        self.method.as_deref().unwrap_or("GET").trim().to_uppercase()
+    }
+
+    fn settings(&self) -> Result<&HttpRequestSettings> {
+        self.settings
+            .as_ref()
+            .ok_or_else(|| eyre!("No hosts are allowed for http_request in the current agent"))
+    }
+
+    fn url(&self) -> Result<Url> {
+        let Some(url) = &self.url else {
+            bail!("Either url or describe_api is required");
+        };
+        Url::parse(url).wrap_err_with(|| format!("Invalid URL '{url}'"))
+    }
+
+    /// Requests that may change something on the server need to be approved unless the agent
+    /// trusts the tool.
+    pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
+        if self.describe_api.is_some()
+            || agent.allowed_tools.contains("http_request")
+            || SAFE_METHODS.contains(&self.method().as_str())
+        {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        if let Some(name) = &self.describe_api {
+            queue!(
+                output,
+                style::Print("Summarizing the OpenAPI spec "),
+                style::SetForegroundColor(Color::Green),
+                style::Print(name),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            if let Some(operation) = &self.operation {
+                queue!(output, style::Print(format!(" for operations matching '{operation}'")))?;
+            }
+            queue!(output, style::Print("\n"))?;
+            return Ok(());
+        }
+
+        queue!(
+            output,
+            style::Print(format!("{} ", self.method())),
+            style::SetForegroundColor(Color::Green),
+            style::Print(self.url.as_deref().unwrap_or_default()),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n"),
+        )?;
+        if let (Ok(url), Some(settings)) = (self.url(), &self.settings) {
+            for name in settings.header_names_for(&url) {
+                queue!(output, style::Print(format!("{name}: (configured)\n")))?;
+            }
+        }
+        for (name, value) in self.headers.iter().flatten() {
+            queue!(output, style::Print(format!("{name}: {value}\n")))?;
+        }
+        if let Some(body) = &self.body {
+            let mut body = match body {
+                Value::String(body) => body.clone(),
+                body => serde_json::to_string_pretty(body)?,
+            };
+            truncate_safe_in_place(&mut body, MAX_BODY_PREVIEW_LEN, "\n... truncated");
+            queue!(
+                output,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("{body}\n")),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let settings = self.settings()?;
+        if let Some(name) = &self.describe_api {
+            let Some(location) = settings.open_api.get(name) else {
+                bail!("Unknown OpenAPI spec '{name}'");
+            };
+            let spec = openapi::load(os, location).await?;
+            let mut text = openapi::summarize(&spec, self.operation.as_deref());
+            truncate_safe_in_place(
+                &mut text,
+                MAX_TOOL_RESPONSE_SIZE / 3,
+                "\n... truncated, use operation to only summarize the matching operations",
+            );
+            return Ok(InvokeOutput {
+                output: OutputKind::Text(text),
+            });
+        }
+
+        let url = self.url()?;
+        // Redirects are only followed to allowed hosts.
+        let redirect_settings = settings.clone();
+        let client = client_builder()
+            .redirect(Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if redirect_settings.allows(attempt.url()) {
+                    attempt.follow()
+                } else {
+                    attempt.stop()
+                }
+            }))
+            .timeout(TIMEOUT)
+            .build()?;
+        let mut request = client.request(Method::from_bytes(self.method().as_bytes())?, url.clone());
+        for (name, value) in settings.headers_for(os, &url)? {
+            request = request.header(name, value);
+        }
+        for (name, value) in self.headers.iter().flatten() {
+            request = request.header(name, value);
+        }
+        request = match &self.body {
+            Some(Value::String(body)) => request.body(body.clone()),
+            Some(body) => request.json(body),
+            None => request,
+        };
+
+        let start = Instant::now();
+        let mut response = request.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let final_url = (response.url() != &url).then(|| response.url().to_string());
+        let max_bytes = settings.max_response_bytes.min(MAX_TOOL_RESPONSE_SIZE / 3);
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > max_bytes {
+                body.truncate(max_bytes);
+                truncated = true;
+                break;
+            }
+        }
+
+        Ok(InvokeOutput {
+            output: OutputKind::Text(format_response(
+                status,
+                start.elapsed(),
+                final_url.as_deref(),
+                &headers,
+                &body,
+                truncated,
+            )),
+        })
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        let settings = self.settings()?;
+        if let Some(name) = &self.describe_api {
+            if !settings.open_api.contains_key(name) {
+                let names = settings.open_api.keys().cloned().collect::<Vec<_>>();
+                bail!(
+                    "Unknown OpenAPI spec '{name}', expected one of: {}",
+                    if names.is_empty() {
+                        "(none configured)".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                );
+            }
+            return Ok(());
+        }
+        let url = self.url()?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Only http and https URLs are allowed");
+        }
+        if !settings.allows(&url) {
+            bail!(
+                "The host of '{url}' is not allowed, expected one of: {}",
+                settings.allowed_hosts.join(", ")
+            );
+        }
+        let method = self.method();
+        if !METHODS.contains(&method.as_str()) {
+            bail!("Unsupported method '{method}', expected one of: {}", METHODS.join(", "));
+        }
+        for name in self.headers.iter().flatten().map(|(name, _)| name) {
+            HeaderName::from_bytes(name.as_bytes())
+                .ok()
+                .ok_or_else(|| eyre!("Invalid header name '{name}'"))?;
+        }
+        Ok(())
+    }
+}
+
+fn format_response(
+    status: StatusCode,
+    elapsed: Duration,
+    final_url: Option<&str>,
+    headers: &HeaderMap,
+    body: &[u8],
+    truncated: bool,
+) -> String {
+    let mut text = format!("HTTP {status} ({} ms)\n", elapsed.as_millis());
+    if let Some(url) = final_url {
+        let _ = writeln!(text, "Redirected to: {url}");
+    }
+    for (name, value) in headers {
+        let value = if SENSITIVE_HEADERS.contains(&name.as_str()) {
+            "[masked]".to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).to_string()
+        };
+        let _ = writeln!(text, "{name}: {value}");
+    }
+    text.push('\n');
+
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    match std::str::from_utf8(body) {
+        // Truncation may split a character at the end.
+        Ok(_) | Err(_) if body.is_empty() => text.push_str("(empty body)"),
+        Ok(body) if !truncated && content_type.contains("json") => match serde_json::from_str::<Value>(body) {
+            Ok(json) => text.push_str(&serde_json::to_string_pretty(&json).unwrap_or_else(|_| body.to_string())),
+            Err(_) => text.push_str(body),
+        },
+        Ok(body) => text.push_str(body),
+        Err(err) if truncated && err.error_len().is_none() => {
+            text.push_str(&String::from_utf8_lossy(&body[..err.valid_up_to()]));
+        },
+        Err(_) => {
+            let _ = write!(
+                text,
+                "<{} bytes of {}>",
+                body.len(),
+                if content_type.is_empty() {
+                    "binary content"
+                } else {
+                    content_type
+                }
+            );
+        },
+    }
+    if truncated {
+        let _ = write!(text, "\n... truncated after {} bytes", body.len());
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_host_matches() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        assert!(host_matches("api.example.com", &url("https://API.example.com/v1")));
+        assert!(!host_matches(
+            "api.example.com",
+            &url("https://api.example.com.evil.io/")
+        ));
+        assert!(host_matches("*.example.com", &url("https://eu.api.example.com/")));
+        assert!(!host_matches("*.example.com", &url("https://example.com/")));
+        assert!(host_matches("localhost:8080", &url("http://localhost:8080/health")));
+        assert!(!host_matches("localhost:8080", &url("http://localhost:9090/health")));
+        assert!(host_matches("api.example.com:443", &url("https://api.example.com/")));
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let os = Os::new().await.unwrap();
+        let settings: HttpRequestSettings = serde_json::from_value(serde_json::json!({
+            "allowedHosts": ["api.example.com"],
+            "openApi": { "example": "example.yaml" }
+        }))
+        .unwrap();
+        let mut tool = HttpRequest {
+            method: Some("post".to_string()),
+            url: Some("https://api.example.com/items".to_string()),
+            headers: Some(BTreeMap::from([("X-Trace".to_string(), "1".to_string())])),
+            body: Some(serde_json::json!({ "name": "item" })),
+            describe_api: None,
+            operation: None,
+            settings: Some(settings),
+        };
+        assert!(tool.validate(&os).await.is_ok());
+        assert_eq!(tool.eval_perm(&Agent::default()), PermissionEvalResult::Ask);
+        tool.method = None;
+        assert_eq!(tool.eval_perm(&Agent::default()), PermissionEvalResult::Allow);
+
+        tool.url = Some("https://other.example.com/".to_string());
+        assert!(tool.validate(&os).await.is_err());
+        tool.url = Some("file:///etc/passwd".to_string());
+        assert!(tool.validate(&os).await.is_err());
+        tool.describe_api = Some("example".to_string());
+        assert!(tool.validate(&os).await.is_ok());
+        tool.describe_api = Some("missing".to_string());
+        assert!(tool.validate(&os).await.is_err());
+        tool.settings = None;
+        tool.describe_api = None;
+        assert!(tool.validate(&os).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expand_env() {
+        let os = Os::new().await.unwrap();
+        unsafe { os.env.set_var("HTTP_REQUEST_TEST_TOKEN", "abc") };
+        assert_eq!(
+            expand_env(&os, "Bearer ${HTTP_REQUEST_TEST_TOKEN}!").unwrap(),
+            "Bearer abc!"
+        );
+        assert_eq!(expand_env(&os, "no variables").unwrap(), "no variables");
+        assert!(expand_env(&os, "${HTTP_REQUEST_TEST_MISSING}").is_err());
+    }
+
+    #[test]
+    fn test_format_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert("set-cookie", HeaderValue::from_static("session=secret"));
+        let text = format_response(
+            StatusCode::OK,
+            Duration::from_millis(12),
+            None,
+            &headers,
+            br#"{"id":1}"#,
+            false,
+        );
+        assert_eq!(
+            text,
+            "HTTP 200 OK (12 ms)\ncontent-type: application/json\nset-cookie: [masked]\n\n{\n  \"id\": 1\n}"
+        );
+
+        let text = format_response(
+            StatusCode::NOT_FOUND,
+            Duration::ZERO,
+            Some("https://api.example.com/b"),
+            &HeaderMap::new(),
+            "héllo".as_bytes().get(..2).unwrap(),
+            true,
+        );
+        assert_eq!(
+            text,
+            "HTTP 404 Not Found (0 ms)\nRedirected to: https://api.example.com/b\n\nh\n... truncated after 2 bytes"
+        );
+    }
+}
@@ -0,0 +1,513 @@
+//! Summaries of OpenAPI specs, listing the operations of an API with the types of their
+//! parameters, request bodies, and responses, so that the model can call it without reading the
+//! whole spec.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use serde_json::{
+    Map,
+    Value,
+};
+use yaml_rust2::{
+    Yaml,
+    YamlLoader,
+};
+
+use super::super::sanitize_path_tool_arg;
+use crate::os::Os;
+use crate::request::new_client;
+
+const METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+/// Levels of nested objects whose properties are spelled out.
+const MAX_DEPTH: usize = 2;
+/// Named schemas described after the operations.
+const MAX_SCHEMAS: usize = 50;
+/// Characters of each description.
+const MAX_DESCRIPTION_LEN: usize = 120;
+
+/// Loads the spec at `location`, a path relative to the current directory or an http(s) URL, in
+/// JSON or YAML.
+pub async fn load(os: &Os, location: &str) -> Result<Value> {
+    let text = if location.starts_with("http://") || location.starts_with("https://") {
+        new_client()?
+            .get(location)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?
+    } else {
+        os.fs
+            .read_to_string(sanitize_path_tool_arg(os, location))
+            .await
+            .wrap_err_with(|| format!("Unable to read {location}"))?
+    };
+    parse(&text).wrap_err_with(|| format!("Unable to parse the OpenAPI spec {location}"))
+}
+
+fn parse(text: &str) -> Result<Value> {
+    if let Ok(value) = serde_json::from_str::<Value>(text) {
+        return Ok(value);
+    }
+    let Some(document) = YamlLoader::load_from_str(text)?.into_iter().next() else {
+        bail!("The spec is empty");
+    };
+    Ok(yaml_to_json(document))
+}
+
+fn yaml_to_json(yaml: Yaml) -> Value {
+    match yaml {
+        Yaml::Real(real) => real.parse::<f64>().map_or(Value::String(real), Value::from),
+        Yaml::Integer(integer) => Value::from(integer),
+        Yaml::String(string) => Value::String(string),
+        Yaml::Boolean(boolean) => Value::Bool(boolean),
+        Yaml::Array(items) => Value::Array(items.into_iter().map(yaml_to_json).collect()),
+        Yaml::Hash(hash) => Value::Object(
+            hash.into_iter()
+                .map(|(key, value)| {
+                    // Response codes are integer keys.
+                    let key = match key {
+                        Yaml::String(key) | Yaml::Real(key) => key,
+                        Yaml::Integer(key) => key.to_string(),
+                        Yaml::Boolean(key) => key.to_string(),
+                        _ => String::new(),
+                    };
+                    (key, yaml_to_json(value))
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => Value::Null,
+    }
+}
+
+/// Returns the base URL of the API, from the servers of OpenAPI 3 or the host of Swagger 2.
+pub fn base_url(spec: &Value) -> Option<String> {
+    if let Some(url) = spec["servers"][0]["url"].as_str() {
+        return Some(url.to_string());
+    }
+    let host = spec["host"].as_str()?;
+    let scheme = spec["schemes"][0].as_str().unwrap_or("https");
+    Some(format!(
+        "{scheme}://{host}{}",
+        spec["basePath"].as_str().unwrap_or_default()
+    ))
+}
+
+/// Summarizes the operations of `spec` whose path, operation id, summary, or tags contain `filter`,
+/// followed by the named schemas they use.
+pub fn summarize(spec: &Value, filter: Option<&str>) -> String {
+    let mut text = String::new();
+    let info = &spec["info"];
+    let _ = writeln!(
+        text,
+        "{} {}",
+        info["title"].as_str().unwrap_or("API"),
+        info["version"].as_str().unwrap_or_default()
+    );
+    if let Some(url) = base_url(spec) {
+        let _ = writeln!(text, "Base URL: {url}");
+    }
+    text.push('\n');
+
+    let filter = filter.map(str::to_lowercase);
+    let mut refs = BTreeSet::new();
+    let mut operations = 0;
+    for (path, item) in spec["paths"].as_object().into_iter().flatten() {
+        let item = resolve(spec, item);
+        for method in METHODS {
+            let Some(operation) = item.get(method).filter(|operation| operation.is_object()) else {
+                continue;
+            };
+            if let Some(filter) = &filter {
+                let tags = operation["tags"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str);
+                let mut haystack = [
+                    path.as_str(),
+                    operation["operationId"].as_str().unwrap_or_default(),
+                    operation["summary"].as_str().unwrap_or_default(),
+                ]
+                .into_iter()
+                .chain(tags);
+                if !haystack.any(|text| text.to_lowercase().contains(filter.as_str())) {
+                    continue;
+                }
+            }
+            operations += 1;
+            describe_operation(&mut text, spec, path, method, item, operation, &mut refs);
+        }
+    }
+    if operations == 0 {
+        text.push_str("No operations found.\n");
+        return text;
+    }
+
+    let mut described = BTreeSet::new();
+    let mut schemas = String::new();
+    while let Some(reference) = refs.iter().find(|reference| !described.contains(*reference)).cloned() {
+        described.insert(reference.clone());
+        if described.len() > MAX_SCHEMAS {
+            schemas.push_str("  ... more schemas omitted\n");
+            break;
+        }
+        let Some(schema) = reference.strip_prefix('#').and_then(|pointer| spec.pointer(pointer)) else {
+            continue;
+        };
+        let _ = writeln!(
+            schemas,
+            "  {}: {}",
+            schema_name(&reference),
+            type_of(spec, schema, &mut refs, 0)
+        );
+    }
+    if !schemas.is_empty() {
+        let _ = write!(text, "\nSchemas:\n{schemas}");
+    }
+    text
+}
+
+fn describe_operation(
+    text: &mut String,
+    spec: &Value,
+    path: &str,
+    method: &str,
+    item: &Value,
+    operation: &Value,
+    refs: &mut BTreeSet<String>,
+) {
+    let _ = write!(text, "{} {path}", method.to_uppercase());
+    if let Some(id) = operation["operationId"].as_str() {
+        let _ = write!(text, " ({id})");
+    }
+    if let Some(summary) = operation["summary"].as_str().or(operation["description"].as_str()) {
+        let _ = write!(text, ": {}", short(summary));
+    }
+    if operation["deprecated"].as_bool() == Some(true) {
+        text.push_str(" [deprecated]");
+    }
+    text.push('\n');
+
+    let parameters = item["parameters"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .chain(operation["parameters"].as_array().into_iter().flatten())
+        .map(|parameter| resolve(spec, parameter));
+    for parameter in parameters {
+        let location = parameter["in"].as_str().unwrap_or_default();
+        // Swagger 2 describes the body as a parameter.
+        if location == "body" {
+            let _ = writeln!(text, "  body: {}", type_of(spec, &parameter["schema"], refs, 0));
+            continue;
+        }
+        let schema = if parameter.get("schema").is_some() {
+            &parameter["schema"]
+        } else {
+            parameter
+        };
+        let _ = write!(
+            text,
+            "  {location} {}{}: {}",
+            parameter["name"].as_str().unwrap_or_default(),
+            if parameter["required"].as_bool() == Some(true) {
+                ""
+            } else {
+                "?"
+            },
+            type_of(spec, schema, refs, 0)
+        );
+        if let Some(description) = parameter["description"].as_str() {
+            let _ = write!(text, " — {}", short(description));
+        }
+        text.push('\n');
+    }
+
+    let body = resolve(spec, &operation["requestBody"]);
+    if let Some((media_type, content)) = body["content"].as_object().and_then(|content| content.iter().next()) {
+        let _ = writeln!(
+            text,
+            "  body{} ({media_type}): {}",
+            if body["required"].as_bool() == Some(true) {
+                ""
+            } else {
+                "?"
+            },
+            type_of(spec, &content["schema"], refs, 0)
+        );
+    }
+
+    let responses = operation["responses"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(code, response)| {
+            let response = resolve(spec, response);
+            let schema = response["content"]
+                .as_object()
+                .and_then(|content| content.values().next())
+                .map(|content| &content["schema"])
+                .or(response.get("schema"));
+            match schema {
+                Some(schema) if !schema.is_null() => format!("{code} {}", type_of(spec, schema, refs, 0)),
+                _ => code.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+    if !responses.is_empty() {
+        let _ = writeln!(text, "  responses: {}", responses.join(", "));
+    }
+}
+
+/// Returns the value `value` refers to, if it is a local `$ref`.
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    value["$ref"]
+        .as_str()
+        .and_then(|reference| reference.strip_prefix('#'))
+        .and_then(|pointer| spec.pointer(pointer))
+        .unwrap_or(value)
+}
+
+fn schema_name(reference: &str) -> &str {
+    reference.rsplit('/').next().unwrap_or(reference)
+}
+
+fn short(description: &str) -> String {
+    let line = description.lines().next().unwrap_or_default().trim();
+    if line.chars().count() > MAX_DESCRIPTION_LEN {
+        format!("{}...", line.chars().take(MAX_DESCRIPTION_LEN).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Returns a compact type for `schema`, such as `{id: integer, tag?: string}` or `array of Pet`,
+/// adding the named schemas it refers to to `refs`.
+fn type_of(spec: &Value, schema: &Value, refs: &mut BTreeSet<String>, depth: usize) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        refs.insert(reference.to_string());
+        return schema_name(reference).to_string();
+    }
+    for (key, separator) in [("oneOf", " | "), ("anyOf", " | "), ("allOf", " & ")] {
+        if let Some(schemas) = schema[key].as_array() {
+            return schemas
+                .iter()
+                .map(|schema| type_of(spec, schema, refs, depth))
+                .collect::<Vec<_>>()
+                .join(separator);
+        }
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        return values.iter().map(Value::to_string).collect::<Vec<_>>().join(" | ");
+    }
+    let kind = match &schema["type"] {
+        Value::String(kind) => kind.as_str(),
+        // OpenAPI 3.1 types such as ["string", "null"].
+        Value::Array(kinds) => {
+            return kinds.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" | ");
+        },
+        _ if schema.get("properties").is_some() => "object",
+        _ => "any",
+    };
+    let mut description = match kind {
+        "array" => format!("array of {}", type_of(spec, &schema["items"], refs, depth)),
+        "object" => object_type(spec, schema, refs, depth),
+        kind => match schema["format"].as_str() {
+            Some(format) => format!("{kind} ({format})"),
+            None => kind.to_string(),
+        },
+    };
+    if schema["nullable"].as_bool() == Some(true) {
+        description.push_str(" | null");
+    }
+    description
+}
+
+fn object_type(spec: &Value, schema: &Value, refs: &mut BTreeSet<String>, depth: usize) -> String {
+    if let Some(properties) = schema["properties"].as_object() {
+        if depth >= MAX_DEPTH {
+            return "object".to_string();
+        }
+        let required = schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>();
+        let fields = properties
+            .iter()
+            .map(|(name, property)| {
+                format!(
+                    "{name}{}: {}",
+                    if required.contains(&name.as_str()) { "" } else { "?" },
+                    type_of(spec, property, refs, depth + 1)
+                )
+            })
+            .collect::<Vec<_>>();
+        return format!("{{{}}}", fields.join(", "));
+    }
+    match &schema["additionalProperties"] {
+        Value::Object(values) => format!(
+            "map of {}",
+            type_of(spec, &Value::Object(values.clone()), refs, depth + 1)
+        ),
+        _ => "object".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PETSTORE: &str = r##"
+openapi: 3.0.0
+info:
+  title: Petstore
+  version: 1.0.0
+servers:
+  - url: https://petstore.example.com/v1
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      summary: List all pets
+      tags: [pets]
+      parameters:
+        - name: limit
+          in: query
+          description: How many items to return at one time
+          schema:
+            type: integer
+            format: int32
+      responses:
+        200:
+          description: A list of pets
+          content:
+            application/json:
+              schema:
+                type: array
+                items:
+                  $ref: "#/components/schemas/Pet"
+        default:
+          $ref: "#/components/responses/Error"
+    post:
+      operationId: createPet
+      summary: Create a pet
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              $ref: "#/components/schemas/Pet"
+      responses:
+        "201":
+          description: Created
+  /pets/{petId}:
+    parameters:
+      - name: petId
+        in: path
+        required: true
+        schema:
+          type: string
+    delete:
+      operationId: deletePet
+      responses:
+        "204":
+          description: Deleted
+components:
+  responses:
+    Error:
+      description: Error
+      content:
+        application/json:
+          schema:
+            $ref: "#/components/schemas/Error"
+  schemas:
+    Pet:
+      type: object
+      required: [id, name]
+      properties:
+        id:
+          type: integer
+          format: int64
+        name:
+          type: string
+        status:
+          type: string
+          enum: [available, sold]
+        owner:
+          $ref: "#/components/schemas/Owner"
+    Owner:
+      type: object
+      properties:
+        email:
+          type: string
+    Error:
+      type: object
+      properties:
+        message:
+          type: string
+"##;
+
+    #[test]
+    fn test_summarize() {
+        let spec = parse(PETSTORE).unwrap();
+        assert_eq!(base_url(&spec).as_deref(), Some("https://petstore.example.com/v1"));
+        assert_eq!(
+            summarize(&spec, None),
+            "Petstore 1.0.0
+Base URL: https://petstore.example.com/v1
+
+GET /pets (listPets): List all pets
+  query limit?: integer (int32) — How many items to return at one time
+  responses: 200 array of Pet, default Error
+POST /pets (createPet): Create a pet
+  body (application/json): Pet
+  responses: 201
+DELETE /pets/{petId} (deletePet)
+  path petId: string
+  responses: 204
+
+Schemas:
+  Error: {message?: string}
+  Pet: {id: integer (int64), name: string, owner?: Owner, status?: \"available\" | \"sold\"}
+  Owner: {email?: string}
+"
+        );
+
+        let filtered = summarize(&spec, Some("DELETE"));
+        assert!(filtered.contains("DELETE /pets/{petId}"));
+        assert!(!filtered.contains("listPets"));
+        assert!(summarize(&spec, Some("orders")).ends_with("No operations found.\n"));
+    }
+
+    #[test]
+    fn test_swagger() {
+        let spec = serde_json::json!({
+            "swagger": "2.0",
+            "info": { "title": "Legacy", "version": "2" },
+            "host": "legacy.example.com",
+            "basePath": "/api",
+            "paths": {
+                "/items": {
+                    "post": {
+                        "parameters": [{ "in": "body", "name": "item", "schema": { "$ref": "#/definitions/Item" } }],
+                        "responses": { "200": { "schema": { "type": "object", "additionalProperties": { "type": "number" } } } }
+                    }
+                }
+            },
+            "definitions": { "Item": { "properties": { "sku": { "type": "string" } } } }
+        });
+        assert_eq!(base_url(&spec).as_deref(), Some("https://legacy.example.com/api"));
+        let summary = summarize(&spec, None);
+        assert!(summary.contains("POST /items\n  body: Item\n  responses: 200 map of number\n"));
+        assert!(summary.contains("  Item: {sku?: string}\n"));
+    }
+}
@@ -0,0 +1,691 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use bstr::ByteSlice;
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::error;
+
+use super::use_aws::aws_command;
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+    sanitize_path_tool_arg,
+};
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+
+/// Attribute diffs shown for each resource.
+const MAX_ATTRIBUTE_DIFFS: usize = 10;
+/// Characters of each attribute value shown in a diff.
+const MAX_VALUE_LEN: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InfraKind {
+    Terraform,
+    CloudFormation,
+}
+
+/// The infra_plan tool previews infrastructure changes without applying them, either by running
+/// `terraform plan` or by describing a CloudFormation change set, and summarizes them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InfraPlan {
+    pub kind: InfraKind,
+    /// Terraform configuration directory.
+    pub directory: Option<String>,
+    /// Terraform variable files.
+    #[serde(default)]
+    pub var_files: Vec<String>,
+    pub stack_name: Option<String>,
+    pub change_set_name: Option<String>,
+    pub region: Option<String>,
+    pub profile_name: Option<String>,
+}
+
+/// A planned change of a resource.
+#[derive(Debug, Clone, PartialEq)]
+struct ResourceChange {
+    address: String,
+    resource_type: String,
+    action: Action,
+    /// Changed attributes, with their values before and after the change when known.
+    attributes: Vec<AttributeDiff>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Action {
+    Create,
+    Update,
+    Replace,
+    Delete,
+}
+
+impl Action {
+    fn symbol(self) -> &'static str {
+        match self {
+            Action::Create => "+",
+            Action::Update => "~",
+            Action::Replace => "-/+",
+            Action::Delete => "-",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AttributeDiff {
+    name: String,
+    before: Option<String>,
+    after: Option<String>,
+    /// Whether changing the attribute replaces the resource.
+    forces_replacement: bool,
+}
+
+impl InfraPlan {
+    fn directory(&self, os: &Os) -> PathBuf {
+        sanitize_path_tool_arg(os, self.directory.as_deref().unwrap_or("."))
+    }
+
+    /// `terraform plan` runs the providers and external data sources of the configuration, so
+    /// planning needs to be approved unless the agent trusts the tool.
+    pub fn eval_perm(agent: &Agent) -> PermissionEvalResult {
+        if agent.allowed_tools.contains("infra_plan") {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    pub fn queue_description(&self, os: &Os, output: &mut impl Write) -> Result<()> {
+        let target = match self.kind {
+            InfraKind::Terraform => format!("terraform plan in {}", self.directory(os).display()),
+            InfraKind::CloudFormation => format!(
+                "change set {} of stack {}",
+                self.change_set_name.as_deref().unwrap_or_default(),
+                self.stack_name.as_deref().unwrap_or_default()
+            ),
+        };
+        queue!(
+            output,
+            style::Print("Previewing "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(target),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(" (no changes are applied)\n"),
+        )?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let changes = match self.kind {
+            InfraKind::Terraform => self.terraform_plan(os).await?,
+            InfraKind::CloudFormation => self.describe_change_set().await?,
+        };
+        Ok(InvokeOutput {
+            output: OutputKind::Text(format_changes(&changes)),
+        })
+    }
+
+    async fn terraform_plan(&self, os: &Os) -> Result<Vec<ResourceChange>> {
+        let directory = self.directory(os);
+        let plan_file = tempfile::Builder::new().prefix("qchat-plan").tempfile()?;
+        let mut command = tokio::process::Command::new("terraform");
+        command
+            .current_dir(&directory)
+            .args(["plan", "-input=false", "-lock=false", "-no-color"])
+            .arg(format!("-out={}", plan_file.path().display()));
+        for var_file in &self.var_files {
+            command.arg(format!("-var-file={var_file}"));
+        }
+        run(command).await?;
+
+        let mut command = tokio::process::Command::new("terraform");
+        command
+            .current_dir(&directory)
+            .args(["show", "-json"])
+            .arg(plan_file.path());
+        parse_terraform_plan(&run(command).await?)
+    }
+
+    async fn describe_change_set(&self) -> Result<Vec<ResourceChange>> {
+        let mut command = aws_command();
+        if let Some(region) = &self.region {
+            command.arg("--region").arg(region);
+        }
+        if let Some(profile_name) = &self.profile_name {
+            command.arg("--profile").arg(profile_name);
+        }
+        command
+            .args([
+                "cloudformation",
+                "describe-change-set",
+                "--output",
+                "json",
+                "--no-cli-pager",
+            ])
+            .arg("--stack-name")
+            .arg(self.stack_name.as_deref().unwrap_or_default())
+            .arg("--change-set-name")
+            .arg(self.change_set_name.as_deref().unwrap_or_default());
+        parse_change_set(&run(command).await?)
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        match self.kind {
+            InfraKind::Terraform => {
+                let directory = self.directory(os);
+                if !os.fs.exists(&directory) {
+                    bail!("The directory {} does not exist", directory.display());
+                }
+            },
+            InfraKind::CloudFormation => {
+                if self.stack_name.is_none() || self.change_set_name.is_none() {
+                    bail!("stack_name and change_set_name are required for CloudFormation");
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/// The `infra_plan` tool settings of an agent.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InfraSettings {
+    /// Whether commands applying infrastructure changes follow the usual permissions of their
+    /// tool, rather than always being approved one use at a time.
+    #[serde(default)]
+    allow_apply: bool,
+}
+
+/// Whether `agent` lets commands applying infrastructure changes follow the usual permissions.
+pub fn apply_allowed(agent: &Agent) -> bool {
+    match agent.tools_settings.get("infra_plan") {
+        Some(settings) => match serde_json::from_value::<InfraSettings>(settings.clone()) {
+            Ok(settings) => settings.allow_apply,
+            Err(e) => {
+                error!("Failed to deserialize tool settings for infra_plan: {:?}", e);
+                false
+            },
+        },
+        None => false,
+    }
+}
+
+/// Whether a CloudFormation API operation applies infrastructure changes.
+pub fn is_cloudformation_apply(operation: &str) -> bool {
+    matches!(
+        operation.replace('_', "-").as_str(),
+        "execute-change-set" | "deploy" | "create-stack" | "update-stack" | "delete-stack"
+    )
+}
+
+/// Whether a shell command applies infrastructure changes with Terraform, CloudFormation, the CDK,
+/// or SAM.
+pub fn applies_infrastructure(command: &str) -> bool {
+    let Some(args) = shlex::split(command) else {
+        // Commands that cannot be parsed are matched as plain text.
+        return [
+            "terraform apply",
+            "terraform destroy",
+            "execute-change-set",
+            "cdk deploy",
+        ]
+        .iter()
+        .any(|pattern| command.contains(pattern));
+    };
+    // A `;` ending a word separates commands too.
+    let args = args
+        .iter()
+        .flat_map(|arg| match arg.strip_suffix(';') {
+            Some(word) if !word.is_empty() => vec![word, ";"],
+            _ => vec![arg.as_str()],
+        })
+        .collect::<Vec<_>>();
+    args.split(|arg| matches!(*arg, "|" | "||" | "&&" | ";")).any(|args| {
+        let mut words = args
+            .iter()
+            .copied()
+            .filter(|arg| !arg.starts_with('-'))
+            .collect::<Vec<_>>();
+        while words
+            .first()
+            .is_some_and(|word| matches!(*word, "sudo" | "npx" | "env" | "time"))
+        {
+            words.remove(0);
+        }
+        let program = words
+            .first()
+            .map(|program| program.rsplit('/').next().unwrap_or(program));
+        match (program, &words[words.len().min(1)..]) {
+            (Some("terraform" | "tofu" | "terragrunt"), [subcommand, ..]) => {
+                matches!(*subcommand, "apply" | "destroy" | "run-all")
+            },
+            (Some("cdk" | "sam"), [subcommand, ..]) => matches!(*subcommand, "deploy" | "destroy" | "delete"),
+            (Some("aws"), rest) => {
+                // Flags with values such as `--region us-east-1` leave their value among the words.
+                rest.windows(2)
+                    .any(|pair| pair[0] == "cloudformation" && is_cloudformation_apply(pair[1]))
+            },
+            _ => false,
+        }
+    })
+}
+
+/// Runs a command, returning its stdout.
+async fn run(mut command: tokio::process::Command) -> Result<String> {
+    let output = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| format!("Unable to run {:?}", command.as_std().get_program()))?
+        .wait_with_output()
+        .await?;
+    if !output.status.success() {
+        let mut stderr = output.stderr.to_str_lossy().trim().to_string();
+        truncate_safe_in_place(&mut stderr, MAX_TOOL_RESPONSE_SIZE / 3, " ... truncated");
+        bail!("{stderr}");
+    }
+    Ok(output.stdout.to_str_lossy().to_string())
+}
+
+/// Parses the output of `terraform show -json` for a plan file.
+fn parse_terraform_plan(output: &str) -> Result<Vec<ResourceChange>> {
+    #[derive(Deserialize)]
+    struct Plan {
+        #[serde(default)]
+        resource_changes: Vec<Change>,
+    }
+    #[derive(Deserialize)]
+    struct Change {
+        address: String,
+        #[serde(rename = "type")]
+        resource_type: String,
+        change: ChangeDetails,
+    }
+    #[derive(Deserialize)]
+    struct ChangeDetails {
+        actions: Vec<String>,
+        #[serde(default)]
+        before: Value,
+        #[serde(default)]
+        after: Value,
+        #[serde(default)]
+        after_unknown: Value,
+        #[serde(default)]
+        before_sensitive: Value,
+        #[serde(default)]
+        after_sensitive: Value,
+        #[serde(default)]
+        replace_paths: Vec<Vec<Value>>,
+    }
+
+    let plan = serde_json::from_str::<Plan>(output)?;
+    let mut changes = Vec::new();
+    for change in plan.resource_changes {
+        let details = change.change;
+        let actions = details.actions.iter().map(String::as_str).collect::<Vec<_>>();
+        let action = match actions.as_slice() {
+            ["create"] => Action::Create,
+            ["update"] => Action::Update,
+            ["delete"] => Action::Delete,
+            ["delete", "create"] | ["create", "delete"] => Action::Replace,
+            // No-op and read actions change nothing.
+            _ => continue,
+        };
+        let replaced = details
+            .replace_paths
+            .iter()
+            .filter_map(|path| path.first()?.as_str())
+            .collect::<Vec<_>>();
+
+        let empty = serde_json::Map::new();
+        let before = details.before.as_object().unwrap_or(&empty);
+        // The attributes of destroyed resources are not worth listing.
+        let after = match action {
+            Action::Delete => &empty,
+            _ => details.after.as_object().unwrap_or(&empty),
+        };
+        let after_unknown = details.after_unknown.as_object().unwrap_or(&empty);
+        let mut names = match action {
+            Action::Delete => Vec::new(),
+            _ => before.keys().chain(after.keys()).chain(after_unknown.keys()).collect(),
+        };
+        names.sort();
+        names.dedup();
+        let attributes = names
+            .into_iter()
+            .filter_map(|name| {
+                let unknown = after_unknown.get(name).is_some_and(|v| v != &Value::Bool(false));
+                let known = |value: Option<&Value>| value.filter(|v| !v.is_null()).cloned();
+                if known(before.get(name)) == known(after.get(name)) && !unknown {
+                    return None;
+                }
+                let show = |value: Option<&Value>, sensitive: &Value| {
+                    if sensitive.get(name).is_some_and(|v| v != &Value::Bool(false)) {
+                        return Some("(sensitive)".to_string());
+                    }
+                    value.filter(|v| !v.is_null()).map(format_value)
+                };
+                Some(AttributeDiff {
+                    name: name.clone(),
+                    before: show(before.get(name), &details.before_sensitive),
+                    after: if unknown {
+                        Some("(known after apply)".to_string())
+                    } else {
+                        show(after.get(name), &details.after_sensitive)
+                    },
+                    forces_replacement: replaced.contains(&name.as_str()),
+                })
+            })
+            .collect();
+        changes.push(ResourceChange {
+            address: change.address,
+            resource_type: change.resource_type,
+            action,
+            attributes,
+        });
+    }
+    Ok(changes)
+}
+
+/// Parses the output of `aws cloudformation describe-change-set`.
+fn parse_change_set(output: &str) -> Result<Vec<ResourceChange>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct ChangeSet {
+        #[serde(default)]
+        changes: Vec<Change>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Change {
+        resource_change: Option<Resource>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Resource {
+        action: String,
+        logical_resource_id: String,
+        #[serde(default)]
+        resource_type: String,
+        replacement: Option<String>,
+        #[serde(default)]
+        details: Vec<Detail>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Detail {
+        target: Target,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Target {
+        attribute: String,
+        name: Option<String>,
+        requires_recreation: Option<String>,
+    }
+
+    let change_set = serde_json::from_str::<ChangeSet>(output)?;
+    Ok(change_set
+        .changes
+        .into_iter()
+        .filter_map(|change| change.resource_change)
+        .filter_map(|resource| {
+            let action = match resource.action.as_str() {
+                "Add" | "Import" => Action::Create,
+                "Remove" => Action::Delete,
+                "Modify" | "Dynamic" if resource.replacement.as_deref() == Some("True") => Action::Replace,
+                "Modify" | "Dynamic" => Action::Update,
+                _ => return None,
+            };
+            let mut attributes = Vec::<AttributeDiff>::new();
+            for detail in resource.details {
+                let name = detail.target.name.unwrap_or(detail.target.attribute);
+                let forces_replacement = detail.target.requires_recreation.as_deref() == Some("Always");
+                // Several sources can change the same property.
+                match attributes.iter_mut().find(|attribute| attribute.name == name) {
+                    Some(attribute) => attribute.forces_replacement |= forces_replacement,
+                    None => attributes.push(AttributeDiff {
+                        name,
+                        before: None,
+                        after: None,
+                        forces_replacement,
+                    }),
+                }
+            }
+            Some(ResourceChange {
+                address: resource.logical_resource_id,
+                resource_type: resource.resource_type,
+                action,
+                attributes,
+            })
+        })
+        .collect())
+}
+
+fn format_value(value: &Value) -> String {
+    let mut text = match value {
+        Value::String(s) => format!("{s:?}"),
+        value => value.to_string(),
+    };
+    truncate_safe_in_place(&mut text, MAX_VALUE_LEN, "...");
+    text
+}
+
+fn format_changes(changes: &[ResourceChange]) -> String {
+    if changes.is_empty() {
+        return "No changes. The infrastructure matches the configuration.".to_string();
+    }
+    let count = |action| changes.iter().filter(|change| change.action == action).count();
+    let mut text = format!(
+        "{} to add, {} to change, {} to replace, {} to destroy\n\n",
+        count(Action::Create),
+        count(Action::Update),
+        count(Action::Replace),
+        count(Action::Delete)
+    );
+    let mut sorted = changes.iter().collect::<Vec<_>>();
+    // Destructive changes matter most, so they are listed first.
+    sorted.sort_by(|a, b| b.action.cmp(&a.action).then_with(|| a.address.cmp(&b.address)));
+    for change in sorted {
+        let _ = writeln!(
+            text,
+            "{} {} ({})",
+            change.action.symbol(),
+            change.address,
+            change.resource_type
+        );
+        for attribute in change.attributes.iter().take(MAX_ATTRIBUTE_DIFFS) {
+            let _ = write!(text, "    {}", attribute.name);
+            match (&attribute.before, &attribute.after) {
+                (None, None) => {},
+                (before, after) => {
+                    let _ = write!(
+                        text,
+                        ": {} -> {}",
+                        before.as_deref().unwrap_or("null"),
+                        after.as_deref().unwrap_or("null")
+                    );
+                },
+            }
+            if attribute.forces_replacement {
+                text.push_str(" (forces replacement)");
+            }
+            text.push('\n');
+        }
+        if change.attributes.len() > MAX_ATTRIBUTE_DIFFS {
+            let _ = writeln!(
+                text,
+                "    ... {} more attributes",
+                change.attributes.len() - MAX_ATTRIBUTE_DIFFS
+            );
+        }
+    }
+    truncate_safe_in_place(&mut text, MAX_TOOL_RESPONSE_SIZE / 3, " ... truncated");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_perm() {
+        let agent = Agent::default();
+        assert_eq!(InfraPlan::eval_perm(&agent), PermissionEvalResult::Ask);
+        let agent = Agent {
+            allowed_tools: ["infra_plan".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        assert_eq!(InfraPlan::eval_perm(&agent), PermissionEvalResult::Allow);
+    }
+
+    #[test]
+    fn test_applies_infrastructure() {
+        for command in [
+            "terraform apply -auto-approve",
+            "cd infra && terraform -chdir=prod destroy",
+            "aws --region us-east-1 cloudformation execute-change-set --change-set-name cs",
+            "aws cloudformation deploy --template-file t.yml --stack-name s",
+            "npx cdk deploy --all",
+            "sam deploy --guided",
+            "terraform plan; terraform apply plan.out",
+        ] {
+            assert!(applies_infrastructure(command), "{command}");
+        }
+        for command in [
+            "terraform plan -out=plan.out",
+            "terraform show -json plan.out",
+            "aws cloudformation describe-change-set --change-set-name cs",
+            "echo terraform apply",
+            "cdk diff",
+        ] {
+            assert!(!applies_infrastructure(command), "{command}");
+        }
+    }
+
+    #[test]
+    fn test_parse_terraform_plan() {
+        let plan = serde_json::json!({
+            "resource_changes": [
+                {
+                    "address": "aws_s3_bucket.logs",
+                    "type": "aws_s3_bucket",
+                    "change": {
+                        "actions": ["create"],
+                        "before": null,
+                        "after": { "bucket": "logs", "tags": null },
+                        "after_unknown": { "arn": true, "id": true }
+                    }
+                },
+                {
+                    "address": "aws_db_instance.main",
+                    "type": "aws_db_instance",
+                    "change": {
+                        "actions": ["delete", "create"],
+                        "before": { "engine_version": "14.1", "password": "old", "port": 5432 },
+                        "after": { "engine_version": "15.2", "password": "new", "port": 5432 },
+                        "after_unknown": {},
+                        "before_sensitive": { "password": true },
+                        "after_sensitive": { "password": true },
+                        "replace_paths": [["engine_version"]]
+                    }
+                },
+                {
+                    "address": "data.aws_caller_identity.current",
+                    "type": "aws_caller_identity",
+                    "change": { "actions": ["read"] }
+                },
+                {
+                    "address": "aws_iam_role.unchanged",
+                    "type": "aws_iam_role",
+                    "change": { "actions": ["no-op"], "before": {}, "after": {} }
+                }
+            ]
+        });
+        let changes = parse_terraform_plan(&plan.to_string()).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].action, Action::Create);
+        assert_eq!(
+            changes[0]
+                .attributes
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["arn", "bucket", "id"]
+        );
+        assert_eq!(changes[0].attributes[0].after.as_deref(), Some("(known after apply)"));
+
+        assert_eq!(changes[1].action, Action::Replace);
+        assert_eq!(changes[1].attributes, vec![
+            AttributeDiff {
+                name: "engine_version".to_string(),
+                before: Some("\"14.1\"".to_string()),
+                after: Some("\"15.2\"".to_string()),
+                forces_replacement: true,
+            },
+            AttributeDiff {
+                name: "password".to_string(),
+                before: Some("(sensitive)".to_string()),
+                after: Some("(sensitive)".to_string()),
+                forces_replacement: false,
+            },
+        ]);
+
+        let text = format_changes(&changes);
+        assert!(text.starts_with("1 to add, 0 to change, 1 to replace, 0 to destroy\n\n-/+ aws_db_instance.main"));
+        assert!(text.contains("    engine_version: \"14.1\" -> \"15.2\" (forces replacement)\n"));
+        assert!(text.contains("+ aws_s3_bucket.logs (aws_s3_bucket)\n"));
+    }
+
+    #[test]
+    fn test_parse_change_set() {
+        let change_set = serde_json::json!({
+            "Changes": [
+                { "Type": "Resource", "ResourceChange": {
+                    "Action": "Modify", "LogicalResourceId": "Queue", "ResourceType": "AWS::SQS::Queue",
+                    "Replacement": "True",
+                    "Details": [
+                        { "Target": { "Attribute": "Properties", "Name": "FifoQueue", "RequiresRecreation": "Always" } },
+                        { "Target": { "Attribute": "Properties", "Name": "FifoQueue", "RequiresRecreation": "Never" } }
+                    ]
+                }},
+                { "Type": "Resource", "ResourceChange": {
+                    "Action": "Remove", "LogicalResourceId": "OldTopic", "ResourceType": "AWS::SNS::Topic"
+                }}
+            ]
+        });
+        let changes = parse_change_set(&change_set.to_string()).unwrap();
+        assert_eq!(changes[0].action, Action::Replace);
+        assert_eq!(changes[0].attributes.len(), 1);
+        assert!(changes[0].attributes[0].forces_replacement);
+        assert_eq!(changes[1].action, Action::Delete);
+
+        let text = format_changes(&changes);
+        assert!(text.contains(
+            "- OldTopic (AWS::SNS::Topic)\n-/+ Queue (AWS::SQS::Queue)\n    FifoQueue (forces replacement)\n"
+        ));
+        assert_eq!(
+            format_changes(&[]),
+            "No changes. The infrastructure matches the configuration."
+        );
+    }
+}
@@ -0,0 +1,498 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{
+    Duration,
+    SystemTime,
+};
+
+use bstr::ByteSlice;
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::Deserialize;
+use tracing::error;
+
+use super::use_aws::aws_command;
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+use crate::cli::agent::Agent;
+use crate::cli::chat::auto_context::{
+    WorkspaceIndex,
+    tokenize,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+use crate::util::directories;
+
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 20;
+/// Characters of each passage returned to the model.
+const MAX_PASSAGE_LEN: usize = 2000;
+/// How long the synced documents of an S3 knowledge base are used before being synced again.
+const S3_SYNC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// File touched after each sync of an S3 knowledge base.
+const S3_SYNC_MARKER: &str = ".synced";
+
+/// A knowledge base configured in the `kb_search` tool settings of an agent, e.g.
+///
+/// ```json
+/// "toolsSettings": {
+///   "kb_search": {
+///     "knowledgeBases": [
+///       { "name": "runbooks", "type": "kendra", "indexId": "...", "region": "us-east-1" },
+///       { "name": "handbook", "type": "qbusiness", "applicationId": "...", "retrieverId": "..." },
+///       { "name": "design-docs", "type": "s3", "uri": "s3://bucket/docs" }
+///     ]
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnowledgeBase {
+    pub name: String,
+    #[serde(flatten)]
+    pub source: KnowledgeBaseSource,
+    pub region: Option<String>,
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum KnowledgeBaseSource {
+    #[serde(rename_all = "camelCase")]
+    Kendra {
+        index_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    QBusiness {
+        application_id: String,
+        retriever_id: String,
+    },
+    S3 {
+        uri: String,
+    },
+}
+
+impl KnowledgeBase {
+    /// Returns the knowledge bases configured for `agent`.
+    pub fn configured(agent: &Agent) -> Vec<Self> {
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Settings {
+            knowledge_bases: Vec<KnowledgeBase>,
+        }
+
+        let Some(settings) = agent.tools_settings.get("kb_search") else {
+            return Vec::new();
+        };
+        match serde_json::from_value::<Settings>(settings.clone()) {
+            Ok(settings) => settings.knowledge_bases,
+            Err(e) => {
+                error!("Failed to deserialize tool settings for kb_search: {:?}", e);
+                Vec::new()
+            },
+        }
+    }
+
+    fn command(&self) -> tokio::process::Command {
+        let mut command = aws_command();
+        if let Some(region) = &self.region {
+            command.arg("--region").arg(region);
+        }
+        if let Some(profile) = &self.profile {
+            command.arg("--profile").arg(profile);
+        }
+        command
+    }
+
+    async fn search(&self, os: &Os, query: &str, limit: usize) -> Result<Vec<Passage>> {
+        match &self.source {
+            KnowledgeBaseSource::Kendra { index_id } => {
+                let mut command = self.command();
+                command
+                    .args(["kendra", "retrieve", "--output", "json", "--index-id", index_id])
+                    .arg("--query-text")
+                    .arg(query)
+                    .arg("--page-size")
+                    .arg(limit.to_string());
+                parse_kendra(&run(command).await?, limit)
+            },
+            KnowledgeBaseSource::QBusiness {
+                application_id,
+                retriever_id,
+            } => {
+                let content_source = serde_json::json!({ "retriever": { "retrieverId": retriever_id } });
+                let mut command = self.command();
+                command
+                    .args(["qbusiness", "search-relevant-content", "--output", "json"])
+                    .args(["--application-id", application_id])
+                    .arg("--query-text")
+                    .arg(query)
+                    .arg("--content-source")
+                    .arg(content_source.to_string())
+                    .arg("--max-results")
+                    .arg(limit.to_string());
+                parse_qbusiness(&run(command).await?, limit)
+            },
+            KnowledgeBaseSource::S3 { uri } => {
+                let dir = directories::knowledge_base_cache_dir(os)?.join(cache_name(&self.name));
+                self.sync(os, uri, &dir).await?;
+                let mut index = WorkspaceIndex::load(os, &dir).await;
+                if index.update(os, &dir) > 0 {
+                    index.save(os, &dir).await?;
+                }
+                let mut passages = Vec::new();
+                for path in index.search(query, limit) {
+                    let Ok(content) = os.fs.read_to_string(dir.join(&path)).await else {
+                        continue;
+                    };
+                    passages.push(Passage {
+                        uri: format!("{}/{}", uri.trim_end_matches('/'), path),
+                        content: best_passage(&content, query),
+                        title: path,
+                    });
+                }
+                Ok(passages)
+            },
+        }
+    }
+
+    /// Syncs the documents at `uri` to `dir`, unless they were synced recently.
+    async fn sync(&self, os: &Os, uri: &str, dir: &Path) -> Result<()> {
+        let marker = dir.join(S3_SYNC_MARKER);
+        let synced_at = os.fs.symlink_metadata(&marker).await.and_then(|m| m.modified()).ok();
+        if synced_at.is_some_and(|time| SystemTime::now().duration_since(time).unwrap_or_default() < S3_SYNC_INTERVAL) {
+            return Ok(());
+        }
+        os.fs.create_dir_all(dir).await?;
+        let mut command = self.command();
+        command
+            .args(["s3", "sync", "--only-show-errors", "--delete"])
+            .arg(uri)
+            .arg(os.fs.chroot_path(dir));
+        run(command).await?;
+        os.fs.write(&marker, "").await?;
+        Ok(())
+    }
+}
+
+/// A passage of a document matching a query.
+#[derive(Debug, Clone, PartialEq)]
+struct Passage {
+    title: String,
+    uri: String,
+    content: String,
+}
+
+/// The kb_search tool searches the knowledge bases configured for the agent, returning ranked
+/// passages for the model to cite.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KbSearch {
+    pub query: String,
+    pub knowledge_base: Option<String>,
+    pub limit: Option<usize>,
+    /// Knowledge bases of the active agent, set before the tool is validated.
+    #[serde(skip)]
+    pub knowledge_bases: Vec<KnowledgeBase>,
+}
+
+impl KbSearch {
+    fn selected(&self) -> impl Iterator<Item = &KnowledgeBase> {
+        self.knowledge_bases
+            .iter()
+            .filter(|kb| self.knowledge_base.as_ref().is_none_or(|name| &kb.name == name))
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        let names = self.selected().map(|kb| kb.name.as_str()).collect::<Vec<_>>();
+        queue!(
+            output,
+            style::Print("Searching "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(names.join(", ")),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(" for: "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(&self.query),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n"),
+        )?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        let mut passages = Vec::new();
+        let mut errors = Vec::new();
+        for kb in self.selected() {
+            match kb.search(os, &self.query, limit).await {
+                Ok(found) => passages.extend(found),
+                Err(err) => errors.push(format!("{}: {err}", kb.name)),
+            }
+        }
+        if passages.is_empty() && !errors.is_empty() {
+            bail!("Failed to search the knowledge bases:\n{}", errors.join("\n"));
+        }
+        Ok(InvokeOutput {
+            output: OutputKind::Text(format_passages(&passages, &errors)),
+        })
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        if self.query.trim().is_empty() {
+            bail!("The query must not be empty");
+        }
+        if self.knowledge_bases.is_empty() {
+            bail!("No knowledge bases are configured for the current agent");
+        }
+        if let Some(name) = &self.knowledge_base {
+            if self.selected().next().is_none() {
+                let names = self
+                    .knowledge_bases
+                    .iter()
+                    .map(|kb| kb.name.as_str())
+                    .collect::<Vec<_>>();
+                bail!("Unknown knowledge base '{name}', expected one of: {}", names.join(", "));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs an AWS CLI command, returning its stdout.
+async fn run(mut command: tokio::process::Command) -> Result<String> {
+    let output = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| eyre!("Unable to run the AWS CLI: {err}"))?
+        .wait_with_output()
+        .await?;
+    if !output.status.success() {
+        bail!("{}", output.stderr.to_str_lossy().trim());
+    }
+    Ok(output.stdout.to_str_lossy().to_string())
+}
+
+fn parse_kendra(output: &str, limit: usize) -> Result<Vec<Passage>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Response {
+        #[serde(default)]
+        result_items: Vec<Item>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Item {
+        #[serde(default)]
+        document_title: String,
+        #[serde(default)]
+        content: String,
+        #[serde(rename = "DocumentURI", default)]
+        document_uri: String,
+    }
+
+    let response = serde_json::from_str::<Response>(output)?;
+    Ok(response
+        .result_items
+        .into_iter()
+        .take(limit)
+        .map(|item| Passage {
+            title: item.document_title,
+            uri: item.document_uri,
+            content: item.content,
+        })
+        .collect())
+}
+
+fn parse_qbusiness(output: &str, limit: usize) -> Result<Vec<Passage>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Response {
+        #[serde(default)]
+        relevant_content: Vec<Item>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Item {
+        #[serde(default)]
+        document_title: String,
+        #[serde(default)]
+        content: String,
+        #[serde(default)]
+        document_uri: String,
+    }
+
+    let response = serde_json::from_str::<Response>(output)?;
+    Ok(response
+        .relevant_content
+        .into_iter()
+        .take(limit)
+        .map(|item| Passage {
+            title: item.document_title,
+            uri: item.document_uri,
+            content: item.content,
+        })
+        .collect())
+}
+
+/// Returns the paragraph of `content` sharing the most terms with `query`.
+fn best_passage(content: &str, query: &str) -> String {
+    let terms = tokenize(query).collect::<HashSet<_>>();
+    content
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .max_by_key(|paragraph| tokenize(paragraph).filter(|term| terms.contains(term)).count())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Name of the cache directory of a knowledge base.
+fn cache_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Formats passages numbered from 1, so that the model cites them as `[[n]](uri)`, which is
+/// rendered as a footnote.
+fn format_passages(passages: &[Passage], errors: &[String]) -> String {
+    let mut text = String::new();
+    if passages.is_empty() {
+        text.push_str("No passages found.\n");
+    } else {
+        text.push_str("Cite the passages you use as [[n]](uri), where n is the number of the passage.\n\n");
+    }
+    for (i, passage) in passages.iter().enumerate() {
+        let mut content = passage.content.trim().to_string();
+        truncate_safe_in_place(&mut content, MAX_PASSAGE_LEN, " ... truncated");
+        let _ = writeln!(text, "[{}] {} — {}\n{}\n", i + 1, passage.title, passage.uri, content);
+    }
+    for error in errors {
+        let _ = writeln!(text, "Failed to search {error}");
+    }
+    truncate_safe_in_place(&mut text, MAX_TOOL_RESPONSE_SIZE, " ... truncated");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knowledge_base_settings() {
+        let agent = Agent {
+            tools_settings: serde_json::from_value(serde_json::json!({
+                "kb_search": {
+                    "knowledgeBases": [
+                        { "name": "runbooks", "type": "kendra", "indexId": "idx", "region": "us-west-2" },
+                        { "name": "handbook", "type": "qbusiness", "applicationId": "app", "retrieverId": "ret" },
+                        { "name": "docs", "type": "s3", "uri": "s3://bucket/docs" }
+                    ]
+                }
+            }))
+            .unwrap(),
+            ..Default::default()
+        };
+        let kbs = KnowledgeBase::configured(&agent);
+        assert_eq!(kbs.len(), 3);
+        assert_eq!(kbs[0].source, KnowledgeBaseSource::Kendra {
+            index_id: "idx".to_string()
+        });
+        assert_eq!(kbs[0].region.as_deref(), Some("us-west-2"));
+        assert_eq!(kbs[1].source, KnowledgeBaseSource::QBusiness {
+            application_id: "app".to_string(),
+            retriever_id: "ret".to_string()
+        });
+        assert_eq!(kbs[2].source, KnowledgeBaseSource::S3 {
+            uri: "s3://bucket/docs".to_string()
+        });
+        assert!(KnowledgeBase::configured(&Agent::default()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_responses() {
+        let kendra = r#"{"QueryId": "q", "ResultItems": [
+            {"Id": "1", "DocumentTitle": "Rotating keys", "Content": "Rotate keys every 90 days.", "DocumentURI": "https://wiki/keys"},
+            {"Id": "2", "DocumentTitle": "Other", "Content": "...", "DocumentURI": "https://wiki/other"}
+        ]}"#;
+        assert_eq!(parse_kendra(kendra, 1).unwrap(), vec![Passage {
+            title: "Rotating keys".to_string(),
+            uri: "https://wiki/keys".to_string(),
+            content: "Rotate keys every 90 days.".to_string(),
+        }]);
+
+        let qbusiness = r#"{"relevantContent": [
+            {"content": "Expenses are filed in the portal.", "documentTitle": "Expenses", "documentUri": "https://hr/expenses", "documentId": "d"}
+        ]}"#;
+        assert_eq!(parse_qbusiness(qbusiness, 5).unwrap(), vec![Passage {
+            title: "Expenses".to_string(),
+            uri: "https://hr/expenses".to_string(),
+            content: "Expenses are filed in the portal.".to_string(),
+        }]);
+        assert!(parse_qbusiness("{}", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_best_passage() {
+        let content =
+            "# Deploying\n\nIntro text.\n\nTo roll back a deployment, run the rollback pipeline.\n\nOther notes.";
+        assert_eq!(
+            best_passage(content, "how do I roll back a deployment?"),
+            "To roll back a deployment, run the rollback pipeline."
+        );
+    }
+
+    #[test]
+    fn test_format_passages() {
+        let passages = vec![Passage {
+            title: "Rotating keys".to_string(),
+            uri: "https://wiki/keys".to_string(),
+            content: "Rotate keys every 90 days.".to_string(),
+        }];
+        let text = format_passages(&passages, &["handbook: access denied".to_string()]);
+        assert!(text.contains("[[n]](uri)"));
+        assert!(text.contains("[1] Rotating keys — https://wiki/keys\nRotate keys every 90 days."));
+        assert!(text.contains("Failed to search handbook: access denied"));
+        assert!(format_passages(&[], &[]).starts_with("No passages found."));
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let os = Os::new().await.unwrap();
+        let mut tool = serde_json::from_value::<KbSearch>(serde_json::json!({ "query": "key rotation" })).unwrap();
+        assert!(tool.validate(&os).await.is_err());
+
+        tool.knowledge_bases = vec![KnowledgeBase {
+            name: "runbooks".to_string(),
+            source: KnowledgeBaseSource::Kendra {
+                index_id: "idx".to_string(),
+            },
+            region: None,
+            profile: None,
+        }];
+        assert!(tool.validate(&os).await.is_ok());
+        tool.knowledge_base = Some("handbook".to_string());
+        assert!(tool.validate(&os).await.unwrap_err().to_string().contains("runbooks"));
+    }
+}
@@ -0,0 +1,324 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use serde::Deserialize;
+
+use super::{
+    InvokeOutput,
+    OutputKind,
+    format_path,
+    sanitize_path_tool_arg,
+};
+use crate::cli::chat::auto_context::SKIPPED_DIRS;
+use crate::cli::chat::cli::memstats::format_bytes;
+use crate::cli::chat::util::gitignore::{
+    Gitignore,
+    is_ignored,
+};
+use crate::os::Os;
+
+const DEFAULT_DEPTH: usize = 3;
+const MAX_DEPTH: usize = 10;
+/// Entries listed per directory, beyond which only their number is.
+const MAX_DIR_ENTRIES: usize = 100;
+/// Lines of the listing, beyond which it is cut.
+const MAX_LINES: usize = 1000;
+/// Entries walked to count the files of the directories, beyond which the counts are incomplete.
+const MAX_WALKED_ENTRIES: usize = 200_000;
+
+/// The list_files tool lists the files of a directory as a tree, without the files ignored by git
+/// nor the directories of dependencies, so that listing a project does not flood the context.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListFiles {
+    /// Directory to list, the current directory by default.
+    pub path: Option<String>,
+    /// Levels of directories to list, [DEFAULT_DEPTH] by default.
+    pub depth: Option<usize>,
+    #[serde(default)]
+    pub include_ignored: bool,
+    /// Roots registered with `/context add-root`, that a listed directory in one of them is named
+    /// relative to.
+    #[serde(skip)]
+    pub roots: Vec<PathBuf>,
+}
+
+/// A directory and what it contains, recursively.
+#[derive(Debug, Default)]
+struct Dir {
+    name: String,
+    dirs: Vec<Dir>,
+    /// The names and sizes of the files of the directory.
+    files: Vec<(String, u64)>,
+    /// Number of files of the directory and its subdirectories.
+    file_count: usize,
+    /// Size in bytes of the files of the directory and its subdirectories.
+    size: u64,
+}
+
+#[derive(Debug, Default)]
+struct WalkStats {
+    walked: usize,
+    ignored: usize,
+}
+
+impl ListFiles {
+    pub fn path(&self) -> &str {
+        self.path.as_deref().unwrap_or(".")
+    }
+
+    fn depth(&self) -> usize {
+        self.depth.unwrap_or(DEFAULT_DEPTH).clamp(1, MAX_DEPTH)
+    }
+
+    pub fn queue_description(&self, os: &Os, output: &mut impl Write) -> Result<()> {
+        let cwd = os.env.current_dir()?;
+        queue!(
+            output,
+            style::Print("Listing the files of "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(format_path(cwd, &self.roots, sanitize_path_tool_arg(os, self.path()))),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(
+                " {} levels deep{}\n",
+                self.depth(),
+                if self.include_ignored {
+                    ", including ignored files"
+                } else {
+                    ""
+                }
+            )),
+        )?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let cwd = os.env.current_dir()?;
+        let path = sanitize_path_tool_arg(os, self.path());
+        let mut gitignores = Vec::new();
+        if !self.include_ignored {
+            // The .gitignore files of the parent directories within the same repository apply too.
+            let mut parents = Vec::new();
+            for dir in path.ancestors().skip(1) {
+                parents.push(dir);
+                if dir.join(".git").exists() {
+                    break;
+                }
+            }
+            if parents.last().is_some_and(|repo| repo.join(".git").exists()) {
+                gitignores.extend(parents.into_iter().rev().filter_map(Gitignore::from_dir));
+            }
+        }
+
+        let mut stats = WalkStats::default();
+        let root = walk(
+            &path,
+            format_path(&cwd, &self.roots, &path),
+            &mut gitignores,
+            self.include_ignored,
+            &mut stats,
+        );
+        let mut text = String::new();
+        render(&root, 0, self.depth(), &mut text);
+        if text.lines().count() > MAX_LINES {
+            let cut = text.lines().take(MAX_LINES).collect::<Vec<_>>().join("\n");
+            text = format!(
+                "{cut}\n... the listing was cut at {MAX_LINES} lines. List a subdirectory or use a smaller depth.\n"
+            );
+        }
+        let _ = writeln!(
+            text,
+            "\n{} files, {} in total",
+            root.file_count,
+            format_bytes(Some(root.size))
+        );
+        if stats.ignored > 0 {
+            let _ = writeln!(
+                text,
+                "{} entries ignored by .gitignore or in dependency directories were left out. Set include_ignored to list them.",
+                stats.ignored
+            );
+        }
+        if stats.walked >= MAX_WALKED_ENTRIES {
+            let _ = writeln!(
+                text,
+                "Only the first {MAX_WALKED_ENTRIES} entries were walked, so the counts are incomplete."
+            );
+        }
+        Ok(InvokeOutput {
+            output: OutputKind::Text(text),
+        })
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        let path = sanitize_path_tool_arg(os, self.path());
+        if !os.fs.exists(&path) {
+            bail!("'{}' does not exist", path.display());
+        }
+        if !os.fs.symlink_metadata(&path).await?.is_dir() {
+            bail!("'{}' is not a directory", path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Walks the directory at `path` named `name`, leaving out what `gitignores` ignore unless
+/// `include_ignored`.
+fn walk(
+    path: &Path,
+    name: String,
+    gitignores: &mut Vec<Gitignore>,
+    include_ignored: bool,
+    stats: &mut WalkStats,
+) -> Dir {
+    let mut dir = Dir {
+        name,
+        ..Default::default()
+    };
+    let Ok(read_dir) = std::fs::read_dir(path) else {
+        return dir;
+    };
+    let gitignore = (!include_ignored).then(|| Gitignore::from_dir(path)).flatten();
+    let has_gitignore = gitignore.is_some();
+    gitignores.extend(gitignore);
+
+    let mut entries = read_dir.filter_map(Result::ok).collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if stats.walked >= MAX_WALKED_ENTRIES {
+            break;
+        }
+        stats.walked += 1;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if name == ".git" {
+            continue;
+        }
+        if !include_ignored
+            && (is_ignored(gitignores, &entry.path(), file_type.is_dir())
+                || file_type.is_dir() && SKIPPED_DIRS.contains(&name.as_str()))
+        {
+            stats.ignored += 1;
+            continue;
+        }
+        // Symlinks are listed as files, so that walking them cannot loop.
+        if file_type.is_dir() {
+            let subdir = walk(&entry.path(), name, gitignores, include_ignored, stats);
+            dir.file_count += subdir.file_count;
+            dir.size += subdir.size;
+            dir.dirs.push(subdir);
+        } else {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+            dir.file_count += 1;
+            dir.size += size;
+            dir.files.push((name, size));
+        }
+    }
+
+    if has_gitignore {
+        gitignores.pop();
+    }
+    dir
+}
+
+/// Renders `dir`, at `depth` levels below the listed directory, and its entries down to
+/// `max_depth` levels as an indented tree.
+fn render(dir: &Dir, depth: usize, max_depth: usize, text: &mut String) {
+    let indent = "  ".repeat(depth);
+    let _ = writeln!(
+        text,
+        "{indent}{}/ ({} files, {})",
+        dir.name.trim_end_matches('/'),
+        dir.file_count,
+        format_bytes(Some(dir.size))
+    );
+    if depth >= max_depth {
+        return;
+    }
+    let indent = "  ".repeat(depth + 1);
+    for subdir in dir.dirs.iter().take(MAX_DIR_ENTRIES) {
+        render(subdir, depth + 1, max_depth, text);
+    }
+    for (name, size) in dir.files.iter().take(MAX_DIR_ENTRIES.saturating_sub(dir.dirs.len())) {
+        let _ = writeln!(text, "{indent}{name} ({})", format_bytes(Some(*size)));
+    }
+    let entries = dir.dirs.len() + dir.files.len();
+    if entries > MAX_DIR_ENTRIES {
+        let _ = writeln!(text, "{indent}... and {} more entries", entries - MAX_DIR_ENTRIES);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_list_files() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/project/src/cli/nested").await.unwrap();
+        os.fs.create_dir_all("/project/node_modules/dep").await.unwrap();
+        os.fs.create_dir_all("/project/out").await.unwrap();
+        os.fs.create_dir_all("/project/.git").await.unwrap();
+        os.fs.write("/project/.gitignore", "out/\n*.log\n").await.unwrap();
+        os.fs.write("/project/.git/HEAD", "ref: refs/heads/main").await.unwrap();
+        os.fs.write("/project/Cargo.toml", "[package]\n").await.unwrap();
+        os.fs.write("/project/debug.log", "log").await.unwrap();
+        os.fs.write("/project/out/app", "binary").await.unwrap();
+        os.fs.write("/project/node_modules/dep/index.js", "").await.unwrap();
+        os.fs.write("/project/src/main.rs", "fn main() {}").await.unwrap();
+        os.fs.write("/project/src/cli/mod.rs", "mod nested;").await.unwrap();
+        os.fs.write("/project/src/cli/nested/mod.rs", "").await.unwrap();
+
+        let mut tool =
+            serde_json::from_value::<ListFiles>(serde_json::json!({ "path": "/project", "depth": 2 })).unwrap();
+        tool.validate(&os).await.unwrap();
+        let OutputKind::Text(text) = tool.invoke(&os, std::io::stdout()).await.unwrap().output else {
+            panic!("expected text output");
+        };
+        let listed = text.lines().skip(1).collect::<Vec<_>>().join("\n");
+        assert_eq!(
+            listed,
+            [
+                "  src/ (3 files, 23 B)",
+                "    cli/ (2 files, 11 B)",
+                "    main.rs (12 B)",
+                "  .gitignore (11 B)",
+                "  Cargo.toml (10 B)",
+                "",
+                "5 files, 44 B in total",
+                "3 entries ignored by .gitignore or in dependency directories were left out. Set include_ignored to list them.",
+            ]
+            .join("\n")
+        );
+
+        let tool = serde_json::from_value::<ListFiles>(serde_json::json!({
+            "path": "/project",
+            "include_ignored": true,
+        }))
+        .unwrap();
+        let OutputKind::Text(text) = tool.invoke(&os, std::io::stdout()).await.unwrap().output else {
+            panic!("expected text output");
+        };
+        assert!(text.contains("  out/ (1 files, 6 B)\n    app (6 B)"), "{text}");
+        assert!(text.contains("      index.js (0 B)"), "{text}");
+        assert!(!text.contains("HEAD"), "{text}");
+
+        let mut tool =
+            serde_json::from_value::<ListFiles>(serde_json::json!({ "path": "/project/Cargo.toml" })).unwrap();
+        assert!(tool.validate(&os).await.is_err());
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::process::Stdio;
+
+use bstr::ByteSlice;
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+    eyre,
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use super::use_aws::aws_command;
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+
+const DEFAULT_SINCE: &str = "15m";
+const DEFAULT_LIMIT: usize = 1000;
+const MAX_LIMIT: usize = 10_000;
+/// Distinct messages returned to the model.
+const MAX_SAMPLES: usize = 50;
+/// Characters of each message returned to the model.
+const MAX_MESSAGE_LEN: usize = 1000;
+
+/// The logs_tail tool reads the events of a CloudWatch Logs group in a time range, returning a
+/// deduplicated sample of their messages.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogsTail {
+    pub log_group_name: String,
+    /// Start of the time range, either relative to now such as `30m` or an RFC 3339 timestamp.
+    pub since: Option<String>,
+    /// End of the time range, in the same formats as `since`. Defaults to now.
+    pub until: Option<String>,
+    pub filter_pattern: Option<String>,
+    pub log_stream_name_prefix: Option<String>,
+    /// Maximum number of events read.
+    pub limit: Option<usize>,
+    pub region: String,
+    pub profile_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogEvent {
+    timestamp: i64,
+    message: String,
+    #[serde(default)]
+    log_stream_name: String,
+}
+
+/// Events whose messages only differ by ids, durations, or timestamps.
+#[derive(Debug, PartialEq)]
+struct Sample {
+    message: String,
+    log_stream_name: String,
+    count: usize,
+    first: i64,
+    last: i64,
+}
+
+impl LogsTail {
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        queue!(
+            output,
+            style::Print("Reading the logs of "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(&self.log_group_name),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(
+                " from {} to {}",
+                self.since.as_deref().unwrap_or(DEFAULT_SINCE),
+                self.until.as_deref().unwrap_or("now")
+            )),
+        )?;
+        if let Some(pattern) = &self.filter_pattern {
+            queue!(output, style::Print(format!(" matching {pattern}")))?;
+        }
+        queue!(output, style::Print(format!("\nRegion: {}", self.region)))?;
+        if let Some(profile_name) = &self.profile_name {
+            queue!(output, style::Print(format!("\nProfile name: {profile_name}")))?;
+        }
+        queue!(output, style::Print("\n"))?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, _os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let now = OffsetDateTime::now_utc();
+        let start = parse_time(self.since.as_deref().unwrap_or(DEFAULT_SINCE), now)?;
+        let end = self.until.as_deref().map(|until| parse_time(until, now)).transpose()?;
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+        let mut command = aws_command();
+        command.arg("--region").arg(&self.region);
+        if let Some(profile_name) = &self.profile_name {
+            command.arg("--profile").arg(profile_name);
+        }
+        command
+            .args(["logs", "filter-log-events", "--output", "json", "--no-cli-pager"])
+            .arg("--log-group-name")
+            .arg(&self.log_group_name)
+            .arg("--start-time")
+            .arg(millis(start).to_string())
+            .arg("--max-items")
+            .arg(limit.to_string());
+        if let Some(end) = end {
+            command.arg("--end-time").arg(millis(end).to_string());
+        }
+        if let Some(pattern) = &self.filter_pattern {
+            command.arg("--filter-pattern").arg(pattern);
+        }
+        if let Some(prefix) = &self.log_stream_name_prefix {
+            command.arg("--log-stream-name-prefix").arg(prefix);
+        }
+        let output = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .wrap_err("Unable to run the AWS CLI")?
+            .wait_with_output()
+            .await?;
+        if !output.status.success() {
+            bail!("{}", output.stderr.to_str_lossy().trim());
+        }
+
+        #[derive(Deserialize)]
+        struct Response {
+            #[serde(default)]
+            events: Vec<LogEvent>,
+        }
+        let response = serde_json::from_slice::<Response>(&output.stdout)?;
+        Ok(InvokeOutput {
+            output: OutputKind::Text(format_samples(response.events.len(), &sample(response.events))),
+        })
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        if self.log_group_name.trim().is_empty() {
+            bail!("The log group name must not be empty");
+        }
+        let now = OffsetDateTime::now_utc();
+        let start = parse_time(self.since.as_deref().unwrap_or(DEFAULT_SINCE), now)?;
+        if let Some(until) = &self.until {
+            if parse_time(until, now)? <= start {
+                bail!("'until' must be later than 'since'");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a time either relative to `now`, such as `90s`, `30m`, `2h`, or `1d`, or as an RFC 3339
+/// timestamp.
+fn parse_time(input: &str, now: OffsetDateTime) -> Result<OffsetDateTime> {
+    let input = input.trim();
+    if let Ok(time) = OffsetDateTime::parse(input, &Rfc3339) {
+        return Ok(time);
+    }
+    let invalid = || eyre!("Invalid time '{input}', expected e.g. 30m, 2h, 1d, or an RFC 3339 timestamp");
+    let unit_at = input.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (amount, unit) = input.split_at(unit_at);
+    let amount = amount.parse::<i64>().ok().ok_or_else(invalid)?;
+    let duration = match unit {
+        "s" => time::Duration::seconds(amount),
+        "m" => time::Duration::minutes(amount),
+        "h" => time::Duration::hours(amount),
+        "d" => time::Duration::days(amount),
+        _ => return Err(invalid()),
+    };
+    Ok(now - duration)
+}
+
+fn millis(time: OffsetDateTime) -> i128 {
+    time.unix_timestamp_nanos() / 1_000_000
+}
+
+/// Returns `message` with its words containing digits replaced, so that messages only differing by
+/// ids, durations, or timestamps are grouped.
+fn normalize(message: &str) -> String {
+    message
+        .split_whitespace()
+        .map(|word| {
+            if word.contains(|c: char| c.is_ascii_digit()) {
+                "#"
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Groups events by their normalized message, keeping the first message of each group, most
+/// frequent groups first.
+fn sample(events: Vec<LogEvent>) -> Vec<Sample> {
+    let mut samples: Vec<Sample> = Vec::new();
+    let mut index = HashMap::new();
+    for event in events {
+        let key = normalize(&event.message);
+        match index.get(&key) {
+            Some(&i) => {
+                let sample: &mut Sample = &mut samples[i];
+                sample.count += 1;
+                sample.first = sample.first.min(event.timestamp);
+                sample.last = sample.last.max(event.timestamp);
+            },
+            None => {
+                index.insert(key, samples.len());
+                samples.push(Sample {
+                    message: event.message.trim().to_string(),
+                    log_stream_name: event.log_stream_name,
+                    count: 1,
+                    first: event.timestamp,
+                    last: event.timestamp,
+                });
+            },
+        }
+    }
+    // The sort is stable, so groups of the same size stay in the order they were first seen.
+    samples.sort_by(|a, b| b.count.cmp(&a.count));
+    samples
+}
+
+fn format_timestamp(millis: i64) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000)
+        .ok()
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_else(|| millis.to_string())
+}
+
+fn format_samples(total: usize, samples: &[Sample]) -> String {
+    if total == 0 {
+        return "No log events found.".to_string();
+    }
+    let mut text = format!("{total} events, {} distinct messages", samples.len());
+    if samples.len() > MAX_SAMPLES {
+        let _ = write!(text, ", showing the {MAX_SAMPLES} most frequent");
+    }
+    text.push_str("\n\n");
+    for sample in samples.iter().take(MAX_SAMPLES) {
+        let mut message = sample.message.clone();
+        truncate_safe_in_place(&mut message, MAX_MESSAGE_LEN, " ... truncated");
+        if sample.count == 1 {
+            let _ = writeln!(text, "[{}] {}", format_timestamp(sample.first), sample.log_stream_name);
+        } else {
+            let _ = writeln!(
+                text,
+                "[{} - {}] {} (x{})",
+                format_timestamp(sample.first),
+                format_timestamp(sample.last),
+                sample.log_stream_name,
+                sample.count
+            );
+        }
+        let _ = writeln!(text, "{message}\n");
+    }
+    truncate_safe_in_place(&mut text, MAX_TOOL_RESPONSE_SIZE / 3, " ... truncated");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        let now = OffsetDateTime::parse("2025-01-01T12:00:00Z", &Rfc3339).unwrap();
+        assert_eq!(
+            parse_time("30m", now).unwrap(),
+            OffsetDateTime::parse("2025-01-01T11:30:00Z", &Rfc3339).unwrap()
+        );
+        assert_eq!(
+            parse_time("1d", now).unwrap(),
+            OffsetDateTime::parse("2024-12-31T12:00:00Z", &Rfc3339).unwrap()
+        );
+        assert_eq!(
+            parse_time("2025-01-01T10:00:00Z", now).unwrap(),
+            OffsetDateTime::parse("2025-01-01T10:00:00Z", &Rfc3339).unwrap()
+        );
+        assert!(parse_time("30", now).is_err());
+        assert!(parse_time("5w", now).is_err());
+        assert!(parse_time("yesterday", now).is_err());
+    }
+
+    #[test]
+    fn test_sample() {
+        let event = |timestamp: i64, message: &str| LogEvent {
+            timestamp,
+            message: message.to_string(),
+            log_stream_name: "stream".to_string(),
+        };
+        let samples = sample(vec![
+            event(1, "START RequestId: 1a2b Version: $LATEST"),
+            event(2, "ERROR Task timed out after 3.00 seconds"),
+            event(3, "START RequestId: 3c4d Version: $LATEST"),
+            event(4, "START RequestId: 5e6f Version: $LATEST\n"),
+        ]);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].count, 3);
+        assert_eq!(samples[0].message, "START RequestId: 1a2b Version: $LATEST");
+        assert_eq!((samples[0].first, samples[0].last), (1, 4));
+        assert_eq!(samples[1].message, "ERROR Task timed out after 3.00 seconds");
+
+        let text = format_samples(4, &samples);
+        assert!(text.starts_with("4 events, 2 distinct messages\n"));
+        assert!(text.contains("stream (x3)\nSTART RequestId: 1a2b"));
+        assert_eq!(format_samples(0, &[]), "No log events found.");
+    }
+}
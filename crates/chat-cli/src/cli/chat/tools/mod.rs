@@ -1,10 +1,27 @@
+pub mod aws_read;
+pub mod browser;
 pub mod custom_tool;
+pub mod db_query;
+pub mod diagnostics;
 pub mod execute;
 pub mod fs_read;
 pub mod fs_write;
 pub mod gh_issue;
+pub mod http_request;
+pub mod infra_plan;
+pub mod kb_search;
 pub mod knowledge;
+pub mod list_files;
+pub mod logs_tail;
+pub mod post_write;
+pub mod process;
+pub mod rename_symbol;
+pub mod run_tests;
+pub mod schema_validation;
+pub mod scratchpad;
+pub mod semantic_search;
 pub mod thinking;
+pub mod update_plan;
 pub mod use_aws;
 
 use std::borrow::{
@@ -17,26 +34,42 @@ use std::path::{
     PathBuf,
 };
 
+use aws_read::AwsRead;
+use browser::Browser;
 use crossterm::queue;
 use crossterm::style::{
     self,
     Color,
 };
 use custom_tool::CustomTool;
+use db_query::DbQuery;
+use diagnostics::Diagnostics;
 use execute::ExecuteCommand;
 use eyre::Result;
 use fs_read::FsRead;
 use fs_write::FsWrite;
 use gh_issue::GhIssue;
+use http_request::HttpRequest;
+use infra_plan::InfraPlan;
+use kb_search::KbSearch;
 use knowledge::Knowledge;
+use list_files::ListFiles;
+use logs_tail::LogsTail;
+use process::Process;
+use rename_symbol::RenameSymbol;
+use run_tests::RunTests;
+use scratchpad::Scratchpad;
+use semantic_search::SemanticSearch;
 use serde::{
     Deserialize,
     Serialize,
 };
 use thinking::Thinking;
 use tracing::error;
+use update_plan::UpdatePlan;
 use use_aws::UseAws;
 
+use super::audit::ApprovalDecision;
 use super::consts::MAX_TOOL_RESPONSE_SIZE;
 use super::util::images::RichImageBlocks;
 use crate::cli::agent::{
@@ -46,17 +79,34 @@ use crate::cli::agent::{
 use crate::os::Os;
 
 pub const DEFAULT_APPROVE: [&str; 1] = ["fs_read"];
-pub const NATIVE_TOOLS: [&str; 7] = [
+pub const NATIVE_TOOLS: [&str; 24] = [
     "fs_read",
     "fs_write",
+    "list_files",
+    "rename_symbol",
     #[cfg(windows)]
     "execute_cmd",
     #[cfg(not(windows))]
     "execute_bash",
     "use_aws",
+    "aws_read",
+    "logs_tail",
+    "infra_plan",
+    "run_tests",
+    "diagnostics",
+    "scratchpad",
+    "db_query",
+    "http_request",
+    "browser",
     "gh_issue",
     "knowledge",
     "thinking",
+    "update_plan",
+    "semantic_search",
+    "kb_search",
+    "process_list",
+    "process_output",
+    "process_kill",
 ];
 
 /// Represents an executable tool use.
@@ -65,12 +115,27 @@ pub const NATIVE_TOOLS: [&str; 7] = [
 pub enum Tool {
     FsRead(FsRead),
     FsWrite(FsWrite),
+    ListFiles(ListFiles),
+    RenameSymbol(RenameSymbol),
     ExecuteCommand(ExecuteCommand),
     UseAws(UseAws),
     Custom(CustomTool),
     GhIssue(GhIssue),
     Knowledge(Knowledge),
     Thinking(Thinking),
+    UpdatePlan(UpdatePlan),
+    SemanticSearch(SemanticSearch),
+    KbSearch(KbSearch),
+    AwsRead(AwsRead),
+    LogsTail(LogsTail),
+    InfraPlan(InfraPlan),
+    RunTests(RunTests),
+    Diagnostics(Diagnostics),
+    Scratchpad(Scratchpad),
+    DbQuery(DbQuery),
+    HttpRequest(HttpRequest),
+    Browser(Browser),
+    Process(Process),
 }
 
 impl Tool {
@@ -79,6 +144,8 @@ impl Tool {
         match self {
             Tool::FsRead(_) => "fs_read",
             Tool::FsWrite(_) => "fs_write",
+            Tool::ListFiles(_) => "list_files",
+            Tool::RenameSymbol(_) => "rename_symbol",
             #[cfg(windows)]
             Tool::ExecuteCommand(_) => "execute_cmd",
             #[cfg(not(windows))]
@@ -88,35 +155,145 @@ impl Tool {
             Tool::GhIssue(_) => "gh_issue",
             Tool::Knowledge(_) => "knowledge",
             Tool::Thinking(_) => "thinking (prerelease)",
+            Tool::UpdatePlan(_) => "update_plan",
+            Tool::SemanticSearch(_) => "semantic_search",
+            Tool::KbSearch(_) => "kb_search",
+            Tool::AwsRead(_) => "aws_read",
+            Tool::LogsTail(_) => "logs_tail",
+            Tool::InfraPlan(_) => "infra_plan",
+            Tool::RunTests(_) => "run_tests",
+            Tool::Diagnostics(_) => "diagnostics",
+            Tool::Scratchpad(_) => "scratchpad",
+            Tool::DbQuery(_) => "db_query",
+            Tool::HttpRequest(_) => "http_request",
+            Tool::Browser(_) => "browser",
+            Tool::Process(process) => process.tool_name(),
         }
         .to_owned()
     }
 
     /// Whether or not the tool should prompt the user to accept before [Self::invoke] is called.
-    pub fn requires_acceptance(&self, agent: &Agent) -> PermissionEvalResult {
+    pub fn requires_acceptance(&self, os: &Os, agent: &Agent) -> PermissionEvalResult {
         match self {
             Tool::FsRead(fs_read) => fs_read.eval_perm(agent),
-            Tool::FsWrite(fs_write) => fs_write.eval_perm(agent),
+            Tool::FsWrite(fs_write) => fs_write.eval_perm(os, agent),
+            Tool::ListFiles(_) => PermissionEvalResult::Allow,
+            Tool::RenameSymbol(_) => RenameSymbol::eval_perm(agent),
             Tool::ExecuteCommand(execute_command) => execute_command.eval_perm(agent),
             Tool::UseAws(use_aws) => use_aws.eval_perm(agent),
             Tool::Custom(custom_tool) => custom_tool.eval_perm(agent),
-            Tool::GhIssue(_) => PermissionEvalResult::Allow,
+            Tool::GhIssue(gh_issue) => gh_issue.eval_perm(agent),
             Tool::Thinking(_) => PermissionEvalResult::Allow,
+            Tool::UpdatePlan(_) => PermissionEvalResult::Allow,
+            Tool::SemanticSearch(_) => PermissionEvalResult::Allow,
+            Tool::KbSearch(_) => PermissionEvalResult::Allow,
+            Tool::AwsRead(_) => PermissionEvalResult::Allow,
+            Tool::LogsTail(_) => PermissionEvalResult::Allow,
+            Tool::InfraPlan(_) => InfraPlan::eval_perm(agent),
+            Tool::RunTests(_) => RunTests::eval_perm(agent),
+            Tool::Diagnostics(_) => Diagnostics::eval_perm(agent),
+            Tool::Scratchpad(_) => Scratchpad::eval_perm(agent),
+            Tool::DbQuery(_) => DbQuery::eval_perm(agent),
+            Tool::HttpRequest(http_request) => http_request.eval_perm(agent),
+            Tool::Browser(_) => PermissionEvalResult::Allow,
+            Tool::Process(_) => PermissionEvalResult::Allow,
             Tool::Knowledge(_) => PermissionEvalResult::Ask,
         }
     }
 
+    /// The paths the tool reads or writes. For execute_bash, these are the arguments of the
+    /// command that look like paths.
+    pub fn paths(&self) -> Vec<String> {
+        match self {
+            Tool::FsRead(fs_read) => fs_read.paths().into_iter().map(str::to_string).collect(),
+            Tool::FsWrite(fs_write) => vec![fs_write.path().to_string()],
+            Tool::ListFiles(list_files) => vec![list_files.path().to_string()],
+            Tool::RenameSymbol(rename_symbol) => vec![rename_symbol.path().to_string()],
+            Tool::ExecuteCommand(execute_command) => execute_command.path_args(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the first path used by the tool that resolves outside of the agent's workspace root
+    /// and the `roots` registered with `/context add-root`, if the agent has strictWorkspace
+    /// enabled.
+    pub fn outside_workspace(&self, os: &Os, agent: &Agent, roots: &[PathBuf]) -> Option<PathBuf> {
+        if !agent.strict_workspace {
+            return None;
+        }
+        let workspace_root = agent.workspace_root.as_deref().unwrap_or(".");
+        let dirs = std::iter::once(Path::new(workspace_root))
+            .chain(roots.iter().map(PathBuf::as_path))
+            .map(|dir| PermissionPath::new(os, dir, roots))
+            .collect::<Vec<_>>();
+        self.paths()
+            .into_iter()
+            .map(|path| PermissionPath::new(os, path, roots))
+            .find(|path| !dirs.iter().any(|dir| path.is_within(dir)))
+            .map(|path| path.resolved().to_path_buf())
+    }
+
+    /// Whether the tool applies infrastructure changes, which always need to be approved one use at
+    /// a time unless the agent allows them in the `infra_plan` tool settings.
+    pub fn applies_infrastructure(&self, agent: &Agent) -> bool {
+        let applies = match self {
+            Tool::ExecuteCommand(execute_command) => infra_plan::applies_infrastructure(&execute_command.command),
+            Tool::UseAws(use_aws) => {
+                use_aws.service_name == "cloudformation" && infra_plan::is_cloudformation_apply(&use_aws.operation_name)
+            },
+            _ => false,
+        };
+        applies && !infra_plan::apply_allowed(agent)
+    }
+
+    /// Whether the tool replaces an existing file that the agent's fs_write create policy requires
+    /// approval for, one use at a time.
+    pub fn asks_before_replacing(&self, os: &Os) -> bool {
+        match self {
+            Tool::FsWrite(fs_write) => fs_write.asks_before_replacing(os),
+            _ => false,
+        }
+    }
+
+    /// Returns the absolute paths of the existing files and directories that the tool overwrites,
+    /// edits, or deletes.
+    pub fn destroyed_paths(&self, os: &Os) -> Vec<PathBuf> {
+        let paths = match self {
+            Tool::FsWrite(fs_write) => fs_write.replaced_path(os).into_iter().collect(),
+            Tool::RenameSymbol(rename_symbol) => rename_symbol.written_paths(os),
+            Tool::ExecuteCommand(execute_command) => execute_command.removed_paths(os),
+            _ => Vec::new(),
+        };
+        let cwd = os.env.current_dir().unwrap_or_default();
+        paths.into_iter().map(|path| cwd.join(path)).collect()
+    }
+
     /// Invokes the tool asynchronously
     pub async fn invoke(&self, os: &Os, stdout: &mut impl Write) -> Result<InvokeOutput> {
         match self {
             Tool::FsRead(fs_read) => fs_read.invoke(os, stdout).await,
             Tool::FsWrite(fs_write) => fs_write.invoke(os, stdout).await,
-            Tool::ExecuteCommand(execute_command) => execute_command.invoke(stdout).await,
+            Tool::ListFiles(list_files) => list_files.invoke(os, stdout).await,
+            Tool::RenameSymbol(rename_symbol) => rename_symbol.invoke(os, stdout).await,
+            Tool::ExecuteCommand(execute_command) => execute_command.invoke(os, stdout).await,
             Tool::UseAws(use_aws) => use_aws.invoke(os, stdout).await,
             Tool::Custom(custom_tool) => custom_tool.invoke(os, stdout).await,
             Tool::GhIssue(gh_issue) => gh_issue.invoke(os, stdout).await,
             Tool::Knowledge(knowledge) => knowledge.invoke(os, stdout).await,
             Tool::Thinking(think) => think.invoke(stdout).await,
+            Tool::UpdatePlan(update_plan) => update_plan.invoke(stdout).await,
+            Tool::SemanticSearch(semantic_search) => semantic_search.invoke(os, stdout).await,
+            Tool::KbSearch(kb_search) => kb_search.invoke(os, stdout).await,
+            Tool::AwsRead(aws_read) => aws_read.invoke(os, stdout).await,
+            Tool::LogsTail(logs_tail) => logs_tail.invoke(os, stdout).await,
+            Tool::InfraPlan(infra_plan) => infra_plan.invoke(os, stdout).await,
+            Tool::RunTests(run_tests) => run_tests.invoke(os, stdout).await,
+            Tool::Diagnostics(diagnostics) => diagnostics.invoke(os, stdout).await,
+            Tool::Scratchpad(scratchpad) => scratchpad.invoke(os, stdout).await,
+            Tool::DbQuery(db_query) => db_query.invoke(os, stdout).await,
+            Tool::HttpRequest(http_request) => http_request.invoke(os, stdout).await,
+            Tool::Browser(browser) => browser.invoke(os, stdout).await,
+            Tool::Process(process) => process.invoke(stdout).await,
         }
     }
 
@@ -125,12 +302,27 @@ impl Tool {
         match self {
             Tool::FsRead(fs_read) => fs_read.queue_description(os, output).await,
             Tool::FsWrite(fs_write) => fs_write.queue_description(os, output),
+            Tool::ListFiles(list_files) => list_files.queue_description(os, output),
+            Tool::RenameSymbol(rename_symbol) => rename_symbol.queue_description(os, output),
             Tool::ExecuteCommand(execute_command) => execute_command.queue_description(output),
             Tool::UseAws(use_aws) => use_aws.queue_description(output),
             Tool::Custom(custom_tool) => custom_tool.queue_description(output),
             Tool::GhIssue(gh_issue) => gh_issue.queue_description(output),
             Tool::Knowledge(knowledge) => knowledge.queue_description(os, output).await,
             Tool::Thinking(thinking) => thinking.queue_description(output),
+            Tool::UpdatePlan(update_plan) => update_plan.queue_description(output),
+            Tool::SemanticSearch(semantic_search) => semantic_search.queue_description(output),
+            Tool::KbSearch(kb_search) => kb_search.queue_description(output),
+            Tool::AwsRead(aws_read) => aws_read.queue_description(output),
+            Tool::LogsTail(logs_tail) => logs_tail.queue_description(output),
+            Tool::InfraPlan(infra_plan) => infra_plan.queue_description(os, output),
+            Tool::RunTests(run_tests) => run_tests.queue_description(os, output),
+            Tool::Diagnostics(diagnostics) => diagnostics.queue_description(os, output),
+            Tool::Scratchpad(scratchpad) => scratchpad.queue_description(output),
+            Tool::DbQuery(db_query) => db_query.queue_description(output),
+            Tool::HttpRequest(http_request) => http_request.queue_description(output),
+            Tool::Browser(browser) => browser.queue_description(output),
+            Tool::Process(process) => process.queue_description(output),
         }
     }
 
@@ -139,12 +331,27 @@ impl Tool {
         match self {
             Tool::FsRead(fs_read) => fs_read.validate(os).await,
             Tool::FsWrite(fs_write) => fs_write.validate(os).await,
+            Tool::ListFiles(list_files) => list_files.validate(os).await,
+            Tool::RenameSymbol(rename_symbol) => rename_symbol.validate(os).await,
             Tool::ExecuteCommand(execute_command) => execute_command.validate(os).await,
             Tool::UseAws(use_aws) => use_aws.validate(os).await,
             Tool::Custom(custom_tool) => custom_tool.validate(os).await,
             Tool::GhIssue(gh_issue) => gh_issue.validate(os).await,
             Tool::Knowledge(knowledge) => knowledge.validate(os).await,
             Tool::Thinking(think) => think.validate(os).await,
+            Tool::UpdatePlan(update_plan) => update_plan.validate(os).await,
+            Tool::SemanticSearch(semantic_search) => semantic_search.validate(os).await,
+            Tool::KbSearch(kb_search) => kb_search.validate(os).await,
+            Tool::AwsRead(aws_read) => aws_read.validate(os).await,
+            Tool::LogsTail(logs_tail) => logs_tail.validate(os).await,
+            Tool::InfraPlan(infra_plan) => infra_plan.validate(os).await,
+            Tool::RunTests(run_tests) => run_tests.validate(os).await,
+            Tool::Diagnostics(diagnostics) => diagnostics.validate(os).await,
+            Tool::Scratchpad(scratchpad) => scratchpad.validate(os).await,
+            Tool::DbQuery(db_query) => db_query.validate(os).await,
+            Tool::HttpRequest(http_request) => http_request.validate(os).await,
+            Tool::Browser(browser) => browser.validate(os).await,
+            Tool::Process(process) => process.validate(os).await,
         }
     }
 }
@@ -230,6 +437,10 @@ pub struct QueuedTool {
     pub name: String,
     pub accepted: bool,
     pub tool: Tool,
+    /// See [super::audit::args_hash].
+    pub args_hash: String,
+    /// Set once the tool use is accepted.
+    pub approval: Option<ApprovalDecision>,
 }
 
 /// The schema specification describing a tool's fields.
@@ -295,6 +506,163 @@ pub fn sanitize_path_tool_arg(os: &Os, path: impl AsRef<Path>) -> PathBuf {
     os.fs.chroot_path(res)
 }
 
+/// A model-provided path resolved for matching against the path globs of tool settings.
+///
+/// Globs are matched against the resolved path rather than the raw argument, so that paths such
+/// as `src/../../secret` cannot bypass globs anchored at absolute paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionPath {
+    /// The path as provided by the model.
+    raw: PathBuf,
+    /// The absolute path, with `~`, `.`, and `..` resolved lexically.
+    absolute: PathBuf,
+    /// [Self::absolute] with symlinks resolved, if that leads elsewhere.
+    canonical: Option<PathBuf>,
+    /// The current working directory, that relative globs are matched from.
+    cwd: PathBuf,
+    /// Roots registered with `/context add-root`, that relative globs are matched from as well.
+    roots: Vec<PathBuf>,
+    /// [Self::cwd] and [Self::roots] with symlinks resolved, which the canonical path is compared
+    /// with, so that a workspace under a symlink such as `/tmp` on macOS still contains it.
+    canonical_dirs: Vec<PathBuf>,
+}
+
+impl PermissionPath {
+    /// Resolves `path`, where `roots` are the roots registered with `/context add-root`.
+    pub fn new(os: &Os, path: impl AsRef<Path>, roots: &[PathBuf]) -> Self {
+        let raw = path.as_ref().to_path_buf();
+        let cwd = os.env.current_dir().unwrap_or_default();
+        let root = os.fs.chroot_path("/");
+
+        // Resolve `..` before applying the chroot of test file systems, so it cannot be escaped.
+        let sanitized = unchroot_path(&root, &sanitize_path_tool_arg(os, &raw));
+        let absolute = normalize_path(&cwd.join(sanitized));
+        let canonical_root = std::fs::canonicalize(&root).unwrap_or(root.clone());
+        let canonicalize = |path: &Path| {
+            canonicalize_existing_prefix(&os.fs.chroot_path(path))
+                .map(|canonical| unchroot_path(&canonical_root, &canonical))
+        };
+        let canonical = canonicalize(&absolute).filter(|canonical| *canonical != absolute);
+        let roots = roots.to_vec();
+        let canonical_dirs = std::iter::once(&cwd)
+            .chain(&roots)
+            .map(|dir| canonicalize(dir).unwrap_or(dir.clone()))
+            .collect();
+
+        Self {
+            raw,
+            absolute,
+            canonical,
+            cwd,
+            roots,
+            canonical_dirs,
+        }
+    }
+
+    /// The path with symlinks resolved.
+    pub fn resolved(&self) -> &Path {
+        self.canonical.as_deref().unwrap_or(&self.absolute)
+    }
+
+    /// Whether the path is inside of `dir`, both before and after resolving symlinks.
+    pub fn is_within(&self, dir: &PermissionPath) -> bool {
+        self.absolute.starts_with(&dir.absolute) && self.resolved().starts_with(dir.resolved())
+    }
+
+    /// Whether the path appears to be in the workspace, the cwd or a registered root, but resolves
+    /// outside of it through a symlink.
+    pub fn is_symlink_escape(&self) -> bool {
+        let in_workspace = std::iter::once(&self.cwd)
+            .chain(&self.roots)
+            .any(|dir| self.absolute.starts_with(dir));
+        in_workspace
+            && self
+                .canonical
+                .as_ref()
+                .is_some_and(|canonical| !self.canonical_dirs.iter().any(|dir| canonical.starts_with(dir)))
+    }
+
+    /// Whether any form of the path is matched by `globs`: the raw argument, or the absolute or
+    /// cwd-relative form of either the lexically resolved or the canonical path.
+    pub fn any_match(&self, globs: &globset::GlobSet) -> bool {
+        globs.is_match(&self.raw)
+            || self.forms(&self.absolute).iter().any(|p| globs.is_match(p))
+            || self
+                .canonical
+                .as_ref()
+                .is_some_and(|canonical| self.forms(canonical).iter().any(|p| globs.is_match(p)))
+    }
+
+    /// Whether both the lexically resolved and the canonical path are matched by `globs`, in
+    /// either their absolute or cwd-relative form. The raw argument is never matched, since it
+    /// may contain `..` components.
+    pub fn all_match(&self, globs: &globset::GlobSet) -> bool {
+        self.forms(&self.absolute).iter().any(|p| globs.is_match(p))
+            && self
+                .canonical
+                .as_ref()
+                .is_none_or(|canonical| self.forms(canonical).iter().any(|p| globs.is_match(p)))
+    }
+
+    /// The absolute form of `path`, and its relative form for the cwd and every registered root
+    /// that contains it.
+    fn forms(&self, path: &Path) -> Vec<PathBuf> {
+        let mut forms = vec![path.to_path_buf()];
+        for base_dir in std::iter::once(&self.cwd)
+            .chain(&self.roots)
+            .chain(&self.canonical_dirs)
+        {
+            if let Ok(relative) = path.strip_prefix(base_dir) {
+                if !relative.as_os_str().is_empty() {
+                    forms.push(relative.to_path_buf());
+                }
+            }
+        }
+        forms
+    }
+}
+
+/// Expands a leading `~` and strips a leading `./` of a path glob from tool settings, so that it
+/// can be matched against a [PermissionPath].
+pub fn sanitize_path_glob(os: &Os, glob: &str) -> String {
+    if let Some(rest) = glob.strip_prefix("~/") {
+        let home = os.env.home().unwrap_or_default();
+        return format!("{}/{rest}", home.to_string_lossy().trim_end_matches('/'));
+    }
+    glob.strip_prefix("./").unwrap_or(glob).to_string()
+}
+
+/// Resolves `.` and `..` components of `path` without accessing the file system.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir => {
+                res.pop();
+            },
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Resolves symlinks in the longest prefix of `path` that exists, since the rest of it may not
+/// have been created yet.
+fn canonicalize_existing_prefix(path: &Path) -> Option<PathBuf> {
+    let existing = path.ancestors().find(|p| std::fs::symlink_metadata(p).is_ok())?;
+    let rest = path.strip_prefix(existing).ok()?;
+    Some(std::fs::canonicalize(existing).ok()?.join(rest))
+}
+
+/// Converts a path of a chroot test file system back to the path seen by the model.
+fn unchroot_path(root: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(root) {
+        Ok(relative) => Path::new("/").join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
 /// Converts `path` to a relative path according to the current working directory `cwd`.
 fn absolute_to_relative(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<PathBuf> {
     let cwd = cwd.as_ref().canonicalize()?;
@@ -325,7 +693,18 @@ fn absolute_to_relative(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> Result
 }
 
 /// Small helper for formatting the path as a relative path, if able.
-fn format_path(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> String {
+///
+/// Paths outside of `cwd` that are inside of one of the `roots` registered with `/context add-root`
+/// are formatted relative to that root, prefixed with its name, e.g. `other-repo:src/lib.rs`.
+fn format_path(cwd: impl AsRef<Path>, roots: &[PathBuf], path: impl AsRef<Path>) -> String {
+    if !path.as_ref().starts_with(cwd.as_ref()) {
+        for root in roots {
+            if let Ok(relative) = path.as_ref().strip_prefix(root) {
+                let name = root.file_name().unwrap_or(root.as_os_str()).to_string_lossy();
+                return format!("{name}:{}", relative.to_string_lossy());
+            }
+        }
+    }
     absolute_to_relative(cwd, path.as_ref())
         .map(|p| p.to_string_lossy().to_string())
         // If we have three consecutive ".." then it should probably just stay as an absolute path.
@@ -340,7 +719,7 @@ fn format_path(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> String {
         .unwrap_or(path.as_ref().to_string_lossy().to_string())
 }
 
-fn supports_truecolor(os: &Os) -> bool {
+pub fn supports_truecolor(os: &Os) -> bool {
     // Simple override to disable truecolor since shell_color doesn't use Context.
     !os.env.get("Q_DISABLE_TRUECOLOR").is_ok_and(|s| !s.is_empty())
         && shell_color::get_color_support().contains(shell_color::ColorSupport::TERM24BIT)
@@ -446,7 +825,7 @@ mod tests {
             fs.create_dir_all(&cwd).await.unwrap();
             fs.create_dir_all(&path).await.unwrap();
 
-            let formatted = format_path(&cwd, &path);
+            let formatted = format_path(&cwd, &[], &path);
 
             if Path::new(expected).is_absolute() {
                 // If the expected path is relative, we need to ensure it is relative to the cwd.
@@ -480,5 +859,88 @@ mod tests {
             format!("{ACTIVE_USER_HOME}{MAIN_SEPARATOR}other").as_str(),
         )
         .await;
+
+        // Paths in a registered root are named relative to it
+        assert_eq!(
+            format_path(
+                "/workspace",
+                &[PathBuf::from("/other-project")],
+                "/other-project/src/lib.rs"
+            ),
+            format!("other-project:src{MAIN_SEPARATOR}lib.rs")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_outside_workspace() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/workspace/src").await.unwrap();
+        os.fs.create_dir_all("/elsewhere").await.unwrap();
+        os.fs.create_dir_all("/other-project").await.unwrap();
+        os.fs.symlink("/elsewhere", "/workspace/link").await.unwrap();
+
+        let mut agent = Agent {
+            workspace_root: Some("/workspace".to_string()),
+            ..Default::default()
+        };
+        let tool = |name: &str, args: serde_json::Value| -> Tool {
+            match name {
+                "fs_write" => Tool::FsWrite(serde_json::from_value(args).unwrap()),
+                "fs_read" => Tool::FsRead(serde_json::from_value(args).unwrap()),
+                _ => Tool::ExecuteCommand(serde_json::from_value(args).unwrap()),
+            }
+        };
+        let write = |path: &str| tool("fs_write", serde_json::json!({ "command": "create", "path": path }));
+
+        // Not enforced unless strict
+        assert_eq!(write("/etc/passwd").outside_workspace(&os, &agent, &[]), None);
+        agent.strict_workspace = true;
+
+        assert_eq!(
+            write("/workspace/src/main.rs").outside_workspace(&os, &agent, &[]),
+            None
+        );
+        assert_eq!(
+            write("/workspace/../etc/passwd").outside_workspace(&os, &agent, &[]),
+            Some(PathBuf::from("/etc/passwd"))
+        );
+        assert!(
+            write("/workspace/link/file")
+                .outside_workspace(&os, &agent, &[])
+                .is_some()
+        );
+        let roots = [PathBuf::from("/other-project")];
+        assert!(
+            write("/other-project/main.rs")
+                .outside_workspace(&os, &agent, &[])
+                .is_some()
+        );
+        assert_eq!(
+            write("/other-project/main.rs").outside_workspace(&os, &agent, &roots),
+            None
+        );
+
+        let read = tool(
+            "fs_read",
+            serde_json::json!({ "operations": [
+                { "mode": "Line", "path": "/workspace/src/main.rs" },
+                { "mode": "Directory", "path": "/home" },
+            ]}),
+        );
+        assert_eq!(read.outside_workspace(&os, &agent, &[]), Some(PathBuf::from("/home")));
+
+        let exec = |command: &str| tool("execute_bash", serde_json::json!({ "command": command }));
+        assert_eq!(
+            exec("cargo build --manifest-path=/workspace/Cargo.toml").outside_workspace(&os, &agent, &[]),
+            None
+        );
+        assert_eq!(
+            exec("curl https://example.com/a/b").outside_workspace(&os, &agent, &[]),
+            None
+        );
+        assert_eq!(
+            exec("rm -rf --no-preserve-root /").outside_workspace(&os, &agent, &[]),
+            Some(PathBuf::from("/"))
+        );
     }
 }
@@ -0,0 +1,284 @@
+//! Formatting and linting of the files written by `fs_write`, configured with `postWrite` in the
+//! `fs_write` tool settings of an agent.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use bstr::ByteSlice;
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use serde::Deserialize;
+use tracing::{
+    debug,
+    error,
+};
+
+use crate::cli::agent::Agent;
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+/// Characters of lint diagnostics returned to the model.
+const MAX_DIAGNOSTICS_LEN: usize = 4000;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostWriteSettings {
+    /// Whether to format written files.
+    #[serde(default)]
+    pub format: bool,
+    /// Whether to lint written files.
+    #[serde(default)]
+    pub lint: bool,
+    /// Formatter commands by file extension, overriding the defaults. `{file}` is replaced with the
+    /// path of the file, which is appended to the command otherwise.
+    #[serde(default)]
+    pub formatters: HashMap<String, String>,
+    /// Linter commands by file extension, overriding the defaults.
+    #[serde(default)]
+    pub linters: HashMap<String, String>,
+}
+
+impl PostWriteSettings {
+    /// Returns the settings configured for `agent`, if it formats or lints written files.
+    pub fn from_agent(agent: &Agent) -> Option<Self> {
+        let settings = agent.tools_settings.get("fs_write")?.get("postWrite")?;
+        match serde_json::from_value::<Self>(settings.clone()) {
+            Ok(settings) if settings.format || settings.lint => Some(settings),
+            Ok(_) => None,
+            Err(e) => {
+                error!(
+                    "Failed to deserialize the postWrite tool settings for fs_write: {:?}",
+                    e
+                );
+                None
+            },
+        }
+    }
+
+    fn formatter(&self, extension: &str) -> Option<&str> {
+        if !self.format {
+            return None;
+        }
+        self.formatters
+            .get(extension)
+            .map(String::as_str)
+            .or(match extension {
+                "rs" => Some("rustfmt --edition 2021 {file}"),
+                "py" => Some("black --quiet {file}"),
+                "go" => Some("gofmt -w {file}"),
+                "js" | "jsx" | "ts" | "tsx" | "json" | "css" | "scss" | "html" | "md" | "yaml" | "yml" => {
+                    Some("npx --no-install prettier --write --log-level warn {file}")
+                },
+                _ => None,
+            })
+            .filter(|command| !command.is_empty())
+    }
+
+    fn linter(&self, extension: &str) -> Option<&str> {
+        if !self.lint {
+            return None;
+        }
+        self.linters
+            .get(extension)
+            .map(String::as_str)
+            .or(match extension {
+                "py" => Some("ruff check --quiet --output-format concise {file}"),
+                "js" | "jsx" | "ts" | "tsx" => Some("npx --no-install eslint --format unix {file}"),
+                "sh" | "bash" => Some("shellcheck --format gcc {file}"),
+                _ => None,
+            })
+            .filter(|command| !command.is_empty())
+    }
+}
+
+/// Formats and lints the file at `path` according to `settings`, printing what was done to
+/// `output`. Returns a note for the model, if the file was formatted or has lint diagnostics.
+///
+/// Formatters and linters that are not installed or fail are skipped, since they are a best effort.
+pub async fn run(
+    os: &Os,
+    settings: &PostWriteSettings,
+    path: &Path,
+    output: &mut impl Write,
+) -> Result<Option<String>> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let cwd = os.env.current_dir()?;
+    let real_path = os.fs.chroot_path(path);
+    let mut note = String::new();
+
+    if let Some(formatter) = settings.formatter(&extension) {
+        let before = os.fs.read(path).await?;
+        match run_command(formatter, &real_path, &cwd).await {
+            Ok((true, _)) => {
+                if os.fs.read(path).await? != before {
+                    queue!(
+                        output,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("Formatted with {}\n", program(formatter))),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    let _ = writeln!(
+                        note,
+                        "The file was formatted with {}, read it again before editing it.",
+                        program(formatter)
+                    );
+                }
+            },
+            Ok((false, stderr)) => debug!("formatter {formatter} failed: {stderr}"),
+            Err(err) => debug!(?err, "failed to run the formatter {formatter}"),
+        }
+    }
+
+    if let Some(linter) = settings.linter(&extension) {
+        match run_command(linter, &real_path, &cwd).await {
+            Ok((false, mut diagnostics)) if !diagnostics.trim().is_empty() => {
+                let count = diagnostics.trim().lines().count();
+                queue!(
+                    output,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print(format!(
+                        "{} reported {count} line{} of diagnostics\n",
+                        program(linter),
+                        if count == 1 { "" } else { "s" }
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                truncate_safe_in_place(&mut diagnostics, MAX_DIAGNOSTICS_LEN, "\n... truncated");
+                let _ = writeln!(
+                    note,
+                    "{} reported diagnostics for the file, fix them:\n{}",
+                    program(linter),
+                    diagnostics.trim()
+                );
+            },
+            Ok(_) => {},
+            Err(err) => debug!(?err, "failed to run the linter {linter}"),
+        }
+    }
+
+    Ok((!note.is_empty()).then_some(note))
+}
+
+/// Name of the program run by `command`, skipping runners such as npx.
+fn program(command: &str) -> &str {
+    command
+        .split_whitespace()
+        .find(|word| !matches!(*word, "npx" | "--no-install" | "uvx" | "poetry" | "run"))
+        .unwrap_or(command)
+}
+
+/// Returns the arguments of `command` with `{file}` replaced with `path`, or `path` appended.
+fn command_args(command: &str, path: &Path) -> Result<Vec<String>> {
+    let Some(mut args) = shlex::split(command).filter(|args| !args.is_empty()) else {
+        bail!("Invalid command: {command}");
+    };
+    let path = path.to_string_lossy();
+    if args.iter().any(|arg| arg.contains("{file}")) {
+        for arg in &mut args {
+            *arg = arg.replace("{file}", &path);
+        }
+    } else {
+        args.push(path.to_string());
+    }
+    Ok(args)
+}
+
+/// Runs `command` for the file at `path`, returning whether it succeeded along with its output.
+async fn run_command(command: &str, path: &Path, cwd: &Path) -> Result<(bool, String)> {
+    let args = command_args(command, path)?;
+    let child = tokio::process::Command::new(&args[0])
+        .args(&args[1..])
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let Ok(output) = tokio::time::timeout(TIMEOUT, child.wait_with_output()).await else {
+        bail!("{command} did not finish within {} seconds", TIMEOUT.as_secs());
+    };
+    let output = output?;
+    let text = format!("{}{}", output.stdout.to_str_lossy(), output.stderr.to_str_lossy());
+    Ok((output.status.success(), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        let agent = Agent {
+            tools_settings: serde_json::from_value(serde_json::json!({
+                "fs_write": {
+                    "allowedPaths": ["src/**"],
+                    "postWrite": { "format": true, "formatters": { "py": "ruff format", "md": "" } }
+                }
+            }))
+            .unwrap(),
+            ..Default::default()
+        };
+        let settings = PostWriteSettings::from_agent(&agent).unwrap();
+        assert_eq!(settings.formatter("py"), Some("ruff format"));
+        assert_eq!(settings.formatter("rs"), Some("rustfmt --edition 2021 {file}"));
+        assert_eq!(settings.formatter("md"), None);
+        assert_eq!(settings.linter("py"), None);
+        assert!(PostWriteSettings::from_agent(&Agent::default()).is_none());
+
+        assert_eq!(command_args("ruff format", Path::new("a b.py")).unwrap(), vec![
+            "ruff", "format", "a b.py"
+        ]);
+        assert_eq!(command_args("fmt --file={file} -q", Path::new("x.rs")).unwrap(), vec![
+            "fmt",
+            "--file=x.rs",
+            "-q"
+        ]);
+        assert_eq!(program("npx --no-install prettier --write {file}"), "prettier");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run() {
+        let os = Os::new().await.unwrap();
+        let path = Path::new("/script.txt");
+        os.fs.write(path, "hello  world\n").await.unwrap();
+
+        let settings = PostWriteSettings {
+            format: true,
+            lint: true,
+            formatters: HashMap::from([("txt".to_string(), "sed -i.bak s/hello/Hello/ {file}".to_string())]),
+            linters: HashMap::from([(
+                "txt".to_string(),
+                "sh -c 'echo \"$0:1:6: double space\"; exit 1'".to_string(),
+            )]),
+        };
+        let mut output = Vec::new();
+        let note = run(&os, &settings, path, &mut output).await.unwrap().unwrap();
+        assert_eq!(os.fs.read_to_string(path).await.unwrap(), "Hello  world\n");
+        assert!(note.contains("formatted with sed"));
+        assert!(note.contains("sh reported diagnostics for the file, fix them:\n"));
+        assert!(note.contains(":1:6: double space"));
+
+        // Nothing is reported when the file is already formatted and has no diagnostics.
+        let settings = PostWriteSettings {
+            linters: HashMap::from([("txt".to_string(), "true".to_string())]),
+            ..settings
+        };
+        assert_eq!(run(&os, &settings, path, &mut output).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::Write;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::Result;
+use serde::Deserialize;
+
+use super::execute::{
+    BackgroundProcesses,
+    OutputBuffer,
+};
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+
+/// Tools for managing the processes launched in the background by execute_bash. Each variant is
+/// exposed to the model as a separate tool: `process_list`, `process_output`, and `process_kill`.
+#[derive(Debug, Clone)]
+pub enum Process {
+    List,
+    Output(ProcessOutput),
+    Kill(ProcessKill),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessOutput {
+    pub process_id: u32,
+    pub tail: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessKill {
+    pub process_id: u32,
+}
+
+impl Process {
+    pub fn tool_name(&self) -> &'static str {
+        match self {
+            Process::List => "process_list",
+            Process::Output(_) => "process_output",
+            Process::Kill(_) => "process_kill",
+        }
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        let (action, id) = match self {
+            Process::List => ("Listing background processes", None),
+            Process::Output(ProcessOutput { process_id, .. }) => {
+                ("Reading output of background process", Some(process_id))
+            },
+            Process::Kill(ProcessKill { process_id }) => ("Stopping background process", Some(process_id)),
+        };
+        queue!(output, style::Print(action))?;
+        if let Some(id) = id {
+            queue!(
+                output,
+                style::Print(" "),
+                style::SetForegroundColor(Color::Green),
+                style::Print(id),
+                style::ResetColor,
+            )?;
+        }
+        queue!(output, style::Print("\n"))?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, _updates: impl Write) -> Result<InvokeOutput> {
+        let result = match self {
+            Process::List => serde_json::to_value(BackgroundProcesses::list())?,
+            Process::Output(ProcessOutput { process_id, tail }) => {
+                let (info, output) = BackgroundProcesses::read_output(*process_id, *tail)?;
+                let mut result = serde_json::to_value(info)?;
+                result["output"] = OutputBuffer::from_output(&output, MAX_TOOL_RESPONSE_SIZE / 3).into();
+                result
+            },
+            Process::Kill(ProcessKill { process_id }) => serde_json::to_value(BackgroundProcesses::kill(*process_id)?)?,
+        };
+        Ok(InvokeOutput {
+            output: OutputKind::Json(result),
+        })
+    }
+
+    pub async fn validate(&mut self, _os: &crate::os::Os) -> Result<()> {
+        Ok(())
+    }
+}
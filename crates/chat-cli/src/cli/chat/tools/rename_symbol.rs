@@ -0,0 +1,621 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use globset::{
+    Glob,
+    GlobSet,
+    GlobSetBuilder,
+};
+use serde::Deserialize;
+
+use super::fs_write::diff_hunks;
+use super::{
+    InvokeOutput,
+    OutputKind,
+    format_path,
+    sanitize_path_tool_arg,
+};
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::auto_context::{
+    MAX_FILE_SIZE,
+    workspace_files,
+};
+use crate::os::Os;
+
+/// Files whose diffs are shown before approval, beyond which only their number is.
+const MAX_PREVIEW_FILES: usize = 20;
+
+/// The rename_symbol tool renames an identifier across the files of a directory in one change,
+/// leaving alone the text that merely contains it and, by default, comments and strings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenameSymbol {
+    pub old_name: String,
+    pub new_name: String,
+    /// File or directory to rename in, the current directory by default.
+    pub path: Option<String>,
+    /// Globs of the files to rename in, relative to [Self::path]. Files of languages that are not
+    /// recognized are only renamed in when they match one of these.
+    pub include: Option<Vec<String>>,
+    #[serde(default)]
+    pub include_comments_and_strings: bool,
+    pub summary: Option<String>,
+    /// Roots registered with `/context add-root`, that the renamed files in one of them are named
+    /// relative to.
+    #[serde(skip)]
+    pub roots: Vec<PathBuf>,
+}
+
+/// The renaming of one file.
+#[derive(Debug)]
+struct FileRename {
+    path: PathBuf,
+    old: String,
+    new: String,
+    renamed: usize,
+    skipped: usize,
+}
+
+impl RenameSymbol {
+    pub fn eval_perm(agent: &Agent) -> PermissionEvalResult {
+        if agent.allowed_tools.contains("rename_symbol") {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        self.path.as_deref().unwrap_or(".")
+    }
+
+    pub fn queue_description(&self, os: &Os, output: &mut impl Write) -> Result<()> {
+        let cwd = os.env.current_dir()?;
+        let renames = self.renames(os)?;
+        let renamed = renames.iter().map(|rename| rename.renamed).sum::<usize>();
+        queue!(
+            output,
+            style::Print("Renaming "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(&self.old_name),
+            style::ResetColor,
+            style::Print(" to "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(&self.new_name),
+            style::ResetColor,
+            style::Print(format!(
+                " ({renamed} occurrences in {} files under {}):\n",
+                renames.len(),
+                format_path(&cwd, &self.roots, sanitize_path_tool_arg(os, self.path()))
+            )),
+        )?;
+        for rename in renames.iter().take(MAX_PREVIEW_FILES) {
+            queue!(
+                output,
+                style::Print("\n"),
+                style::SetForegroundColor(Color::Green),
+                style::Print(format_path(&cwd, &self.roots, &rename.path)),
+                style::ResetColor,
+                style::Print("\n"),
+            )?;
+            for hunk in diff_hunks(&rename.old, &rename.new) {
+                hunk.queue(output)?;
+            }
+        }
+        if renames.len() > MAX_PREVIEW_FILES {
+            queue!(
+                output,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("\n... and {} more files\n", renames.len() - MAX_PREVIEW_FILES)),
+                style::ResetColor,
+            )?;
+        }
+        let skipped = renames.iter().map(|rename| rename.skipped).sum::<usize>();
+        if skipped > 0 {
+            queue!(
+                output,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!(
+                    "\n{skipped} occurrences in comments and strings are left as they are\n"
+                )),
+                style::ResetColor,
+            )?;
+        }
+        queue!(output, style::Print("\n"))?;
+
+        super::display_purpose(self.summary.as_ref(), output)?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let cwd = os.env.current_dir()?;
+        let renames = self.renames(os)?;
+        if renames.is_empty() {
+            bail!(
+                "No identifier named {} found under {}",
+                self.old_name,
+                format_path(&cwd, &self.roots, sanitize_path_tool_arg(os, self.path()))
+            );
+        }
+        // Every file is renamed in before any is written, so that a file that can not be read does
+        // not leave the rename half done.
+        for rename in &renames {
+            os.fs.write(&rename.path, &rename.new).await?;
+        }
+
+        let renamed = renames.iter().map(|rename| rename.renamed).sum::<usize>();
+        let mut text = format!(
+            "Renamed {} to {} ({renamed} occurrences in {} files):\n",
+            self.old_name,
+            self.new_name,
+            renames.len()
+        );
+        for rename in &renames {
+            let _ = writeln!(
+                text,
+                "{} ({})",
+                format_path(&cwd, &self.roots, &rename.path),
+                rename.renamed
+            );
+        }
+        let skipped = renames.iter().map(|rename| rename.skipped).sum::<usize>();
+        if skipped > 0 {
+            let _ = writeln!(
+                text,
+                "{skipped} occurrences in comments and strings were left as they are. Set include_comments_and_strings to rename them too."
+            );
+        }
+        Ok(InvokeOutput {
+            output: OutputKind::Text(text),
+        })
+    }
+
+    /// Returns the paths of the files that the rename writes.
+    pub fn written_paths(&self, os: &Os) -> Vec<PathBuf> {
+        self.renames(os)
+            .map(|renames| renames.into_iter().map(|rename| rename.path).collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        for name in [&self.old_name, &self.new_name] {
+            if !is_identifier(name) {
+                bail!("{name:?} is not an identifier");
+            }
+        }
+        if self.old_name == self.new_name {
+            bail!("The new name must be different from the old name");
+        }
+        let path = sanitize_path_tool_arg(os, self.path());
+        if !os.fs.exists(&path) {
+            bail!("'{}' does not exist", path.display());
+        }
+        self.include_globs()?;
+        Ok(())
+    }
+
+    fn include_globs(&self) -> Result<Option<GlobSet>> {
+        let Some(include) = &self.include else {
+            return Ok(None);
+        };
+        let mut builder = GlobSetBuilder::new();
+        for glob in include {
+            builder.add(Glob::new(glob)?);
+        }
+        Ok(Some(builder.build()?))
+    }
+
+    /// Returns the renaming of every file that contains the old name as an identifier.
+    fn renames(&self, os: &Os) -> Result<Vec<FileRename>> {
+        let root = sanitize_path_tool_arg(os, self.path());
+        let include = self.include_globs()?;
+        let files: Vec<PathBuf> = match root.is_file() {
+            true => vec![root.clone()],
+            false => workspace_files(&root).map(|entry| entry.into_path()).collect(),
+        };
+
+        let mut renames = Vec::new();
+        for path_on_disk in files {
+            let relative = path_on_disk.strip_prefix(&root).unwrap_or(&path_on_disk);
+            let syntax = match (&include, Syntax::of(&path_on_disk)) {
+                (Some(include), syntax) if relative.as_os_str().is_empty() || include.is_match(relative) => {
+                    syntax.unwrap_or(Syntax::PLAIN)
+                },
+                (Some(_), _) => continue,
+                (None, Some(syntax)) => syntax,
+                (None, None) if relative.as_os_str().is_empty() => Syntax::PLAIN,
+                (None, None) => continue,
+            };
+            if path_on_disk
+                .metadata()
+                .is_ok_and(|metadata| metadata.len() > MAX_FILE_SIZE)
+            {
+                continue;
+            }
+            let Ok(old) = std::fs::read_to_string(&path_on_disk) else {
+                continue;
+            };
+            let occurrences = find_identifier(&old, &self.old_name, &syntax);
+            let (renamed, skipped): (Vec<_>, Vec<_>) = occurrences
+                .into_iter()
+                .partition(|occurrence| occurrence.in_code || self.include_comments_and_strings);
+            if renamed.is_empty() {
+                continue;
+            }
+            let mut new = String::with_capacity(old.len());
+            let mut end = 0;
+            for occurrence in &renamed {
+                new.push_str(&old[end..occurrence.start]);
+                new.push_str(&self.new_name);
+                end = occurrence.start + self.old_name.len();
+            }
+            new.push_str(&old[end..]);
+            renames.push(FileRename {
+                path: path_on_disk.clone(),
+                old,
+                new,
+                renamed: renamed.len(),
+                skipped: skipped.len(),
+            });
+        }
+        renames.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(renames)
+    }
+}
+
+/// Whether `name` can be renamed: a word of letters, digits and the characters that identifiers of
+/// some languages have, such as `$` in JavaScript and `-` in CSS.
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_alphanumeric() || "_$-".contains(c))
+}
+
+/// What of the syntax of a language tells identifiers apart from comments and strings.
+#[derive(Debug, Clone, Copy)]
+struct Syntax {
+    /// Characters other than letters, digits and `_` that identifiers can have.
+    identifier_chars: &'static str,
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    quotes: &'static [char],
+    /// Whether `'` starts character literals, but not strings, as with lifetimes in Rust.
+    char_literals: bool,
+}
+
+impl Syntax {
+    /// Text without comments nor strings.
+    const PLAIN: Self = Self {
+        identifier_chars: "",
+        line_comments: &[],
+        block_comments: &[],
+        quotes: &[],
+        char_literals: false,
+    };
+
+    /// Returns the syntax of the language of the file at `path`, going by its extension.
+    fn of(path: &Path) -> Option<Self> {
+        const C_COMMENTS: &[(&str, &str)] = &[("/*", "*/")];
+        let c_like = Self {
+            identifier_chars: "",
+            line_comments: &["//"],
+            block_comments: C_COMMENTS,
+            quotes: &['"', '\''],
+            char_literals: false,
+        };
+        let hash_comments = Self {
+            line_comments: &["#"],
+            block_comments: &[],
+            ..c_like
+        };
+        Some(match path.extension()?.to_str()? {
+            "rs" => Self {
+                quotes: &['"'],
+                char_literals: true,
+                ..c_like
+            },
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "java" | "kt" | "kts" | "cs" | "swift" | "scala" | "dart" => {
+                c_like
+            },
+            "go" => Self {
+                quotes: &['"', '\'', '`'],
+                ..c_like
+            },
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => Self {
+                identifier_chars: "$",
+                quotes: &['"', '\'', '`'],
+                ..c_like
+            },
+            "php" => Self {
+                line_comments: &["//", "#"],
+                ..c_like
+            },
+            "py" | "pyi" | "rb" | "sh" | "bash" | "zsh" | "pl" | "r" => hash_comments,
+            "lua" | "sql" => Self {
+                line_comments: &["--"],
+                block_comments: &[],
+                ..c_like
+            },
+            "css" => Self {
+                identifier_chars: "-",
+                line_comments: &[],
+                ..c_like
+            },
+            "scss" | "less" => Self {
+                identifier_chars: "-",
+                ..c_like
+            },
+            _ => return None,
+        })
+    }
+
+    fn is_identifier_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || self.identifier_chars.contains(c)
+    }
+}
+
+/// An occurrence of an identifier.
+#[derive(Debug, PartialEq, Eq)]
+struct Occurrence {
+    /// Byte offset of the occurrence.
+    start: usize,
+    /// Whether the occurrence is in code rather than in a comment or string.
+    in_code: bool,
+}
+
+/// Returns the occurrences of `name` in `content` as a whole identifier, in order.
+fn find_identifier(content: &str, name: &str, syntax: &Syntax) -> Vec<Occurrence> {
+    enum State {
+        Code,
+        LineComment,
+        BlockComment(&'static str),
+        String(char),
+    }
+
+    let mut occurrences = Vec::new();
+    let mut state = State::Code;
+    let mut i = 0;
+    while let Some(c) = content[i..].chars().next() {
+        let rest = &content[i..];
+        match state {
+            State::Code => {
+                if let Some(start) = syntax.line_comments.iter().find(|start| rest.starts_with(**start)) {
+                    state = State::LineComment;
+                    i += start.len();
+                    continue;
+                }
+                if let Some((start, end)) = syntax.block_comments.iter().find(|(start, _)| rest.starts_with(start)) {
+                    state = State::BlockComment(end);
+                    i += start.len();
+                    continue;
+                }
+                if syntax.quotes.contains(&c) {
+                    state = State::String(c);
+                    i += 1;
+                    continue;
+                }
+                if c == '\'' && syntax.char_literals {
+                    if let Some(len) = char_literal_len(rest) {
+                        i += len;
+                        continue;
+                    }
+                }
+            },
+            State::LineComment if c == '\n' => state = State::Code,
+            State::BlockComment(end) if rest.starts_with(end) => {
+                state = State::Code;
+                i += end.len();
+                continue;
+            },
+            State::String(_) if c == '\\' => {
+                i += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+                continue;
+            },
+            State::String(quote) if c == quote => {
+                state = State::Code;
+                i += 1;
+                continue;
+            },
+            _ => (),
+        }
+        if rest.starts_with(name)
+            && !content[..i]
+                .chars()
+                .next_back()
+                .is_some_and(|c| syntax.is_identifier_char(c))
+            && !rest[name.len()..]
+                .chars()
+                .next()
+                .is_some_and(|c| syntax.is_identifier_char(c))
+        {
+            occurrences.push(Occurrence {
+                start: i,
+                in_code: matches!(state, State::Code),
+            });
+            i += name.len();
+            continue;
+        }
+        i += c.len_utf8();
+    }
+    occurrences
+}
+
+/// Returns the length of the character literal `rest` starts with, such as `'a'` or `'\n'`, or
+/// `None` if the `'` it starts with is not one, as with a lifetime.
+fn char_literal_len(rest: &str) -> Option<usize> {
+    let mut chars = rest.char_indices().skip(1);
+    match chars.next()? {
+        (_, '\\') => chars.take(10).find(|(_, c)| *c == '\'').map(|(i, _)| i + 1),
+        (_, '\'') => None,
+        _ => chars.next().filter(|(_, c)| *c == '\'').map(|(i, _)| i + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renamed(content: &str, name: &str, syntax: &Syntax) -> Vec<(usize, bool)> {
+        find_identifier(content, name, syntax)
+            .into_iter()
+            .map(|occurrence| (occurrence.start, occurrence.in_code))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_identifier() {
+        let rust = Syntax::of(Path::new("lib.rs")).unwrap();
+        let content = "fn load(x: &'a str) -> char { // load it\n    let c = '\"'; loader(load, \"load\", r#load) }";
+        assert_eq!(renamed(content, "load", &rust), vec![
+            (3, true),
+            (33, false),
+            (65, true),
+            (72, false),
+            (81, true)
+        ]);
+
+        // `$` is part of JavaScript identifiers, but not of Rust ones.
+        let js = Syntax::of(Path::new("app.ts")).unwrap();
+        assert_eq!(renamed("$el = el; `${el}` /* el */", "el", &js), vec![
+            (6, true),
+            (13, false),
+            (21, false)
+        ]);
+        assert_eq!(renamed("$el", "el", &rust), vec![(1, true)]);
+
+        let python = Syntax::of(Path::new("main.py")).unwrap();
+        assert_eq!(renamed("count = 1 # count\ns = 'count'", "count", &python), vec![
+            (0, true),
+            (12, false),
+            (23, false)
+        ]);
+
+        let css = Syntax::of(Path::new("style.css")).unwrap();
+        assert_eq!(renamed(".btn-primary .btn { }", "btn", &css), vec![(14, true)]);
+
+        assert!(Syntax::of(Path::new("README")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rename_symbol() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/project/src/nested").await.unwrap();
+        os.fs.create_dir_all("/project/node_modules/dep").await.unwrap();
+        os.fs
+            .write(
+                "/project/src/main.rs",
+                "// Calls parse_config.\nfn main() {\n    parse_config(\"parse_config\");\n}\n",
+            )
+            .await
+            .unwrap();
+        os.fs
+            .write(
+                "/project/src/nested/config.rs",
+                "pub fn parse_config() {}\npub fn parse_config_file() {}\n",
+            )
+            .await
+            .unwrap();
+        os.fs
+            .write("/project/node_modules/dep/index.js", "parse_config();\n")
+            .await
+            .unwrap();
+        os.fs.write("/project/notes.md", "parse_config\n").await.unwrap();
+
+        let mut tool = serde_json::from_value::<RenameSymbol>(serde_json::json!({
+            "old_name": "parse_config",
+            "new_name": "load_config",
+            "path": "/project",
+        }))
+        .unwrap();
+        tool.validate(&os).await.unwrap();
+        let mut preview = Vec::new();
+        tool.queue_description(&os, &mut preview).unwrap();
+        let preview = String::from_utf8(preview).unwrap();
+        assert!(preview.contains("2 occurrences in 2 files"), "{preview}");
+        assert_eq!(tool.written_paths(&os), vec![
+            os.fs.chroot_path("/project/src/main.rs"),
+            os.fs.chroot_path("/project/src/nested/config.rs"),
+        ]);
+
+        let output = tool.invoke(&os, std::io::stdout()).await.unwrap();
+        let OutputKind::Text(text) = output.output else {
+            panic!("expected text output");
+        };
+        assert!(text.contains("2 occurrences in comments and strings"), "{text}");
+        assert_eq!(
+            os.fs.read_to_string("/project/src/main.rs").await.unwrap(),
+            "// Calls parse_config.\nfn main() {\n    load_config(\"parse_config\");\n}\n"
+        );
+        assert_eq!(
+            os.fs.read_to_string("/project/src/nested/config.rs").await.unwrap(),
+            "pub fn load_config() {}\npub fn parse_config_file() {}\n"
+        );
+        // Dependencies and files of unknown languages are left alone.
+        assert_eq!(
+            os.fs
+                .read_to_string("/project/node_modules/dep/index.js")
+                .await
+                .unwrap(),
+            "parse_config();\n"
+        );
+        assert_eq!(
+            os.fs.read_to_string("/project/notes.md").await.unwrap(),
+            "parse_config\n"
+        );
+
+        // Other files are renamed in when included, and comments and strings when asked for.
+        let tool = serde_json::from_value::<RenameSymbol>(serde_json::json!({
+            "old_name": "parse_config",
+            "new_name": "load_config",
+            "path": "/project",
+            "include": ["**/*.md", "src/*.rs"],
+            "include_comments_and_strings": true,
+        }))
+        .unwrap();
+        tool.invoke(&os, std::io::stdout()).await.unwrap();
+        assert_eq!(
+            os.fs.read_to_string("/project/notes.md").await.unwrap(),
+            "load_config\n"
+        );
+        assert_eq!(
+            os.fs.read_to_string("/project/src/main.rs").await.unwrap(),
+            "// Calls load_config.\nfn main() {\n    load_config(\"load_config\");\n}\n"
+        );
+
+        assert!(tool.invoke(&os, std::io::stdout()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let os = Os::new().await.unwrap();
+        for (old_name, new_name, path) in [
+            ("foo", "foo bar", "/"),
+            ("foo", "foo", "/"),
+            ("1foo", "foo", "/"),
+            ("foo", "bar", "/missing"),
+        ] {
+            let mut tool = serde_json::from_value::<RenameSymbol>(serde_json::json!({
+                "old_name": old_name,
+                "new_name": new_name,
+                "path": path,
+            }))
+            .unwrap();
+            assert!(tool.validate(&os).await.is_err(), "{old_name} -> {new_name} in {path}");
+        }
+    }
+}
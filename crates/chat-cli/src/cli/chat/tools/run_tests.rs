@@ -0,0 +1,616 @@
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Stdio;
+use std::time::Duration;
+
+use bstr::ByteSlice;
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+
+use super::{
+    InvokeOutput,
+    OutputKind,
+    sanitize_path_tool_arg,
+};
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+
+const TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Failures returned to the model.
+const MAX_FAILURES: usize = 20;
+/// Characters of the output kept for each failure.
+const MAX_SNIPPET_LEN: usize = 1500;
+/// Lines of output returned when no failure could be parsed from a failed run, e.g. because the
+/// tests did not compile.
+const OUTPUT_TAIL_LINES: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Framework {
+    Cargo,
+    Pytest,
+    Jest,
+}
+
+impl Framework {
+    /// Detects the framework of the project at `dir` from its manifest and config files.
+    fn detect(dir: &Path) -> Option<Self> {
+        if dir.join("Cargo.toml").exists() {
+            return Some(Framework::Cargo);
+        }
+        if let Ok(package) = std::fs::read_to_string(dir.join("package.json")) {
+            if package.contains("jest") {
+                return Some(Framework::Jest);
+            }
+        }
+        ["pytest.ini", "conftest.py", "pyproject.toml", "setup.cfg", "tox.ini"]
+            .iter()
+            .any(|file| dir.join(file).exists())
+            .then_some(Framework::Pytest)
+    }
+
+    fn command(self, filter: Option<&str>, report: &Path) -> Vec<String> {
+        let mut args = match self {
+            Framework::Cargo => vec!["cargo", "test", "--no-fail-fast"],
+            Framework::Pytest => vec!["python", "-m", "pytest", "-q", "-rf", "--tb=short", "--color=no"],
+            Framework::Jest => vec!["npx", "jest", "--json", "--ci"],
+        }
+        .into_iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+        if self == Framework::Jest {
+            args.push(format!("--outputFile={}", report.display()));
+        }
+        match (self, filter) {
+            (Framework::Cargo, Some(filter)) => args.push(filter.to_string()),
+            (Framework::Pytest, Some(filter)) => args.extend(["-k".to_string(), filter.to_string()]),
+            (Framework::Jest, Some(filter)) => args.extend(["-t".to_string(), filter.to_string()]),
+            (_, None) => {},
+        }
+        if self == Framework::Cargo {
+            args.extend(["--".to_string(), "--color=never".to_string()]);
+        }
+        args
+    }
+}
+
+/// The run_tests tool runs the tests of a project and returns their results as structured JSON.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunTests {
+    /// Detected from the project if not given.
+    pub framework: Option<Framework>,
+    /// Only runs the tests whose names match.
+    pub filter: Option<String>,
+    /// Directory of the project, defaults to the current directory.
+    pub directory: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct TestReport {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    failures: Vec<TestFailure>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct TestFailure {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    assertion: String,
+    snippet: String,
+}
+
+impl RunTests {
+    fn directory(&self, os: &Os) -> PathBuf {
+        sanitize_path_tool_arg(os, self.directory.as_deref().unwrap_or("."))
+    }
+
+    /// Running tests runs code of the project, so it needs to be approved unless the agent trusts
+    /// the tool.
+    pub fn eval_perm(agent: &Agent) -> PermissionEvalResult {
+        if agent.allowed_tools.contains("run_tests") {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    pub fn queue_description(&self, os: &Os, output: &mut impl Write) -> Result<()> {
+        let directory = self.directory(os);
+        let framework = self.framework.or_else(|| Framework::detect(&directory));
+        let command = framework
+            .map(|framework| {
+                framework
+                    .command(self.filter.as_deref(), Path::new("report.json"))
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        queue!(
+            output,
+            style::Print("Running tests in "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(directory.display()),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(": {command}\n")),
+        )?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let directory = self.directory(os);
+        let Some(framework) = self.framework.or_else(|| Framework::detect(&directory)) else {
+            bail!("Could not detect the test framework of {}", directory.display());
+        };
+        let report_file = tempfile::Builder::new()
+            .prefix("qchat-tests")
+            .suffix(".json")
+            .tempfile()?;
+        let args = framework.command(self.filter.as_deref(), report_file.path());
+
+        let child = tokio::process::Command::new(&args[0])
+            .args(&args[1..])
+            .current_dir(&directory)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err_with(|| format!("Unable to run {}", args[0]))?;
+        let output = match tokio::time::timeout(TIMEOUT, child.wait_with_output()).await {
+            Ok(output) => output?,
+            Err(_) => bail!("The tests did not finish within {} minutes", TIMEOUT.as_secs() / 60),
+        };
+        let text = format!("{}\n{}", output.stdout.to_str_lossy(), output.stderr.to_str_lossy());
+
+        let mut report = match framework {
+            Framework::Cargo => parse_cargo(&text),
+            Framework::Pytest => parse_pytest(&text),
+            Framework::Jest => {
+                let json = std::fs::read_to_string(report_file.path()).unwrap_or_default();
+                parse_jest(&json, &directory).unwrap_or_default()
+            },
+        };
+        let total_failures = report.failures.len();
+        report.failures.truncate(MAX_FAILURES);
+
+        let mut result = serde_json::json!({
+            "framework": framework,
+            "command": args.join(" "),
+            "exit_status": output.status.code(),
+            "passed": report.passed,
+            "failed": report.failed,
+            "skipped": report.skipped,
+            "failures": report.failures,
+        });
+        if total_failures > MAX_FAILURES {
+            result["omitted_failures"] = Value::from(total_failures - MAX_FAILURES);
+        }
+        if !output.status.success() && total_failures == 0 {
+            result["output_tail"] = Value::from(tail(&text, OUTPUT_TAIL_LINES));
+        }
+        Ok(InvokeOutput {
+            output: OutputKind::Json(result),
+        })
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        let directory = self.directory(os);
+        if !os.fs.exists(&directory) {
+            bail!("The directory {} does not exist", directory.display());
+        }
+        if self.framework.is_none() && Framework::detect(&directory).is_none() {
+            bail!(
+                "Could not detect the test framework of {}, specify one of cargo, pytest, or jest",
+                directory.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn snippet(lines: &[&str]) -> String {
+    let mut snippet = lines.join("\n").trim().to_string();
+    truncate_safe_in_place(&mut snippet, MAX_SNIPPET_LEN, " ... truncated");
+    snippet
+}
+
+fn tail(text: &str, lines: usize) -> String {
+    let all = text.trim_end().lines().collect::<Vec<_>>();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+/// Parses `file:line` or `file:line:column` at the end of `location`.
+fn parse_location(location: &str) -> Option<(String, u32)> {
+    let mut parts = location
+        .trim()
+        .trim_end_matches(':')
+        .rsplitn(3, ':')
+        .collect::<Vec<_>>();
+    parts.reverse();
+    let (file, line) = match parts.as_slice() {
+        [file, line, column] if column.parse::<u32>().is_ok() => (*file, *line),
+        [rest @ .., line] if !rest.is_empty() => (*rest.last()?, *line),
+        _ => return None,
+    };
+    Some((file.to_string(), line.parse().ok()?))
+}
+
+/// Parses the output of `cargo test`, which runs one test binary after another.
+fn parse_cargo(text: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let lines = text.lines().collect::<Vec<_>>();
+    for line in &lines {
+        if let Some(summary) = line.strip_prefix("test result: ") {
+            for part in summary.split([';', '.']) {
+                let mut words = part.split_whitespace();
+                if let (Some(count), Some(kind)) = (words.next(), words.next()) {
+                    let Ok(count) = count.parse::<usize>() else {
+                        continue;
+                    };
+                    match kind {
+                        "passed" => report.passed += count,
+                        "failed" => report.failed += count,
+                        "ignored" => report.skipped += count,
+                        _ => {},
+                    }
+                }
+            }
+        }
+    }
+
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(name) = lines[i]
+            .strip_prefix("---- ")
+            .and_then(|line| line.strip_suffix(" stdout ----"))
+        else {
+            i += 1;
+            continue;
+        };
+        let start = i + 1;
+        i = start;
+        while i < lines.len() && !lines[i].starts_with("---- ") && lines[i] != "failures:" {
+            i += 1;
+        }
+        let block = &lines[start..i];
+
+        let mut failure = TestFailure {
+            name: name.to_string(),
+            snippet: snippet(block),
+            ..Default::default()
+        };
+        if let Some(at) = block.iter().position(|line| line.contains("' panicked at ")) {
+            let location = block[at].split("' panicked at ").nth(1).unwrap_or_default();
+            // Older versions of Rust print the message before the location: `'message', file:line:column`.
+            let location = location.rsplit(", ").next().unwrap_or(location);
+            if let Some((file, line)) = parse_location(location) {
+                failure.file = Some(file);
+                failure.line = Some(line);
+            }
+            failure.assertion = block[at + 1..]
+                .iter()
+                .take_while(|line| !line.starts_with("note: ") && !line.starts_with("stack backtrace:"))
+                .copied()
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string();
+        }
+        report.failures.push(failure);
+    }
+    report
+}
+
+/// Parses the output of `pytest -rf --tb=short`.
+fn parse_pytest(text: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let lines = text.lines().collect::<Vec<_>>();
+
+    // The last line is e.g. `2 failed, 10 passed, 1 skipped in 0.52s`.
+    if let Some(summary) = lines
+        .iter()
+        .rev()
+        .find(|line| line.contains(" in ") && (line.contains("passed") || line.contains("failed")))
+    {
+        let summary = summary.trim_matches(|c: char| c == '=' || c.is_whitespace());
+        for part in summary.split(" in ").next().unwrap_or_default().split(',') {
+            let mut words = part.split_whitespace();
+            if let (Some(Ok(count)), Some(kind)) = (words.next().map(str::parse::<usize>), words.next()) {
+                match kind {
+                    "passed" => report.passed += count,
+                    "failed" | "error" | "errors" => report.failed += count,
+                    "skipped" | "xfailed" => report.skipped += count,
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    for line in &lines {
+        let Some(failed) = line.strip_prefix("FAILED ") else {
+            continue;
+        };
+        let (node_id, assertion) = failed.split_once(" - ").unwrap_or((failed, ""));
+        let test_name = node_id.rsplit("::").next().unwrap_or(node_id);
+        let file = node_id.split("::").next().map(str::to_string);
+
+        // With --tb=short, the traceback of each test is under a `____ test_name ____` header, and
+        // ends with `file:line: Error` for the failing line of the test file.
+        let header = lines.iter().position(|line| {
+            line.starts_with('_')
+                && line
+                    .trim_matches(|c: char| c == '_' || c.is_whitespace())
+                    .ends_with(test_name)
+        });
+        let block = header
+            .map(|start| {
+                let end = lines[start + 1..]
+                    .iter()
+                    .position(|line| line.starts_with("____") || line.starts_with("===="))
+                    .map_or(lines.len(), |end| start + 1 + end);
+                &lines[start + 1..end]
+            })
+            .unwrap_or_default();
+        let line = file.as_ref().and_then(|file| {
+            block
+                .iter()
+                .filter_map(|line| line.strip_prefix(file.as_str())?.strip_prefix(':'))
+                .filter_map(|rest| rest.split(':').next()?.parse().ok())
+                .next_back()
+        });
+        report.failures.push(TestFailure {
+            name: node_id.to_string(),
+            file,
+            line,
+            assertion: assertion.to_string(),
+            snippet: snippet(block),
+        });
+    }
+    report
+}
+
+/// Parses the report written by `jest --json --outputFile`.
+fn parse_jest(json: &str, directory: &Path) -> Result<TestReport> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Report {
+        num_passed_tests: usize,
+        num_failed_tests: usize,
+        #[serde(default)]
+        num_pending_tests: usize,
+        #[serde(default)]
+        num_todo_tests: usize,
+        #[serde(default)]
+        test_results: Vec<Suite>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Suite {
+        name: String,
+        #[serde(default)]
+        message: String,
+        #[serde(default)]
+        assertion_results: Vec<Assertion>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Assertion {
+        full_name: String,
+        status: String,
+        #[serde(default)]
+        failure_messages: Vec<String>,
+        location: Option<Location>,
+    }
+    #[derive(Deserialize)]
+    struct Location {
+        line: u32,
+    }
+
+    let report = serde_json::from_str::<Report>(json)?;
+    let mut failures = Vec::new();
+    for suite in report.test_results {
+        let file = Path::new(&suite.name)
+            .strip_prefix(directory)
+            .map_or(suite.name.clone(), |path| path.to_string_lossy().to_string());
+        let failed = suite
+            .assertion_results
+            .into_iter()
+            .filter(|assertion| assertion.status == "failed")
+            .collect::<Vec<_>>();
+        // Suites that fail to run, e.g. because of a syntax error, have a message but no results.
+        if failed.is_empty() && !suite.message.trim().is_empty() {
+            let lines = suite.message.lines().collect::<Vec<_>>();
+            failures.push(TestFailure {
+                name: file.clone(),
+                file: Some(file),
+                line: None,
+                assertion: lines
+                    .iter()
+                    .find(|line| !line.trim().is_empty())
+                    .unwrap_or(&"")
+                    .trim()
+                    .to_string(),
+                snippet: snippet(&lines),
+            });
+            continue;
+        }
+        for assertion in failed {
+            let message = assertion.failure_messages.join("\n");
+            let lines = message.lines().collect::<Vec<_>>();
+            failures.push(TestFailure {
+                name: assertion.full_name,
+                file: Some(file.clone()),
+                line: assertion.location.map(|location| location.line),
+                // The assertion is the message before the stack trace.
+                assertion: lines
+                    .iter()
+                    .take_while(|line| !line.trim_start().starts_with("at "))
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join("\n")
+                    .trim()
+                    .to_string(),
+                snippet: snippet(&lines),
+            });
+        }
+    }
+    Ok(TestReport {
+        passed: report.num_passed_tests,
+        failed: report.num_failed_tests,
+        skipped: report.num_pending_tests + report.num_todo_tests,
+        failures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Framework::detect(dir.path()), None);
+        std::fs::write(dir.path().join("pyproject.toml"), "").unwrap();
+        assert_eq!(Framework::detect(dir.path()), Some(Framework::Pytest));
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"devDependencies": {"jest": "^29"}}"#,
+        )
+        .unwrap();
+        assert_eq!(Framework::detect(dir.path()), Some(Framework::Jest));
+        std::fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        assert_eq!(Framework::detect(dir.path()), Some(Framework::Cargo));
+    }
+
+    #[test]
+    fn test_parse_cargo() {
+        let output = "\
+running 3 tests
+test tests::adds ... ok
+test tests::parses ... FAILED
+test tests::slow ... ignored
+
+failures:
+
+---- tests::parses stdout ----
+
+thread 'tests::parses' panicked at src/parse.rs:42:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+failures:
+    tests::parses
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.01s
+
+running 2 tests
+test it_works ... ok
+test it_also_works ... ok
+
+test result: ok. 2 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+";
+        let report = parse_cargo(output);
+        assert_eq!((report.passed, report.failed, report.skipped), (3, 1, 1));
+        assert_eq!(report.failures, vec![TestFailure {
+            name: "tests::parses".to_string(),
+            file: Some("src/parse.rs".to_string()),
+            line: Some(42),
+            assertion: "assertion `left == right` failed\n  left: 1\n right: 2".to_string(),
+            snippet: "thread 'tests::parses' panicked at src/parse.rs:42:9:\nassertion `left == right` failed\n  left: 1\n right: 2\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_parse_pytest() {
+        let output = "\
+..F.s                                                                    [100%]
+=================================== FAILURES ===================================
+_________________________________ test_divide __________________________________
+tests/test_math.py:12: in test_divide
+    assert divide(4, 2) == 3
+E   assert 2.0 == 3
+E    +  where 2.0 = divide(4, 2)
+=========================== short test summary info ============================
+FAILED tests/test_math.py::test_divide - assert 2.0 == 3
+1 failed, 3 passed, 1 skipped in 0.05s
+";
+        let report = parse_pytest(output);
+        assert_eq!((report.passed, report.failed, report.skipped), (3, 1, 1));
+        assert_eq!(report.failures.len(), 1);
+        let failure = &report.failures[0];
+        assert_eq!(failure.name, "tests/test_math.py::test_divide");
+        assert_eq!(failure.file.as_deref(), Some("tests/test_math.py"));
+        assert_eq!(failure.line, Some(12));
+        assert_eq!(failure.assertion, "assert 2.0 == 3");
+        assert!(failure.snippet.starts_with("tests/test_math.py:12: in test_divide"));
+        assert!(failure.snippet.ends_with("where 2.0 = divide(4, 2)"));
+    }
+
+    #[test]
+    fn test_parse_jest() {
+        let json = serde_json::json!({
+            "numPassedTests": 4,
+            "numFailedTests": 1,
+            "numPendingTests": 1,
+            "numTodoTests": 0,
+            "testResults": [
+                {
+                    "name": "/repo/src/sum.test.js",
+                    "message": "",
+                    "assertionResults": [
+                        { "fullName": "sum adds", "status": "passed", "failureMessages": [] },
+                        {
+                            "fullName": "sum handles negatives",
+                            "status": "failed",
+                            "failureMessages": ["Error: expect(received).toBe(expected)\n\nExpected: -1\nReceived: 1\n    at Object.<anonymous> (/repo/src/sum.test.js:9:20)"],
+                            "location": { "column": 3, "line": 8 }
+                        }
+                    ]
+                },
+                {
+                    "name": "/repo/src/broken.test.js",
+                    "message": "SyntaxError: Unexpected token (3:4)",
+                    "assertionResults": []
+                }
+            ]
+        });
+        let report = parse_jest(&json.to_string(), Path::new("/repo")).unwrap();
+        assert_eq!((report.passed, report.failed, report.skipped), (4, 1, 1));
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(report.failures[0].name, "sum handles negatives");
+        assert_eq!(report.failures[0].file.as_deref(), Some("src/sum.test.js"));
+        assert_eq!(report.failures[0].line, Some(8));
+        assert_eq!(
+            report.failures[0].assertion,
+            "Error: expect(received).toBe(expected)\n\nExpected: -1\nReceived: 1"
+        );
+        assert_eq!(report.failures[1].assertion, "SyntaxError: Unexpected token (3:4)");
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::Write;
+
+use jsonschema::error::ValidationErrorKind;
+use serde_json::{
+    Value,
+    json,
+};
+
+/// Maximum number of schema violations reported back to the model for a single tool use.
+const MAX_REPORTED_ERRORS: usize = 5;
+
+/// Validates the arguments of a tool use against the `input_schema` of its [super::ToolSpec].
+///
+/// Returns a description of every violation for the model, each naming the offending field, the
+/// expected type, and an example value. Schemas that cannot be compiled are not enforced, since
+/// they come from MCP servers as well.
+pub fn validate_tool_args(tool_name: &str, schema: &Value, args: &Value) -> Result<(), String> {
+    let Ok(validator) = jsonschema::validator_for(schema) else {
+        return Ok(());
+    };
+
+    let errors = validator.iter_errors(args).collect::<Vec<_>>();
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = format!("Invalid arguments for tool \"{tool_name}\":\n");
+    for error in errors.iter().take(MAX_REPORTED_ERRORS) {
+        let mut path = error
+            .instance_path
+            .as_str()
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+            .collect::<Vec<_>>();
+        let problem = match &error.kind {
+            ValidationErrorKind::Required { property } => {
+                path.push(property.as_str().unwrap_or_default().to_string());
+                "missing required field".to_string()
+            },
+            _ => error.to_string(),
+        };
+
+        let field = match path.is_empty() {
+            true => "<arguments>".to_string(),
+            false => path.join("."),
+        };
+        let _ = write!(message, "- `{field}`: {problem}");
+        if let Some(property) = property_schema(schema, &path) {
+            let expected = expected_type(property);
+            let example = example_value(property, 0);
+            let _ = write!(message, ". Expected {expected}, e.g. {example}");
+        }
+        message.push('\n');
+    }
+    if errors.len() > MAX_REPORTED_ERRORS {
+        let _ = writeln!(message, "- ...and {} more", errors.len() - MAX_REPORTED_ERRORS);
+    }
+    message.push_str("Fix the arguments and call the tool again.");
+
+    Err(message)
+}
+
+/// Returns the schema of the value at `path` of the arguments.
+fn property_schema<'a>(schema: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(schema, |schema, segment| match segment.parse::<usize>() {
+            Ok(_) if schema.get("items").is_some() => schema.get("items"),
+            _ => schema.get("properties")?.get(segment),
+        })
+}
+
+fn expected_type(schema: &Value) -> String {
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        let options = options.iter().map(Value::to_string).collect::<Vec<_>>();
+        return format!("one of {}", options.join(", "));
+    }
+    match schema.get("type") {
+        Some(Value::String(ty)) => ty.clone(),
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" or "),
+        _ => "any value".to_string(),
+    }
+}
+
+/// Builds an example value for `schema`, preferring examples, enum options, and defaults from the
+/// schema itself.
+fn example_value(schema: &Value, depth: usize) -> Value {
+    if let Some(example) = schema.get("examples").and_then(|e| e.get(0)) {
+        return example.clone();
+    }
+    if let Some(option) = schema.get("enum").and_then(|e| e.get(0)) {
+        return option.clone();
+    }
+    if let Some(default) = schema.get("default") {
+        return default.clone();
+    }
+
+    let ty = match schema.get("type") {
+        Some(Value::Array(types)) => types.first().and_then(Value::as_str),
+        ty => ty.and_then(Value::as_str),
+    };
+    match ty {
+        Some("string") => json!("..."),
+        Some("integer") => json!(1),
+        Some("number") => json!(1.0),
+        Some("boolean") => json!(true),
+        Some("array") if depth < 2 => match schema.get("items") {
+            Some(items) => json!([example_value(items, depth + 1)]),
+            None => json!([]),
+        },
+        Some("object") if depth < 2 => {
+            let mut example = serde_json::Map::new();
+            let required = schema.get("required").and_then(Value::as_array);
+            if let (Some(properties), Some(required)) = (schema.get("properties"), required) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if let Some(property) = properties.get(name) {
+                        example.insert(name.to_string(), example_value(property, depth + 1));
+                    }
+                }
+            }
+            Value::Object(example)
+        },
+        Some("array") => json!([]),
+        Some("object") => json!({}),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": { "type": "string", "enum": ["create", "append"] },
+                "path": { "type": "string" },
+                "insert_line": { "type": "integer" },
+                "operations": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": { "mode": { "type": "string", "enum": ["Line", "Directory"] } },
+                        "required": ["mode"]
+                    }
+                }
+            },
+            "required": ["command", "path"]
+        })
+    }
+
+    #[test]
+    fn test_valid_args() {
+        assert!(validate_tool_args("fs_write", &schema(), &json!({ "command": "create", "path": "a.txt" })).is_ok());
+        assert!(validate_tool_args("mcp_tool", &json!({ "type": "not-a-type" }), &json!({})).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        let err = validate_tool_args(
+            "fs_write",
+            &schema(),
+            &json!({ "command": "delete", "insert_line": "3", "operations": [{}] }),
+        )
+        .unwrap_err();
+
+        assert!(err.starts_with("Invalid arguments for tool \"fs_write\":\n"), "{err}");
+        assert!(
+            err.contains("- `path`: missing required field. Expected string, e.g. \"...\""),
+            "{err}"
+        );
+        assert!(
+            err.contains("- `command`: \"delete\" is not one of [\"create\",\"append\"]. Expected one of \"create\", \"append\", e.g. \"create\""),
+            "{err}"
+        );
+        assert!(
+            err.contains("- `insert_line`: \"3\" is not of type \"integer\". Expected integer, e.g. 1"),
+            "{err}"
+        );
+        assert!(
+            err.contains(
+                "- `operations.0.mode`: missing required field. Expected one of \"Line\", \"Directory\", e.g. \"Line\""
+            ),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tempfile::{
+    NamedTempFile,
+    TempDir,
+};
+use tokio::io::{
+    AsyncBufReadExt,
+    AsyncWriteExt,
+    BufReader,
+    Lines,
+};
+use tokio::process::{
+    Child,
+    ChildStdin,
+    ChildStdout,
+};
+use tokio::sync::Mutex;
+
+use super::execute::{
+    ShellCommand,
+    shell_command,
+};
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+use crate::cli::agent::{
+    Agent,
+    ExecutionSandbox,
+    PermissionEvalResult,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+const MAX_TIMEOUT_SECS: u64 = 600;
+
+/// Runs cells read from stdin as JSON lines in a persistent namespace, writing the result of each
+/// as a JSON line to stdout. The value of a trailing expression is returned like in a notebook.
+const PYTHON_DRIVER: &str = r#"import ast, contextlib, io, json, sys, traceback
+
+_stdout = sys.stdout
+_namespace = {"__name__": "__main__"}
+for _line in sys.stdin:
+    _code = json.loads(_line)["code"]
+    _buffer = io.StringIO()
+    _result = {"output": "", "value": None, "error": None}
+    try:
+        with contextlib.redirect_stdout(_buffer), contextlib.redirect_stderr(_buffer):
+            _tree = ast.parse(_code, "<cell>", "exec")
+            _last = None
+            if _tree.body and isinstance(_tree.body[-1], ast.Expr):
+                _last = ast.Expression(_tree.body.pop().value)
+            exec(compile(_tree, "<cell>", "exec"), _namespace)
+            if _last is not None:
+                _value = eval(compile(_last, "<cell>", "eval"), _namespace)
+                if _value is not None:
+                    _result["value"] = repr(_value)
+    except BaseException as _e:
+        _tb = _e.__traceback__.tb_next if _e.__traceback__ else None
+        _result["error"] = "".join(traceback.format_exception(type(_e), _e, _tb))
+    _result["output"] = _buffer.getvalue()
+    _stdout.write(json.dumps(_result) + "\n")
+    _stdout.flush()
+"#;
+
+const NODE_DRIVER: &str = r#"const readline = require("readline");
+const util = require("util");
+const vm = require("vm");
+
+let buffer = [];
+const write = (...args) => { buffer.push(util.format(...args) + "\n"); };
+const console = { log: write, info: write, warn: write, error: write, debug: write, dir: (v) => write(util.inspect(v)) };
+const context = vm.createContext({
+  console, require, process, Buffer, URL, TextEncoder, TextDecoder,
+  setTimeout, clearTimeout, setInterval, clearInterval, setImmediate, structuredClone, fetch: globalThis.fetch,
+});
+
+async function run(code) {
+  buffer = [];
+  const result = { output: "", value: null, error: null };
+  try {
+    let value = vm.runInContext(code, context, { filename: "cell" });
+    if (value && typeof value.then === "function") value = await value;
+    if (value !== undefined) result.value = util.inspect(value, { depth: 4 });
+  } catch (e) {
+    const stack = e && e.stack ? e.stack : String(e);
+    result.error = stack.split("\n").filter((line) => !line.includes("node:") && !line.includes(__filename)).join("\n");
+  }
+  result.output = buffer.join("");
+  process.stdout.write(JSON.stringify(result) + "\n");
+}
+
+let queue = Promise.resolve();
+readline.createInterface({ input: process.stdin, terminal: false })
+  .on("line", (line) => { queue = queue.then(() => run(JSON.parse(line).code)); });
+"#;
+
+static REPLS: LazyLock<Mutex<HashMap<ScratchpadLanguage, Repl>>> = LazyLock::new(Default::default);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScratchpadLanguage {
+    Python,
+    Node,
+}
+
+impl ScratchpadLanguage {
+    fn interpreter(self) -> &'static str {
+        match self {
+            #[cfg(windows)]
+            ScratchpadLanguage::Python => "python -u",
+            #[cfg(not(windows))]
+            ScratchpadLanguage::Python => "python3 -u",
+            ScratchpadLanguage::Node => "node",
+        }
+    }
+
+    fn driver(self) -> (&'static str, &'static str) {
+        match self {
+            ScratchpadLanguage::Python => (PYTHON_DRIVER, ".py"),
+            ScratchpadLanguage::Node => (NODE_DRIVER, ".js"),
+        }
+    }
+}
+
+/// The result of a cell, as written by the drivers.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+struct CellResult {
+    output: String,
+    value: Option<String>,
+    error: Option<String>,
+}
+
+/// An interpreter kept running for the rest of the session, so that the state of the code run in
+/// it carries across uses of the tool.
+struct Repl {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    sandbox: ExecutionSandbox,
+    /// Number of cells run, shown to the model to tell a restarted interpreter apart.
+    cells: usize,
+    _driver: NamedTempFile,
+    _workdir: Option<TempDir>,
+}
+
+impl Repl {
+    fn spawn(language: ScratchpadLanguage, sandbox: ExecutionSandbox) -> Result<Self> {
+        let (source, suffix) = language.driver();
+        let mut driver = tempfile::Builder::new()
+            .prefix("qchat-scratchpad")
+            .suffix(suffix)
+            .tempfile()?;
+        driver.write_all(source.as_bytes())?;
+        driver.flush()?;
+
+        let path = driver.path().to_string_lossy().to_string();
+        let command = format!(
+            "{} {}",
+            language.interpreter(),
+            shlex::try_quote(&path).unwrap_or(path.as_str().into())
+        );
+        let ShellCommand {
+            command: mut cmd,
+            workdir,
+        } = shell_command(&command, sandbox)?;
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err_with(|| format!("Unable to start {}", language.interpreter()))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            bail!("Unable to talk to {}", language.interpreter());
+        };
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            sandbox,
+            cells: 0,
+            _driver: driver,
+            _workdir: workdir,
+        })
+    }
+
+    async fn run(&mut self, code: &str) -> Result<CellResult> {
+        let mut line = serde_json::to_string(&serde_json::json!({ "code": code }))?;
+        line.push('\n');
+        self.stdin.write_all(line.as_bytes()).await?;
+        self.stdin.flush().await?;
+        self.cells += 1;
+        let line = self
+            .stdout
+            .next_line()
+            .await?
+            .ok_or_else(|| eyre!("the interpreter exited"))?;
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+/// The scratchpad tool runs code in a Python or Node.js interpreter kept running for the session,
+/// so that variables, imports, and functions defined by earlier uses are still available.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scratchpad {
+    pub language: ScratchpadLanguage,
+    pub code: String,
+    /// Restarts the interpreter before running the code, clearing its state.
+    #[serde(default)]
+    pub reset: bool,
+    pub timeout_secs: Option<u64>,
+    #[serde(skip)]
+    pub sandbox: ExecutionSandbox,
+}
+
+impl Scratchpad {
+    /// Running code needs to be approved unless the agent trusts the tool.
+    pub fn eval_perm(agent: &Agent) -> PermissionEvalResult {
+        if agent.allowed_tools.contains("scratchpad") {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        let language = match self.language {
+            ScratchpadLanguage::Python => "Python",
+            ScratchpadLanguage::Node => "Node.js",
+        };
+        queue!(
+            output,
+            style::Print(format!(
+                "Running {language} code in the scratchpad{}:\n",
+                if self.reset { " after restarting it" } else { "" }
+            )),
+            style::SetForegroundColor(Color::Green),
+            style::Print(&self.code),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n"),
+        )?;
+        if self.sandbox != ExecutionSandbox::None {
+            queue!(
+                output,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("Running in the {} sandbox\n", self.sandbox)),
+                style::ResetColor
+            )?;
+        }
+        Ok(())
+    }
+
+    pub async fn invoke(&self, _os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let timeout = Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS).min(MAX_TIMEOUT_SECS));
+        let mut repls = REPLS.lock().await;
+        let mut notes = Vec::new();
+        if let Some(repl) = repls.get_mut(&self.language) {
+            if self.reset || repl.sandbox != self.sandbox || !matches!(repl.child.try_wait(), Ok(None)) {
+                repls.remove(&self.language);
+                if !self.reset {
+                    notes.push("The interpreter was restarted, so the state of earlier code was lost.".to_string());
+                }
+            }
+        }
+        let repl = match repls.entry(self.language) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Repl::spawn(self.language, self.sandbox)?),
+        };
+
+        let reason = match tokio::time::timeout(timeout, repl.run(&self.code)).await {
+            Ok(Ok(result)) => {
+                let cell = repl.cells;
+                return Ok(InvokeOutput {
+                    output: OutputKind::Text(format_result(cell, &result, &notes)),
+                });
+            },
+            Ok(Err(err)) => format!("failed: {err}"),
+            Err(_) => format!("did not finish within {} seconds", timeout.as_secs()),
+        };
+        // The interpreter is stopped when dropped, so that the next use starts a new one.
+        repls.remove(&self.language);
+        bail!("The code {reason}. The interpreter was stopped, so the state of earlier code was lost.")
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        if self.code.trim().is_empty() {
+            bail!("The code must not be empty");
+        }
+        if self.timeout_secs == Some(0) {
+            bail!("The timeout must be at least 1 second");
+        }
+        Ok(())
+    }
+}
+
+/// Shuts down the interpreters started during the session.
+pub async fn shutdown_all() {
+    REPLS.lock().await.clear();
+}
+
+fn format_result(cell: usize, result: &CellResult, notes: &[String]) -> String {
+    let mut text = String::new();
+    for note in notes {
+        let _ = writeln!(text, "{note}");
+    }
+    let _ = writeln!(text, "[cell {cell}]");
+    let sections = [
+        (
+            "output",
+            Some(result.output.as_str()).filter(|output| !output.is_empty()),
+        ),
+        ("value", result.value.as_deref()),
+        ("error", result.error.as_deref()),
+    ];
+    let mut empty = true;
+    for (name, content) in sections {
+        if let Some(content) = content {
+            let mut content = content.trim_end().to_string();
+            truncate_safe_in_place(&mut content, MAX_TOOL_RESPONSE_SIZE / 6, "\n... truncated");
+            let _ = writeln!(text, "{name}:\n{content}");
+            empty = false;
+        }
+    }
+    if empty {
+        text.push_str("(no output)\n");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_result() {
+        let result = CellResult {
+            output: "loaded 3 rows\n".to_string(),
+            value: Some("{'a': 1}".to_string()),
+            error: None,
+        };
+        assert_eq!(
+            format_result(2, &result, &[]),
+            "[cell 2]\noutput:\nloaded 3 rows\nvalue:\n{'a': 1}\n"
+        );
+        assert_eq!(
+            format_result(1, &CellResult::default(), &["restarted".to_string()]),
+            "restarted\n[cell 1]\n(no output)\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_python_state_persists() {
+        if std::process::Command::new("python3").arg("--version").output().is_err() {
+            return;
+        }
+        let mut repl = Repl::spawn(ScratchpadLanguage::Python, ExecutionSandbox::None).unwrap();
+        let result = repl.run("import math\nx = 21\nprint('hi')").await.unwrap();
+        assert_eq!(result, CellResult {
+            output: "hi\n".to_string(),
+            value: None,
+            error: None,
+        });
+        let result = repl.run("x * 2").await.unwrap();
+        assert_eq!(result.value.as_deref(), Some("42"));
+        let result = repl.run("1 / 0").await.unwrap();
+        assert!(result.error.unwrap().contains("ZeroDivisionError"));
+        let result = repl.run("print(math.floor(x / 2))").await.unwrap();
+        assert_eq!(result.output, "10\n");
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use serde::Deserialize;
+
+use super::{
+    InvokeOutput,
+    OutputKind,
+};
+use crate::cli::chat::semantic_index::SemanticIndex;
+use crate::os::Os;
+
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 20;
+
+/// The semantic_search tool searches the embedding index of the workspace built with `q index`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SemanticSearch {
+    pub query: String,
+    pub limit: Option<usize>,
+}
+
+impl SemanticSearch {
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        queue!(
+            output,
+            style::Print("Searching the workspace for: "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(&self.query),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n"),
+        )?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let root = os.env.current_dir()?;
+        let query = self.query.clone();
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        // Embedding the query runs the model, which blocks.
+        let matches = tokio::task::spawn_blocking(move || SemanticIndex::open(&root)?.search(&query, limit)).await??;
+
+        if matches.is_empty() {
+            return Ok(InvokeOutput {
+                output: OutputKind::Text("No matches found.".to_string()),
+            });
+        }
+        let mut text = String::new();
+        for code_match in matches {
+            let _ = writeln!(
+                text,
+                "{}:{} (distance {:.3})\n```\n{}\n```",
+                code_match.path, code_match.start_line, code_match.distance, code_match.text
+            );
+        }
+        Ok(InvokeOutput {
+            output: OutputKind::Text(text),
+        })
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        if self.query.trim().is_empty() {
+            bail!("The query must not be empty");
+        }
+        if !SemanticIndex::exists(&os.env.current_dir()?) {
+            bail!("The workspace has no semantic index yet. Ask the user to run `q index` to build it.");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_validate() {
+        let os = Os::new().await.unwrap();
+        let mut tool = serde_json::from_value::<SemanticSearch>(serde_json::json!({ "query": " " })).unwrap();
+        assert!(tool.validate(&os).await.is_err());
+
+        let mut tool =
+            serde_json::from_value::<SemanticSearch>(serde_json::json!({ "query": "retry logic", "limit": 3 }))
+                .unwrap();
+        assert_eq!(tool.limit, Some(3));
+        let err = tool.validate(&os).await.unwrap_err();
+        assert!(err.to_string().contains("q index"));
+    }
+}
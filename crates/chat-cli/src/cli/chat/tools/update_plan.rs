@@ -0,0 +1,197 @@
+use std::fmt::Display;
+use std::io::Write;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    InvokeOutput,
+    OutputKind,
+};
+
+/// The status of a single step within a [Plan].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlanStep {
+    /// Short description of the step
+    pub step: String,
+    pub status: StepStatus,
+}
+
+/// A structured plan emitted by the model for a multi-step task. The latest plan is tracked as
+/// part of the conversation state so that it can be displayed to the user and re-sent to the model
+/// with every request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Plan {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    /// Returns the number of completed steps and the total number of steps.
+    pub fn progress(&self) -> (usize, usize) {
+        let completed = self.steps.iter().filter(|s| s.status == StepStatus::Completed).count();
+        (completed, self.steps.len())
+    }
+
+    /// Queues the plan as a checklist for the user.
+    pub fn queue_checklist(&self, output: &mut impl Write) -> Result<()> {
+        for PlanStep { step, status } in &self.steps {
+            let (marker, color) = match status {
+                StepStatus::Completed => ("[x]", Color::Green),
+                StepStatus::InProgress => ("[~]", Color::Yellow),
+                StepStatus::Pending => ("[ ]", Color::DarkGrey),
+            };
+            queue!(
+                output,
+                style::SetForegroundColor(color),
+                style::Print(format!("   {marker} ")),
+                style::SetForegroundColor(Color::Reset),
+                style::Print(step),
+                style::Print("\n"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Plain text representation of the plan, used when re-injecting the plan as context.
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(explanation) = &self.explanation {
+            writeln!(f, "{explanation}")?;
+        }
+        for (i, PlanStep { step, status }) in self.steps.iter().enumerate() {
+            let status = match status {
+                StepStatus::Pending => "pending",
+                StepStatus::InProgress => "in progress",
+                StepStatus::Completed => "completed",
+            };
+            writeln!(f, "{}. [{status}] {step}", i + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// The update_plan tool allows the model to record and update a plan for a multi-step task. It
+/// performs no system operations - the plan itself is stored on the conversation state once the
+/// tool is invoked.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdatePlan {
+    #[serde(flatten)]
+    pub plan: Plan,
+}
+
+impl UpdatePlan {
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        let (completed, total) = self.plan.progress();
+        queue!(
+            output,
+            style::Print("Updating plan "),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!("({completed}/{total} completed)")),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n"),
+        )?;
+        if let Some(explanation) = &self.plan.explanation {
+            queue!(
+                output,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("   {explanation}\n")),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        self.plan.queue_checklist(output)
+    }
+
+    pub async fn invoke(&self, _updates: impl Write) -> Result<InvokeOutput> {
+        let (completed, total) = self.plan.progress();
+        Ok(InvokeOutput {
+            output: OutputKind::Text(format!("Plan updated. {completed} of {total} steps completed.")),
+        })
+    }
+
+    pub async fn validate(&mut self, _os: &crate::os::Os) -> Result<()> {
+        if self.plan.steps.is_empty() {
+            bail!("The plan must contain at least one step");
+        }
+        if self.plan.steps.iter().any(|s| s.step.trim().is_empty()) {
+            bail!("Plan steps must not be empty");
+        }
+        let in_progress = self
+            .plan
+            .steps
+            .iter()
+            .filter(|s| s.status == StepStatus::InProgress)
+            .count();
+        if in_progress > 1 {
+            bail!("At most one step can be in_progress at a time, found {in_progress}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::os::Os;
+
+    #[tokio::test]
+    async fn test_update_plan_deser_and_validate() {
+        let os = Os::new().await.unwrap();
+        let mut tool = serde_json::from_value::<UpdatePlan>(serde_json::json!({
+            "explanation": "Ship the feature",
+            "steps": [
+                { "step": "Write code", "status": "completed" },
+                { "step": "Write tests", "status": "in_progress" },
+                { "step": "Open PR", "status": "pending" },
+            ]
+        }))
+        .unwrap();
+        assert!(tool.validate(&os).await.is_ok());
+        assert_eq!(tool.plan.progress(), (1, 3));
+
+        tool.plan.steps[2].status = StepStatus::InProgress;
+        assert!(tool.validate(&os).await.is_err());
+
+        let mut empty = serde_json::from_value::<UpdatePlan>(serde_json::json!({ "steps": [] })).unwrap();
+        assert!(empty.validate(&os).await.is_err());
+    }
+
+    #[test]
+    fn test_plan_display() {
+        let plan = Plan {
+            explanation: None,
+            steps: vec![
+                PlanStep {
+                    step: "a".to_string(),
+                    status: StepStatus::Completed,
+                },
+                PlanStep {
+                    step: "b".to_string(),
+                    status: StepStatus::Pending,
+                },
+            ],
+        };
+        assert_eq!(plan.to_string(), "1. [completed] a\n2. [pending] b\n");
+    }
+}
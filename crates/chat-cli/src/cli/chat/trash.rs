@@ -0,0 +1,335 @@
+//! Recycle bin for the files that tools overwrite or delete while all tools are trusted, so that
+//! one bad autonomous edit can be undone with `/trash restore`.
+//!
+//! Each entry is a directory under `.amazonq/trash` of the current directory, named after its id,
+//! with the metadata of the entry in `entry.json` and the copy of the file or directory in
+//! `content`. Entries older than `chat.trashRetentionDays` are removed whenever new ones are kept.
+//! The trash ignores itself with a `.gitignore`, so that it is neither committed nor listed.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::{
+    Result,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use time::OffsetDateTime;
+use tracing::warn;
+use walkdir::WalkDir;
+
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Days entries are kept for when `chat.trashRetentionDays` is not set.
+const DEFAULT_RETENTION_DAYS: i64 = 7;
+/// Files and directories larger than this are not kept, since copying them would take too long.
+const MAX_ENTRY_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Path of the trash of the project at `root`.
+pub fn trash_dir(root: &Path) -> PathBuf {
+    root.join(".amazonq").join("trash")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashEntry {
+    /// Number of the entry, used to restore it.
+    pub id: u64,
+    /// Where the file or directory was.
+    pub path: PathBuf,
+    /// What overwrote or deleted it.
+    pub reason: String,
+    /// Unix timestamp in seconds of when the entry was kept.
+    pub trashed_at: i64,
+}
+
+/// Returns the days entries are kept for, or `None` if copies are not kept at all.
+fn retention_days(os: &Os) -> Option<i64> {
+    match os.database.settings.get_int(Setting::ChatTrashRetentionDays) {
+        Some(days) if days <= 0 => None,
+        Some(days) => Some(days),
+        None => Some(DEFAULT_RETENTION_DAYS),
+    }
+}
+
+/// Returns the entries of the trash of the project at `root`, oldest first.
+pub async fn list(os: &Os, root: &Path) -> Vec<TrashEntry> {
+    let dir = trash_dir(root);
+    let Ok(mut read_dir) = os.fs.read_dir(&dir).await else {
+        return Vec::new();
+    };
+    let mut entries = Vec::new();
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        let metadata = dir.join(entry.file_name()).join("entry.json");
+        match os
+            .fs
+            .read(&metadata)
+            .await
+            .map(|content| serde_json::from_slice(&content))
+        {
+            Ok(Ok(entry)) => entries.push(entry),
+            Ok(Err(err)) => warn!(?err, "ignoring the invalid trash entry {}", metadata.display()),
+            Err(_) => (),
+        }
+    }
+    entries.sort_by_key(|entry: &TrashEntry| entry.id);
+    entries
+}
+
+/// Keeps copies of the existing `paths` in the trash of the project at `root` before they are
+/// overwritten or deleted for `reason`, and removes the entries past retention. Returns the new
+/// entries.
+pub async fn keep(os: &Os, root: &Path, paths: &[PathBuf], reason: &str) -> Result<Vec<TrashEntry>> {
+    let Some(retention_days) = retention_days(os) else {
+        return Ok(Vec::new());
+    };
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let mut next_id = list(os, root).await.last().map_or(1, |entry| entry.id + 1);
+    let mut kept = Vec::new();
+    for path in paths {
+        if !os.fs.exists(path) || path.starts_with(trash_dir(root)) {
+            continue;
+        }
+        if size_within_max(os, path).await?.is_none() {
+            warn!(
+                "not keeping a copy of {} in the trash, it is over {MAX_ENTRY_BYTES} bytes",
+                path.display()
+            );
+            continue;
+        }
+        let entry = TrashEntry {
+            id: next_id,
+            path: path.clone(),
+            reason: reason.to_string(),
+            trashed_at: now,
+        };
+        let entry_dir = trash_dir(root).join(entry.id.to_string());
+        os.fs.create_dir_all(&entry_dir).await?;
+        let gitignore = trash_dir(root).join(".gitignore");
+        if !os.fs.exists(&gitignore) {
+            os.fs.write(&gitignore, "*\n").await?;
+        }
+        copy(os, path, &entry_dir.join("content")).await?;
+        os.fs
+            .write(entry_dir.join("entry.json"), serde_json::to_vec_pretty(&entry)?)
+            .await?;
+        next_id += 1;
+        kept.push(entry);
+    }
+    prune(os, root, now - retention_days * 24 * 60 * 60).await;
+    Ok(kept)
+}
+
+/// Puts the entry `id` of the trash of the project at `root` back where it was. Whatever is there
+/// now is kept in the trash in turn, so that restoring can be undone too.
+pub async fn restore(os: &Os, root: &Path, id: u64) -> Result<TrashEntry> {
+    let Some(entry) = list(os, root).await.into_iter().find(|entry| entry.id == id) else {
+        bail!("there is no entry {id} in the trash");
+    };
+    let entry_dir = trash_dir(root).join(id.to_string());
+    if os.fs.exists(&entry.path) {
+        keep(
+            os,
+            root,
+            std::slice::from_ref(&entry.path),
+            &format!("/trash restore {id}"),
+        )
+        .await?;
+        match os.fs.symlink_metadata(&entry.path).await?.is_dir() {
+            true => os.fs.remove_dir_all(&entry.path).await?,
+            false => os.fs.remove_file(&entry.path).await?,
+        }
+    }
+    if let Some(parent) = entry.path.parent() {
+        os.fs.create_dir_all(parent).await?;
+    }
+    os.fs.rename(entry_dir.join("content"), &entry.path).await?;
+    os.fs.remove_dir_all(&entry_dir).await?;
+    Ok(entry)
+}
+
+/// Removes the entries of the trash of the project at `root` kept before `before`.
+async fn prune(os: &Os, root: &Path, before: i64) {
+    for entry in list(os, root)
+        .await
+        .into_iter()
+        .filter(|entry| entry.trashed_at < before)
+    {
+        if let Err(err) = os.fs.remove_dir_all(trash_dir(root).join(entry.id.to_string())).await {
+            warn!(?err, "failed to remove the trash entry {}", entry.id);
+        }
+    }
+}
+
+/// Returns the size in bytes of the file or directory at `path`, or `None` if it is over
+/// [MAX_ENTRY_BYTES], in which case the walk stops there.
+async fn size_within_max(os: &Os, path: &Path) -> Result<Option<u64>> {
+    let path = os.fs.chroot_path(path);
+    let size = tokio::task::spawn_blocking(move || {
+        let mut size = 0;
+        for metadata in WalkDir::new(path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+        {
+            size += metadata.len();
+            if size > MAX_ENTRY_BYTES {
+                return None;
+            }
+        }
+        Some(size)
+    })
+    .await?;
+    Ok(size)
+}
+
+/// Copies the file or directory at `from` to `to`.
+async fn copy(os: &Os, from: &Path, to: &Path) -> Result<()> {
+    let real_from = os.fs.chroot_path(from);
+    for entry in WalkDir::new(&real_from).follow_links(false) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(&real_from)?;
+        let (source, target) = match relative.as_os_str().is_empty() {
+            true => (from.to_path_buf(), to.to_path_buf()),
+            false => (from.join(relative), to.join(relative)),
+        };
+        if entry.file_type().is_dir() {
+            os.fs.create_dir_all(&target).await?;
+        } else if entry.file_type().is_symlink() {
+            os.fs.symlink(os.fs.read_link(&source).await?, &target).await?;
+        } else {
+            os.fs.copy(&source, &target).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_keep_and_restore() {
+        let os = Os::new().await.unwrap();
+        let root = Path::new("/project");
+        os.fs.create_dir_all("/project/src/nested").await.unwrap();
+        os.fs.write("/project/src/main.rs", "fn main() {}").await.unwrap();
+        os.fs
+            .write("/project/src/nested/lib.rs", "pub fn lib() {}")
+            .await
+            .unwrap();
+
+        let kept = keep(
+            &os,
+            root,
+            &[PathBuf::from("/project/src/main.rs"), PathBuf::from("/project/missing")],
+            "fs_write",
+        )
+        .await
+        .unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].id, 1);
+        os.fs.write("/project/src/main.rs", "oops").await.unwrap();
+
+        let kept = keep(&os, root, &[PathBuf::from("/project/src")], "rm -rf src")
+            .await
+            .unwrap();
+        assert_eq!(kept[0].id, 2);
+        os.fs.remove_dir_all("/project/src").await.unwrap();
+        assert_eq!(list(&os, root).await.len(), 2);
+
+        // Restoring the directory brings back all of its files.
+        restore(&os, root, 2).await.unwrap();
+        assert_eq!(
+            os.fs.read_to_string("/project/src/nested/lib.rs").await.unwrap(),
+            "pub fn lib() {}"
+        );
+        assert_eq!(os.fs.read_to_string("/project/src/main.rs").await.unwrap(), "oops");
+
+        // Restoring over an existing file keeps that file in the trash.
+        restore(&os, root, 1).await.unwrap();
+        assert_eq!(
+            os.fs.read_to_string("/project/src/main.rs").await.unwrap(),
+            "fn main() {}"
+        );
+        let entries = list(&os, root).await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, 2);
+        assert_eq!(entries[0].reason, "/trash restore 1");
+
+        assert!(restore(&os, root, 42).await.is_err());
+
+        // The trash is ignored by git, and the file ignoring it is not an entry.
+        assert_eq!(
+            os.fs
+                .read_to_string("/project/.amazonq/trash/.gitignore")
+                .await
+                .unwrap(),
+            "*\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keep_too_large() {
+        let os = Os::new().await.unwrap();
+        let root = Path::new("/project");
+        os.fs.create_dir_all("/project/data").await.unwrap();
+        os.fs.write("/project/data/small.txt", "small").await.unwrap();
+        // A sparse file, so that the test does not write its size.
+        std::fs::File::create(os.fs.chroot_path("/project/data/large.bin"))
+            .unwrap()
+            .set_len(MAX_ENTRY_BYTES + 1)
+            .unwrap();
+
+        assert_eq!(
+            size_within_max(&os, Path::new("/project/data/small.txt"))
+                .await
+                .unwrap(),
+            Some(5)
+        );
+        assert_eq!(size_within_max(&os, Path::new("/project/data")).await.unwrap(), None);
+        let kept = keep(
+            &os,
+            root,
+            &[PathBuf::from("/project/data"), PathBuf::from("/project/data/small.txt")],
+            "rm -rf data",
+        )
+        .await
+        .unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].path, PathBuf::from("/project/data/small.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_retention() {
+        let mut os = Os::new().await.unwrap();
+        let root = Path::new("/project");
+        os.fs.create_dir_all("/project").await.unwrap();
+        os.fs.write("/project/a.txt", "a").await.unwrap();
+
+        keep(&os, root, &[PathBuf::from("/project/a.txt")], "fs_write")
+            .await
+            .unwrap();
+        // Entries kept before the retention are removed when new ones are kept.
+        prune(&os, root, OffsetDateTime::now_utc().unix_timestamp() + 1).await;
+        assert!(list(&os, root).await.is_empty());
+
+        os.database
+            .settings
+            .set(Setting::ChatTrashRetentionDays, 0)
+            .await
+            .unwrap();
+        let kept = keep(&os, root, &[PathBuf::from("/project/a.txt")], "fs_write")
+            .await
+            .unwrap();
+        assert!(kept.is_empty());
+        assert!(list(&os, root).await.is_empty());
+    }
+}
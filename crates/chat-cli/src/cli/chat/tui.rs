@@ -0,0 +1,501 @@
+//! Full-screen frontend of `q chat --tui`.
+//!
+//! The screen is split into a conversation pane, a pane listing the tools waiting to run, a
+//! status pane and an input box. Everything the session writes to its output streams is shown in
+//! the conversation pane without formatting, while lines typed into the input box are handed to
+//! the session through [TuiInput].
+//!
+//! The screen is drawn by a thread of its own, which also reads the keyboard.
+
+use std::io::Write;
+use std::sync::atomic::{
+    AtomicBool,
+    AtomicUsize,
+    Ordering,
+};
+use std::sync::mpsc::{
+    Receiver,
+    Sender,
+    channel,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crossterm::event::{
+    self,
+    Event,
+    KeyCode,
+    KeyEvent,
+    KeyEventKind,
+    KeyModifiers,
+};
+use ratatui::Frame;
+use ratatui::layout::{
+    Constraint,
+    Layout,
+};
+use ratatui::style::{
+    Color,
+    Style,
+    Stylize,
+};
+use ratatui::text::Line;
+use ratatui::widgets::{
+    Block,
+    List,
+    ListItem,
+    Paragraph,
+    Wrap,
+};
+use spinners::Spinner;
+use tracing::error;
+
+use super::renderer::{
+    Renderer,
+    SessionStatus,
+};
+
+/// Lines scrolled by page up and page down.
+const SCROLL_STEP: u16 = 10;
+
+/// Width of the text in the conversation pane, see [pane_width].
+static PANE_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Width of the text in the conversation pane once the screen has been drawn, used in place of
+/// the width of the terminal to wrap responses.
+pub fn pane_width() -> Option<usize> {
+    match PANE_WIDTH.load(Ordering::Relaxed) {
+        0 => None,
+        width => Some(width),
+    }
+}
+
+#[derive(Debug, Default)]
+struct TuiState {
+    conversation: String,
+    status: SessionStatus,
+    /// Shown in the status pane while the session is busy, until the next status update.
+    busy: Option<String>,
+    /// Prompt of the line being read, if any.
+    prompt: Option<String>,
+    input: String,
+    /// Lines scrolled up from the end of the conversation.
+    scroll: u16,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum KeyAction {
+    /// Hands a line to the session, or `None` if the user cancelled the prompt.
+    Submit(Option<String>),
+    /// Interrupts the session while it is not reading input.
+    Interrupt,
+}
+
+impl TuiState {
+    fn handle_key(&mut self, key: KeyEvent) -> Option<KeyAction> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => {
+                if self.prompt.is_none() {
+                    return Some(KeyAction::Interrupt);
+                }
+                if self.input.is_empty() {
+                    return Some(self.submit(None));
+                }
+                self.input.clear();
+            },
+            KeyCode::Char('d') if ctrl && self.input.is_empty() && self.prompt.is_some() => {
+                return Some(self.submit(None));
+            },
+            KeyCode::Char('j') if ctrl => self.input.push('\n'),
+            KeyCode::Char('u') if ctrl => self.input.clear(),
+            KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => self.input.push('\n'),
+            KeyCode::Enter if self.prompt.is_some() => {
+                let line = std::mem::take(&mut self.input);
+                return Some(self.submit(Some(line)));
+            },
+            KeyCode::Backspace => {
+                self.input.pop();
+            },
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_add(SCROLL_STEP),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(SCROLL_STEP),
+            KeyCode::Char(c) if !ctrl => self.input.push(c),
+            _ => (),
+        }
+        None
+    }
+
+    /// Ends the prompt, echoing the line into the conversation as the line-oriented frontend
+    /// does.
+    fn submit(&mut self, line: Option<String>) -> KeyAction {
+        let prompt = self.prompt.take().unwrap_or_default();
+        if let Some(line) = &line {
+            self.conversation.push_str(&format!("{prompt}{line}\n"));
+        }
+        self.scroll = 0;
+        KeyAction::Submit(line)
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>) {
+        let [main, input_area] = Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+        let [conversation_area, side] =
+            Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)]).areas(main);
+        let [tools_area, status_area] =
+            Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(side);
+
+        let width = conversation_area.width.saturating_sub(2);
+        PANE_WIDTH.store(width as usize, Ordering::Relaxed);
+
+        // Scroll to the end of the conversation, less the lines the user scrolled up.
+        let conversation = Paragraph::new(self.conversation.as_str())
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(" Conversation "));
+        let height = conversation_area.height.saturating_sub(2);
+        let total = conversation.line_count(width) as u16;
+        let scroll = total.saturating_sub(height).saturating_sub(self.scroll);
+        frame.render_widget(conversation.scroll((scroll, 0)), conversation_area);
+
+        let tools = self
+            .status
+            .pending_tools
+            .iter()
+            .map(|tool| match tool.awaiting_approval {
+                true => ListItem::new(format!("{} (needs approval)", tool.name)).fg(Color::Yellow),
+                false => ListItem::new(tool.name.clone()),
+            })
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            List::new(tools).block(Block::bordered().title(" Pending tools ")),
+            tools_area,
+        );
+
+        let activity = self.busy.as_deref().unwrap_or(&self.status.activity);
+        let mut status = vec![
+            Line::from(activity.to_string()).fg(Color::Cyan),
+            Line::from(format!("Agent: {}", self.status.agent.as_deref().unwrap_or("-"))),
+            Line::from(format!("Model: {}", self.status.model.as_deref().unwrap_or("-"))),
+            Line::from(""),
+            Line::from("Context files:").bold(),
+        ];
+        status.extend(self.status.context_files.iter().map(|path| Line::from(path.clone())));
+        frame.render_widget(
+            Paragraph::new(status)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title(" Status ")),
+            status_area,
+        );
+
+        let (title, style) = match &self.prompt {
+            Some(prompt) => (format!(" {} ", prompt.trim()), Style::default()),
+            None => (" Input ".to_string(), Style::default().fg(Color::DarkGray)),
+        };
+        // Only the last line of multi-line input fits in the box.
+        let input = self.input.rsplit('\n').next().unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(input).style(style).block(Block::bordered().title(title)),
+            input_area,
+        );
+        if self.prompt.is_some() {
+            let column = input_area.x + 1 + input.chars().count() as u16;
+            frame.set_cursor_position((column.min(input_area.right().saturating_sub(2)), input_area.y + 1));
+        }
+    }
+}
+
+/// The full-screen frontend. The terminal is restored once it is dropped.
+#[derive(Debug)]
+pub struct Tui {
+    state: Arc<Mutex<TuiState>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Tui {
+    /// Takes over the terminal and starts drawing the screen, returning the source of the lines
+    /// typed into the input box.
+    pub fn start() -> (Self, TuiInput) {
+        let state = Arc::new(Mutex::new(TuiState::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+
+        let thread = {
+            let state = state.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || run(&state, &stop, &sender))
+        };
+
+        let input = TuiInput {
+            state: state.clone(),
+            lines: receiver,
+        };
+        (
+            Self {
+                state,
+                stop,
+                thread: Some(thread),
+            },
+            input,
+        )
+    }
+
+    /// Returns a stream writing to the conversation pane.
+    pub fn output(&self) -> TuiOutput {
+        TuiOutput::new(ConversationWriter(self.state.clone()))
+    }
+
+    fn with_state(&self, f: impl FnOnce(&mut TuiState)) {
+        match self.state.lock() {
+            Ok(mut state) => f(&mut state),
+            Err(err) => error!(?err, "tui state lock poisoned"),
+        }
+    }
+}
+
+impl Renderer for Tui {
+    fn update(&self, status: &SessionStatus) {
+        self.with_state(|state| {
+            state.status = status.clone();
+            state.busy = None;
+        });
+    }
+
+    fn busy(&self, message: &str) -> Option<Spinner> {
+        self.with_state(|state| state.busy = Some(message.to_string()));
+        None
+    }
+
+    fn reads_terminal(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(state: &Mutex<TuiState>, stop: &AtomicBool, lines: &Sender<Option<String>>) {
+    let mut terminal = ratatui::init();
+    while !stop.load(Ordering::Relaxed) {
+        let drawn = match state.lock() {
+            Ok(state) => terminal.draw(|frame| state.draw(frame)).map(|_| ()),
+            Err(_) => break,
+        };
+        if let Err(err) = drawn {
+            error!(?err, "failed to draw the tui");
+            break;
+        }
+
+        match event::poll(Duration::from_millis(50)) {
+            Ok(true) => (),
+            Ok(false) => continue,
+            Err(err) => {
+                error!(?err, "failed to read the terminal");
+                break;
+            },
+        }
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let action = match state.lock() {
+            Ok(mut state) => state.handle_key(key),
+            Err(_) => break,
+        };
+        match action {
+            Some(KeyAction::Submit(line)) => {
+                let _ = lines.send(line);
+            },
+            Some(KeyAction::Interrupt) => interrupt(),
+            None => (),
+        }
+    }
+    ratatui::restore();
+}
+
+/// Raw mode keeps ctrl + c from interrupting the process, so it is raised here for the session
+/// to handle as it would in the line-oriented frontend.
+fn interrupt() {
+    #[cfg(unix)]
+    if let Err(err) = nix::sys::signal::raise(nix::sys::signal::Signal::SIGINT) {
+        error!(?err, "failed to interrupt the session");
+    }
+}
+
+#[derive(Clone)]
+struct ConversationWriter(Arc<Mutex<TuiState>>);
+
+impl Write for ConversationWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        let mut state = self.0.lock().map_err(|err| std::io::Error::other(err.to_string()))?;
+        // Carriage returns are only used to redraw lines, which the pane does not support.
+        state.conversation.extend(text.chars().filter(|c| *c != '\r'));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Output stream of a session shown in the conversation pane, with ANSI escapes removed.
+pub struct TuiOutput {
+    writer: Box<strip_ansi_escapes::Writer<ConversationWriter>>,
+    /// Where [Self::writer] writes to, kept to create other outputs to the same destination.
+    destination: ConversationWriter,
+}
+
+impl TuiOutput {
+    fn new(destination: ConversationWriter) -> Self {
+        Self {
+            writer: Box::new(strip_ansi_escapes::Writer::new(destination.clone())),
+            destination,
+        }
+    }
+
+    /// Returns another output writing to the conversation pane, see
+    /// [super::output::SessionOutput::duplicate].
+    pub fn duplicate(&self) -> Self {
+        Self::new(self.destination.clone())
+    }
+}
+
+impl std::fmt::Debug for TuiOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TuiOutput").finish_non_exhaustive()
+    }
+}
+
+impl Write for TuiOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Lines typed into the input box.
+#[derive(Debug)]
+pub struct TuiInput {
+    state: Arc<Mutex<TuiState>>,
+    lines: Receiver<Option<String>>,
+}
+
+impl TuiInput {
+    /// Shows `prompt` above the input box, which starts with `initial_line`, and waits for a line,
+    /// returning `None` if the user cancelled the prompt or the screen was closed.
+    pub fn read_line(&self, prompt: &str, initial_line: Option<String>) -> Option<String> {
+        if let Ok(mut state) = self.state.lock() {
+            state.prompt = Some(String::from_utf8_lossy(&strip_ansi_escapes::strip(prompt)).into_owned());
+            state.input = initial_line.unwrap_or_default();
+        }
+        self.lines.recv().ok().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+    use crate::cli::chat::renderer::PendingTool;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn test_handle_key() {
+        let mut state = TuiState::default();
+        assert_eq!(state.handle_key(ctrl('c')), Some(KeyAction::Interrupt));
+
+        state.prompt = Some("> ".to_string());
+        for c in "hi".chars() {
+            assert_eq!(state.handle_key(key(KeyCode::Char(c))), None);
+        }
+        assert_eq!(
+            state.handle_key(key(KeyCode::Enter)),
+            Some(KeyAction::Submit(Some("hi".to_string())))
+        );
+        assert_eq!(state.conversation, "> hi\n");
+        assert_eq!(state.prompt, None);
+        // Enter does nothing until the session reads input again.
+        assert_eq!(state.handle_key(key(KeyCode::Enter)), None);
+
+        state.prompt = Some("> ".to_string());
+        state.handle_key(key(KeyCode::Char('x')));
+        assert_eq!(state.handle_key(ctrl('c')), None);
+        assert_eq!(state.input, "");
+        assert_eq!(state.handle_key(ctrl('c')), Some(KeyAction::Submit(None)));
+    }
+
+    #[test]
+    fn test_output_strips_escapes() {
+        let tui_state = Arc::new(Mutex::new(TuiState::default()));
+        let mut output = TuiOutput::new(ConversationWriter(tui_state.clone()));
+        crossterm::execute!(
+            output,
+            crossterm::style::SetForegroundColor(crossterm::style::Color::Green),
+            crossterm::style::Print("Hello\r\n"),
+            crossterm::style::ResetColor,
+        )
+        .unwrap();
+        assert_eq!(tui_state.lock().unwrap().conversation, "Hello\n");
+    }
+
+    #[test]
+    fn test_draw() {
+        let state = TuiState {
+            conversation: "> list the files\nThere are two files.\n".to_string(),
+            status: SessionStatus {
+                activity: "Waiting for input".to_string(),
+                model: Some("claude-sonnet-4".to_string()),
+                agent: Some("q_cli_default".to_string()),
+                context_files: vec!["README.md".to_string()],
+                pending_tools: vec![PendingTool {
+                    name: "fs_write".to_string(),
+                    awaiting_approval: true,
+                }],
+            },
+            prompt: Some("> ".to_string()),
+            ..Default::default()
+        };
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| state.draw(frame)).unwrap();
+
+        let screen = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect::<String>();
+        for text in [
+            "There are two files.",
+            "fs_write (needs approval)",
+            "Model: claude-sonnet-4",
+            "README.md",
+        ] {
+            assert!(screen.contains(text), "{text} is not drawn");
+        }
+        assert_eq!(pane_width(), Some(68));
+    }
+}
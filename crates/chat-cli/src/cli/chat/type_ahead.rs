@@ -0,0 +1,171 @@
+//! Capture of the keys typed while the model is responding or tools are running, so that they
+//! can be offered as the next prompt instead of being lost.
+//!
+//! While capturing, the terminal is switched out of canonical mode without echo, and a thread
+//! reads the keys from stdin. Signals are left enabled so that ctrl + c keeps working.
+
+/// Keys typed on a terminal since [TypeAhead::start].
+#[derive(Debug)]
+pub struct TypeAhead {
+    #[cfg(unix)]
+    inner: unix::Capture,
+}
+
+impl TypeAhead {
+    /// Starts capturing the keys typed on stdin, if it is a terminal.
+    pub fn start() -> Option<Self> {
+        #[cfg(unix)]
+        {
+            unix::Capture::start().map(|inner| Self { inner })
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Stops capturing, returning the text typed so far.
+    pub fn stop(self) -> String {
+        #[cfg(unix)]
+        {
+            decode(&self.inner.stop())
+        }
+        #[cfg(not(unix))]
+        {
+            String::new()
+        }
+    }
+}
+
+/// Returns the text typed with the keys `bytes`, applying backspaces and dropping escape
+/// sequences such as arrow keys and other control characters.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn decode(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{7f}' | '\u{8}' => {
+                out.pop();
+            },
+            '\r' | '\n' => out.push('\n'),
+            '\u{1b}' => {
+                // CSI sequences end with a letter or `~`, e.g. `ESC [ A` or `ESC [ 3 ~`.
+                if chars.next_if(|c| *c == '[' || *c == 'O').is_some() {
+                    while chars.next_if(|c| !c.is_ascii_alphabetic() && *c != '~').is_some() {}
+                }
+                chars.next();
+            },
+            '\t' => out.push(' '),
+            c if c.is_control() => (),
+            c => out.push(c),
+        }
+    }
+    out.trim_end_matches('\n').to_string()
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io::stdin;
+    use std::os::fd::AsRawFd;
+    use std::sync::Arc;
+    use std::sync::atomic::{
+        AtomicBool,
+        Ordering,
+    };
+    use std::thread::JoinHandle;
+
+    use nix::sys::termios::{
+        LocalFlags,
+        SetArg,
+        SpecialCharacterIndices,
+        Termios,
+        tcgetattr,
+        tcsetattr,
+    };
+    use tracing::warn;
+
+    #[derive(Debug)]
+    pub struct Capture {
+        original: Termios,
+        stopped: Arc<AtomicBool>,
+        reader: Option<JoinHandle<Vec<u8>>>,
+    }
+
+    impl Capture {
+        pub fn start() -> Option<Self> {
+            let original = tcgetattr(stdin()).ok()?;
+            let mut termios = original.clone();
+            termios.local_flags.remove(LocalFlags::ICANON | LocalFlags::ECHO);
+            // Reads return after a tenth of a second without input, so that the reader notices
+            // when it is stopped.
+            termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+            termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 1;
+            tcsetattr(stdin(), SetArg::TCSANOW, &termios).ok()?;
+
+            let stopped = Arc::new(AtomicBool::new(false));
+            let reader = std::thread::spawn({
+                let stopped = Arc::clone(&stopped);
+                move || {
+                    let fd = stdin().as_raw_fd();
+                    let mut bytes = Vec::new();
+                    let mut buf = [0; 256];
+                    while !stopped.load(Ordering::Relaxed) {
+                        match nix::unistd::read(fd, &mut buf) {
+                            Ok(n) => bytes.extend_from_slice(&buf[..n]),
+                            Err(nix::errno::Errno::EINTR | nix::errno::Errno::EAGAIN) => (),
+                            Err(err) => {
+                                warn!(?err, "failed to read typed ahead input");
+                                break;
+                            },
+                        }
+                    }
+                    bytes
+                }
+            });
+
+            Some(Self {
+                original,
+                stopped,
+                reader: Some(reader),
+            })
+        }
+
+        pub fn stop(mut self) -> Vec<u8> {
+            self.finish()
+        }
+
+        fn finish(&mut self) -> Vec<u8> {
+            self.stopped.store(true, Ordering::Relaxed);
+            let bytes = match self.reader.take() {
+                Some(reader) => reader.join().unwrap_or_default(),
+                None => return Vec::new(),
+            };
+            if let Err(err) = tcsetattr(stdin(), SetArg::TCSANOW, &self.original) {
+                warn!(?err, "failed to restore the terminal");
+            }
+            bytes
+        }
+    }
+
+    impl Drop for Capture {
+        fn drop(&mut self) {
+            self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"hello"), "hello");
+        assert_eq!(decode(b"helo\x7flo"), "hello");
+        assert_eq!(decode(b"a\x1b[Ab\x1b[3~c"), "abc");
+        assert_eq!(decode(b"line one\rline two\r"), "line one\nline two");
+        assert_eq!(decode("caf\u{e9}\u{7}".as_bytes()), "caf\u{e9}");
+    }
+}
@@ -0,0 +1,118 @@
+use std::process::Command;
+
+use base64::Engine;
+use eyre::{
+    Result,
+    eyre,
+};
+
+use super::images::{
+    ImageMetadata,
+    RichImageBlock,
+};
+use crate::api_client::model::{
+    ImageBlock,
+    ImageFormat,
+    ImageSource,
+};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Copies `text` to the system clipboard.
+pub fn copy_text(text: &str) -> Result<()> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    clipboard.set_text(text)?;
+    Ok(())
+}
+
+/// Returns the text content of the system clipboard.
+pub fn read_text() -> Result<String> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    Ok(clipboard.get_text()?)
+}
+
+/// Returns the image content of the system clipboard as a PNG image, or [None] if the clipboard
+/// does not hold an image.
+///
+/// Images are read with platform commands, since they are already encoded as PNG: `osascript` on
+/// macOS, PowerShell on Windows, and `wl-paste` or `xclip` on Linux.
+pub fn read_image() -> Result<Option<Vec<u8>>> {
+    let png = if cfg!(target_os = "macos") {
+        let output = Command::new("osascript")
+            .args(["-e", "get the clipboard as «class PNGf»"])
+            .output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        parse_osascript_data(&String::from_utf8_lossy(&output.stdout))
+    } else if cfg!(windows) {
+        let script = "Add-Type -AssemblyName System.Windows.Forms; Add-Type -AssemblyName System.Drawing; \
+            $img = [System.Windows.Forms.Clipboard]::GetImage(); \
+            if ($img) { $ms = New-Object System.IO.MemoryStream; \
+            $img.Save($ms, [System.Drawing.Imaging.ImageFormat]::Png); [Convert]::ToBase64String($ms.ToArray()) }";
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-STA", "-Command", script])
+            .output()?;
+        base64::engine::general_purpose::STANDARD
+            .decode(String::from_utf8_lossy(&output.stdout).trim())
+            .ok()
+    } else {
+        let output = match std::env::var_os("WAYLAND_DISPLAY") {
+            Some(_) => Command::new("wl-paste")
+                .args(["--no-newline", "--type", "image/png"])
+                .output(),
+            None => Command::new("xclip")
+                .args(["-selection", "clipboard", "-target", "image/png", "-out"])
+                .output(),
+        }
+        .map_err(|err| eyre!("Reading images from the clipboard requires wl-paste or xclip: {err}"))?;
+        output.status.success().then_some(output.stdout)
+    };
+
+    Ok(png.filter(|bytes| bytes.starts_with(PNG_SIGNATURE)))
+}
+
+/// Creates an image block for a PNG image taken from the clipboard.
+pub fn image_block_from_png(png: Vec<u8>, index: usize) -> RichImageBlock {
+    let size = png.len() as u64;
+    (
+        ImageBlock {
+            format: ImageFormat::Png,
+            source: ImageSource::Bytes(png),
+        },
+        ImageMetadata {
+            filepath: String::new(),
+            size,
+            filename: format!("clipboard-{index}.png"),
+        },
+    )
+}
+
+/// Parses the output of `osascript` for clipboard data, which looks like `«data PNGf89504E47...»`.
+fn parse_osascript_data(output: &str) -> Option<Vec<u8>> {
+    let data = output.trim().strip_prefix("«data ")?.strip_suffix('»')?;
+    hex::decode(data.get(4..)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osascript_data() {
+        assert_eq!(
+            parse_osascript_data("«data PNGf89504E470D0A1A0A»\n"),
+            Some(PNG_SIGNATURE.to_vec())
+        );
+        assert_eq!(parse_osascript_data("hello"), None);
+        assert_eq!(parse_osascript_data("«data PNGfZZ»"), None);
+    }
+
+    #[test]
+    fn test_image_block_from_png() {
+        let (block, metadata) = image_block_from_png(PNG_SIGNATURE.to_vec(), 2);
+        assert_eq!(block.format, ImageFormat::Png);
+        assert_eq!(metadata.filename, "clipboard-2.png");
+        assert_eq!(metadata.size, 8);
+    }
+}
@@ -0,0 +1,136 @@
+//! Matching of paths against the patterns of `.gitignore` files, as described in gitignore(5).
+//!
+//! Each directory of a walk can have its own `.gitignore`, whose patterns are relative to that
+//! directory and take precedence over those of its parents. Within a file, later patterns take
+//! precedence over earlier ones, so that `!` patterns can include again what was ignored.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use globset::{
+    GlobBuilder,
+    GlobMatcher,
+};
+
+/// The patterns of one `.gitignore` file.
+#[derive(Debug)]
+pub struct Gitignore {
+    /// The directory the patterns are relative to.
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    matcher: GlobMatcher,
+    /// Whether the pattern starts with `!`, including the paths it matches again.
+    negated: bool,
+    /// Whether the pattern ends with `/`, matching only directories.
+    dir_only: bool,
+}
+
+impl Gitignore {
+    /// Parses the `content` of the `.gitignore` file of the directory `base`. Invalid patterns are
+    /// skipped, as git does.
+    pub fn parse(base: &Path, content: &str) -> Self {
+        let rules = content
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim_end();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let (negated, pattern) = match line.strip_prefix('!') {
+                    Some(pattern) => (true, pattern),
+                    None => (false, line),
+                };
+                // `\#` and `\!` start patterns with a literal `#` or `!`.
+                let pattern = pattern.strip_prefix('\\').unwrap_or(pattern);
+                let dir_only = pattern.ends_with('/');
+                let pattern = pattern.trim_end_matches('/');
+                // Patterns with a `/` other than a trailing one are relative to the directory of the
+                // file, and the others match at any depth.
+                let glob = match pattern.contains('/') {
+                    true => pattern.trim_start_matches('/').to_string(),
+                    false => format!("**/{pattern}"),
+                };
+                let matcher = GlobBuilder::new(&glob)
+                    .literal_separator(true)
+                    .build()
+                    .ok()?
+                    .compile_matcher();
+                Some(Rule {
+                    matcher,
+                    negated,
+                    dir_only,
+                })
+            })
+            .collect();
+        Self {
+            base: base.to_path_buf(),
+            rules,
+        }
+    }
+
+    /// Reads the `.gitignore` file of the directory `dir`, if it has one.
+    pub fn from_dir(dir: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(dir.join(".gitignore")).ok()?;
+        Some(Self::parse(dir, &content))
+    }
+
+    /// Returns whether `path` is ignored by the patterns of this file, or `None` if none of them
+    /// match it.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.matcher.is_match(relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Returns whether `path` is ignored by `gitignores`, the `.gitignore` files of the directories
+/// that contain it from the outermost to the innermost.
+pub fn is_ignored(gitignores: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    gitignores
+        .iter()
+        .rev()
+        .find_map(|gitignore| gitignore.matched(path, is_dir))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ignored() {
+        let root = Gitignore::parse(
+            Path::new("/project"),
+            "# build output\n/target\nnode_modules/\n*.log\n!keep.log\ndocs/*.html\n\\#notes\n",
+        );
+        let nested = Gitignore::parse(Path::new("/project/web"), "!debug.log\ncache\n");
+        let gitignores = [root, nested];
+        let ignored = |path: &str, is_dir: bool| is_ignored(&gitignores, Path::new(path), is_dir);
+
+        assert!(ignored("/project/target", true));
+        // Patterns starting with `/` only match in the directory of the file.
+        assert!(!ignored("/project/src/target", true));
+        assert!(ignored("/project/web/node_modules", true));
+        // Patterns ending with `/` only match directories.
+        assert!(!ignored("/project/node_modules", false));
+        assert!(ignored("/project/src/server.log", false));
+        assert!(!ignored("/project/src/keep.log", false));
+        // Nested files take precedence over their parents.
+        assert!(!ignored("/project/web/debug.log", false));
+        assert!(ignored("/project/web/app/cache", true));
+        assert!(!ignored("/project/cache", true));
+        assert!(ignored("/project/docs/index.html", false));
+        assert!(!ignored("/project/docs/api/index.html", false));
+        assert!(ignored("/project/#notes", false));
+        assert!(!ignored("/project/src/main.rs", false));
+    }
+}
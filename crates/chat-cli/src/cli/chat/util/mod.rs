@@ -1,3 +1,5 @@
+pub mod clipboard;
+pub mod gitignore;
 pub mod images;
 pub mod issue;
 #[cfg(test)]
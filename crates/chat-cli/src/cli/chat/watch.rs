@@ -0,0 +1,77 @@
+//! `q chat watch`, which follows another interactive session through its socket, e.g. for pair
+//! debugging or demos, see [agent_socket::watch].
+
+use std::io::Write;
+
+use crossterm::style::Color;
+use crossterm::{
+    execute,
+    style,
+};
+use eyre::Result;
+
+use super::agent_socket::{
+    self,
+    SessionEvent,
+};
+
+/// Prints the events of the session `target` until it ends.
+pub async fn run(target: &str) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    execute!(
+        stdout,
+        style::SetForegroundColor(Color::DarkGrey),
+        style::Print(format!("Watching session {target}, press ctrl + c to stop.\n\n")),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    agent_socket::watch(target, |event| print_event(&mut stdout, &event)).await?;
+    execute!(
+        stdout,
+        style::SetForegroundColor(Color::DarkGrey),
+        style::Print("\nThe session ended.\n"),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    Ok(())
+}
+
+fn print_event(output: &mut impl Write, event: &SessionEvent) -> Result<()> {
+    match event {
+        SessionEvent::Prompt { text } => execute!(
+            output,
+            style::SetForegroundColor(Color::Magenta),
+            style::Print("> "),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!("{text}\n\n")),
+        )?,
+        SessionEvent::ResponseText { text } => execute!(output, style::Print(text))?,
+        SessionEvent::ResponseEnd => execute!(output, style::Print("\n\n"))?,
+        SessionEvent::ToolUse { name } => execute!(
+            output,
+            style::SetForegroundColor(Color::Magenta),
+            style::Print(format!("🛠️  Using tool: {name}\n\n")),
+            style::SetForegroundColor(Color::Reset),
+        )?,
+        SessionEvent::ToolApproval { name } => execute!(
+            output,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!("Waiting for the approval of {name}\n\n")),
+            style::SetForegroundColor(Color::Reset),
+        )?,
+        SessionEvent::ToolResult { name, success } => execute!(
+            output,
+            style::SetForegroundColor(if *success { Color::Green } else { Color::Red }),
+            style::Print(format!(
+                " ● {name} {}\n\n",
+                if *success { "completed" } else { "failed" }
+            )),
+            style::SetForegroundColor(Color::Reset),
+        )?,
+        SessionEvent::Error { message } => execute!(
+            output,
+            style::SetForegroundColor(Color::Red),
+            style::Print(format!("{message}\n\n")),
+            style::SetForegroundColor(Color::Reset),
+        )?,
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+use crossterm::style::Stylize;
+use eyre::Result;
+
+use crate::cli::chat::semantic_index::{
+    SemanticIndex,
+    index_dir,
+};
+use crate::os::Os;
+
+#[derive(Clone, Debug, Args, PartialEq, Eq)]
+pub struct IndexArgs {
+    /// Directory of the workspace, the current directory by default
+    path: Option<PathBuf>,
+}
+
+impl IndexArgs {
+    pub async fn execute(self, os: &Os) -> Result<ExitCode> {
+        let root = match self.path {
+            Some(path) => os.env.current_dir()?.join(path),
+            None => os.env.current_dir()?,
+        };
+        eprintln!("Indexing {}", root.display());
+
+        // Embedding runs the model, which blocks.
+        let update = tokio::task::spawn_blocking({
+            let root = root.clone();
+            move || {
+                let mut index = SemanticIndex::open(&root)?;
+                index.update(|checked| {
+                    if checked % 100 == 0 {
+                        eprint!("\rChecked {checked} files");
+                        let _ = std::io::stderr().flush();
+                    }
+                })
+            }
+        })
+        .await??;
+
+        eprintln!(
+            "\r{} {} files indexed, {} removed, {} files in {}",
+            "✓".green(),
+            update.indexed,
+            update.removed,
+            update.total,
+            index_dir(&root).display()
+        );
+        Ok(ExitCode::SUCCESS)
+    }
+}
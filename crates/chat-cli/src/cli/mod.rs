@@ -3,9 +3,11 @@ mod chat;
 mod debug;
 mod diagnostics;
 mod feed;
+mod index;
 mod issue;
 mod mcp;
 mod settings;
+mod stats;
 mod user;
 
 use std::fmt::Display;
@@ -18,6 +20,9 @@ use std::process::ExitCode;
 use agent::AgentArgs;
 use anstream::println;
 pub use chat::ConversationState;
+#[cfg(feature = "test-harness")]
+pub use chat::test_harness;
+pub use chat::tool_stats::ToolStats;
 use clap::{
     ArgAction,
     CommandFactory,
@@ -37,8 +42,12 @@ use tracing::{
     debug,
 };
 
-use crate::cli::chat::ChatArgs;
+use crate::cli::chat::{
+    ChatArgs,
+    ChatSubcommand,
+};
 use crate::cli::mcp::McpSubcommand;
+use crate::cli::stats::StatsSubcommand;
 use crate::cli::user::{
     LoginArgs,
     WhoamiArgs,
@@ -86,6 +95,7 @@ impl OutputFormat {
 #[derive(Debug, PartialEq, Subcommand)]
 pub enum RootSubcommand {
     /// Manage agents
+    #[command(alias = "agents")]
     Agent(AgentArgs),
     /// AI assistant in your terminal
     Chat(ChatArgs),
@@ -105,6 +115,11 @@ pub enum RootSubcommand {
     Diagnostic(diagnostics::DiagnosticArgs),
     /// Create a new Github issue
     Issue(issue::IssueArgs),
+    /// Build or update the semantic index of the workspace, searched by the semantic_search tool
+    Index(index::IndexArgs),
+    /// Show statistics of the chat sessions run in a directory
+    #[command(subcommand)]
+    Stats(StatsSubcommand),
     /// Version
     #[command(hide = true)]
     Version {
@@ -127,7 +142,12 @@ impl RootSubcommand {
     }
 
     pub fn requires_auth(&self) -> bool {
-        matches!(self, Self::Chat(_) | Self::Profile)
+        match self {
+            // Doctor reports the authentication problems itself.
+            Self::Chat(args) => !matches!(args.subcommand, Some(ChatSubcommand::Doctor(_))),
+            Self::Profile => true,
+            _ => false,
+        }
     }
 
     pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
@@ -153,6 +173,8 @@ impl RootSubcommand {
             Self::Profile => user::profile(os).await,
             Self::Settings(settings_args) => settings_args.execute(os).await,
             Self::Issue(args) => args.execute(os).await,
+            Self::Index(args) => args.execute(os).await,
+            Self::Stats(subcommand) => subcommand.execute(os).await,
             Self::Version { changelog } => Cli::print_version(changelog),
             Self::Chat(args) => args.execute(os).await,
             Self::Mcp(args) => args.execute(os, &mut std::io::stderr()).await,
@@ -178,6 +200,8 @@ impl Display for RootSubcommand {
             Self::Settings(_) => "settings",
             Self::Diagnostic(_) => "diagnostic",
             Self::Issue(_) => "issue",
+            Self::Index(_) => "index",
+            Self::Stats(_) => "stats",
             Self::Version { .. } => "version",
             Self::Mcp(_) => "mcp",
         };
@@ -323,7 +347,11 @@ impl Cli {
 
 #[cfg(test)]
 mod test {
+    use std::path::PathBuf;
+
     use super::*;
+    use crate::cli::chat::ChatSubcommand;
+    use crate::cli::chat::cli::ask_file::AskFileArgs;
     use crate::util::CHAT_BINARY_NAME;
     use crate::util::test::assert_parse;
 
@@ -362,12 +390,39 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
             })),
             verbose: 2,
             help_all: false,
         });
     }
 
+    #[test]
+    fn test_stats_tools() {
+        assert_parse!(
+            ["stats", "tools", "--format", "json"],
+            RootSubcommand::Stats(StatsSubcommand::Tools {
+                path: None,
+                format: OutputFormat::Json,
+                reset: false,
+            })
+        );
+        assert_parse!(
+            ["stats", "tools", "services/api", "--reset"],
+            RootSubcommand::Stats(StatsSubcommand::Tools {
+                path: Some(PathBuf::from("services/api")),
+                format: OutputFormat::Plain,
+                reset: true,
+            })
+        );
+    }
+
     #[test]
     fn test_version_changelog() {
         assert_parse!(["version", "--changelog"], RootSubcommand::Version {
@@ -401,6 +456,13 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
             })
         );
     }
@@ -417,6 +479,13 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
             })
         );
     }
@@ -433,6 +502,13 @@ mod test {
                 trust_all_tools: true,
                 trust_tools: None,
                 no_interactive: false,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
             })
         );
     }
@@ -449,6 +525,13 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: true,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
             })
         );
         assert_parse!(
@@ -461,6 +544,59 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: true,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_ask_file() {
+        assert_parse!(
+            ["chat", "ask", "src/main.rs", "what does this do?"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                no_interactive: false,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: Some(ChatSubcommand::Ask(AskFileArgs {
+                    path: "src/main.rs".to_string(),
+                    question: vec!["what does this do?".to_string()],
+                })),
+            })
+        );
+        assert_parse!(
+            ["chat", "help"],
+            RootSubcommand::Chat(ChatArgs {
+                input: Some("help".to_string()),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_fail_on() {
+        assert_parse!(
+            ["chat", "--no-interactive", "--fail-on", "FAIL|ERROR", "run the tests"],
+            RootSubcommand::Chat(ChatArgs {
+                input: Some("run the tests".to_string()),
+                no_interactive: true,
+                fail_on: Some("FAIL|ERROR".to_string()),
+                ..Default::default()
             })
         );
     }
@@ -477,6 +613,13 @@ mod test {
                 trust_all_tools: true,
                 trust_tools: None,
                 no_interactive: false,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
             })
         );
     }
@@ -493,6 +636,13 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: Some(vec!["".to_string()]),
                 no_interactive: false,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
             })
         );
     }
@@ -509,6 +659,36 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: Some(vec!["fs_read".to_string(), "fs_write".to_string()]),
                 no_interactive: false,
+                quiet: false,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_quiet() {
+        assert_parse!(
+            ["chat", "--no-interactive", "--quiet", "hello"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: Some("hello".to_string()),
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                no_interactive: true,
+                quiet: true,
+                tee: None,
+                setup: false,
+                tui: false,
+                acp: false,
+                fail_on: None,
+                subcommand: None,
             })
         );
     }
@@ -68,13 +68,18 @@ impl SettingsArgs {
             Some(SettingsSubcommands::All { format, state }) => {
                 let settings = match state {
                     true => os.database.get_all_entries()?,
-                    false => os.database.settings.map().clone(),
+                    false => os.database.settings.map(),
                 };
 
                 match format {
                     OutputFormat::Plain => {
                         for (key, value) in settings {
-                            println!("{key} = {value}");
+                            let locked = Setting::try_from(key.as_str())
+                                .is_ok_and(|setting| os.database.settings.is_locked(setting));
+                            match locked {
+                                true => println!("{key} = {value} (locked by policy)"),
+                                false => println!("{key} = {value}"),
+                            }
                         }
                     },
                     OutputFormat::Json => println!("{}", serde_json::to_string(&settings)?),
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Subcommand;
+use eyre::Result;
+
+use super::OutputFormat;
+use crate::cli::chat::tool_stats;
+use crate::os::Os;
+
+#[derive(Clone, Debug, Subcommand, PartialEq, Eq)]
+pub enum StatsSubcommand {
+    /// Show how often each tool was used, approved, and failed in chat sessions run in a directory
+    Tools {
+        /// Directory the chat sessions ran in, the current directory by default
+        path: Option<PathBuf>,
+        /// Format of the output
+        #[arg(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+        /// Clear the statistics of the directory
+        #[arg(long, conflicts_with = "format")]
+        reset: bool,
+    },
+}
+
+impl StatsSubcommand {
+    pub async fn execute(self, os: &Os) -> Result<ExitCode> {
+        match self {
+            Self::Tools { path, format, reset } => {
+                let dir = match path {
+                    Some(path) => os.env.current_dir()?.join(path),
+                    None => os.env.current_dir()?,
+                };
+                if reset {
+                    os.database.set_tool_stats(&dir, &Default::default())?;
+                    eprintln!("Cleared the tool statistics of {}", dir.display());
+                    return Ok(ExitCode::SUCCESS);
+                }
+
+                let stats = tool_stats::load(os, &dir);
+                if stats.tools.is_empty() && format == OutputFormat::Plain {
+                    eprintln!("No tool uses recorded in {} yet", dir.display());
+                    return Ok(ExitCode::SUCCESS);
+                }
+                format.print(
+                    || {
+                        let mut text = stats.to_table();
+                        let suggested = stats.suggested_allowed_tools();
+                        if !suggested.is_empty() {
+                            text.push_str(&format!(
+                                "\nAlways approved when asked, consider adding them to allowedTools: {}",
+                                suggested.join(", ")
+                            ));
+                        }
+                        text.trim_end().to_string()
+                    },
+                    || &stats,
+                );
+                Ok(ExitCode::SUCCESS)
+            },
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod policy;
 pub mod settings;
 
 use std::ops::Deref;
@@ -34,7 +35,10 @@ use tracing::{
 };
 use uuid::Uuid;
 
-use crate::cli::ConversationState;
+use crate::cli::{
+    ConversationState,
+    ToolStats,
+};
 use crate::util::directories::{
     DirectoryError,
     database_path,
@@ -61,6 +65,9 @@ const IDC_REGION_KEY: &str = "auth.idc.region";
 // We include this key to remove for backwards compatibility
 const CUSTOMIZATION_STATE_KEY: &str = "api.selectedCustomization";
 const PROFILE_MIGRATION_KEY: &str = "profile.Migrated";
+const AVAILABLE_MODELS_KEY: &str = "api.codewhisperer.availableModels";
+const INTERRUPTED_SESSION_KEY_PREFIX: &str = "chat.interruptedSession.";
+const TOOL_STATS_KEY_PREFIX: &str = "chat.toolStats.";
 
 const MIGRATIONS: &[Migration] = migrations![
     "000_migration_table",
@@ -70,7 +77,8 @@ const MIGRATIONS: &[Migration] = migrations![
     "004_state_table",
     "005_auth_table",
     "006_make_state_blob",
-    "007_conversations_table"
+    "007_conversations_table",
+    "008_conversation_entries_table"
 ];
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -96,6 +104,45 @@ impl From<amzn_codewhisperer_client::types::Profile> for AuthProfile {
     }
 }
 
+/// A model returned by the ListAvailableModels API.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ModelInfo {
+    pub model_id: String,
+    pub description: Option<String>,
+}
+
+impl From<amzn_codewhisperer_client::types::Model> for ModelInfo {
+    fn from(model: amzn_codewhisperer_client::types::Model) -> Self {
+        Self {
+            model_id: model.model_id,
+            description: model.description,
+        }
+    }
+}
+
+/// The models available to a profile, as of the last time they were listed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CachedModels {
+    /// Unix timestamp in seconds of when the models were listed.
+    pub fetched_at: i64,
+    /// ARN of the profile the models were listed for, if any.
+    #[serde(default)]
+    pub profile_arn: Option<String>,
+    /// Region of the endpoint the models were listed from.
+    #[serde(default)]
+    pub region: String,
+    pub models: Vec<ModelInfo>,
+}
+
+/// A chat session that was terminated by a signal, e.g. when its terminal was closed.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InterruptedSession {
+    /// Name of the signal, e.g. `SIGTERM`.
+    pub signal: String,
+    /// Unix timestamp in seconds of when the session was terminated.
+    pub terminated_at: i64,
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
 #[serde(transparent)]
 pub struct Secret(pub String);
@@ -144,6 +191,10 @@ pub enum DatabaseError {
     StrFromUtf8(#[from] std::str::Utf8Error),
     #[error("`{}` is not a valid setting", .0)]
     InvalidSetting(String),
+    #[error("the managed policy {} is invalid: {}", .0, .1)]
+    InvalidPolicy(String, String),
+    #[error("`{}` is managed by the policy {} and cannot be changed", .0, .1)]
+    LockedSetting(String, String),
 }
 
 impl<T> From<PoisonError<T>> for DatabaseError {
@@ -156,8 +207,12 @@ impl<T> From<PoisonError<T>> for DatabaseError {
 pub enum Table {
     /// The state table contains persistent application state.
     State,
-    /// The conversations tables contains user chat conversations.
+    /// The conversations tables contains user chat conversations. Their history is stored in the
+    /// conversation entries table, except for conversations stored by older versions.
     Conversations,
+    /// The conversation entries table contains the history entries of the conversations, one row
+    /// per entry.
+    ConversationEntries,
     /// The auth table contains SSO and Builder ID credentials.
     Auth,
 }
@@ -167,6 +222,7 @@ impl std::fmt::Display for Table {
         match self {
             Table::State => write!(f, "state"),
             Table::Conversations => write!(f, "conversations"),
+            Table::ConversationEntries => write!(f, "conversation_entries"),
             Table::Auth => write!(f, "auth_kv"),
         }
     }
@@ -258,15 +314,27 @@ impl Database {
     /// Set the current user profile used to determine API endpoints.
     pub fn set_auth_profile(&mut self, profile: &AuthProfile) -> Result<(), DatabaseError> {
         self.set_json_entry(Table::State, CODEWHISPERER_PROFILE_KEY, profile)?;
+        self.delete_entry(Table::State, AVAILABLE_MODELS_KEY)?;
         self.delete_entry(Table::State, CUSTOMIZATION_STATE_KEY)
     }
 
     /// Unset the current user profile used to determine API endpoints.
     pub fn unset_auth_profile(&mut self) -> Result<(), DatabaseError> {
         self.delete_entry(Table::State, CODEWHISPERER_PROFILE_KEY)?;
+        self.delete_entry(Table::State, AVAILABLE_MODELS_KEY)?;
         self.delete_entry(Table::State, CUSTOMIZATION_STATE_KEY)
     }
 
+    /// Get the models last listed, with the profile and region they were listed for.
+    pub fn get_cached_models(&self) -> Result<Option<CachedModels>, DatabaseError> {
+        self.get_json_entry(Table::State, AVAILABLE_MODELS_KEY)
+    }
+
+    /// Set the models listed for a profile and region.
+    pub fn set_cached_models(&self, models: &CachedModels) -> Result<usize, DatabaseError> {
+        self.set_json_entry(Table::State, AVAILABLE_MODELS_KEY, models)
+    }
+
     /// Get the client ID used for telemetry requests.
     pub fn get_client_id(&mut self) -> Result<Option<Uuid>, DatabaseError> {
         Ok(self
@@ -326,10 +394,48 @@ impl Database {
     //     self.delete_entry(Table::State, LAST_USED_MODEL_ID)
     // }
 
-    /// Get a chat conversation given a path to the conversation.
+    /// Records that the chat session running in `path` was terminated by a signal.
+    pub fn set_interrupted_session(
+        &self,
+        path: impl AsRef<Path>,
+        session: &InterruptedSession,
+    ) -> Result<usize, DatabaseError> {
+        let key = format!("{INTERRUPTED_SESSION_KEY_PREFIX}{}", path.as_ref().display());
+        self.set_json_entry(Table::State, key, session)
+    }
+
+    /// Removes and returns the record of the chat session in `path` that was terminated by a
+    /// signal, if any.
+    pub fn take_interrupted_session(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Option<InterruptedSession>, DatabaseError> {
+        let key = format!("{INTERRUPTED_SESSION_KEY_PREFIX}{}", path.as_ref().display());
+        let session = self.get_json_entry(Table::State, &key)?;
+        if session.is_some() {
+            self.delete_entry(Table::State, &key)?;
+        }
+        Ok(session)
+    }
+
+    /// Get the statistics of the tools used in the chat sessions run in `path`.
+    pub fn get_tool_stats(&self, path: impl AsRef<Path>) -> Result<Option<ToolStats>, DatabaseError> {
+        let key = format!("{TOOL_STATS_KEY_PREFIX}{}", path.as_ref().display());
+        self.get_json_entry(Table::State, &key)
+    }
+
+    /// Set the statistics of the tools used in the chat sessions run in `path`.
+    pub fn set_tool_stats(&self, path: impl AsRef<Path>, stats: &ToolStats) -> Result<usize, DatabaseError> {
+        let key = format!("{TOOL_STATS_KEY_PREFIX}{}", path.as_ref().display());
+        self.set_json_entry(Table::State, key, stats)
+    }
+
+    /// Get a chat conversation given a path to the conversation, along with the `tail_len` most
+    /// recent of its history entries, see [ConversationState::restore_history].
     pub fn get_conversation_by_path(
         &mut self,
         path: impl AsRef<Path>,
+        tail_len: usize,
     ) -> Result<Option<ConversationState>, DatabaseError> {
         // We would need to encode this to support non utf8 paths.
         let path = match path.as_ref().to_str() {
@@ -337,22 +443,166 @@ impl Database {
             None => return Ok(None),
         };
 
-        self.get_json_entry(Table::Conversations, path)
+        let Some(mut state) = self.get_json_entry::<ConversationState>(Table::Conversations, path)? else {
+            return Ok(None);
+        };
+        let conn = self.pool.get()?;
+        let (count, min, max): (i64, Option<i64>, Option<i64>) = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), MIN(seq), MAX(seq) FROM {} WHERE key = ?1 AND conversation_id = ?2",
+                Table::ConversationEntries
+            ),
+            params![path, state.conversation_id()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let stored = match (min, max) {
+            (Some(min), Some(max)) => min..max + 1,
+            _ => 0..0,
+        };
+        // Everything is loaded when entries are missing, for the history to be rewritten.
+        let limit = match count == stored.end - stored.start {
+            true => tail_len as i64,
+            false => count,
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT seq, value FROM {} WHERE key = ?1 AND conversation_id = ?2 ORDER BY seq DESC LIMIT ?3",
+            Table::ConversationEntries
+        ))?;
+        let mut tail = stmt
+            .query_map(params![path, state.conversation_id(), limit], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<Vec<(i64, String)>, _>>()?;
+        tail.reverse();
+        state.restore_history(path, stored, tail)?;
+        Ok(Some(state))
+    }
+
+    /// Get the history entries of a chat conversation stored with [Self::set_conversation_by_path],
+    /// as `(sequence number, JSON)` pairs.
+    pub fn get_conversation_entries(
+        &self,
+        path: impl AsRef<Path>,
+        conversation_id: &str,
+        seqs: std::ops::Range<i64>,
+    ) -> Result<Vec<(i64, String)>, DatabaseError> {
+        // We would need to encode this to support non utf8 paths.
+        let path = match path.as_ref().to_str() {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        };
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT seq, value FROM {} WHERE key = ?1 AND conversation_id = ?2 AND seq >= ?3 AND seq < ?4 ORDER BY seq",
+            Table::ConversationEntries
+        ))?;
+        let entries = stmt
+            .query_map(params![path, conversation_id, seqs.start, seqs.end], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    /// Set a chat conversation given a path to the conversation, replacing what was stored for the
+    /// path.
+    ///
+    /// The conversation is stored as its `head`, i.e. the conversation without its history, and
+    /// one row per history entry numbered from 0.
+    pub fn set_conversation_by_path<T: Serialize>(
+        &mut self,
+        path: impl AsRef<Path>,
+        head: &ConversationState,
+        entries: impl IntoIterator<Item = T>,
+    ) -> Result<(), DatabaseError> {
+        // We would need to encode this to support non utf8 paths.
+        let path = match path.as_ref().to_str() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut conn = self.pool.get()?;
+        let transaction = conn.transaction()?;
+        transaction.execute(&format!("DELETE FROM {} WHERE key = ?1", Table::ConversationEntries), [
+            path,
+        ])?;
+        insert_conversation_entries(&transaction, path, head.conversation_id(), 0, entries)?;
+        transaction.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (key, value) VALUES (?1, ?2)",
+                Table::Conversations
+            ),
+            params![path, serde_json::to_string(head)?],
+        )?;
+        transaction.commit()?;
+        Ok(())
     }
 
-    /// Set a chat conversation given a path to the conversation.
-    pub fn set_conversation_by_path(
+    /// Updates a conversation stored with [Self::set_conversation_by_path] without rewriting its
+    /// history: the entries numbered before `keep_from` are deleted, `entries` are appended after
+    /// the `stored` ones, and the head is replaced.
+    ///
+    /// Returns false without writing anything if the entries stored for the path are not the
+    /// `stored` entries of the conversation, e.g. when another session stored its conversation
+    /// in the meantime.
+    pub fn append_conversation_by_path<T: Serialize>(
         &mut self,
         path: impl AsRef<Path>,
-        state: &ConversationState,
-    ) -> Result<usize, DatabaseError> {
+        head: &ConversationState,
+        stored: std::ops::Range<i64>,
+        keep_from: i64,
+        entries: impl IntoIterator<Item = T>,
+    ) -> Result<bool, DatabaseError> {
         // We would need to encode this to support non utf8 paths.
         let path = match path.as_ref().to_str() {
             Some(path) => path,
-            None => return Ok(0),
+            None => return Ok(false),
         };
 
-        self.set_json_entry(Table::Conversations, path, state)
+        let mut conn = self.pool.get()?;
+        let transaction = conn.transaction()?;
+        let (count, min, max): (i64, Option<i64>, Option<i64>) = transaction.query_row(
+            &format!(
+                "SELECT COUNT(*), MIN(seq), MAX(seq) FROM {} WHERE key = ?1 AND conversation_id = ?2",
+                Table::ConversationEntries
+            ),
+            params![path, head.conversation_id()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let total: i64 = transaction.query_row(
+            &format!("SELECT COUNT(*) FROM {} WHERE key = ?1", Table::ConversationEntries),
+            [path],
+            |row| row.get(0),
+        )?;
+        let expected = match stored.is_empty() {
+            true => (0, None, None),
+            false => (stored.end - stored.start, Some(stored.start), Some(stored.end - 1)),
+        };
+        if total != count || (count, min, max) != expected {
+            return Ok(false);
+        }
+
+        transaction.execute(
+            &format!("DELETE FROM {} WHERE key = ?1 AND seq < ?2", Table::ConversationEntries),
+            params![path, keep_from],
+        )?;
+        insert_conversation_entries(
+            &transaction,
+            path,
+            head.conversation_id(),
+            stored.end.max(keep_from),
+            entries,
+        )?;
+        transaction.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (key, value) VALUES (?1, ?2)",
+                Table::Conversations
+            ),
+            params![path, serde_json::to_string(head)?],
+        )?;
+        transaction.commit()?;
+        Ok(true)
     }
 
     pub async fn get_secret(&self, key: &str) -> Result<Option<Secret>, DatabaseError> {
@@ -371,6 +621,16 @@ impl Database {
         self.delete_entry(Table::Auth, key)
     }
 
+    /// Checks the integrity of the database, returning the problems found.
+    pub fn check_integrity(&self) -> Result<Vec<String>, DatabaseError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare("PRAGMA quick_check")?;
+        let problems = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(problems.into_iter().filter(|problem| problem != "ok").collect())
+    }
+
     // Private functions. Do not expose.
 
     fn migrate(self) -> Result<Self, DatabaseError> {
@@ -465,6 +725,23 @@ impl Database {
     }
 }
 
+fn insert_conversation_entries<T: Serialize>(
+    conn: &Connection,
+    path: &str,
+    conversation_id: &str,
+    first_seq: i64,
+    entries: impl IntoIterator<Item = T>,
+) -> Result<(), DatabaseError> {
+    let mut stmt = conn.prepare(&format!(
+        "INSERT OR REPLACE INTO {} (key, conversation_id, seq, value) VALUES (?1, ?2, ?3, ?4)",
+        Table::ConversationEntries
+    ))?;
+    for (seq, entry) in (first_seq..).zip(entries) {
+        stmt.execute(params![path, conversation_id, seq, serde_json::to_string(&entry)?])?;
+    }
+    Ok(())
+}
+
 fn max_migration_version<C: Deref<Target = Connection>>(conn: &C) -> Option<i64> {
     let mut stmt = conn.prepare("SELECT MAX(version) FROM migrations").ok()?;
     stmt.query_row([], |row| row.get(0)).ok()
@@ -543,6 +820,12 @@ mod tests {
         assert_eq!(max_migration, Some(MIGRATIONS.len() as i64 - 1));
     }
 
+    #[tokio::test]
+    async fn test_check_integrity() {
+        let db = Database::new().await.unwrap();
+        assert!(db.check_integrity().unwrap().is_empty());
+    }
+
     #[test]
     fn list_migrations() {
         // Assert the migrations are in order
@@ -614,6 +897,33 @@ mod tests {
         store.delete_secret(key).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_interrupted_session() {
+        let db = Database::new().await.unwrap();
+        let session = InterruptedSession {
+            signal: "SIGHUP".to_string(),
+            terminated_at: 1,
+        };
+        db.set_interrupted_session("/project", &session).unwrap();
+        assert_eq!(db.take_interrupted_session("/other").unwrap(), None);
+        assert_eq!(db.take_interrupted_session("/project").unwrap(), Some(session));
+        assert_eq!(db.take_interrupted_session("/project").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_conversation_entries() {
+        let db = Database::new().await.unwrap();
+        let conn = db.pool.get().unwrap();
+        insert_conversation_entries(&conn, "/project", "conv_id", 3, ["a", "b", "c"]).unwrap();
+        insert_conversation_entries(&conn, "/project", "other_id", 6, ["d"]).unwrap();
+        insert_conversation_entries(&conn, "/other", "conv_id", 0, ["e"]).unwrap();
+
+        let entries = |seqs| db.get_conversation_entries("/project", "conv_id", seqs).unwrap();
+        assert_eq!(entries(4..6), vec![(4, "\"b\"".to_string()), (5, "\"c\"".to_string())]);
+        assert_eq!(entries(0..4), vec![(3, "\"a\"".to_string())]);
+        assert!(entries(6..9).is_empty());
+    }
+
     #[tokio::test]
     #[ignore = "not on ci"]
     async fn secret_delete() {
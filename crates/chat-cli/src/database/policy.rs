@@ -0,0 +1,168 @@
+//! Managed configuration that an administrator installs to enforce settings and restrictions,
+//! which users cannot change. See [Policy].
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use serde::Deserialize;
+use serde_json::{
+    Map,
+    Value,
+};
+
+use super::DatabaseError;
+use super::settings::Setting;
+
+/// Location of the policy file, unless overridden with [POLICY_PATH_ENV].
+pub const POLICY_PATH: &str = "/etc/amazonq/policy.json";
+/// Environment variable overriding [POLICY_PATH].
+pub const POLICY_PATH_ENV: &str = "Q_POLICY_PATH";
+
+/// A read-only policy file, e.g.
+///
+/// ```json
+/// {
+///   "settings": { "chat.enableThinking": false },
+///   "lockedSettings": ["chat.defaultModel"],
+///   "disableTrustAll": true,
+///   "allowedModels": ["claude-4-sonnet"],
+///   "allowedMcpServers": ["git"],
+///   "telemetryMode": "local"
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Policy {
+    /// Path of the file the policy was read from, [None] when there is no policy.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    /// Settings forced to a value.
+    #[serde(default)]
+    pub settings: Map<String, Value>,
+    /// Settings that users cannot change, which keep their default value unless forced by
+    /// [Self::settings].
+    #[serde(default)]
+    pub locked_settings: Vec<String>,
+    /// Disallows trusting all tools, with `--trust-all-tools` or `/tools trust-all`.
+    #[serde(default)]
+    pub disable_trust_all: bool,
+    /// Names or ids of the models that can be used, all of them when [None].
+    #[serde(default)]
+    pub allowed_models: Option<Vec<String>>,
+    /// Names of the MCP servers that can be loaded, all of them when [None].
+    #[serde(default)]
+    pub allowed_mcp_servers: Option<Vec<String>>,
+    /// Forces the `telemetry.mode` setting.
+    #[serde(default)]
+    pub telemetry_mode: Option<String>,
+}
+
+impl Policy {
+    /// Reads the policy at [POLICY_PATH], or the path in [POLICY_PATH_ENV]. A missing file is an
+    /// empty policy, while an invalid one is an error, so that a broken policy is not ignored.
+    pub fn load() -> Result<Self, DatabaseError> {
+        let path = std::env::var_os(POLICY_PATH_ENV).map_or_else(|| PathBuf::from(POLICY_PATH), PathBuf::from);
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, DatabaseError> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut policy: Self = serde_json::from_slice(&content)
+            .map_err(|err| DatabaseError::InvalidPolicy(path.display().to_string(), err.to_string()))?;
+        for key in policy.settings.keys().chain(&policy.locked_settings) {
+            if let Err(err) = Setting::try_from(key.as_str()) {
+                return Err(DatabaseError::InvalidPolicy(
+                    path.display().to_string(),
+                    err.to_string(),
+                ));
+            }
+        }
+        if let Some(mode) = policy.telemetry_mode.take() {
+            policy
+                .settings
+                .insert(Setting::TelemetryMode.as_ref().to_string(), Value::String(mode));
+        }
+        policy.path = Some(path.to_path_buf());
+        Ok(policy)
+    }
+
+    /// Returns the value `key` is forced to.
+    pub fn forced(&self, key: Setting) -> Option<&Value> {
+        self.settings.get(key.as_ref())
+    }
+
+    /// Whether users cannot change `key`.
+    pub fn is_locked(&self, key: Setting) -> bool {
+        self.forced(key).is_some() || self.locked_settings.iter().any(|locked| locked == key.as_ref())
+    }
+
+    /// Whether the model with `model_id`, or named `name`, can be used.
+    pub fn allows_model(&self, model_id: &str, name: Option<&str>) -> bool {
+        self.allowed_models.as_ref().is_none_or(|allowed| {
+            allowed.iter().any(|allowed| {
+                allowed.eq_ignore_ascii_case(model_id) || name.is_some_and(|name| allowed.eq_ignore_ascii_case(name))
+            })
+        })
+    }
+
+    pub fn allows_mcp_server(&self, name: &str) -> bool {
+        self.allowed_mcp_servers
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|allowed| allowed == name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.json");
+        assert_eq!(Policy::load_from(&path).unwrap(), Policy::default());
+
+        std::fs::write(
+            &path,
+            r#"{
+                "settings": { "chat.enableThinking": false },
+                "lockedSettings": ["chat.defaultModel"],
+                "disableTrustAll": true,
+                "allowedModels": ["claude-4-sonnet"],
+                "allowedMcpServers": ["git"],
+                "telemetryMode": "local"
+            }"#,
+        )
+        .unwrap();
+        let policy = Policy::load_from(&path).unwrap();
+        assert_eq!(policy.path, Some(path.clone()));
+        assert_eq!(policy.forced(Setting::EnabledThinking), Some(&Value::Bool(false)));
+        assert_eq!(
+            policy.forced(Setting::TelemetryMode),
+            Some(&Value::String("local".to_string()))
+        );
+        assert!(policy.is_locked(Setting::ChatDefaultModel));
+        assert!(policy.is_locked(Setting::TelemetryMode));
+        assert!(!policy.is_locked(Setting::ChatEditMode));
+        assert!(policy.disable_trust_all);
+        assert!(policy.allows_model("CLAUDE_SONNET_4_20250514_V1_0", Some("claude-4-sonnet")));
+        assert!(!policy.allows_model("CLAUDE_3_7_SONNET_20250219_V1_0", Some("claude-3.7-sonnet")));
+        assert!(policy.allows_mcp_server("git"));
+        assert!(!policy.allows_mcp_server("fetch"));
+        assert!(Policy::default().allows_model("any", None));
+
+        std::fs::write(&path, r#"{ "lockedSettings": ["not.a.setting"] }"#).unwrap();
+        assert!(matches!(
+            Policy::load_from(&path),
+            Err(DatabaseError::InvalidPolicy(..))
+        ));
+        std::fs::write(&path, r#"{ "allowModels": [] }"#).unwrap();
+        assert!(Policy::load_from(&path).is_err());
+    }
+}
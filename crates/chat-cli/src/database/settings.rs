@@ -14,17 +14,24 @@ use tokio::io::{
 };
 
 use super::DatabaseError;
+use super::policy::Policy;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Setting {
     TelemetryEnabled,
+    TelemetryMode,
+    TelemetryOtlpEndpoint,
     OldClientId,
     ShareCodeWhispererContent,
     EnabledThinking,
     EnabledKnowledge,
     SkimCommandKey,
     ChatGreetingEnabled,
+    ChatGreetingMotd,
+    ChatGreetingTipsEnabled,
+    ChatGreetingPolicy,
     ApiTimeout,
+    ApiCaBundle,
     ChatEditMode,
     ChatEnableNotifications,
     ApiCodeWhispererService,
@@ -36,19 +43,112 @@ pub enum Setting {
     ChatDefaultAgent,
     ChatDisableAutoCompaction,
     ChatEnableHistoryHints,
+    ChatEnableAutoTitle,
+    ChatMaxTranscriptEntries,
+    ChatMaxFailedRequestIds,
+    ChatMaxPendingToolTelemetryEvents,
+    ChatExecuteMaxOutputBytes,
+    ChatRedactionEnabled,
+    ChatRedactionPatterns,
+    ChatInjectionDetectionEnabled,
+    ChatInjectionDetectionQuarantine,
+    ChatSecretDetectionEnabled,
+    ChatModelFallbacks,
+    ChatEnablePrefetch,
+    ChatInstantRender,
+    ChatRenderMath,
+    ChatMaxResponseWidth,
+    ChatKeepPartialResponse,
+    ChatTypeAhead,
+    ChatSetupCompleted,
+    ChatQueueOfflinePrompts,
+    ChatLargeRequestWarningKb,
+    ChatSocketAllowedUsers,
+    ChatSocketCommands,
+    ChatShowTimings,
+    ChatAliases,
+    ChatKeymap,
+    ChatResponseLanguage,
+    ChatDiffStyle,
+    ChatTrashRetentionDays,
+}
+
+impl Setting {
+    /// Every setting, in declaration order.
+    pub const ALL: &[Self] = &[
+        Self::TelemetryEnabled,
+        Self::TelemetryMode,
+        Self::TelemetryOtlpEndpoint,
+        Self::OldClientId,
+        Self::ShareCodeWhispererContent,
+        Self::EnabledThinking,
+        Self::EnabledKnowledge,
+        Self::SkimCommandKey,
+        Self::ChatGreetingEnabled,
+        Self::ChatGreetingMotd,
+        Self::ChatGreetingTipsEnabled,
+        Self::ChatGreetingPolicy,
+        Self::ApiTimeout,
+        Self::ApiCaBundle,
+        Self::ChatEditMode,
+        Self::ChatEnableNotifications,
+        Self::ApiCodeWhispererService,
+        Self::ApiQService,
+        Self::McpInitTimeout,
+        Self::McpNoInteractiveTimeout,
+        Self::McpLoadedBefore,
+        Self::ChatDefaultModel,
+        Self::ChatDefaultAgent,
+        Self::ChatDisableAutoCompaction,
+        Self::ChatEnableHistoryHints,
+        Self::ChatEnableAutoTitle,
+        Self::ChatMaxTranscriptEntries,
+        Self::ChatMaxFailedRequestIds,
+        Self::ChatMaxPendingToolTelemetryEvents,
+        Self::ChatExecuteMaxOutputBytes,
+        Self::ChatRedactionEnabled,
+        Self::ChatRedactionPatterns,
+        Self::ChatInjectionDetectionEnabled,
+        Self::ChatInjectionDetectionQuarantine,
+        Self::ChatSecretDetectionEnabled,
+        Self::ChatModelFallbacks,
+        Self::ChatEnablePrefetch,
+        Self::ChatInstantRender,
+        Self::ChatRenderMath,
+        Self::ChatMaxResponseWidth,
+        Self::ChatKeepPartialResponse,
+        Self::ChatTypeAhead,
+        Self::ChatSetupCompleted,
+        Self::ChatQueueOfflinePrompts,
+        Self::ChatLargeRequestWarningKb,
+        Self::ChatSocketAllowedUsers,
+        Self::ChatSocketCommands,
+        Self::ChatShowTimings,
+        Self::ChatAliases,
+        Self::ChatKeymap,
+        Self::ChatResponseLanguage,
+        Self::ChatDiffStyle,
+        Self::ChatTrashRetentionDays,
+    ];
 }
 
 impl AsRef<str> for Setting {
     fn as_ref(&self) -> &'static str {
         match self {
             Self::TelemetryEnabled => "telemetry.enabled",
+            Self::TelemetryMode => "telemetry.mode",
+            Self::TelemetryOtlpEndpoint => "telemetry.otlpEndpoint",
             Self::OldClientId => "telemetryClientId",
             Self::ShareCodeWhispererContent => "codeWhisperer.shareCodeWhispererContentWithAWS",
             Self::EnabledThinking => "chat.enableThinking",
             Self::EnabledKnowledge => "chat.enableKnowledge",
             Self::SkimCommandKey => "chat.skimCommandKey",
             Self::ChatGreetingEnabled => "chat.greeting.enabled",
+            Self::ChatGreetingMotd => "chat.greeting.motd",
+            Self::ChatGreetingTipsEnabled => "chat.greeting.tipsEnabled",
+            Self::ChatGreetingPolicy => "chat.greeting.policy",
             Self::ApiTimeout => "api.timeout",
+            Self::ApiCaBundle => "api.caBundle",
             Self::ChatEditMode => "chat.editMode",
             Self::ChatEnableNotifications => "chat.enableNotifications",
             Self::ApiCodeWhispererService => "api.codewhisperer.service",
@@ -60,6 +160,34 @@ impl AsRef<str> for Setting {
             Self::ChatDefaultAgent => "chat.defaultAgent",
             Self::ChatDisableAutoCompaction => "chat.disableAutoCompaction",
             Self::ChatEnableHistoryHints => "chat.enableHistoryHints",
+            Self::ChatEnableAutoTitle => "chat.enableAutoTitle",
+            Self::ChatMaxTranscriptEntries => "chat.memory.maxTranscriptEntries",
+            Self::ChatMaxFailedRequestIds => "chat.memory.maxFailedRequestIds",
+            Self::ChatMaxPendingToolTelemetryEvents => "chat.memory.maxPendingToolTelemetryEvents",
+            Self::ChatExecuteMaxOutputBytes => "chat.execute.maxOutputBytes",
+            Self::ChatRedactionEnabled => "chat.redaction.enabled",
+            Self::ChatRedactionPatterns => "chat.redaction.patterns",
+            Self::ChatInjectionDetectionEnabled => "chat.injectionDetection.enabled",
+            Self::ChatInjectionDetectionQuarantine => "chat.injectionDetection.quarantine",
+            Self::ChatSecretDetectionEnabled => "chat.secretDetection.enabled",
+            Self::ChatModelFallbacks => "chat.modelFallbacks",
+            Self::ChatEnablePrefetch => "chat.enablePrefetch",
+            Self::ChatInstantRender => "chat.instantRender",
+            Self::ChatRenderMath => "chat.renderMath",
+            Self::ChatMaxResponseWidth => "chat.maxResponseWidth",
+            Self::ChatKeepPartialResponse => "chat.keepPartialResponse",
+            Self::ChatTypeAhead => "chat.typeAhead",
+            Self::ChatSetupCompleted => "chat.setupCompleted",
+            Self::ChatQueueOfflinePrompts => "chat.queueOfflinePrompts",
+            Self::ChatLargeRequestWarningKb => "chat.largeRequestWarningKb",
+            Self::ChatSocketAllowedUsers => "chat.socketAllowedUsers",
+            Self::ChatSocketCommands => "chat.socketCommands",
+            Self::ChatShowTimings => "chat.showTimings",
+            Self::ChatAliases => "chat.aliases",
+            Self::ChatKeymap => "chat.keymap",
+            Self::ChatResponseLanguage => "chat.responseLanguage",
+            Self::ChatDiffStyle => "chat.diffStyle",
+            Self::ChatTrashRetentionDays => "chat.trashRetentionDays",
         }
     }
 }
@@ -76,13 +204,19 @@ impl TryFrom<&str> for Setting {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "telemetry.enabled" => Ok(Self::TelemetryEnabled),
+            "telemetry.mode" => Ok(Self::TelemetryMode),
+            "telemetry.otlpEndpoint" => Ok(Self::TelemetryOtlpEndpoint),
             "telemetryClientId" => Ok(Self::OldClientId),
             "codeWhisperer.shareCodeWhispererContentWithAWS" => Ok(Self::ShareCodeWhispererContent),
             "chat.enableThinking" => Ok(Self::EnabledThinking),
             "chat.enableKnowledge" => Ok(Self::EnabledKnowledge),
             "chat.skimCommandKey" => Ok(Self::SkimCommandKey),
             "chat.greeting.enabled" => Ok(Self::ChatGreetingEnabled),
+            "chat.greeting.motd" => Ok(Self::ChatGreetingMotd),
+            "chat.greeting.tipsEnabled" => Ok(Self::ChatGreetingTipsEnabled),
+            "chat.greeting.policy" => Ok(Self::ChatGreetingPolicy),
             "api.timeout" => Ok(Self::ApiTimeout),
+            "api.caBundle" => Ok(Self::ApiCaBundle),
             "chat.editMode" => Ok(Self::ChatEditMode),
             "chat.enableNotifications" => Ok(Self::ChatEnableNotifications),
             "api.codewhisperer.service" => Ok(Self::ApiCodeWhispererService),
@@ -94,19 +228,53 @@ impl TryFrom<&str> for Setting {
             "chat.defaultAgent" => Ok(Self::ChatDefaultAgent),
             "chat.disableAutoCompaction" => Ok(Self::ChatDisableAutoCompaction),
             "chat.enableHistoryHints" => Ok(Self::ChatEnableHistoryHints),
+            "chat.enableAutoTitle" => Ok(Self::ChatEnableAutoTitle),
+            "chat.memory.maxTranscriptEntries" => Ok(Self::ChatMaxTranscriptEntries),
+            "chat.memory.maxFailedRequestIds" => Ok(Self::ChatMaxFailedRequestIds),
+            "chat.memory.maxPendingToolTelemetryEvents" => Ok(Self::ChatMaxPendingToolTelemetryEvents),
+            "chat.execute.maxOutputBytes" => Ok(Self::ChatExecuteMaxOutputBytes),
+            "chat.redaction.enabled" => Ok(Self::ChatRedactionEnabled),
+            "chat.redaction.patterns" => Ok(Self::ChatRedactionPatterns),
+            "chat.injectionDetection.enabled" => Ok(Self::ChatInjectionDetectionEnabled),
+            "chat.injectionDetection.quarantine" => Ok(Self::ChatInjectionDetectionQuarantine),
+            "chat.secretDetection.enabled" => Ok(Self::ChatSecretDetectionEnabled),
+            "chat.modelFallbacks" => Ok(Self::ChatModelFallbacks),
+            "chat.enablePrefetch" => Ok(Self::ChatEnablePrefetch),
+            "chat.instantRender" => Ok(Self::ChatInstantRender),
+            "chat.renderMath" => Ok(Self::ChatRenderMath),
+            "chat.maxResponseWidth" => Ok(Self::ChatMaxResponseWidth),
+            "chat.keepPartialResponse" => Ok(Self::ChatKeepPartialResponse),
+            "chat.typeAhead" => Ok(Self::ChatTypeAhead),
+            "chat.setupCompleted" => Ok(Self::ChatSetupCompleted),
+            "chat.queueOfflinePrompts" => Ok(Self::ChatQueueOfflinePrompts),
+            "chat.largeRequestWarningKb" => Ok(Self::ChatLargeRequestWarningKb),
+            "chat.socketAllowedUsers" => Ok(Self::ChatSocketAllowedUsers),
+            "chat.socketCommands" => Ok(Self::ChatSocketCommands),
+            "chat.showTimings" => Ok(Self::ChatShowTimings),
+            "chat.aliases" => Ok(Self::ChatAliases),
+            "chat.keymap" => Ok(Self::ChatKeymap),
+            "chat.responseLanguage" => Ok(Self::ChatResponseLanguage),
+            "chat.diffStyle" => Ok(Self::ChatDiffStyle),
+            "chat.trashRetentionDays" => Ok(Self::ChatTrashRetentionDays),
             _ => Err(DatabaseError::InvalidSetting(value.to_string())),
         }
     }
 }
 
+/// The settings of the user, merged with the managed [Policy].
 #[derive(Debug, Clone, Default)]
-pub struct Settings(Map<String, Value>);
+pub struct Settings {
+    /// The settings stored in the settings file.
+    values: Map<String, Value>,
+    policy: Policy,
+}
 
 impl Settings {
     pub async fn new() -> Result<Self, DatabaseError> {
         if cfg!(test) {
             return Ok(Self::default());
         }
+        let policy = Policy::load()?;
 
         let path = crate::util::directories::settings_path()?;
 
@@ -117,7 +285,7 @@ impl Settings {
             }
         }
 
-        Ok(Self(match path.exists() {
+        let values = match path.exists() {
             true => {
                 let mut file = RwLock::new(File::open(&path).await?);
                 let mut buf = Vec::new();
@@ -129,28 +297,72 @@ impl Settings {
                 file.write()?.write_all(b"{}").await?;
                 serde_json::Map::new()
             },
-        }))
+        };
+        Ok(Self { values, policy })
+    }
+
+    /// Returns the settings that are set, including those forced by the policy.
+    pub fn map(&self) -> Map<String, Value> {
+        let mut map = self
+            .values
+            .iter()
+            .filter(|(key, _)| Setting::try_from(key.as_str()).is_ok_and(|key| !self.policy.is_locked(key)))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Map<_, _>>();
+        map.extend(self.policy.settings.clone());
+        map
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
     }
 
-    pub fn map(&self) -> &'_ Map<String, Value> {
-        &self.0
+    /// Whether `key` cannot be changed because of the policy.
+    pub fn is_locked(&self, key: Setting) -> bool {
+        self.policy.is_locked(key)
     }
 
     pub fn get(&self, key: Setting) -> Option<&Value> {
-        self.0.get(key.as_ref())
+        match self.policy.is_locked(key) {
+            true => self.policy.forced(key),
+            false => self.values.get(key.as_ref()),
+        }
     }
 
     pub async fn set(&mut self, key: Setting, value: impl Into<serde_json::Value>) -> Result<(), DatabaseError> {
-        self.0.insert(key.to_string(), value.into());
+        self.check_unlocked(key)?;
+        self.values.insert(key.to_string(), value.into());
+        self.save_to_file().await
+    }
+
+    /// Sets all of `entries`, writing the settings file once.
+    pub async fn set_all(&mut self, entries: Vec<(Setting, Value)>) -> Result<(), DatabaseError> {
+        for (key, _) in &entries {
+            self.check_unlocked(*key)?;
+        }
+        for (key, value) in entries {
+            self.values.insert(key.to_string(), value);
+        }
         self.save_to_file().await
     }
 
     pub async fn remove(&mut self, key: Setting) -> Result<Option<Value>, DatabaseError> {
-        let key = self.0.remove(key.as_ref());
+        self.check_unlocked(key)?;
+        let key = self.values.remove(key.as_ref());
         self.save_to_file().await?;
         Ok(key)
     }
 
+    fn check_unlocked(&self, key: Setting) -> Result<(), DatabaseError> {
+        match (self.policy.is_locked(key), &self.policy.path) {
+            (true, Some(path)) => Err(DatabaseError::LockedSetting(
+                key.to_string(),
+                path.display().to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
     pub fn get_bool(&self, key: Setting) -> Option<bool> {
         self.get(key).and_then(|value| value.as_bool())
     }
@@ -185,7 +397,7 @@ impl Settings {
         let mut file = RwLock::new(file_opts.open(&path).await?);
         let mut lock = file.write()?;
 
-        match serde_json::to_string_pretty(&self.0) {
+        match serde_json::to_string_pretty(&self.values) {
             Ok(json) => lock.write_all(json.as_bytes()).await?,
             Err(_err) => {
                 lock.seek(SeekFrom::Start(0)).await?;
@@ -245,4 +457,58 @@ mod test {
         assert_eq!(settings.get(Setting::ShareCodeWhispererContent), None);
         assert_eq!(settings.get(Setting::McpLoadedBefore), None);
     }
+
+    #[tokio::test]
+    async fn test_policy() {
+        let mut settings = Settings {
+            values: serde_json::from_value(serde_json::json!({
+                "chat.defaultModel": "model 1",
+                "chat.editMode": "vi",
+                "telemetry.mode": "full",
+            }))
+            .unwrap(),
+            policy: Policy {
+                path: Some("/etc/amazonq/policy.json".into()),
+                settings: serde_json::from_value(serde_json::json!({ "telemetry.mode": "off" })).unwrap(),
+                locked_settings: vec!["chat.defaultModel".to_string()],
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(settings.get(Setting::ChatDefaultModel), None);
+        assert_eq!(settings.get_string(Setting::TelemetryMode).as_deref(), Some("off"));
+        assert_eq!(settings.get_string(Setting::ChatEditMode).as_deref(), Some("vi"));
+        assert_eq!(
+            settings.map(),
+            serde_json::from_value::<Map<String, Value>>(serde_json::json!({
+                "chat.editMode": "vi",
+                "telemetry.mode": "off",
+            }))
+            .unwrap()
+        );
+
+        assert!(matches!(
+            settings.set(Setting::TelemetryMode, "full").await,
+            Err(DatabaseError::LockedSetting(..))
+        ));
+        assert!(settings.remove(Setting::ChatDefaultModel).await.is_err());
+        assert!(
+            settings
+                .set_all(vec![
+                    (Setting::ChatEditMode, "emacs".into()),
+                    (Setting::TelemetryMode, "full".into())
+                ])
+                .await
+                .is_err()
+        );
+        assert_eq!(settings.get_string(Setting::ChatEditMode).as_deref(), Some("vi"));
+        settings.set(Setting::ChatEditMode, "emacs").await.unwrap();
+    }
+
+    #[test]
+    fn test_all_keys_round_trip() {
+        for setting in Setting::ALL {
+            assert_eq!(Setting::try_from(setting.as_ref()).unwrap().as_ref(), setting.as_ref());
+        }
+    }
 }
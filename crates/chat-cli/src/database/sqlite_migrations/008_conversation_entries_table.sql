@@ -0,0 +1,7 @@
+CREATE TABLE conversation_entries (
+    key TEXT NOT NULL,
+    conversation_id TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (key, seq)
+);
@@ -14,6 +14,8 @@ use std::process::ExitCode;
 
 use anstream::eprintln;
 use clap::Parser;
+#[cfg(feature = "test-harness")]
+pub use cli::test_harness;
 use crossterm::style::Stylize;
 use eyre::Result;
 use logging::get_log_level_max;
@@ -186,6 +186,15 @@ impl Client<StdioTransport> {
             #[cfg(not(windows))]
             command.process_group(0);
 
+            // Servers reaching the network through an intercepting proxy trust the same authorities.
+            if let Some(path) = crate::request::ca_bundle() {
+                for name in ["NODE_EXTRA_CA_CERTS", "AWS_CA_BUNDLE"] {
+                    if std::env::var_os(name).is_none() {
+                        command.env(name, path);
+                    }
+                }
+            }
+
             if let Some(env) = env {
                 for (env_name, env_value) in env {
                     command.env(env_name, env_value);
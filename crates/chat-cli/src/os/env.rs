@@ -117,6 +117,23 @@ impl Env {
         }
     }
 
+    /// Returns all environment variables of the current process that are valid unicode.
+    pub fn vars(&self) -> Vec<(String, String)> {
+        use inner::Inner;
+        match &self.0 {
+            Inner::Real => env::vars_os()
+                .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+                .collect(),
+            Inner::Fake(fake) => fake
+                .lock()
+                .unwrap()
+                .vars
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
     pub fn home(&self) -> Option<PathBuf> {
         match &self.0 {
             inner::Inner::Real => dirs::home_dir(),
@@ -132,6 +149,14 @@ impl Env {
         }
     }
 
+    /// Sets the current directory of a fake environment.
+    #[cfg(test)]
+    pub fn set_current_dir(&self, path: impl Into<PathBuf>) {
+        if let inner::Inner::Fake(fake) = &self.0 {
+            fake.lock().unwrap().cwd = path.into();
+        }
+    }
+
     pub fn current_exe(&self) -> Result<PathBuf, io::Error> {
         use inner::Inner;
         match &self.0 {
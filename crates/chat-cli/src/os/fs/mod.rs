@@ -230,6 +230,27 @@ impl Fs {
         }
     }
 
+    /// Appends `contents` to the file at `path`, creating it if it does not exist.
+    pub async fn append(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let path = match self {
+            Self::Real => path.as_ref().to_path_buf(),
+            Self::Chroot(root) => append(root.path(), path),
+            Self::Fake(map) => {
+                let Ok(mut lock) = map.lock() else {
+                    return Err(io::Error::other("poisoned lock"));
+                };
+                lock.entry(path.as_ref().to_owned())
+                    .or_default()
+                    .extend_from_slice(contents.as_ref());
+                return Ok(());
+            },
+        };
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(path).await?;
+        file.write_all(contents.as_ref()).await
+    }
+
     /// Removes a file from the filesystem.
     ///
     /// Note that there is no guarantee that the file is immediately deleted (e.g.
@@ -381,6 +402,17 @@ impl Fs {
         }
     }
 
+    /// Queries the file system metadata of `path`, following symbolic links.
+    ///
+    /// This is a proxy to [`tokio::fs::metadata`].
+    pub async fn metadata(&self, path: impl AsRef<Path>) -> io::Result<std::fs::Metadata> {
+        match self {
+            Self::Real => fs::metadata(path).await,
+            Self::Chroot(root) => fs::metadata(append(root.path(), path)).await,
+            Self::Fake(_) => panic!("unimplemented"),
+        }
+    }
+
     /// Reads a symbolic link, returning the file that the link points to.
     ///
     /// This is a proxy to [`tokio::fs::read_link`].
@@ -466,6 +498,8 @@ mod tests {
         fs.write(dir.join("write"), b"write").await.unwrap();
         assert_eq!(fs.read(dir.join("write")).await.unwrap(), b"write");
         assert_eq!(fs.read_to_string(dir.join("write")).await.unwrap(), "write");
+        fs.append(dir.join("write"), b" append").await.unwrap();
+        assert_eq!(fs.read_to_string(dir.join("write")).await.unwrap(), "write append");
     }
 
     #[tokio::test]
@@ -45,6 +45,7 @@ impl Os {
         let env = Env::new();
         let fs = Fs::new();
         let mut database = Database::new().await?;
+        crate::request::configure(&database.settings);
         let client = ApiClient::new(&env, &fs, &mut database, None).await?;
         let telemetry = TelemetryThread::new(&env, &fs, &mut database).await?;
 
@@ -1,17 +1,41 @@
 use std::env::current_exe;
+use std::io::BufReader;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::sync::{
     Arc,
     LazyLock,
+    OnceLock,
 };
 
-use reqwest::Client;
+use reqwest::{
+    Client,
+    ClientBuilder,
+    NoProxy,
+    Proxy,
+};
+use rustls::pki_types::CertificateDer;
 use rustls::{
     ClientConfig,
     RootCertStore,
 };
 use thiserror::Error;
+use tracing::{
+    debug,
+    error,
+};
 use url::ParseError;
 
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+/// Path of a PEM bundle of additional certificate authorities, set by [configure].
+static CA_BUNDLE: OnceLock<Option<PathBuf>> = OnceLock::new();
+
 #[derive(Debug, Error)]
 pub enum RequestError {
     #[error(transparent)]
@@ -29,11 +53,79 @@ pub enum RequestError {
 }
 
 pub fn new_client() -> Result<Client, RequestError> {
-    Ok(Client::builder()
+    Ok(client_builder().build()?)
+}
+
+/// The builder of [new_client], for clients that need other options such as a redirect policy.
+pub fn client_builder() -> ClientBuilder {
+    // The proxies are set explicitly rather than left to reqwest so that invalid ones are reported.
+    let mut builder = Client::builder()
         .use_preconfigured_tls(client_config())
         .user_agent(USER_AGENT.chars().filter(|c| c.is_ascii_graphic()).collect::<String>())
         .cookie_store(true)
-        .build()?)
+        .no_proxy();
+    match env_proxies(|name| std::env::var(name).ok()) {
+        Ok(proxies) => {
+            for proxy in proxies {
+                builder = builder.proxy(proxy);
+            }
+        },
+        Err(err) => error!("Ignoring the invalid proxy configuration: {err}"),
+    }
+    builder
+}
+
+/// Applies the network settings to the clients created afterwards, i.e. the `api.caBundle`
+/// setting, or the `AWS_CA_BUNDLE` environment variable when it is not set.
+///
+/// Only the first call has an effect, since the settings are loaded once per process.
+pub fn configure(settings: &Settings) {
+    let path = settings
+        .get_string(Setting::ApiCaBundle)
+        .or_else(|| std::env::var("AWS_CA_BUNDLE").ok())
+        .filter(|path| !path.trim().is_empty())
+        .map(|path| PathBuf::from(shellexpand::tilde(path.trim()).into_owned()));
+    let _ = CA_BUNDLE.set(path);
+}
+
+/// Path of the PEM bundle of additional certificate authorities trusted by the clients.
+pub fn ca_bundle() -> Option<&'static Path> {
+    CA_BUNDLE.get().and_then(|path| path.as_deref())
+}
+
+/// Reads the certificates of a PEM bundle.
+pub fn load_ca_bundle(path: &Path) -> Result<Vec<CertificateDer<'static>>, RequestError> {
+    let file = std::fs::File::open(path)?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file)).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "no certificate found").into());
+    }
+    Ok(certs)
+}
+
+/// Returns the proxies set by `HTTPS_PROXY`, `HTTP_PROXY`, and `ALL_PROXY`, or their lowercase
+/// variants, which are not used for the hosts listed in `NO_PROXY`.
+pub fn env_proxies(get: impl Fn(&str) -> Option<String>) -> Result<Vec<Proxy>, reqwest::Error> {
+    let var = |names: [&str; 2]| {
+        names
+            .into_iter()
+            .find_map(&get)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let no_proxy = var(["NO_PROXY", "no_proxy"]).and_then(|value| NoProxy::from_string(&value));
+
+    let mut proxies = Vec::new();
+    if let Some(url) = var(["HTTPS_PROXY", "https_proxy"]) {
+        proxies.push(Proxy::https(url)?.no_proxy(no_proxy.clone()));
+    }
+    if let Some(url) = var(["HTTP_PROXY", "http_proxy"]) {
+        proxies.push(Proxy::http(url)?.no_proxy(no_proxy.clone()));
+    }
+    if let Some(url) = var(["ALL_PROXY", "all_proxy"]) {
+        proxies.push(Proxy::all(url)?.no_proxy(no_proxy));
+    }
+    Ok(proxies)
 }
 
 pub fn create_default_root_cert_store() -> RootCertStore {
@@ -54,10 +146,21 @@ fn client_config() -> ClientConfig {
         .cloned()
         .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider()));
 
+    let mut root_cert_store = create_default_root_cert_store();
+    if let Some(path) = ca_bundle() {
+        match load_ca_bundle(path) {
+            Ok(certs) => {
+                let (added, ignored) = root_cert_store.add_parsable_certificates(certs);
+                debug!(added, ignored, ?path, "loaded the CA bundle");
+            },
+            Err(err) => error!("Unable to load the CA bundle {}: {err}", path.display()),
+        }
+    }
+
     ClientConfig::builder_with_provider(provider)
         .with_protocol_versions(rustls::DEFAULT_VERSIONS)
         .expect("Failed to set supported TLS versions")
-        .with_root_certificates(create_default_root_cert_store())
+        .with_root_certificates(root_cert_store)
         .with_no_client_auth()
 }
 
@@ -102,4 +205,40 @@ mod tests {
 
         mock.expect(1).assert();
     }
+
+    #[test]
+    fn test_env_proxies() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| (*v).to_string())
+        };
+        assert_eq!(env_proxies(env(&[])).unwrap().len(), 0);
+        assert_eq!(
+            env_proxies(env(&[
+                ("https_proxy", "http://proxy.example.com:3128"),
+                ("NO_PROXY", "localhost,.internal")
+            ]))
+            .unwrap()
+            .len(),
+            1
+        );
+        assert_eq!(
+            env_proxies(env(&[
+                ("HTTPS_PROXY", "proxy.example.com:3128"),
+                ("ALL_PROXY", "socks5://localhost:1080")
+            ]))
+            .unwrap()
+            .len(),
+            2
+        );
+        assert!(env_proxies(env(&[("HTTPS_PROXY", "http://[invalid")])).is_err());
+    }
+
+    #[test]
+    fn test_load_ca_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ca.pem");
+        std::fs::write(&path, "not a certificate").unwrap();
+        assert!(load_ca_bundle(&path).is_err());
+        assert!(load_ca_bundle(&dir.path().join("missing.pem")).is_err());
+    }
 }
@@ -13,6 +13,7 @@ use crate::telemetry::definitions::IntoMetricDatum;
 use crate::telemetry::definitions::metrics::{
     AmazonqDidSelectProfile,
     AmazonqEndChat,
+    AmazonqMessageFeedback,
     AmazonqMessageResponseError,
     AmazonqProfileState,
     AmazonqStartChat,
@@ -168,6 +169,7 @@ impl Event {
                         tool_use_id,
                         assistant_response_length,
                         message_meta_tags,
+                        ..
                     },
             } => Some(
                 CodewhispererterminalAddChatMessage {
@@ -225,6 +227,8 @@ impl Event {
                         follow_up_count,
                         user_prompt_length,
                         message_meta_tags,
+                        stream_time_ms,
+                        tool_time_ms,
                     },
             } => Some(
                 CodewhispererterminalRecordUserTurnCompletion {
@@ -260,6 +264,8 @@ impl Event {
                     codewhispererterminal_assistant_response_length: Some(assistant_response_length.into()),
                     codewhispererterminal_user_turn_duration_seconds: Some(user_turn_duration_seconds.into()),
                     codewhispererterminal_follow_up_count: Some(follow_up_count.into()),
+                    codewhispererterminal_stream_time_ms: Some(stream_time_ms.into()),
+                    codewhispererterminal_tool_time_ms: Some(tool_time_ms.into()),
                     codewhispererterminal_user_prompt_length: Some(user_prompt_length.into()),
                     codewhispererterminal_chat_message_meta_tags: Some(
                         message_meta_tags
@@ -287,6 +293,7 @@ impl Event {
                 output_token_size,
                 custom_tool_call_latency,
                 model,
+                ..
             } => Some(
                 CodewhispererterminalToolUseSuggested {
                     create_time: self.created_time,
@@ -400,6 +407,24 @@ impl Event {
                 }
                 .into_metric_datum(),
             ),
+            EventType::MessageFeedback {
+                conversation_id,
+                message_id,
+                vote,
+                comment,
+            } => Some(
+                AmazonqMessageFeedback {
+                    create_time: self.created_time,
+                    value: None,
+                    credential_start_url: self.credential_start_url.map(Into::into),
+                    sso_region: self.sso_region.map(Into::into),
+                    amazonq_conversation_id: Some(conversation_id.into()),
+                    codewhispererterminal_utterance_id: Some(message_id.into()),
+                    codewhispererterminal_feedback_vote: Some(vote.to_string().into()),
+                    reason_desc: comment.map(Into::into),
+                }
+                .into_metric_datum(),
+            ),
         }
     }
 }
@@ -444,6 +469,14 @@ pub struct ChatAddedMessageParams {
     pub tool_use_id: Option<String>,
     pub assistant_response_length: Option<i32>,
     pub message_meta_tags: Vec<MessageMetaTag>,
+    /// Unix timestamp (milliseconds) immediately before sending the request.
+    pub request_start_timestamp_ms: Option<u64>,
+    /// Unix timestamp (milliseconds) once the response stream ended.
+    pub stream_end_timestamp_ms: Option<u64>,
+    /// Estimated number of tokens sent with the request.
+    pub input_token_count: Option<usize>,
+    /// Estimated number of tokens of the response.
+    pub output_token_count: Option<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, Default)]
@@ -460,6 +493,8 @@ pub struct RecordUserTurnCompletionArgs {
     pub user_turn_duration_seconds: i64,
     pub follow_up_count: i64,
     pub message_meta_tags: Vec<MessageMetaTag>,
+    pub stream_time_ms: i64,
+    pub tool_time_ms: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -516,6 +551,8 @@ pub enum EventType {
         output_token_size: Option<usize>,
         custom_tool_call_latency: Option<usize>,
         model: Option<String>,
+        execution_start_timestamp_ms: Option<u64>,
+        execution_duration_ms: Option<u64>,
     },
     McpServerInit {
         conversation_id: String,
@@ -546,6 +583,12 @@ pub enum EventType {
         message_id: Option<String>,
         context_file_length: Option<usize>,
     },
+    MessageFeedback {
+        conversation_id: String,
+        message_id: String,
+        vote: FeedbackVote,
+        comment: Option<String>,
+    },
 }
 
 #[derive(Debug)]
@@ -564,6 +607,10 @@ pub struct ToolUseEventBuilder {
     pub output_token_size: Option<usize>,
     pub custom_tool_call_latency: Option<usize>,
     pub model: Option<String>,
+    /// Unix timestamp (milliseconds) at which the tool started executing.
+    pub execution_start_timestamp_ms: Option<u64>,
+    /// Time taken to execute the tool, in milliseconds.
+    pub execution_duration_ms: Option<u64>,
 }
 
 impl ToolUseEventBuilder {
@@ -583,6 +630,8 @@ impl ToolUseEventBuilder {
             output_token_size: None,
             custom_tool_call_latency: None,
             model,
+            execution_start_timestamp_ms: None,
+            execution_duration_ms: None,
         }
     }
 
@@ -638,6 +687,17 @@ pub enum TelemetryResult {
 /// 'auth' -> users change the profile through dashboard
 /// 'update' -> CLI auto select the profile on users' behalf as there is only 1 profile
 /// 'reload' -> CLI will try to reload previous selected profile upon CLI is running
+/// Rating of a response given with `/feedback`.
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, EnumString, Display, serde::Serialize, serde::Deserialize, clap::ValueEnum,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackVote {
+    Up,
+    Down,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, EnumString, Display, serde::Serialize, serde::Deserialize)]
 pub enum QProfileSwitchIntent {
     User,
@@ -3,16 +3,21 @@ pub mod core;
 pub mod definitions;
 pub mod endpoint;
 mod install_method;
+mod otlp;
 
 use core::{
     ChatAddedMessageParams,
+    FeedbackVote,
     RecordUserTurnCompletionArgs,
     ToolUseEventBuilder,
 };
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use amzn_codewhisperer_client::types::{
     ChatAddMessageEvent,
+    ChatInteractWithMessageEvent,
+    ChatMessageInteractionType,
     IdeCategory,
     OperatingSystem,
     TelemetryEvent,
@@ -35,6 +40,12 @@ pub use install_method::{
     InstallMethod,
     get_install_method,
 };
+use otlp::OtlpExporter;
+use strum::{
+    Display,
+    EnumString,
+};
+use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tokio::time::error::Elapsed;
@@ -70,6 +81,7 @@ pub use crate::telemetry::core::{
     QProfileSwitchIntent,
     TelemetryResult,
 };
+use crate::util::directories;
 use crate::util::system_info::os_version;
 
 #[derive(thiserror::Error, Debug)]
@@ -110,6 +122,40 @@ const PRODUCT: &str = "CodeWhisperer";
 const PRODUCT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const CLIENT_ID_ENV_VAR: &str = "Q_TELEMETRY_CLIENT_ID";
 
+/// Where telemetry events are sent, see [Setting::TelemetryMode].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum TelemetryMode {
+    /// No event is emitted.
+    Off,
+    /// Events are only written to [directories::local_telemetry_path], for the user's own
+    /// analysis.
+    Local,
+    /// Events are sent to the telemetry services.
+    Full,
+}
+
+impl TelemetryMode {
+    /// Returns the mode set with [Setting::TelemetryMode], defaulting to
+    /// [Setting::TelemetryEnabled] for compatibility. `Q_DISABLE_TELEMETRY` turns telemetry off
+    /// regardless.
+    pub fn from_settings(env: &Env, database: &Database) -> Self {
+        if cfg!(test) || env.get_os("Q_DISABLE_TELEMETRY").is_some() {
+            return Self::Off;
+        }
+        match database.settings.get_string(Setting::TelemetryMode) {
+            Some(mode) => Self::from_str(&mode.to_lowercase()).unwrap_or_else(|_| {
+                error!(%mode, "Invalid telemetry mode, expected off, local, or full");
+                Self::Off
+            }),
+            None => match database.settings.get_bool(Setting::TelemetryEnabled).unwrap_or(true) {
+                true => Self::Full,
+                false => Self::Off,
+            },
+        }
+    }
+}
+
 /// A IDE toolkit telemetry stage
 #[derive(Debug, Clone)]
 #[non_exhaustive]
@@ -179,6 +225,7 @@ impl Clone for TelemetrySender {
 pub struct TelemetryThread {
     handle: Option<JoinHandle<()>>,
     tx: TelemetrySender,
+    mode: TelemetryMode,
 }
 
 impl Clone for TelemetryThread {
@@ -186,6 +233,7 @@ impl Clone for TelemetryThread {
         Self {
             handle: None,
             tx: self.tx.clone(),
+            mode: self.mode,
         }
     }
 }
@@ -193,6 +241,7 @@ impl Clone for TelemetryThread {
 impl TelemetryThread {
     pub async fn new(env: &Env, fs: &Fs, database: &mut Database) -> Result<Self, TelemetryError> {
         let telemetry_client = TelemetryClient::new(env, fs, database).await?;
+        let mode = telemetry_client.mode;
         let (tx, mut rx) = mpsc::unbounded_channel();
         let tx = TelemetrySender::Strong(tx);
         let handle = tokio::spawn(async move {
@@ -205,9 +254,14 @@ impl TelemetryThread {
         Ok(Self {
             handle: Some(handle),
             tx,
+            mode,
         })
     }
 
+    pub fn mode(&self) -> TelemetryMode {
+        self.mode
+    }
+
     pub async fn finish(self) -> Result<(), TelemetryError> {
         drop(self.tx);
         if let Some(handle) = self.handle {
@@ -306,6 +360,8 @@ impl TelemetryThread {
             output_token_size: event.output_token_size,
             custom_tool_call_latency: event.custom_tool_call_latency,
             model: event.model,
+            execution_start_timestamp_ms: event.execution_start_timestamp_ms,
+            execution_duration_ms: event.execution_duration_ms,
         }))?)
     }
 
@@ -383,6 +439,25 @@ impl TelemetryThread {
 
         Ok(self.tx.send(event)?)
     }
+
+    pub async fn send_message_feedback(
+        &self,
+        database: &Database,
+        conversation_id: String,
+        message_id: String,
+        vote: FeedbackVote,
+        comment: Option<String>,
+    ) -> Result<(), TelemetryError> {
+        let mut event = Event::new(EventType::MessageFeedback {
+            conversation_id,
+            message_id,
+            vote,
+            comment,
+        });
+        set_start_url_and_region(database, &mut event).await;
+
+        Ok(self.tx.send(event)?)
+    }
 }
 
 async fn set_start_url_and_region(database: &Database, event: &mut Event) {
@@ -398,16 +473,25 @@ async fn set_start_url_and_region(database: &Database, event: &mut Event) {
 #[derive(Debug)]
 struct TelemetryClient {
     client_id: Uuid,
+    mode: TelemetryMode,
     telemetry_enabled: bool,
+    /// File the events are written to in [TelemetryMode::Local].
+    local_path: Option<PathBuf>,
     codewhisperer_client: Option<ApiClient>,
     toolkit_telemetry_client: Option<ToolkitTelemetryClient>,
+    /// Exports spans to the collector configured with [Setting::TelemetryOtlpEndpoint],
+    /// independently of whether telemetry is enabled.
+    otlp_exporter: Option<OtlpExporter>,
 }
 
 impl TelemetryClient {
     async fn new(env: &Env, fs: &Fs, database: &mut Database) -> Result<Self, TelemetryError> {
-        let telemetry_enabled = !cfg!(test)
-            && env.get_os("Q_DISABLE_TELEMETRY").is_none()
-            && database.settings.get_bool(Setting::TelemetryEnabled).unwrap_or(true);
+        let mode = TelemetryMode::from_settings(env, database);
+        let telemetry_enabled = mode == TelemetryMode::Full;
+        let local_path = match mode {
+            TelemetryMode::Local => directories::local_telemetry_path().ok(),
+            _ => None,
+        };
 
         // If telemetry is disabled we do not emit using toolkit_telemetry
         let toolkit_telemetry_client = if telemetry_enabled {
@@ -457,25 +541,49 @@ impl TelemetryClient {
         }
 
         // cw telemetry is only available with bearer token auth.
-        let codewhisperer_client = if env.get("AMAZON_Q_SIGV4").is_ok() {
+        let codewhisperer_client = if !telemetry_enabled || env.get("AMAZON_Q_SIGV4").is_ok() {
             None
         } else {
             Some(ApiClient::new(env, fs, database, None).await?)
         };
 
+        let otlp_exporter = database
+            .settings
+            .get_string(Setting::TelemetryOtlpEndpoint)
+            .filter(|endpoint| !endpoint.is_empty())
+            .and_then(|endpoint| match OtlpExporter::new(&endpoint) {
+                Ok(exporter) => Some(exporter),
+                Err(err) => {
+                    error!(%err, "Failed to create the OTLP exporter");
+                    None
+                },
+            });
+
         Ok(Self {
             client_id: client_id(env, database, telemetry_enabled)?,
+            mode,
             telemetry_enabled,
+            local_path,
             toolkit_telemetry_client,
             codewhisperer_client,
+            otlp_exporter,
         })
     }
 
-    /// Sends a telemetry event to both the CW and toolkit API's. If the clients do not exist, then
-    /// telemetry is not sent.
+    /// Sends a telemetry event to both the CW and toolkit API's, or writes it to the local file in
+    /// [TelemetryMode::Local], and to the OTLP collector if one is configured. If the clients do
+    /// not exist, then telemetry is not sent.
     ///
     /// See [TelemetryClient::new] for which conditions the clients are created for.
     async fn send_event(&self, event: Event) {
+        if let Some(exporter) = &self.otlp_exporter {
+            exporter.export(&event).await;
+        }
+        if let Some(path) = &self.local_path {
+            if let Err(err) = write_local_event(path, &event).await {
+                error!(%err, ?path, "Failed to write the telemetry event");
+            }
+        }
         self.send_cw_telemetry_event(&event).await;
         self.send_telemetry_toolkit_metric(event).await;
     }
@@ -531,6 +639,46 @@ impl TelemetryClient {
                 error!(err =% DisplayErrorContext(err), "Failed to send cw telemetry event");
             }
         }
+
+        if let EventType::MessageFeedback {
+            conversation_id,
+            message_id,
+            vote,
+            ..
+        } = &event.ty
+        {
+            let user_context = self.user_context().unwrap();
+
+            let interaction_event = match ChatInteractWithMessageEvent::builder()
+                .conversation_id(conversation_id)
+                .message_id(message_id)
+                .interaction_type(match vote {
+                    FeedbackVote::Up => ChatMessageInteractionType::Upvote,
+                    FeedbackVote::Down => ChatMessageInteractionType::Downvote,
+                })
+                .build()
+            {
+                Ok(event) => event,
+                Err(err) => {
+                    error!(err =% DisplayErrorContext(err), "Failed to send cw telemetry event");
+                    return;
+                },
+            };
+
+            let event = TelemetryEvent::ChatInteractWithMessageEvent(interaction_event);
+            debug!(
+                ?event,
+                ?user_context,
+                telemetry_enabled = self.telemetry_enabled,
+                "Sending cw telemetry event"
+            );
+            if let Err(err) = codewhisperer_client
+                .send_telemetry_event(event, user_context, self.telemetry_enabled, None)
+                .await
+            {
+                error!(err =% DisplayErrorContext(err), "Failed to send cw telemetry event");
+            }
+        }
     }
 
     async fn send_telemetry_toolkit_metric(&self, event: Event) {
@@ -593,6 +741,23 @@ impl TelemetryClient {
     }
 }
 
+/// Appends `event` to the file at `path` as a line of JSON.
+async fn write_local_event(path: &std::path::Path, event: &Event) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(&line).await?;
+    // Writes of a tokio file complete in the background, until flushed.
+    file.flush().await
+}
+
 pub trait ReasonCode: std::error::Error {
     fn reason_code(&self) -> String;
 }
@@ -643,6 +808,33 @@ mod test {
         assert_eq!(context.ide_version.as_deref(), Some(PRODUCT_VERSION));
     }
 
+    #[tokio::test]
+    async fn test_write_local_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("amazon-q").join("telemetry.jsonl");
+        write_local_event(&path, &Event::new(EventType::UserLoggedIn {}))
+            .await
+            .unwrap();
+        write_local_event(&path, &Event::new(EventType::UserLoggedIn {}))
+            .await
+            .unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let events = content
+            .lines()
+            .map(|line| serde_json::from_str::<Event>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].ty, EventType::UserLoggedIn {});
+    }
+
+    #[test]
+    fn test_telemetry_mode() {
+        assert_eq!(TelemetryMode::from_str("local"), Ok(TelemetryMode::Local));
+        assert_eq!(TelemetryMode::Full.to_string(), "full");
+        assert!(TelemetryMode::from_str("some").is_err());
+    }
+
     #[tracing_test::traced_test]
     #[tokio::test]
     #[ignore = "needs auth which is not in CI"]
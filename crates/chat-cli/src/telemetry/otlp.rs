@@ -0,0 +1,324 @@
+//! Export of chat telemetry as OpenTelemetry spans over OTLP/HTTP, for the endpoint configured
+//! with the `telemetry.otlpEndpoint` setting.
+//!
+//! Spans are encoded as OTLP JSON, so any collector accepting OTLP/HTTP can receive them. All
+//! spans of a conversation share a trace id derived from the conversation id.
+
+use reqwest::Client;
+use serde_json::{
+    Value,
+    json,
+};
+use tracing::{
+    debug,
+    warn,
+};
+use uuid::Uuid;
+
+use super::core::{
+    ChatAddedMessageParams,
+    Event,
+    EventType,
+    MessageMetaTag,
+    TelemetryResult,
+};
+use crate::request::{
+    RequestError,
+    new_client,
+};
+
+const SERVICE_NAME: &str = "amazon-q-cli";
+const SCOPE_NAME: &str = "chat_cli";
+const TRACES_PATH: &str = "/v1/traces";
+
+/// OTLP span kind of requests to the model.
+const SPAN_KIND_CLIENT: u8 = 3;
+/// OTLP span kind of operations performed locally.
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+const STATUS_CODE_OK: u8 = 1;
+const STATUS_CODE_ERROR: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub struct OtlpExporter {
+    client: Client,
+    url: String,
+}
+
+impl OtlpExporter {
+    /// Creates an exporter sending spans to `endpoint`, the base URL of an OTLP/HTTP collector
+    /// such as `http://localhost:4318`.
+    pub fn new(endpoint: &str) -> Result<Self, RequestError> {
+        Ok(Self {
+            client: new_client()?,
+            url: traces_url(endpoint),
+        })
+    }
+
+    /// Exports the span corresponding to `event`, if any.
+    pub async fn export(&self, event: &Event) {
+        let Some(span) = Span::from_event(event) else {
+            return;
+        };
+
+        debug!(name = span.name, url = self.url, "exporting span");
+        match self.client.post(&self.url).json(&span.into_request()).send().await {
+            Ok(response) if !response.status().is_success() => {
+                warn!(status = %response.status(), "OTLP collector rejected the span");
+            },
+            Ok(_) => (),
+            Err(err) => warn!(?err, "failed to export span"),
+        }
+    }
+}
+
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    match endpoint.ends_with(TRACES_PATH) {
+        true => endpoint.to_string(),
+        false => format!("{endpoint}{TRACES_PATH}"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    name: &'static str,
+    kind: u8,
+    trace_id: String,
+    start_ms: u64,
+    end_ms: u64,
+    is_error: bool,
+    attributes: Vec<(&'static str, Value)>,
+}
+
+impl Span {
+    /// Returns the span of a request, compaction, or tool execution recorded by `event`.
+    fn from_event(event: &Event) -> Option<Self> {
+        match &event.ty {
+            EventType::ChatAddedMessage {
+                conversation_id,
+                result,
+                data:
+                    ChatAddedMessageParams {
+                        message_id,
+                        request_id,
+                        reason,
+                        status_code,
+                        model,
+                        time_to_first_chunk_ms,
+                        tool_name,
+                        message_meta_tags,
+                        request_start_timestamp_ms,
+                        stream_end_timestamp_ms,
+                        input_token_count,
+                        output_token_count,
+                        ..
+                    },
+            } => {
+                let is_compaction = message_meta_tags.contains(&MessageMetaTag::Compact);
+                Some(Self {
+                    name: if is_compaction { "compaction" } else { "chat_request" },
+                    kind: SPAN_KIND_CLIENT,
+                    trace_id: trace_id(conversation_id),
+                    start_ms: (*request_start_timestamp_ms)?,
+                    end_ms: (*stream_end_timestamp_ms)?,
+                    is_error: !matches!(result, TelemetryResult::Succeeded),
+                    attributes: attributes([
+                        ("conversation.id", Some(json!(conversation_id))),
+                        ("message.id", message_id.as_ref().map(|v| json!(v))),
+                        ("request.id", request_id.as_ref().map(|v| json!(v))),
+                        ("gen_ai.request.model", model.as_ref().map(|v| json!(v))),
+                        ("gen_ai.usage.input_tokens", input_token_count.map(|v| json!(v))),
+                        ("gen_ai.usage.output_tokens", output_token_count.map(|v| json!(v))),
+                        ("time_to_first_chunk_ms", time_to_first_chunk_ms.map(|v| json!(v))),
+                        ("tool.names", tool_name.as_ref().map(|v| json!(v))),
+                        ("result", Some(json!(result.to_string()))),
+                        ("error.reason", reason.as_ref().map(|v| json!(v))),
+                        ("http.response.status_code", status_code.map(|v| json!(v))),
+                    ]),
+                })
+            },
+            EventType::ToolUseSuggested {
+                conversation_id,
+                tool_use_id,
+                tool_name,
+                is_success,
+                reason_desc,
+                is_custom_tool,
+                input_token_size,
+                output_token_size,
+                model,
+                execution_start_timestamp_ms,
+                execution_duration_ms,
+                ..
+            } => {
+                let start_ms = (*execution_start_timestamp_ms)?;
+                Some(Self {
+                    name: "tool_execution",
+                    kind: SPAN_KIND_INTERNAL,
+                    trace_id: trace_id(conversation_id),
+                    start_ms,
+                    end_ms: start_ms + execution_duration_ms.unwrap_or_default(),
+                    is_error: *is_success == Some(false),
+                    attributes: attributes([
+                        ("conversation.id", Some(json!(conversation_id))),
+                        ("tool.name", tool_name.as_ref().map(|v| json!(v))),
+                        ("tool.use_id", tool_use_id.as_ref().map(|v| json!(v))),
+                        ("tool.is_custom", Some(json!(is_custom_tool))),
+                        ("tool.is_success", is_success.map(|v| json!(v))),
+                        ("tool.input_tokens", input_token_size.map(|v| json!(v))),
+                        ("tool.output_tokens", output_token_size.map(|v| json!(v))),
+                        ("gen_ai.request.model", model.as_ref().map(|v| json!(v))),
+                        ("error.reason", reason_desc.as_ref().map(|v| json!(v))),
+                    ]),
+                })
+            },
+            _ => None,
+        }
+    }
+
+    /// Encodes the span as an OTLP/HTTP JSON export request.
+    fn into_request(self) -> Value {
+        let attributes = self
+            .attributes
+            .into_iter()
+            .map(|(key, value)| json!({ "key": key, "value": any_value(value) }))
+            .collect::<Vec<_>>();
+
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        { "key": "service.name", "value": { "stringValue": SERVICE_NAME } },
+                        { "key": "service.version", "value": { "stringValue": env!("CARGO_PKG_VERSION") } },
+                    ],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": SCOPE_NAME },
+                    "spans": [{
+                        "traceId": self.trace_id,
+                        "spanId": hex::encode(rand::random::<[u8; 8]>()),
+                        "name": self.name,
+                        "kind": self.kind,
+                        "startTimeUnixNano": (u128::from(self.start_ms) * 1_000_000).to_string(),
+                        "endTimeUnixNano": (u128::from(self.end_ms) * 1_000_000).to_string(),
+                        "attributes": attributes,
+                        "status": { "code": if self.is_error { STATUS_CODE_ERROR } else { STATUS_CODE_OK } },
+                    }],
+                }],
+            }],
+        })
+    }
+}
+
+/// Uses the conversation id as the trace id if it is a UUID, so that all spans of a
+/// conversation belong to the same trace.
+fn trace_id(conversation_id: &str) -> String {
+    Uuid::parse_str(conversation_id)
+        .unwrap_or_else(|_| Uuid::new_v4())
+        .simple()
+        .to_string()
+}
+
+fn attributes<const N: usize>(attributes: [(&'static str, Option<Value>); N]) -> Vec<(&'static str, Value)> {
+    attributes
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect()
+}
+
+fn any_value(value: Value) -> Value {
+    match value {
+        Value::Bool(v) => json!({ "boolValue": v }),
+        Value::Number(v) if v.is_i64() || v.is_u64() => json!({ "intValue": v.to_string() }),
+        Value::Number(v) => json!({ "doubleValue": v }),
+        Value::String(v) => json!({ "stringValue": v }),
+        other => json!({ "stringValue": other.to_string() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(traces_url("http://localhost:4318"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_url("http://localhost:4318/"), "http://localhost:4318/v1/traces");
+        assert_eq!(
+            traces_url("https://otel.example.com/v1/traces"),
+            "https://otel.example.com/v1/traces"
+        );
+    }
+
+    #[test]
+    fn test_span_from_event() {
+        let conversation_id = "5f0b6b8e-6c1e-4b7b-9a51-3d1f0f1e2a3b".to_string();
+        let event = Event::new(EventType::ChatAddedMessage {
+            conversation_id: conversation_id.clone(),
+            result: TelemetryResult::Succeeded,
+            data: ChatAddedMessageParams {
+                model: Some("model".to_string()),
+                message_meta_tags: vec![MessageMetaTag::Compact],
+                request_start_timestamp_ms: Some(1_000),
+                stream_end_timestamp_ms: Some(3_500),
+                input_token_count: Some(1_200),
+                output_token_count: Some(40),
+                ..Default::default()
+            },
+        });
+        let span = Span::from_event(&event).unwrap();
+        assert_eq!(span.name, "compaction");
+        assert_eq!(span.trace_id, "5f0b6b8e6c1e4b7b9a513d1f0f1e2a3b");
+
+        let request = span.into_request();
+        let span = &request["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["startTimeUnixNano"], "1000000000");
+        assert_eq!(span["endTimeUnixNano"], "3500000000");
+        assert_eq!(span["status"]["code"], STATUS_CODE_OK);
+        assert!(span["attributes"].as_array().unwrap().contains(&json!({
+            "key": "gen_ai.usage.input_tokens",
+            "value": { "intValue": "1200" },
+        })));
+
+        // Requests that failed before being sent have no timing.
+        let event = Event::new(EventType::ChatAddedMessage {
+            conversation_id,
+            result: TelemetryResult::Failed,
+            data: ChatAddedMessageParams::default(),
+        });
+        assert!(Span::from_event(&event).is_none());
+    }
+
+    #[test]
+    fn test_tool_span_from_event() {
+        let mut builder = crate::telemetry::core::ToolUseEventBuilder::new("conv".into(), "tool_id".into(), None)
+            .set_tool_name("fs_read".to_string());
+        builder.is_success = Some(false);
+        builder.execution_start_timestamp_ms = Some(10);
+        builder.execution_duration_ms = Some(25);
+        let event = Event::new(EventType::ToolUseSuggested {
+            conversation_id: builder.conversation_id,
+            utterance_id: None,
+            user_input_id: None,
+            tool_use_id: builder.tool_use_id,
+            tool_name: builder.tool_name,
+            is_accepted: true,
+            is_success: builder.is_success,
+            reason_desc: None,
+            is_valid: Some(true),
+            is_custom_tool: false,
+            input_token_size: None,
+            output_token_size: None,
+            custom_tool_call_latency: None,
+            model: None,
+            execution_start_timestamp_ms: builder.execution_start_timestamp_ms,
+            execution_duration_ms: builder.execution_duration_ms,
+        });
+        let span = Span::from_event(&event).unwrap();
+        assert_eq!(span.name, "tool_execution");
+        assert_eq!((span.start_ms, span.end_ms), (10, 35));
+        assert!(span.is_error);
+        assert_eq!(span.trace_id.len(), 32);
+    }
+}
@@ -166,6 +166,18 @@ pub fn chat_global_context_path(os: &Os) -> Result<PathBuf> {
     Ok(home_dir(os)?.join(".aws").join("amazonq").join("global_context.json"))
 }
 
+/// Greeting config file, which organizations can manage to set the message of the day and
+/// policy text shown at the start of `q chat`.
+pub fn chat_greeting_config_path(os: &Os) -> Result<PathBuf> {
+    Ok(home_dir(os)?.join(".aws").join("amazonq").join("greeting.json"))
+}
+
+/// The directory where the documents of S3-hosted knowledge bases searched by the `kb_search`
+/// tool are synced.
+pub fn knowledge_base_cache_dir(os: &Os) -> Result<PathBuf> {
+    Ok(home_dir(os)?.join(".aws").join("amazonq").join("knowledge_bases"))
+}
+
 /// The directory to the directory containing config for the `/context` feature in `q chat`.
 #[allow(dead_code)]
 pub fn chat_profiles_dir(os: &Os) -> Result<PathBuf> {
@@ -177,6 +189,11 @@ pub fn settings_path() -> Result<PathBuf> {
     Ok(fig_data_dir()?.join("settings.json"))
 }
 
+/// The path to the events recorded in the local telemetry mode, one JSON object per line
+pub fn local_telemetry_path() -> Result<PathBuf> {
+    Ok(fig_data_dir()?.join("telemetry.jsonl"))
+}
+
 /// The path to the local sqlite database
 pub fn database_path() -> Result<PathBuf> {
     Ok(fig_data_dir()?.join("data.sqlite3"))